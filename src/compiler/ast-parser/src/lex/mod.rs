@@ -2,6 +2,7 @@ use core::iter::Peekable;
 use core::str::Chars;
 
 use smallvec::SmallVec;
+use vsp_error::VspError;
 use vsp_error::VspResult;
 use vsp_span::Position;
 use vsp_span::Span;
@@ -9,25 +10,40 @@ use vsp_span::Span;
 use crate::parser::token::LocatableToken;
 use crate::parser::token::TokenStream;
 use crate::token::mapping_non_literal_token;
+use crate::token::Base;
 use crate::token::Token;
 
+const SHEBANG: &str = "#!";
+
+/// Interpreter directive on the first line of the script, such as `/usr/bin/env vsp run`, without
+/// the leading `#!`.
+pub fn shebang(source: &str) -> Option<String> {
+  let line = source.strip_prefix(SHEBANG)?.lines().next().unwrap_or_default();
+  Some(line.trim().to_owned())
+}
+
 pub struct DefaultLexer;
 
 impl DefaultLexer {
   pub fn tokenize(&mut self, str: &str) -> VspResult<TokenStream> {
     let mut ctx = CharContext::from(str);
     let mut tokens = vec![];
+    // The shebang line of the scripts is not part of the program, but its newline is kept so that
+    // the lines of the tokens still match the source.
+    if str.starts_with(SHEBANG) {
+      skip_line_comment(&mut ctx);
+    }
     'core: while let Some(c) = ctx.next() {
       if c.is_whitespace() {
         continue 'core;
       }
       match c {
+        '.' if ctx.peek() == Some(&'.') => readout_dots(&mut tokens, &mut ctx),
         '.' => punctuation_handler(&mut tokens, &mut ctx, Token::Dot),
         ',' => punctuation_handler(&mut tokens, &mut ctx, Token::Comma),
-        ';' => punctuation_handler(&mut tokens, &mut ctx, Token::Colon),
+        ';' => punctuation_handler(&mut tokens, &mut ctx, Token::SemiColon),
         '+' => punctuation_handler(&mut tokens, &mut ctx, Token::Plus),
         '*' => punctuation_handler(&mut tokens, &mut ctx, Token::Asterisk),
-        '/' => punctuation_handler(&mut tokens, &mut ctx, Token::Slash),
         '%' => punctuation_handler(&mut tokens, &mut ctx, Token::Percentage),
         '(' => punctuation_handler(&mut tokens, &mut ctx, Token::LParenthesis),
         ')' => punctuation_handler(&mut tokens, &mut ctx, Token::RParenthesis),
@@ -38,9 +54,59 @@ impl DefaultLexer {
         '@' => punctuation_handler(&mut tokens, &mut ctx, Token::At),
         '^' => punctuation_handler(&mut tokens, &mut ctx, Token::Xor),
         '?' => punctuation_handler(&mut tokens, &mut ctx, Token::Question),
-        '0'..='9' => readout_numeric(&mut tokens, &mut ctx),
-        '"' => readout_literal_string(&mut tokens, &mut ctx),
-        _ => readout_symbol(&mut tokens, &mut ctx),
+        '\'' => punctuation_handler(&mut tokens, &mut ctx, Token::SQuote),
+        '/' => match ctx.peek() {
+          Some('/') => skip_line_comment(&mut ctx),
+          Some('*') => skip_block_comment(&mut ctx)?,
+          _ => punctuation_handler(&mut tokens, &mut ctx, Token::Slash),
+        },
+        ':' => {
+          compound_punctuation_handler(&mut tokens, &mut ctx, Token::Colon, &[(':', Token::DColon)])
+        }
+        '-' => {
+          compound_punctuation_handler(&mut tokens, &mut ctx, Token::Minus, &[('>', Token::Arrow)])
+        }
+        '=' => compound_punctuation_handler(
+          &mut tokens,
+          &mut ctx,
+          Token::Assigment,
+          &[('=', Token::Equal), ('>', Token::DArrow)],
+        ),
+        '!' => {
+          compound_punctuation_handler(&mut tokens, &mut ctx, Token::Not, &[('=', Token::NotEqual)])
+        }
+        '<' => compound_punctuation_handler(
+          &mut tokens,
+          &mut ctx,
+          Token::Less,
+          &[('=', Token::LessEqual)],
+        ),
+        '>' => compound_punctuation_handler(
+          &mut tokens,
+          &mut ctx,
+          Token::Greater,
+          &[('=', Token::GreaterEqual)],
+        ),
+        '&' => compound_punctuation_handler(
+          &mut tokens,
+          &mut ctx,
+          Token::Ampersand,
+          &[('&', Token::And)],
+        ),
+        '|' if ctx.peek() == Some(&'|') => {
+          compound_punctuation_handler(&mut tokens, &mut ctx, Token::Or, &[('|', Token::Or)])
+        }
+        '0'..='9' => readout_numeric(&mut tokens, &mut ctx)?,
+        '"' => readout_literal_string(&mut tokens, &mut ctx)?,
+        c if is_identifier_starter(c) => readout_symbol(&mut tokens, &mut ctx),
+        c => {
+          return Err(VspError::new(format!(
+            "{}:{}: unexpected character `{}`",
+            ctx.last_pos().line,
+            ctx.last_pos().column,
+            c
+          )))
+        }
       };
     }
 
@@ -136,62 +202,208 @@ impl<'a> AsRef<CharIterator<'a>> for CharContext<'a> {
   }
 }
 
-/// Common punctuation handler for .
+/// Common punctuation handler for single character punctuations.
 fn punctuation_handler(tokens: &mut TokenStream, ctx: &mut CharContext, expected: Token) {
-  let token = LocatableToken::new(
-    expected,
-    Span::range(ctx.last_pos().clone(), ctx.curr_pos().clone()),
-  );
+  let token = LocatableToken::new(expected, Span::range(*ctx.last_pos(), *ctx.curr_pos()));
   tokens.push(token);
 }
 
-fn readout_numeric(tokens: &mut TokenStream, ctx: &mut CharContext) {
-  let digit = ctx.curr_unchecked().to_digit(10).unwrap().into();
-  let token = Token::LiteralInteger(digit);
-  let span = Span::at(ctx.curr_pos().clone());
-  let token = LocatableToken::new(token, span);
-  tokens.push(token);
+/// Punctuation handler for punctuations which might be followed by another character, such as
+/// `-` and `->`. The candidates are checked in order, otherwise the single one is taken.
+fn compound_punctuation_handler(
+  tokens: &mut TokenStream,
+  ctx: &mut CharContext,
+  single: Token,
+  candidates: &[(char, Token)],
+) {
+  let start = *ctx.last_pos();
+  let token = match ctx.peek() {
+    Some(c) => candidates
+      .iter()
+      .find(|(second, _)| second == c)
+      .map(|(_, token)| token.clone()),
+    None => None,
+  };
+  let token = match token {
+    Some(token) => {
+      ctx.next();
+      token
+    }
+    None => single,
+  };
+  tokens.push(LocatableToken::new(
+    token,
+    Span::range(start, *ctx.curr_pos()),
+  ));
 }
 
-/// Read out the symbol
-fn readout_symbol(tokens: &mut TokenStream, ctx: &mut CharContext) {
-  let mut buf: SmallVec<[char; 1 << 4]> = SmallVec::new();
-  let start = ctx.last_pos().clone();
-  buf.push(ctx.curr_unchecked());
+/// Read `..` or `...` after the first `.`.
+fn readout_dots(tokens: &mut TokenStream, ctx: &mut CharContext) {
+  let start = *ctx.last_pos();
+  ctx.next();
+  let token = if ctx.peek() == Some(&'.') {
+    ctx.next();
+    Token::Ellipsis
+  } else {
+    Token::DotDot
+  };
+  tokens.push(LocatableToken::new(
+    token,
+    Span::range(start, *ctx.curr_pos()),
+  ));
+}
+
+/// Skip the line comment, including doc comments `///` and `//!`.
+fn skip_line_comment(ctx: &mut CharContext) {
   while let Some(&c) = ctx.peek() {
-    // If not a valid identifier successor, collect the chars in buffer.
-    if is_identifier_successor(c) {
-      buf.push(c);
-      ctx.next();
-    } else {
-      let token = buf.iter().collect::<String>();
-      let token = token.as_str();
-      let token = read_keyword_or_identifier(token);
-      let span = Span::range(start, ctx.curr_pos().clone());
+    if c == '\n' {
+      return;
+    }
+    ctx.next();
+  }
+}
 
-      if let Some(token) = token {
-        tokens.push(LocatableToken::new(token, span));
-        return;
-      }
+/// Skip the block comment `/* ... */`.
+fn skip_block_comment(ctx: &mut CharContext) -> VspResult<()> {
+  let start = *ctx.last_pos();
+  ctx.next();
+  while let Some(c) = ctx.next() {
+    if c == '*' && ctx.peek() == Some(&'/') {
+      ctx.next();
+      return Ok(());
     }
   }
+  Err(VspError::new(format!(
+    "{}:{}: unterminated block comment",
+    start.line, start.column
+  )))
 }
 
-fn readout_literal_string(tokens: &mut TokenStream, ctx: &mut CharContext) {
-  let mut buf: Vec<char> = Vec::with_capacity(1 << 4);
-  let start = ctx.last_pos().clone();
+fn readout_numeric(tokens: &mut TokenStream, ctx: &mut CharContext) -> VspResult<()> {
+  let start = *ctx.last_pos();
+  let mut buf = String::new();
+  let mut base = Base::Decimal;
+  let first = ctx.curr_unchecked();
+  if first == '0' {
+    base = match ctx.peek() {
+      Some('x') | Some('X') => Base::Hexadecimal,
+      Some('o') | Some('O') => Base::Octal,
+      Some('b') | Some('B') => Base::Binary,
+      _ => Base::Decimal,
+    };
+  }
+  if base != Base::Decimal {
+    ctx.next();
+  } else {
+    buf.push(first);
+  }
+
+  let mut is_float = false;
   while let Some(&c) = ctx.peek() {
-    if c != '"' {
+    if c == '_' {
+      ctx.next();
+    } else if c.is_digit(base.clone() as u32) {
       buf.push(c);
       ctx.next();
-    } else {
-      let token = Token::LiteralText(buf.iter().collect::<String>());
-      let span = Span::range(start, ctx.curr_pos().clone());
-      tokens.push(LocatableToken::new(token, span));
+    } else if base == Base::Decimal && c == '.' && !is_float {
+      // Look one more character ahead, `1.foo()` is a method call on an integer.
+      let mut lookahead = ctx.as_ref().clone();
+      lookahead.next();
+      if !matches!(lookahead.peek(), Some(c) if c.is_ascii_digit()) {
+        break;
+      }
+      is_float = true;
+      buf.push(c);
       ctx.next();
-      return;
+    } else if base == Base::Decimal && (c == 'e' || c == 'E') {
+      is_float = true;
+      buf.push(c);
+      ctx.next();
+      if let Some(&sign) = ctx.peek() {
+        if sign == '+' || sign == '-' {
+          buf.push(sign);
+          ctx.next();
+        }
+      }
+    } else {
+      break;
+    }
+  }
+
+  let span = Span::range(start, *ctx.curr_pos());
+  let token = if is_float {
+    Token::LiteralFloat(buf)
+  } else {
+    let value = i64::from_str_radix(buf.as_str(), base as u32).map_err(|e| {
+      VspError::new(format!(
+        "{}:{}: invalid integer literal: {}",
+        start.line, start.column, e
+      ))
+    })?;
+    Token::LiteralInteger(value)
+  };
+  tokens.push(LocatableToken::new(token, span));
+  Ok(())
+}
+
+/// Read out the symbol
+fn readout_symbol(tokens: &mut TokenStream, ctx: &mut CharContext) {
+  let mut buf: SmallVec<[char; 1 << 4]> = SmallVec::new();
+  let start = *ctx.last_pos();
+  buf.push(ctx.curr_unchecked());
+  while let Some(&c) = ctx.peek() {
+    if !is_identifier_successor(c) {
+      break;
     }
+    buf.push(c);
+    ctx.next();
   }
+  let token = buf.iter().collect::<String>();
+  let span = Span::range(start, *ctx.curr_pos());
+  if let Some(token) = read_keyword_or_identifier(token.as_str()) {
+    tokens.push(LocatableToken::new(token, span));
+  }
+}
+
+fn readout_literal_string(tokens: &mut TokenStream, ctx: &mut CharContext) -> VspResult<()> {
+  let mut buf: Vec<char> = Vec::with_capacity(1 << 4);
+  let start = *ctx.last_pos();
+  while let Some(c) = ctx.next() {
+    match c {
+      '"' => {
+        let token = Token::LiteralText(buf.iter().collect::<String>());
+        let span = Span::range(start, *ctx.curr_pos());
+        tokens.push(LocatableToken::new(token, span));
+        return Ok(());
+      }
+      '\\' => {
+        let escaped = match ctx.next() {
+          Some('n') => '\n',
+          Some('r') => '\r',
+          Some('t') => '\t',
+          Some('0') => '\0',
+          Some('\\') => '\\',
+          Some('"') => '"',
+          Some('\'') => '\'',
+          Some(c) => {
+            return Err(VspError::new(format!(
+              "{}:{}: unknown character escape `\\{}`",
+              ctx.last_pos().line,
+              ctx.last_pos().column,
+              c
+            )))
+          }
+          None => break,
+        };
+        buf.push(escaped);
+      }
+      c => buf.push(c),
+    }
+  }
+  Err(VspError::new(format!(
+    "{}:{}: unterminated string literal",
+    start.line, start.column
+  )))
 }
 
 fn read_keyword_or_identifier(s: &str) -> Option<Token> {
@@ -210,7 +422,6 @@ fn is_valid_identifier(_s: &str) -> bool {
 }
 
 /// True if it is a valid identifier starter
-#[allow(unused)]
 fn is_identifier_starter(c: char) -> bool {
   c.is_alphabetic() || c == '_'
 }
@@ -232,4 +443,32 @@ mod tests {
       println!("{:?}", token);
     }
   }
+
+  #[test]
+  pub fn test_lexer_dots_and_labels() {
+    let mut lex = DefaultLexer {};
+    let tokens = lex.tokenize("'outer: for i in 0..n { f(a, ...); }").unwrap();
+    let tokens = tokens.iter().map(|t| t.token().clone()).collect::<Vec<_>>();
+    assert_eq!(
+      &tokens[..3],
+      &[
+        Token::SQuote,
+        Token::Identifier("outer".to_owned()),
+        Token::Colon
+      ]
+    );
+    assert!(tokens.contains(&Token::In));
+    assert!(tokens.contains(&Token::DotDot));
+    assert!(tokens.contains(&Token::Ellipsis));
+  }
+
+  #[test]
+  pub fn test_lexer_shebang() {
+    let source = "#!/usr/bin/env vsp run\nfunc main() {}";
+    assert_eq!(shebang(source), Some("/usr/bin/env vsp run".to_owned()));
+    assert_eq!(shebang("func main() {}"), None);
+    let tokens = DefaultLexer {}.tokenize(source).unwrap();
+    assert_eq!(tokens[0].token(), &Token::Func);
+    assert_eq!(tokens[0].span().start.line, 2);
+  }
 }
@@ -1,9 +1,11 @@
 use vsp_ast::ast::expr::Expression;
-use vsp_ast::ast::AST;
+use vsp_ast::ast::expr::ExpressionKind;
+use vsp_ast::ast::CompilationUnit;
 use vsp_error::VspResult;
 use vsp_support::debug_println;
 
 use crate::parser::combine::CombinatorParser;
+use crate::parser::state::ParseState;
 use crate::parser::token::LocatableToken;
 use crate::parser::token::TokenStream;
 use crate::token::Token;
@@ -26,7 +28,7 @@ pub enum ParserKind {
 /// use vsp_ast_parser::parser::ParserKind;
 ///
 /// let mut tokens: TokenStream = vec![];
-/// let mut parser = ASTFactory::create_parser(ParserKind::Default);
+/// let mut parser = ASTFactory::create_parser(ParserKind::Traditional);
 /// let _ = parser.parse(tokens);
 /// ```
 pub struct ASTFactory;
@@ -47,14 +49,17 @@ impl ASTFactory {
 }
 
 pub trait ASTParser {
-  fn parse(&mut self, tokens: TokenStream) -> VspResult<AST>;
+  /// Parse the token stream of a single source file into the compilation unit.
+  fn parse(&mut self, tokens: TokenStream) -> VspResult<CompilationUnit>;
 }
 
+/// Hand-written recursive descent parser.
 pub struct TraditionalParser;
 
 impl ASTParser for TraditionalParser {
-  fn parse(&mut self, tokens: TokenStream) -> VspResult<AST> {
-    todo!()
+  fn parse(&mut self, tokens: TokenStream) -> VspResult<CompilationUnit> {
+    let mut state = ParseState::new(&tokens);
+    state::parse_compilation_unit(&mut state)
   }
 }
 
@@ -94,28 +99,22 @@ impl TryInto<Expression> for LocatableToken {
 
   fn try_into(self) -> Result<Expression, Self::Error> {
     debug_println!("Try into {:?}", self);
-    match self.token() {
-      Token::False => Ok(Expression::LiteralBoolean(false)),
-      Token::True => Ok(Expression::LiteralBoolean(true)),
+    let kind = match self.token() {
+      Token::False => ExpressionKind::LiteralBoolean(false),
+      Token::True => ExpressionKind::LiteralBoolean(true),
       // Token::Self_ => {},
-      // Token::Identifier(_) => {},
-      Token::LiteralText(s) => Ok(Expression::LiteralString(s.to_owned())),
-      Token::LiteralInteger(val) => Ok(Expression::LiteralInteger(*val)),
+      Token::Identifier(s) => ExpressionKind::Identifier(s.to_owned()),
+      Token::LiteralText(s) => ExpressionKind::LiteralString(s.to_owned()),
+      Token::LiteralInteger(val) => ExpressionKind::LiteralInteger(*val),
       // Token::LiteralFloat(_) => {},
-      _ => Err(()),
-    }
+      _ => return Err(()),
+    };
+    Ok(Expression::new(kind, self.span().clone()))
   }
 }
 
-enum TerminatorKind {
-  /// `]`
-  Array,
-  /// `}`
-  Block,
-}
-
 pub(crate) mod combine {
-  use vsp_ast::ast::AST;
+  use vsp_ast::ast::CompilationUnit;
   use vsp_error::VspResult;
 
   use crate::parser::token::TokenStream;
@@ -124,7 +123,7 @@ pub(crate) mod combine {
   pub struct CombinatorParser;
 
   impl ASTParser for CombinatorParser {
-    fn parse(&mut self, tokens: TokenStream) -> VspResult<AST> {
+    fn parse(&mut self, _tokens: TokenStream) -> VspResult<CompilationUnit> {
       todo!()
     }
   }
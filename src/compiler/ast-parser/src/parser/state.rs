@@ -1,307 +1,2169 @@
-use std::fmt::Debug;
-
-use smallvec::SmallVec;
+use vsp_ast::ast::annotation::Annotation;
+use vsp_ast::ast::constant::ConstDefinition;
+use vsp_ast::ast::constant::ConstKind;
+use vsp_ast::ast::enumeration::EnumDefinition;
+use vsp_ast::ast::enumeration::Variant;
 use vsp_ast::ast::expr::BinaryOp;
 use vsp_ast::ast::expr::Expression;
+use vsp_ast::ast::expr::ExpressionKind;
+use vsp_ast::ast::expr::UnaryOp;
+use vsp_ast::ast::function::Abi;
+use vsp_ast::ast::function::Function;
+use vsp_ast::ast::function::FunctionSignature;
+use vsp_ast::ast::generics::GenericParam;
+use vsp_ast::ast::generics::Generics;
+use vsp_ast::ast::generics::WherePredicate;
+use vsp_ast::ast::interface::AssociatedType;
+use vsp_ast::ast::interface::ImplDefinition;
+use vsp_ast::ast::interface::TraitDefinition;
+use vsp_ast::ast::modifier::Accessibility;
+use vsp_ast::ast::modifier::Asyncness;
+use vsp_ast::ast::modifier::Constancy;
+use vsp_ast::ast::modifier::Unsafety;
+use vsp_ast::ast::module::ModuleDeclaration;
+use vsp_ast::ast::module::Path;
+use vsp_ast::ast::module::UseDeclaration;
+use vsp_ast::ast::module::UseKind;
+use vsp_ast::ast::pattern::MatchArm;
+use vsp_ast::ast::pattern::Pattern;
+use vsp_ast::ast::pattern::PatternKind;
+use vsp_ast::ast::stmt::ForIterable;
+use vsp_ast::ast::stmt::ForStatement;
+use vsp_ast::ast::stmt::IfStatement;
+use vsp_ast::ast::stmt::JumpStatement;
+use vsp_ast::ast::stmt::LoopStatement;
+use vsp_ast::ast::stmt::ReturnStatement;
 use vsp_ast::ast::stmt::Statement;
 use vsp_ast::ast::stmt::StatementBlock;
-use vsp_ast::ast::DeclNode;
-use vsp_ast::ast::Declaration;
+use vsp_ast::ast::stmt::VariableDeclaration;
+use vsp_ast::ast::stmt::WhileStatement;
+use vsp_ast::ast::structure::Field;
+use vsp_ast::ast::structure::StructDefinition;
+use vsp_ast::ast::types::Parameter;
+use vsp_ast::ast::types::PrimitiveType;
+use vsp_ast::ast::types::Type;
+use vsp_ast::ast::CompilationUnit;
+use vsp_ast::ast::NodeId;
+use vsp_error::VspError;
+use vsp_error::VspResult;
+use vsp_span::Position;
+use vsp_span::Span;
 
 use crate::parser::token::LocatableToken;
 use crate::parser::token::TokenStream;
 use crate::token::Token;
-use crate::token::TokenType;
-
-pub(crate) type Input<'ctx> = TokenStream;
-
-#[derive(Debug)]
-pub enum ParseResult<T> {
-  Matched(T, (usize, usize)),
-  Failed,
-}
 
-impl<T> ParseResult<T> {
-  pub const fn is_err(&self) -> bool {
-    matches!(self, ParseResult::Failed)
-  }
-
-  pub fn unwrap(&self) -> (T, (usize, usize))
-  where
-    T: Clone,
-  {
-    match self {
-      ParseResult::Matched(value, range) => (value.clone(), range.to_owned()),
-      ParseResult::Failed => panic!(),
-    }
-  }
-}
+pub(crate) type Input<'ctx> = &'ctx [LocatableToken];
 
 /// The lower the precedence enumeration lies, the higher precedence the token has.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
 #[repr(u8)]
 pub(crate) enum Precedence {
   Lowest = 0,
   Assign,
-  Logic,
-  Ternary,
+  LogicalOr,
+  LogicalAnd,
   Compare,
   Sum,
   Product,
+  Cast,
   Prefix,
   Call,
-  Index,
-  Dot,
 }
 
 impl LocatableToken {
+  /// Precedence of the token when it is used as an infix operator.
   fn into_precedence(&self) -> Precedence {
     match self.token() {
-      Token::Equal => Precedence::Assign,
-      Token::Not | Token::And | Token::Or | Token::Xor => Precedence::Logic,
-      // _ => Precedence::Ternary,
-      Token::Less | Token::Greater | Token::LessEqual | Token::GreaterEqual => Precedence::Compare,
-      // _ => Precedence::Sum,
-      // _ => Precedence::Product,
-      // _ => Precedence::Prefix,
-      // _ => Precedence::Call,
-      // _ => Precedence::Index,
-      Token::Dot => Precedence::Dot,
+      Token::Assigment => Precedence::Assign,
+      Token::Or => Precedence::LogicalOr,
+      Token::And => Precedence::LogicalAnd,
+      Token::Equal
+      | Token::NotEqual
+      | Token::Less
+      | Token::Greater
+      | Token::LessEqual
+      | Token::GreaterEqual => Precedence::Compare,
+      Token::Plus | Token::Minus => Precedence::Sum,
+      Token::Asterisk | Token::Slash | Token::Percentage => Precedence::Product,
+      Token::As => Precedence::Cast,
+      Token::LParenthesis | Token::LBracket | Token::Dot | Token::Question => Precedence::Call,
       _ => Precedence::Lowest,
     }
   }
 }
 
-#[inline]
-fn is_left_marker(token: &LocatableToken) -> bool {
-  match token.token() {
-    Token::SemiColon | Token::LParenthesis | Token::LBracket | Token::LBrace => true,
-    _ => false,
+fn into_binary_op(token: &Token) -> Option<BinaryOp> {
+  match token {
+    Token::Plus => Some(BinaryOp::Add),
+    Token::Minus => Some(BinaryOp::Subtract),
+    Token::Asterisk => Some(BinaryOp::Multiply),
+    Token::Slash => Some(BinaryOp::Division),
+    Token::Percentage => Some(BinaryOp::Remainder),
+    Token::Equal => Some(BinaryOp::Equal),
+    Token::NotEqual => Some(BinaryOp::NotEqual),
+    Token::Less => Some(BinaryOp::Less),
+    Token::LessEqual => Some(BinaryOp::LessEqual),
+    Token::Greater => Some(BinaryOp::Greater),
+    Token::GreaterEqual => Some(BinaryOp::GreaterEqual),
+    Token::And => Some(BinaryOp::And),
+    Token::Or => Some(BinaryOp::Or),
+    Token::Assigment => Some(BinaryOp::Assignment),
+    _ => None,
+  }
+}
+
+fn into_unary_op(token: &Token) -> Option<UnaryOp> {
+  match token {
+    Token::Asterisk => Some(UnaryOp::Dereference),
+    Token::Ampersand => Some(UnaryOp::AddressOf),
+    Token::Not => Some(UnaryOp::Not),
+    Token::Minus => Some(UnaryOp::Negative),
+    _ => None,
+  }
+}
+
+/// Recursive descent parser state over the token stream.
+pub(crate) struct ParseState<'ctx> {
+  input:             Input<'ctx>,
+  pos:               usize,
+  /// Whether struct expressions are forbidden, such as in the condition of `if`, where the brace
+  /// starts the block instead.
+  no_struct_literal: bool,
+}
+
+impl<'ctx> ParseState<'ctx> {
+  pub fn new(input: &'ctx TokenStream) -> ParseState<'ctx> {
+    Self {
+      input:             input.as_slice(),
+      pos:               0,
+      no_struct_literal: false,
+    }
+  }
+
+  /// Run the parse function with struct expressions allowed or not, and restore it afterwards.
+  fn with_struct_literal<T>(&mut self, allowed: bool, f: impl FnOnce(&mut Self) -> T) -> T {
+    let saved = std::mem::replace(&mut self.no_struct_literal, !allowed);
+    let result = f(self);
+    self.no_struct_literal = saved;
+    result
+  }
+
+  #[inline]
+  fn peek(&self) -> Option<&'ctx LocatableToken> {
+    self.input.get(self.pos)
+  }
+
+  /// Look ahead the token after the next `n` tokens.
+  #[inline]
+  fn peek_nth(&self, n: usize) -> Option<&'ctx LocatableToken> {
+    self.input.get(self.pos + n)
+  }
+
+  #[inline]
+  fn check(&self, token: &Token) -> bool {
+    matches!(self.peek(), Some(t) if t.token() == token)
+  }
+
+  #[inline]
+  fn is_eof(&self) -> bool {
+    self.pos >= self.input.len()
+  }
+
+  fn advance(&mut self) -> Option<&'ctx LocatableToken> {
+    let token = self.input.get(self.pos);
+    if token.is_some() {
+      self.pos += 1;
+    }
+    token
+  }
+
+  /// Consume the token if it is the expected one.
+  fn eat(&mut self, token: &Token) -> bool {
+    if self.check(token) {
+      self.pos += 1;
+      true
+    } else {
+      false
+    }
+  }
+
+  /// Consume the expected token, otherwise raise an error.
+  fn expect(&mut self, token: &Token) -> VspResult<&'ctx LocatableToken> {
+    match self.peek() {
+      Some(t) if t.token() == token => {
+        self.pos += 1;
+        Ok(t)
+      }
+      _ => Err(self.error(format!("expected `{}`", token.as_str()))),
+    }
+  }
+
+  fn expect_identifier(&mut self) -> VspResult<(String, Span)> {
+    match self.peek() {
+      Some(t) => match t.token() {
+        Token::Identifier(name) => {
+          self.pos += 1;
+          Ok((name.to_owned(), t.span().clone()))
+        }
+        _ => Err(self.error("expected identifier")),
+      },
+      None => Err(self.error("expected identifier")),
+    }
+  }
+
+  /// Position where the last consumed token ends.
+  fn last_end(&self) -> Position {
+    match self.pos.checked_sub(1).and_then(|i| self.input.get(i)) {
+      Some(t) => t.span().end,
+      None => Position::start(),
+    }
+  }
+
+  fn span_from(&self, start: Position) -> Span {
+    Span::range(start, self.last_end())
+  }
+
+  fn current_start(&self) -> Position {
+    match self.peek() {
+      Some(t) => t.span().start,
+      None => self.last_end(),
+    }
+  }
+
+  /// Construct the parse error with the location and what is found actually.
+  fn error(&self, message: impl Into<String>) -> VspError {
+    let message = message.into();
+    match self.peek() {
+      Some(t) => VspError::new(format!(
+        "{}:{}: {}, found {}",
+        t.span().start.line,
+        t.span().start.column,
+        message,
+        t.token()
+      )),
+      None => {
+        let end = self.last_end();
+        VspError::new(format!(
+          "{}:{}: {}, found end of file",
+          end.line, end.column, message
+        ))
+      }
+    }
   }
 }
 
-#[inline]
-fn is_right_marker(token: &LocatableToken) -> bool {
-  match token.token() {
-    Token::RParenthesis | Token::RBracket | Token::RBrace => true,
-    _ => false,
+/// Parse the whole token stream as a compilation unit.
+pub(crate) fn parse_compilation_unit(state: &mut ParseState) -> VspResult<CompilationUnit> {
+  let mut unit = CompilationUnit::new("");
+  while !state.is_eof() {
+    parse_item(state, &mut unit)?;
   }
+  if let Some(last) = state.input.last() {
+    unit.span = Span::range(Position::start(), last.span().end);
+  }
+  Ok(unit)
 }
 
-fn is_unary_op(token: &LocatableToken) -> bool {
-  match token.token() {
-    Token::Asterisk | Token::Not | Token::Minus => true,
-    _ => false,
+fn parse_annotations(state: &mut ParseState) -> VspResult<Option<Vec<Annotation>>> {
+  let mut annotations = vec![];
+  while state.eat(&Token::At) {
+    let (name, _) = state.expect_identifier()?;
+    let mut annotation = Annotation::new(name);
+    if state.eat(&Token::LParenthesis) {
+      while !state.check(&Token::RParenthesis) {
+        let (attribute, _) = state.expect_identifier()?;
+        annotation.add_attribute(attribute);
+        if !state.eat(&Token::Comma) {
+          break;
+        }
+      }
+      state.expect(&Token::RParenthesis)?;
+    }
+    annotations.push(annotation);
   }
+  Ok(if annotations.is_empty() {
+    None
+  } else {
+    Some(annotations)
+  })
 }
 
-fn is_binary_op(token: &LocatableToken) -> bool {
-  match token.token() {
-    Token::Plus | Token::Minus | Token::Asterisk | Token::Slash | Token::Percentage => true,
-    _ => false,
+/// Parse the item, i.e. a function, a struct, an enum, a trait, an impl, a constant, a static item,
+/// a `use` declaration or a module declaration, and add it to the compilation unit.
+fn parse_item(state: &mut ParseState, unit: &mut CompilationUnit) -> VspResult<()> {
+  let start = state.current_start();
+  let annotations = parse_annotations(state)?;
+  let accessibility = parse_accessibility(state);
+  match state.peek().map(|t| t.token()) {
+    Some(Token::Use | Token::Module | Token::Impl) if annotations.is_some() => {
+      Err(state.error("expected function, struct, enum or trait after annotations"))
+    }
+    Some(Token::Use) => {
+      let decl = parse_use(state, accessibility, start)?;
+      unit.uses.push(decl);
+      Ok(())
+    }
+    Some(Token::Module) => parse_module_declaration(state, unit, accessibility, start),
+    Some(Token::Struct) => {
+      let definition = parse_struct(state, start, annotations, accessibility)?;
+      check_duplicate(unit, "struct", &definition.name, &definition.span)?;
+      unit.structs.insert(definition.name.to_owned(), definition);
+      Ok(())
+    }
+    Some(Token::Enum) => {
+      let definition = parse_enum(state, start, annotations, accessibility)?;
+      check_duplicate(unit, "enum", &definition.name, &definition.span)?;
+      unit.enums.insert(definition.name.to_owned(), definition);
+      Ok(())
+    }
+    Some(Token::Trait | Token::Interface) => {
+      let definition = parse_trait(state, start, annotations, accessibility)?;
+      check_duplicate(unit, "trait", &definition.name, &definition.span)?;
+      unit.traits.insert(definition.name.to_owned(), definition);
+      Ok(())
+    }
+    Some(Token::Const | Token::Static)
+      if !matches!(
+        state.peek_nth(1).map(|t| t.token()),
+        Some(Token::Func | Token::Async | Token::Unsafe)
+      ) =>
+    {
+      if annotations.is_some() {
+        return Err(state.error("expected function, struct, enum or trait after annotations"));
+      }
+      let definition = parse_const(state, start, accessibility)?;
+      check_duplicate(
+        unit,
+        definition.kind.keyword(),
+        &definition.name,
+        &definition.span,
+      )?;
+      unit.consts.insert(definition.name.to_owned(), definition);
+      Ok(())
+    }
+    Some(Token::Impl) => {
+      if accessibility == Accessibility::Public {
+        return Err(state.error("unnecessary visibility qualifier on `impl`"));
+      }
+      let definition = parse_impl(state, start)?;
+      unit.impls.push(definition);
+      Ok(())
+    }
+    _ => {
+      let function = parse_function(state, start, annotations, accessibility)?;
+      check_duplicate(unit, "function", &function.name, &function.span)?;
+      unit.add_function(function);
+      Ok(())
+    }
   }
 }
 
-fn into_precedence(token: &LocatableToken) -> Precedence {
-  match token.token() {
-    Token::Equal => Precedence::Assign,
-    Token::Not | Token::And | Token::Or | Token::Xor => Precedence::Logic,
-    // _ => Precedence::Ternary,
-    Token::Less | Token::Greater | Token::LessEqual | Token::GreaterEqual => Precedence::Compare,
-    // _ => Precedence::Sum,
-    // _ => Precedence::Product,
-    // _ => Precedence::Prefix,
-    // _ => Precedence::Call,
-    // _ => Precedence::Index,
-    Token::Dot => Precedence::Dot,
-    _ => Precedence::Lowest,
+fn check_duplicate(unit: &CompilationUnit, kind: &str, name: &str, span: &Span) -> VspResult<()> {
+  if unit.has_item(name) {
+    return Err(VspError::new(format!(
+      "{}:{}: {} `{}` is defined multiple times",
+      span.start.line, span.start.column, kind, name
+    )));
   }
+  Ok(())
 }
 
-pub(crate) struct ParseState<'ctx> {
-  _phantom: std::marker::PhantomData<&'ctx ()>,
+fn parse_accessibility(state: &mut ParseState) -> Accessibility {
+  if state.eat(&Token::Public) {
+    Accessibility::Public
+  } else {
+    Accessibility::Private
+  }
 }
 
-impl<'ctx> ParseState<'ctx> {
-  pub fn new() -> ParseState<'ctx> {
-    Self {
-      _phantom: std::marker::PhantomData,
+/// Parse the `use` declaration after the accessibility.
+///
+/// ```vsp
+/// use core::iter::Iterator;
+/// use std::collect::*;
+/// public use std::String as Str;
+/// ```
+fn parse_use(
+  state: &mut ParseState,
+  visibility: Accessibility,
+  start: Position,
+) -> VspResult<UseDeclaration> {
+  state.expect(&Token::Use)?;
+  let mut path = Path::root();
+  let mut kind = UseKind::Single(None);
+  loop {
+    if state.eat(&Token::Self_) {
+      if !path.is_empty() {
+        return Err(state.error("`self` is only allowed at the start of the path"));
+      }
+      path.push("self");
+    } else {
+      let (segment, _) = state.expect_identifier()?;
+      path.push(segment);
+    }
+    if !state.eat(&Token::DColon) {
+      break;
+    }
+    if state.eat(&Token::Asterisk) {
+      kind = UseKind::Glob;
+      break;
+    }
+  }
+  if kind != UseKind::Glob && state.eat(&Token::As) {
+    let (alias, _) = state.expect_identifier()?;
+    kind = UseKind::Single(Some(alias));
+  }
+  if kind != UseKind::Glob && path.name() == Some("self") {
+    return Err(state.error("expected item after `self`"));
+  }
+  state.expect(&Token::SemiColon)?;
+  Ok(UseDeclaration {
+    path,
+    kind,
+    visibility,
+    span: state.span_from(start),
+  })
+}
+
+/// Parse the module declaration after the accessibility. Items inside the braces are added to the
+/// compilation unit.
+///
+/// ```vsp
+/// public module = {
+///   use self::sorted::*;
+/// }
+/// ```
+fn parse_module_declaration(
+  state: &mut ParseState,
+  unit: &mut CompilationUnit,
+  visibility: Accessibility,
+  start: Position,
+) -> VspResult<()> {
+  state.expect(&Token::Module)?;
+  if unit.module.is_some() {
+    return Err(state.error("module is declared multiple times"));
+  }
+  state.expect(&Token::Assigment)?;
+  state.expect(&Token::LBrace)?;
+  while !state.check(&Token::RBrace) {
+    if state.is_eof() {
+      return Err(state.error("expected `}`"));
     }
+    parse_item(state, unit)?;
   }
+  state.expect(&Token::RBrace)?;
+  state.eat(&Token::SemiColon);
+  unit.module = Some(ModuleDeclaration {
+    visibility,
+    span: state.span_from(start),
+  });
+  Ok(())
 }
 
-fn peek(tokens: &TokenStream, pos: usize) -> Option<&LocatableToken> {
-  tokens.get(pos + 1)
+/// Parse the function definition or declaration.
+///
+/// ```vsp
+/// public func add(a: int32, b: int32) -> int32 {
+///   return a + b;
+/// }
+///
+/// async func fetch(id: int64): str {
+///   return load(id).await;
+/// }
+///
+/// unsafe func read(p: *int64): int64 {
+///   return *p;
+/// }
+///
+/// extern "C" func printf(format: str, ...): int32;
+/// ```
+/// The annotations and the accessibility are parsed ahead by the caller. `extern` functions without
+/// body are defined in foreign codes, and only they could be variadic.
+fn parse_function(
+  state: &mut ParseState,
+  start: Position,
+  annotations: Option<Vec<Annotation>>,
+  accessibility: Accessibility,
+) -> VspResult<Function> {
+  let abi = if state.eat(&Token::Extern) {
+    parse_abi(state)?
+  } else {
+    Abi::Vsp
+  };
+  let constancy = if state.eat(&Token::Const) {
+    Constancy::Constant
+  } else {
+    Constancy::None
+  };
+  let asyncness = if state.eat(&Token::Async) {
+    Asyncness::Async
+  } else {
+    Asyncness::None
+  };
+  if constancy.is_constant() && asyncness.is_async() {
+    return Err(state.error("functions cannot be both `const` and `async`"));
+  }
+  let unsafety = if state.eat(&Token::Unsafe) {
+    Unsafety::Unsafe
+  } else {
+    Unsafety::None
+  };
+  if !state.check(&Token::Func) {
+    return Err(state.error("expected item"));
+  }
+  state.expect(&Token::Func)?;
+  let (name, _) = state.expect_identifier()?;
+  let mut generics = parse_generic_params(state)?;
+
+  state.expect(&Token::LParenthesis)?;
+  let mut parameters = vec![];
+  let mut variadic = false;
+  while !state.check(&Token::RParenthesis) {
+    if state.check(&Token::Ellipsis) {
+      if !abi.is_c() {
+        return Err(state.error("only `extern \"C\"` functions could be variadic"));
+      }
+      state.advance();
+      variadic = true;
+      break;
+    }
+    let param_start = state.current_start();
+    let (param, _) = state.expect_identifier()?;
+    state.expect(&Token::Colon)?;
+    let ty = parse_type(state)?;
+    parameters.push(Parameter::new(param, ty, state.span_from(param_start)));
+    if !state.eat(&Token::Comma) {
+      break;
+    }
+  }
+  state.expect(&Token::RParenthesis)?;
+
+  // Both `->` and `:` are accepted to introduce the return type.
+  let return_type = if state.eat(&Token::Arrow) || state.eat(&Token::Colon) {
+    parse_type(state)?
+  } else {
+    Type::unit()
+  };
+  generics.where_clause = parse_where_clause(state)?;
+
+  let mut signature = FunctionSignature::new(accessibility, constancy, parameters, return_type);
+  signature.generics = generics;
+  signature.asyncness = asyncness;
+  signature.unsafety = unsafety;
+  signature.abi = abi;
+  signature.variadic = variadic;
+  let mut function = Function::new(name, signature);
+  function.annotations = annotations;
+  if !state.eat(&Token::SemiColon) {
+    if variadic {
+      return Err(state.error("variadic functions could only be declared without body"));
+    }
+    function.body = Some(Box::new(parse_block(state)?));
+  }
+  function.span = state.span_from(start);
+  Ok(function)
 }
 
-fn get(tokens: &TokenStream, pos: usize) -> Option<&LocatableToken> {
-  tokens.get(pos)
+/// Parse the calling convention after `extern`, which is C if omitted.
+fn parse_abi(state: &mut ParseState) -> VspResult<Abi> {
+  match state.peek().map(|t| t.token()) {
+    Some(Token::LiteralText(abi)) if abi == "C" => {
+      state.advance();
+      Ok(Abi::C)
+    }
+    Some(Token::LiteralText(_)) => Err(state.error("expected ABI \"C\"")),
+    _ => Ok(Abi::C),
+  }
 }
 
-pub(crate) fn parse<'ctx>(
-  state: &mut ParseState<'ctx>,
-  input: &'ctx Input,
-  range: (usize, usize),
-) -> ParseResult<Statement> {
-  let mut decls: SmallVec<[Declaration; 8]> = SmallVec::new();
+/// Parse the constant or the static item after the accessibility.
+///
+/// ```vsp
+/// public const SIZE: int64 = 4 * 1024;
+/// static GREETING: str = "hello";
+/// ```
+fn parse_const(
+  state: &mut ParseState,
+  start: Position,
+  visibility: Accessibility,
+) -> VspResult<ConstDefinition> {
+  let kind = match state.advance().map(|t| t.token()) {
+    Some(Token::Static) => ConstKind::Static,
+    _ => ConstKind::Const,
+  };
+  let (name, _) = state.expect_identifier()?;
+  state.expect(&Token::Colon)?;
+  let ty = parse_type(state)?;
+  state.expect(&Token::Assigment)?;
+  let init = parse_expr(state)?;
+  state.expect(&Token::SemiColon)?;
+  Ok(ConstDefinition {
+    name,
+    kind,
+    visibility,
+    ty,
+    init,
+    span: state.span_from(start),
+  })
+}
 
-  // parse_stmts(state, input, pos, range);
-  ParseResult::Failed
+/// Parse the struct definition after the annotations and the accessibility.
+///
+/// ```vsp
+/// public struct Point {
+///   public x: int64,
+///   public y: int64,
+/// }
+/// ```
+fn parse_struct(
+  state: &mut ParseState,
+  start: Position,
+  annotations: Option<Vec<Annotation>>,
+  visibility: Accessibility,
+) -> VspResult<StructDefinition> {
+  state.expect(&Token::Struct)?;
+  let (name, _) = state.expect_identifier()?;
+  let mut generics = parse_generic_params(state)?;
+  generics.where_clause = parse_where_clause(state)?;
+  state.expect(&Token::LBrace)?;
+  let mut fields: Vec<Field> = vec![];
+  while !state.check(&Token::RBrace) {
+    let field_start = state.current_start();
+    let field_visibility = parse_accessibility(state);
+    let (field, _) = state.expect_identifier()?;
+    if fields.iter().any(|f| f.name == field) {
+      return Err(state.error(format!("field `{}` is already declared", field)));
+    }
+    state.expect(&Token::Colon)?;
+    let ty = parse_type(state)?;
+    fields.push(Field {
+      name: field,
+      ty,
+      visibility: field_visibility,
+      span: state.span_from(field_start),
+    });
+    if !state.eat(&Token::Comma) {
+      break;
+    }
+  }
+  state.expect(&Token::RBrace)?;
+  Ok(StructDefinition {
+    name,
+    visibility,
+    annotations,
+    generics,
+    fields,
+    span: state.span_from(start),
+  })
 }
 
-pub(crate) fn parse_stmts<'ctx>(
-  state: &mut ParseState<'ctx>,
-  input: &'ctx Input,
-  range: (usize, usize),
-) -> ParseResult<Statement> {
-  let mut stmts: SmallVec<[Statement; 16]> = SmallVec::new();
-  let (stmt, range) = parse_stmt(state, input, range).unwrap();
+/// Parse the enum definition after the accessibility.
+///
+/// ```vsp
+/// public enum Optional<T> {
+///   Value(T),
+///   None,
+/// }
+///
+/// enum Color { Red = 1, Green, Blue = 2 * 2 }
+/// ```
+fn parse_enum(
+  state: &mut ParseState,
+  start: Position,
+  annotations: Option<Vec<Annotation>>,
+  visibility: Accessibility,
+) -> VspResult<EnumDefinition> {
+  state.expect(&Token::Enum)?;
+  let (name, _) = state.expect_identifier()?;
+  let mut generics = parse_generic_params(state)?;
+  generics.where_clause = parse_where_clause(state)?;
+  state.expect(&Token::LBrace)?;
+  let mut variants: Vec<Variant> = vec![];
+  while !state.check(&Token::RBrace) {
+    let variant_start = state.current_start();
+    let (variant, _) = state.expect_identifier()?;
+    if variants.iter().any(|v| v.name == variant) {
+      return Err(state.error(format!("variant `{}` is already declared", variant)));
+    }
+    let mut fields = vec![];
+    if state.eat(&Token::LParenthesis) {
+      while !state.check(&Token::RParenthesis) {
+        fields.push(parse_type(state)?);
+        if !state.eat(&Token::Comma) {
+          break;
+        }
+      }
+      state.expect(&Token::RParenthesis)?;
+    }
+    let discriminant = if state.eat(&Token::Assigment) {
+      Some(parse_expr(state)?)
+    } else {
+      None
+    };
+    variants.push(Variant {
+      name: variant,
+      fields,
+      discriminant,
+      span: state.span_from(variant_start),
+    });
+    if !state.eat(&Token::Comma) {
+      break;
+    }
+  }
+  state.expect(&Token::RBrace)?;
+  Ok(EnumDefinition {
+    name,
+    visibility,
+    annotations,
+    generics,
+    variants,
+    span: state.span_from(start),
+  })
+}
 
-  let stmts = stmts.to_vec();
-  ParseResult::Matched(
-    Statement::Block(Box::new(StatementBlock::from(stmts))),
-    range.to_owned(),
-  )
+/// Parse the generic parameters in angle brackets if any, such as `<T: Comparable + Error, U>`.
+fn parse_generic_params(state: &mut ParseState) -> VspResult<Generics> {
+  let mut generics = Generics::default();
+  if !state.eat(&Token::Less) {
+    return Ok(generics);
+  }
+  while !state.check(&Token::Greater) {
+    let start = state.current_start();
+    let (name, _) = state.expect_identifier()?;
+    if generics.params.iter().any(|p| p.name == name) {
+      return Err(state.error(format!(
+        "the name `{}` is already used for a generic parameter",
+        name
+      )));
+    }
+    let bounds = if state.eat(&Token::Colon) {
+      parse_bounds(state)?
+    } else {
+      vec![]
+    };
+    generics.params.push(GenericParam {
+      name,
+      bounds,
+      span: state.span_from(start),
+    });
+    if !state.eat(&Token::Comma) {
+      break;
+    }
+  }
+  state.expect(&Token::Greater)?;
+  Ok(generics)
 }
 
-pub(crate) fn parse_stmt<'ctx>(
-  state: &mut ParseState<'ctx>,
-  input: &'ctx Input,
-  range: (usize, usize),
-) -> ParseResult<Statement> {
-  let mut i = range.0;
-  while i <= range.1 {
-    let current = input.get(i).unwrap();
-    let next = input.get(i + 1);
-    if matches!(current.token(), Token::Return) {
-      let range = (i + 1, range.1);
-      let (expr, range) = parse_literal(state, input, range).unwrap();
-      i = range.1 + 1;
+/// Parse the `where` clause if any, such as `where T: Comparable, U: Iterator`.
+fn parse_where_clause(state: &mut ParseState) -> VspResult<Vec<WherePredicate>> {
+  let mut predicates = vec![];
+  if !state.eat(&Token::Where) {
+    return Ok(predicates);
+  }
+  loop {
+    let start = state.current_start();
+    let ty = parse_type(state)?;
+    state.expect(&Token::Colon)?;
+    let bounds = parse_bounds(state)?;
+    predicates.push(WherePredicate {
+      ty,
+      bounds,
+      span: state.span_from(start),
+    });
+    if !state.eat(&Token::Comma) || state.check(&Token::LBrace) || state.check(&Token::SemiColon) {
+      return Ok(predicates);
     }
   }
-  ParseResult::Failed
 }
 
-pub(crate) fn parse_expr<'ctx>(
-  state: &mut ParseState<'ctx>,
-  input: &'ctx Input,
-  range: (usize, usize),
-) -> ParseResult<Expression> {
-  // Stack of parsed expressions
-  let mut exprs: SmallVec<[Expression; 8]> = SmallVec::new();
+/// Parse the trait bounds separated by `+`.
+fn parse_bounds(state: &mut ParseState) -> VspResult<Vec<Path>> {
+  let mut bounds = vec![parse_path(state)?];
+  while state.eat(&Token::Plus) {
+    bounds.push(parse_path(state)?);
+  }
+  Ok(bounds)
+}
+
+/// Parse the trait definition after the accessibility, declared by either `trait` or `interface`.
+///
+/// ```vsp
+/// public interface Iterator {
+///   type Entry;
+///
+///   func next(): Self::Entry;
+/// }
+/// ```
+fn parse_trait(
+  state: &mut ParseState,
+  start: Position,
+  annotations: Option<Vec<Annotation>>,
+  visibility: Accessibility,
+) -> VspResult<TraitDefinition> {
+  state.advance();
+  let (name, _) = state.expect_identifier()?;
+  let (associated_types, mut methods) = parse_associated_items(state, false)?;
+  // Methods of the trait are as visible as the trait itself.
+  for method in &mut methods {
+    method.signature.accessibility = visibility;
+  }
+  Ok(TraitDefinition {
+    name,
+    visibility,
+    annotations,
+    associated_types,
+    methods,
+    span: state.span_from(start),
+  })
+}
+
+/// Parse the implementation, either of a trait or of inherent methods.
+///
+/// ```vsp
+/// impl Iterator for Counter {
+///   type Entry = int64;
+///
+///   func next(): int64 { return 0; }
+/// }
+///
+/// impl Counter {
+///   public static func new(): Counter { return Counter { count: 0 }; }
+/// }
+/// ```
+fn parse_impl(state: &mut ParseState, start: Position) -> VspResult<ImplDefinition> {
+  state.expect(&Token::Impl)?;
+  if state.check(&Token::Less) {
+    return Err(state.error("generic impls are not supported yet"));
+  }
+  let ty = parse_type(state)?;
+  let (trait_, self_ty) = if state.eat(&Token::For) {
+    match ty {
+      Type::Named(path, args) if args.is_empty() => (Some(path), parse_type(state)?),
+      Type::Named(..) => return Err(state.error("generic traits are not supported yet")),
+      _ => return Err(state.error("expected trait before `for`")),
+    }
+  } else {
+    (None, ty)
+  };
+  let (associated_types, methods) = parse_associated_items(state, true)?;
+  Ok(ImplDefinition {
+    trait_,
+    self_ty,
+    associated_types,
+    methods,
+    span: state.span_from(start),
+  })
+}
 
-  let mut i = range.0;
-  while i <= range.1 {
-    let current = input.get(i).unwrap();
-    let next = input.get(i + 1);
-    // Literal
-    if current.token().into_u8() >= crate::token::LITERAL_TYPE_ID {
-      let range = (i, i);
-      let (expr, range) = parse_literal(state, input, range).unwrap();
-      exprs.push(expr);
-      i += 1;
+/// Parse the associated types and methods in the braces of traits or impls. Associated types are
+/// defined with `= type` in impls, and only declared in traits.
+fn parse_associated_items(
+  state: &mut ParseState,
+  in_impl: bool,
+) -> VspResult<(Vec<AssociatedType>, Vec<Function>)> {
+  let mut associated_types: Vec<AssociatedType> = vec![];
+  let mut methods: Vec<Function> = vec![];
+  state.expect(&Token::LBrace)?;
+  while !state.check(&Token::RBrace) {
+    if state.is_eof() {
+      return Err(state.error("expected `}`"));
+    }
+    let start = state.current_start();
+    if state.eat(&Token::Type) {
+      let (name, _) = state.expect_identifier()?;
+      if associated_types.iter().any(|t| t.name == name) {
+        return Err(state.error(format!("associated type `{}` is already declared", name)));
+      }
+      let ty = if in_impl {
+        state.expect(&Token::Assigment)?;
+        Some(parse_type(state)?)
+      } else {
+        None
+      };
+      state.expect(&Token::SemiColon)?;
+      associated_types.push(AssociatedType {
+        name,
+        ty,
+        span: state.span_from(start),
+      });
       continue;
     }
-    // Binary Ops
-    else if is_binary_op(&current) {
-      if next.is_some() && !exprs.is_empty() {
-        let range = (i, range.1);
-        let binops = parse_binary(
-          state,
-          input,
-          range,
-          current.into_precedence(),
-          exprs.pop().unwrap(),
-        );
-        if binops.is_err() {
-          return ParseResult::Failed;
-        } else {
-          let binops = binops.unwrap();
-          exprs.push(binops.0);
-          i = binops.1 .1 + 1;
+    let annotations = parse_annotations(state)?;
+    let accessibility = parse_accessibility(state);
+    let receiver = !state.eat(&Token::Static);
+    let mut method = parse_function(state, start, annotations, accessibility)?;
+    if methods.iter().any(|m| m.name == method.name) {
+      return Err(VspError::new(format!(
+        "{}:{}: method `{}` is defined multiple times",
+        method.span.start.line, method.span.start.column, method.name
+      )));
+    }
+    method.signature.receiver = receiver;
+    methods.push(method);
+  }
+  state.expect(&Token::RBrace)?;
+  Ok((associated_types, methods))
+}
+
+/// Parse the path separated by `::`, such as `core::iter::Iterator`.
+fn parse_path(state: &mut ParseState) -> VspResult<Path> {
+  let mut path = Path::root();
+  loop {
+    let (segment, _) = state.expect_identifier()?;
+    path.push(segment);
+    if !state.eat(&Token::DColon) {
+      return Ok(path);
+    }
+  }
+}
+
+/// Parse the type, such as `int32`, `int32[4]`, `Point`, `Pair<int64, bool>`, `dyn Iterator`,
+/// `Self::Entry` or `*uint8`. The pointer applies to the whole type after it, so `*int64[4]` points
+/// to the array.
+pub(crate) fn parse_type(state: &mut ParseState) -> VspResult<Type> {
+  let token = match state.peek() {
+    Some(token) => token,
+    None => return Err(state.error("expected type")),
+  };
+  let primitive = match token.token() {
+    Token::Int | Token::Int32 => Some(PrimitiveType::Int32),
+    Token::Int8 => Some(PrimitiveType::Int8),
+    Token::Int16 => Some(PrimitiveType::Int16),
+    Token::Int64 => Some(PrimitiveType::Int64),
+    Token::Uint | Token::Uint32 => Some(PrimitiveType::Uint32),
+    Token::Uint8 => Some(PrimitiveType::Uint8),
+    Token::Uint16 => Some(PrimitiveType::Uint16),
+    Token::Uint64 => Some(PrimitiveType::Uint64),
+    Token::Identifier(name) => PrimitiveType::from_keyword(name.as_str()),
+    _ => None,
+  };
+  let mut ty = match (primitive, token.token()) {
+    (Some(primitive), _) => {
+      state.advance();
+      Type::Primitive(primitive)
+    }
+    (None, Token::Dyn) => {
+      state.advance();
+      Type::Dyn(parse_path(state)?)
+    }
+    (None, Token::Asterisk) => {
+      state.advance();
+      return Ok(Type::pointer(parse_type(state)?));
+    }
+    (None, Token::Identifier(name)) if name == "Self" => {
+      state.advance();
+      let mut ty = Type::SelfType;
+      while state.eat(&Token::DColon) {
+        let (name, _) = state.expect_identifier()?;
+        ty = Type::Associated(Box::new(ty), name);
+      }
+      ty
+    }
+    (None, Token::Identifier(_)) => {
+      let path = parse_path(state)?;
+      let mut args = vec![];
+      if state.eat(&Token::Less) {
+        while !state.check(&Token::Greater) {
+          args.push(parse_type(state)?);
+          if !state.eat(&Token::Comma) {
+            break;
+          }
         }
+        state.expect(&Token::Greater)?;
+      }
+      Type::Named(path, args)
+    }
+    _ => return Err(state.error("expected type")),
+  };
+
+  // Array sizes are either integer literals or paths to constants.
+  while state.eat(&Token::LBracket) {
+    ty = match state.peek().map(|t| t.token()) {
+      Some(Token::LiteralInteger(size)) if *size >= 0 => {
+        state.advance();
+        Type::array(ty, *size as usize)
+      }
+      Some(Token::Identifier(_)) => Type::ConstArray(Box::new(ty), parse_path(state)?),
+      _ => return Err(state.error("expected array size")),
+    };
+    state.expect(&Token::RBracket)?;
+  }
+  Ok(ty)
+}
+
+/// Parse the statement block surrounded with braces.
+pub(crate) fn parse_block(state: &mut ParseState) -> VspResult<StatementBlock> {
+  state.expect(&Token::LBrace)?;
+  let mut block = StatementBlock::new();
+  while !state.check(&Token::RBrace) {
+    if state.is_eof() {
+      return Err(state.error("expected `}`"));
+    }
+    block.add_stmt(parse_stmt(state)?);
+  }
+  state.expect(&Token::RBrace)?;
+  Ok(block)
+}
+
+pub(crate) fn parse_stmt(state: &mut ParseState) -> VspResult<Statement> {
+  let start = state.current_start();
+  let token = match state.peek() {
+    Some(token) => token.token(),
+    None => return Err(state.error("expected statement")),
+  };
+  match token {
+    Token::SemiColon => {
+      state.advance();
+      Ok(Statement::NoOp)
+    }
+    Token::LBrace => Ok(Statement::Block(Box::new(parse_block(state)?))),
+    Token::Unsafe => {
+      state.advance();
+      Ok(Statement::Unsafe(Box::new(parse_block(state)?)))
+    }
+    Token::Let | Token::Var => {
+      let mutable = state.advance().map(|t| t.token() == &Token::Var).unwrap_or_default();
+      let (name, _) = state.expect_identifier()?;
+      let ty = if state.eat(&Token::Colon) {
+        Some(parse_type(state)?)
+      } else {
+        None
+      };
+      let init = if state.eat(&Token::Assigment) {
+        Some(parse_expr(state)?)
+      } else {
+        None
+      };
+      state.expect(&Token::SemiColon)?;
+      Ok(Statement::VariableDeclaration(VariableDeclaration {
+        id: NodeId::next(),
+        name,
+        mutable,
+        ty,
+        init,
+        span: state.span_from(start),
+      }))
+    }
+    Token::Return => {
+      state.advance();
+      let value = if state.check(&Token::SemiColon) {
+        None
       } else {
-        return ParseResult::Failed;
+        Some(parse_expr(state)?)
+      };
+      state.expect(&Token::SemiColon)?;
+      Ok(Statement::Return(ReturnStatement {
+        value,
+        span: state.span_from(start),
+      }))
+    }
+    Token::If => parse_if(state),
+    Token::Match => {
+      let expr = parse_match_expr(state)?;
+      state.eat(&Token::SemiColon);
+      Ok(Statement::Expression(expr))
+    }
+    Token::While | Token::Loop | Token::For => parse_loop(state, None, start),
+    Token::SQuote => {
+      let label = parse_label(state)?;
+      state.expect(&Token::Colon)?;
+      if !matches!(
+        state.peek().map(|t| t.token()),
+        Some(Token::While | Token::Loop | Token::For)
+      ) {
+        return Err(state.error("expected `while`, `loop` or `for` after the label"));
       }
-      i += 1;
-      continue;
+      parse_loop(state, Some(label), start)
+    }
+    Token::Break | Token::Continue => {
+      let is_break = state.advance().map(|t| t.token() == &Token::Break).unwrap_or_default();
+      let label = if state.check(&Token::SQuote) {
+        Some(parse_label(state)?)
+      } else {
+        None
+      };
+      state.expect(&Token::SemiColon)?;
+      let stmt = JumpStatement {
+        label,
+        span: state.span_from(start),
+      };
+      Ok(match is_break {
+        true => Statement::Break(stmt),
+        false => Statement::Continue(stmt),
+      })
+    }
+    _ => {
+      let expr = parse_expr(state)?;
+      state.expect(&Token::SemiColon)?;
+      Ok(Statement::Expression(expr))
     }
   }
-  debug_assert!(exprs.len() == 1, "expression stack should be empty");
-  ParseResult::Matched(exprs.pop().unwrap(), (range.0, range.1))
-}
-
-/// Parse literal value unchecked.
-pub(crate) fn parse_literal<'ctx>(
-  _state: &mut ParseState<'ctx>,
-  input: &'ctx Input,
-  range: (usize, usize),
-) -> ParseResult<Expression> {
-  debug_assert!(range.0 == range.1, "range must be equal");
-  let current = input.get(range.0).unwrap();
-  match current.token().token_type() {
-    TokenType::Identifier => ParseResult::Matched(
-      Expression::Identifier(unsafe { current.get_string_unchecked() }),
-      range,
-    ),
-    TokenType::LiteralText => ParseResult::Matched(
-      Expression::LiteralString(unsafe { current.get_string_unchecked() }),
-      range,
-    ),
-    TokenType::LiteralInteger => ParseResult::Matched(
-      Expression::LiteralInteger(unsafe { current.get_i64_unchecked() }),
-      range,
-    ),
-    _ => ParseResult::Failed,
-  }
-}
-
-pub(crate) fn parse_binary<'ctx>(
-  state: &mut ParseState<'ctx>,
-  input: &'ctx Input,
-  range: (usize, usize),
+}
+
+/// Parse the label of loops, such as `'outer`, into the name without `'`.
+fn parse_label(state: &mut ParseState) -> VspResult<String> {
+  state.expect(&Token::SQuote)?;
+  let (name, _) = state.expect_identifier()?;
+  Ok(name)
+}
+
+/// Parse `while`, `loop` or `for`, which starts at `start` including the optional label.
+fn parse_loop(
+  state: &mut ParseState,
+  label: Option<String>,
+  start: Position,
+) -> VspResult<Statement> {
+  let keyword = state.advance().map(|t| t.token().clone());
+  match keyword {
+    Some(Token::While) => {
+      let condition = state.with_struct_literal(false, parse_expr)?;
+      let body = parse_block(state)?;
+      Ok(Statement::While(WhileStatement {
+        label,
+        condition,
+        body: Box::new(body),
+        span: state.span_from(start),
+      }))
+    }
+    Some(Token::Loop) => {
+      let body = parse_block(state)?;
+      Ok(Statement::Loop(LoopStatement {
+        label,
+        body: Box::new(body),
+        span: state.span_from(start),
+      }))
+    }
+    _ => {
+      let (name, _) = state.expect_identifier()?;
+      state.expect(&Token::In)?;
+      let iterable = state.with_struct_literal(false, parse_expr)?;
+      let iterable = if state.eat(&Token::DotDot) {
+        let end = state.with_struct_literal(false, parse_expr)?;
+        ForIterable::Range(iterable, end)
+      } else {
+        ForIterable::Iterator(iterable)
+      };
+      let body = parse_block(state)?;
+      Ok(Statement::For(ForStatement {
+        id: NodeId::next(),
+        label,
+        name,
+        iterable,
+        body: Box::new(body),
+        span: state.span_from(start),
+      }))
+    }
+  }
+}
+
+fn parse_if(state: &mut ParseState) -> VspResult<Statement> {
+  let start = state.current_start();
+  state.expect(&Token::If)?;
+  let condition = state.with_struct_literal(false, parse_expr)?;
+  let then = parse_block(state)?;
+  let otherwise = if state.eat(&Token::Else) {
+    if state.check(&Token::If) {
+      Some(Box::new(parse_if(state)?))
+    } else {
+      Some(Box::new(Statement::Block(Box::new(parse_block(state)?))))
+    }
+  } else {
+    None
+  };
+  Ok(Statement::If(IfStatement {
+    condition,
+    then: Box::new(then),
+    otherwise,
+    span: state.span_from(start),
+  }))
+}
+
+/// Parse the expression by precedence climbing.
+pub(crate) fn parse_expr(state: &mut ParseState) -> VspResult<Expression> {
+  parse_expr_with_precedence(state, Precedence::Lowest)
+}
+
+fn parse_expr_with_precedence(
+  state: &mut ParseState,
   precedence: Precedence,
-  left: Expression,
-) -> ParseResult<Expression> {
-  let token = input.get(range.0).unwrap();
-  let op = match token.token() {
-    Token::Plus => BinaryOp::Add,
-    Token::Minus => BinaryOp::Subtract,
-    Token::Asterisk => BinaryOp::Multiply,
-    Token::Slash => BinaryOp::Division,
-    _ => return ParseResult::Failed,
+) -> VspResult<Expression> {
+  let start = state.current_start();
+  let mut left = parse_prefix(state)?;
+
+  while let Some(token) = state.peek() {
+    let next = token.into_precedence();
+    if next <= precedence {
+      break;
+    }
+    if token.token() == &Token::LParenthesis {
+      state.advance();
+      let args = parse_arguments(state)?;
+      left = Expression::new(
+        ExpressionKind::Call(Box::new(left), args),
+        state.span_from(start),
+      );
+      continue;
+    }
+    if token.token() == &Token::LBracket {
+      state.advance();
+      let index = state.with_struct_literal(true, parse_expr)?;
+      state.expect(&Token::RBracket)?;
+      left = Expression::new(
+        ExpressionKind::Index(Box::new(left), Box::new(index)),
+        state.span_from(start),
+      );
+      continue;
+    }
+    if token.token() == &Token::Dot {
+      state.advance();
+      if state.eat(&Token::Await) {
+        left = Expression::new(
+          ExpressionKind::Await(Box::new(left)),
+          state.span_from(start),
+        );
+        continue;
+      }
+      let (name, _) = state.expect_identifier()?;
+      left = if state.eat(&Token::LParenthesis) {
+        let args = parse_arguments(state)?;
+        Expression::new(
+          ExpressionKind::MethodCall(Box::new(left), name, args),
+          state.span_from(start),
+        )
+      } else {
+        Expression::new(
+          ExpressionKind::Field(Box::new(left), name),
+          state.span_from(start),
+        )
+      };
+      continue;
+    }
+    if token.token() == &Token::Question {
+      state.advance();
+      left = Expression::new(ExpressionKind::Try(Box::new(left)), state.span_from(start));
+      continue;
+    }
+    if token.token() == &Token::As {
+      state.advance();
+      let ty = parse_type(state)?;
+      left = Expression::new(
+        ExpressionKind::Cast(Box::new(left), ty),
+        state.span_from(start),
+      );
+      continue;
+    }
+    let op = match into_binary_op(token.token()) {
+      Some(op) => op,
+      None => break,
+    };
+    state.advance();
+    // Assignment is right associative, others are left associative.
+    let right = if op == BinaryOp::Assignment {
+      parse_expr_with_precedence(state, Precedence::Lowest)?
+    } else {
+      parse_expr_with_precedence(state, next)?
+    };
+    left = Expression::new(
+      ExpressionKind::Binary(op, Box::new(left), Box::new(right)),
+      state.span_from(start),
+    );
+  }
+  Ok(left)
+}
+
+fn parse_arguments(state: &mut ParseState) -> VspResult<Vec<Expression>> {
+  let mut args = vec![];
+  while !state.check(&Token::RParenthesis) {
+    args.push(state.with_struct_literal(true, parse_expr)?);
+    if !state.eat(&Token::Comma) {
+      break;
+    }
+  }
+  state.expect(&Token::RParenthesis)?;
+  Ok(args)
+}
+
+fn parse_prefix(state: &mut ParseState) -> VspResult<Expression> {
+  let token = match state.peek() {
+    Some(token) => token,
+    None => return Err(state.error("expected expression")),
+  };
+  let start = token.span().start;
+  if let Some(op) = into_unary_op(token.token()) {
+    state.advance();
+    let operand = parse_expr_with_precedence(state, Precedence::Prefix)?;
+    return Ok(Expression::new(
+      ExpressionKind::Unary(op, Box::new(operand)),
+      state.span_from(start),
+    ));
+  }
+  if token.token() == &Token::LParenthesis {
+    state.advance();
+    if state.eat(&Token::RParenthesis) {
+      return Ok(Expression::new(
+        ExpressionKind::Unit,
+        state.span_from(start),
+      ));
+    }
+    let mut expr = state.with_struct_literal(true, parse_expr)?;
+    state.expect(&Token::RParenthesis)?;
+    expr.span = state.span_from(start);
+    return Ok(expr);
+  }
+  if token.token() == &Token::LBracket {
+    state.advance();
+    let mut elements = vec![];
+    while !state.check(&Token::RBracket) {
+      elements.push(state.with_struct_literal(true, parse_expr)?);
+      if !state.eat(&Token::Comma) {
+        break;
+      }
+    }
+    state.expect(&Token::RBracket)?;
+    return Ok(Expression::new(
+      ExpressionKind::ArrayLiteral(elements),
+      state.span_from(start),
+    ));
+  }
+  if token.token() == &Token::If {
+    return parse_if_expr(state);
+  }
+  if token.token() == &Token::Match {
+    return parse_match_expr(state);
+  }
+  if matches!(token.token(), Token::Identifier(_))
+    && matches!(
+      state.input.get(state.pos + 1).map(|t| t.token()),
+      Some(Token::DColon | Token::LBrace)
+    )
+  {
+    return parse_path_expr(state);
+  }
+  parse_literal(state)
+}
+
+/// Parse the conditional expression, such as `if a < b { a } else { b }`, where `else` is
+/// required and could be followed by another conditional expression.
+fn parse_if_expr(state: &mut ParseState) -> VspResult<Expression> {
+  let start = state.current_start();
+  state.expect(&Token::If)?;
+  let condition = state.with_struct_literal(false, parse_expr)?;
+  let then = parse_branch(state)?;
+  state.expect(&Token::Else)?;
+  let otherwise = if state.check(&Token::If) {
+    parse_if_expr(state)?
+  } else {
+    parse_branch(state)?
+  };
+  Ok(Expression::new(
+    ExpressionKind::If(Box::new(condition), Box::new(then), Box::new(otherwise)),
+    state.span_from(start),
+  ))
+}
+
+/// Parse the branch of the conditional expression, which is a single expression in braces.
+fn parse_branch(state: &mut ParseState) -> VspResult<Expression> {
+  state.expect(&Token::LBrace)?;
+  let expr = state.with_struct_literal(true, parse_expr)?;
+  state.expect(&Token::RBrace)?;
+  Ok(expr)
+}
+
+/// Parse the pattern matching expression, such as `match x { Optional::Value(v) => v, _ => 0 }`,
+/// whose arms are separated by commas, which could be omitted after the arms with blocks.
+fn parse_match_expr(state: &mut ParseState) -> VspResult<Expression> {
+  let start = state.current_start();
+  state.expect(&Token::Match)?;
+  let scrutinee = state.with_struct_literal(false, parse_expr)?;
+  state.expect(&Token::LBrace)?;
+  let mut arms = vec![];
+  while !state.check(&Token::RBrace) {
+    let arm_start = state.current_start();
+    let pattern = parse_pattern(state)?;
+    state.expect(&Token::DArrow)?;
+    let (body, block) = if state.check(&Token::LBrace) {
+      let block_start = state.current_start();
+      let block = parse_block(state)?;
+      let kind = ExpressionKind::Block(Box::new(block));
+      (Expression::new(kind, state.span_from(block_start)), true)
+    } else {
+      (state.with_struct_literal(true, parse_expr)?, false)
+    };
+    arms.push(MatchArm {
+      pattern,
+      body,
+      span: state.span_from(arm_start),
+    });
+    if !state.eat(&Token::Comma) && !block {
+      break;
+    }
+  }
+  state.expect(&Token::RBrace)?;
+  Ok(Expression::new(
+    ExpressionKind::Match(Box::new(scrutinee), arms),
+    state.span_from(start),
+  ))
+}
+
+/// Parse the pattern, such as `_`, `x`, `-1`, `true`, `Optional::None` or `Optional::Value(x)`.
+fn parse_pattern(state: &mut ParseState) -> VspResult<Pattern> {
+  let start = state.current_start();
+  let kind = match state.peek().map(|t| t.token()) {
+    Some(Token::True) => {
+      state.advance();
+      PatternKind::Boolean(true)
+    }
+    Some(Token::False) => {
+      state.advance();
+      PatternKind::Boolean(false)
+    }
+    Some(Token::LiteralInteger(value)) => {
+      state.advance();
+      PatternKind::Integer(*value)
+    }
+    Some(Token::Minus) => {
+      state.advance();
+      match state.peek().map(|t| t.token()) {
+        Some(Token::LiteralInteger(value)) => {
+          state.advance();
+          PatternKind::Integer(value.wrapping_neg())
+        }
+        _ => return Err(state.error("expected integer")),
+      }
+    }
+    Some(Token::Identifier(name))
+      if !matches!(
+        state.peek_nth(1).map(|t| t.token()),
+        Some(Token::DColon | Token::LParenthesis)
+      ) =>
+    {
+      state.advance();
+      match name.as_str() {
+        "_" => PatternKind::Wildcard,
+        _ => PatternKind::Binding(name.to_owned()),
+      }
+    }
+    Some(Token::Identifier(_)) => {
+      let path = parse_path(state)?;
+      let mut fields = vec![];
+      if state.eat(&Token::LParenthesis) {
+        while !state.check(&Token::RParenthesis) {
+          fields.push(parse_pattern(state)?);
+          if !state.eat(&Token::Comma) {
+            break;
+          }
+        }
+        state.expect(&Token::RParenthesis)?;
+      }
+      PatternKind::Variant(path, fields)
+    }
+    _ => return Err(state.error("expected pattern")),
   };
-  let right = parse_expr(state, input, (range.0 + 1, range.1));
-  if right.is_err() {
-    return ParseResult::Failed;
+  Ok(Pattern::new(kind, state.span_from(start)))
+}
+
+/// Parse the path to associated functions, such as `Point::new`, or the struct expression, such
+/// as `Point { x: 1, y: 2 }`.
+fn parse_path_expr(state: &mut ParseState) -> VspResult<Expression> {
+  let start = state.current_start();
+  let path = parse_path(state)?;
+  if state.no_struct_literal || !state.check(&Token::LBrace) {
+    let kind = match path.len() {
+      1 => ExpressionKind::Identifier(path.name().unwrap_or_default().to_owned()),
+      _ => ExpressionKind::Path(path),
+    };
+    return Ok(Expression::new(kind, state.span_from(start)));
+  }
+  state.expect(&Token::LBrace)?;
+  let mut fields = vec![];
+  while !state.check(&Token::RBrace) {
+    let (name, _) = state.expect_identifier()?;
+    state.expect(&Token::Colon)?;
+    let value = state.with_struct_literal(true, parse_expr)?;
+    fields.push((name, value));
+    if !state.eat(&Token::Comma) {
+      break;
+    }
   }
-  let right = right.unwrap();
-  let binops = Expression::Binary(op, Box::new(left), Box::new(right.0));
-  ParseResult::Matched(binops, right.1)
+  state.expect(&Token::RBrace)?;
+  Ok(Expression::new(
+    ExpressionKind::StructLiteral(path, fields),
+    state.span_from(start),
+  ))
+}
+
+/// Parse the literal value or identifier.
+pub(crate) fn parse_literal(state: &mut ParseState) -> VspResult<Expression> {
+  let token = match state.peek() {
+    Some(token) => token,
+    None => return Err(state.error("expected expression")),
+  };
+  let kind = match token.token() {
+    Token::True => ExpressionKind::LiteralBoolean(true),
+    Token::False => ExpressionKind::LiteralBoolean(false),
+    Token::Identifier(name) => ExpressionKind::Identifier(name.to_owned()),
+    Token::Self_ => ExpressionKind::Identifier("self".to_owned()),
+    Token::LiteralText(text) => ExpressionKind::LiteralString(text.to_owned()),
+    Token::LiteralInteger(value) => ExpressionKind::LiteralInteger(*value),
+    Token::LiteralFloat(value) => match value.parse::<f64>() {
+      Ok(value) => ExpressionKind::LiteralFloat(value),
+      Err(_) => return Err(state.error("invalid float literal")),
+    },
+    _ => return Err(state.error("expected expression")),
+  };
+  state.advance();
+  Ok(Expression::new(kind, token.span().clone()))
 }
 
 #[cfg(test)]
 mod tests {
-  use vsp_support::debug_println;
+  use vsp_ast::ast::constant::ConstKind;
+  use vsp_ast::ast::expr::BinaryOp;
+  use vsp_ast::ast::expr::ExpressionKind;
+  use vsp_ast::ast::expr::UnaryOp;
+  use vsp_ast::ast::function::Abi;
+  use vsp_ast::ast::modifier::Accessibility;
+  use vsp_ast::ast::module::Path;
+  use vsp_ast::ast::module::UseKind;
+  use vsp_ast::ast::pattern::PatternKind;
+  use vsp_ast::ast::stmt::ForIterable;
+  use vsp_ast::ast::stmt::ForStatement;
+  use vsp_ast::ast::stmt::Statement;
+  use vsp_ast::ast::types::Type;
 
   use crate::lex::DefaultLexer;
+  use crate::parser::state::parse_compilation_unit;
   use crate::parser::state::parse_expr;
-  use crate::parser::state::ParseResult;
   use crate::parser::state::ParseState;
 
   #[test]
   pub fn test() {
     let mut lexer = DefaultLexer {};
-    let mut tokens = lexer.tokenize("1 * 2 - 3").unwrap();
-    let mut end = tokens.len() - 1;
+    let tokens = lexer.tokenize("1 * 2 - 3").unwrap();
+
+    let mut state = ParseState::new(&tokens);
+    let expr = parse_expr(&mut state).unwrap();
+    match expr.kind {
+      ExpressionKind::Binary(BinaryOp::Subtract, left, _) => {
+        assert!(matches!(
+          left.kind,
+          ExpressionKind::Binary(BinaryOp::Multiply, _, _)
+        ))
+      }
+      kind => panic!("unexpected expression: {:?}", kind),
+    }
+  }
+
+  #[test]
+  pub fn test_function() {
+    let mut lexer = DefaultLexer {};
+    let tokens = lexer
+      .tokenize(
+        "public func main() -> int16 {\n  let x: int16 = 1 + 2;\n  if x > 1 { return x; } else { \
+         return 0; }\n}",
+      )
+      .unwrap();
+
+    let mut state = ParseState::new(&tokens);
+    let unit = parse_compilation_unit(&mut state).unwrap();
+    let main = unit.functions.get("main").unwrap();
+    let body = main.body.as_ref().unwrap();
+    assert_eq!(body.stmts().len(), 2);
+    assert!(matches!(body.stmts()[0], Statement::VariableDeclaration(_)));
+    assert!(matches!(body.stmts()[1], Statement::If(_)));
+  }
+
+  #[test]
+  pub fn test_module() {
+    let mut lexer = DefaultLexer {};
+    let tokens = lexer
+      .tokenize(
+        "public module = {\n  use core::iter::Iterator;\n  public use self::sorted::*;\n}\nuse \
+         std::String as Str;\nfunc main() {}",
+      )
+      .unwrap();
+
+    let mut state = ParseState::new(&tokens);
+    let unit = parse_compilation_unit(&mut state).unwrap();
+    assert_eq!(
+      unit.module.as_ref().map(|m| m.visibility),
+      Some(Accessibility::Public)
+    );
+    let uses = unit
+      .uses
+      .iter()
+      .map(|u| (u.path.to_string(), u.kind.clone(), u.visibility))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      uses,
+      vec![
+        (
+          "core::iter::Iterator".to_string(),
+          UseKind::Single(None),
+          Accessibility::Private
+        ),
+        (
+          "self::sorted".to_string(),
+          UseKind::Glob,
+          Accessibility::Public
+        ),
+        (
+          "std::String".to_string(),
+          UseKind::Single(Some("Str".to_string())),
+          Accessibility::Private
+        ),
+      ]
+    );
+    assert!(unit.functions.contains_key("main"));
+  }
+
+  #[test]
+  pub fn test_items() {
+    let mut lexer = DefaultLexer {};
+    let tokens = lexer
+      .tokenize(
+        "public struct Counter { public count: int64, step: int64 }
+         public interface Iterator {
+           type Entry;
+           func next(): Self::Entry;
+         }
+         impl Iterator for Counter {
+           type Entry = int64;
+           func next(): int64 { return self.count; }
+         }
+         impl Counter {
+           public static func new(): Counter { return Counter { count: 0, step: 1 }; }
+         }
+         func total(iter: dyn Iterator) {}",
+      )
+      .unwrap();
+
+    let mut state = ParseState::new(&tokens);
+    let unit = parse_compilation_unit(&mut state).unwrap();
+    let counter = &unit.structs["Counter"];
+    assert_eq!(counter.fields.len(), 2);
+    assert_eq!(counter.fields[1].visibility, Accessibility::Private);
+
+    let iterator = &unit.traits["Iterator"];
+    assert_eq!(iterator.associated_types[0].name, "Entry");
+    assert!(iterator.associated_types[0].ty.is_none());
+    assert_eq!(
+      iterator.methods[0].signature.return_type,
+      Type::Associated(Box::new(Type::SelfType), "Entry".to_string())
+    );
+    assert_eq!(
+      iterator.methods[0].signature.accessibility,
+      Accessibility::Public
+    );
+
+    assert_eq!(unit.impls.len(), 2);
+    assert_eq!(unit.impls[0].trait_, Some(Path::from("Iterator")));
+    assert_eq!(unit.impls[0].self_ty, Type::named(Path::from("Counter")));
+    assert_eq!(unit.impls[0].associated_types[0].ty, Some(Type::int64()));
+    assert!(unit.impls[0].methods[0].signature.receiver);
+    assert_eq!(unit.impls[1].trait_, None);
+    assert!(!unit.impls[1].methods[0].signature.receiver);
+    assert_eq!(
+      unit.functions["total"].signature.parameters[0].ty,
+      Type::Dyn(Path::from("Iterator"))
+    );
+  }
+
+  #[test]
+  pub fn test_generics() {
+    let mut lexer = DefaultLexer {};
+    let tokens = lexer
+      .tokenize(
+        "struct Pair<A, B> { first: A, second: B }
+         func max<T: Comparable + Error, U>(a: T, b: Pair<U, Pair<T, int64>>): T
+           where U: Iterator, Pair<T, U>: Error { return a; }",
+      )
+      .unwrap();
+    let mut state = ParseState::new(&tokens);
+    let unit = parse_compilation_unit(&mut state).unwrap();
+    let pair = &unit.structs["Pair"];
+    assert_eq!(pair.generics.params.len(), 2);
+    assert_eq!(pair.fields[1].ty, Type::named(Path::from("B")));
+
+    let generics = &unit.functions["max"].signature.generics;
+    assert_eq!(generics.params[0].name, "T");
+    assert_eq!(
+      generics.params[0].bounds,
+      vec![Path::from("Comparable"), Path::from("Error")]
+    );
+    assert!(generics.params[1].bounds.is_empty());
+    assert_eq!(generics.where_clause.len(), 2);
+    assert_eq!(generics.where_clause[0].ty, Type::named(Path::from("U")));
+    assert_eq!(generics.where_clause[1].ty.to_string(), "Pair<T, U>");
+    assert_eq!(
+      unit.functions["max"].signature.parameters[1].ty.to_string(),
+      "Pair<U, Pair<T, int64>>"
+    );
+
+    let tokens = lexer.tokenize("impl<T> Pair<T, T> {}").unwrap();
+    let mut state = ParseState::new(&tokens);
+    assert!(parse_compilation_unit(&mut state).is_err());
+  }
+
+  #[test]
+  pub fn test_consts() {
+    let mut lexer = DefaultLexer {};
+    let tokens = lexer
+      .tokenize(
+        "public const SIZE: int64 = 4 * 1024;
+         static GREETING: str = \"hello\";
+         const func double(x: int64): int64 { return x * 2; }
+         func sum(values: int64[SIZE][2]) {}",
+      )
+      .unwrap();
+    let mut state = ParseState::new(&tokens);
+    let unit = parse_compilation_unit(&mut state).unwrap();
+    let size = &unit.consts["SIZE"];
+    assert_eq!(size.kind, ConstKind::Const);
+    assert_eq!(size.visibility, Accessibility::Public);
+    assert_eq!(size.ty, Type::int64());
+    assert!(matches!(
+      size.init.kind,
+      ExpressionKind::Binary(BinaryOp::Multiply, ..)
+    ));
+    assert_eq!(unit.consts["GREETING"].kind, ConstKind::Static);
+    assert!(unit.functions["double"].signature.constancy.is_constant());
+    assert_eq!(
+      unit.functions["sum"].signature.parameters[0].ty,
+      Type::array(
+        Type::ConstArray(Box::new(Type::int64()), Path::from("SIZE")),
+        2
+      )
+    );
 
-    let mut state = ParseState::new();
-    match parse_expr(&mut state, &mut tokens, (0, end).clone()) {
-      ParseResult::Matched(t, range) => {
-        debug_println!("result = {:?}", t);
+    let tokens = lexer.tokenize("const SIZE = 1;").unwrap();
+    let mut state = ParseState::new(&tokens);
+    let error = parse_compilation_unit(&mut state).err().unwrap();
+    assert_eq!(error.message(), "1:12: expected `:`, found `=`");
+  }
+
+  #[test]
+  pub fn test_postfix() {
+    let mut lexer = DefaultLexer {};
+    let tokens = lexer.tokenize("Point::new(1).scale(2).x").unwrap();
+    let mut state = ParseState::new(&tokens);
+    let expr = parse_expr(&mut state).unwrap();
+    let receiver = match expr.kind {
+      ExpressionKind::Field(receiver, field) if field == "x" => receiver,
+      kind => panic!("unexpected expression: {:?}", kind),
+    };
+    let receiver = match receiver.kind {
+      ExpressionKind::MethodCall(receiver, method, args) if method == "scale" => {
+        assert_eq!(args.len(), 1);
+        receiver
       }
-      ParseResult::Failed => {
-        debug_println!("Failed")
+      kind => panic!("unexpected expression: {:?}", kind),
+    };
+    assert!(matches!(
+      receiver.kind,
+      ExpressionKind::Call(callee, _) if matches!(&callee.kind, ExpressionKind::Path(path) if path.to_string() == "Point::new")
+    ));
+
+    // The brace after the condition starts the block rather than a struct expression.
+    let tokens = lexer
+      .tokenize("func main() { if p { let q = Point { x: (Point { x: 1 }).x }; } }")
+      .unwrap();
+    let mut state = ParseState::new(&tokens);
+    let unit = parse_compilation_unit(&mut state).unwrap();
+    let body = unit.functions["main"].body.as_ref().unwrap();
+    match &body.stmts()[0] {
+      Statement::If(stmt) => {
+        assert!(matches!(&stmt.condition.kind, ExpressionKind::Identifier(name) if name == "p"));
+        assert!(matches!(
+          &stmt.then.stmts()[0],
+          Statement::VariableDeclaration(decl)
+            if matches!(&decl.init.as_ref().unwrap().kind, ExpressionKind::StructLiteral(..))
+        ));
       }
+      stmt => panic!("unexpected statement: {:?}", stmt),
+    }
+  }
+
+  #[test]
+  pub fn test_index() {
+    let mut lexer = DefaultLexer {};
+    let tokens = lexer.tokenize("values[i + 1].x = [1, 2, 3,][0]").unwrap();
+    let mut state = ParseState::new(&tokens);
+    let expr = parse_expr(&mut state).unwrap();
+    let (left, right) = match expr.kind {
+      ExpressionKind::Binary(BinaryOp::Assignment, left, right) => (left, right),
+      kind => panic!("unexpected expression: {:?}", kind),
+    };
+    assert!(left.is_place());
+    match left.kind {
+      ExpressionKind::Field(base, field) if field == "x" => assert!(matches!(
+        &base.kind,
+        ExpressionKind::Index(array, index)
+          if matches!(&array.kind, ExpressionKind::Identifier(name) if name == "values")
+            && matches!(&index.kind, ExpressionKind::Binary(BinaryOp::Add, ..))
+      )),
+      kind => panic!("unexpected expression: {:?}", kind),
     }
+    match right.kind {
+      ExpressionKind::Index(array, _) => assert!(matches!(
+        &array.kind,
+        ExpressionKind::ArrayLiteral(elements) if elements.len() == 3
+      )),
+      kind => panic!("unexpected expression: {:?}", kind),
+    }
+
+    let tokens = lexer.tokenize("values[0").unwrap();
+    let mut state = ParseState::new(&tokens);
+    assert!(parse_expr(&mut state).is_err());
+  }
+
+  #[test]
+  pub fn test_enums() {
+    let mut lexer = DefaultLexer {};
+    let tokens = lexer
+      .tokenize(
+        "@Lang(Expected)
+         public enum Expected<T, E> { Value(T), Error(E), }
+         enum Ordering { Less = -1, Equal, Greater }
+         @Lang(From)
+         public interface From {
+           type Source;
+           static func from(value: Self::Source): Self;
+         }
+         func parse(): Expected<int64, str> { return Expected::Value(read()?.value? + 1); }",
+      )
+      .unwrap();
+    let mut state = ParseState::new(&tokens);
+    let unit = parse_compilation_unit(&mut state).unwrap();
+    let expected = &unit.enums["Expected"];
+    assert_eq!(expected.visibility, Accessibility::Public);
+    assert!(expected.annotations.as_ref().unwrap()[0].has_attribute("Expected"));
+    assert_eq!(expected.generics.params.len(), 2);
+    let variants = expected
+      .variants
+      .iter()
+      .map(|v| (v.name.as_str(), v.fields.clone()))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      variants,
+      vec![
+        ("Value", vec![Type::named(Path::from("T"))]),
+        ("Error", vec![Type::named(Path::from("E"))]),
+      ]
+    );
+    assert_eq!(unit.enums["Ordering"].variants.len(), 3);
+    assert!(unit.enums["Ordering"].variants[2].fields.is_empty());
+    assert!(matches!(
+      unit.enums["Ordering"].variants[0].discriminant.as_ref().unwrap().kind,
+      ExpressionKind::Unary(UnaryOp::Negative, _)
+    ));
+    assert!(unit.enums["Ordering"].variants[1].discriminant.is_none());
+    assert_eq!(
+      unit.traits["From"].annotations.as_ref().unwrap()[0].name(),
+      "Lang"
+    );
+
+    let body = unit.functions["parse"].body.as_ref().unwrap();
+    let value = match &body.stmts()[0] {
+      Statement::Return(stmt) => match &stmt.value.as_ref().unwrap().kind {
+        ExpressionKind::Call(_, args) => args[0].clone(),
+        kind => panic!("unexpected expression: {:?}", kind),
+      },
+      stmt => panic!("unexpected statement: {:?}", stmt),
+    };
+    // `?` binds tighter than the binary operators, and applies to the field after the call.
+    let left = match value.kind {
+      ExpressionKind::Binary(BinaryOp::Add, left, _) => left,
+      kind => panic!("unexpected expression: {:?}", kind),
+    };
+    assert!(matches!(
+      left.kind,
+      ExpressionKind::Try(operand) if matches!(
+        &operand.kind,
+        ExpressionKind::Field(base, _) if matches!(base.kind, ExpressionKind::Try(_))
+      )
+    ));
+
+    let tokens = lexer.tokenize("enum Bit { Zero, Zero }").unwrap();
+    let mut state = ParseState::new(&tokens);
+    let error = parse_compilation_unit(&mut state).err().unwrap();
+    assert_eq!(
+      error.message(),
+      "1:23: variant `Zero` is already declared, found `}`"
+    );
+  }
+
+  #[test]
+  pub fn test_match() {
+    let mut lexer = DefaultLexer {};
+    let tokens = lexer
+      .tokenize(
+        "func sign(x: Optional<int64>): int64 {
+           match x {
+             Optional::Value(0) => { return 0; }
+             Optional::Value(v) => if v < 0 { -1 } else { 1 },
+             _ => 0,
+           }
+           return match true { true => 1, false => -1 };
+         }",
+      )
+      .unwrap();
+    let mut state = ParseState::new(&tokens);
+    let unit = parse_compilation_unit(&mut state).unwrap();
+    let body = unit.functions["sign"].body.as_ref().unwrap();
+    let arms = match &body.stmts()[0] {
+      Statement::Expression(expr) => match &expr.kind {
+        ExpressionKind::Match(scrutinee, arms) => {
+          assert!(matches!(&scrutinee.kind, ExpressionKind::Identifier(x) if x == "x"));
+          arms.clone()
+        }
+        kind => panic!("unexpected expression: {:?}", kind),
+      },
+      stmt => panic!("unexpected statement: {:?}", stmt),
+    };
+    assert_eq!(arms.len(), 3);
+    assert!(matches!(
+      &arms[0].pattern.kind,
+      PatternKind::Variant(path, fields)
+        if path == &Path::from("Optional::Value")
+          && matches!(fields[0].kind, PatternKind::Integer(0))
+    ));
+    assert!(matches!(&arms[0].body.kind, ExpressionKind::Block(block) if block.diverges()));
+    assert!(matches!(
+      &arms[1].pattern.kind,
+      PatternKind::Variant(_, fields) if matches!(&fields[0].kind, PatternKind::Binding(v) if v == "v")
+    ));
+    assert!(matches!(arms[1].body.kind, ExpressionKind::If(..)));
+    assert!(matches!(arms[2].pattern.kind, PatternKind::Wildcard));
+    assert!(matches!(
+      &body.stmts()[1],
+      Statement::Return(stmt) if matches!(
+        &stmt.value.as_ref().unwrap().kind,
+        ExpressionKind::Match(_, arms) if matches!(arms[1].pattern.kind, PatternKind::Boolean(false))
+      )
+    ));
+
+    let tokens = lexer.tokenize("func f(): int64 { return match 1 { + => 1 }; }").unwrap();
+    let mut state = ParseState::new(&tokens);
+    let error = parse_compilation_unit(&mut state).err().unwrap();
+    assert_eq!(error.message(), "1:36: expected pattern, found `+`");
+  }
+
+  #[test]
+  pub fn test_async() {
+    let mut lexer = DefaultLexer {};
+    let tokens = lexer
+      .tokenize(
+        "public async func fetch(id: int64): int64 { return load(id).await.value + 1; }
+         const SIZE: int64 = 1;",
+      )
+      .unwrap();
+    let mut state = ParseState::new(&tokens);
+    let unit = parse_compilation_unit(&mut state).unwrap();
+    let fetch = &unit.functions["fetch"];
+    assert!(fetch.signature.asyncness.is_async());
+    assert!(unit.consts.contains_key("SIZE"));
+    let value = match &fetch.body.as_ref().unwrap().stmts()[0] {
+      Statement::Return(stmt) => stmt.value.as_ref().unwrap().clone(),
+      stmt => panic!("unexpected statement: {:?}", stmt),
+    };
+    // `.await` is a postfix operator like the field access.
+    let left = match value.kind {
+      ExpressionKind::Binary(BinaryOp::Add, left, _) => left,
+      kind => panic!("unexpected expression: {:?}", kind),
+    };
+    assert!(matches!(
+      left.kind,
+      ExpressionKind::Field(base, _) if matches!(
+        &base.kind,
+        ExpressionKind::Await(operand) if matches!(operand.kind, ExpressionKind::Call(..))
+      )
+    ));
+
+    let tokens = lexer.tokenize("const async func zero(): int64 { return 0; }").unwrap();
+    let mut state = ParseState::new(&tokens);
+    let error = parse_compilation_unit(&mut state).err().unwrap();
+    assert_eq!(
+      error.message(),
+      "1:13: functions cannot be both `const` and `async`, found `func`"
+    );
+  }
+
+  #[test]
+  pub fn test_unsafe() {
+    let mut lexer = DefaultLexer {};
+    let tokens = lexer
+      .tokenize(
+        "const unsafe func read(p: *int64[4]): int64 { return *(p as *int64 + 1); }
+         func main() { var x = 1; unsafe { *&x = read(&x as *int64[4]); } }",
+      )
+      .unwrap();
+    let mut state = ParseState::new(&tokens);
+    let unit = parse_compilation_unit(&mut state).unwrap();
+    let read = &unit.functions["read"];
+    assert!(read.signature.unsafety.is_unsafe());
+    assert!(read.signature.constancy.is_constant());
+    // The pointer applies to the whole array type.
+    assert_eq!(
+      read.signature.parameters[0].ty,
+      Type::pointer(Type::array(Type::int64(), 4))
+    );
+    assert!(!unit.functions["main"].signature.unsafety.is_unsafe());
+
+    let body = unit.functions["main"].body.as_ref().unwrap();
+    let block = match &body.stmts()[1] {
+      Statement::Unsafe(block) => block,
+      stmt => panic!("unexpected statement: {:?}", stmt),
+    };
+    let (left, right) = match &block.stmts()[0] {
+      Statement::Expression(expr) => match &expr.kind {
+        ExpressionKind::Binary(BinaryOp::Assignment, left, right) => (left, right),
+        kind => panic!("unexpected expression: {:?}", kind),
+      },
+      stmt => panic!("unexpected statement: {:?}", stmt),
+    };
+    assert!(left.is_place());
+    assert!(matches!(
+      &left.kind,
+      ExpressionKind::Unary(UnaryOp::Dereference, operand)
+        if matches!(operand.kind, ExpressionKind::Unary(UnaryOp::AddressOf, _))
+    ));
+    // `&` binds tighter than `as`.
+    assert!(matches!(
+      &right.kind,
+      ExpressionKind::Call(_, args) if matches!(
+        &args[0].kind,
+        ExpressionKind::Cast(operand, Type::Pointer(_))
+          if matches!(operand.kind, ExpressionKind::Unary(UnaryOp::AddressOf, _))
+      )
+    ));
+  }
+
+  #[test]
+  pub fn test_extern() {
+    let mut lexer = DefaultLexer {};
+    let tokens = lexer
+      .tokenize(
+        "extern \"C\" func printf(format: str, ...): int32;
+         public extern func add(a: int32, b: int32): int32 { return a + b; }",
+      )
+      .unwrap();
+    let mut state = ParseState::new(&tokens);
+    let unit = parse_compilation_unit(&mut state).unwrap();
+    let printf = &unit.functions["printf"];
+    assert_eq!(printf.signature.abi, Abi::C);
+    assert!(printf.signature.variadic);
+    assert_eq!(printf.signature.parameters.len(), 1);
+    assert!(printf.body.is_none());
+    let add = &unit.functions["add"];
+    assert_eq!(add.signature.abi, Abi::C);
+    assert_eq!(add.signature.accessibility, Accessibility::Public);
+    assert!(!add.signature.variadic);
+    assert!(add.body.is_some());
+
+    for (source, message) in [
+      (
+        "extern \"stdcall\" func f();",
+        "1:8: expected ABI \"C\", found string literal \"stdcall\"",
+      ),
+      (
+        "func f(a: int32, ...);",
+        "1:18: only `extern \"C\"` functions could be variadic, found `...`",
+      ),
+      (
+        "extern func f(a: int32, ...) {}",
+        "1:30: variadic functions could only be declared without body, found `{`",
+      ),
+      (
+        "extern \"C\" func f(a: int32, ..);",
+        "1:29: expected identifier, found `..`",
+      ),
+    ] {
+      let tokens = lexer.tokenize(source).unwrap();
+      let mut state = ParseState::new(&tokens);
+      let error = parse_compilation_unit(&mut state).err().unwrap();
+      assert_eq!(error.message(), message);
+    }
+  }
+
+  #[test]
+  pub fn test_loops() {
+    let mut lexer = DefaultLexer {};
+    let tokens = lexer
+      .tokenize(
+        "func main(n: int64): int64 {
+           'outer: for i in 0..n {
+             loop { if i > 2 { break 'outer; } continue; }
+           }
+           while n > 0 { break; }
+           return if n < 0 { 0 } else if n < 10 { n } else { Point { x: n }.x };
+         }",
+      )
+      .unwrap();
+    let mut state = ParseState::new(&tokens);
+    let unit = parse_compilation_unit(&mut state).unwrap();
+    let body = unit.functions["main"].body.as_ref().unwrap();
+    let stmt = match &body.stmts()[0] {
+      Statement::For(stmt) => stmt,
+      stmt => panic!("unexpected statement: {:?}", stmt),
+    };
+    assert_eq!(stmt.label.as_deref(), Some("outer"));
+    assert_eq!(stmt.name, "i");
+    assert!(matches!(
+      &stmt.iterable,
+      ForIterable::Range(_, end) if matches!(end.kind, ExpressionKind::Identifier(ref name) if name == "n")
+    ));
+    let inner = match &stmt.body.stmts()[0] {
+      Statement::Loop(stmt) => stmt.body.stmts(),
+      stmt => panic!("unexpected statement: {:?}", stmt),
+    };
+    assert!(matches!(
+      &inner[0],
+      Statement::If(stmt) if matches!(
+        &stmt.then.stmts()[0],
+        Statement::Break(jump) if jump.label.as_deref() == Some("outer")
+      )
+    ));
+    assert!(matches!(&inner[1], Statement::Continue(jump) if jump.label.is_none()));
+    assert!(matches!(&body.stmts()[1], Statement::While(stmt) if stmt.label.is_none()));
+    // `else if` chains another conditional expression.
+    let value = match &body.stmts()[2] {
+      Statement::Return(stmt) => stmt.value.as_ref().unwrap().clone(),
+      stmt => panic!("unexpected statement: {:?}", stmt),
+    };
+    assert!(matches!(
+      value.kind,
+      ExpressionKind::If(_, _, otherwise) if matches!(otherwise.kind, ExpressionKind::If(..))
+    ));
+
+    // Struct literals are not allowed as the iterator, whose block would be taken as the body.
+    let tokens = lexer.tokenize("func main() { for x in items {} }").unwrap();
+    let mut state = ParseState::new(&tokens);
+    let unit = parse_compilation_unit(&mut state).unwrap();
+    let body = unit.functions["main"].body.as_ref().unwrap();
+    assert!(matches!(
+      &body.stmts()[0],
+      Statement::For(ForStatement { iterable: ForIterable::Iterator(iterator), .. })
+        if matches!(iterator.kind, ExpressionKind::Identifier(ref name) if name == "items")
+    ));
+
+    let tokens = lexer.tokenize("func main() { 'outer: if true {} }").unwrap();
+    let mut state = ParseState::new(&tokens);
+    let error = parse_compilation_unit(&mut state).err().unwrap();
+    assert_eq!(
+      error.message(),
+      "1:23: expected `while`, `loop` or `for` after the label, found `if`"
+    );
+  }
+
+  #[test]
+  pub fn test_error() {
+    let mut lexer = DefaultLexer {};
+    let tokens = lexer.tokenize("func main() { return 1 }").unwrap();
+
+    let mut state = ParseState::new(&tokens);
+    let error = parse_compilation_unit(&mut state).err().unwrap();
+    assert_eq!(error.message(), "1:24: expected `;`, found `}`");
   }
 }
@@ -1,4 +1,6 @@
 use core::fmt::Debug;
+use core::fmt::Display;
+use core::fmt::Formatter;
 
 #[allow(deprecated)]
 pub const IDENTIFIER_TYPE_ID: u8 = std::u8::MAX - 4;
@@ -38,6 +40,7 @@ pub enum Token {
   /*  `=`   */Assigment,
   /*  `@`   */At,
   /*  `!`   */Not,
+  /*  `&`   */Ampersand,
   /*  `&&`  */And,
   /*  `||`  */Or,
   /*  `^`   */Xor,
@@ -48,6 +51,8 @@ pub enum Token {
   /*  `->`  */Arrow,
   /*  `=>`  */DArrow,
   /*  `::`  */DColon,
+  /*  `...` */Ellipsis,
+  /*  `..`  */DotDot,
 
   //============================================================================//
   //  Keywords
@@ -60,8 +65,10 @@ pub enum Token {
   Break,
   Const,
   Continue,
+  Dyn,
   Else,
   Enum,
+  Extern,
   False,
   Func,
   For,
@@ -69,13 +76,16 @@ pub enum Token {
   // H-N
   If,
   Impl,
+  In,
   Int,
   Int8,
   Int16,
   Int32,
   Int64,
+  Interface,
   Let,
   Loop,
+  Match,
   Module,
 
   // O-T
@@ -138,6 +148,114 @@ impl Token {
   }
 }
 
+impl Display for Token {
+  fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+    match self {
+      Token::Identifier(s) => write!(f, "identifier `{}`", s),
+      Token::LiteralText(s) => write!(f, "string literal \"{}\"", s),
+      Token::LiteralInteger(i) => write!(f, "integer literal `{}`", i),
+      Token::LiteralFloat(s) => write!(f, "float literal `{}`", s),
+      token => write!(f, "`{}`", token.as_str()),
+    }
+  }
+}
+
+impl Token {
+  /// The literal string of the non-literal token, as the reverse of
+  /// [`mapping_non_literal_token`].
+  #[rustfmt::skip]
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Token::Dot => ".",
+      Token::Comma => ",",
+      Token::Colon => ":",
+      Token::SemiColon => ";",
+      Token::Plus => "+",
+      Token::Minus => "-",
+      Token::Asterisk => "*",
+      Token::Slash => "/",
+      Token::Percentage => "%",
+      Token::LParenthesis => "(",
+      Token::RParenthesis => ")",
+      Token::LBracket => "[",
+      Token::RBracket => "]",
+      Token::LBrace => "{",
+      Token::RBrace => "}",
+      Token::Less => "<",
+      Token::Greater => ">",
+      Token::LessEqual => "<=",
+      Token::GreaterEqual => ">=",
+      Token::Equal => "==",
+      Token::NotEqual => "!=",
+      Token::Assigment => "=",
+      Token::At => "@",
+      Token::Not => "!",
+      Token::Ampersand => "&",
+      Token::And => "&&",
+      Token::Or => "||",
+      Token::Xor => "^",
+      Token::Question => "?",
+      Token::SQuote => "'",
+      Token::DQuote => "\"",
+      Token::TQuote => "\"\"\"",
+      Token::Arrow => "->",
+      Token::DArrow => "=>",
+      Token::DColon => "::",
+      Token::Ellipsis => "...",
+      Token::DotDot => "..",
+      Token::As => "as",
+      Token::Async => "async",
+      Token::Await => "await",
+      Token::Break => "break",
+      Token::Const => "const",
+      Token::Continue => "continue",
+      Token::Dyn => "dyn",
+      Token::Else => "else",
+      Token::Enum => "enum",
+      Token::Extern => "extern",
+      Token::False => "false",
+      Token::Func => "func",
+      Token::For => "for",
+      Token::If => "if",
+      Token::Impl => "impl",
+      Token::In => "in",
+      Token::Int => "int",
+      Token::Int8 => "int8",
+      Token::Int16 => "int16",
+      Token::Int32 => "int32",
+      Token::Int64 => "int64",
+      Token::Interface => "interface",
+      Token::Let => "let",
+      Token::Loop => "loop",
+      Token::Match => "match",
+      Token::Module => "module",
+      Token::Public => "public",
+      Token::Ref => "ref",
+      Token::Return => "return",
+      Token::Static => "static",
+      Token::Struct => "struct",
+      Token::Trait => "trait",
+      Token::True => "true",
+      Token::Type => "type",
+      Token::Unsafe => "unsafe",
+      Token::Use => "use",
+      Token::Uint => "uint",
+      Token::Uint8 => "uint8",
+      Token::Uint16 => "uint16",
+      Token::Uint32 => "uint32",
+      Token::Uint64 => "uint64",
+      Token::Var => "var",
+      Token::Where => "where",
+      Token::While => "while",
+      Token::Self_ => "self",
+      Token::Identifier(_)
+      | Token::LiteralText(_)
+      | Token::LiteralInteger(_)
+      | Token::LiteralFloat(_) => "",
+    }
+  }
+}
+
 /// Mapping the literal string to the corresponding non-literal token.
 /// If the return values is `None`, try to
 #[rustfmt::skip]
@@ -167,6 +285,7 @@ pub fn mapping_non_literal_token(s: &str) -> Option<Token> {
     "=" => Some(Token::Assigment),
     "@" => Some(Token::At),
     "!" => Some(Token::Not),
+    "&" => Some(Token::Ampersand),
     "&&" => Some(Token::And),
     "||" => Some(Token::Or),
     "^" => Some(Token::Xor),
@@ -174,6 +293,8 @@ pub fn mapping_non_literal_token(s: &str) -> Option<Token> {
     "->" => Some(Token::Arrow),
     "=>" => Some(Token::DArrow),
     "::" => Some(Token::DColon),
+    "..." => Some(Token::Ellipsis),
+    ".." => Some(Token::DotDot),
     "'" => Some(Token::SQuote),
     "\"" => Some(Token::DQuote),
     "\"\"\"" => Some(Token::TQuote),
@@ -184,20 +305,25 @@ pub fn mapping_non_literal_token(s: &str) -> Option<Token> {
     "break" => Some(Token::Break),
     "const" => Some(Token::Const),
     "continue" => Some(Token::Continue),
+    "dyn" => Some(Token::Dyn),
     "else" => Some(Token::Else),
     "enum" => Some(Token::Enum),
+    "extern" => Some(Token::Extern),
     "false" => Some(Token::False),
     "func" => Some(Token::Func),
     "for" => Some(Token::For),
     "if" => Some(Token::If),
     "impl" => Some(Token::Impl),
+    "in" => Some(Token::In),
     "int" => Some(Token::Int),
     "int8" => Some(Token::Int8),
     "int16" => Some(Token::Int16),
     "int32" => Some(Token::Int32),
     "int64" => Some(Token::Int64),
+    "interface" => Some(Token::Interface),
     "let" => Some(Token::Let),
     "loop" => Some(Token::Loop),
+    "match" => Some(Token::Match),
     "module" => Some(Token::Module),
     "public" => Some(Token::Public),
     "ref" => Some(Token::Ref),
@@ -210,7 +336,7 @@ pub fn mapping_non_literal_token(s: &str) -> Option<Token> {
     "unsafe" => Some(Token::Unsafe),
     "use" => Some(Token::Use),
     "var" => Some(Token::Var),
-    "uint" => Some(Token::Uint8),
+    "uint" => Some(Token::Uint),
     "uint8" => Some(Token::Uint8),
     "uint16" => Some(Token::Uint16),
     "uint32" => Some(Token::Uint32),
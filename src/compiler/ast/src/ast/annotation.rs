@@ -12,4 +12,29 @@ pub struct Annotation {
   attributes: HashMap<String, AnnotationAttribute>,
 }
 
+impl Annotation {
+  pub fn new(name: impl Into<String>) -> Self {
+    Self {
+      name:       name.into(),
+      attributes: HashMap::new(),
+    }
+  }
+
+  pub fn name(&self) -> &str {
+    self.name.as_str()
+  }
+
+  pub fn add_attribute(&mut self, attribute: impl Into<String>) {
+    self.attributes.insert(attribute.into(), ());
+  }
+
+  pub fn has_attribute(&self, attribute: &str) -> bool {
+    self.attributes.contains_key(attribute)
+  }
+
+  pub fn attributes(&self) -> impl Iterator<Item = &str> {
+    self.attributes.keys().map(String::as_str)
+  }
+}
+
 pub type AnnotationAttribute = ();
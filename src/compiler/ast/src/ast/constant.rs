@@ -0,0 +1,39 @@
+use vsp_span::Span;
+
+use crate::ast::expr::Expression;
+use crate::ast::modifier::Accessibility;
+use crate::ast::types::Type;
+
+/// # Constant and static item
+///
+/// Both are evaluated at compile time. Uses of a constant are replaced by its value, while a
+/// static item is a single value in memory which all uses refer to.
+///
+/// ```vsp
+/// public const SIZE: int64 = 4 * 1024;
+/// static GREETING: str = "hello";
+/// ```
+pub struct ConstDefinition {
+  pub name:       String,
+  pub kind:       ConstKind,
+  pub visibility: Accessibility,
+  /// Type of the item, which is always written explicitly.
+  pub ty:         Type,
+  pub init:       Expression,
+  pub span:       Span,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConstKind {
+  Const,
+  Static,
+}
+
+impl ConstKind {
+  pub fn keyword(&self) -> &'static str {
+    match self {
+      ConstKind::Const => "const",
+      ConstKind::Static => "static",
+    }
+  }
+}
@@ -0,0 +1,37 @@
+use vsp_span::Span;
+
+use crate::ast::annotation::Annotation;
+use crate::ast::expr::Expression;
+use crate::ast::generics::Generics;
+use crate::ast::modifier::Accessibility;
+use crate::ast::types::Type;
+
+/// # Enum
+///
+/// ```vsp
+/// public enum Either<L, R> {
+///   Left(L),
+///   Right(R),
+/// }
+/// ```
+pub struct EnumDefinition {
+  pub name:        String,
+  pub visibility:  Accessibility,
+  pub annotations: Option<Vec<Annotation>>,
+  pub generics:    Generics,
+  /// Variants in the order of declaration.
+  pub variants:    Vec<Variant>,
+  pub span:        Span,
+}
+
+/// Variant of the enum, either without fields as `None`, or with fields as `Value(T)`. Variants
+/// are as visible as the enum.
+#[derive(Clone, Debug)]
+pub struct Variant {
+  pub name:         String,
+  pub fields:       Vec<Type>,
+  /// Constant expression given as `Red = 1` to the discriminant of the variant. The variants
+  /// without it take the discriminant next to the previous variant, or 0 if it is the first one.
+  pub discriminant: Option<Expression>,
+  pub span:         Span,
+}
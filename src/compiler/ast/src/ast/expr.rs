@@ -1,11 +1,56 @@
+use core::cmp::Ordering;
+
+use vsp_span::Span;
+
+use crate::ast::module::Path;
+use crate::ast::pattern::MatchArm;
+use crate::ast::stmt::StatementBlock;
+use crate::ast::types::Type;
 use crate::ast::ASTNode;
 use crate::ast::ExprNode;
+use crate::ast::NodeId;
 
 /// # Expression
 ///
-/// Expression indicates ...
+/// Expression indicates any AST node which evaluates to a value. Each expression carries its own
+/// span and a unique node ID, so that the semantic analysis could attach information such as the
+/// type to it without modifying the tree.
+#[derive(Clone, Debug)]
+pub struct Expression {
+  pub id:   NodeId,
+  pub kind: ExpressionKind,
+  pub span: Span,
+}
+
+impl Expression {
+  pub fn new(kind: ExpressionKind, span: Span) -> Self {
+    Self {
+      id: NodeId::next(),
+      kind,
+      span,
+    }
+  }
+
+  /// Create the expression without a meaningful span, mostly used in tests.
+  pub fn dummy(kind: ExpressionKind) -> Self {
+    Self::new(kind, Span::default())
+  }
+
+  /// True if the expression could be placed on the left side of the assignment.
+  pub fn is_place(&self) -> bool {
+    match &self.kind {
+      ExpressionKind::Identifier(_) => true,
+      ExpressionKind::Field(base, _) => base.is_place(),
+      ExpressionKind::Index(base, _) => base.is_place(),
+      ExpressionKind::Unary(UnaryOp::Dereference, _) => true,
+      _ => false,
+    }
+  }
+}
+
+/// Kind of the expression.
 #[derive(Clone, Debug)]
-pub enum Expression {
+pub enum ExpressionKind {
   Unit,
 
   // Literal
@@ -13,16 +58,48 @@ pub enum Expression {
   LiteralFloat(f64),
   LiteralBoolean(bool),
   LiteralString(String),
+  /// Name of local variables and functions, including `self` in methods.
   Identifier(String),
+  /// Path to associated functions, such as `Point::new`.
+  Path(Path),
+  /// Struct expression, such as `Point { x: 1, y: 2 }`.
+  StructLiteral(Path, Vec<(String, Expression)>),
+  /// Field access expression, such as `point.x`.
+  Field(Box<Expression>, String),
+  /// Array expression, such as `[1, 2, 3]`.
+  ArrayLiteral(Vec<Expression>),
+  /// Index expression, such as `values[i]`, whose index is checked against the length of the
+  /// array.
+  Index(Box<Expression>, Box<Expression>),
 
   // Operations
   /// Unary operation expression, such as `!foo`.
   Unary(UnaryOp, Box<Expression>),
   /// Binary operation expression, such as `foo + bar`.
   Binary(BinaryOp, Box<Expression>, Box<Expression>),
+  /// Explicit type conversion, such as `foo as int64`.
+  Cast(Box<Expression>, Type),
+  /// Error propagation expression, such as `parse(text)?`, which returns the error of `Expected`
+  /// or the none of `Optional` from the function early.
+  Try(Box<Expression>),
+  /// Suspension point in `async func`, such as `fetch(url).await`, which waits for the future to
+  /// complete and evaluates to its output.
+  Await(Box<Expression>),
+  /// Conditional expression, such as `if a < b { a } else { b }`, whose branches are single
+  /// expressions of the same type.
+  If(Box<Expression>, Box<Expression>, Box<Expression>),
+  /// Pattern matching expression, such as `match x { Optional::Value(v) => v, _ => 0 }`, which
+  /// evaluates the first arm whose pattern matches the value.
+  Match(Box<Expression>, Vec<MatchArm>),
+  /// Block of statements as the body of the match arm, whose value is unit unless it ends with
+  /// `return`, `break` or `continue`, so that it never completes.
+  Block(Box<StatementBlock>),
 
   // Call
-  MethodCall(String, Box<Expression>),
+  /// Function call expression, such as `foo(1, 2)`.
+  Call(Box<Expression>, Vec<Expression>),
+  /// Method call expression, such as `iter.next()`.
+  MethodCall(Box<Expression>, String, Vec<Expression>),
   LambdaExpression(Vec<String>, Box<Expression>),
 }
 
@@ -35,6 +112,8 @@ impl ExprNode for Expression {}
 pub enum UnaryOp {
   /// `*` for dereference the pointer.
   Dereference,
+  /// `&` for the raw pointer to the place.
+  AddressOf,
   /// `!` for logical `not`.
   Not,
   /// `-` for numeric negation.
@@ -45,6 +124,7 @@ impl UnaryOp {
   pub fn as_str(&self) -> &'static str {
     match self {
       UnaryOp::Dereference => "*",
+      UnaryOp::AddressOf => "&",
       UnaryOp::Not => "!",
       UnaryOp::Negative => "-",
     }
@@ -63,6 +143,24 @@ pub enum BinaryOp {
   Multiply,
   /// `/` for division.
   Division,
+  /// `%` for remainder.
+  Remainder,
+  /// `==` for equality.
+  Equal,
+  /// `!=` for inequality.
+  NotEqual,
+  /// `<` for less than.
+  Less,
+  /// `<=` for less than or equal to.
+  LessEqual,
+  /// `>` for greater than.
+  Greater,
+  /// `>=` for greater than or equal to.
+  GreaterEqual,
+  /// `&&` for logical `and`.
+  And,
+  /// `||` for logical `or`.
+  Or,
   /// `=` for assignment.
   Assignment,
 }
@@ -72,9 +170,61 @@ impl BinaryOp {
     match *self {
       BinaryOp::Add => "+",
       BinaryOp::Subtract => "-",
-      BinaryOp::Multiply => "+",
+      BinaryOp::Multiply => "*",
       BinaryOp::Division => "/",
+      BinaryOp::Remainder => "%",
+      BinaryOp::Equal => "==",
+      BinaryOp::NotEqual => "!=",
+      BinaryOp::Less => "<",
+      BinaryOp::LessEqual => "<=",
+      BinaryOp::Greater => ">",
+      BinaryOp::GreaterEqual => ">=",
+      BinaryOp::And => "&&",
+      BinaryOp::Or => "||",
       BinaryOp::Assignment => "=",
     }
   }
+
+  /// True if the operation is an arithmetic operation on numbers.
+  pub fn is_arithmetic(&self) -> bool {
+    matches!(
+      self,
+      BinaryOp::Add
+        | BinaryOp::Subtract
+        | BinaryOp::Multiply
+        | BinaryOp::Division
+        | BinaryOp::Remainder
+    )
+  }
+
+  /// True if the operation compares two operands and results in a boolean.
+  pub fn is_comparison(&self) -> bool {
+    matches!(
+      self,
+      BinaryOp::Equal
+        | BinaryOp::NotEqual
+        | BinaryOp::Less
+        | BinaryOp::LessEqual
+        | BinaryOp::Greater
+        | BinaryOp::GreaterEqual
+    )
+  }
+
+  /// True if the operation is a short-circuit logical operation.
+  pub fn is_logical(&self) -> bool {
+    matches!(self, BinaryOp::And | BinaryOp::Or)
+  }
+
+  /// Result of the comparison of the operands which are ordered as given.
+  pub fn compare(&self, ordering: Ordering) -> bool {
+    match self {
+      BinaryOp::Equal => ordering.is_eq(),
+      BinaryOp::NotEqual => ordering.is_ne(),
+      BinaryOp::Less => ordering.is_lt(),
+      BinaryOp::LessEqual => ordering.is_le(),
+      BinaryOp::Greater => ordering.is_gt(),
+      BinaryOp::GreaterEqual => ordering.is_ge(),
+      op => unreachable!("invalid comparison operator `{}`", op.as_str()),
+    }
+  }
 }
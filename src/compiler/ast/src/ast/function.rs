@@ -1,6 +1,11 @@
+use vsp_span::Span;
+
 use crate::ast::annotation::Annotation;
+use crate::ast::generics::Generics;
 use crate::ast::modifier::Accessibility;
+use crate::ast::modifier::Asyncness;
 use crate::ast::modifier::Constancy;
+use crate::ast::modifier::Unsafety;
 use crate::ast::stmt::StatementBlock;
 use crate::ast::types::Parameter;
 use crate::ast::types::Type;
@@ -8,6 +13,23 @@ use crate::ast::types::Type;
 // Aliases for types.
 pub type FunctionAccessibility = Accessibility;
 pub type FunctionConstancy = Constancy;
+pub type FunctionAsyncness = Asyncness;
+pub type FunctionUnsafety = Unsafety;
+
+/// Calling convention of the function.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Abi {
+  /// Calling convention of vsp, which is unspecified.
+  Vsp,
+  /// Calling convention of C on the target, declared by `extern "C"`.
+  C,
+}
+
+impl Abi {
+  pub fn is_c(&self) -> bool {
+    matches!(self, Abi::C)
+  }
+}
 
 /// # Function
 /// The function AST represents a function,
@@ -26,6 +48,8 @@ pub struct Function {
   pub signature:   FunctionSignature,
   /** Function body (statements) */
   pub body:        Option<Box<StatementBlock>>,
+  /** Span of the whole function */
+  pub span:        Span,
 }
 
 impl Function {
@@ -35,20 +59,35 @@ impl Function {
       annotations: None,
       signature,
       body: None,
+      span: Span::default(),
     }
   }
 }
 
 /// Function signature or function declarator.
+#[derive(Debug)]
 pub struct FunctionSignature {
   /** Function accessibility */
   pub accessibility: FunctionAccessibility,
   /** Function constancy */
   pub constancy:     FunctionConstancy,
+  /** Function asynchrony, where calling `async func` returns the state machine of its body */
+  pub asyncness:     FunctionAsyncness,
+  /** Function safety, where calling `unsafe func` requires an `unsafe` block or function */
+  pub unsafety:      FunctionUnsafety,
+  /** Calling convention, where `extern` functions without body are defined in foreign codes */
+  pub abi:           Abi,
+  /** Whether the function takes variable arguments after the parameters by `...` */
+  pub variadic:      bool,
+  /** Generic parameters and the `where` clause */
+  pub generics:      Generics,
   /** Parameter list */
   pub parameters:    Vec<Parameter>,
   /** Return type */
   pub return_type:   Type,
+  /** Whether the method takes the receiver `self` implicitly, false for `static` methods and
+   * functions outside traits and impls */
+  pub receiver:      bool,
 }
 
 impl FunctionSignature {
@@ -61,8 +100,14 @@ impl FunctionSignature {
     Self {
       accessibility,
       constancy,
+      asyncness: Asyncness::None,
+      unsafety: Unsafety::None,
+      abi: Abi::Vsp,
+      variadic: false,
+      generics: Generics::default(),
       parameters,
       return_type,
+      receiver: false,
     }
   }
 }
@@ -0,0 +1,47 @@
+//! Generic parameters and `where` clauses.
+//!
+//! Functions and structs could be generic over types. Bounds of the parameters are written either
+//! inline or in the `where` clause after the signature.
+//!
+//! ```vsp
+//! public func max<T: Comparable>(a: T, b: T): T { ... }
+//!
+//! public func sum<I>(iter: I): int64 where I: Iterator { ... }
+//!
+//! public struct Pair<A, B> {
+//!   public first: A,
+//!   public second: B,
+//! }
+//! ```
+use vsp_span::Span;
+
+use crate::ast::module::Path;
+use crate::ast::types::Type;
+
+#[derive(Clone, Debug, Default)]
+pub struct Generics {
+  pub params:       Vec<GenericParam>,
+  pub where_clause: Vec<WherePredicate>,
+}
+
+impl Generics {
+  pub fn is_empty(&self) -> bool {
+    self.params.is_empty()
+  }
+}
+
+/// Generic parameter with its inline bounds, such as `T: Comparable + Error`.
+#[derive(Clone, Debug)]
+pub struct GenericParam {
+  pub name:   String,
+  pub bounds: Vec<Path>,
+  pub span:   Span,
+}
+
+/// Predicate in the `where` clause, such as `T: Iterator`.
+#[derive(Clone, Debug)]
+pub struct WherePredicate {
+  pub ty:     Type,
+  pub bounds: Vec<Path>,
+  pub span:   Span,
+}
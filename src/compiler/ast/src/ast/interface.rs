@@ -0,0 +1,59 @@
+//! Traits and implementations.
+//!
+//! Traits are declared by either `trait` or `interface`. Methods declared in traits and impls take
+//! the receiver `self` implicitly, unless they are declared `static`.
+//!
+//! ```vsp
+//! public interface Iterator {
+//!   type Entry;
+//!
+//!   func next(): Self::Entry;
+//! }
+//!
+//! impl Iterator for Counter {
+//!   type Entry = int64;
+//!
+//!   func next(): int64 {
+//!     return self.count;
+//!   }
+//! }
+//! ```
+use vsp_span::Span;
+
+use crate::ast::annotation::Annotation;
+use crate::ast::function::Function;
+use crate::ast::modifier::Accessibility;
+use crate::ast::module::Path;
+use crate::ast::types::Type;
+
+/// # Trait
+pub struct TraitDefinition {
+  pub name:             String,
+  pub visibility:       Accessibility,
+  pub annotations:      Option<Vec<Annotation>>,
+  pub associated_types: Vec<AssociatedType>,
+  /// Methods in the order of declaration, which is also the order of slots in the vtable.
+  pub methods:          Vec<Function>,
+  pub span:             Span,
+}
+
+/// # Implementation
+///
+/// Implementation of the trait for the type, or inherent methods of the type if `trait_` is
+/// `None`.
+pub struct ImplDefinition {
+  pub trait_:           Option<Path>,
+  pub self_ty:          Type,
+  pub associated_types: Vec<AssociatedType>,
+  pub methods:          Vec<Function>,
+  pub span:             Span,
+}
+
+/// Associated type, declared as `type Entry;` in traits and defined as `type Entry = int64;` in
+/// impls.
+#[derive(Clone, Debug)]
+pub struct AssociatedType {
+  pub name: String,
+  pub ty:   Option<Type>,
+  pub span: Span,
+}
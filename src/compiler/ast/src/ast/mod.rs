@@ -3,23 +3,54 @@
 //! Only type definitions for AST.
 //! No parser implementation for this module.
 use std::collections::HashMap;
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::Ordering;
 
 use vsp_span::Span;
 
+use crate::ast::constant::ConstDefinition;
+use crate::ast::enumeration::EnumDefinition;
 use crate::ast::function::Function;
+use crate::ast::interface::ImplDefinition;
+use crate::ast::interface::TraitDefinition;
 use crate::ast::module::Module;
+use crate::ast::module::ModuleDeclaration;
+use crate::ast::module::UseDeclaration;
+use crate::ast::structure::StructDefinition;
 
 pub mod annotation;
+pub mod constant;
+pub mod enumeration;
 pub mod expr;
 pub mod function;
+pub mod generics;
+pub mod interface;
 pub mod modifier;
 pub mod module;
 pub mod naming;
+pub mod pattern;
 pub mod stmt;
+pub mod structure;
 pub mod types;
 
 pub type AST = Box<dyn ASTNode>;
 
+/// Unique ID of the AST node, used as the key of side tables produced by semantic analysis.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct NodeId(u32);
+
+impl NodeId {
+  /// Allocate a new unique node ID.
+  pub fn next() -> Self {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+    Self(COUNTER.fetch_add(1, Ordering::Relaxed))
+  }
+
+  pub fn as_u32(&self) -> u32 {
+    self.0
+  }
+}
+
 /// # AST node
 ///
 /// AST node is any type of abstract syntax tree node.
@@ -74,12 +105,26 @@ pub trait DeclNode: ASTNode {
 ///
 /// It is also the root of AST (abstract syntax tree) which all the items in a single source file
 /// are mounted at.
+///
+/// The compilation unit of the entry file is the root module of the package, and `modules` holds
+/// all other modules of the package keyed by their paths, such as `core::iter`. Other source files
+/// in the directory of the entry file are held by the module keyed by the empty path.
 pub struct CompilationUnit {
   pub meta:      FsMeta,
   pub span:      Span,
   pub shebang:   Option<String>,
+  /// Declaration of the module, only in `module.vsp`.
+  pub module:    Option<ModuleDeclaration>,
+  pub uses:      Vec<UseDeclaration>,
   pub modules:   HashMap<String, Module>,
   pub functions: HashMap<String, Function>,
+  pub structs:   HashMap<String, StructDefinition>,
+  pub enums:     HashMap<String, EnumDefinition>,
+  pub traits:    HashMap<String, TraitDefinition>,
+  /// Constants and static items.
+  pub consts:    HashMap<String, ConstDefinition>,
+  /// Implementations in the order of appearance.
+  pub impls:     Vec<ImplDefinition>,
 }
 
 impl CompilationUnit {
@@ -90,11 +135,35 @@ impl CompilationUnit {
       },
       span:      Span::default(),
       shebang:   None,
+      module:    None,
+      uses:      vec![],
       modules:   HashMap::new(),
       functions: HashMap::new(),
+      structs:   HashMap::new(),
+      enums:     HashMap::new(),
+      traits:    HashMap::new(),
+      consts:    HashMap::new(),
+      impls:     vec![],
     }
   }
 
+  pub fn filename(&self) -> &str {
+    self.meta.filename.as_str()
+  }
+
+  pub fn set_filename(&mut self, filename: impl Into<String>) {
+    self.meta.filename = filename.into();
+  }
+
+  /// True if any function, struct, enum, trait, constant or static item is defined with the name.
+  pub fn has_item(&self, name: &str) -> bool {
+    self.functions.contains_key(name)
+      || self.structs.contains_key(name)
+      || self.enums.contains_key(name)
+      || self.traits.contains_key(name)
+      || self.consts.contains_key(name)
+  }
+
   pub fn add_function(&mut self, function: Function) {
     self.functions.insert(function.name.clone(), function);
   }
@@ -1,5 +1,5 @@
 /// Definite of constancy referring to the preserved word `const`.
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Constancy {
   Constant,
   None,
@@ -14,9 +14,42 @@ impl Constancy {
   }
 }
 
+/// Definite of asynchrony referring to the preserved word `async`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Asyncness {
+  Async,
+  None,
+}
+
+impl Asyncness {
+  pub fn is_async(&self) -> bool {
+    match self {
+      Asyncness::Async => true,
+      Asyncness::None => false,
+    }
+  }
+}
+
+/// Definite of safety referring to the preserved word `unsafe`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Unsafety {
+  Unsafe,
+  None,
+}
+
+impl Unsafety {
+  pub fn is_unsafe(&self) -> bool {
+    match self {
+      Unsafety::Unsafe => true,
+      Unsafety::None => false,
+    }
+  }
+}
+
 /// Accessibility.
 /// - `Public`
 /// - `Private`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Accessibility {
   Public,
   Private,
@@ -1,16 +1,110 @@
 //! Module system
 //!
+//! A package is a directory tree of source files. The entry file, `lib.vsp` for libraries, is the
+//! root module of the package, and each subdirectory is a module made of all source files in it.
+//! A `module.vsp` in the directory declares the module itself, such as its visibility.
 //!
 //! ```plaintext
+//! stdlib
+//! ├── lib.vsp             # root module
+//! ├── module.vsp          # `public module = {}`
+//! ├── core                # module `core`
+//! │   ├── Optional.vsp
+//! │   └── iter            # module `core::iter`
+//! │       └── Iterator.vsp
+//! └── std                 # module `std`
+//!     └── String.vsp
 //! ```
-
+//!
+//! Items of other modules are imported by `use` declarations with paths from the package root.
+//!
+//! ```vsp
+//! use core::iter::Iterator;
+//! use std::collect::*;
+//! use std::String as Str;
+//! ```
+use core::fmt::Display;
+use core::fmt::Formatter;
 use std::borrow::Cow;
 
-///
+use vsp_span::Span;
+
+use crate::ast::modifier::Accessibility;
+use crate::ast::CompilationUnit;
+
+/// Path of modules or items separated by `::`, such as `core::iter::Iterator`.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Path {
   segments: Vec<Cow<'static, str>>,
 }
 
+impl Path {
+  /// Empty path, referring to the root module of the package.
+  pub fn root() -> Self {
+    Self::default()
+  }
+
+  pub fn segments(&self) -> impl Iterator<Item = &str> {
+    self.segments.iter().map(|s| s.as_ref())
+  }
+
+  pub fn len(&self) -> usize {
+    self.segments.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.segments.is_empty()
+  }
+
+  /// The last segment of the path.
+  pub fn name(&self) -> Option<&str> {
+    self.segments.last().map(|s| s.as_ref())
+  }
+
+  /// The path without the last segment.
+  pub fn parent(&self) -> Option<Path> {
+    if self.segments.is_empty() {
+      return None;
+    }
+    Some(Self {
+      segments: self.segments[..self.segments.len() - 1].to_vec(),
+    })
+  }
+
+  /// Create a new path by appending the segment.
+  pub fn join(&self, segment: impl Into<String>) -> Path {
+    let mut path = self.clone();
+    path.push(segment);
+    path
+  }
+
+  pub fn push(&mut self, segment: impl Into<String>) {
+    self.segments.push(Cow::Owned(segment.into()));
+  }
+
+  /// True if the path is equal to or nested in the other path.
+  pub fn starts_with(&self, other: &Path) -> bool {
+    self.segments.starts_with(&other.segments)
+  }
+}
+
+impl From<&str> for Path {
+  fn from(path: &str) -> Self {
+    let mut result = Self::root();
+    path
+      .split("::")
+      .filter(|segment| !segment.is_empty())
+      .for_each(|segment| result.push(segment));
+    result
+  }
+}
+
+impl Display for Path {
+  fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+    write!(f, "{}", self.segments.join("::"))
+  }
+}
+
 /// # Package
 pub struct Package {
   pub name:   String,
@@ -18,4 +112,86 @@ pub struct Package {
 }
 
 /// # Module
-pub struct Module {}
+///
+/// Module is made of the source files in the same directory.
+pub struct Module {
+  pub path:       Path,
+  pub visibility: Accessibility,
+  pub units:      Vec<CompilationUnit>,
+}
+
+impl Module {
+  pub fn new(path: Path) -> Self {
+    Self {
+      path,
+      visibility: Accessibility::Public,
+      units: vec![],
+    }
+  }
+}
+
+/// Declaration of the module written in `module.vsp`. Modules without the declaration are public.
+///
+/// ```vsp
+/// public module = {
+///   use self::sorted::*;
+/// }
+/// ```
+///
+/// Items declared in the braces belong to the module, like those in other files of the module.
+#[derive(Clone, Debug)]
+pub struct ModuleDeclaration {
+  pub visibility: Accessibility,
+  pub span:       Span,
+}
+
+/// Import items of other modules into the scope.
+#[derive(Clone, Debug)]
+pub struct UseDeclaration {
+  /// Path of the imported item, or of the module for glob imports.
+  pub path:       Path,
+  pub kind:       UseKind,
+  /// Public imports are re-exported, so that other modules could import them from this module.
+  pub visibility: Accessibility,
+  pub span:       Span,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum UseKind {
+  /// `use a::b;` or `use a::b as c;`
+  Single(Option<String>),
+  /// `use a::*;`
+  Glob,
+}
+
+impl UseDeclaration {
+  /// Name bound in the scope for single imports, which is the alias if any.
+  pub fn binding(&self) -> Option<&str> {
+    match &self.kind {
+      UseKind::Single(Some(alias)) => Some(alias.as_str()),
+      UseKind::Single(None) => self.path.name(),
+      UseKind::Glob => None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_path() {
+    let path = Path::from("core::iter::Iterator");
+    assert_eq!(path.len(), 3);
+    assert_eq!(path.name(), Some("Iterator"));
+    assert_eq!(path.parent(), Some(Path::from("core::iter")));
+    assert!(path.starts_with(&Path::from("core")));
+    assert!(path.starts_with(&Path::root()));
+    assert!(!Path::from("core").starts_with(&path));
+    assert_eq!(
+      Path::root().join("std").join("String").to_string(),
+      "std::String"
+    );
+    assert_eq!(Path::root().parent(), None);
+  }
+}
@@ -0,0 +1,53 @@
+use vsp_span::Span;
+
+use crate::ast::expr::Expression;
+use crate::ast::module::Path;
+use crate::ast::NodeId;
+
+/// # Match arm
+///
+/// ```vsp
+/// Optional::Value(value) => value + 1,
+/// Optional::None => { return 0; }
+/// ```
+#[derive(Clone, Debug)]
+pub struct MatchArm {
+  pub pattern: Pattern,
+  /// Expression evaluated if the pattern matches, which is a block as
+  /// [`ExpressionKind::Block`](crate::ast::expr::ExpressionKind::Block) if written in braces.
+  pub body:    Expression,
+  pub span:    Span,
+}
+
+/// Pattern which the value is tested against, binding the parts of the value to local variables.
+#[derive(Clone, Debug)]
+pub struct Pattern {
+  pub id:   NodeId,
+  pub kind: PatternKind,
+  pub span: Span,
+}
+
+impl Pattern {
+  pub fn new(kind: PatternKind, span: Span) -> Self {
+    Self {
+      id: NodeId::next(),
+      kind,
+      span,
+    }
+  }
+}
+
+#[derive(Clone, Debug)]
+pub enum PatternKind {
+  /// `_`, which matches anything.
+  Wildcard,
+  /// Name of the local variable bound to the value, which matches anything.
+  Binding(String),
+  /// Integer literal, such as `-1`.
+  Integer(i64),
+  /// Boolean literal `true` or `false`.
+  Boolean(bool),
+  /// Variant of the enum with the patterns of its fields, such as `Optional::Value(x)`, or without
+  /// fields as `Optional::None`.
+  Variant(Path, Vec<Pattern>),
+}
@@ -1,7 +1,9 @@
 use vsp_span::Span;
 
 use crate::ast::expr::Expression;
+use crate::ast::types::Type;
 use crate::ast::ASTNode;
+use crate::ast::NodeId;
 use crate::ast::StmtNode;
 
 /// # Statement
@@ -9,23 +11,34 @@ use crate::ast::StmtNode;
 pub enum Statement {
   /// No operations: Just a single `;`
   NoOp,
-  // Expression(Box<Expression>),
-  // Assignment(String, Expression),
+  /// Expression followed by a `;`.
+  Expression(Expression),
 
   //================================================================//
   // Procedure control flow
   //================================================================//
   If(IfStatement),
   While(WhileStatement),
+  /// Infinite loop by `loop`, which is only left by `break` or `return`.
+  Loop(LoopStatement),
+  /// `for i in start..end`, iterating over the integers from `start` up to `end` exclusively, or
+  /// `for x in iterator` over the entries of the iterator.
+  For(ForStatement),
+  /// `break` out of the innermost loop, or the labelled one such as `break 'outer;`.
+  Break(JumpStatement),
+  /// `continue` with the next iteration of the innermost loop, or the labelled one.
+  Continue(JumpStatement),
 
   // Declarations
-  // VariableDeclaration(String, Option<Expression>),
-  // FunctionDeclaration(String, Option<Expression>),
+  /// Local variable declaration by `let` or `var`.
+  VariableDeclaration(VariableDeclaration),
 
   // Blocks
-  Return(Option<Expression>),
+  Return(ReturnStatement),
   /// Statement block consisting of statements
   Block(Box<StatementBlock>),
+  /// `unsafe` block, in which raw pointers could be dereferenced and `unsafe func`s called.
+  Unsafe(Box<StatementBlock>),
 }
 
 impl ASTNode for Statement {}
@@ -39,12 +52,89 @@ impl NoOpStatement {}
 /// Statement represents a if / else statement.
 #[derive(Clone, Debug)]
 pub struct IfStatement {
-  pub span: Span,
+  pub condition: Expression,
+  pub then:      Box<StatementBlock>,
+  pub otherwise: Option<Box<Statement>>,
+  pub span:      Span,
 }
 
 /// Statement represents a while statement.
 #[derive(Clone, Debug)]
-pub struct WhileStatement {}
+pub struct WhileStatement {
+  pub label:     Option<String>,
+  pub condition: Expression,
+  pub body:      Box<StatementBlock>,
+  pub span:      Span,
+}
+
+/// Statement represents a loop statement.
+#[derive(Clone, Debug)]
+pub struct LoopStatement {
+  pub label: Option<String>,
+  pub body:  Box<StatementBlock>,
+  pub span:  Span,
+}
+
+/// Statement represents a for statement over the integer range or the iterator.
+///
+/// ```vsp
+/// 'outer: for i in 0..n {
+///   sum = sum + i;
+/// }
+/// ```
+///
+/// The binding `i` is immutable, and its type is the integer type of the bounds, or the `Entry`
+/// of the iterator.
+#[derive(Clone, Debug)]
+pub struct ForStatement {
+  pub id:       NodeId,
+  pub label:    Option<String>,
+  pub name:     String,
+  pub iterable: ForIterable,
+  pub body:     Box<StatementBlock>,
+  pub span:     Span,
+}
+
+/// What the for statement iterates over.
+#[derive(Clone, Debug)]
+pub enum ForIterable {
+  /// Integers from the start up to the end exclusively, as `start..end`.
+  Range(Expression, Expression),
+  /// Entries of the value whose type implements the lang item `Iterator`.
+  Iterator(Expression),
+}
+
+/// Statement represents `break` or `continue` with an optional label of the loop, without `'`.
+#[derive(Clone, Debug)]
+pub struct JumpStatement {
+  pub label: Option<String>,
+  pub span:  Span,
+}
+
+/// Statement represents a return statement with an optional value.
+#[derive(Clone, Debug)]
+pub struct ReturnStatement {
+  pub value: Option<Expression>,
+  pub span:  Span,
+}
+
+/// Local variable declaration.
+///
+/// ```vsp
+/// let answer: int32 = 42;
+/// var counter = 0;
+/// ```
+///
+/// Bindings declared by `let` are immutable, while those by `var` are mutable.
+#[derive(Clone, Debug)]
+pub struct VariableDeclaration {
+  pub id:      NodeId,
+  pub name:    String,
+  pub mutable: bool,
+  pub ty:      Option<Type>,
+  pub init:    Option<Expression>,
+  pub span:    Span,
+}
 
 /// Statement block contains list of statements.
 #[derive(Clone, Debug)]
@@ -61,6 +151,10 @@ impl StatementBlock {
     Self { stmts }
   }
 
+  pub fn stmts(&self) -> &[Statement] {
+    &self.stmts
+  }
+
   pub fn add_stmt(&mut self, stmt: Statement) -> &Self {
     self.stmts.push(stmt);
     self
@@ -73,6 +167,14 @@ impl StatementBlock {
     stmts.into_iter().for_each(|s| self.stmts.push(s));
     self
   }
+
+  /// Whether the block never completes, as it ends with `return`, `break` or `continue`.
+  pub fn diverges(&self) -> bool {
+    matches!(
+      self.stmts.last(),
+      Some(Statement::Return(_) | Statement::Break(_) | Statement::Continue(_))
+    )
+  }
 }
 
 impl Default for StatementBlock {
@@ -0,0 +1,33 @@
+use vsp_span::Span;
+
+use crate::ast::annotation::Annotation;
+use crate::ast::generics::Generics;
+use crate::ast::modifier::Accessibility;
+use crate::ast::types::Type;
+
+/// # Struct
+///
+/// ```vsp
+/// public struct Point {
+///   public x: int64,
+///   public y: int64,
+/// }
+/// ```
+pub struct StructDefinition {
+  pub name:        String,
+  pub visibility:  Accessibility,
+  pub annotations: Option<Vec<Annotation>>,
+  pub generics:    Generics,
+  pub fields:      Vec<Field>,
+  pub span:        Span,
+}
+
+/// Field of the struct, as `name: type`. Private fields are only accessible in the module
+/// defining the struct and its descendants.
+#[derive(Clone, Debug)]
+pub struct Field {
+  pub name:       String,
+  pub ty:         Type,
+  pub visibility: Accessibility,
+  pub span:       Span,
+}
@@ -1,20 +1,42 @@
 /// Type system implementation for AST.
+use core::fmt::Display;
+use core::fmt::Formatter;
 use core::hash::Hash;
 use core::hash::Hasher;
 
+use vsp_span::Span;
+
+use crate::ast::module::Path;
+
 /// # Type System of LLVM Wrapper
 /// There are types that are implemented in the type system of LLVM wrapper.
 /// - [Primitive Type](https://llvm.org/2.0/docs/LangRef.html#t_primitive)
 /// - [Array Type](https://releases.llvm.org/2.0/docs/LangRef.html#t_array)
 /// - [Struct Type](https://releases.llvm.org/2.0/docs/LangRef.html#t_struct)
 /// - [Function Type](https://releases.llvm.org/2.0/docs/LangRef.html#t_function)
-#[derive(Clone, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Type {
   Primitive(PrimitiveType),
   Array(Box<Type>, usize),
+  /// Array whose size is given by the constant, such as `int64[SIZE]`. It is replaced by
+  /// [`Type::Array`] once the constant is evaluated.
+  ConstArray(Box<Type>, Path),
   Struct(StructType),
   Function(FunctionType),
-  Pointer(StructType),
+  /// Raw pointer to the value of the type, such as `*int64`.
+  Pointer(Box<Type>),
+  /// Type defined by `struct` with its generic arguments, such as `Point` or `Pair<int64, bool>`,
+  /// or a generic parameter. Once resolved, the path is absolute from the package root.
+  Named(Path, Vec<Type>),
+  /// Trait object, such as `dyn Iterator`, whose methods are dispatched dynamically.
+  Dyn(Path),
+  /// `Self` in traits and impls.
+  SelfType,
+  /// Associated type, such as `Self::Entry`.
+  Associated(Box<Type>, String),
+  /// State machine returned by calling the `async func` at the path, with the generic arguments
+  /// of the function. It could not be written in the source codes.
+  Coroutine(Path, Vec<Type>),
 }
 
 impl Type {
@@ -23,6 +45,11 @@ impl Type {
     Self::Primitive(PrimitiveType::Unit)
   }
 
+  #[inline]
+  pub fn bool() -> Self {
+    Self::Primitive(PrimitiveType::Bool)
+  }
+
   #[inline]
   pub fn int8() -> Self {
     Self::Primitive(PrimitiveType::Int8)
@@ -43,6 +70,26 @@ impl Type {
     Self::Primitive(PrimitiveType::Int64)
   }
 
+  #[inline]
+  pub fn uint8() -> Self {
+    Self::Primitive(PrimitiveType::Uint8)
+  }
+
+  #[inline]
+  pub fn uint16() -> Self {
+    Self::Primitive(PrimitiveType::Uint16)
+  }
+
+  #[inline]
+  pub fn uint32() -> Self {
+    Self::Primitive(PrimitiveType::Uint32)
+  }
+
+  #[inline]
+  pub fn uint64() -> Self {
+    Self::Primitive(PrimitiveType::Uint64)
+  }
+
   #[inline]
   pub fn float64() -> Self {
     Self::Primitive(PrimitiveType::Float64)
@@ -53,10 +100,99 @@ impl Type {
     Self::Primitive(PrimitiveType::Double64)
   }
 
+  #[inline]
+  pub fn str() -> Self {
+    Self::Primitive(PrimitiveType::Str)
+  }
+
   #[inline]
   pub fn array(ty: Type, size: usize) -> Self {
     Self::Array(Box::new(ty), size)
   }
+
+  #[inline]
+  pub fn pointer(pointee: Type) -> Self {
+    Self::Pointer(Box::new(pointee))
+  }
+
+  /// Named type without generic arguments.
+  #[inline]
+  pub fn named(path: Path) -> Self {
+    Self::Named(path, vec![])
+  }
+
+  pub fn is_unit(&self) -> bool {
+    matches!(self, Type::Primitive(PrimitiveType::Unit))
+  }
+
+  /// True if `Self` occurs in the type, including associated types of `Self`.
+  pub fn mentions_self(&self) -> bool {
+    match self {
+      Type::SelfType => true,
+      Type::Associated(ty, _) => ty.mentions_self(),
+      Type::Array(element, _) | Type::ConstArray(element, _) | Type::Pointer(element) => {
+        element.mentions_self()
+      }
+      Type::Named(_, args) | Type::Coroutine(_, args) => args.iter().any(Type::mentions_self),
+      Type::Function(function) => {
+        function.params.iter().any(Type::mentions_self) || function.ret.mentions_self()
+      }
+      _ => false,
+    }
+  }
+}
+
+impl Display for Type {
+  fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+    match self {
+      Type::Primitive(primitive) => write!(f, "{}", primitive.keyword()),
+      Type::Array(element, size) => write!(f, "{}[{}]", element, size),
+      Type::ConstArray(element, size) => write!(f, "{}[{}]", element, size),
+      Type::Struct(_) => write!(f, "struct"),
+      Type::Function(function) => {
+        write!(f, "func(")?;
+        for (i, param) in function.params.iter().enumerate() {
+          if i > 0 {
+            write!(f, ", ")?;
+          }
+          write!(f, "{}", param)?;
+        }
+        write!(f, ") -> {}", function.ret)
+      }
+      Type::Pointer(pointee) => write!(f, "*{}", pointee),
+      Type::Named(path, args) => {
+        write!(f, "{}", path)?;
+        if !args.is_empty() {
+          write!(f, "<")?;
+          for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+              write!(f, ", ")?;
+            }
+            write!(f, "{}", arg)?;
+          }
+          write!(f, ">")?;
+        }
+        Ok(())
+      }
+      Type::Dyn(path) => write!(f, "dyn {}", path),
+      Type::SelfType => write!(f, "Self"),
+      Type::Associated(ty, name) => write!(f, "{}::{}", ty, name),
+      Type::Coroutine(path, args) => {
+        write!(f, "{{async {}", path)?;
+        if !args.is_empty() {
+          write!(f, "<")?;
+          for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+              write!(f, ", ")?;
+            }
+            write!(f, "{}", arg)?;
+          }
+          write!(f, ">")?;
+        }
+        write!(f, "}}")
+      }
+    }
+  }
 }
 
 impl Hash for Type {
@@ -65,7 +201,7 @@ impl Hash for Type {
   }
 }
 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum PrimitiveType {
   Unit,
   Bool,
@@ -73,9 +209,14 @@ pub enum PrimitiveType {
   Int16,
   Int32,
   Int64,
+  Uint8,
+  Uint16,
+  Uint32,
+  Uint64,
   Float64,
   Double64,
   Char,
+  Str,
 }
 
 impl PrimitiveType {
@@ -87,13 +228,59 @@ impl PrimitiveType {
       PrimitiveType::Int16 => "Int16",
       PrimitiveType::Int32 => "Int32",
       PrimitiveType::Int64 => "Int64",
+      PrimitiveType::Uint8 => "Uint8",
+      PrimitiveType::Uint16 => "Uint16",
+      PrimitiveType::Uint32 => "Uint32",
+      PrimitiveType::Uint64 => "Uint64",
       PrimitiveType::Float64 => "Float64",
       PrimitiveType::Double64 => "Double64",
       PrimitiveType::Char => "Char",
+      PrimitiveType::Str => "Str",
     }
     .to_string()
   }
 
+  /// The keyword of the primitive type written in the source codes.
+  pub fn keyword(&self) -> &'static str {
+    match self {
+      PrimitiveType::Unit => "unit",
+      PrimitiveType::Bool => "bool",
+      PrimitiveType::Int8 => "int8",
+      PrimitiveType::Int16 => "int16",
+      PrimitiveType::Int32 => "int32",
+      PrimitiveType::Int64 => "int64",
+      PrimitiveType::Uint8 => "uint8",
+      PrimitiveType::Uint16 => "uint16",
+      PrimitiveType::Uint32 => "uint32",
+      PrimitiveType::Uint64 => "uint64",
+      PrimitiveType::Float64 => "float64",
+      PrimitiveType::Double64 => "double",
+      PrimitiveType::Char => "char",
+      PrimitiveType::Str => "str",
+    }
+  }
+
+  /// Find the primitive type by the keyword written in the source codes.
+  pub fn from_keyword(keyword: &str) -> Option<PrimitiveType> {
+    match keyword {
+      "unit" => Some(Self::Unit),
+      "bool" => Some(Self::Bool),
+      "int8" => Some(Self::Int8),
+      "int16" => Some(Self::Int16),
+      "int" | "int32" => Some(Self::Int32),
+      "int64" => Some(Self::Int64),
+      "uint8" => Some(Self::Uint8),
+      "uint16" => Some(Self::Uint16),
+      "uint" | "uint32" => Some(Self::Uint32),
+      "uint64" => Some(Self::Uint64),
+      "float64" => Some(Self::Float64),
+      "double" => Some(Self::Double64),
+      "char" => Some(Self::Char),
+      "str" => Some(Self::Str),
+      _ => None,
+    }
+  }
+
   pub fn from_name(name: &str) -> Option<PrimitiveType> {
     match name {
       "Void" => Some(Self::Unit),
@@ -102,9 +289,14 @@ impl PrimitiveType {
       "Int16" => Some(Self::Int16),
       "Int32" => Some(Self::Int32),
       "Int64" => Some(Self::Int64),
+      "Uint8" => Some(Self::Uint8),
+      "Uint16" => Some(Self::Uint16),
+      "Uint32" => Some(Self::Uint32),
+      "Uint64" => Some(Self::Uint64),
       "Float64" => Some(Self::Float64),
       "Double64" => Some(Self::Double64),
       "Char" => Some(Self::Char),
+      "Str" => Some(Self::Str),
       _ => None,
     }
   }
@@ -136,9 +328,98 @@ impl PrimitiveType {
   pub fn is_char(&self) -> bool {
     matches!(self, PrimitiveType::Char)
   }
+
+  pub fn is_integer(&self) -> bool {
+    self.is_signed_integer() || self.is_unsigned_integer()
+  }
+
+  pub fn is_signed_integer(&self) -> bool {
+    matches!(
+      self,
+      PrimitiveType::Int8 | PrimitiveType::Int16 | PrimitiveType::Int32 | PrimitiveType::Int64
+    )
+  }
+
+  pub fn is_unsigned_integer(&self) -> bool {
+    matches!(
+      self,
+      PrimitiveType::Uint8 | PrimitiveType::Uint16 | PrimitiveType::Uint32 | PrimitiveType::Uint64
+    )
+  }
+
+  /// Width in bits of the integer type.
+  pub fn bit_width(&self) -> Option<u32> {
+    match self {
+      PrimitiveType::Int8 | PrimitiveType::Uint8 => Some(8),
+      PrimitiveType::Int16 | PrimitiveType::Uint16 => Some(16),
+      PrimitiveType::Int32 | PrimitiveType::Uint32 => Some(32),
+      PrimitiveType::Int64 | PrimitiveType::Uint64 => Some(64),
+      _ => None,
+    }
+  }
+
+  /// Inclusive range of values the integer type could represent.
+  pub fn integer_range(&self) -> Option<(i128, i128)> {
+    let width = self.bit_width()?;
+    if self.is_signed_integer() {
+      Some((-(1i128 << (width - 1)), (1i128 << (width - 1)) - 1))
+    } else {
+      Some((0, (1i128 << width) - 1))
+    }
+  }
+
+  /// Check that the result of the arithmetic is in the range of the integer type, or return the
+  /// message of the overflow of the operation named by the verb. Other types are not checked.
+  pub fn check_range(&self, value: i128, verb: &str) -> Result<i128, String> {
+    match self.integer_range() {
+      Some((min, max)) if value < min || value > max => {
+        Err(format!("attempt to {} with overflow", verb))
+      }
+      _ => Ok(value),
+    }
+  }
+
+  /// Wrap the integer around into the range of the integer type, as the conversions between
+  /// integers truncate or extend the bits. Other types are not wrapped.
+  pub fn wrap(&self, value: i128) -> i128 {
+    match self.integer_range() {
+      Some((min, max)) => (value - min).rem_euclid(max - min + 1) + min,
+      None => value,
+    }
+  }
+
+  /// Convert the float into the integer type with saturation.
+  pub fn saturate(&self, value: f64) -> i128 {
+    let (min, max) = self.integer_range().unwrap_or((i128::MIN, i128::MAX));
+    (value as i128).clamp(min, max)
+  }
+
+  /// Whether the value of this integer type could be converted implicitly into the target type
+  /// without losing any information:
+  /// - Signed integers widen to the signed integers with more bits.
+  /// - Unsigned integers widen to the unsigned integers with more bits.
+  /// - Unsigned integers widen to the signed integers with more bits.
+  ///
+  /// Other conversions must be written explicitly with `as`.
+  pub fn can_widen_to(&self, target: &PrimitiveType) -> bool {
+    match (self.bit_width(), target.bit_width()) {
+      (Some(from), Some(to)) if from < to => {
+        self.is_unsigned_integer() || target.is_signed_integer()
+      }
+      _ => false,
+    }
+  }
+
+  pub fn is_float(&self) -> bool {
+    matches!(self, PrimitiveType::Float64 | PrimitiveType::Double64)
+  }
+
+  pub fn is_numeric(&self) -> bool {
+    self.is_integer() || self.is_float()
+  }
 }
 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct FunctionType {
   pub params: Vec<Type>,
   pub ret:    Box<Type>,
@@ -159,12 +440,12 @@ impl Default for FunctionType {
   }
 }
 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct StructType {
   pub record: Vec<(u8, Type)>,
 }
 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ArrayType {
   element: Box<Type>,
   size:    usize,
@@ -180,4 +461,43 @@ impl ArrayType {
   }
 }
 
-pub struct Parameter;
+/// Parameter of the function, as `name: type`.
+#[derive(Clone, Debug)]
+pub struct Parameter {
+  pub name: String,
+  pub ty:   Type,
+  pub span: Span,
+}
+
+impl Parameter {
+  pub fn new(name: impl Into<String>, ty: Type, span: Span) -> Self {
+    Self {
+      name: name.into(),
+      ty,
+      span,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_integer_range() {
+    let int8 = PrimitiveType::Int8;
+    assert_eq!(int8.integer_range(), Some((-128, 127)));
+    assert_eq!(int8.check_range(127, "add"), Ok(127));
+    assert_eq!(
+      int8.check_range(128, "add"),
+      Err("attempt to add with overflow".to_string())
+    );
+    assert_eq!(int8.wrap(200), -56);
+    assert_eq!(PrimitiveType::Uint16.wrap(-1), 65535);
+    assert_eq!(PrimitiveType::Int32.saturate(1e10), 2147483647);
+    assert_eq!(PrimitiveType::Uint8.saturate(-1.5), 0);
+    // Values of the types other than the integers are neither checked nor wrapped.
+    assert_eq!(PrimitiveType::Char.check_range(1 << 40, "add"), Ok(1 << 40));
+    assert_eq!(PrimitiveType::Unit.wrap(-1), -1);
+  }
+}
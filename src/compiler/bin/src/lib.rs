@@ -0,0 +1,2 @@
+/// Project issue report URL.
+pub const REPORT_URL: &str = "https://github.com/leryn1122/vsp/issues";
@@ -0,0 +1,89 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use target_lexicon::Triple;
+use vsp_compiler::link::CrateType;
+use vsp_compiler::option::EmitKind;
+use vsp_compiler::option::OptLevel;
+use vsp_compiler::option::TargetOptions;
+use vsp_error::VspError;
+use vsp_error::VspResult;
+use vsp_support::clap_ext::TripleValueParser;
+
+use crate::ops::Entrypoint;
+
+#[derive(Args)]
+pub struct CandidateArgument {
+  /// Input source file
+  #[arg(required = true)]
+  input:           PathBuf,
+  /// Target triple to compile for
+  #[arg(long, value_parser = TripleValueParser::default())]
+  target:          Option<Triple>,
+  /// Optimization level: 0, 1, 2 or 3 for speed, s or z for size
+  #[arg(short = 'O', default_value = "0")]
+  optimization:    OptLevel,
+  /// Comma separated LLVM passes to run instead of those of the optimization level
+  #[arg(long, value_name = "PASSES")]
+  passes:          Option<String>,
+  /// Print the time spent by each optimization pass
+  #[arg(long)]
+  time_passes:     bool,
+  /// Generate the debug information
+  #[arg(short = 'g')]
+  debuginfo:       bool,
+  /// Comma separated kinds of the output files: obj, asm, llvm-bc, llvm-ir, link or bytecode
+  #[arg(long, value_delimiter = ',', default_value = "link")]
+  emit:            Vec<EmitKind>,
+  /// Kind of the linked artifact: bin, staticlib or dylib
+  #[arg(long, default_value = "bin")]
+  crate_type:      CrateType,
+  /// Codegen options: target-cpu, target-feature, relocation-model, code-model, linker,
+  /// linker-flavor, link-arg or link-args
+  #[arg(short = 'C', value_name = "OPT=VALUE")]
+  codegen:         Vec<String>,
+  /// Native library to link against
+  #[arg(short = 'l', value_name = "LIB")]
+  libraries:       Vec<String>,
+  /// Directory to search for the native libraries
+  #[arg(short = 'L', value_name = "PATH")]
+  search_paths:    Vec<String>,
+  /// Print the command of the linker before it runs
+  #[arg(long)]
+  print_link_args: bool,
+  /// Root of the vsp installation with the runtime and stdlib objects
+  #[arg(long)]
+  sysroot:         Option<PathBuf>,
+  /// Directory of the artifacts
+  #[arg(long, default_value = "target")]
+  target_dir:      PathBuf,
+  /// Enable verbose mode
+  #[arg(short, long)]
+  verbose:         bool,
+}
+
+impl Entrypoint for CandidateArgument {
+  fn entrypoint(&mut self) -> VspResult<()> {
+    let mut target_options = TargetOptions::default();
+    if let Some(target) = self.target.to_owned() {
+      target_options.set_target_triple(target);
+    }
+    target_options.set_optimization(self.optimization);
+    target_options.set_passes(self.passes.to_owned());
+    target_options.set_time_passes(self.time_passes);
+    target_options.set_debuginfo(self.debuginfo);
+    target_options.set_emit(self.emit.to_owned());
+    target_options.set_target_dir(self.target_dir.to_owned());
+    target_options.set_library(self.crate_type != CrateType::Executable);
+    target_options.set_crate_type(self.crate_type);
+    target_options.set_print_link_args(self.print_link_args);
+    target_options.set_sysroot(self.sysroot.to_owned());
+    let search_paths = self.search_paths.iter().map(|path| format!("-L{}", path));
+    let libraries = self.libraries.iter().map(|library| format!("-l{}", library));
+    target_options.set_link_args(search_paths.chain(libraries).collect());
+    for option in &self.codegen {
+      target_options.set_codegen_option(option).map_err(VspError::new)?;
+    }
+    vsp_compiler::start_compile(&self.input, target_options)
+  }
+}
@@ -0,0 +1,19 @@
+use clap::Args;
+use vsp_cli::repl::do_run_repl_with;
+use vsp_compiler::session::Session;
+use vsp_error::VspResult;
+
+use crate::ops::Entrypoint;
+
+#[derive(Args)]
+pub struct CandidateArgument {}
+
+impl Entrypoint for CandidateArgument {
+  fn entrypoint(&mut self) -> VspResult<()> {
+    // Lines other than the applets are interpreted in the session.
+    let mut session = Session::new();
+    do_run_repl_with(Box::new(move |line| {
+      session.eval(line).map_err(|error| error.message())
+    }))
+  }
+}
@@ -0,0 +1,95 @@
+use std::path::Path;
+use std::path::PathBuf;
+
+use clap::Args;
+use vsp_compiler::option::OptLevel;
+use vsp_compiler::option::TargetOptions;
+use vsp_compiler::source::loader::BYTECODE_EXTENSION;
+use vsp_compiler::source::loader::LIBRARY_ENTRY;
+use vsp_compiler::source::read_shebang;
+use vsp_error::VspError;
+use vsp_error::VspResult;
+
+use crate::ops::Entrypoint;
+
+#[derive(Args)]
+pub struct CandidateArgument {
+  /// Input source file, bytecode module, or directory of the project
+  #[arg(default_value = ".")]
+  input:        PathBuf,
+  /// Optimization level: 0, 1, 2 or 3 for speed, s or z for size
+  #[arg(short = 'O', default_value = "0")]
+  optimization: OptLevel,
+  /// Native library to load
+  #[arg(short = 'l', value_name = "LIB")]
+  libraries:    Vec<String>,
+  /// Directory to search for the native libraries
+  #[arg(short = 'L', value_name = "PATH")]
+  search_paths: Vec<String>,
+  /// Run by the interpreter instead of the JIT, which ignores the libraries
+  #[arg(long, conflicts_with = "bytecode")]
+  interpret:    bool,
+  /// Run by the VM after compiling into bytecode, which ignores the libraries. Scripts with a
+  /// shebang run by the VM unless interpreted
+  #[arg(long)]
+  bytecode:     bool,
+  /// Arguments passed to the program, which follow the input as those of the scripts do
+  #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+  args:         Vec<String>,
+}
+
+impl Entrypoint for CandidateArgument {
+  fn entrypoint(&mut self) -> VspResult<()> {
+    let mut target_options = TargetOptions::default();
+    target_options.set_optimization(self.optimization);
+    let search_paths = self.search_paths.iter().map(|path| format!("-L{}", path));
+    let libraries = self.libraries.iter().map(|library| format!("-l{}", library));
+    target_options.set_link_args(search_paths.chain(libraries).collect());
+
+    // A project is run from its library entry, and named after its directory.
+    let input = self.input.canonicalize().map_err(|error| {
+      VspError::new(format!(
+        "could not find `{}`: {}",
+        self.input.display(),
+        error
+      ))
+    })?;
+    let file = if input.is_dir() {
+      let entry = input.join(LIBRARY_ENTRY);
+      if !entry.is_file() {
+        return Err(VspError::new(format!(
+          "could not find `{}` in the project `{}`",
+          LIBRARY_ENTRY,
+          self.input.display()
+        )));
+      }
+      entry
+    } else {
+      input.clone()
+    };
+    let name = input.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+    let mut args = vec![name.into_owned()];
+    args.extend(self.args.iter().cloned());
+    let code = if file.extension().map_or(false, |ext| ext == BYTECODE_EXTENSION) {
+      vsp_compiler::bytecode::run_module_file(&file, &args)?
+    } else if self.interpret {
+      vsp_compiler::start_interpret(&file, target_options, &args)?
+    } else if self.bytecode || read_shebang(&file).is_some() {
+      vsp_compiler::start_bytecode(&file, target_options, &args)?
+    } else {
+      start_jit(&file, target_options, &args)?
+    };
+    std::process::exit(code)
+  }
+}
+
+#[cfg(feature = "llvm")]
+fn start_jit(file: &Path, target_options: TargetOptions, args: &[String]) -> VspResult<i32> {
+  vsp_compiler::start_run(file, target_options, args)
+}
+
+/// Without LLVM, programs are only run by the interpreter.
+#[cfg(not(feature = "llvm"))]
+fn start_jit(file: &Path, target_options: TargetOptions, args: &[String]) -> VspResult<i32> {
+  vsp_compiler::start_interpret(file, target_options, args)
+}
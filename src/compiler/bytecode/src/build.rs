@@ -0,0 +1,668 @@
+//! Compilation of the MIR into the bytecode module.
+//!
+//! Every instance of the functions and the methods, collected as the code generation does, is
+//! compiled into a function of the module, where the generic parameters are substituted by the
+//! type arguments of the instance. Each basic block is compiled in order, so that the blocks
+//! falling through need no jumps. Places are read by value unless a raw pointer is dereferenced,
+//! and they are written through their addresses once projected. Static items are compiled into
+//! the constants of their values, which are evaluated at compile time.
+//!
+//! Trait objects are pairs of the value and its vtable, which is the struct of the functions of the
+//! methods in the order of the trait, and the dynamic calls pass the value to the function taken
+//! from the vtable. Arrays hold their elements before they are initialized, so that the elements
+//! are written through the pointers to them.
+use std::collections::HashMap;
+
+use vsp_ast::ast::expr::BinaryOp;
+use vsp_ast::ast::expr::UnaryOp;
+use vsp_ast::ast::module::Path;
+use vsp_ast::ast::types::PrimitiveType;
+use vsp_ast::ast::types::Type;
+use vsp_error::VspError;
+use vsp_error::VspResult;
+use vsp_mir::mir::Body;
+use vsp_mir::mir::Constant as MirConstant;
+use vsp_mir::mir::ConstantKind;
+use vsp_mir::mir::Operand;
+use vsp_mir::mir::Place;
+use vsp_mir::mir::Program;
+use vsp_mir::mir::ProjectionElem;
+use vsp_mir::mir::Rvalue;
+use vsp_mir::mir::StatementKind;
+use vsp_mir::mir::TerminatorKind;
+use vsp_sema::check::TypeckResults;
+use vsp_sema::items::ItemTable;
+use vsp_sema::items::MethodPath;
+use vsp_sema::mono::substitute;
+use vsp_sema::mono::Instance;
+use vsp_sema::mono::MonoItem;
+use vsp_sema::mono::MonoItems;
+use vsp_sema::resolve::DefPath;
+use vsp_span::Span;
+
+use crate::module::BinOp;
+use crate::module::Constant;
+use crate::module::FuncIndex;
+use crate::module::Function;
+use crate::module::Instr;
+use crate::module::Module;
+use crate::module::Scalar;
+
+/// Name of the entry of the programs.
+pub const MAIN: &str = "main";
+
+/// Compile the program checked without errors into the module, whose integer arithmetic traps on
+/// overflow or wraps around.
+pub fn build_module(
+  program: &Program,
+  results: &TypeckResults,
+  overflow_checks: bool,
+) -> VspResult<Module> {
+  let instances = MonoItems::collect(results)
+    .instances()
+    .iter()
+    .filter(|instance| program.body(&instance.item).is_some())
+    .cloned()
+    .collect::<Vec<_>>();
+  let mut builder = ModuleBuilder {
+    program,
+    items: results.items(),
+    module: Module {
+      overflow_checks,
+      ..Module::default()
+    },
+    functions: instances
+      .iter()
+      .enumerate()
+      .map(|(index, i)| (i.clone(), index as u32))
+      .collect(),
+    natives: HashMap::new(),
+  };
+  for instance in &instances {
+    let function = builder.build_function(instance)?;
+    builder.module.functions.push(function);
+  }
+
+  let main = Instance {
+    item: MonoItem::Function(DefPath::new(Path::root(), MAIN)),
+    args: vec![],
+  };
+  if let Some(index) = builder.functions.get(&main) {
+    check_main(program.body(&main.item).unwrap())?;
+    builder.module.entry = Some(*index);
+  }
+  Ok(builder.module)
+}
+
+/// Check that `main` either takes no parameters, or `argc: int32` and `argv: *str`, and returns
+/// nothing or an integer, as the interpreter does.
+fn check_main(body: &Body) -> VspResult<()> {
+  match body.return_type() {
+    Type::Primitive(PrimitiveType::Unit | PrimitiveType::Bool) => {}
+    Type::Primitive(primitive) if primitive.integer_range().is_some() => {}
+    _ => return VspError::new("`main` must return nothing or an integer").to_err(),
+  }
+  let params = body.args().map(|arg| &body.local(arg).ty).collect::<Vec<_>>();
+  let argv = Type::Pointer(Box::new(Type::str()));
+  match params.as_slice() {
+    [] => Ok(()),
+    [argc, pointer] if **argc == Type::int32() && **pointer == argv => Ok(()),
+    _ => {
+      VspError::new("`main` must take no parameters, or `argc: int32` and `argv: *str`").to_err()
+    }
+  }
+}
+
+struct ModuleBuilder<'a> {
+  program:   &'a Program,
+  items:     &'a ItemTable,
+  module:    Module,
+  /// Index of the function of each instance.
+  functions: HashMap<Instance, FuncIndex>,
+  /// Index of the native of each symbol.
+  natives:   HashMap<String, u32>,
+}
+
+impl<'a> ModuleBuilder<'a> {
+  fn build_function(&mut self, instance: &Instance) -> VspResult<Function> {
+    let body = self.program.body(&instance.item).expect("the instance has a body");
+    let name = self.debug_name(instance);
+    let mut function = Function {
+      name: self.module.add_constant(Constant::Str(name.clone())),
+      file: body
+        .file
+        .as_ref()
+        .map(|file| self.module.add_constant(Constant::Str(file.to_owned()))),
+      params: u16::try_from(body.arg_count).map_err(|_| too_many(&name, "parameters"))?,
+      locals: u16::try_from(body.locals.len()).map_err(|_| too_many(&name, "locals"))?,
+      ..Function::default()
+    };
+    let substitution = instance.substitution(self.items);
+    let mut builder = FunctionBuilder {
+      cx: self,
+      name,
+      body,
+      substitution,
+      code: vec![],
+    };
+    for (index, local) in body.locals.iter().enumerate() {
+      let is_arg = (1..=body.arg_count).contains(&index);
+      if !is_arg && builder.uninit(&builder.ty(&local.ty))? {
+        builder.emit(Instr::StoreLocal(index as u16));
+      }
+    }
+    let mut starts = vec![];
+    for (index, block) in body.blocks.iter().enumerate() {
+      starts.push(builder.code.len() as u32);
+      for statement in &block.statements {
+        builder.locate(&mut function, &statement.span);
+        match &statement.kind {
+          StatementKind::Assign(place, rvalue) => {
+            builder.assign(place, |builder| builder.rvalue(rvalue))?;
+          }
+          StatementKind::StorageLive(_) => {}
+        }
+      }
+      builder.locate(&mut function, &block.terminator().span);
+      builder.terminator(&block.terminator().kind, index + 1)?;
+    }
+    // Jumps are built to the indices of the blocks, which are resolved once all are built.
+    function.code = builder.code;
+    for instr in &mut function.code {
+      if let Instr::Jump(target) | Instr::JumpIfNot(target) = instr {
+        *target = starts[*target as usize];
+      }
+    }
+    Ok(function)
+  }
+
+  /// Name of the function of the instance, such as `main` or `Point::norm`.
+  fn debug_name(&self, instance: &Instance) -> String {
+    match &instance.item {
+      MonoItem::Function(def) | MonoItem::Const(def) | MonoItem::Static(def) => def.name.clone(),
+      MonoItem::Coroutine(def) => format!("{}::poll", def.name),
+      MonoItem::Method(path) => match self.items.impl_info(path.impl_id).self_ty.to_type() {
+        Some(ty) => format!("{}::{}", ty, path.name),
+        None => path.name.clone(),
+      },
+    }
+  }
+
+  /// Instance of the callee, where the type arguments and the methods of the trait bounds are
+  /// resolved by the substitution of the caller.
+  fn resolve(&self, callee: &ConstantKind, substitution: &HashMap<String, Type>) -> Instance {
+    match callee {
+      ConstantKind::Function(def, args) => Instance {
+        item: MonoItem::Function(def.clone()),
+        args: args.iter().map(|arg| substitute(arg, substitution)).collect(),
+      },
+      ConstantKind::Method(path) => Instance {
+        item: MonoItem::Method(path.clone()),
+        args: vec![],
+      },
+      ConstantKind::BoundMethod {
+        trait_,
+        name,
+        self_ty,
+      } => match substitute(self_ty, substitution) {
+        Type::Coroutine(path, args) => Instance {
+          item: MonoItem::Coroutine(DefPath::new(
+            path.parent().unwrap_or_default(),
+            path.name().unwrap_or_default(),
+          )),
+          args,
+        },
+        self_ty => self.method_instance(trait_, &self_ty, name),
+      },
+      kind => unreachable!("unexpected callee: {:?}", kind),
+    }
+  }
+
+  /// Instance of the method of the implementation of the trait for the type.
+  fn method_instance(&self, trait_: &DefPath, self_ty: &Type, name: &str) -> Instance {
+    let implementation =
+      self.items.impls.iter().find(|i| {
+        i.trait_.as_ref() == Some(trait_) && i.self_ty.to_type().as_ref() == Some(self_ty)
+      });
+    let implementation = implementation.expect("bounds are checked by the type checker");
+    Instance {
+      item: MonoItem::Method(MethodPath {
+        impl_id: implementation.id,
+        name:    name.to_owned(),
+      }),
+      args: vec![],
+    }
+  }
+
+  fn function(&self, instance: &Instance) -> VspResult<FuncIndex> {
+    match self.functions.get(instance) {
+      Some(index) => Ok(*index),
+      None => VspError::new(format!(
+        "`{}` is not instantiated",
+        self.debug_name(instance)
+      ))
+      .to_err(),
+    }
+  }
+
+  /// Index of the native bound to the symbol of the foreign function.
+  fn native(&mut self, def: &DefPath) -> Option<u32> {
+    let symbol = self.program.foreign_function(def)?.symbol();
+    if let Some(index) = self.natives.get(symbol) {
+      return Some(*index);
+    }
+    let name = self.module.add_constant(Constant::Str(symbol.to_owned()));
+    let index = self.module.natives.len() as u32;
+    self.module.natives.push(name);
+    self.natives.insert(symbol.to_owned(), index);
+    Some(index)
+  }
+}
+
+struct FunctionBuilder<'a, 'b> {
+  cx:           &'b mut ModuleBuilder<'a>,
+  /// Name of the function, by which the errors are reported.
+  name:         String,
+  body:         &'a Body,
+  /// Type arguments of the generic parameters of the body.
+  substitution: HashMap<String, Type>,
+  code:         Vec<Instr>,
+}
+
+impl<'a, 'b> FunctionBuilder<'a, 'b> {
+  fn emit(&mut self, instr: Instr) {
+    self.code.push(instr);
+  }
+
+  /// Locate the instructions built next at the span, unless it is unknown.
+  fn locate(&self, function: &mut Function, span: &Span) {
+    if span.start.line > 0 {
+      let (line, column) = (span.start.line as u32, span.start.column as u32);
+      function.lines.add(self.code.len() as u32, line, column);
+    }
+  }
+
+  /// Type in the body with the generic parameters substituted.
+  fn ty(&self, ty: &Type) -> Type {
+    substitute(ty, &self.substitution)
+  }
+
+  fn terminator(&mut self, kind: &TerminatorKind, next: usize) -> VspResult<()> {
+    match kind {
+      TerminatorKind::Goto(target) if target.0 == next => {}
+      TerminatorKind::Goto(target) => self.emit(Instr::Jump(target.0 as u32)),
+      TerminatorKind::If {
+        condition,
+        then,
+        otherwise,
+      } => {
+        self.operand(condition)?;
+        self.emit(Instr::JumpIfNot(otherwise.0 as u32));
+        if then.0 != next {
+          self.emit(Instr::Jump(then.0 as u32));
+        }
+      }
+      TerminatorKind::Return => self.emit(Instr::Return),
+      TerminatorKind::Unreachable => self.emit(Instr::Unreachable),
+      TerminatorKind::Yield { .. } => unreachable!("`yield` is removed from `async func`"),
+      TerminatorKind::Call {
+        func,
+        args,
+        destination,
+        target,
+      } => {
+        let count = u8::try_from(args.len()).map_err(|_| too_many(&self.name, "arguments"))?;
+        self.assign(destination, |builder| {
+          let call = match func {
+            Operand::Constant(MirConstant {
+              kind: ConstantKind::Function(def, _),
+              ..
+            }) if builder.cx.program.foreign_function(def).is_some() => {
+              Instr::CallNative(builder.cx.native(def).unwrap(), count)
+            }
+            Operand::Constant(constant) => {
+              let instance = builder.cx.resolve(&constant.kind, &builder.substitution);
+              Instr::Call(builder.cx.function(&instance)?, count)
+            }
+            func => {
+              builder.operand(func)?;
+              Instr::CallIndirect(count)
+            }
+          };
+          for arg in args {
+            builder.operand(arg)?;
+          }
+          builder.emit(call);
+          Ok(())
+        })?;
+        if target.0 != next {
+          self.emit(Instr::Jump(target.0 as u32));
+        }
+      }
+      TerminatorKind::DynCall {
+        object,
+        index,
+        args,
+        destination,
+        target,
+        ..
+      } => {
+        let count = u8::try_from(args.len() + 1).map_err(|_| too_many(&self.name, "arguments"))?;
+        self.assign(destination, |builder| {
+          builder.operand(object)?;
+          builder.emit(Instr::Field(1));
+          builder.emit(Instr::Field(*index as u16));
+          builder.operand(object)?;
+          builder.emit(Instr::Field(0));
+          for arg in args {
+            builder.operand(arg)?;
+          }
+          builder.emit(Instr::CallIndirect(count));
+          Ok(())
+        })?;
+        if target.0 != next {
+          self.emit(Instr::Jump(target.0 as u32));
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// Push the value of the local of the type before it is initialized if it is an array, whose
+  /// elements are arrays or the unit values, and return whether it is.
+  fn uninit(&mut self, ty: &Type) -> VspResult<bool> {
+    let (element, size) = match ty {
+      Type::Array(element, size) => (element, *size),
+      _ => return Ok(false),
+    };
+    if !self.uninit(element)? {
+      self.emit(Instr::Unit);
+    }
+    let size = u32::try_from(size).map_err(|_| too_many(&self.name, "elements in an array"))?;
+    self.emit(Instr::Array(size));
+    Ok(true)
+  }
+
+  /// Assign the value built by the closure to the place, whose address is built before the value
+  /// if it is projected.
+  fn assign(
+    &mut self,
+    place: &Place,
+    value: impl FnOnce(&mut Self) -> VspResult<()>,
+  ) -> VspResult<()> {
+    if place.projection.is_empty() {
+      value(self)?;
+      self.emit(Instr::StoreLocal(place.local.0 as u16));
+    } else {
+      self.address(place)?;
+      value(self)?;
+      self.emit(Instr::Store);
+    }
+    Ok(())
+  }
+
+  /// Push the value of the place.
+  fn read(&mut self, place: &Place) -> VspResult<()> {
+    if place.is_indirect() {
+      self.address(place)?;
+      self.emit(Instr::Load);
+      return Ok(());
+    }
+    self.emit(Instr::LoadLocal(place.local.0 as u16));
+    self.project_values(place, place.projection.len())
+  }
+
+  /// Push the raw pointer to the place. Projections up to the first dereference are read by value,
+  /// which yields the pointer dereferenced.
+  fn address(&mut self, place: &Place) -> VspResult<()> {
+    let first = place.projection.iter().position(|(elem, _)| *elem == ProjectionElem::Deref);
+    let rest = match first {
+      Some(first) => {
+        self.emit(Instr::LoadLocal(place.local.0 as u16));
+        self.project_values(place, first)?;
+        first + 1
+      }
+      None => {
+        self.emit(Instr::AddrLocal(place.local.0 as u16));
+        0
+      }
+    };
+    for position in rest..place.projection.len() {
+      match place.projection[position].0 {
+        ProjectionElem::Field(index) => self.emit(Instr::AddrField(index as u16)),
+        ProjectionElem::Deref => self.emit(Instr::Load),
+        ProjectionElem::Index(index) => {
+          let length = self.length(place, position)?;
+          self.emit(Instr::LoadLocal(index.0 as u16));
+          self.emit(Instr::AddrIndex(length));
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// Project the value of the place on the stack by the projections before the end, which are the
+  /// fields and the elements of arrays.
+  fn project_values(&mut self, place: &Place, end: usize) -> VspResult<()> {
+    for position in 0..end {
+      match place.projection[position].0 {
+        ProjectionElem::Field(index) => self.emit(Instr::Field(index as u16)),
+        ProjectionElem::Index(index) => {
+          let length = self.length(place, position)?;
+          self.emit(Instr::LoadLocal(index.0 as u16));
+          self.emit(Instr::Index(length));
+        }
+        ProjectionElem::Deref => unreachable!("dereferences are projected by the addresses"),
+      }
+    }
+    Ok(())
+  }
+
+  /// Length of the array projected by the projection at the position of the place.
+  fn length(&self, place: &Place, position: usize) -> VspResult<u32> {
+    let ty = match position {
+      0 => &self.body.local(place.local).ty,
+      position => &place.projection[position - 1].1,
+    };
+    match self.ty(ty) {
+      Type::Array(_, size) => {
+        u32::try_from(size).map_err(|_| too_many(&self.name, "elements in an array"))
+      }
+      ty => unreachable!("indexing into non-array type `{}`", ty),
+    }
+  }
+
+  fn operand(&mut self, operand: &Operand) -> VspResult<()> {
+    match operand {
+      Operand::Copy(place) => self.read(place),
+      Operand::Constant(constant) => self.constant(constant),
+    }
+  }
+
+  fn constant(&mut self, constant: &MirConstant) -> VspResult<()> {
+    let value = match &constant.kind {
+      ConstantKind::Unit => {
+        self.emit(Instr::Unit);
+        return Ok(());
+      }
+      ConstantKind::Integer(value) => Constant::Int(integer(*value, &self.ty(&constant.ty))),
+      ConstantKind::Float(value) => Constant::Float(*value),
+      ConstantKind::Bool(value) => Constant::Bool(*value),
+      ConstantKind::Str(value) => Constant::Str(value.to_owned()),
+      ConstantKind::Static(def) => {
+        let owner = MonoItem::Static(def.clone());
+        let value = self.cx.program.consts.iter().find(|value| value.owner == owner);
+        let value = value.expect("static items are evaluated");
+        return self.constant(&value.value);
+      }
+      ConstantKind::Const(_) => unreachable!("constants are replaced by their values"),
+      ConstantKind::Function(def, _) if self.cx.program.foreign_function(def).is_some() => {
+        let native = self.cx.native(def).unwrap();
+        self.emit(Instr::Native(native));
+        return Ok(());
+      }
+      kind => {
+        let instance = self.cx.resolve(kind, &self.substitution);
+        let function = self.cx.function(&instance)?;
+        self.emit(Instr::Function(function));
+        return Ok(());
+      }
+    };
+    let index = self.cx.module.add_constant(value);
+    self.emit(Instr::Const(index));
+    Ok(())
+  }
+
+  fn rvalue(&mut self, rvalue: &Rvalue) -> VspResult<()> {
+    match rvalue {
+      Rvalue::Use(operand) => self.operand(operand)?,
+      Rvalue::Unary(op, operand) => {
+        self.operand(operand)?;
+        match op {
+          UnaryOp::Not => self.emit(Instr::Not),
+          UnaryOp::Negative => self.emit(Instr::Neg(scalar(&self.ty(operand.ty(self.body))))),
+          UnaryOp::Dereference | UnaryOp::AddressOf => {
+            unreachable!("`{}` is lowered into the place", op.as_str())
+          }
+        }
+      }
+      Rvalue::Binary(op, left, right) => {
+        // The operands have the same type after the implicit widening.
+        let scalar = scalar(&self.ty(left.ty(self.body)));
+        self.operand(left)?;
+        self.operand(right)?;
+        self.emit(Instr::Binary(binary_op(op), scalar));
+      }
+      Rvalue::AddressOf(place) => self.address(place)?,
+      Rvalue::Cast(operand, target) => {
+        let from = self.ty(operand.ty(self.body));
+        self.operand(operand)?;
+        match self.ty(target) {
+          Type::Primitive(to) if from != Type::Primitive(to.clone()) => {
+            self.emit(Instr::Cast(scalar(&Type::Primitive(to))))
+          }
+          Type::Pointer(_) if !matches!(from, Type::Pointer(_)) => self.emit(Instr::ToPointer),
+          Type::Pointer(pointee) if is_array_pointer(&from) && !is_array(&pointee) => {
+            self.emit(Instr::AddrElements)
+          }
+          _ => {}
+        }
+      }
+      Rvalue::Struct(ty, fields) => {
+        let count = match self.ty(ty) {
+          Type::Named(path, _) => self.cx.program.struct_def(&path).map(|def| def.fields.len()),
+          _ => None,
+        };
+        let count =
+          count.unwrap_or_else(|| fields.iter().map(|(index, _)| index + 1).max().unwrap_or(0));
+        for index in 0..count {
+          match fields.iter().find(|(field, _)| *field == index) {
+            Some((_, operand)) => self.operand(operand)?,
+            None => self.emit(Instr::Unit),
+          }
+        }
+        self.emit(Instr::Struct(count as u16));
+      }
+      Rvalue::Variant(_, index, fields) => {
+        for field in fields {
+          self.operand(field)?;
+        }
+        self.emit(Instr::Variant(*index as u16, fields.len() as u16));
+      }
+      Rvalue::Array(_, elements) => {
+        for element in elements {
+          self.operand(element)?;
+        }
+        let count = u32::try_from(elements.len())
+          .map_err(|_| too_many(&self.name, "elements in an array"))?;
+        self.emit(Instr::ArrayOf(count));
+      }
+      Rvalue::Discriminant(place) => {
+        self.read(place)?;
+        self.emit(Instr::Discriminant);
+      }
+      Rvalue::VariantField(place, variant, index) => {
+        self.read(place)?;
+        self.emit(Instr::VariantField(*variant as u16, *index as u16));
+      }
+      Rvalue::ToDyn(operand, ty) => {
+        let trait_ = match ty {
+          Type::Dyn(path) => DefPath::new(
+            path.parent().unwrap_or_default(),
+            path.name().unwrap_or_default(),
+          ),
+          ty => unreachable!("type `{}` is not a trait object", ty),
+        };
+        let self_ty = self.ty(operand.ty(self.body));
+        self.operand(operand)?;
+        let methods = &self.cx.items.traits[&trait_].methods;
+        for method in methods {
+          let instance = self.cx.method_instance(&trait_, &self_ty, &method.name);
+          self.emit(Instr::Function(self.cx.function(&instance)?));
+        }
+        self.emit(Instr::Struct(methods.len() as u16));
+        self.emit(Instr::Struct(2));
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Value of the integer constant of the type, whose unsigned values are stored in the bits of
+/// `i64`.
+fn integer(value: i64, ty: &Type) -> i128 {
+  match ty {
+    Type::Primitive(primitive) if primitive.is_unsigned_integer() => value as u64 as i128,
+    _ => value as i128,
+  }
+}
+
+fn scalar(ty: &Type) -> Scalar {
+  let primitive = match ty {
+    Type::Primitive(primitive) => primitive,
+    _ => return Scalar::Other,
+  };
+  match primitive {
+    PrimitiveType::Bool => Scalar::Bool,
+    PrimitiveType::Int8 => Scalar::Int8,
+    PrimitiveType::Int16 => Scalar::Int16,
+    PrimitiveType::Int32 => Scalar::Int32,
+    PrimitiveType::Int64 => Scalar::Int64,
+    PrimitiveType::Uint8 => Scalar::Uint8,
+    PrimitiveType::Uint16 => Scalar::Uint16,
+    PrimitiveType::Uint32 => Scalar::Uint32,
+    PrimitiveType::Uint64 => Scalar::Uint64,
+    PrimitiveType::Float64 | PrimitiveType::Double64 => Scalar::Float,
+    PrimitiveType::Char => Scalar::Char,
+    PrimitiveType::Unit | PrimitiveType::Str => Scalar::Other,
+  }
+}
+
+fn binary_op(op: &BinaryOp) -> BinOp {
+  match op {
+    BinaryOp::Add => BinOp::Add,
+    BinaryOp::Subtract => BinOp::Sub,
+    BinaryOp::Multiply => BinOp::Mul,
+    BinaryOp::Division => BinOp::Div,
+    BinaryOp::Remainder => BinOp::Rem,
+    BinaryOp::Equal => BinOp::Eq,
+    BinaryOp::NotEqual => BinOp::Ne,
+    BinaryOp::Less => BinOp::Lt,
+    BinaryOp::LessEqual => BinOp::Le,
+    BinaryOp::Greater => BinOp::Gt,
+    BinaryOp::GreaterEqual => BinOp::Ge,
+    op => unreachable!("`{}` is lowered into the control flow", op.as_str()),
+  }
+}
+
+fn too_many(name: &str, what: &str) -> VspError {
+  VspError::new(format!("`{}` has too many {} for the bytecode", name, what))
+}
+
+fn is_array(ty: &Type) -> bool {
+  matches!(ty, Type::Array(..))
+}
+
+fn is_array_pointer(ty: &Type) -> bool {
+  matches!(ty, Type::Pointer(pointee) if is_array(pointee))
+}
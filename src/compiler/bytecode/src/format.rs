@@ -0,0 +1,737 @@
+//! Serialized format of the bytecode modules, written into the `.vspb` files.
+//!
+//! All integers are little-endian, and the strings are UTF-8 prefixed by their length in bytes as
+//! `u32`. A module is laid out as follows.
+//!
+//! ```plaintext
+//! file      = shebang? module
+//! shebang   = "#!" text "\n"                  interpreter directive, as of the scripts
+//! module    = magic major minor flags constants natives functions entry
+//! magic     = "VSPB"
+//! major     = u16                             incompatible revisions of the format
+//! minor     = u16                             compatible additions to the format
+//! flags     = u32                             bit 0: integer arithmetic traps on overflow
+//! constants = u32 constant*
+//! constant  = 0x00                            unit
+//!           | 0x01 i128                       integer
+//!           | 0x02 f64                        float, by its bits
+//!           | 0x03 u8                         bool
+//!           | 0x04 string                     string
+//! natives   = u32 u32*                        constants of the names of the natives
+//! functions = u32 function*
+//! function  = name file params locals code lines
+//! name      = u32                             constant of the name
+//! file      = u32                             constant of the source file, or 0xFFFFFFFF
+//! params    = u16
+//! locals    = u16
+//! code      = u32 instr*                      number of the instructions, and the instructions
+//! instr     = opcode operand*                 opcode u8, with the operands listed in `Opcode`
+//! lines     = u32 (u32 u32 u32)*              index of the instruction, line and column
+//! entry     = u32                             function run as `main`, or 0xFFFFFFFF
+//! ```
+//!
+//! Readers accept the modules of the same major version and of the same or an older minor
+//! version, and verify every index in the module, the ends of the functions and the heights of the
+//! operand stack, so that the VM never reaches outside of the module or below its stack.
+use vsp_error::VspError;
+use vsp_error::VspResult;
+
+use crate::module::BinOp;
+use crate::module::Constant;
+use crate::module::Function;
+use crate::module::Instr;
+use crate::module::LineEntry;
+use crate::module::LineTable;
+use crate::module::Module;
+use crate::module::Scalar;
+
+pub const MAGIC: &[u8; 4] = b"VSPB";
+pub const MAJOR_VERSION: u16 = 1;
+pub const MINOR_VERSION: u16 = 0;
+/// Extension of the files of the bytecode modules.
+pub const EXTENSION: &str = "vspb";
+
+const SHEBANG: &str = "#!";
+const OVERFLOW_CHECKS: u32 = 1;
+/// Missing index of the optional files and the entry.
+const NONE: u32 = u32::MAX;
+
+/// Opcodes of the instructions, with their operands after them.
+#[repr(u8)]
+enum Opcode {
+  Unit,
+  /// Constant `u32`.
+  Const,
+  /// Local `u16`.
+  LoadLocal,
+  /// Local `u16`.
+  StoreLocal,
+  /// Local `u16`.
+  AddrLocal,
+  /// Field `u16`.
+  AddrField,
+  Load,
+  Store,
+  /// Field `u16`.
+  Field,
+  Pop,
+  /// Scalar `u8`.
+  Neg,
+  Not,
+  /// Operator `u8` and scalar `u8`.
+  Binary,
+  /// Scalar `u8`.
+  Cast,
+  ToPointer,
+  /// Number of the fields `u16`.
+  Struct,
+  /// Variant `u16` and number of the fields `u16`.
+  Variant,
+  Discriminant,
+  /// Variant `u16` and field `u16`.
+  VariantField,
+  /// Function `u32`.
+  Function,
+  /// Native `u32`.
+  Native,
+  /// Function `u32` and number of the arguments `u8`.
+  Call,
+  /// Native `u32` and number of the arguments `u8`.
+  CallNative,
+  /// Number of the arguments `u8`.
+  CallIndirect,
+  /// Target `u32`.
+  Jump,
+  /// Target `u32`.
+  JumpIfNot,
+  Return,
+  Unreachable,
+  /// Number of the elements `u32`.
+  Array,
+  AddrElements,
+  /// Number of the elements `u32`.
+  ArrayOf,
+  /// Length `u32`.
+  Index,
+  /// Length `u32`.
+  AddrIndex,
+}
+
+/// Serialize the module.
+pub fn write(module: &Module) -> Vec<u8> {
+  let mut writer = Writer::default();
+  if let Some(shebang) = &module.shebang {
+    writer.bytes.extend_from_slice(format!("{}{}\n", SHEBANG, shebang).as_bytes());
+  }
+  writer.bytes.extend_from_slice(MAGIC);
+  writer.u16(MAJOR_VERSION);
+  writer.u16(MINOR_VERSION);
+  writer.u32(if module.overflow_checks {
+    OVERFLOW_CHECKS
+  } else {
+    0
+  });
+
+  writer.len(module.constants.len());
+  for constant in &module.constants {
+    match constant {
+      Constant::Unit => writer.u8(0),
+      Constant::Int(value) => {
+        writer.u8(1);
+        writer.bytes.extend_from_slice(&value.to_le_bytes());
+      }
+      Constant::Float(value) => {
+        writer.u8(2);
+        writer.bytes.extend_from_slice(&value.to_bits().to_le_bytes());
+      }
+      Constant::Bool(value) => {
+        writer.u8(3);
+        writer.u8(*value as u8);
+      }
+      Constant::Str(value) => {
+        writer.u8(4);
+        writer.len(value.len());
+        writer.bytes.extend_from_slice(value.as_bytes());
+      }
+    }
+  }
+
+  writer.len(module.natives.len());
+  for native in &module.natives {
+    writer.u32(*native);
+  }
+
+  writer.len(module.functions.len());
+  for function in &module.functions {
+    writer.u32(function.name);
+    writer.u32(function.file.unwrap_or(NONE));
+    writer.u16(function.params);
+    writer.u16(function.locals);
+    writer.len(function.code.len());
+    for instr in &function.code {
+      writer.instr(instr);
+    }
+    writer.len(function.lines.entries.len());
+    for entry in &function.lines.entries {
+      writer.u32(entry.pc);
+      writer.u32(entry.line);
+      writer.u32(entry.column);
+    }
+  }
+  writer.u32(module.entry.unwrap_or(NONE));
+  writer.bytes
+}
+
+/// Deserialize the module, and verify it.
+pub fn read(bytes: &[u8]) -> VspResult<Module> {
+  let (shebang, bytes) = split_shebang(bytes)?;
+  let mut reader = Reader { bytes, offset: 0 };
+  if bytes.get(..MAGIC.len()) != Some(&MAGIC[..]) {
+    return VspError::new("not a bytecode module").to_err();
+  }
+  reader.offset = MAGIC.len();
+  let (major, minor) = (reader.u16()?, reader.u16()?);
+  if major != MAJOR_VERSION || minor > MINOR_VERSION {
+    return VspError::new(format!(
+      "unsupported bytecode version {}.{}, expected {}.{} or older",
+      major, minor, MAJOR_VERSION, MINOR_VERSION
+    ))
+    .to_err();
+  }
+  let mut module = Module {
+    shebang,
+    overflow_checks: reader.u32()? & OVERFLOW_CHECKS != 0,
+    ..Module::default()
+  };
+
+  for _ in 0..reader.u32()? {
+    let constant = match reader.u8()? {
+      0 => Constant::Unit,
+      1 => Constant::Int(i128::from_le_bytes(reader.array()?)),
+      2 => Constant::Float(f64::from_bits(u64::from_le_bytes(reader.array()?))),
+      3 => Constant::Bool(reader.u8()? != 0),
+      4 => {
+        let length = reader.u32()? as usize;
+        let text = std::str::from_utf8(reader.take(length)?)
+          .map_err(|_| invalid("a string is not in UTF-8"))?;
+        Constant::Str(text.to_owned())
+      }
+      tag => return invalid(format!("unknown constant tag {}", tag)).to_err(),
+    };
+    module.constants.push(constant);
+  }
+
+  for _ in 0..reader.u32()? {
+    module.natives.push(reader.u32()?);
+  }
+
+  for _ in 0..reader.u32()? {
+    let mut function = Function {
+      name: reader.u32()?,
+      file: Some(reader.u32()?).filter(|file| *file != NONE),
+      params: reader.u16()?,
+      locals: reader.u16()?,
+      ..Function::default()
+    };
+    for _ in 0..reader.u32()? {
+      function.code.push(reader.instr()?);
+    }
+    let mut lines = LineTable::default();
+    for _ in 0..reader.u32()? {
+      lines.entries.push(LineEntry {
+        pc:     reader.u32()?,
+        line:   reader.u32()?,
+        column: reader.u32()?,
+      });
+    }
+    function.lines = lines;
+    module.functions.push(function);
+  }
+  module.entry = Some(reader.u32()?).filter(|entry| *entry != NONE);
+  if reader.offset != bytes.len() {
+    return invalid("trailing bytes after the module").to_err();
+  }
+  verify(&module)?;
+  Ok(module)
+}
+
+/// Check that every index in the module is within its bounds, the calls pass as many arguments as
+/// the callees take, every function ends with a jump or a return, and the operand stack never
+/// underflows.
+pub fn verify(module: &Module) -> VspResult<()> {
+  let string = |index: u32, what: &str| match module.string(index) {
+    Some(_) => Ok(()),
+    None => invalid(format!("{} is not a string constant", what)).to_err(),
+  };
+  for native in &module.natives {
+    string(*native, "the name of a native")?;
+  }
+  let functions = module.functions.len();
+  if let Some(entry) = module.entry {
+    match module.functions.get(entry as usize) {
+      Some(main) if main.params == 0 || main.params == 2 => {}
+      Some(_) => return invalid("the entry takes neither none nor two parameters").to_err(),
+      None => return invalid("the entry is out of bounds").to_err(),
+    }
+  }
+  for function in &module.functions {
+    string(function.name, "the name of a function")?;
+    if let Some(file) = function.file {
+      string(file, "the file of a function")?;
+    }
+    if function.params >= function.locals {
+      return invalid("a function has fewer locals than its parameters").to_err();
+    }
+    let code = function.code.len();
+    for instr in &function.code {
+      let valid = match instr {
+        Instr::Const(index) => (*index as usize) < module.constants.len(),
+        Instr::LoadLocal(local) | Instr::StoreLocal(local) | Instr::AddrLocal(local) => {
+          *local < function.locals
+        }
+        Instr::Function(index) => (*index as usize) < functions,
+        Instr::Call(index, count) => module
+          .functions
+          .get(*index as usize)
+          .map_or(false, |callee| callee.params == *count as u16),
+        Instr::Native(index) | Instr::CallNative(index, _) => {
+          (*index as usize) < module.natives.len()
+        }
+        Instr::Jump(target) | Instr::JumpIfNot(target) => (*target as usize) < code,
+        _ => true,
+      };
+      if !valid {
+        return invalid(format!("operand of {:?} is out of bounds", instr)).to_err();
+      }
+    }
+    match function.code.last() {
+      Some(Instr::Return | Instr::Jump(_) | Instr::Unreachable) => {}
+      _ => return invalid("a function runs past the end of its code").to_err(),
+    }
+    verify_stack(function)?;
+  }
+  Ok(())
+}
+
+/// Check that the operand stack has the same height whichever path reaches each instruction, holds
+/// the operands of every instruction, and is empty when the function returns.
+fn verify_stack(function: &Function) -> VspResult<()> {
+  let mut heights = vec![None; function.code.len()];
+  heights[0] = Some(0);
+  let mut pending = vec![0];
+  while let Some(pc) = pending.pop() {
+    let instr = &function.code[pc];
+    let height = heights[pc].unwrap_or_default();
+    let (pops, pushes) = stack_effect(instr);
+    if height < pops {
+      return invalid(format!("{:?} pops more values than the stack holds", instr)).to_err();
+    }
+    let successors = match instr {
+      Instr::Jump(target) => vec![*target as usize],
+      Instr::JumpIfNot(target) => vec![pc + 1, *target as usize],
+      Instr::Return if height > 0 => {
+        return invalid("a function returns with values left on the stack").to_err()
+      }
+      Instr::Return | Instr::Unreachable => vec![],
+      _ => vec![pc + 1],
+    };
+    let height = height - pops + pushes;
+    for successor in successors {
+      match heights[successor] {
+        None => {
+          heights[successor] = Some(height);
+          pending.push(successor);
+        }
+        Some(other) if other != height => {
+          return invalid(format!(
+            "the stack has different heights at the instruction {}",
+            successor
+          ))
+          .to_err()
+        }
+        Some(_) => {}
+      }
+    }
+  }
+  Ok(())
+}
+
+/// Numbers of the values which the instruction pops and pushes.
+fn stack_effect(instr: &Instr) -> (usize, usize) {
+  match *instr {
+    Instr::Unit
+    | Instr::Const(_)
+    | Instr::LoadLocal(_)
+    | Instr::AddrLocal(_)
+    | Instr::Function(_)
+    | Instr::Native(_) => (0, 1),
+    Instr::StoreLocal(_) | Instr::Pop | Instr::JumpIfNot(_) => (1, 0),
+    Instr::AddrField(_)
+    | Instr::Load
+    | Instr::Field(_)
+    | Instr::Neg(_)
+    | Instr::Not
+    | Instr::Cast(_)
+    | Instr::ToPointer
+    | Instr::Array(_)
+    | Instr::AddrElements
+    | Instr::Discriminant
+    | Instr::VariantField(..) => (1, 1),
+    Instr::Store => (2, 0),
+    Instr::Binary(..) | Instr::Index(_) | Instr::AddrIndex(_) => (2, 1),
+    Instr::Struct(count) | Instr::Variant(_, count) => (count as usize, 1),
+    Instr::ArrayOf(count) => (count as usize, 1),
+    Instr::Call(_, count) | Instr::CallNative(_, count) => (count as usize, 1),
+    Instr::CallIndirect(count) => (count as usize + 1, 1),
+    Instr::Jump(_) | Instr::Return | Instr::Unreachable => (0, 0),
+  }
+}
+
+/// Split the shebang line off the front of the file, if any.
+fn split_shebang(bytes: &[u8]) -> VspResult<(Option<String>, &[u8])> {
+  if !bytes.starts_with(SHEBANG.as_bytes()) {
+    return Ok((None, bytes));
+  }
+  let end = match bytes.iter().position(|&byte| byte == b'\n') {
+    Some(end) => end,
+    None => return invalid("the module is truncated").to_err(),
+  };
+  let shebang = std::str::from_utf8(&bytes[SHEBANG.len()..end])
+    .map_err(|_| invalid("the shebang is not UTF-8"))?;
+  Ok((Some(shebang.to_owned()), &bytes[end + 1..]))
+}
+
+fn invalid(message: impl Into<String>) -> VspError {
+  VspError::new(format!("invalid bytecode module: {}", message.into()))
+}
+
+#[derive(Default)]
+struct Writer {
+  bytes: Vec<u8>,
+}
+
+impl Writer {
+  fn u8(&mut self, value: u8) {
+    self.bytes.push(value);
+  }
+
+  fn u16(&mut self, value: u16) {
+    self.bytes.extend_from_slice(&value.to_le_bytes());
+  }
+
+  fn u32(&mut self, value: u32) {
+    self.bytes.extend_from_slice(&value.to_le_bytes());
+  }
+
+  fn len(&mut self, length: usize) {
+    self.u32(u32::try_from(length).expect("the module is too large to serialize"));
+  }
+
+  fn op(&mut self, opcode: Opcode) {
+    self.u8(opcode as u8);
+  }
+
+  fn instr(&mut self, instr: &Instr) {
+    match *instr {
+      Instr::Unit => self.op(Opcode::Unit),
+      Instr::Const(index) => {
+        self.op(Opcode::Const);
+        self.u32(index);
+      }
+      Instr::LoadLocal(local) => {
+        self.op(Opcode::LoadLocal);
+        self.u16(local);
+      }
+      Instr::StoreLocal(local) => {
+        self.op(Opcode::StoreLocal);
+        self.u16(local);
+      }
+      Instr::AddrLocal(local) => {
+        self.op(Opcode::AddrLocal);
+        self.u16(local);
+      }
+      Instr::AddrField(field) => {
+        self.op(Opcode::AddrField);
+        self.u16(field);
+      }
+      Instr::Load => self.op(Opcode::Load),
+      Instr::Store => self.op(Opcode::Store),
+      Instr::Field(field) => {
+        self.op(Opcode::Field);
+        self.u16(field);
+      }
+      Instr::Pop => self.op(Opcode::Pop),
+      Instr::Neg(scalar) => {
+        self.op(Opcode::Neg);
+        self.u8(scalar as u8);
+      }
+      Instr::Not => self.op(Opcode::Not),
+      Instr::Binary(op, scalar) => {
+        self.op(Opcode::Binary);
+        self.u8(op as u8);
+        self.u8(scalar as u8);
+      }
+      Instr::Cast(scalar) => {
+        self.op(Opcode::Cast);
+        self.u8(scalar as u8);
+      }
+      Instr::ToPointer => self.op(Opcode::ToPointer),
+      Instr::Struct(count) => {
+        self.op(Opcode::Struct);
+        self.u16(count);
+      }
+      Instr::Variant(variant, count) => {
+        self.op(Opcode::Variant);
+        self.u16(variant);
+        self.u16(count);
+      }
+      Instr::Array(count) => {
+        self.op(Opcode::Array);
+        self.u32(count);
+      }
+      Instr::AddrElements => self.op(Opcode::AddrElements),
+      Instr::ArrayOf(count) => {
+        self.op(Opcode::ArrayOf);
+        self.u32(count);
+      }
+      Instr::Index(length) => {
+        self.op(Opcode::Index);
+        self.u32(length);
+      }
+      Instr::AddrIndex(length) => {
+        self.op(Opcode::AddrIndex);
+        self.u32(length);
+      }
+      Instr::Discriminant => self.op(Opcode::Discriminant),
+      Instr::VariantField(variant, field) => {
+        self.op(Opcode::VariantField);
+        self.u16(variant);
+        self.u16(field);
+      }
+      Instr::Function(index) => {
+        self.op(Opcode::Function);
+        self.u32(index);
+      }
+      Instr::Native(index) => {
+        self.op(Opcode::Native);
+        self.u32(index);
+      }
+      Instr::Call(index, count) => {
+        self.op(Opcode::Call);
+        self.u32(index);
+        self.u8(count);
+      }
+      Instr::CallNative(index, count) => {
+        self.op(Opcode::CallNative);
+        self.u32(index);
+        self.u8(count);
+      }
+      Instr::CallIndirect(count) => {
+        self.op(Opcode::CallIndirect);
+        self.u8(count);
+      }
+      Instr::Jump(target) => {
+        self.op(Opcode::Jump);
+        self.u32(target);
+      }
+      Instr::JumpIfNot(target) => {
+        self.op(Opcode::JumpIfNot);
+        self.u32(target);
+      }
+      Instr::Return => self.op(Opcode::Return),
+      Instr::Unreachable => self.op(Opcode::Unreachable),
+    }
+  }
+}
+
+struct Reader<'a> {
+  bytes:  &'a [u8],
+  offset: usize,
+}
+
+impl<'a> Reader<'a> {
+  fn take(&mut self, length: usize) -> VspResult<&'a [u8]> {
+    let end = self.offset.checked_add(length).filter(|end| *end <= self.bytes.len());
+    let end = end.ok_or_else(|| invalid("the module is truncated"))?;
+    let bytes = &self.bytes[self.offset..end];
+    self.offset = end;
+    Ok(bytes)
+  }
+
+  fn array<const N: usize>(&mut self) -> VspResult<[u8; N]> {
+    let mut array = [0; N];
+    array.copy_from_slice(self.take(N)?);
+    Ok(array)
+  }
+
+  fn u8(&mut self) -> VspResult<u8> {
+    Ok(self.take(1)?[0])
+  }
+
+  fn u16(&mut self) -> VspResult<u16> {
+    Ok(u16::from_le_bytes(self.array()?))
+  }
+
+  fn u32(&mut self) -> VspResult<u32> {
+    Ok(u32::from_le_bytes(self.array()?))
+  }
+
+  fn scalar(&mut self) -> VspResult<Scalar> {
+    let byte = self.u8()?;
+    Scalar::from_u8(byte).ok_or_else(|| invalid(format!("unknown scalar type {}", byte)))
+  }
+
+  fn instr(&mut self) -> VspResult<Instr> {
+    let opcode = self.u8()?;
+    let instr = match opcode {
+      op if op == Opcode::Unit as u8 => Instr::Unit,
+      op if op == Opcode::Const as u8 => Instr::Const(self.u32()?),
+      op if op == Opcode::LoadLocal as u8 => Instr::LoadLocal(self.u16()?),
+      op if op == Opcode::StoreLocal as u8 => Instr::StoreLocal(self.u16()?),
+      op if op == Opcode::AddrLocal as u8 => Instr::AddrLocal(self.u16()?),
+      op if op == Opcode::AddrField as u8 => Instr::AddrField(self.u16()?),
+      op if op == Opcode::Load as u8 => Instr::Load,
+      op if op == Opcode::Store as u8 => Instr::Store,
+      op if op == Opcode::Field as u8 => Instr::Field(self.u16()?),
+      op if op == Opcode::Pop as u8 => Instr::Pop,
+      op if op == Opcode::Neg as u8 => Instr::Neg(self.scalar()?),
+      op if op == Opcode::Not as u8 => Instr::Not,
+      op if op == Opcode::Binary as u8 => {
+        let byte = self.u8()?;
+        let op =
+          BinOp::from_u8(byte).ok_or_else(|| invalid(format!("unknown operator {}", byte)))?;
+        Instr::Binary(op, self.scalar()?)
+      }
+      op if op == Opcode::Cast as u8 => Instr::Cast(self.scalar()?),
+      op if op == Opcode::ToPointer as u8 => Instr::ToPointer,
+      op if op == Opcode::Struct as u8 => Instr::Struct(self.u16()?),
+      op if op == Opcode::Variant as u8 => Instr::Variant(self.u16()?, self.u16()?),
+      op if op == Opcode::Discriminant as u8 => Instr::Discriminant,
+      op if op == Opcode::VariantField as u8 => Instr::VariantField(self.u16()?, self.u16()?),
+      op if op == Opcode::Function as u8 => Instr::Function(self.u32()?),
+      op if op == Opcode::Native as u8 => Instr::Native(self.u32()?),
+      op if op == Opcode::Call as u8 => Instr::Call(self.u32()?, self.u8()?),
+      op if op == Opcode::CallNative as u8 => Instr::CallNative(self.u32()?, self.u8()?),
+      op if op == Opcode::CallIndirect as u8 => Instr::CallIndirect(self.u8()?),
+      op if op == Opcode::Jump as u8 => Instr::Jump(self.u32()?),
+      op if op == Opcode::JumpIfNot as u8 => Instr::JumpIfNot(self.u32()?),
+      op if op == Opcode::Return as u8 => Instr::Return,
+      op if op == Opcode::Unreachable as u8 => Instr::Unreachable,
+      op if op == Opcode::Array as u8 => Instr::Array(self.u32()?),
+      op if op == Opcode::AddrElements as u8 => Instr::AddrElements,
+      op if op == Opcode::ArrayOf as u8 => Instr::ArrayOf(self.u32()?),
+      op if op == Opcode::Index as u8 => Instr::Index(self.u32()?),
+      op if op == Opcode::AddrIndex as u8 => Instr::AddrIndex(self.u32()?),
+      opcode => return invalid(format!("unknown opcode {}", opcode)).to_err(),
+    };
+    Ok(instr)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn sample() -> Module {
+    let mut module = Module {
+      overflow_checks: true,
+      ..Module::default()
+    };
+    let name = module.add_constant(Constant::Str("main".to_owned()));
+    let file = module.add_constant(Constant::Str("main.vsp".to_owned()));
+    let value = module.add_constant(Constant::Int(-(1 << 100)));
+    let half = module.add_constant(Constant::Float(0.5));
+    let println = module.add_constant(Constant::Str("println".to_owned()));
+    module.natives.push(println);
+    let mut lines = LineTable::default();
+    lines.add(0, 1, 1);
+    lines.add(2, 2, 5);
+    module.functions.push(Function {
+      name,
+      file: Some(file),
+      params: 0,
+      locals: 2,
+      code: vec![
+        Instr::Const(value),
+        Instr::Const(half),
+        Instr::Binary(BinOp::Ge, Scalar::Float),
+        Instr::JumpIfNot(6),
+        Instr::CallNative(0, 0),
+        Instr::StoreLocal(1),
+        Instr::Return,
+      ],
+      lines,
+    });
+    module.entry = Some(0);
+    module
+  }
+
+  #[test]
+  fn test_round_trip() {
+    let module = sample();
+    let bytes = write(&module);
+    assert_eq!(&bytes[..4], b"VSPB");
+    assert_eq!(read(&bytes).unwrap(), module);
+    assert_eq!(module.function(0).lines.locate(3), Some((2, 5)));
+    assert_eq!(module.function(0).lines.locate(1), Some((1, 1)));
+
+    let module = Module {
+      shebang: Some("/usr/bin/env vsp run".to_owned()),
+      ..sample()
+    };
+    let bytes = write(&module);
+    assert!(bytes.starts_with(b"#!/usr/bin/env vsp run\nVSPB"));
+    assert_eq!(read(&bytes).unwrap(), module);
+  }
+
+  #[test]
+  fn test_invalid() {
+    let mut bytes = write(&sample());
+    let message = read(&bytes[..bytes.len() - 1]).unwrap_err().message();
+    assert_eq!(message, "invalid bytecode module: the module is truncated");
+
+    bytes[4] = 2;
+    let message = read(&bytes).unwrap_err().message();
+    assert_eq!(
+      message,
+      "unsupported bytecode version 2.0, expected 1.0 or older"
+    );
+    assert_eq!(
+      read(b"\x7fELF").unwrap_err().message(),
+      "not a bytecode module"
+    );
+
+    let mut module = sample();
+    module.functions[0].code.push(Instr::Jump(10));
+    let message = read(&write(&module)).unwrap_err().message();
+    assert_eq!(
+      message,
+      "invalid bytecode module: operand of Jump(10) is out of bounds"
+    );
+
+    // Flipped bytes could leave the operand stack underflowing or unbalanced.
+    let mut module = sample();
+    module.functions[0].code = vec![Instr::Binary(BinOp::Add, Scalar::Int64), Instr::Return];
+    assert_eq!(
+      verify(&module).unwrap_err().message(),
+      "invalid bytecode module: Binary(Add, Int64) pops more values than the stack holds"
+    );
+    module.functions[0].code = vec![
+      Instr::Unit,
+      Instr::Unit,
+      Instr::JumpIfNot(4),
+      Instr::Pop,
+      Instr::Pop,
+      Instr::Return,
+    ];
+    assert_eq!(
+      verify(&module).unwrap_err().message(),
+      "invalid bytecode module: the stack has different heights at the instruction 4"
+    );
+    module.functions[0].code = vec![Instr::Unit, Instr::Return];
+    assert_eq!(
+      verify(&module).unwrap_err().message(),
+      "invalid bytecode module: a function returns with values left on the stack"
+    );
+  }
+}
@@ -0,0 +1,30 @@
+//! # Bytecode
+//!
+//! Portable target of the compiler, besides the native code. The MIR of the program is compiled
+//! into a [`module::Module`] of instructions for a stack machine, with a constant pool, the
+//! functions and their line tables, which is serialized into a versioned `.vspb` file by
+//! [`format`] and run by the VM of `vsp-vm`. The modules start fast, since they need neither the
+//! checks of the source nor the code generation, which suits the scripts run by a shebang.
+//!
+//! ```rust
+//! use vsp_ast_parser::lex::DefaultLexer;
+//! use vsp_ast_parser::parser::ASTParser;
+//! use vsp_ast_parser::parser::TraditionalParser;
+//! use vsp_bytecode::build::build_module;
+//! use vsp_bytecode::format;
+//! use vsp_diag::DiagnosticEngine;
+//!
+//! let tokens = DefaultLexer {}.tokenize("func main(): int32 { return 6 * 7; }").unwrap();
+//! let unit = TraditionalParser {}.parse(tokens).unwrap();
+//! let mut diagnostics = DiagnosticEngine::default();
+//! let results = vsp_sema::check_compilation_unit(&unit, &mut diagnostics);
+//! let program = vsp_hir::lower_compilation_unit(&unit, &results);
+//! let program = vsp_mir::build::build_program(&program, &mut diagnostics);
+//! assert!(!diagnostics.has_errors());
+//! let module = build_module(&program, &results, true).unwrap();
+//! let bytes = format::write(&module);
+//! assert_eq!(format::read(&bytes).unwrap(), module);
+//! ```
+pub mod build;
+pub mod format;
+pub mod module;
@@ -0,0 +1,287 @@
+//! Bytecode modules and their instructions.
+//!
+//! The instructions run on an operand stack. Each call has a frame of local variables, numbered as
+//! in the MIR, where the local `0` holds the return value and the arguments follow it. Jumps are
+//! to the absolute indices of the instructions in the function.
+use vsp_ast::ast::types::PrimitiveType;
+
+/// Index of the constant in the constant pool.
+pub type ConstIndex = u32;
+/// Index of the function in the module.
+pub type FuncIndex = u32;
+
+/// Compiled program, which is self-contained except for the natives it binds by their names.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Module {
+  /// Interpreter directive of the script the module is compiled from, written on the first line
+  /// of the file so that the file runs as the script does.
+  pub shebang:         Option<String>,
+  /// Whether the integer arithmetic traps on overflow, or wraps around.
+  pub overflow_checks: bool,
+  pub constants:       Vec<Constant>,
+  /// Names of the natives, which the foreign functions are bound to, as constants of strings.
+  pub natives:         Vec<ConstIndex>,
+  pub functions:       Vec<Function>,
+  /// Function run as `main`, which is `None` for the modules without it.
+  pub entry:           Option<FuncIndex>,
+}
+
+impl Module {
+  /// Add the constant to the pool unless it is already there, and return its index.
+  pub fn add_constant(&mut self, constant: Constant) -> ConstIndex {
+    let index = self.constants.iter().position(|c| c.same(&constant));
+    let index = index.unwrap_or_else(|| {
+      self.constants.push(constant);
+      self.constants.len() - 1
+    });
+    index as ConstIndex
+  }
+
+  /// Text of the constant, if it is a string.
+  pub fn string(&self, index: ConstIndex) -> Option<&str> {
+    match self.constants.get(index as usize) {
+      Some(Constant::Str(text)) => Some(text),
+      _ => None,
+    }
+  }
+
+  pub fn function(&self, index: FuncIndex) -> &Function {
+    &self.functions[index as usize]
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Constant {
+  Unit,
+  Int(i128),
+  Float(f64),
+  Bool(bool),
+  Str(String),
+}
+
+impl Constant {
+  /// Whether the constants are interchangeable, where the floats are compared by their bits.
+  fn same(&self, other: &Constant) -> bool {
+    match (self, other) {
+      (Constant::Float(left), Constant::Float(right)) => left.to_bits() == right.to_bits(),
+      (left, right) => left == right,
+    }
+  }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Function {
+  /// Name of the function as a constant of string, such as `main` or `Point::norm`.
+  pub name:   ConstIndex,
+  /// Source file of the function as a constant of string, if known.
+  pub file:   Option<ConstIndex>,
+  /// Number of the arguments, which are the locals `1..=params`.
+  pub params: u16,
+  /// Number of the locals, including the return place and the arguments.
+  pub locals: u16,
+  pub code:   Vec<Instr>,
+  pub lines:  LineTable,
+}
+
+/// Lines and columns in the source of the instructions, recorded where they change.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LineTable {
+  /// Index of the first instruction at each location, in the ascending order.
+  pub entries: Vec<LineEntry>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LineEntry {
+  pub pc:     u32,
+  pub line:   u32,
+  pub column: u32,
+}
+
+impl LineTable {
+  /// Record the location of the instructions from the index, unless it is unchanged.
+  pub fn add(&mut self, pc: u32, line: u32, column: u32) {
+    match self.entries.last_mut() {
+      Some(last) if last.line == line && last.column == column => {}
+      Some(last) if last.pc == pc => {
+        last.line = line;
+        last.column = column;
+      }
+      _ => self.entries.push(LineEntry { pc, line, column }),
+    }
+  }
+
+  /// Location of the instruction at the index, if any is recorded up to it.
+  pub fn locate(&self, pc: u32) -> Option<(u32, u32)> {
+    let index = self.entries.partition_point(|entry| entry.pc <= pc);
+    let entry = self.entries.get(index.checked_sub(1)?)?;
+    Some((entry.line, entry.column))
+  }
+}
+
+/// Scalar type of the operands of the arithmetic, the comparisons and the conversions, by which
+/// the integers are checked or wrapped into their ranges.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum Scalar {
+  Bool,
+  Int8,
+  Int16,
+  Int32,
+  Int64,
+  Uint8,
+  Uint16,
+  Uint32,
+  Uint64,
+  Float,
+  Char,
+  /// Strings, raw pointers and aggregates, which are only compared.
+  Other,
+}
+
+impl Scalar {
+  const ALL: [Scalar; 12] = [
+    Scalar::Bool,
+    Scalar::Int8,
+    Scalar::Int16,
+    Scalar::Int32,
+    Scalar::Int64,
+    Scalar::Uint8,
+    Scalar::Uint16,
+    Scalar::Uint32,
+    Scalar::Uint64,
+    Scalar::Float,
+    Scalar::Char,
+    Scalar::Other,
+  ];
+
+  pub fn from_u8(byte: u8) -> Option<Scalar> {
+    Scalar::ALL.get(byte as usize).copied()
+  }
+
+  /// Primitive type of the scalar, into whose range the integers are checked and wrapped, where
+  /// `char` is unchecked as in the interpreter. Other values have no range, as `unit` does.
+  pub fn primitive(self) -> PrimitiveType {
+    match self {
+      Scalar::Bool => PrimitiveType::Bool,
+      Scalar::Int8 => PrimitiveType::Int8,
+      Scalar::Int16 => PrimitiveType::Int16,
+      Scalar::Int32 => PrimitiveType::Int32,
+      Scalar::Int64 => PrimitiveType::Int64,
+      Scalar::Uint8 => PrimitiveType::Uint8,
+      Scalar::Uint16 => PrimitiveType::Uint16,
+      Scalar::Uint32 => PrimitiveType::Uint32,
+      Scalar::Uint64 => PrimitiveType::Uint64,
+      Scalar::Float => PrimitiveType::Float64,
+      Scalar::Char => PrimitiveType::Char,
+      Scalar::Other => PrimitiveType::Unit,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum BinOp {
+  Add,
+  Sub,
+  Mul,
+  Div,
+  Rem,
+  Eq,
+  Ne,
+  Lt,
+  Le,
+  Gt,
+  Ge,
+}
+
+impl BinOp {
+  const ALL: [BinOp; 11] = [
+    BinOp::Add,
+    BinOp::Sub,
+    BinOp::Mul,
+    BinOp::Div,
+    BinOp::Rem,
+    BinOp::Eq,
+    BinOp::Ne,
+    BinOp::Lt,
+    BinOp::Le,
+    BinOp::Gt,
+    BinOp::Ge,
+  ];
+
+  pub fn from_u8(byte: u8) -> Option<BinOp> {
+    BinOp::ALL.get(byte as usize).copied()
+  }
+}
+
+/// Instruction of the stack machine. Operands are popped in the reverse order of their pushes,
+/// e.g. `Binary` pops the right operand first.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Instr {
+  /// Push the unit value.
+  Unit,
+  /// Push the constant.
+  Const(ConstIndex),
+  LoadLocal(u16),
+  /// Pop the value into the local.
+  StoreLocal(u16),
+  /// Push the raw pointer to the local.
+  AddrLocal(u16),
+  /// Pop the raw pointer to a struct, and push the raw pointer to its field.
+  AddrField(u16),
+  /// Pop the raw pointer, and push the value it points to.
+  Load,
+  /// Pop the value and then the raw pointer, and write the value where it points to.
+  Store,
+  /// Pop the struct, and push its field.
+  Field(u16),
+  Pop,
+  /// Negate the number.
+  Neg(Scalar),
+  /// Logical `not` of the boolean.
+  Not,
+  /// Pop the right operand and then the left one, and push the result of the operation. Raw
+  /// pointers are offset by `Add` and `Sub` with integers on the right.
+  Binary(BinOp, Scalar),
+  /// Convert the number into the scalar type.
+  Cast(Scalar),
+  /// Convert the integer or the raw pointer into a raw pointer, where only `0` converts into the
+  /// null pointer.
+  ToPointer,
+  /// Pop the fields of the struct in the order of their declaration, and push the struct.
+  Struct(u16),
+  /// Pop the value, and push the array of the number of its copies.
+  Array(u32),
+  /// Pop the raw pointer to an array, and push the raw pointer to its first element.
+  AddrElements,
+  /// Pop the elements of the number in order, and push the array of them.
+  ArrayOf(u32),
+  /// Pop the index and then the array of the length, and push its element. The index is checked
+  /// against the length.
+  Index(u32),
+  /// Pop the index and then the raw pointer to an array of the length, and push the raw pointer to
+  /// its element. The index is checked against the length.
+  AddrIndex(u32),
+  /// Pop the fields of the variant, and push the enum value of the variant.
+  Variant(u16, u16),
+  /// Pop the enum value, and push the index of its variant.
+  Discriminant,
+  /// Pop the enum value, which must be of the variant, and push its field.
+  VariantField(u16, u16),
+  /// Push the function as a value.
+  Function(FuncIndex),
+  /// Push the native as a value.
+  Native(u32),
+  /// Call the function with the number of the arguments popped, and push its return value.
+  Call(FuncIndex, u8),
+  CallNative(u32, u8),
+  /// Pop the arguments and then the function value, and call it.
+  CallIndirect(u8),
+  Jump(u32),
+  /// Pop the boolean, and jump unless it is true.
+  JumpIfNot(u32),
+  /// Return the value of the local `0`.
+  Return,
+  /// Trap, since the control never reaches here.
+  Unreachable,
+}
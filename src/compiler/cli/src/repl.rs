@@ -6,33 +6,56 @@ use vsp_error::VspError;
 use vsp_error::VspResult;
 
 pub fn do_run_repl() -> VspResult<()> {
-  let repl = REPL::new();
+  let mut repl = REPL::new();
   match repl.core_loop() {
     Ok(res) => Ok(res),
     Err(message) => Err(VspError::new(message)),
   }
 }
 
+/// Run the REPL, where the lines other than the applets are evaluated by the evaluator.
+pub fn do_run_repl_with(evaluator: Evaluator) -> VspResult<()> {
+  let mut repl = REPL::new();
+  repl.set_evaluator(evaluator);
+  match repl.core_loop() {
+    Ok(res) => Ok(res),
+    Err(message) => Err(VspError::new(message)),
+  }
+}
+
+/// Evaluator of the lines of the source codes, which returns the text to print if any.
+pub type Evaluator = Box<dyn FnMut(&str) -> Result<Option<String>, String>>;
+
 #[allow(non_snake_case)]
 pub struct REPL {
-  prompt: String,
+  prompt:    String,
+  evaluator: Option<Evaluator>,
 }
 
 impl REPL {
   pub fn new() -> Self {
     Self {
-      prompt: "> ".to_string(),
+      prompt:    "> ".to_string(),
+      evaluator: None,
     }
   }
 
+  pub fn set_evaluator(&mut self, evaluator: Evaluator) {
+    self.evaluator = Some(evaluator);
+  }
+
   pub fn set_prompt_string(&mut self, prompt: impl Into<String>) {
     self.prompt = prompt.into();
   }
 
   #[allow(unused_must_use)]
-  pub fn core_loop(&self) -> VspResult<(), String> {
+  pub fn core_loop(&mut self) -> VspResult<(), String> {
     loop {
       let line = readline().unwrap();
+      // End of the input.
+      if line.is_empty() {
+        break;
+      }
       let line = line.trim();
       if line.is_empty() {
         continue;
@@ -59,7 +82,21 @@ impl REPL {
   }
 
   #[allow(unused_must_use)]
-  pub fn eval(&self, line: &str) -> Result<bool, String> {
+  pub fn eval(&mut self, line: &str) -> Result<bool, String> {
+    if let Some(evaluator) = &mut self.evaluator {
+      let name = line.split_whitespace().next().unwrap_or_default();
+      if !cli().get_subcommands().any(|applet| {
+        applet.get_name() == name || applet.get_all_aliases().any(|alias| alias == name)
+      }) {
+        match evaluator(line) {
+          Ok(Some(text)) => writeln!(stdout(), "{}", text),
+          Ok(None) => Ok(()),
+          Err(message) => writeln!(stdout(), "{}", message.trim_end()),
+        }
+        .map_err(|e| e.to_string())?;
+        return Ok(false);
+      }
+    }
     let args = shlex::split(line).ok_or("[ERROR]: Invalid quoting, please check your input.")?;
     let matches = cli().try_get_matches_from(args).map_err(|e| e.to_string())?;
     match matches.subcommand() {
@@ -0,0 +1,86 @@
+//! Compilation into the portable bytecode modules, which are written into the `.vspb` files or run
+//! by the VM. Unlike the code generation by LLVM, it is always built.
+use std::path::Path;
+use std::path::PathBuf;
+
+use vsp_ast::ast::CompilationUnit;
+use vsp_bytecode::build::build_module;
+use vsp_bytecode::format;
+use vsp_bytecode::module::Module;
+use vsp_error::VspError;
+use vsp_error::VspResult;
+use vsp_mir::mir::Program;
+use vsp_sema::check::TypeckResults;
+use vsp_vm::vm::Vm;
+
+use crate::source::loader::ModuleLoader;
+use crate::CompilerInstance;
+
+impl CompilerInstance {
+  /// Load and check the package rooted at the file, and compile it into a bytecode module.
+  pub fn compile_bytecode(&mut self, file: &Path) -> VspResult<Module> {
+    let unit = ModuleLoader::new(&mut self.source_manager).load(file)?;
+    let (program, results) = self.check_and_lower_unit(&unit, file)?;
+    self.build_bytecode(&unit, &program, &results)
+  }
+
+  /// Compile the lowered package into a bytecode module, which keeps the shebang of the script so
+  /// that the module runs as the script does.
+  pub(crate) fn build_bytecode(
+    &self,
+    unit: &CompilationUnit,
+    program: &Program,
+    results: &TypeckResults,
+  ) -> VspResult<Module> {
+    let mut module = build_module(program, results, self.target_options.overflow_checks())?;
+    module.shebang = unit.shebang.clone();
+    Ok(module)
+  }
+
+  /// Compile the package rooted at the file into a bytecode module, and write it into the output
+  /// directory, named after the package.
+  pub fn emit_bytecode(&mut self, file: &Path) -> VspResult<PathBuf> {
+    let module = self.compile_bytecode(file)?;
+    self.write_bytecode(&module, &self.output_name(file))
+  }
+
+  /// Write the module into `<name>.vspb` under the output directory. Modules of the scripts are
+  /// made executable, as their shebang runs them.
+  pub(crate) fn write_bytecode(&self, module: &Module, name: &str) -> VspResult<PathBuf> {
+    let dir = self.target_options.output_dir();
+    std::fs::create_dir_all(&dir).map_err(VspError::from)?;
+    let path = dir.join(format!("{}.{}", name, format::EXTENSION));
+    std::fs::write(&path, format::write(module)).map_err(VspError::from)?;
+    #[cfg(unix)]
+    if module.shebang.is_some() {
+      use std::os::unix::fs::PermissionsExt;
+
+      let permissions = std::fs::Permissions::from_mode(0o755);
+      std::fs::set_permissions(&path, permissions).map_err(VspError::from)?;
+    }
+    Ok(path)
+  }
+
+  /// Load and check the package rooted at the file, compile it into a bytecode module, and run its
+  /// `main` by the VM with the arguments of the command line, which start with the name of the
+  /// program. Return the exit code of the program.
+  ///
+  /// As in the interpreter, foreign functions are bound to the natives instead of the libraries of
+  /// the manifest.
+  pub fn run_bytecode(&mut self, file: &Path, args: &[String]) -> VspResult<i32> {
+    let module = self.compile_bytecode(file)?;
+    let mut vm = Vm::new(&module);
+    vm.run_main(args)
+  }
+}
+
+/// Read the bytecode module from the `.vspb` file, and run its `main` by the VM with the arguments
+/// of the command line, which start with the name of the program. Return the exit code of the
+/// program.
+pub fn run_module_file(file: &Path, args: &[String]) -> VspResult<i32> {
+  let bytes = std::fs::read(file).map_err(VspError::from)?;
+  let module = format::read(&bytes)
+    .map_err(|error| VspError::new(format!("{}: {}", file.display(), error.message())))?;
+  let mut vm = Vm::new(&module);
+  vm.run_main(args)
+}
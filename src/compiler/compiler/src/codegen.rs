@@ -0,0 +1,232 @@
+//! Code generation by LLVM, which compiles the checked packages into the output files of the
+//! target, or runs them by the JIT. It is only built with the `llvm` feature.
+use std::path::Path;
+
+use inkwell::context::Context;
+use inkwell::targets::TargetMachine;
+use vsp_error::VspError;
+use vsp_error::VspResult;
+use vsp_llvm::llvm::ir::OverflowMode;
+use vsp_llvm::llvm::jit::load_library;
+use vsp_llvm::llvm::jit::run_main;
+use vsp_llvm::llvm::opt::timing_report;
+use vsp_llvm::llvm::opt::Pipeline;
+use vsp_llvm::llvm::runtime::define_output_functions;
+use vsp_llvm::llvm::spec::target_spec;
+use vsp_llvm::llvm::target::code_model;
+use vsp_llvm::llvm::target::create_target_machine;
+use vsp_llvm::llvm::target::native_target_machine;
+use vsp_llvm::llvm::target::reloc_mode;
+use vsp_llvm::llvm::target::MachineOptions;
+use vsp_llvm::llvm::CodegenContext;
+use vsp_pm::manifest::Manifest;
+
+use crate::link::default_sysroot;
+use crate::link::runtime_objects;
+use crate::link::Linker;
+use crate::link::LinkerFlavor;
+use crate::option::EmitKind;
+use crate::option::OptLevel;
+use crate::source::loader::ModuleLoader;
+use crate::CompilerInstance;
+
+/// Libraries linked into the compiler, which the programs run by the JIT share with it.
+const PROCESS_LIBRARIES: &[&str] = &["c", "m", "dl", "pthread", "rt"];
+
+impl CompilerInstance {
+  /// Compile the package rooted at the file into an LLVM module for the target, optimize it, and
+  /// emit the output files under the output directory.
+  pub fn run(&mut self, file: &Path) -> VspResult<()> {
+    let pipeline = self.pipeline()?;
+    let machine = self.create_target_machine(&pipeline)?;
+    let unit = ModuleLoader::new(&mut self.source_manager).load(file)?;
+    let (program, results) = self.check_and_lower_unit(&unit, file)?;
+    let name = self.output_name(file);
+    if self.target_options.emit().contains(&EmitKind::Bytecode) {
+      let module = self.build_bytecode(&unit, &program, &results)?;
+      self.write_bytecode(&module, &name)?;
+    }
+
+    let context = Context::create();
+    let stem = file.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+    let mut codegen = CodegenContext::new(stem.to_string(), &context);
+    codegen.set_target(&machine);
+    if *self.target_options.debuginfo() {
+      let optimized = *self.target_options.optimization() != OptLevel::O0;
+      codegen.enable_debug_info(file, optimized);
+    }
+    let overflow = OverflowMode::from_overflow_checks(self.target_options.overflow_checks());
+    codegen.add_program(&program, &results, overflow)?;
+    define_output_functions(codegen.module());
+    self.optimize(&codegen, &pipeline, &machine)?;
+    self.emit(&codegen, &machine, file, &name)?;
+
+    Ok(())
+  }
+
+  /// Compile the package rooted at the file into an LLVM module for the host, optimize it, and run
+  /// its `main` by the JIT with the arguments of the command line, which start with the name of the
+  /// program. Return the exit code of the program.
+  ///
+  /// Nothing is linked, but the libraries of the manifest and the options are loaded into the
+  /// process, for the foreign functions to be resolved against them.
+  pub fn execute(&mut self, file: &Path, args: &[String]) -> VspResult<i32> {
+    let pipeline = self.pipeline()?;
+    let machine = native_target_machine(pipeline.codegen_level())?;
+    let (program, results) = self.check_and_lower(file)?;
+
+    let context = Context::create();
+    let name = file.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+    let codegen = CodegenContext::new(name.to_string(), &context);
+    codegen.set_target(&machine);
+    let overflow = OverflowMode::from_overflow_checks(self.target_options.overflow_checks());
+    codegen.add_program(&program, &results, overflow)?;
+    self.optimize(&codegen, &pipeline, &machine)?;
+    for library in self.libraries(file)? {
+      load_library(&library)?;
+    }
+    run_main(codegen.module(), pipeline.codegen_level(), args)
+  }
+
+  /// Pipeline of the optimization level, or of the passes given instead.
+  fn pipeline(&self) -> VspResult<Pipeline> {
+    let options = &self.target_options;
+    let speed = options.optimization().speed_level();
+    let size = options.optimization().size_level();
+    match options.passes() {
+      Some(passes) => Pipeline::custom(passes, speed, size),
+      None => Ok(Pipeline::default_for(speed, size)),
+    }
+  }
+
+  /// Target machine of the target triple by the codegen options.
+  fn create_target_machine(&self, pipeline: &Pipeline) -> VspResult<TargetMachine> {
+    let options = &self.target_options;
+    let name_or =
+      |name: &Option<String>, default: &str| name.clone().unwrap_or_else(|| default.into());
+    create_target_machine(&MachineOptions {
+      triple:     options.target_triple().to_string(),
+      cpu:        options.target_cpu().clone().unwrap_or_default(),
+      features:   options.target_features().join(","),
+      reloc:      reloc_mode(&name_or(options.relocation_model(), "pic"))?,
+      code_model: code_model(&name_or(options.code_model(), "default"))?,
+      level:      pipeline.codegen_level(),
+    })
+  }
+
+  /// Optimize the module by the pipeline, and print the time spent by each pass if asked.
+  fn optimize(
+    &self,
+    codegen: &CodegenContext,
+    pipeline: &Pipeline,
+    machine: &TargetMachine,
+  ) -> VspResult<()> {
+    let timings = codegen.optimize(pipeline, machine)?;
+    if *self.target_options.time_passes() {
+      eprint!("{}", timing_report(&timings));
+    }
+    Ok(())
+  }
+
+  /// Write the output files of the kinds to emit, named after the package, into the output
+  /// directory `<target-dir>/<triple>/<profile>`. The object file is written as well if the
+  /// artifact is linked.
+  fn emit(
+    &self,
+    codegen: &CodegenContext,
+    machine: &TargetMachine,
+    file: &Path,
+    name: &str,
+  ) -> VspResult<()> {
+    let dir = self.target_options.output_dir();
+    std::fs::create_dir_all(&dir).map_err(VspError::from)?;
+    let emit = self.target_options.emit();
+    let object = dir.join(format!("{}.o", name));
+    if emit.contains(&EmitKind::Link) && !emit.contains(&EmitKind::Object) {
+      codegen.emit_object(machine, &object)?;
+    }
+    for kind in emit {
+      let path = match kind.extension() {
+        Some(extension) => dir.join(format!("{}.{}", name, extension)),
+        None => continue,
+      };
+      match kind {
+        EmitKind::Object => codegen.emit_object(machine, &path)?,
+        EmitKind::Assembly => codegen.emit_assembly(machine, &path)?,
+        EmitKind::LlvmBitcode => codegen.emit_bitcode(&path)?,
+        EmitKind::LlvmIr => codegen.emit_ir(&path)?,
+        // Bytecode modules are written along with the MIR, before the code generation.
+        EmitKind::Bytecode => {}
+        EmitKind::Link => unreachable!(),
+      }
+    }
+    if emit.contains(&EmitKind::Link) {
+      let output = dir.join(self.target_options.crate_type().file_name(name));
+      self.link(file, &object, &output)?;
+    }
+    Ok(())
+  }
+
+  /// Link the object file into the artifact of the crate type, along with the runtime and stdlib
+  /// objects of the sysroot, and the libraries of the manifest and the options. Artifacts of the
+  /// other targets than the host are linked by the linker of the target spec, unless the linker or
+  /// its flavor is given.
+  fn link(&self, file: &Path, object: &Path, output: &Path) -> VspResult<()> {
+    let options = &self.target_options;
+    let triple = options.target_triple().to_string();
+    let spec = target_spec(&triple).filter(|_| options.target_triple() != options.host_triple());
+    let flavor = match (options.linker_flavor(), spec) {
+      (Some(flavor), _) => *flavor,
+      (None, Some(spec)) => spec.linker_flavor.parse().map_err(VspError::new)?,
+      (None, None) => LinkerFlavor::Cc,
+    };
+    let mut linker = Linker::new(flavor, *options.crate_type(), output);
+    match (options.linker(), spec) {
+      (Some(program), _) => {
+        linker.set_program(program.clone());
+      }
+      (None, Some(spec)) if options.linker_flavor().is_none() => {
+        linker.set_program(spec.linker);
+      }
+      _ => {}
+    }
+    linker.add_object(object);
+    if let Some(sysroot) = options.sysroot().clone().or_else(default_sysroot) {
+      for object in runtime_objects(&sysroot, &triple)? {
+        linker.add_object(object);
+      }
+    }
+    let manifest = file.parent().unwrap_or_else(|| Path::new("")).join("manifest.toml");
+    if manifest.is_file() {
+      linker.add_flags(Manifest::load(&manifest)?.link().flags());
+    }
+    linker.add_flags(options.link_args().iter().cloned());
+    if *options.print_link_args() {
+      println!("{}", linker.command());
+    }
+    linker.link()
+  }
+
+  /// Shared libraries to load for the JIT, named by the `-l` flags of the manifest and the options,
+  /// and found in the directories of the `-L` flags, or else by the dynamic loader. The libraries
+  /// linked into the compiler itself, such as the C library, are already loaded.
+  fn libraries(&self, file: &Path) -> VspResult<Vec<String>> {
+    let mut flags = Vec::new();
+    let manifest = file.parent().unwrap_or_else(|| Path::new("")).join("manifest.toml");
+    if manifest.is_file() {
+      flags.extend(Manifest::load(&manifest)?.link().flags());
+    }
+    flags.extend(self.target_options.link_args().iter().cloned());
+    let search_paths: Vec<&str> = flags.iter().filter_map(|flag| flag.strip_prefix("-L")).collect();
+    let names = flags.iter().filter_map(|flag| flag.strip_prefix("-l"));
+    let libraries = names.filter(|name| !PROCESS_LIBRARIES.contains(name)).map(|name| {
+      let file_name = format!("lib{}.so", name);
+      let path = search_paths
+        .iter()
+        .map(|path| Path::new(path).join(&file_name))
+        .find(|path| path.is_file());
+      path.map_or(file_name, |path| path.to_string_lossy().into_owned())
+    });
+    Ok(libraries.collect())
+  }
+}
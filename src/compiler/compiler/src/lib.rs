@@ -1,28 +1,42 @@
 use std::io::Read;
+use std::path::Path;
 use std::path::PathBuf;
 
 use getset::Getters;
 use getset::Setters;
+use vsp_ast::ast::CompilationUnit;
 use vsp_ast_parser::parser::ASTParser;
 use vsp_diag::DiagnosticEngine;
 use vsp_error::VspError;
 use vsp_error::VspResult;
 use vsp_fs::manager::VFSManager;
+use vsp_interp::interp::Interpreter;
+use vsp_interp::interp::STACK_SIZE;
+use vsp_sema::check::TypeckResults;
 
 use crate::dispatch::CompilationDispatcher;
+use crate::option::EmitKind;
 use crate::option::LangOptions;
 use crate::option::TargetOptions;
+use crate::source::loader::ModuleLoader;
 use crate::source::SourceManager;
 
 pub mod action;
+pub mod bytecode;
+#[cfg(feature = "llvm")]
+mod codegen;
 pub mod db;
 pub mod dispatch;
+pub mod link;
 pub mod option;
+pub mod session;
 pub mod source;
 pub mod sym;
+#[cfg(test)]
+mod testing;
 
-/// Entrypoint to compile the source codes.
-pub fn start_compile(filename: &PathBuf, target_options: TargetOptions) -> VspResult<()> {
+/// Entrypoint to compile the source codes. Bytecode modules alone are emitted without LLVM.
+pub fn start_compile(filename: &Path, target_options: TargetOptions) -> VspResult<()> {
   let dispatcher = CompilationDispatcher::default();
   let mut compiler = CompilerInstance::from(dispatcher, target_options);
 
@@ -32,9 +46,73 @@ pub fn start_compile(filename: &PathBuf, target_options: TargetOptions) -> VspRe
   #[cfg(debug_assertions)]
   compiler.debug_print_status();
 
+  let emit = compiler.target_options.emit();
+  if emit.iter().all(|kind| *kind == EmitKind::Bytecode) {
+    compiler.emit_bytecode(filename)?;
+    return Ok(());
+  }
+  start_codegen(&mut compiler, filename)
+}
+
+#[cfg(feature = "llvm")]
+fn start_codegen(compiler: &mut CompilerInstance, filename: &Path) -> VspResult<()> {
   compiler.run(filename)
 }
 
+/// Without LLVM, only the bytecode modules are emitted.
+#[cfg(not(feature = "llvm"))]
+fn start_codegen(_: &mut CompilerInstance, _: &Path) -> VspResult<()> {
+  VspError::new("only bytecode can be emitted, as the compiler is built without LLVM").to_err()
+}
+
+/// Entrypoint to run the program of the source codes by the JIT, with the arguments of the command
+/// line which start with the name of the program. Return the exit code of the program.
+#[cfg(feature = "llvm")]
+pub fn start_run(
+  filename: &Path,
+  target_options: TargetOptions,
+  args: &[String],
+) -> VspResult<i32> {
+  let mut compiler = CompilerInstance::from(CompilationDispatcher, target_options);
+
+  compiler.create_preprocessor();
+  compiler.create_ast_context();
+
+  compiler.execute(filename, args)
+}
+
+/// Entrypoint to run the program of the source codes by the interpreter, which does not require
+/// LLVM, with the arguments of the command line which start with the name of the program. Return
+/// the exit code of the program.
+pub fn start_interpret(
+  filename: &Path,
+  target_options: TargetOptions,
+  args: &[String],
+) -> VspResult<i32> {
+  let mut compiler = CompilerInstance::from(CompilationDispatcher, target_options);
+
+  compiler.create_preprocessor();
+  compiler.create_ast_context();
+
+  compiler.interpret(filename, args)
+}
+
+/// Entrypoint to run the program of the source codes by the VM, after compiling it into a bytecode
+/// module, with the arguments of the command line which start with the name of the program. Return
+/// the exit code of the program.
+pub fn start_bytecode(
+  filename: &Path,
+  target_options: TargetOptions,
+  args: &[String],
+) -> VspResult<i32> {
+  let mut compiler = CompilerInstance::from(CompilationDispatcher, target_options);
+
+  compiler.create_preprocessor();
+  compiler.create_ast_context();
+
+  compiler.run_bytecode(filename, args)
+}
+
 type ASTContext = ();
 type InMemoryModuleCache = ();
 
@@ -89,16 +167,120 @@ impl CompilerInstance {
     self.target_options.debug_print_status();
   }
 
-  pub fn run(&mut self, file: &PathBuf) -> VspResult<()> {
-    use vsp_fs::path::VFSPath;
+  /// Load and check the package rooted at the file, and lower it into the MIR.
+  pub fn lower_to_mir(&mut self, file: &Path) -> VspResult<vsp_mir::mir::Program> {
+    let (program, _) = self.check_and_lower(file)?;
+    Ok(program)
+  }
+
+  /// Load and check the package rooted at the file, and lower it into the MIR along with the
+  /// results of the type checking.
+  fn check_and_lower(&mut self, file: &Path) -> VspResult<(vsp_mir::mir::Program, TypeckResults)> {
+    let unit = ModuleLoader::new(&mut self.source_manager).load(file)?;
+    self.check_and_lower_unit(&unit, file)
+  }
 
-    let path = VFSPath::from(file.to_str().unwrap());
-    let mut file = self.vfs_manager.get_file(&path).unwrap();
-    let main_file = self.source_manager.create_main_file_id(file.as_ref());
+  /// Check the package loaded from the file, and lower it into the MIR along with the results of
+  /// the type checking.
+  fn check_and_lower_unit(
+    &mut self,
+    unit: &CompilationUnit,
+    file: &Path,
+  ) -> VspResult<(vsp_mir::mir::Program, TypeckResults)> {
+    let typeck_results = vsp_sema::check_compilation_unit(unit, &mut self.diagnostics);
+    self.report_diagnostics(file)?;
+    let program = vsp_hir::lower_compilation_unit(unit, &typeck_results);
+    let program = vsp_mir::build::build_program(&program, &mut self.diagnostics);
+    self.report_diagnostics(file)?;
 
-    let mut buf = String::new();
-    file.as_mut().read_to_string(&mut buf).unwrap();
+    Ok((program, typeck_results))
+  }
+
+  /// Load and check the package rooted at the file, and run its `main` by the interpreter with the
+  /// arguments of the command line, which start with the name of the program. Return the exit code
+  /// of the program.
+  ///
+  /// The package is lowered into the MIR as well, for the checks on the MIR to run, but the
+  /// interpreter evaluates the typed HIR. Foreign functions are bound to the natives of the
+  /// interpreter instead of the libraries of the manifest. The arithmetic overflows as the code
+  /// compiled at the optimization level does.
+  pub fn interpret(&mut self, file: &Path, args: &[String]) -> VspResult<i32> {
+    let (program, results) = self.check_and_lower_hir(file)?;
+    let args = args.to_vec();
+    let overflow_checks = self.target_options.overflow_checks();
+    // The interpreter recurses along with the calls of the program, which the default stack of the
+    // threads is too small for.
+    let thread = std::thread::Builder::new()
+      .stack_size(STACK_SIZE)
+      .spawn(move || {
+        let mut interpreter = Interpreter::new(&program, results.items());
+        interpreter.set_overflow_checks(overflow_checks);
+        interpreter.run_main(&args)
+      })
+      .map_err(VspError::from)?;
+    thread
+      .join()
+      .unwrap_or_else(|_| VspError::new("the interpreter panicked").to_err())
+  }
+
+  /// Load and check the package rooted at the file, and lower it into the HIR along with the
+  /// results of the type checking, after the checks on the MIR.
+  fn check_and_lower_hir(
+    &mut self,
+    file: &Path,
+  ) -> VspResult<(vsp_hir::hir::Program, TypeckResults)> {
+    let unit = ModuleLoader::new(&mut self.source_manager).load(file)?;
+
+    let typeck_results = vsp_sema::check_compilation_unit(&unit, &mut self.diagnostics);
+    self.report_diagnostics(file)?;
+    let program = vsp_hir::lower_compilation_unit(&unit, &typeck_results);
+    vsp_mir::build::build_program(&program, &mut self.diagnostics);
+    self.report_diagnostics(file)?;
+
+    Ok((program, typeck_results))
+  }
+
+  /// Load and check the package rooted at the file, and generate the C header of its exported
+  /// functions and `@Repr(C)` structs, named after the file.
+  pub fn generate_c_header(&mut self, file: &Path) -> VspResult<String> {
+    let unit = ModuleLoader::new(&mut self.source_manager).load(file)?;
+
+    let typeck_results = vsp_sema::check_compilation_unit(&unit, &mut self.diagnostics);
+    self.report_diagnostics(file)?;
+    let name = file.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+    let header = vsp_sema::header::generate_c_header(&unit, &typeck_results, &name);
+
+    Ok(header)
+  }
+
+  /// Name of the output files of the package rooted at the file, which is the binary name if given,
+  /// or the name of the file.
+  fn output_name(&self, file: &Path) -> String {
+    match self.target_options.binary() {
+      Some(binary) => binary.to_string(),
+      None => file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default(),
+    }
+  }
 
+  /// Print the diagnostics reported so far, and fail if any of them is an error.
+  fn report_diagnostics(&mut self, file: &Path) -> VspResult<()> {
+    let source_manager = &self.source_manager;
+    eprint!(
+      "{}",
+      self.diagnostics.render_files(|filename| source_manager.get_source(filename))
+    );
+    if self.diagnostics.has_errors() {
+      return VspError::new(format!(
+        "could not compile `{}` due to {} previous error(s)",
+        file.display(),
+        self.diagnostics.error_count()
+      ))
+      .to_err();
+    }
+    self.diagnostics.take();
     Ok(())
   }
 }
@@ -122,3 +304,701 @@ impl SimpleCompilerInstance {
     Ok(())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  #[cfg(feature = "llvm")]
+  use target_lexicon::Triple;
+  #[cfg(feature = "llvm")]
+  use vsp_llvm::llvm::spec::target_spec;
+
+  use super::*;
+  #[cfg(feature = "llvm")]
+  use crate::link::CrateType;
+  use crate::option::OptLevel;
+  use crate::testing::TempDir;
+
+  #[test]
+  #[cfg(feature = "llvm")]
+  fn test_emit() {
+    let root = TempDir::new("emit");
+    let file = root.join("square.vsp");
+    std::fs::write(&file, "func main(n: int64): int64 { return n * n; }").unwrap();
+
+    let mut options = TargetOptions::default();
+    options.set_optimization(OptLevel::O1);
+    options.set_target_dir(root.join("target"));
+    options.set_emit(vec![
+      EmitKind::Object,
+      EmitKind::Assembly,
+      EmitKind::LlvmBitcode,
+      EmitKind::LlvmIr,
+    ]);
+    options.set_codegen_option("target-cpu=x86-64").unwrap();
+    options.set_codegen_option("target-feature=+avx2").unwrap();
+    options.set_codegen_option("relocation-model=pic").unwrap();
+    assert_eq!(
+      options.set_codegen_option("target-cpu"),
+      Err("codegen option `target-cpu` requires a value".to_owned())
+    );
+    let dir = root.join("target").join(Triple::host().to_string()).join("release");
+    assert_eq!(options.output_dir(), dir);
+
+    let mut compiler = CompilerInstance::from(CompilationDispatcher, options);
+    compiler.run(&file).unwrap();
+    let object = std::fs::read(dir.join("square.o")).unwrap();
+    assert!(object.starts_with(b"\x7fELF"));
+    let assembly = std::fs::read_to_string(dir.join("square.s")).unwrap();
+    assert!(assembly.contains("main:"), "{}", assembly);
+    let bitcode = std::fs::read(dir.join("square.bc")).unwrap();
+    assert!(bitcode.starts_with(b"BC\xc0\xde"));
+    let ir = std::fs::read_to_string(dir.join("square.ll")).unwrap();
+    assert!(
+      ir.contains(&format!("target triple = \"{}\"", Triple::host())),
+      "{}",
+      ir
+    );
+  }
+
+  #[test]
+  #[cfg(feature = "llvm")]
+  fn test_link() {
+    let root = TempDir::new("link");
+    let file = root.join("exit.vsp");
+    std::fs::write(
+      &file,
+      "func main(): int32 { unsafe { puts(\"linked\"); return sqrt(16.0) as int32 + 3; } }
+       extern \"C\" func puts(s: str): int32;
+       extern \"C\" func sqrt(x: float64): float64;",
+    )
+    .unwrap();
+    std::fs::write(
+      root.join("manifest.toml"),
+      "[project]\nname = \"exit\"\nversion = \"0.1.0\"\n[link]\nlibraries = [\"m\"]\n",
+    )
+    .unwrap();
+
+    for crate_type in [
+      CrateType::Executable,
+      CrateType::StaticLib,
+      CrateType::SharedLib,
+    ] {
+      let mut options = TargetOptions::default();
+      options.set_target_dir(root.join("target"));
+      options.set_crate_type(crate_type);
+      options.set_codegen_option("link-arg=-Wl,--as-needed").unwrap();
+      let dir = options.output_dir();
+      let mut compiler = CompilerInstance::from(CompilationDispatcher, options);
+      compiler.run(&file).unwrap();
+      assert!(dir.join(crate_type.file_name("exit")).is_file());
+    }
+
+    let executable = root.join("target").join(Triple::host().to_string()).join("release/exit");
+    let output = std::process::Command::new(executable).output().unwrap();
+    assert_eq!(output.status.code(), Some(7));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "linked\n");
+  }
+
+  #[test]
+  #[cfg(feature = "llvm")]
+  fn test_hello_world() {
+    let root = TempDir::new("hello");
+    let file = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../example/HelloWorld.vsp");
+    let mut options = TargetOptions::default();
+    options.set_target_dir(root.join("target"));
+    let dir = options.output_dir();
+    let mut compiler = CompilerInstance::from(CompilationDispatcher, options);
+    compiler.run(&file).unwrap();
+
+    // `println` of the prelude is defined by the module, without any runtime object.
+    let output = std::process::Command::new(dir.join("HelloWorld")).output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Hello World!!\n");
+  }
+
+  #[test]
+  #[cfg(feature = "llvm")]
+  fn test_overflow_panic() {
+    let root = TempDir::new("overflow");
+    let file = root.join("overflow.vsp");
+    std::fs::write(
+      &file,
+      "func main(argc: int32, argv: *str): int32 {\n  return argc + 2147483647;\n}",
+    )
+    .unwrap();
+    let bounds = root.join("bounds.vsp");
+    std::fs::write(
+      &bounds,
+      "func main(argc: int32, argv: *str): int64 {\n  let values = [1, 2, 3];\n  return values[argc + 2];\n}",
+    )
+    .unwrap();
+    let mut options = TargetOptions::default();
+    options.set_optimization(OptLevel::O0);
+    options.set_target_dir(root.join("target"));
+    let dir = options.output_dir();
+    let mut compiler = CompilerInstance::from(CompilationDispatcher, options);
+    compiler.run(&file).unwrap();
+    compiler.run(&bounds).unwrap();
+
+    // The failed checks report the locations as the interpreter does, rather than trapping.
+    let output = std::process::Command::new(dir.join("overflow")).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+      stderr.ends_with("overflow.vsp:2:10: attempt to add with overflow\n"),
+      "{}",
+      stderr
+    );
+    let message = "bounds.vsp:3:10: index out of bounds: the length is 3 but the index is 3";
+    let output = std::process::Command::new(dir.join("bounds")).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.ends_with(&format!("{}\n", message)), "{}", stderr);
+    let args = vec!["bounds".to_owned()];
+    let interpreted = compiler.interpret(&bounds, &args).unwrap_err().message();
+    assert!(interpreted.ends_with(message), "{}", interpreted);
+    let bytecode = compiler.run_bytecode(&bounds, &args).unwrap_err().message();
+    assert!(bytecode.ends_with(message), "{}", bytecode);
+  }
+
+  #[test]
+  #[cfg(feature = "llvm")]
+  fn test_cross_compile() {
+    let root = TempDir::new("cross");
+    let file = root.join("cross.vsp");
+    std::fs::write(
+      &file,
+      "extern \"C\" func twice(n: int32): int32 { unsafe { return abs(n) * 2; } }
+       extern \"C\" func abs(n: int32): int32;",
+    )
+    .unwrap();
+
+    for (triple, format) in [
+      ("x86_64-unknown-linux-musl", "elf64-x86-64"),
+      ("aarch64-unknown-linux-gnu", "elf64-littleaarch64"),
+      ("riscv64gc-unknown-linux-gnu", "elf64-littleriscv"),
+      ("wasm32-unknown-unknown", "wasm"),
+    ] {
+      let mut options = TargetOptions::default();
+      options.set_target_triple(triple.parse().unwrap());
+      options.set_target_dir(root.join("target"));
+      options.set_emit(vec![EmitKind::Object, EmitKind::LlvmIr]);
+      let dir = options.output_dir();
+      let mut compiler = CompilerInstance::from(CompilationDispatcher, options);
+      compiler.run(&file).unwrap();
+
+      let object = dir.join("cross.o");
+      let magic: &[u8] = if format == "wasm" {
+        b"\0asm"
+      } else {
+        b"\x7fELF"
+      };
+      assert!(
+        std::fs::read(&object).unwrap().starts_with(magic),
+        "{}",
+        triple
+      );
+      let output = std::process::Command::new("llvm-objdump")
+        .arg("-f")
+        .arg(&object)
+        .output()
+        .unwrap();
+      let output = String::from_utf8_lossy(&output.stdout);
+      assert!(
+        output.contains(&format!("file format {}", format)),
+        "{}",
+        output
+      );
+      let ir = std::fs::read_to_string(dir.join("cross.ll")).unwrap();
+      let spec = target_spec(triple).unwrap();
+      assert!(
+        ir.contains(&format!("target triple = \"{}\"", spec.llvm_triple)),
+        "{}",
+        ir
+      );
+      assert!(ir.contains(spec.data_layout), "{}", ir);
+    }
+
+    let ir =
+      std::fs::read_to_string(root.join("target/riscv64gc-unknown-linux-gnu/release/cross.ll"))
+        .unwrap();
+    assert!(ir.contains("!\"target-abi\", !\"lp64d\""), "{}", ir);
+    let object = std::fs::read(root.join("target/riscv64gc-unknown-linux-gnu/release/cross.o"));
+    // `EF_RISCV_RVC | EF_RISCV_FLOAT_ABI_DOUBLE`
+    assert_eq!(object.unwrap()[48..52], [0x5, 0, 0, 0]);
+
+    std::fs::write(
+      &file,
+      "@Repr(C) struct Point { x: int32, y: int32 }
+       extern \"C\" func norm(p: Point): int32;
+       func main(): int32 { unsafe { return norm(Point { x: 3, y: 4 }); } }",
+    )
+    .unwrap();
+    let mut options = TargetOptions::default();
+    options.set_target_triple("aarch64-unknown-linux-gnu".parse().unwrap());
+    options.set_target_dir(root.join("target"));
+    options.set_emit(vec![EmitKind::Object]);
+    let mut compiler = CompilerInstance::from(CompilationDispatcher, options);
+    assert_eq!(
+      compiler.run(&file).unwrap_err().message(),
+      "function `norm` passes `Point` by value, which the C ABI of `aarch64-unknown-linux-gnu` \
+       does not support yet"
+    );
+  }
+
+  #[test]
+  #[cfg(feature = "llvm")]
+  fn test_debuginfo() {
+    let root = TempDir::new("debuginfo");
+    let file = root.join("point.vsp");
+    std::fs::write(
+      &file,
+      "struct Point { x: int64, y: int64 }
+func main(n: int64): int64 {
+  let p = Point { x: n, y: 2 };
+  if n > 0 {
+    let sum = p.x + p.y;
+    return sum;
+  }
+  return p.x;
+}",
+    )
+    .unwrap();
+
+    let mut object = PathBuf::new();
+    for level in [OptLevel::O2, OptLevel::O0] {
+      let mut options = TargetOptions::default();
+      options.set_target_dir(root.join("target"));
+      options.set_optimization(level);
+      options.set_debuginfo(true);
+      options.set_emit(vec![EmitKind::Object, EmitKind::LlvmIr]);
+      let dir = options.output_dir();
+      let mut compiler = CompilerInstance::from(CompilationDispatcher, options);
+      compiler.run(&file).unwrap();
+      let ir = std::fs::read_to_string(dir.join("point.ll")).unwrap();
+      assert!(ir.contains("!DICompileUnit("), "{}", ir);
+      assert!(ir.contains("!DIFile(filename: \"point.vsp\""), "{}", ir);
+      assert!(ir.contains("name: \"main\""), "{}", ir);
+      assert!(ir.contains("!\"Debug Info Version\", i32 3"), "{}", ir);
+      if level == OptLevel::O0 {
+        assert!(ir.contains("!DILexicalBlock("), "{}", ir);
+        assert!(ir.contains("!DILocalVariable(name: \"sum\""), "{}", ir);
+        assert!(
+          ir.contains("!DILocalVariable(name: \"n\", arg: 1"),
+          "{}",
+          ir
+        );
+      }
+      object = dir.join("point.o");
+    }
+
+    let dump = |args: &[&str]| {
+      let output = std::process::Command::new("llvm-dwarfdump")
+        .args(args)
+        .arg(&object)
+        .output()
+        .unwrap();
+      String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    let lines = dump(&["--debug-line"]);
+    assert!(lines.contains("point.vsp"), "{}", lines);
+    // The lines of `let p`, `let sum` and `return p.x`.
+    for line in [3, 5, 8] {
+      assert!(
+        lines
+          .lines()
+          .any(|row| row.split_whitespace().nth(1) == Some(&line.to_string())),
+        "{}",
+        lines
+      );
+    }
+    let info = dump(&["--debug-info"]);
+    for name in ["\"p\"", "\"sum\"", "\"Point\"", "\"x\"", "\"y\""] {
+      assert!(info.contains(name), "{} in {}", name, info);
+    }
+    assert!(info.contains("DW_TAG_lexical_block"), "{}", info);
+    assert!(info.contains("DW_TAG_structure_type"), "{}", info);
+  }
+
+  #[test]
+  #[cfg(feature = "llvm")]
+  fn test_execute() {
+    let root = TempDir::new("execute");
+    std::fs::create_dir_all(root.join("util")).unwrap();
+    std::fs::write(
+      root.join("manifest.toml"),
+      "[project]\nname = \"args\"\nversion = \"0.1.0\"\n[link]\nlibraries = [\"m\"]\n",
+    )
+    .unwrap();
+    std::fs::write(root.join("module.vsp"), "public module = {}").unwrap();
+    std::fs::write(
+      root.join("util/count.vsp"),
+      "public func count(argc: int32): int32 { return argc - 1; }",
+    )
+    .unwrap();
+    std::fs::write(
+      root.join("lib.vsp"),
+      "use util::count;
+       extern \"C\" func atoi(s: str): int32;
+       func main(argc: int32, argv: *str): int32 {
+         unsafe { return count(argc) * 100 + atoi(*(argv + 2)); }
+       }",
+    )
+    .unwrap();
+    let args: Vec<String> = ["args", "x", "42"].iter().map(|arg| arg.to_string()).collect();
+    let mut options = TargetOptions::default();
+    options.set_optimization(OptLevel::O2);
+    let mut compiler = CompilerInstance::from(CompilationDispatcher, options);
+    assert_eq!(compiler.execute(&root.join("lib.vsp"), &args).unwrap(), 242);
+
+    let mut options = TargetOptions::default();
+    options.set_link_args(vec![
+      format!("-L{}", root.display()),
+      "-lvsp-missing".to_owned(),
+    ]);
+    let mut compiler = CompilerInstance::from(CompilationDispatcher, options);
+    assert_eq!(
+      compiler.execute(&root.join("lib.vsp"), &args).unwrap_err().message(),
+      "could not load the library `libvsp-missing.so`"
+    );
+  }
+
+  #[test]
+  fn test_interpret() {
+    let root = TempDir::new("interpret");
+    std::fs::create_dir_all(root.join("util")).unwrap();
+    std::fs::write(
+      root.join("manifest.toml"),
+      "[project]\nname = \"args\"\nversion = \"0.1.0\"\n",
+    )
+    .unwrap();
+    std::fs::write(root.join("module.vsp"), "public module = {}").unwrap();
+    std::fs::write(
+      root.join("util/count.vsp"),
+      "public func count(argc: int32): int32 { return argc - 1; }",
+    )
+    .unwrap();
+    std::fs::write(
+      root.join("lib.vsp"),
+      "use util::count;
+       extern \"C\" func atoi(s: str): int32;
+       extern \"C\" func strlen(s: str): int64;
+       func main(argc: int32, argv: *str): int32 {
+         unsafe { return count(argc) * 100 + atoi(*(argv + 2)) + strlen(*argv) as int32; }
+       }",
+    )
+    .unwrap();
+    let args: Vec<String> = ["args", "x", "42"].iter().map(|arg| arg.to_string()).collect();
+    let mut compiler = CompilerInstance::from(CompilationDispatcher, TargetOptions::default());
+    assert_eq!(
+      compiler.interpret(&root.join("lib.vsp"), &args).unwrap(),
+      246
+    );
+
+    std::fs::write(
+      root.join("lib.vsp"),
+      "func main(): int32 { let n: int32 = 2147483647; return n + 1; }",
+    )
+    .unwrap();
+    let mut options = TargetOptions::default();
+    options.set_optimization(OptLevel::O0);
+    let mut compiler = CompilerInstance::from(CompilationDispatcher, options);
+    let message = compiler.interpret(&root.join("lib.vsp"), &args).unwrap_err().message();
+    assert!(
+      message.ends_with("attempt to add with overflow"),
+      "{}",
+      message
+    );
+    // The arithmetic wraps around when optimized, as the compiled code does.
+    let mut compiler = CompilerInstance::from(CompilationDispatcher, TargetOptions::default());
+    assert_eq!(
+      compiler.interpret(&root.join("lib.vsp"), &args).unwrap(),
+      i32::MIN
+    );
+  }
+
+  #[test]
+  fn test_bytecode() {
+    let root = TempDir::new("bytecode");
+    let file = root.join("double.vsp");
+    std::fs::write(
+      &file,
+      "#!/usr/bin/env vsp run
+       extern \"C\" func atoi(s: str): int32;
+       func main(argc: int32, argv: *str): int32 {
+         unsafe { return atoi(*(argv + 1)) * 2; }
+       }",
+    )
+    .unwrap();
+    let args: Vec<String> = ["double", "21"].iter().map(|arg| arg.to_string()).collect();
+
+    let mut options = TargetOptions::default();
+    options.set_target_dir(root.join("target"));
+    options.set_emit(vec![EmitKind::Bytecode]);
+    let mut compiler = CompilerInstance::from(CompilationDispatcher, options);
+    let path = compiler.emit_bytecode(&file).unwrap();
+    assert_eq!(path.file_name().unwrap(), "double.vspb");
+    assert!(std::fs::read(&path).unwrap().starts_with(b"#!/usr/bin/env vsp run\nVSPB"));
+    assert_eq!(bytecode::run_module_file(&path, &args).unwrap(), 42);
+    assert_eq!(compiler.run_bytecode(&file, &args).unwrap(), 42);
+
+    // The shebang line still counts in the locations of the errors.
+    std::fs::write(
+      &file,
+      "#!/usr/bin/env vsp run\nfunc main(): int32 {\n  let n: int32 = 2147483647;\n  return n + 1;\n}",
+    )
+    .unwrap();
+    let mut options = TargetOptions::default();
+    options.set_optimization(OptLevel::O0);
+    let mut compiler = CompilerInstance::from(CompilationDispatcher, options);
+    let message = compiler.run_bytecode(&file, &args).unwrap_err().message();
+    assert!(message.contains("double.vsp:4:"), "{}", message);
+    assert!(
+      message.ends_with("attempt to add with overflow"),
+      "{}",
+      message
+    );
+    std::fs::write(&path, b"#!/usr/bin/env vsp run\nVSPB").unwrap();
+    let message = bytecode::run_module_file(&path, &args).unwrap_err().message();
+    assert!(message.ends_with("invalid bytecode module: the module is truncated"));
+  }
+
+  #[test]
+  fn test_prelude() {
+    let root = TempDir::new("prelude");
+    let file = root.join("hello.vsp");
+    // `println` of the prelude needs no declaration, and `print` is shadowed by the function.
+    std::fs::write(
+      &file,
+      "func print(n: int32): int32 { return n * 2; }
+       func main(): int32 {
+         println(\"Hello World!!\");
+         return print(21);
+       }",
+    )
+    .unwrap();
+    let args = vec!["hello".to_owned()];
+    let mut compiler = CompilerInstance::from(CompilationDispatcher, TargetOptions::default());
+    assert_eq!(compiler.interpret(&file, &args).unwrap(), 42);
+    assert_eq!(compiler.run_bytecode(&file, &args).unwrap(), 42);
+    #[cfg(feature = "llvm")]
+    assert_eq!(compiler.execute(&file, &args).unwrap(), 42);
+  }
+
+  #[test]
+  #[cfg(feature = "llvm")]
+  fn test_parity() {
+    let root = TempDir::new("parity");
+    let programs = [
+      // Pointers to arrays cast into the pointers to their elements.
+      "func main(): int64 {
+         var values: int64[3];
+         let p = &values as *int64;
+         var sum: int64 = 0;
+         var j: int64 = 0;
+         unsafe {
+           *p = 10;
+           *(p + 1) = 20;
+           *(p + 2) = 30;
+           while j < 3 {
+             sum = sum + *(p + j);
+             j = j + 1;
+           }
+         }
+         return sum;
+       }",
+      "const SIZE: int64 = 2;
+       func main(argc: int32, argv: *str): int64 {
+         var values: int64[SIZE];
+         let p = &values as *int64;
+         unsafe {
+           *p = argc as int64;
+           *(p + 1) = 2;
+           if p < p + 1 { return *p * *(p + 1) + ((p + 1) - p) * 10; }
+         }
+         return 0;
+       }",
+      // Arrays indexed by the values known at runtime, through the places and the pointers.
+      "struct Grid { cells: int64[3][2] }
+       func sum(values: *int64[3]): int64 {
+         var total: int64 = 0;
+         var i: int64 = 0;
+         unsafe { while i < 3 { total = total + (*values)[i]; i = i + 1; } }
+         return total;
+       }
+       func main(argc: int32, argv: *str): int64 {
+         var grid = Grid { cells: [[1, 2, 3], [4, 5, 6]] };
+         grid.cells[argc - 1][argc] = 60;
+         let row = grid.cells[argc - 1];
+         return sum(&grid.cells[1]) + row[0] * 100 + [7, 8, 9][argc] * 1000;
+       }",
+      // Patterns tested in order, binding the fields of the variants.
+      "enum Shape { Circle(int64), Rect(int64, int64), Empty }
+       func area(shape: Shape): int64 {
+         return match shape {
+           Shape::Circle(r) => 3 * r * r,
+           Shape::Rect(1, h) => h,
+           Shape::Rect(w, h) => w * h,
+           Shape::Empty => 0,
+         };
+       }
+       func main(argc: int32, argv: *str): int64 {
+         let shape = match argc { 1 => Shape::Empty, 2 => Shape::Rect(2, 5), _ => Shape::Circle(1) };
+         return area(shape) + area(Shape::Rect(1, 4)) + area(Shape::Circle(2));
+       }",
+      // User iterators over the generic parameter and the pointer to the state, by the iterator
+      // interface of the stdlib.
+      "use core::iter::Iterator;
+       struct Counter { next: int64, end: int64 }
+       impl Iterator for *Counter {
+         type Entry = int64;
+         func has_next(): bool { unsafe { return (*self).next < (*self).end; } }
+         func next(): int64 {
+           unsafe { let value = (*self).next; (*self).next = value + 1; return value; }
+         }
+         func try_next(): int64 { return self.next(); }
+       }
+       func count<I: Iterator>(iter: I): int64 {
+         var n = 0;
+         for x in iter { n = n + 1; }
+         return n;
+       }
+       func main(): int64 {
+         var counter = Counter { next: 2, end: 6 };
+         var product = 1;
+         'outer: for x in &counter {
+           for i in 0..x { if i == 3 { continue 'outer; } }
+           product = product * x;
+         }
+         var other = Counter { next: 1, end: 5 };
+         return product + count(&other) * 10 + counter.next * 100;
+       }",
+      // Trait objects of the values passed by value and by pointer, returned from the functions.
+      "interface Shape {
+         func area(): int64;
+         func scaled(factor: int64): Rect;
+       }
+       struct Square { side: int64 }
+       struct Rect { w: int64, h: int64, name: str, scale: int64 }
+       impl Shape for Square {
+         func area(): int64 { return self.side * self.side; }
+         func scaled(factor: int64): Rect {
+           return Rect { w: self.side * factor, h: self.side, name: \"square\", scale: factor };
+         }
+       }
+       impl Shape for Rect {
+         func area(): int64 { return self.w * self.h; }
+         func scaled(factor: int64): Rect {
+           return Rect { w: self.w * factor, h: self.h, name: self.name, scale: factor };
+         }
+       }
+       func pick(wide: bool): dyn Shape {
+         if wide { return Rect { w: 2, h: 3, name: \"rect\", scale: 1 }; }
+         return Square { side: 2 };
+       }
+       func main(argc: int32, argv: *str): int64 {
+         let shape = pick(argc > 1);
+         return shape.area() + shape.scaled(2).area() * 10 + pick(false).area() * 100;
+       }",
+    ];
+    let args: Vec<String> = ["parity", "x"].iter().map(|arg| arg.to_string()).collect();
+    for (index, source) in programs.iter().enumerate() {
+      let file = root.join(format!("parity{}.vsp", index));
+      std::fs::write(&file, source).unwrap();
+      let mut options = TargetOptions::default();
+      options.set_optimization(OptLevel::O0);
+      let mut compiler = CompilerInstance::from(CompilationDispatcher, options);
+      let expected = compiler.execute(&file, &args).unwrap();
+      assert_eq!(
+        compiler.interpret(&file, &args).unwrap(),
+        expected,
+        "{}",
+        source
+      );
+      assert_eq!(
+        compiler.run_bytecode(&file, &args).unwrap(),
+        expected,
+        "{}",
+        source
+      );
+    }
+  }
+
+  #[test]
+  #[cfg(feature = "llvm")]
+  fn test_stdlib() {
+    let root = TempDir::new("stdlib");
+    let stdlib = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../stdlib");
+    let mut options = TargetOptions::default();
+    options.set_target_dir(root.join("target"));
+    options.set_emit(vec![EmitKind::LlvmIr]);
+    let dir = options.output_dir();
+    let mut compiler = CompilerInstance::from(CompilationDispatcher, options);
+    compiler.run(&stdlib.join("lib.vsp")).unwrap();
+    assert!(dir.join("lib.ll").exists());
+
+    // The program of a single file uses the items of the stdlib, which every package is loaded
+    // with.
+    let file = root.join("main.vsp");
+    std::fs::write(
+      &file,
+      "use core::Expected;
+       use core::Optional;
+       use core::convert::From;
+       use core::iter::Iterator;
+       use std::task::block_on;
+       struct ParseError { code: int64 }
+       struct AppError { code: int64 }
+       impl From for AppError {
+         type Source = ParseError;
+         static func from(value: ParseError): AppError {
+           return AppError { code: value.code + 100 };
+         }
+       }
+       func parse(n: int64): Expected<int64, ParseError> {
+         if n < 0 { return Expected::Error(ParseError { code: n }); }
+         return Expected::Value(n * 2);
+       }
+       func checked(n: int64): Expected<int64, AppError> {
+         let value = parse(n)?;
+         return Expected::Value(value);
+       }
+       func half(n: int64): Optional<int64> {
+         if n % 2 == 0 { return Optional::Value(n / 2); }
+         return Optional::None;
+       }
+       func quarter(n: int64): Optional<int64> {
+         return half(half(n)?);
+       }
+       struct Counter { next: int64, end: int64 }
+       impl Iterator for *Counter {
+         type Entry = int64;
+         func has_next(): bool { unsafe { return (*self).next < (*self).end; } }
+         func next(): int64 {
+           unsafe { let value = (*self).next; (*self).next = value + 1; return value; }
+         }
+         func try_next(): int64 { return self.next(); }
+       }
+       async func fetch(id: int64): int64 { return id * 10; }
+       async func total(n: int64): int64 { return fetch(n).await + fetch(n + 1).await; }
+       func main(): int32 {
+         let a = match checked(5) { Expected::Value(v) => v, Expected::Error(e) => e.code };
+         let b = match checked(-3) { Expected::Value(v) => v, Expected::Error(e) => e.code };
+         let c = match quarter(12) { Optional::Value(v) => v, Optional::None => 0 };
+         let d = match quarter(6) { Optional::Value(v) => v, Optional::None => 50 };
+         var counter = Counter { next: 1, end: 5 };
+         var sum: int64 = 0;
+         for x in &counter { sum = sum + x; }
+         let e: int64 = block_on(total(2));
+         return (a + b + c + d + sum + e) as int32;
+       }",
+    )
+    .unwrap();
+    let args = vec!["main".to_owned()];
+    let mut compiler = CompilerInstance::from(CompilationDispatcher, TargetOptions::default());
+    let expected = 10 + 97 + 3 + 50 + (1 + 2 + 3 + 4) + (20 + 30);
+    assert_eq!(compiler.execute(&file, &args).unwrap(), expected);
+    assert_eq!(compiler.interpret(&file, &args).unwrap(), expected);
+    assert_eq!(compiler.run_bytecode(&file, &args).unwrap(), expected);
+  }
+}
@@ -0,0 +1,276 @@
+//! Linker driver producing the executables, static libraries and shared libraries from the object
+//! files.
+//!
+//! Executables and shared libraries are linked by the C compiler driver `cc` by default, which
+//! brings the C runtime startup files and the C library, or by `ld` or `ld.lld` directly, which
+//! link only what they are given. Static libraries are archived by `ar` whatever the linker is.
+//!
+//! Cross-compiled artifacts are linked by the linker of the target spec, such as
+//! `aarch64-linux-gnu-gcc`, unless another one is given.
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use vsp_error::VspError;
+use vsp_error::VspResult;
+use vsp_support::process::ProcessBuilder;
+
+/// Kind of the linker, given by `-C linker-flavor=`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkerFlavor {
+  /// The C compiler driver, `cc`.
+  Cc,
+  /// The GNU linker, `ld`.
+  Ld,
+  /// The LLVM linker, `ld.lld`.
+  Lld,
+  /// The LLVM linker for WebAssembly, `wasm-ld`.
+  WasmLd,
+}
+
+impl LinkerFlavor {
+  /// Program of the linker when it is not given by `-C linker=`.
+  pub fn default_program(&self) -> &'static str {
+    match self {
+      Self::Cc => "cc",
+      Self::Ld => "ld",
+      Self::Lld => "ld.lld",
+      Self::WasmLd => "wasm-ld",
+    }
+  }
+}
+
+impl FromStr for LinkerFlavor {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "cc" => Ok(Self::Cc),
+      "ld" => Ok(Self::Ld),
+      "lld" => Ok(Self::Lld),
+      "wasm-ld" => Ok(Self::WasmLd),
+      _ => Err(format!(
+        "unknown linker flavor `{}`, expected cc, ld, lld or wasm-ld",
+        s
+      )),
+    }
+  }
+}
+
+/// Kind of the linked artifact, given by `--crate-type`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrateType {
+  /// Executable, `bin`.
+  Executable,
+  /// Static library, `staticlib`.
+  StaticLib,
+  /// Shared library, `dylib`.
+  SharedLib,
+}
+
+impl CrateType {
+  /// File name of the artifact named after the package.
+  pub fn file_name(&self, name: &str) -> String {
+    match self {
+      Self::Executable => name.to_owned(),
+      Self::StaticLib => format!("lib{}.a", name),
+      Self::SharedLib => format!("lib{}.so", name),
+    }
+  }
+}
+
+impl FromStr for CrateType {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "bin" => Ok(Self::Executable),
+      "staticlib" => Ok(Self::StaticLib),
+      "dylib" => Ok(Self::SharedLib),
+      _ => Err(format!(
+        "unknown crate type `{}`, expected bin, staticlib or dylib",
+        s
+      )),
+    }
+  }
+}
+
+impl Display for CrateType {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Executable => write!(f, "bin"),
+      Self::StaticLib => write!(f, "staticlib"),
+      Self::SharedLib => write!(f, "dylib"),
+    }
+  }
+}
+
+/// Invocation of the linker for an artifact.
+#[derive(Clone, Debug)]
+pub struct Linker {
+  flavor:     LinkerFlavor,
+  program:    String,
+  crate_type: CrateType,
+  output:     PathBuf,
+  /// Object files and archives, the compiled module first, followed by the runtime and stdlib.
+  objects:    Vec<PathBuf>,
+  /// Flags after the objects, such as `-L`, `-l` and the linker arguments.
+  flags:      Vec<String>,
+}
+
+impl Linker {
+  pub fn new(flavor: LinkerFlavor, crate_type: CrateType, output: impl Into<PathBuf>) -> Self {
+    Self {
+      flavor,
+      program: flavor.default_program().to_owned(),
+      crate_type,
+      output: output.into(),
+      objects: Vec::new(),
+      flags: Vec::new(),
+    }
+  }
+
+  /// Run the program instead of the default one of the flavor.
+  pub fn set_program(&mut self, program: impl Into<String>) -> &mut Self {
+    self.program = program.into();
+    self
+  }
+
+  pub fn add_object(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+    self.objects.push(path.into());
+    self
+  }
+
+  pub fn add_flags(&mut self, flags: impl IntoIterator<Item = String>) -> &mut Self {
+    self.flags.extend(flags);
+    self
+  }
+
+  pub fn output(&self) -> &Path {
+    &self.output
+  }
+
+  /// Command of the linker. Static libraries are archived without the flags, since the libraries
+  /// they need are linked into the final artifact instead.
+  pub fn command(&self) -> ProcessBuilder {
+    if self.crate_type == CrateType::StaticLib {
+      let mut builder = ProcessBuilder::new("ar");
+      builder.arg("crs").arg(&self.output).args(&self.objects);
+      return builder;
+    }
+    let mut builder = ProcessBuilder::new(&self.program);
+    if self.crate_type == CrateType::SharedLib {
+      builder.arg("-shared");
+    }
+    builder.arg("-o").arg(&self.output).args(&self.objects);
+    builder.args(&self.flags);
+    match self.flavor {
+      // The C library is linked by `cc` itself, but not by the bare linkers.
+      LinkerFlavor::Ld | LinkerFlavor::Lld => {
+        builder.arg("-lc");
+      }
+      // WebAssembly modules have no C library nor entry, but export their functions instead.
+      LinkerFlavor::WasmLd => {
+        builder.arg("--no-entry").arg("--export-dynamic");
+      }
+      LinkerFlavor::Cc => {}
+    }
+    builder
+  }
+
+  /// Link the artifact, replacing the previous one.
+  pub fn link(&self) -> VspResult<()> {
+    if self.crate_type == CrateType::StaticLib && self.output.exists() {
+      // `ar` appends to the existing archive.
+      std::fs::remove_file(&self.output).map_err(VspError::from)?;
+    }
+    self.command().exec()
+  }
+}
+
+/// Object files and archives of the vsp runtime and stdlib for the target, which are shipped in
+/// `<sysroot>/lib/vsp/<triple>`, sorted by their names. No object is linked if there is no such
+/// directory.
+pub fn runtime_objects(sysroot: &Path, triple: &str) -> VspResult<Vec<PathBuf>> {
+  let dir = sysroot.join("lib").join("vsp").join(triple);
+  if !dir.is_dir() {
+    return Ok(Vec::new());
+  }
+  let mut objects = Vec::new();
+  for entry in std::fs::read_dir(&dir).map_err(VspError::from)? {
+    let path = entry.map_err(VspError::from)?.path();
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    if matches!(extension, Some("o") | Some("a")) {
+      objects.push(path);
+    }
+  }
+  objects.sort();
+  Ok(objects)
+}
+
+/// Sysroot of the running compiler, the parent of the `bin` directory of the executable.
+pub fn default_sysroot() -> Option<PathBuf> {
+  let exe = std::env::current_exe().ok()?;
+  Some(exe.parent()?.parent()?.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TempDir;
+
+  fn linker(flavor: LinkerFlavor, crate_type: CrateType) -> Linker {
+    let mut linker = Linker::new(flavor, crate_type, crate_type.file_name("main"));
+    linker
+      .add_object("main.o")
+      .add_object("rt.a")
+      .add_flags(vec!["-L/opt/lib".to_owned(), "-lm".to_owned()]);
+    linker
+  }
+
+  #[test]
+  fn test_command() {
+    let command = linker(LinkerFlavor::Cc, CrateType::Executable).command();
+    assert_eq!(command.to_string(), "cc -o main main.o rt.a -L/opt/lib -lm");
+    let command = linker(LinkerFlavor::Cc, CrateType::SharedLib).command();
+    assert_eq!(
+      command.to_string(),
+      "cc -shared -o libmain.so main.o rt.a -L/opt/lib -lm"
+    );
+    let command = linker(LinkerFlavor::Lld, CrateType::SharedLib).command();
+    assert_eq!(
+      command.to_string(),
+      "ld.lld -shared -o libmain.so main.o rt.a -L/opt/lib -lm -lc"
+    );
+    let command = linker(LinkerFlavor::Ld, CrateType::StaticLib).command();
+    assert_eq!(command.to_string(), "ar crs libmain.a main.o rt.a");
+    let command = linker(LinkerFlavor::WasmLd, CrateType::Executable).command();
+    assert_eq!(
+      command.to_string(),
+      "wasm-ld -o main main.o rt.a -L/opt/lib -lm --no-entry --export-dynamic"
+    );
+    let mut linker = linker(LinkerFlavor::Cc, CrateType::Executable);
+    linker.set_program("clang");
+    assert_eq!(
+      linker.command().to_string(),
+      "clang -o main main.o rt.a -L/opt/lib -lm"
+    );
+  }
+
+  #[test]
+  fn test_runtime_objects() {
+    let sysroot = TempDir::new("sysroot");
+    let dir = sysroot.join("lib/vsp/x86_64-unknown-linux-gnu");
+    std::fs::create_dir_all(&dir).unwrap();
+    for file in ["std.a", "rt.o", "README.md"] {
+      std::fs::write(dir.join(file), "").unwrap();
+    }
+    assert_eq!(
+      runtime_objects(&sysroot, "x86_64-unknown-linux-gnu").unwrap(),
+      vec![dir.join("rt.o"), dir.join("std.a")]
+    );
+    assert!(runtime_objects(&sysroot, "aarch64-unknown-linux-gnu").unwrap().is_empty());
+  }
+}
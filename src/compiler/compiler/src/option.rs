@@ -1,10 +1,17 @@
 use std::borrow::Cow;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::path::PathBuf;
+use std::str::FromStr;
 
 use getset::Getters;
 use getset::Setters;
 use semver::Version;
 use target_lexicon::Triple;
 
+use crate::link::CrateType;
+use crate::link::LinkerFlavor;
+
 /// Target options for compilation.
 #[derive(Getters, Setters)]
 pub struct TargetOptions {
@@ -22,10 +29,94 @@ pub struct TargetOptions {
   target_triple:      Triple,
   /// Optimization level
   #[getset(get = "pub", set = "pub")]
-  optimization:       u8,
+  optimization:       OptLevel,
+  /// Passes run instead of the pipeline of the optimization level
+  #[getset(get = "pub", set = "pub")]
+  passes:             Option<String>,
+  /// Print the time spent by each optimization pass
+  #[getset(get = "pub", set = "pub")]
+  time_passes:        bool,
+  /// Generate the debug information
+  #[getset(get = "pub", set = "pub")]
+  debuginfo:          bool,
+  /// Kinds of the output files to emit
+  #[getset(get = "pub", set = "pub")]
+  emit:               Vec<EmitKind>,
+  /// Target CPU, or `native` for the host CPU
+  #[getset(get = "pub", set = "pub")]
+  target_cpu:         Option<String>,
+  /// Target features, such as `+avx2`
+  #[getset(get = "pub", set = "pub")]
+  target_features:    Vec<String>,
+  /// Relocation model, `pic` by default
+  #[getset(get = "pub", set = "pub")]
+  relocation_model:   Option<String>,
+  /// Code model, such as `small`
+  #[getset(get = "pub", set = "pub")]
+  code_model:         Option<String>,
+  /// Directory of the artifacts
+  #[getset(get = "pub", set = "pub")]
+  target_dir:         PathBuf,
+  /// Kind of the linked artifact
+  #[getset(get = "pub", set = "pub")]
+  crate_type:         CrateType,
+  /// Kind of the linker instead of the one of the target
+  #[getset(get = "pub", set = "pub")]
+  linker_flavor:      Option<LinkerFlavor>,
+  /// Linker program instead of the one of the target or the flavor
+  #[getset(get = "pub", set = "pub")]
+  linker:             Option<String>,
+  /// Flags passed to the linker, such as `-L`, `-l` and the linker arguments
+  #[getset(get = "pub", set = "pub")]
+  link_args:          Vec<String>,
+  /// Print the command of the linker before it runs
+  #[getset(get = "pub", set = "pub")]
+  print_link_args:    bool,
+  /// Root of the vsp installation, whose `lib/vsp/<triple>` holds the runtime and stdlib objects
+  #[getset(get = "pub", set = "pub")]
+  sysroot:            Option<PathBuf>,
 }
 
 impl TargetOptions {
+  /// Integer arithmetic traps on overflow in debug builds, i.e. without optimization, and wraps
+  /// around in release builds.
+  pub fn overflow_checks(&self) -> bool {
+    self.optimization == OptLevel::O0
+  }
+
+  /// Apply the codegen option given by `-C <key>=<value>`.
+  pub fn set_codegen_option(&mut self, option: &str) -> Result<(), String> {
+    let (key, value) = option
+      .split_once('=')
+      .ok_or_else(|| format!("codegen option `{}` requires a value", option))?;
+    match key {
+      "target-cpu" => self.target_cpu = Some(value.to_owned()),
+      "target-feature" => self.target_features.push(value.to_owned()),
+      "relocation-model" => self.relocation_model = Some(value.to_owned()),
+      "code-model" => self.code_model = Some(value.to_owned()),
+      "linker" => self.linker = Some(value.to_owned()),
+      "linker-flavor" => self.linker_flavor = Some(value.parse()?),
+      "link-arg" => self.link_args.push(value.to_owned()),
+      "link-args" => self.link_args.extend(value.split_whitespace().map(str::to_owned)),
+      _ => return Err(format!("unknown codegen option `{}`", key)),
+    }
+    Ok(())
+  }
+
+  /// Profile of the build, `debug` without optimization and `release` otherwise.
+  pub fn profile(&self) -> &'static str {
+    if self.optimization == OptLevel::O0 {
+      "debug"
+    } else {
+      "release"
+    }
+  }
+
+  /// Directory of the outputs, `<target-dir>/<triple>/<profile>`.
+  pub fn output_dir(&self) -> PathBuf {
+    self.target_dir.join(self.target_triple.to_string()).join(self.profile())
+  }
+
   #[cfg(debug_assertions)]
   pub fn debug_print_status(&self) {
     println!("Version = {}", &self.version);
@@ -36,18 +127,149 @@ impl TargetOptions {
     println!("Library = {}", &self.library);
     println!("Host triple = {}", &self.host_triple);
     println!("Target triple = {}", &self.target_triple);
+    println!("Optimization = {}", self.optimization);
   }
 }
 
 impl Default for TargetOptions {
   fn default() -> Self {
     Self {
-      version:       Version::new(0, 0, 0),
-      binary:        None,
-      library:       false,
-      host_triple:   Triple::host(),
-      target_triple: Triple::host(),
-      optimization:  3,
+      version:          Version::new(0, 0, 0),
+      binary:           None,
+      library:          false,
+      host_triple:      Triple::host(),
+      target_triple:    Triple::host(),
+      optimization:     OptLevel::O3,
+      passes:           None,
+      time_passes:      false,
+      debuginfo:        false,
+      emit:             vec![EmitKind::Link],
+      target_cpu:       None,
+      target_features:  Vec::new(),
+      relocation_model: None,
+      code_model:       None,
+      target_dir:       PathBuf::from("target"),
+      crate_type:       CrateType::Executable,
+      linker_flavor:    None,
+      linker:           None,
+      link_args:        Vec::new(),
+      print_link_args:  false,
+      sysroot:          None,
+    }
+  }
+}
+
+/// Optimization level, `-O0` to `-O3` for speed, and `-Os` or `-Oz` for size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OptLevel {
+  O0,
+  O1,
+  O2,
+  O3,
+  Os,
+  Oz,
+}
+
+impl OptLevel {
+  /// Speed level from 0 to 3, where the size levels optimize for speed as `-O2` does.
+  pub fn speed_level(&self) -> u8 {
+    match self {
+      Self::O0 => 0,
+      Self::O1 => 1,
+      Self::O2 | Self::Os | Self::Oz => 2,
+      Self::O3 => 3,
+    }
+  }
+
+  /// Size level, 1 for `-Os` and 2 for `-Oz`.
+  pub fn size_level(&self) -> u8 {
+    match self {
+      Self::Os => 1,
+      Self::Oz => 2,
+      _ => 0,
+    }
+  }
+}
+
+impl FromStr for OptLevel {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "0" => Ok(Self::O0),
+      "1" => Ok(Self::O1),
+      "2" => Ok(Self::O2),
+      "3" => Ok(Self::O3),
+      "s" => Ok(Self::Os),
+      "z" => Ok(Self::Oz),
+      _ => Err(format!(
+        "invalid optimization level `{}`, expected 0, 1, 2, 3, s or z",
+        s
+      )),
+    }
+  }
+}
+
+impl Display for OptLevel {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let level = match self {
+      Self::O0 => "0",
+      Self::O1 => "1",
+      Self::O2 => "2",
+      Self::O3 => "3",
+      Self::Os => "s",
+      Self::Oz => "z",
+    };
+    write!(f, "-O{}", level)
+  }
+}
+
+/// Kind of the output file, given by `--emit`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmitKind {
+  /// Object file, `obj`.
+  Object,
+  /// Assembly, `asm`.
+  Assembly,
+  /// LLVM bitcode, `llvm-bc`.
+  LlvmBitcode,
+  /// LLVM textual IR, `llvm-ir`.
+  LlvmIr,
+  /// Artifact linked from the object file, `link`.
+  Link,
+  /// Bytecode module run by the VM, `bytecode`.
+  Bytecode,
+}
+
+impl EmitKind {
+  /// Extension of the output file, except the linked artifact, named by its crate type.
+  pub fn extension(&self) -> Option<&'static str> {
+    match self {
+      Self::Object => Some("o"),
+      Self::Assembly => Some("s"),
+      Self::LlvmBitcode => Some("bc"),
+      Self::LlvmIr => Some("ll"),
+      Self::Link => None,
+      Self::Bytecode => Some(vsp_bytecode::format::EXTENSION),
+    }
+  }
+}
+
+impl FromStr for EmitKind {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "obj" => Ok(Self::Object),
+      "asm" => Ok(Self::Assembly),
+      "llvm-bc" => Ok(Self::LlvmBitcode),
+      "llvm-ir" => Ok(Self::LlvmIr),
+      "link" => Ok(Self::Link),
+      "bytecode" => Ok(Self::Bytecode),
+      _ => Err(format!(
+        "unknown emit kind `{}`, expected obj, asm, llvm-bc, llvm-ir, link or bytecode",
+        s
+      )),
     }
   }
 }
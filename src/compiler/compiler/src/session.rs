@@ -0,0 +1,373 @@
+//! Session of the REPL, which interprets the lines one after another.
+//!
+//! Lines declaring items, such as functions and structs, are kept in the session, and every later
+//! line is checked along with them. Other lines are statements, or expressions without the
+//! trailing `;` whose values are printed, and they are interpreted in a function of their own.
+//! Local variables declared by the statements are kept as the bindings of the session, which the
+//! function of every later line takes and binds again, unless their types could not be written in
+//! the source codes. The heap is kept too, so that the pointers in the bindings are never confused
+//! with the later allocations.
+//!
+//! The code hidden before the line is lexed as if it followed the line, and the code hidden after
+//! it as if it were written at the end of the line, so that the diagnostics and the errors are
+//! located in the line as it is written.
+use vsp_ast::ast::module::Path;
+use vsp_ast::ast::types::PrimitiveType;
+use vsp_ast::ast::types::Type;
+use vsp_ast::ast::CompilationUnit;
+use vsp_ast_parser::lex::DefaultLexer;
+use vsp_ast_parser::parser::ASTParser;
+use vsp_ast_parser::parser::TraditionalParser;
+use vsp_ast_parser::token::Token;
+use vsp_diag::DiagnosticEngine;
+use vsp_error::VspError;
+use vsp_error::VspResult;
+use vsp_hir::hir::LocalId;
+use vsp_hir::hir::Program;
+use vsp_hir::hir::StmtKind;
+use vsp_interp::heap::Heap;
+use vsp_interp::interp::Interpreter;
+use vsp_interp::interp::STACK_SIZE;
+use vsp_interp::value::Value;
+use vsp_sema::check::TypeckResults;
+use vsp_sema::mono::MonoItem;
+use vsp_sema::resolve::DefPath;
+
+use crate::source::loader::ModuleLoader;
+use crate::source::SourceManager;
+
+/// Name of the source of the REPL in the diagnostics.
+pub const FILENAME: &str = "<repl>";
+
+/// Function which the statements and the expressions are interpreted in.
+const ENTRY: &str = "__repl";
+
+#[derive(Default)]
+pub struct Session {
+  /// Lines of the items declared so far.
+  items:    Vec<String>,
+  /// Local variables declared by the lines so far, in the order of declaration.
+  bindings: Vec<Binding>,
+  heap:     Heap,
+}
+
+struct Binding {
+  name:    String,
+  ty:      Type,
+  mutable: bool,
+  value:   Value,
+}
+
+impl Session {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Evaluate the line, and return the text of the value of the expression, which is `None` for
+  /// the items, the statements and the expressions of the unit type.
+  pub fn eval(&mut self, line: &str) -> VspResult<Option<String>> {
+    let line = line.trim();
+    let kind = classify(line)?;
+    let items = self.items.iter().map(|item| format!("{}\n", item)).collect::<String>();
+    if kind == LineKind::Item {
+      self.check(&items, line, "")?;
+      self.items.push(line.to_owned());
+      return Ok(None);
+    }
+
+    // The bindings are passed as the parameters of the entry, and bound again by their names.
+    let params = self
+      .bindings
+      .iter()
+      .map(|binding| format!("__{}: {}", binding.name, binding.ty));
+    let mut prefix = format!(
+      "{}func {}({}) {{\n",
+      items,
+      ENTRY,
+      params.collect::<Vec<_>>().join(", ")
+    );
+    for binding in &self.bindings {
+      let keyword = if binding.mutable { "var" } else { "let" };
+      prefix.push_str(&format!(
+        "{} {} = __{};\n",
+        keyword, binding.name, binding.name
+      ));
+    }
+    let expression = kind == LineKind::Expr;
+    let suffix = if expression {
+      prefix.push_str("let it =\n");
+      ";\n}\n"
+    } else {
+      "\n}\n"
+    };
+    let (mut program, results) = self.check(&prefix, line, suffix)?;
+    let owner = MonoItem::Function(DefPath::new(Path::root(), ENTRY));
+    let declared = declared_bindings(&program, &owner, expression);
+    let ty = if expression {
+      return_initializer(&mut program, &owner)
+    } else {
+      Type::unit()
+    };
+
+    let args = self.bindings.iter().map(|binding| binding.value.clone()).collect();
+    let heap = std::mem::take(&mut self.heap);
+    // The interpreter recurses along with the calls of the line, which the default stack of the
+    // threads is too small for.
+    let thread = std::thread::Builder::new()
+      .stack_size(STACK_SIZE)
+      .spawn(move || {
+        let mut interpreter = Interpreter::new(&program, results.items());
+        interpreter.set_heap(heap);
+        let locals = declared.iter().map(|(local, _)| *local).collect::<Vec<_>>();
+        let result = interpreter.call_keeping(&owner, args, &locals);
+        let heap = interpreter.take_heap();
+        let result = result.map(|(value, values)| {
+          let text = match ty {
+            Type::Primitive(PrimitiveType::Unit) => None,
+            ty => Some(interpreter.describe(&value, &ty)),
+          };
+          let bindings = declared
+            .into_iter()
+            .zip(values)
+            .map(|((_, mut binding), value)| {
+              binding.value = value;
+              binding
+            })
+            .collect::<Vec<_>>();
+          (text, bindings)
+        });
+        (result, heap)
+      })
+      .map_err(VspError::from)?;
+    let (result, heap) = thread.join().map_err(|_| VspError::new("the interpreter panicked"))?;
+    self.heap = heap;
+    let (text, bindings) = result?;
+    for binding in bindings {
+      self.bindings.retain(|kept| kept.name != binding.name);
+      self.bindings.push(binding);
+    }
+    Ok(text)
+  }
+
+  /// Check the line between the hidden codes and lower them into the HIR, after the checks on the
+  /// MIR, failing with the rendered diagnostics if there are any errors.
+  fn check(&self, prefix: &str, line: &str, suffix: &str) -> VspResult<(Program, TypeckResults)> {
+    let lines = line.lines().count().max(1);
+    let mut tokens = DefaultLexer {}.tokenize(&format!("{}{}", "\n".repeat(lines), prefix))?;
+    tokens.extend(DefaultLexer {}.tokenize(line)?);
+    // The closing code is written right after the end of the line, where the errors of incomplete
+    // lines are reported.
+    let blank = line.chars().map(|c| if c == '\n' { c } else { ' ' }).collect::<String>();
+    tokens.extend(DefaultLexer {}.tokenize(&format!("{}{}", blank, suffix.replace('\n', " ")))?);
+    let source = format!("{}\n{}", line, prefix);
+
+    let mut unit: CompilationUnit = TraditionalParser {}
+      .parse(tokens)
+      .map_err(|e| VspError::new(format!("{}:{}", FILENAME, e.message())))?;
+    unit.set_filename(FILENAME);
+    let mut source_manager = SourceManager::default();
+    let mut loader = ModuleLoader::new(&mut source_manager);
+    let prelude = loader.load_prelude()?;
+    unit.modules.insert(prelude.path.to_string(), prelude);
+    loader.load_stdlib(&mut unit.modules)?;
+
+    let mut diagnostics = DiagnosticEngine::default();
+    let results = vsp_sema::check_compilation_unit(&unit, &mut diagnostics);
+    if diagnostics.has_errors() {
+      return VspError::new(diagnostics.render(FILENAME, &source)).to_err();
+    }
+    let program = vsp_hir::lower_compilation_unit(&unit, &results);
+    vsp_mir::build::build_program(&program, &mut diagnostics);
+    if diagnostics.has_errors() {
+      return VspError::new(diagnostics.render(FILENAME, &source)).to_err();
+    }
+    Ok((program, results))
+  }
+}
+
+#[derive(Debug, PartialEq)]
+enum LineKind {
+  Item,
+  Stmt,
+  Expr,
+}
+
+/// Kind of the line by its first tokens, where the lines ending with `;` are statements too.
+fn classify(line: &str) -> VspResult<LineKind> {
+  let tokens = DefaultLexer {}.tokenize(line)?;
+  let mut tokens = tokens.iter().map(|token| token.token());
+  let kind = match tokens.next() {
+    Some(Token::Unsafe) if tokens.next() == Some(&Token::Func) => LineKind::Item,
+    Some(
+      Token::At
+      | Token::Public
+      | Token::Use
+      | Token::Module
+      | Token::Struct
+      | Token::Enum
+      | Token::Trait
+      | Token::Interface
+      | Token::Const
+      | Token::Static
+      | Token::Impl
+      | Token::Func
+      | Token::Async
+      | Token::Extern,
+    ) => LineKind::Item,
+    Some(
+      Token::Unsafe
+      | Token::LBrace
+      | Token::Let
+      | Token::Var
+      | Token::If
+      | Token::While
+      | Token::For
+      | Token::Loop
+      | Token::Return,
+    ) => LineKind::Stmt,
+    _ if line.ends_with(';') => LineKind::Stmt,
+    _ => LineKind::Expr,
+  };
+  Ok(kind)
+}
+
+/// Replace the last statement `let it = <expr>;` of the entry with `return <expr>;`, and return the
+/// type of the expression.
+fn return_initializer(program: &mut Program, owner: &MonoItem) -> Type {
+  let body = program
+    .bodies
+    .iter_mut()
+    .find(|body| body.owner == *owner)
+    .expect("the entry of the REPL is lowered");
+  let stmt = body.block.stmts.last_mut().expect("the entry of the REPL has statements");
+  let expr = match std::mem::replace(&mut stmt.kind, StmtKind::Return(None)) {
+    StmtKind::Let(_, Some(expr)) => expr,
+    kind => unreachable!("expected `let it = <expr>;`, found {:?}", kind),
+  };
+  body.ret = expr.ty.clone();
+  stmt.kind = StmtKind::Return(Some(expr));
+  body.ret.clone()
+}
+
+/// Local variables declared at the top level of the entry, which are kept as the bindings if
+/// their types could be written in the source codes. The later ones shadow the earlier ones. `it`
+/// of the expressions is not a binding.
+fn declared_bindings(
+  program: &Program,
+  owner: &MonoItem,
+  expression: bool,
+) -> Vec<(LocalId, Binding)> {
+  let body = program
+    .bodies
+    .iter()
+    .find(|body| body.owner == *owner)
+    .expect("the entry of the REPL is lowered");
+  let stmts = &body.block.stmts;
+  let mut bindings: Vec<(LocalId, Binding)> = vec![];
+  for stmt in &stmts[..stmts.len() - usize::from(expression)] {
+    let local = match &stmt.kind {
+      StmtKind::Let(local, _) => *local,
+      _ => continue,
+    };
+    let declared = body.local(local);
+    bindings.retain(|(_, binding)| binding.name != declared.name);
+    if is_nameable(&declared.ty) {
+      bindings.push((
+        local,
+        Binding {
+          name:    declared.name.clone(),
+          ty:      declared.ty.clone(),
+          mutable: declared.mutable,
+          value:   Value::Unit,
+        },
+      ));
+    }
+  }
+  bindings
+}
+
+/// Whether the type could be written in the source codes as it is printed.
+fn is_nameable(ty: &Type) -> bool {
+  match ty {
+    Type::Primitive(PrimitiveType::Unit) => false,
+    Type::Primitive(_) | Type::Dyn(_) => true,
+    Type::Array(element, _) | Type::Pointer(element) => is_nameable(element),
+    Type::Named(_, args) => args.iter().all(is_nameable),
+    _ => false,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_session() {
+    let mut session = Session::new();
+    assert_eq!(session.eval("1 + 2 * 3").unwrap(), Some("7".to_owned()));
+    assert_eq!(
+      session.eval("func square(n: int64): int64 { return n * n; }").unwrap(),
+      None
+    );
+    assert_eq!(
+      session.eval("struct Point { x: int64, y: int64 }").unwrap(),
+      None
+    );
+    assert_eq!(
+      session.eval("Point { x: square(3), y: 4 }").unwrap(),
+      Some("Point { x: 9, y: 4 }".to_owned())
+    );
+    assert_eq!(session.eval("let p = square(2);").unwrap(), None);
+    assert_eq!(
+      session.eval("square(-5) > 20").unwrap(),
+      Some("true".to_owned())
+    );
+
+    let message = session
+      .eval("func square(n: int32): int32 { return n; }")
+      .unwrap_err()
+      .message();
+    assert!(message.contains("defined multiple times"), "{}", message);
+    let message = session.eval("undefined + 1").unwrap_err().message();
+    assert!(message.contains("undefined"), "{}", message);
+    let message = session.eval("square(4294967296)").unwrap_err().message();
+    assert!(
+      message.ends_with("attempt to multiply with overflow"),
+      "{}",
+      message
+    );
+    // Items failing the checks are not kept.
+    assert_eq!(session.eval("square(3)").unwrap(), Some("9".to_owned()));
+  }
+
+  #[test]
+  fn test_session_bindings() {
+    let mut session = Session::new();
+    assert_eq!(session.eval("let x = 3;").unwrap(), None);
+    assert_eq!(session.eval("x * 2").unwrap(), Some("6".to_owned()));
+    assert_eq!(session.eval("var total: int64 = 0;").unwrap(), None);
+    assert_eq!(session.eval("total = total + x;").unwrap(), None);
+    assert_eq!(session.eval("total = total + 4;").unwrap(), None);
+    assert_eq!(session.eval("total").unwrap(), Some("7".to_owned()));
+    // The later bindings shadow the earlier ones of the same names.
+    assert_eq!(session.eval("let x = true;").unwrap(), None);
+    assert_eq!(session.eval("x").unwrap(), Some("true".to_owned()));
+    // Lines failing the checks or the interpretation keep the bindings as they were.
+    assert!(session.eval("let y = total / 0;").is_err());
+    assert!(session.eval("y").is_err());
+    assert_eq!(session.eval("total").unwrap(), Some("7".to_owned()));
+    assert_eq!(session.eval("println(\"hello\");").unwrap(), None);
+  }
+
+  #[test]
+  fn test_session_spans() {
+    let mut session = Session::new();
+    assert_eq!(session.eval("let x = 1;").unwrap(), None);
+    let message = session.eval("x + missing").unwrap_err().message();
+    assert!(message.contains("--> <repl>:1:5\n"), "{}", message);
+    let message = session.eval("let y: bool = 1;").unwrap_err().message();
+    assert!(message.contains("--> <repl>:1:"), "{}", message);
+    let message = session.eval("x +").unwrap_err().message();
+    assert!(message.starts_with("<repl>:1:"), "{}", message);
+  }
+}
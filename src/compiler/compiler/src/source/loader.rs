@@ -0,0 +1,329 @@
+//! Discovery of the modules of a package from the directory layout.
+//!
+//! If the entry file is `lib.vsp`, or there is `module.vsp` beside the entry file, the directory
+//! of the entry file is the package root. Each subdirectory containing source files is a module
+//! named after the directory, and all source files in the directory belong to the module. Build
+//! scripts `build.vsp` at the package root and directories such as `target` are skipped.
+//!
+//! Otherwise, the entry file is compiled alone.
+//!
+//! Either way, the prelude built into the compiler is added to the package as a module, and so
+//! are the modules of the stdlib, such as `core::future`, unless the package defines the modules
+//! `core` or `std` itself as the stdlib does. The lang items of the stdlib are thus available to
+//! every package, while its other items are imported by `use` as those of the package are.
+use std::collections::HashMap;
+use std::path::Path as FsPath;
+
+use vsp_ast::ast::module::Module;
+use vsp_ast::ast::module::Path;
+use vsp_ast::ast::CompilationUnit;
+use vsp_ast_parser::lex::shebang;
+use vsp_ast_parser::lex::DefaultLexer;
+use vsp_ast_parser::parser::ASTParser;
+use vsp_ast_parser::parser::TraditionalParser;
+use vsp_error::VspError;
+use vsp_error::VspResult;
+use vsp_sema::resolve::PRELUDE;
+
+use crate::source::SourceManager;
+
+pub const SOURCE_EXTENSION: &str = "vsp";
+pub const BYTECODE_EXTENSION: &str = vsp_bytecode::format::EXTENSION;
+pub const LIBRARY_ENTRY: &str = "lib.vsp";
+pub const MODULE_DECLARATION: &str = "module.vsp";
+pub const BUILD_SCRIPT: &str = "build.vsp";
+/// Name of the source file of the prelude in the diagnostics.
+pub const PRELUDE_FILE: &str = "<prelude>";
+
+/// Directory of the source files of the stdlib in the diagnostics.
+pub const STDLIB_DIRECTORY: &str = "<stdlib>";
+
+const PRELUDE_SOURCE: &str = include_str!("prelude.vsp");
+
+/// Pairs of the paths relative to the stdlib root, which is `src/stdlib` of the project, and the
+/// contents of the source files.
+macro_rules! stdlib_sources {
+  ($($path:literal),* $(,)?) => {
+    &[$(($path, include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/../../stdlib/", $path)))),*]
+  };
+}
+
+/// Source files of the stdlib by their paths relative to the stdlib root, ordered by path.
+const STDLIB_SOURCES: &[(&str, &str)] = stdlib_sources![
+  "core/BigDecimal.vsp",
+  "core/Compare.vsp",
+  "core/Either.vsp",
+  "core/Expected.vsp",
+  "core/Optional.vsp",
+  "core/cmp/PartialOrder.vsp",
+  "core/convert/From.vsp",
+  "core/future/Future.vsp",
+  "core/future/Poll.vsp",
+  "core/iter/Iterator.vsp",
+  "std/String.vsp",
+  "std/collect/List.vsp",
+  "std/collect/Map.vsp",
+  "std/lang/Error.vsp",
+  "std/task/block_on.vsp",
+];
+
+/// Directories which never contain modules.
+const SKIPPED_DIRECTORIES: &[&str] = &["target"];
+
+pub struct ModuleLoader<'a> {
+  source_manager: &'a mut SourceManager,
+}
+
+impl<'a> ModuleLoader<'a> {
+  pub fn new(source_manager: &'a mut SourceManager) -> Self {
+    Self { source_manager }
+  }
+
+  /// Load the entry file, together with all modules of the package if it is the package root.
+  pub fn load(&mut self, entry: &FsPath) -> VspResult<CompilationUnit> {
+    let mut unit = self.parse_file(entry)?;
+    let mut modules = HashMap::new();
+    let prelude = self.load_prelude()?;
+    modules.insert(prelude.path.to_string(), prelude);
+    let root = match entry.parent() {
+      // The parent of a bare file name such as `lib.vsp` is empty, which is the current directory.
+      Some(root) if root.as_os_str().is_empty() && is_package_root(entry) => FsPath::new("."),
+      Some(root) if is_package_root(entry) => root,
+      _ => {
+        self.load_stdlib(&mut modules)?;
+        unit.modules = modules;
+        return Ok(unit);
+      }
+    };
+
+    let mut root_module = Module::new(Path::root());
+    for file in list_source_files(root)? {
+      if file.file_name() == entry.file_name()
+        || file.file_name().map_or(false, |name| name == BUILD_SCRIPT)
+      {
+        continue;
+      }
+      root_module.units.push(self.parse_file(&file)?);
+    }
+    if !root_module.units.is_empty() {
+      modules.insert(String::new(), root_module);
+    }
+    for (name, directory) in list_module_directories(root)? {
+      self.load_module(&directory, Path::root().join(name), &mut modules)?;
+    }
+    self.load_stdlib(&mut modules)?;
+    unit.modules = modules;
+    Ok(unit)
+  }
+
+  /// Load the module in the directory and its submodules recursively. Directories without any
+  /// source files in the subtree are not modules.
+  fn load_module(
+    &mut self,
+    directory: &FsPath,
+    path: Path,
+    modules: &mut HashMap<String, Module>,
+  ) -> VspResult<bool> {
+    let mut module = Module::new(path.clone());
+    for file in list_source_files(directory)? {
+      let unit = self.parse_file(&file)?;
+      if let Some(decl) = &unit.module {
+        module.visibility = decl.visibility;
+      }
+      module.units.push(unit);
+    }
+    let mut has_submodules = false;
+    for (name, subdirectory) in list_module_directories(directory)? {
+      has_submodules |= self.load_module(&subdirectory, path.join(name), modules)?;
+    }
+    if module.units.is_empty() && !has_submodules {
+      return Ok(false);
+    }
+    modules.insert(path.to_string(), module);
+    Ok(true)
+  }
+
+  /// Parse the prelude into its module.
+  pub fn load_prelude(&mut self) -> VspResult<Module> {
+    let mut module = Module::new(Path::root().join(PRELUDE));
+    let unit = self.parse_source(PRELUDE_FILE.to_owned(), PRELUDE_SOURCE.to_owned())?;
+    module.units.push(unit);
+    Ok(module)
+  }
+
+  /// Parse the modules of the stdlib into the modules of the package, except those under `core`
+  /// and `std` if the package defines the modules itself.
+  pub fn load_stdlib(&mut self, modules: &mut HashMap<String, Module>) -> VspResult<()> {
+    let skipped = ["core", "std"]
+      .into_iter()
+      .filter(|root| modules.contains_key(*root))
+      .collect::<Vec<_>>();
+    for (file, source) in STDLIB_SOURCES {
+      let mut names = file.split('/').collect::<Vec<_>>();
+      names.pop();
+      if skipped.contains(&names[0]) {
+        continue;
+      }
+      let path = names.iter().fold(Path::root(), |path, name| path.join(*name));
+      let filename = format!("{}/{}", STDLIB_DIRECTORY, file);
+      let unit = self.parse_source(filename, source.to_string())?;
+      modules
+        .entry(path.to_string())
+        .or_insert_with(|| Module::new(path))
+        .units
+        .push(unit);
+    }
+    Ok(())
+  }
+
+  fn parse_file(&mut self, file: &FsPath) -> VspResult<CompilationUnit> {
+    let filename = file.to_string_lossy().to_string();
+    let source = std::fs::read_to_string(file).map_err(VspError::from)?;
+    self.parse_source(filename, source)
+  }
+
+  fn parse_source(&mut self, filename: String, source: String) -> VspResult<CompilationUnit> {
+    let tokens = DefaultLexer {}.tokenize(source.as_str());
+    let shebang = shebang(source.as_str());
+    self.source_manager.add_source(filename.as_str(), source);
+    let mut unit = TraditionalParser {}
+      .parse(tokens?)
+      .map_err(|e| VspError::new(format!("{}:{}", filename, e.message())))?;
+    unit.set_filename(filename);
+    unit.shebang = shebang;
+    Ok(unit)
+  }
+}
+
+fn is_package_root(entry: &FsPath) -> bool {
+  entry.file_name().map_or(false, |name| name == LIBRARY_ENTRY)
+    || entry.with_file_name(MODULE_DECLARATION).is_file()
+}
+
+/// Source files directly in the directory, ordered by name.
+fn list_source_files(directory: &FsPath) -> VspResult<Vec<std::path::PathBuf>> {
+  let mut files = vec![];
+  for entry in std::fs::read_dir(directory).map_err(VspError::from)? {
+    let path = entry.map_err(VspError::from)?.path();
+    if path.is_file() && path.extension().map_or(false, |ext| ext == SOURCE_EXTENSION) {
+      files.push(path);
+    }
+  }
+  files.sort();
+  Ok(files)
+}
+
+/// Subdirectories whose names are valid module names, ordered by name.
+fn list_module_directories(directory: &FsPath) -> VspResult<Vec<(String, std::path::PathBuf)>> {
+  let mut directories = vec![];
+  for entry in std::fs::read_dir(directory).map_err(VspError::from)? {
+    let path = entry.map_err(VspError::from)?.path();
+    let name = match path.file_name().and_then(|name| name.to_str()) {
+      Some(name) if path.is_dir() && is_module_name(name) => name.to_owned(),
+      _ => continue,
+    };
+    if !SKIPPED_DIRECTORIES.contains(&name.as_str()) {
+      directories.push((name, path));
+    }
+  }
+  directories.sort();
+  Ok(directories)
+}
+
+fn is_module_name(name: &str) -> bool {
+  let mut chars = name.chars();
+  matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+    && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+  use vsp_ast::ast::modifier::Accessibility;
+
+  use super::*;
+  use crate::testing::TempDir;
+
+  fn write(root: &FsPath, file: &str, source: &str) {
+    let path = root.join(file);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, source).unwrap();
+  }
+
+  #[test]
+  fn test_load_package() {
+    let root = TempDir::new("loader-package");
+    write(&root, "lib.vsp", "use collect::push;\nfunc main() {}");
+    write(&root, "module.vsp", "public module = {}");
+    write(&root, "build.vsp", "public func build() {}");
+    write(&root, "collect/List.vsp", "public func push() {}");
+    write(&root, "collect/Map.vsp", "public func insert() {}");
+    write(&root, "collect/module.vsp", "module = {}");
+    write(&root, "core/iter/Iterator.vsp", "public func next() {}");
+    write(&root, "resources/README.md", "");
+    write(&root, "target/debug/out.vsp", "");
+
+    let mut source_manager = SourceManager::default();
+    let unit = ModuleLoader::new(&mut source_manager).load(&root.join("lib.vsp")).unwrap();
+    let mut paths = unit.modules.keys().cloned().collect::<Vec<_>>();
+    paths.sort();
+    // The modules of the package named `core` replace those of the stdlib.
+    assert_eq!(
+      paths,
+      vec![
+        "",
+        "collect",
+        "core",
+        "core::iter",
+        "std",
+        "std::collect",
+        "std::lang",
+        "std::task",
+        PRELUDE
+      ]
+    );
+    assert_eq!(unit.modules[""].units.len(), 1);
+    assert_eq!(unit.modules["collect"].units.len(), 3);
+    assert_eq!(unit.modules["collect"].visibility, Accessibility::Private);
+    assert_eq!(unit.modules["core"].units.len(), 0);
+    assert_eq!(unit.modules["core::iter"].visibility, Accessibility::Public);
+    assert!(source_manager
+      .get_source(root.join("collect/List.vsp").to_str().unwrap())
+      .is_some());
+  }
+
+  #[test]
+  fn test_load_single_file() {
+    let root = TempDir::new("loader-single");
+    write(&root, "main.vsp", "func main() {}");
+    write(&root, "other/Other.vsp", "func other() {}");
+
+    let mut source_manager = SourceManager::default();
+    let unit = ModuleLoader::new(&mut source_manager).load(&root.join("main.vsp")).unwrap();
+    assert!(!unit.modules.contains_key("other"));
+    assert!(unit.modules[PRELUDE].units[0].functions.contains_key("println"));
+    assert_eq!(unit.modules["core::future"].units.len(), 2);
+    assert_eq!(
+      unit.modules["core"].units[0].filename(),
+      "<stdlib>/core/BigDecimal.vsp"
+    );
+    assert!(unit.functions.contains_key("main"));
+  }
+
+  #[test]
+  fn test_load_relative_entry() {
+    let root = TempDir::new("loader-relative");
+    write(&root, "lib.vsp", "func main() {}");
+    write(&root, "module.vsp", "public module = {}");
+    write(&root, "util/Count.vsp", "public func count() {}");
+
+    // Bare file names are relative to the current directory, which is the package root.
+    let cwd = std::env::current_dir().unwrap();
+    std::env::set_current_dir(&root).unwrap();
+    let mut source_manager = SourceManager::default();
+    let unit = ModuleLoader::new(&mut source_manager).load(FsPath::new("lib.vsp"));
+    std::env::set_current_dir(cwd).unwrap();
+    let unit = unit.unwrap();
+    assert!(unit.modules.contains_key("util"));
+    assert!(unit.modules.contains_key(""));
+  }
+}
@@ -1,13 +1,34 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::path::Path;
+
+use vsp_ast_parser::lex::shebang;
 use vsp_fs::vfs::FileObject;
 
-pub struct SourceManager {}
+pub mod loader;
+
+/// Source manager owns the texts of all loaded source files, keyed by their filenames.
+#[derive(Default)]
+pub struct SourceManager {
+  sources: HashMap<String, String>,
+}
 
 impl SourceManager {
   pub fn create_main_file_id(&mut self, file: &dyn FileObject) {}
-}
 
-impl Default for SourceManager {
-  fn default() -> Self {
-    Self {}
+  pub fn add_source(&mut self, filename: impl Into<String>, source: impl Into<String>) {
+    self.sources.insert(filename.into(), source.into());
   }
+
+  pub fn get_source(&self, filename: &str) -> Option<&str> {
+    self.sources.get(filename).map(String::as_str)
+  }
+}
+
+/// Shebang of the source file, which marks it as a script, without reading the rest of the file.
+pub fn read_shebang(file: &Path) -> Option<String> {
+  let mut line = String::new();
+  BufReader::new(std::fs::File::open(file).ok()?).read_line(&mut line).ok()?;
+  shebang(&line)
 }
@@ -0,0 +1,41 @@
+//! Fixtures shared by the tests of the compiler.
+use std::ops::Deref;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Directory created under the temporary directory of the system, which is removed on drop, so
+/// that it does not leak when the test panics.
+pub struct TempDir {
+  path: PathBuf,
+}
+
+impl TempDir {
+  /// Create the empty directory of the name, unique to the process, removing any left by a
+  /// previous run.
+  pub fn new(name: &str) -> TempDir {
+    let path = std::env::temp_dir().join(format!("vsp-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    TempDir { path }
+  }
+}
+
+impl Deref for TempDir {
+  type Target = Path;
+
+  fn deref(&self) -> &Path {
+    &self.path
+  }
+}
+
+impl AsRef<Path> for TempDir {
+  fn as_ref(&self) -> &Path {
+    &self.path
+  }
+}
+
+impl Drop for TempDir {
+  fn drop(&mut self) {
+    let _ = std::fs::remove_dir_all(&self.path);
+  }
+}
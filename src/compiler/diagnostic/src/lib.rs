@@ -1,32 +1,223 @@
+use core::fmt::Display;
+use core::fmt::Formatter;
+use std::fmt::Write;
+
+use vsp_span::Span;
+
 /// Alias for `DiagnosticLevel`.
 pub(crate) type Level = DiagnosticLevel;
 
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub enum DiagnosticLevel {
   Ignored,
   Note,
   Remark,
   Warning,
+  Error,
   Fatal,
 }
 
+impl Display for DiagnosticLevel {
+  fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+    f.write_str(match self {
+      DiagnosticLevel::Ignored => "ignored",
+      DiagnosticLevel::Note => "note",
+      DiagnosticLevel::Remark => "remark",
+      DiagnosticLevel::Warning => "warning",
+      DiagnosticLevel::Error => "error",
+      DiagnosticLevel::Fatal => "fatal",
+    })
+  }
+}
+
+/// A single diagnostic message reported by the compiler, located at the span of the source codes.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+  pub level:   Level,
+  pub message: String,
+  /// Source file which the span is located in.
+  pub file:    Option<String>,
+  pub span:    Option<Span>,
+  pub notes:   Vec<String>,
+}
+
+impl Diagnostic {
+  pub fn new(level: Level, message: impl Into<String>, span: Option<Span>) -> Self {
+    Self {
+      level,
+      message: message.into(),
+      file: None,
+      span,
+      notes: vec![],
+    }
+  }
+
+  pub fn error(message: impl Into<String>, span: Span) -> Self {
+    Self::new(Level::Error, message, Some(span))
+  }
+
+  pub fn warning(message: impl Into<String>, span: Span) -> Self {
+    Self::new(Level::Warning, message, Some(span))
+  }
+
+  /// Attach an additional note to the diagnostic.
+  pub fn with_note(mut self, note: impl Into<String>) -> Self {
+    self.notes.push(note.into());
+    self
+  }
+
+  /// Render the diagnostic with the source snippet which the span points at.
+  ///
+  /// ```plaintext
+  /// error: expected int16, found bool
+  ///  --> main.vsp:2:18
+  ///   |
+  /// 2 |   let x: int16 = true;
+  ///   |                  ^^^^
+  /// ```
+  pub fn render(&self, filename: &str, source: &str) -> String {
+    let mut buf = String::new();
+    let _ = writeln!(buf, "{}: {}", self.level, self.message);
+    if let Some(span) = &self.span {
+      let (line, column, end_line, end_column) = span.expand();
+      let gutter = " ".repeat(line.to_string().len());
+      let _ = writeln!(buf, "{}--> {}:{}:{}", gutter, filename, line, column);
+      if let Some(text) = source.lines().nth(line.saturating_sub(1)) {
+        let width = if end_line == line && end_column > column {
+          end_column - column
+        } else {
+          1
+        };
+        let _ = writeln!(buf, "{} |", gutter);
+        let _ = writeln!(buf, "{} | {}", line, text);
+        let _ = writeln!(
+          buf,
+          "{} | {}{}",
+          gutter,
+          " ".repeat(column.saturating_sub(1)),
+          "^".repeat(width)
+        );
+      }
+    }
+    for note in &self.notes {
+      let _ = writeln!(buf, "  = note: {}", note);
+    }
+    buf
+  }
+}
+
+impl Display for Diagnostic {
+  fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+    match &self.span {
+      Some(span) => write!(
+        f,
+        "{}:{}: {}: {}",
+        span.start.line, span.start.column, self.level, self.message
+      ),
+      None => write!(f, "{}: {}", self.level, self.message),
+    }
+  }
+}
+
 pub struct DiagnosticConsumer {}
 
 pub struct DiagnosticEngine {
-  suppressed: bool,
-  show_color: bool,
-  level:      Level,
-  owner:      DiagnosticConsumer,
+  suppressed:   bool,
+  show_color:   bool,
+  level:        Level,
+  owner:        DiagnosticConsumer,
+  diagnostics:  Vec<Diagnostic>,
+  current_file: Option<String>,
 }
 
-impl DiagnosticEngine {}
+impl DiagnosticEngine {
+  /// Report the diagnostic. Diagnostics below the minimum level are dropped.
+  pub fn emit(&mut self, mut diagnostic: Diagnostic) {
+    if self.suppressed || diagnostic.level < self.level {
+      return;
+    }
+    if diagnostic.file.is_none() {
+      diagnostic.file = self.current_file.clone();
+    }
+    self.diagnostics.push(diagnostic);
+  }
+
+  /// Set the source file which the diagnostics emitted later are located in, unless specified.
+  pub fn set_current_file(&mut self, file: Option<String>) {
+    self.current_file = file;
+  }
+
+  pub fn diagnostics(&self) -> &[Diagnostic] {
+    &self.diagnostics
+  }
+
+  /// Take out all reported diagnostics.
+  pub fn take(&mut self) -> Vec<Diagnostic> {
+    std::mem::take(&mut self.diagnostics)
+  }
+
+  pub fn error_count(&self) -> usize {
+    self.diagnostics.iter().filter(|d| d.level >= Level::Error).count()
+  }
+
+  pub fn has_errors(&self) -> bool {
+    self.error_count() > 0
+  }
+
+  /// Render all diagnostics against the source file.
+  pub fn render(&self, filename: &str, source: &str) -> String {
+    self
+      .diagnostics
+      .iter()
+      .map(|d| d.render(filename, source))
+      .collect::<Vec<_>>()
+      .join("\n")
+  }
+
+  /// Render all diagnostics against the source files they are located in, where the sources are
+  /// looked up by the filename.
+  pub fn render_files<'a>(&self, sources: impl Fn(&str) -> Option<&'a str>) -> String {
+    self
+      .diagnostics
+      .iter()
+      .map(|d| {
+        let filename = d.file.as_deref().unwrap_or("<unknown>");
+        d.render(filename, sources(filename).unwrap_or(""))
+      })
+      .collect::<Vec<_>>()
+      .join("\n")
+  }
+}
 
 impl Default for DiagnosticEngine {
   fn default() -> Self {
     Self {
-      suppressed: false,
-      show_color: false,
-      level:      Level::Remark,
-      owner:      DiagnosticConsumer {},
+      suppressed:   false,
+      show_color:   false,
+      level:        Level::Remark,
+      owner:        DiagnosticConsumer {},
+      diagnostics:  vec![],
+      current_file: None,
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use vsp_span::Position;
+
+  use super::*;
+
+  #[test]
+  fn test_render() {
+    let source = "func main() {\n  let x: int16 = true;\n}";
+    let span = Span::range(Position::at(2, 18), Position::at(2, 22));
+    let diagnostic = Diagnostic::error("expected int16, found bool", span);
+    let rendered = diagnostic.render("main.vsp", source);
+    let lines = rendered.lines().collect::<Vec<_>>();
+    assert_eq!(lines[0], "error: expected int16, found bool");
+    assert_eq!(lines[1], " --> main.vsp:2:18");
+    assert_eq!(lines[3], "2 |   let x: int16 = true;");
+    assert_eq!(lines[4], format!("  | {}^^^^", " ".repeat(17)));
+  }
+}
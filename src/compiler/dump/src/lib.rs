@@ -4,6 +4,9 @@ use std::path::Path;
 use std::path::PathBuf;
 
 use vsp_ast_parser::lex::DefaultLexer;
+use vsp_compiler::dispatch::CompilationDispatcher;
+use vsp_compiler::option::TargetOptions;
+use vsp_compiler::CompilerInstance;
 use vsp_error::VspError;
 use vsp_error::VspResult;
 
@@ -13,6 +16,8 @@ pub enum DumpType {
   Preprocessor,
   AST,
   LLVM,
+  MIR,
+  CHeader,
 }
 
 impl From<u8> for DumpType {
@@ -22,6 +27,8 @@ impl From<u8> for DumpType {
       1 => Self::Preprocessor,
       2 => Self::AST,
       3 => Self::LLVM,
+      4 => Self::MIR,
+      5 => Self::CHeader,
       _ => unreachable!("Unexpected value"),
     }
   }
@@ -50,7 +57,21 @@ impl Dumper {
     self
   }
 
+  /// Write the output into the file instead of the standard output.
+  pub fn to<P>(&mut self, path: P) -> VspResult<&Dumper>
+  where
+    P: AsRef<Path>,
+  {
+    self.output_stream = Box::new(std::fs::File::create(path).map_err(VspError::from)?);
+    Ok(self)
+  }
+
   pub fn dump(&mut self) -> VspResult<()> {
+    match self.dump_type {
+      DumpType::MIR => return self.dump_mir(),
+      DumpType::CHeader => return self.dump_c_header(),
+      _ => {}
+    }
     let content =
       std::fs::read_to_string(self.path.clone().unwrap()).map_err(|e| VspError::from(e))?;
     &self.dump_token(content.as_str());
@@ -64,4 +85,19 @@ impl Dumper {
       let _ = std::io::stdout().write(format!("{}\n", t).as_bytes());
     })
   }
+
+  /// Print the MIR of every function body in the package rooted at the input file.
+  fn dump_mir(&mut self) -> VspResult<()> {
+    let mut compiler = CompilerInstance::from(CompilationDispatcher, TargetOptions::default());
+    let program = compiler.lower_to_mir(self.path.as_ref().unwrap())?;
+    write!(self.output_stream, "{}", program).map_err(VspError::from)
+  }
+
+  /// Print the C header of the public `extern "C"` functions and `@Repr(C)` structs in the package
+  /// rooted at the input file.
+  fn dump_c_header(&mut self) -> VspResult<()> {
+    let mut compiler = CompilerInstance::from(CompilationDispatcher, TargetOptions::default());
+    let header = compiler.generate_c_header(self.path.as_ref().unwrap())?;
+    write!(self.output_stream, "{}", header).map_err(VspError::from)
+  }
 }
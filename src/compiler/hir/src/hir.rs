@@ -0,0 +1,276 @@
+//! Definition of the HIR.
+//!
+//! Each function and method with a body is lowered into a [`Body`], whose local variables are
+//! numbered and typed, and whose expressions carry their types and refer to the items by their
+//! resolved paths. So is the initializer of each constant and static item, as a body without
+//! parameters which returns the value.
+use vsp_ast::ast::expr::BinaryOp;
+use vsp_ast::ast::expr::UnaryOp;
+use vsp_ast::ast::modifier::Constancy;
+use vsp_ast::ast::types::Type;
+use vsp_sema::items::MethodPath;
+use vsp_sema::mono::MonoItem;
+use vsp_sema::resolve::DefPath;
+use vsp_span::Span;
+
+/// Bodies of all functions, methods, constants and static items in the package, in the order of
+/// appearance, with the declarations of the foreign functions and the definitions of the structs
+/// and the enums.
+#[derive(Debug, Default)]
+pub struct Program {
+  pub bodies:  Vec<Body>,
+  pub foreign: Vec<ForeignFunction>,
+  /// Structs sorted by their paths.
+  pub structs: Vec<StructDef>,
+  /// Enums sorted by their paths.
+  pub enums:   Vec<EnumDef>,
+}
+
+impl Program {
+  pub fn body(&self, owner: &MonoItem) -> Option<&Body> {
+    self.bodies.iter().find(|body| &body.owner == owner)
+  }
+
+  pub fn foreign_function(&self, def: &DefPath) -> Option<&ForeignFunction> {
+    self.foreign.iter().find(|function| &function.def == def)
+  }
+}
+
+/// Function declared by `extern "C"` without body, which is defined in foreign codes and linked by
+/// its name.
+#[derive(Clone, Debug)]
+pub struct ForeignFunction {
+  pub def:      DefPath,
+  pub params:   Vec<Type>,
+  pub ret:      Type,
+  /// Whether the function takes variable arguments after the parameters, such as `printf`.
+  pub variadic: bool,
+}
+
+impl ForeignFunction {
+  /// Name of the symbol, which is not mangled.
+  pub fn symbol(&self) -> &str {
+    &self.def.name
+  }
+}
+
+/// Definition of the struct, whose fields might mention the generic parameters.
+#[derive(Clone, Debug)]
+pub struct StructDef {
+  pub def:      DefPath,
+  pub generics: Vec<String>,
+  /// Types of the fields in the order of declaration.
+  pub fields:   Vec<Type>,
+}
+
+/// Definition of the enum, whose fields of the variants might mention the generic parameters.
+#[derive(Clone, Debug)]
+pub struct EnumDef {
+  pub def:           DefPath,
+  pub generics:      Vec<String>,
+  /// Types of the fields of each variant, in the order of declaration.
+  pub variants:      Vec<Vec<Type>>,
+  /// Constants of the discriminants of the variants, if any discriminant is given explicitly.
+  /// Otherwise, the discriminants are the indices of the variants.
+  pub discriminants: Vec<DefPath>,
+}
+
+/// Body of a function or a method. Types might mention the generic parameters of the function,
+/// which are substituted by the monomorphization.
+#[derive(Debug)]
+pub struct Body {
+  pub owner:     MonoItem,
+  /// Source file which the function is located in.
+  pub file:      Option<String>,
+  /// Local variables, where the parameters come first, including the receiver `self` of methods.
+  pub locals:    Vec<Local>,
+  pub params:    usize,
+  pub ret:       Type,
+  pub block:     Block,
+  pub span:      Span,
+  /// Whether the body could be evaluated at compile time, i.e. `const func` or the initializer.
+  pub constancy: Constancy,
+  /// State machine of `async func`, whose `poll` runs the body. `ret` is the output of the body.
+  pub coroutine: Option<Coroutine>,
+}
+
+/// State machine which calling `async func` returns, instead of running its body.
+#[derive(Debug)]
+pub struct Coroutine {
+  /// Type of the state machine, with the generic parameters of the function as the arguments.
+  pub ty:   Type,
+  /// Type `Poll<ty, ret>` which `poll` of the state machine returns.
+  pub poll: Type,
+}
+
+impl Body {
+  pub fn local(&self, id: LocalId) -> &Local {
+    &self.locals[id.0]
+  }
+
+  /// IDs of the parameters, in the order of the signature.
+  pub fn params(&self) -> impl Iterator<Item = LocalId> {
+    (0..self.params).map(LocalId)
+  }
+}
+
+/// Index of the local variable in the body.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct LocalId(pub usize);
+
+/// Loop targeted by `break` and `continue`, by its nesting depth in the body, where the outermost
+/// loop is 0.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct LoopId(pub usize);
+
+#[derive(Debug)]
+pub struct Local {
+  pub name:    String,
+  pub ty:      Type,
+  pub mutable: bool,
+  pub span:    Span,
+}
+
+#[derive(Debug, Default)]
+pub struct Block {
+  pub stmts: Vec<Stmt>,
+}
+
+#[derive(Debug)]
+pub struct Stmt {
+  pub kind: StmtKind,
+  pub span: Span,
+}
+
+/// Statement. Loops of all kinds are lowered into [`StmtKind::Loop`] with explicit
+/// [`StmtKind::Break`]s.
+#[derive(Debug)]
+pub enum StmtKind {
+  /// Declaration of the local variable with its initializer.
+  Let(LocalId, Option<Expr>),
+  Expr(Expr),
+  /// Conditional statement, where `else if` is lowered into the `else` block with a single `if`.
+  If(Expr, Block, Option<Block>),
+  /// Infinite loop, which is only exited by `break` or `return`.
+  Loop(Block),
+  /// Exit the loop.
+  Break(LoopId),
+  /// Jump to the start of the next iteration of the loop.
+  Continue(LoopId),
+  Return(Option<Expr>),
+  Block(Block),
+}
+
+/// Expression with its type.
+#[derive(Debug)]
+pub struct Expr {
+  pub kind: ExprKind,
+  pub ty:   Type,
+  pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum ExprKind {
+  Literal(Literal),
+  Local(LocalId),
+  /// Function with its type arguments, which are empty for non-generic functions.
+  Function(DefPath, Vec<Type>),
+  /// Method or associated function of the implementation.
+  Method(MethodPath),
+  /// Value of the constant, which is evaluated at compile time.
+  Const(DefPath),
+  /// Value of the static item, which is read from its location in memory.
+  Static(DefPath),
+  /// Method of the trait bound, called on the generic parameter `self_ty`. It is resolved to the
+  /// method of the implementation for the type argument by the monomorphization.
+  BoundMethod {
+    trait_:  DefPath,
+    name:    String,
+    self_ty: Type,
+  },
+  /// Unary operation, where the operand of `&` is a place, and dereferencing the raw pointer by
+  /// `*` results in a place.
+  Unary(UnaryOp, Box<Expr>),
+  /// Binary operation other than the assignment, where `&&` and `||` short-circuit. Raw pointers
+  /// are offset by `+` and `-` with `int64` on the right.
+  Binary(BinaryOp, Box<Expr>, Box<Expr>),
+  Assign(Box<Expr>, Box<Expr>),
+  /// Conditional expression, which evaluates only one of the branches.
+  If(Box<Expr>, Box<Expr>, Box<Expr>),
+  /// Pattern matching, which evaluates the body of the first arm whose pattern matches the value
+  /// of the scrutinee. The patterns are checked to be exhaustive.
+  Match(Box<Expr>, Vec<Arm>),
+  /// Block of statements as the body of the arm, whose value is unit.
+  Block(Block),
+  /// Conversion between primitive types, either explicit by `as` or implicit by widening.
+  Cast(Box<Expr>, Type),
+  /// Conversion of the value into the trait object, whose trait is given by the type of the
+  /// expression.
+  ToDyn(Box<Expr>),
+  /// Struct literal, with the index of each field in the declaration. Fields are evaluated in the
+  /// order written in the source.
+  Struct(Vec<(usize, Expr)>),
+  /// Field access by the index of the field in the declaration.
+  Field(Box<Expr>, usize),
+  /// Array of the elements, which are evaluated in order.
+  Array(Vec<Expr>),
+  /// Element of the array by the `int64` index, which is checked against the length of the array
+  /// when evaluated.
+  Index(Box<Expr>, Box<Expr>),
+  /// Enum value of the variant by its index in the declaration, with the fields of the variant.
+  Variant(usize, Vec<Expr>),
+  /// Discriminant of the enum value without fields as `int64`, which is the index of its variant
+  /// unless the discriminants are given by the constants of the variants.
+  Discriminant(Box<Expr>, Vec<DefPath>),
+  /// `operand.await`, which calls `poll` on the future until it is ready. While pending, `async
+  /// func` is suspended, and the executor `block_on` polls it again.
+  Await {
+    operand: Box<Expr>,
+    poll:    Box<Expr>,
+  },
+  /// Call of the function value, where the receivers of the methods are the first arguments.
+  Call(Box<Expr>, Vec<Expr>),
+  /// Call through the vtable of the trait object, by the index of the method in the trait.
+  DynCall {
+    object: Box<Expr>,
+    trait_: DefPath,
+    index:  usize,
+    args:   Vec<Expr>,
+  },
+}
+
+/// Arm of the match, whose bindings are declared as locals of the body.
+#[derive(Debug)]
+pub struct Arm {
+  pub pattern: Pattern,
+  pub body:    Expr,
+}
+
+/// Pattern with the type of the value which it matches.
+#[derive(Debug)]
+pub struct Pattern {
+  pub kind: PatternKind,
+  pub ty:   Type,
+  pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum PatternKind {
+  /// Matches anything, including the bindings whose names are not used.
+  Wild,
+  /// Matches anything, and binds the value to the local variable.
+  Binding(LocalId),
+  /// Matches the integer or the boolean equal to the literal.
+  Literal(Literal),
+  /// Matches the enum value of the variant by its index, whose fields match the patterns.
+  Variant(usize, Vec<Pattern>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+  Unit,
+  Integer(i64),
+  Float(f64),
+  Bool(bool),
+  Str(String),
+}
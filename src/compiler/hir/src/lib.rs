@@ -0,0 +1,26 @@
+//! # High-level IR
+//!
+//! HIR is a typed, name-resolved and desugared tree lowered from the AST after the type checking,
+//! which is consumed by the code generation instead of the AST.
+//!
+//! - Each expression carries its type, and implicit conversions are explicit.
+//! - Names refer to the numbered local variables, or to the items by their paths.
+//! - Method calls are resolved into calls of the methods, or calls through the vtables.
+//! - `while` and `for` loops are lowered into `loop` with `break`, and labels into the loops they
+//!   refer to. `for` over the iterator calls `has_next` and `next` of its `Iterator`.
+//! - The `?` operator is lowered into `match`, which returns the residual from the function.
+//!
+//! ```rust
+//! use vsp_ast::ast::CompilationUnit;
+//! use vsp_diag::DiagnosticEngine;
+//!
+//! let unit = CompilationUnit::new("main.vsp");
+//! let mut diagnostics = DiagnosticEngine::default();
+//! let results = vsp_sema::check_compilation_unit(&unit, &mut diagnostics);
+//! let program = vsp_hir::lower_compilation_unit(&unit, &results);
+//! assert!(program.bodies.is_empty());
+//! ```
+pub mod hir;
+pub mod lower;
+
+pub use crate::lower::lower_compilation_unit;
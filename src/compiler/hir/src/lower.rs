@@ -0,0 +1,1468 @@
+//! Lowering from the AST into the HIR.
+//!
+//! The lowering runs after the type checking without errors, so the types and the resolutions
+//! recorded by the type checker are always available.
+use std::collections::HashMap;
+
+use vsp_ast::ast::constant::ConstDefinition;
+use vsp_ast::ast::constant::ConstKind;
+use vsp_ast::ast::enumeration::Variant;
+use vsp_ast::ast::expr::BinaryOp;
+use vsp_ast::ast::expr::Expression;
+use vsp_ast::ast::expr::ExpressionKind;
+use vsp_ast::ast::expr::UnaryOp;
+use vsp_ast::ast::function::Function;
+use vsp_ast::ast::modifier::Constancy;
+use vsp_ast::ast::module::Path;
+use vsp_ast::ast::pattern::MatchArm;
+use vsp_ast::ast::pattern::Pattern as AstPattern;
+use vsp_ast::ast::pattern::PatternKind as AstPatternKind;
+use vsp_ast::ast::stmt::ForIterable;
+use vsp_ast::ast::stmt::ForStatement;
+use vsp_ast::ast::stmt::JumpStatement;
+use vsp_ast::ast::stmt::Statement;
+use vsp_ast::ast::stmt::StatementBlock;
+use vsp_ast::ast::types::FunctionType;
+use vsp_ast::ast::types::PrimitiveType;
+use vsp_ast::ast::types::Type;
+use vsp_ast::ast::CompilationUnit;
+use vsp_sema::check::TypeckResults;
+use vsp_sema::items::ItemTable;
+use vsp_sema::items::MethodCallee;
+use vsp_sema::items::MethodPath;
+use vsp_sema::lang::LangItem;
+use vsp_sema::mono::MonoItem;
+use vsp_sema::resolve::collect_modules;
+use vsp_sema::resolve::DefPath;
+use vsp_sema::ty::Ty;
+use vsp_span::Span;
+
+use crate::hir::Arm;
+use crate::hir::Block;
+use crate::hir::Body;
+use crate::hir::Coroutine;
+use crate::hir::EnumDef;
+use crate::hir::Expr;
+use crate::hir::ExprKind;
+use crate::hir::ForeignFunction;
+use crate::hir::Literal;
+use crate::hir::Local;
+use crate::hir::LocalId;
+use crate::hir::LoopId;
+use crate::hir::Pattern;
+use crate::hir::PatternKind;
+use crate::hir::Program;
+use crate::hir::Stmt;
+use crate::hir::StmtKind;
+use crate::hir::StructDef;
+
+/// Lower all functions and methods with bodies, constants and static items in the compilation unit
+/// and its modules, and collect the foreign functions and the structs.
+pub fn lower_compilation_unit(unit: &CompilationUnit, results: &TypeckResults) -> Program {
+  let items = results.items();
+  let mut program = Program::default();
+  for module in collect_modules(unit) {
+    for unit in &module.units {
+      let file = Some(unit.filename().to_owned()).filter(|f| !f.is_empty());
+      let mut bodies = vec![];
+      for definition in unit.consts.values() {
+        let def = DefPath::new(module.path.clone(), definition.name.as_str());
+        let lowering = LoweringContext::new(results);
+        bodies.push(lowering.lower_const(def, definition));
+      }
+      for definition in unit.enums.values() {
+        let def = DefPath::new(module.path.clone(), definition.name.as_str());
+        let discriminants = &items.enums[&def].discriminants;
+        for (index, variant) in definition.variants.iter().enumerate() {
+          if let Some(discriminant) = discriminants.get(index) {
+            let previous = index.checked_sub(1).map(|index| &discriminants[index]);
+            let lowering = LoweringContext::new(results);
+            bodies.push(lowering.lower_discriminant(discriminant.clone(), previous, variant));
+          }
+        }
+      }
+      let mut functions =
+        unit.functions.values().map(|function| (None, function)).collect::<Vec<_>>();
+      for definition in &unit.impls {
+        if let Some(info) = items.impl_at(&module.path, unit.filename(), &definition.span) {
+          functions.extend(definition.methods.iter().map(|method| (Some(info), method)));
+        }
+      }
+      functions.sort_by_key(|(_, function)| function.span.start);
+      for (info, function) in functions {
+        let (owner, signature, receiver) = match info {
+          Some(info) => {
+            let owner = MonoItem::Method(MethodPath {
+              impl_id: info.id,
+              name:    function.name.to_owned(),
+            });
+            let receiver = match function.signature.receiver {
+              true => Some(known(&info.self_ty)),
+              false => None,
+            };
+            (owner, &info.methods[&function.name].ty, receiver)
+          }
+          None => {
+            let def = DefPath::new(module.path.clone(), function.name.as_str());
+            let signature = &items.functions[&def].ty;
+            (MonoItem::Function(def), signature, None)
+          }
+        };
+        if let MonoItem::Function(def) = &owner {
+          if function.signature.abi.is_c() && function.body.is_none() {
+            let (params, ret) = items.functions[def].signature();
+            program.foreign.push(ForeignFunction {
+              def:      def.clone(),
+              params:   params.iter().map(known).collect(),
+              ret:      known(ret),
+              variadic: function.signature.variadic,
+            });
+            continue;
+          }
+        }
+        let lowering = LoweringContext::new(results);
+        let body = match &owner {
+          MonoItem::Function(def) if items.functions[def].asyncness.is_async() => {
+            lowering.lower_async_body(def, function)
+          }
+          MonoItem::Function(def) if items.lang.item_of(def) == Some(LangItem::BlockOn) => {
+            Some(lowering.lower_block_on(owner.clone(), function, signature))
+          }
+          _ => lowering.lower_body(owner, function, signature, receiver),
+        };
+        bodies.extend(body);
+      }
+      bodies.sort_by_key(|body| body.span.start);
+      for mut body in bodies {
+        body.file = file.clone();
+        program.bodies.push(body);
+      }
+    }
+  }
+  program.structs = items
+    .structs
+    .values()
+    .map(|info| StructDef {
+      def:      info.def.clone(),
+      generics: info.generics.names(),
+      fields:   info.fields.iter().map(|field| known(&field.ty)).collect(),
+    })
+    .collect();
+  program.structs.sort_by_key(|definition| definition.def.to_string());
+  program.enums = items
+    .enums
+    .values()
+    .map(|info| EnumDef {
+      def:           info.def.clone(),
+      generics:      info.generics.names(),
+      variants:      info
+        .variants
+        .iter()
+        .map(|variant| variant.fields.iter().map(known).collect())
+        .collect(),
+      discriminants: info.discriminants.clone(),
+    })
+    .collect();
+  program.enums.sort_by_key(|definition| definition.def.to_string());
+  program
+}
+
+/// Context to lower the body of a function.
+struct LoweringContext<'a> {
+  results: &'a TypeckResults,
+  locals:  Vec<Local>,
+  scopes:  Vec<HashMap<String, LocalId>>,
+  /// Labels of the loops around the statement being lowered, with the innermost one last.
+  loops:   Vec<Option<String>>,
+  /// Return type of the body, which the residuals of `?` are returned as.
+  ret:     Type,
+}
+
+impl<'a> LoweringContext<'a> {
+  fn new(results: &'a TypeckResults) -> Self {
+    Self {
+      results,
+      locals: vec![],
+      scopes: vec![HashMap::new()],
+      loops: vec![],
+      ret: Type::unit(),
+    }
+  }
+
+  fn lower_body(
+    mut self,
+    owner: MonoItem,
+    function: &Function,
+    signature: &Ty,
+    receiver: Option<Type>,
+  ) -> Option<Body> {
+    let block = function.body.as_ref()?;
+    let (params, ret) = match signature {
+      Ty::Function(params, ret) => (params, ret),
+      ty => unreachable!("expected function type, found `{}`", ty),
+    };
+    if let Some(ty) = receiver {
+      self.declare("self", ty, false, function.span.clone());
+    }
+    for (param, ty) in function.signature.parameters.iter().zip(params) {
+      self.declare(param.name.as_str(), known(ty), false, param.span.clone());
+    }
+    let params = self.locals.len();
+    self.ret = known(ret);
+    let block = self.lower_block(block);
+    Some(Body {
+      owner,
+      file: None,
+      locals: self.locals,
+      params,
+      ret: self.ret,
+      block,
+      span: function.span.clone(),
+      constancy: function.signature.constancy,
+      coroutine: None,
+    })
+  }
+
+  /// Lower the body of `async func`, which returns the output instead of the state machine.
+  fn lower_async_body(self, def: &DefPath, function: &Function) -> Option<Body> {
+    let items = self.results.items();
+    let info = &items.functions[def];
+    let (signature, ty) = match &info.ty {
+      Ty::Function(params, ty) => (
+        Ty::Function(params.clone(), Box::new(info.output.clone())),
+        known(ty),
+      ),
+      ty => unreachable!("expected function type, found `{}`", ty),
+    };
+    let owner = MonoItem::Function(def.clone());
+    let mut body = self.lower_body(owner, function, &signature, None)?;
+    body.coroutine = Some(Coroutine {
+      poll: poll_type(items, ty.clone(), body.ret.clone()),
+      ty,
+    });
+    Some(body)
+  }
+
+  /// `block_on` of the lang item is declared without a body, and the compiler generates the
+  /// executor `return future.await;`, which polls the future until it is ready.
+  fn lower_block_on(mut self, owner: MonoItem, function: &Function, signature: &Ty) -> Body {
+    let items = self.results.items();
+    let (future, output) = match signature {
+      Ty::Function(params, output) => (known(&params[0]), known(output)),
+      ty => unreachable!("expected function type, found `{}`", ty),
+    };
+    let param = &function.signature.parameters[0];
+    let local = self.declare(
+      param.name.as_str(),
+      future.clone(),
+      false,
+      param.span.clone(),
+    );
+    let span = function.span.clone();
+    let poll = poll_type(items, future.clone(), output.clone());
+    let poll = Expr {
+      kind: ExprKind::BoundMethod {
+        trait_:  items.lang.get(LangItem::Future).cloned().unwrap(),
+        name:    "poll".to_owned(),
+        self_ty: future.clone(),
+      },
+      ty:   Type::Function(FunctionType::new(vec![future.clone()], Box::new(poll))),
+      span: span.clone(),
+    };
+    let operand = Expr {
+      kind: ExprKind::Local(local),
+      ty:   future,
+      span: span.clone(),
+    };
+    let value = Expr {
+      kind: ExprKind::Await {
+        operand: Box::new(operand),
+        poll:    Box::new(poll),
+      },
+      ty:   output.clone(),
+      span: span.clone(),
+    };
+    Body {
+      owner,
+      file: None,
+      locals: self.locals,
+      params: 1,
+      ret: output,
+      block: Block {
+        stmts: vec![Stmt {
+          kind: StmtKind::Return(Some(value)),
+          span: span.clone(),
+        }],
+      },
+      span,
+      constancy: Constancy::None,
+      coroutine: None,
+    }
+  }
+
+  /// Lower the initializer of the constant or the static item into the body returning its value.
+  fn lower_const(mut self, def: DefPath, definition: &ConstDefinition) -> Body {
+    let ty = known(&self.results.items().consts[&def].ty);
+    self.ret = ty.clone();
+    let init = self.lower_expr(&definition.init);
+    let span = init.span.clone();
+    let owner = match definition.kind {
+      ConstKind::Const => MonoItem::Const(def),
+      ConstKind::Static => MonoItem::Static(def),
+    };
+    Body {
+      owner,
+      file: None,
+      locals: self.locals,
+      params: 0,
+      ret: ty,
+      block: Block {
+        stmts: vec![Stmt {
+          kind: StmtKind::Return(Some(init)),
+          span,
+        }],
+      },
+      span: definition.span.clone(),
+      constancy: Constancy::Constant,
+      coroutine: None,
+    }
+  }
+
+  /// Lower the discriminant of the variant into the body of its constant. The discriminant not
+  /// given explicitly is next to the previous one, or 0 for the first variant.
+  fn lower_discriminant(
+    mut self,
+    def: DefPath,
+    previous: Option<&DefPath>,
+    variant: &Variant,
+  ) -> Body {
+    let ty = Type::int64();
+    let span = variant.span.clone();
+    let literal = |value| Expr {
+      kind: ExprKind::Literal(Literal::Integer(value)),
+      ty:   ty.clone(),
+      span: span.clone(),
+    };
+    let value = match (&variant.discriminant, previous) {
+      (Some(discriminant), _) => self.lower_expr(discriminant),
+      (None, Some(previous)) => {
+        let previous = Expr {
+          kind: ExprKind::Const(previous.clone()),
+          ty:   ty.clone(),
+          span: span.clone(),
+        };
+        Expr {
+          kind: ExprKind::Binary(BinaryOp::Add, Box::new(previous), Box::new(literal(1))),
+          ty:   ty.clone(),
+          span: span.clone(),
+        }
+      }
+      (None, None) => literal(0),
+    };
+    Body {
+      owner: MonoItem::Const(def),
+      file: None,
+      locals: self.locals,
+      params: 0,
+      ret: ty,
+      block: Block {
+        stmts: vec![Stmt {
+          kind: StmtKind::Return(Some(value)),
+          span: span.clone(),
+        }],
+      },
+      span,
+      constancy: Constancy::Constant,
+      coroutine: None,
+    }
+  }
+
+  fn declare(&mut self, name: &str, ty: Type, mutable: bool, span: Span) -> LocalId {
+    let id = self.declare_hidden(name, ty, mutable, span);
+    self.scopes.last_mut().unwrap().insert(name.to_owned(), id);
+    id
+  }
+
+  /// Declare the local variable introduced by the lowering, which could not be referred by name.
+  fn declare_hidden(&mut self, name: &str, ty: Type, mutable: bool, span: Span) -> LocalId {
+    let id = LocalId(self.locals.len());
+    self.locals.push(Local {
+      name: name.to_owned(),
+      ty,
+      mutable,
+      span,
+    });
+    id
+  }
+
+  fn lookup(&self, name: &str) -> Option<LocalId> {
+    self.scopes.iter().rev().find_map(|scope| scope.get(name).copied())
+  }
+
+  fn lower_block(&mut self, block: &StatementBlock) -> Block {
+    self.scopes.push(HashMap::new());
+    let mut stmts = vec![];
+    block.stmts().iter().for_each(|stmt| self.lower_stmt(stmt, &mut stmts));
+    self.scopes.pop();
+    Block { stmts }
+  }
+
+  fn lower_stmt(&mut self, stmt: &Statement, stmts: &mut Vec<Stmt>) {
+    let (kind, span) = match stmt {
+      Statement::NoOp => return,
+      Statement::Expression(expr) => {
+        let expr = self.lower_expr(expr);
+        let span = expr.span.clone();
+        (StmtKind::Expr(expr), span)
+      }
+      Statement::If(stmt) => {
+        let condition = self.lower_expr(&stmt.condition);
+        let then = self.lower_block(&stmt.then);
+        let otherwise = stmt.otherwise.as_ref().map(|otherwise| match otherwise.as_ref() {
+          Statement::Block(block) => self.lower_block(block),
+          otherwise => {
+            let mut stmts = vec![];
+            self.lower_stmt(otherwise, &mut stmts);
+            Block { stmts }
+          }
+        });
+        (StmtKind::If(condition, then, otherwise), stmt.span.clone())
+      }
+      // `while cond { body }` is lowered into `loop { if !cond { break; } body }`.
+      Statement::While(stmt) => {
+        let condition = self.lower_expr(&stmt.condition);
+        let id = self.enter_loop(&stmt.label);
+        let mut body = self.lower_block(&stmt.body);
+        self.loops.pop();
+        body.stmts.insert(0, break_unless(condition, id));
+        (StmtKind::Loop(body), stmt.span.clone())
+      }
+      Statement::Loop(stmt) => {
+        self.enter_loop(&stmt.label);
+        let body = self.lower_block(&stmt.body);
+        self.loops.pop();
+        (StmtKind::Loop(body), stmt.span.clone())
+      }
+      Statement::For(stmt) => (self.lower_for(stmt), stmt.span.clone()),
+      Statement::Break(stmt) => (StmtKind::Break(self.loop_id(stmt)), stmt.span.clone()),
+      Statement::Continue(stmt) => (StmtKind::Continue(self.loop_id(stmt)), stmt.span.clone()),
+      Statement::VariableDeclaration(decl) => {
+        // The initializer is lowered first, since it could not see the variable itself.
+        let init = decl.init.as_ref().map(|init| self.lower_expr(init));
+        let ty = match self.results.local_type(decl.id) {
+          Some(ty) => ty.clone(),
+          None => panic!("type of the local variable `{}` is unknown", decl.name),
+        };
+        let id = self.declare(decl.name.as_str(), ty, decl.mutable, decl.span.clone());
+        (StmtKind::Let(id, init), decl.span.clone())
+      }
+      Statement::Return(stmt) => {
+        let value = stmt.value.as_ref().map(|value| self.lower_expr(value));
+        (StmtKind::Return(value), stmt.span.clone())
+      }
+      // `unsafe` blocks are only checked by the type checking.
+      Statement::Block(block) | Statement::Unsafe(block) => {
+        let block = self.lower_block(block);
+        // Blocks have no spans of their own, so they span over their statements.
+        let span = match (block.stmts.first(), block.stmts.last()) {
+          (Some(first), Some(last)) => Span::range(first.span.start, last.span.end),
+          _ => Span::default(),
+        };
+        (StmtKind::Block(block), span)
+      }
+    };
+    stmts.push(Stmt { kind, span });
+  }
+
+  fn lower_for(&mut self, stmt: &ForStatement) -> StmtKind {
+    match &stmt.iterable {
+      ForIterable::Range(start, end) => self.lower_range(stmt, start, end),
+      ForIterable::Iterator(iterator) => self.lower_iterator(stmt, iterator),
+    }
+  }
+
+  /// `for i in start..end { body }` is lowered into
+  ///
+  /// ```vsp
+  /// {
+  ///   var next = start;
+  ///   let end = end;
+  ///   loop { if !(next < end) { break; } let i = next; next = next + 1; body }
+  /// }
+  /// ```
+  ///
+  /// where `next` is advanced before the body, so that `continue` goes on with the next integer.
+  /// `next` and `end` are hidden from the body.
+  fn lower_range(&mut self, stmt: &ForStatement, start: &Expression, end: &Expression) -> StmtKind {
+    let start = self.lower_expr(start);
+    let end = self.lower_expr(end);
+    let ty = self.binding_type(stmt);
+    let span = stmt.span.clone();
+    let next = self.declare_hidden("next", ty.clone(), true, span.clone());
+    let last = self.declare_hidden("end", ty.clone(), false, span.clone());
+    let local = |id: LocalId| Expr {
+      kind: ExprKind::Local(id),
+      ty:   ty.clone(),
+      span: span.clone(),
+    };
+    let binary = |op: BinaryOp, left: Expr, right: Expr, ty: Type| Expr {
+      kind: ExprKind::Binary(op, Box::new(left), Box::new(right)),
+      ty,
+      span: span.clone(),
+    };
+    let stmt_of = |kind: StmtKind| Stmt {
+      kind,
+      span: span.clone(),
+    };
+
+    let (id, binding, body) = self.lower_for_body(stmt, ty.clone());
+    let condition = binary(
+      BinaryOp::Less,
+      local(next),
+      local(last),
+      Type::Primitive(PrimitiveType::Bool),
+    );
+    let one = Expr {
+      kind: ExprKind::Literal(Literal::Integer(1)),
+      ty:   ty.clone(),
+      span: span.clone(),
+    };
+    let advance = Expr {
+      kind: ExprKind::Assign(
+        Box::new(local(next)),
+        Box::new(binary(BinaryOp::Add, local(next), one, ty.clone())),
+      ),
+      ty:   Type::unit(),
+      span: span.clone(),
+    };
+    let body = Block {
+      stmts: vec![
+        break_unless(condition, id),
+        stmt_of(StmtKind::Let(binding, Some(local(next)))),
+        stmt_of(StmtKind::Expr(advance)),
+        stmt_of(StmtKind::Block(body)),
+      ],
+    };
+    StmtKind::Block(Block {
+      stmts: vec![
+        stmt_of(StmtKind::Let(next, Some(start))),
+        stmt_of(StmtKind::Let(last, Some(end))),
+        stmt_of(StmtKind::Loop(body)),
+      ],
+    })
+  }
+
+  /// `for x in iterator { body }` is lowered into
+  ///
+  /// ```vsp
+  /// {
+  ///   let iter = iterator;
+  ///   loop { if !iter.has_next() { break; } let x = iter.next(); body }
+  /// }
+  /// ```
+  ///
+  /// by the methods of the implementation of `Iterator`, where `iter` is hidden from the body.
+  fn lower_iterator(&mut self, stmt: &ForStatement, iterator: &Expression) -> StmtKind {
+    let iterator = self.lower_expr(iterator);
+    let entry = self.binding_type(stmt);
+    let span = stmt.span.clone();
+    let iter_ty = iterator.ty.clone();
+    let iter = self.declare_hidden("iter", iter_ty.clone(), false, span.clone());
+    let method = |name: &str, ret: Type| {
+      let kind = match self.results.method_callee(stmt.id) {
+        Some(MethodCallee::Static(path)) => ExprKind::Method(MethodPath {
+          impl_id: path.impl_id,
+          name:    name.to_owned(),
+        }),
+        Some(MethodCallee::Bound { trait_, .. }) => ExprKind::BoundMethod {
+          trait_:  trait_.clone(),
+          name:    name.to_owned(),
+          self_ty: iter_ty.clone(),
+        },
+        _ => panic!("iterator of `for` is not resolved"),
+      };
+      let callee = Expr {
+        kind,
+        ty: Type::Function(FunctionType::new(
+          vec![iter_ty.clone()],
+          Box::new(ret.clone()),
+        )),
+        span: span.clone(),
+      };
+      let receiver = Expr {
+        kind: ExprKind::Local(iter),
+        ty:   iter_ty.clone(),
+        span: span.clone(),
+      };
+      Expr {
+        kind: ExprKind::Call(Box::new(callee), vec![receiver]),
+        ty:   ret,
+        span: span.clone(),
+      }
+    };
+    let has_next = method("has_next", Type::Primitive(PrimitiveType::Bool));
+    let next = method("next", entry.clone());
+    let stmt_of = |kind: StmtKind| Stmt {
+      kind,
+      span: span.clone(),
+    };
+
+    let (id, binding, body) = self.lower_for_body(stmt, entry);
+    let body = Block {
+      stmts: vec![
+        break_unless(has_next, id),
+        stmt_of(StmtKind::Let(binding, Some(next))),
+        stmt_of(StmtKind::Block(body)),
+      ],
+    };
+    StmtKind::Block(Block {
+      stmts: vec![
+        stmt_of(StmtKind::Let(iter, Some(iterator))),
+        stmt_of(StmtKind::Loop(body)),
+      ],
+    })
+  }
+
+  /// Lower the body of the for statement in its loop, where the binding of the type is declared.
+  /// Returns the loop, the binding and the body.
+  fn lower_for_body(&mut self, stmt: &ForStatement, ty: Type) -> (LoopId, LocalId, Block) {
+    let id = self.enter_loop(&stmt.label);
+    self.scopes.push(HashMap::new());
+    let binding = self.declare(stmt.name.as_str(), ty, false, stmt.span.clone());
+    let body = self.lower_block(&stmt.body);
+    self.scopes.pop();
+    self.loops.pop();
+    (id, binding, body)
+  }
+
+  fn binding_type(&self, stmt: &ForStatement) -> Type {
+    match self.results.local_type(stmt.id) {
+      Some(ty) => ty.clone(),
+      None => panic!("type of the local variable `{}` is unknown", stmt.name),
+    }
+  }
+
+  /// Enter the loop with the label, and return its ID.
+  fn enter_loop(&mut self, label: &Option<String>) -> LoopId {
+    self.loops.push(label.clone());
+    LoopId(self.loops.len() - 1)
+  }
+
+  /// The loop targeted by `break` or `continue`, which is the innermost one without label.
+  fn loop_id(&self, stmt: &JumpStatement) -> LoopId {
+    let depth = match &stmt.label {
+      Some(label) => self.loops.iter().rposition(|l| l.as_ref() == Some(label)),
+      None => self.loops.len().checked_sub(1),
+    };
+    LoopId(depth.expect("jumps are checked to be in loops"))
+  }
+
+  /// Lower the expression, with its implicit conversion made explicit.
+  fn lower_expr(&mut self, expr: &Expression) -> Expr {
+    let lowered = Expr {
+      kind: self.lower_expr_kind(expr),
+      ty:   self.type_of(expr),
+      span: expr.span.clone(),
+    };
+    let kind = match self.results.coercion(expr.id) {
+      Some(Type::Dyn(_)) => ExprKind::ToDyn(Box::new(lowered)),
+      Some(target) => ExprKind::Cast(Box::new(lowered), target.clone()),
+      None => return lowered,
+    };
+    Expr {
+      kind,
+      ty: self.results.coercion(expr.id).cloned().unwrap(),
+      span: expr.span.clone(),
+    }
+  }
+
+  fn lower_expr_kind(&mut self, expr: &Expression) -> ExprKind {
+    match &expr.kind {
+      ExpressionKind::Unit => ExprKind::Literal(Literal::Unit),
+      ExpressionKind::LiteralInteger(value) => ExprKind::Literal(Literal::Integer(*value)),
+      ExpressionKind::LiteralFloat(value) => ExprKind::Literal(Literal::Float(*value)),
+      ExpressionKind::LiteralBoolean(value) => ExprKind::Literal(Literal::Bool(*value)),
+      ExpressionKind::LiteralString(value) => ExprKind::Literal(Literal::Str(value.to_owned())),
+      ExpressionKind::Identifier(name) => match self.lookup(name) {
+        Some(id) => ExprKind::Local(id),
+        None => self.lower_item_ref(expr),
+      },
+      ExpressionKind::Path(path) => match self.results.method_callee(expr.id) {
+        Some(MethodCallee::Static(path)) => ExprKind::Method(path.clone()),
+        Some(MethodCallee::Bound { trait_, name }) => ExprKind::BoundMethod {
+          trait_:  trait_.clone(),
+          name:    name.to_owned(),
+          self_ty: Type::named(path.parent().unwrap_or_default()),
+        },
+        Some(MethodCallee::Dynamic { .. }) => {
+          unreachable!("paths are never dispatched dynamically")
+        }
+        None => self.lower_item_ref(expr),
+      },
+      ExpressionKind::Unary(op, operand) => {
+        ExprKind::Unary(op.clone(), Box::new(self.lower_expr(operand)))
+      }
+      ExpressionKind::Binary(BinaryOp::Assignment, place, value) => ExprKind::Assign(
+        Box::new(self.lower_expr(place)),
+        Box::new(self.lower_expr(value)),
+      ),
+      ExpressionKind::Binary(op, left, right) => ExprKind::Binary(
+        op.clone(),
+        Box::new(self.lower_expr(left)),
+        Box::new(self.lower_expr(right)),
+      ),
+      ExpressionKind::Cast(operand, _) => {
+        let mut operand = self.lower_expr(operand);
+        // Enums are converted through their discriminants.
+        let discriminants = match &operand.ty {
+          Type::Named(path, _) => {
+            let info = self.results.items().enums.get(&def_path(path));
+            info.map(|info| info.discriminants.clone())
+          }
+          _ => None,
+        };
+        if let Some(discriminants) = discriminants {
+          let span = operand.span.clone();
+          operand = Expr {
+            kind: ExprKind::Discriminant(Box::new(operand), discriminants),
+            ty: Type::int64(),
+            span,
+          };
+        }
+        ExprKind::Cast(Box::new(operand), self.type_of(expr))
+      }
+      ExpressionKind::Call(callee, args) => {
+        let args = args.iter().map(|arg| self.lower_expr(arg)).collect();
+        match self.results.variant(expr.id) {
+          Some(index) => ExprKind::Variant(index, args),
+          None => ExprKind::Call(Box::new(self.lower_expr(callee)), args),
+        }
+      }
+      ExpressionKind::StructLiteral(_, fields) => {
+        let def = match self.type_of(expr) {
+          Type::Named(path, _) => def_path(&path),
+          ty => unreachable!("expected struct, found `{}`", ty),
+        };
+        let declared = &self.results.items().structs[&def].fields;
+        let fields = fields
+          .iter()
+          .map(|(name, value)| {
+            let index = declared.iter().position(|f| &f.name == name).unwrap();
+            (index, self.lower_expr(value))
+          })
+          .collect();
+        ExprKind::Struct(fields)
+      }
+      ExpressionKind::Field(base, name) => match self.results.field_index(expr.id) {
+        Some(index) => ExprKind::Field(Box::new(self.lower_expr(base)), index),
+        None => panic!("field `{}` is not resolved", name),
+      },
+      ExpressionKind::ArrayLiteral(elements) => {
+        ExprKind::Array(elements.iter().map(|element| self.lower_expr(element)).collect())
+      }
+      ExpressionKind::Index(base, index) => ExprKind::Index(
+        Box::new(self.lower_expr(base)),
+        Box::new(self.lower_expr(index)),
+      ),
+      ExpressionKind::MethodCall(receiver, _, args) => self.lower_method_call(expr, receiver, args),
+      ExpressionKind::Try(operand) => self.lower_try(expr, operand),
+      ExpressionKind::Await(operand) => self.lower_await(expr, operand),
+      ExpressionKind::If(condition, then, otherwise) => ExprKind::If(
+        Box::new(self.lower_expr(condition)),
+        Box::new(self.lower_expr(then)),
+        Box::new(self.lower_expr(otherwise)),
+      ),
+      ExpressionKind::Match(scrutinee, arms) => ExprKind::Match(
+        Box::new(self.lower_expr(scrutinee)),
+        arms.iter().map(|arm| self.lower_arm(arm)).collect(),
+      ),
+      ExpressionKind::Block(block) => ExprKind::Block(self.lower_block(block)),
+      ExpressionKind::LambdaExpression(..) => unreachable!("lambdas are rejected by type checking"),
+    }
+  }
+
+  /// The bindings of the pattern are only in scope of the body of the arm.
+  fn lower_arm(&mut self, arm: &MatchArm) -> Arm {
+    self.scopes.push(HashMap::new());
+    let pattern = self.lower_pattern(&arm.pattern);
+    let body = self.lower_expr(&arm.body);
+    self.scopes.pop();
+    Arm { pattern, body }
+  }
+
+  fn lower_pattern(&mut self, pattern: &AstPattern) -> Pattern {
+    let ty = match self.results.local_type(pattern.id) {
+      Some(ty) => ty.clone(),
+      None => panic!("type of the pattern is unknown"),
+    };
+    let span = pattern.span.clone();
+    let kind = match &pattern.kind {
+      AstPatternKind::Wildcard => PatternKind::Wild,
+      AstPatternKind::Binding(name) => {
+        PatternKind::Binding(self.declare(name.as_str(), ty.clone(), false, span.clone()))
+      }
+      AstPatternKind::Integer(value) => PatternKind::Literal(Literal::Integer(*value)),
+      AstPatternKind::Boolean(value) => PatternKind::Literal(Literal::Bool(*value)),
+      AstPatternKind::Variant(_, fields) => match self.results.variant(pattern.id) {
+        Some(index) => PatternKind::Variant(
+          index,
+          fields.iter().map(|f| self.lower_pattern(f)).collect(),
+        ),
+        None => panic!("variant of the pattern is not resolved"),
+      },
+    };
+    Pattern { kind, ty, span }
+  }
+
+  /// Lower the reference to the function, the constant, the static item or the unit variant.
+  fn lower_item_ref(&self, expr: &Expression) -> ExprKind {
+    if let Some(index) = self.results.variant(expr.id) {
+      return ExprKind::Variant(index, vec![]);
+    }
+    if let Some(def) = self.results.constant(expr.id) {
+      return match self.results.items().consts[def].kind {
+        ConstKind::Const => ExprKind::Const(def.clone()),
+        ConstKind::Static => ExprKind::Static(def.clone()),
+      };
+    }
+    match self.results.function(expr.id) {
+      Some(def) => {
+        let args = self.results.instantiation(expr.id).unwrap_or_default();
+        ExprKind::Function(def.clone(), args.to_vec())
+      }
+      None => panic!("expression is not resolved"),
+    }
+  }
+
+  /// Method calls are resolved into the calls of the methods with the receivers as the first
+  /// arguments, except for those on trait objects which are dispatched through the vtables.
+  fn lower_method_call(
+    &mut self,
+    expr: &Expression,
+    receiver: &Expression,
+    args: &[Expression],
+  ) -> ExprKind {
+    let receiver = self.lower_expr(receiver);
+    let args = args.iter().map(|arg| self.lower_expr(arg)).collect::<Vec<_>>();
+    let callee = match self.results.method_callee(expr.id) {
+      Some(MethodCallee::Dynamic { trait_, index }) => {
+        return ExprKind::DynCall {
+          object: Box::new(receiver),
+          trait_: trait_.clone(),
+          index: *index,
+          args,
+        }
+      }
+      Some(MethodCallee::Static(path)) => ExprKind::Method(path.clone()),
+      Some(MethodCallee::Bound { trait_, name }) => ExprKind::BoundMethod {
+        trait_:  trait_.clone(),
+        name:    name.to_owned(),
+        self_ty: receiver.ty.clone(),
+      },
+      None => panic!("method call is not resolved"),
+    };
+    // Arguments are converted to the types of the parameters already.
+    let params = std::iter::once(&receiver)
+      .chain(args.iter())
+      .map(|arg| arg.ty.clone())
+      .collect();
+    let callee = Expr {
+      kind: callee,
+      ty:   Type::Function(FunctionType::new(params, Box::new(self.type_of(expr)))),
+      span: expr.span.clone(),
+    };
+    let mut args = args;
+    args.insert(0, receiver);
+    ExprKind::Call(Box::new(callee), args)
+  }
+
+  /// `operand?` is lowered into
+  ///
+  /// ```vsp
+  /// match operand {
+  ///   Expected::Value(value) => value,
+  ///   Expected::Error(error) => { return Expected::Error(convert(error)); }
+  /// }
+  /// ```
+  ///
+  /// where `convert` is the conversion of the error if any, and `Optional::None` is returned as
+  /// `Optional::None` of the return type.
+  fn lower_try(&mut self, expr: &Expression, operand: &Expression) -> ExprKind {
+    let operand = self.lower_expr(operand);
+    let items = self.results.items();
+    let span = expr.span.clone();
+    let (item, args) = match &operand.ty {
+      Type::Named(path, args) => (items.lang.item_of(&def_path(path)), args.clone()),
+      _ => (None, vec![]),
+    };
+    let item = item.expect("operand of `?` is neither `Optional` nor `Expected`");
+    let local = |id: LocalId, ty: &Type| Expr {
+      kind: ExprKind::Local(id),
+      ty:   ty.clone(),
+      span: span.clone(),
+    };
+    let pattern = |kind: PatternKind, ty: &Type| Pattern {
+      kind,
+      ty: ty.clone(),
+      span: span.clone(),
+    };
+
+    let value = self.declare_hidden("value", args[0].clone(), false, span.clone());
+    let value_arm = Arm {
+      pattern: pattern(
+        PatternKind::Variant(
+          LangItem::VALUE,
+          vec![pattern(PatternKind::Binding(value), &args[0])],
+        ),
+        &operand.ty,
+      ),
+      body:    local(value, &args[0]),
+    };
+    let mut patterns = vec![];
+    let mut fields = vec![];
+    if item == LangItem::Expected {
+      let error = self.declare_hidden("error", args[1].clone(), false, span.clone());
+      patterns.push(pattern(PatternKind::Binding(error), &args[1]));
+      let field = match self.results.conversion(expr.id) {
+        Some(path) => {
+          let signature = known(&items.impl_info(path.impl_id).methods[&path.name].ty);
+          let converted = match &signature {
+            Type::Function(function) => function.ret.as_ref().clone(),
+            ty => unreachable!("expected function, found `{}`", ty),
+          };
+          let convert = Expr {
+            kind: ExprKind::Method(path.clone()),
+            ty:   signature,
+            span: span.clone(),
+          };
+          Expr {
+            kind: ExprKind::Call(Box::new(convert), vec![local(error, &args[1])]),
+            ty:   converted,
+            span: span.clone(),
+          }
+        }
+        None => local(error, &args[1]),
+      };
+      fields.push(field);
+    }
+    let residual = Expr {
+      kind: ExprKind::Variant(LangItem::RESIDUAL, fields),
+      ty:   self.ret.clone(),
+      span: span.clone(),
+    };
+    let residual_arm = Arm {
+      pattern: pattern(
+        PatternKind::Variant(LangItem::RESIDUAL, patterns),
+        &operand.ty,
+      ),
+      body:    Expr {
+        kind: ExprKind::Block(Block {
+          stmts: vec![Stmt {
+            kind: StmtKind::Return(Some(residual)),
+            span: span.clone(),
+          }],
+        }),
+        ty:   Type::unit(),
+        span: span.clone(),
+      },
+    };
+    ExprKind::Match(Box::new(operand), vec![value_arm, residual_arm])
+  }
+
+  /// `.await` polls the operand by `poll` of the state machine, or of the implementation of
+  /// `Future` for its type.
+  fn lower_await(&mut self, expr: &Expression, operand: &Expression) -> ExprKind {
+    let operand = self.lower_expr(operand);
+    let items = self.results.items();
+    let kind = match self.results.method_callee(expr.id) {
+      Some(MethodCallee::Static(path)) => ExprKind::Method(path.clone()),
+      Some(MethodCallee::Bound { trait_, name }) => ExprKind::BoundMethod {
+        trait_:  trait_.clone(),
+        name:    name.to_owned(),
+        self_ty: operand.ty.clone(),
+      },
+      _ => panic!("`.await` is not resolved"),
+    };
+    let poll = poll_type(items, operand.ty.clone(), self.type_of(expr));
+    let poll = Expr {
+      kind,
+      ty: Type::Function(FunctionType::new(vec![operand.ty.clone()], Box::new(poll))),
+      span: expr.span.clone(),
+    };
+    ExprKind::Await {
+      operand: Box::new(operand),
+      poll:    Box::new(poll),
+    }
+  }
+
+  fn type_of(&self, expr: &Expression) -> Type {
+    match self.results.expr_type(expr.id) {
+      Some(ty) => ty.clone(),
+      None => panic!("type of the expression is unknown"),
+    }
+  }
+}
+
+/// `if !condition { break; }` to exit the loop.
+fn break_unless(condition: Expr, id: LoopId) -> Stmt {
+  let span = condition.span.clone();
+  let exit = Expr {
+    kind: ExprKind::Unary(UnaryOp::Not, Box::new(condition)),
+    ty:   Type::Primitive(PrimitiveType::Bool),
+    span: span.clone(),
+  };
+  let then = Block {
+    stmts: vec![Stmt {
+      kind: StmtKind::Break(id),
+      span: span.clone(),
+    }],
+  };
+  Stmt {
+    kind: StmtKind::If(exit, then, None),
+    span,
+  }
+}
+
+/// Type in the signature, which is always known after the type checking without errors.
+fn known(ty: &Ty) -> Type {
+  match ty.to_type() {
+    Some(ty) => ty,
+    None => panic!("type `{}` is unknown", ty),
+  }
+}
+
+/// Type `Poll<future, output>` of the lang item returned by `poll` of the future.
+fn poll_type(items: &ItemTable, future: Type, output: Type) -> Type {
+  match items.lang.get(LangItem::Poll) {
+    Some(poll) => Type::Named(poll.path(), vec![future, output]),
+    None => panic!("lang item `Poll` is missing"),
+  }
+}
+
+/// Path to the definition from its absolute path, such as the path of the struct type.
+fn def_path(path: &Path) -> DefPath {
+  DefPath::new(
+    path.parent().unwrap_or_default(),
+    path.name().unwrap_or_default(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use vsp_sema::testing::check_source;
+
+  use super::*;
+
+  fn lower(source: &str) -> Program {
+    let (unit, results) = check_source(source);
+    lower_compilation_unit(&unit, &results)
+  }
+
+  fn function<'a>(program: &'a Program, name: &str) -> &'a Body {
+    let owner = MonoItem::Function(DefPath::new(Path::root(), name));
+    program.body(&owner).unwrap()
+  }
+
+  fn returned(body: &Body) -> &Expr {
+    match body.block.stmts.last() {
+      Some(Stmt {
+        kind: StmtKind::Return(Some(value)),
+        ..
+      }) => value,
+      stmt => panic!("expected return, found {:?}", stmt),
+    }
+  }
+
+  #[test]
+  fn test_while() {
+    let program = lower(
+      "func count(n: int64): int64 {
+         var i = 0;
+         while i < n { let next = i + 1; i = next; }
+         return i;
+       }",
+    );
+    let body = function(&program, "count");
+    assert_eq!(body.params().collect::<Vec<_>>(), vec![LocalId(0)]);
+    assert_eq!(body.locals.len(), 3);
+    assert_eq!(body.local(LocalId(1)).ty, Type::int64());
+    assert!(body.local(LocalId(1)).mutable);
+    let stmts = match &body.block.stmts[1].kind {
+      StmtKind::Loop(block) => &block.stmts,
+      stmt => panic!("expected loop, found {:?}", stmt),
+    };
+    match &stmts[0].kind {
+      StmtKind::If(condition, then, None) => {
+        assert!(matches!(
+          &condition.kind,
+          ExprKind::Unary(UnaryOp::Not, operand)
+            if matches!(operand.kind, ExprKind::Binary(BinaryOp::Less, ..))
+        ));
+        assert!(matches!(
+          then.stmts.as_slice(),
+          [Stmt {
+            kind: StmtKind::Break(LoopId(0)),
+            ..
+          }]
+        ));
+      }
+      stmt => panic!("expected if, found {:?}", stmt),
+    }
+    assert!(matches!(stmts[1].kind, StmtKind::Let(LocalId(2), Some(_))));
+    assert!(matches!(
+      &stmts[2].kind,
+      StmtKind::Expr(Expr {
+        kind: ExprKind::Assign(..),
+        ..
+      })
+    ));
+    assert!(matches!(returned(body).kind, ExprKind::Local(LocalId(1))));
+  }
+
+  #[test]
+  fn test_for() {
+    let program = lower(
+      "func sum(end: int32): int32 {
+         var total = 0;
+         'outer: for i in 0..end {
+           loop { if i > end { continue 'outer; } break; }
+           total = total + i;
+         }
+         return if total > 0 { total } else { end };
+       }",
+    );
+    let body = function(&program, "sum");
+    // `next` and `end` of the range, and then `i`.
+    assert_eq!(
+      body.locals.iter().map(|l| l.name.as_str()).collect::<Vec<_>>(),
+      vec!["end", "total", "next", "end", "i"]
+    );
+    let stmts = match &body.block.stmts[1].kind {
+      StmtKind::Block(block) => &block.stmts,
+      stmt => panic!("expected block, found {:?}", stmt),
+    };
+    assert!(matches!(stmts[0].kind, StmtKind::Let(LocalId(2), Some(_))));
+    assert!(matches!(
+      &stmts[1].kind,
+      StmtKind::Let(
+        LocalId(3),
+        Some(Expr {
+          kind: ExprKind::Local(LocalId(0)),
+          ..
+        })
+      )
+    ));
+    let stmts = match &stmts[2].kind {
+      StmtKind::Loop(block) => &block.stmts,
+      stmt => panic!("expected loop, found {:?}", stmt),
+    };
+    assert!(matches!(
+      &stmts[0].kind,
+      StmtKind::If(_, then, None) if matches!(then.stmts[0].kind, StmtKind::Break(LoopId(0)))
+    ));
+    assert!(matches!(
+      &stmts[1].kind,
+      StmtKind::Let(
+        LocalId(4),
+        Some(Expr {
+          kind: ExprKind::Local(LocalId(2)),
+          ..
+        })
+      )
+    ));
+    let inner = match &stmts[3].kind {
+      StmtKind::Block(block) => match &block.stmts[0].kind {
+        StmtKind::Loop(block) => &block.stmts,
+        stmt => panic!("expected loop, found {:?}", stmt),
+      },
+      stmt => panic!("expected block, found {:?}", stmt),
+    };
+    // The user's `end` is the parameter, rather than the hidden end of the range.
+    match &inner[0].kind {
+      StmtKind::If(condition, then, None) => {
+        assert!(matches!(
+          &condition.kind,
+          ExprKind::Binary(BinaryOp::Greater, _, right)
+            if matches!(right.kind, ExprKind::Local(LocalId(0)))
+        ));
+        assert!(matches!(then.stmts[0].kind, StmtKind::Continue(LoopId(0))));
+      }
+      stmt => panic!("expected if, found {:?}", stmt),
+    }
+    assert!(matches!(inner[1].kind, StmtKind::Break(LoopId(1))));
+    assert!(matches!(returned(body).kind, ExprKind::If(..)));
+
+    let program = lower(
+      "@Lang(Iterator) interface Iterator {
+         type Entry;
+         func has_next(): bool;
+         func next(): Self::Entry;
+       }
+       struct Counter { next: int64, end: int64 }
+       impl Iterator for *Counter {
+         type Entry = int64;
+         func has_next(): bool { unsafe { return (*self).next < (*self).end; } }
+         func next(): int64 { unsafe { (*self).next = (*self).next + 1; return (*self).next; } }
+       }
+       func main(): int64 {
+         var counter = Counter { next: 0, end: 3 };
+         for x in &counter {}
+         return 0;
+       }",
+    );
+    let body = function(&program, "main");
+    // The iterator, and then `x`.
+    assert_eq!(
+      body.locals.iter().map(|l| l.name.as_str()).collect::<Vec<_>>(),
+      vec!["counter", "iter", "x"]
+    );
+    let stmts = match &body.block.stmts[1].kind {
+      StmtKind::Block(block) => &block.stmts,
+      stmt => panic!("expected block, found {:?}", stmt),
+    };
+    assert!(matches!(
+      &stmts[0].kind,
+      StmtKind::Let(
+        LocalId(1),
+        Some(Expr {
+          kind: ExprKind::Unary(UnaryOp::AddressOf, _),
+          ..
+        })
+      )
+    ));
+    let stmts = match &stmts[1].kind {
+      StmtKind::Loop(block) => &block.stmts,
+      stmt => panic!("expected loop, found {:?}", stmt),
+    };
+    let is_call = |expr: &Expr, name: &str| match &expr.kind {
+      ExprKind::Call(callee, args) => {
+        matches!(&callee.kind, ExprKind::Method(path) if path.name == name)
+          && matches!(args[0].kind, ExprKind::Local(LocalId(1)))
+      }
+      _ => false,
+    };
+    match &stmts[0].kind {
+      StmtKind::If(
+        Expr {
+          kind: ExprKind::Unary(UnaryOp::Not, condition),
+          ..
+        },
+        then,
+        None,
+      ) => {
+        assert!(is_call(condition, "has_next"));
+        assert!(matches!(then.stmts[0].kind, StmtKind::Break(LoopId(0))));
+      }
+      stmt => panic!("expected if, found {:?}", stmt),
+    }
+    match &stmts[1].kind {
+      StmtKind::Let(LocalId(2), Some(next)) => assert!(is_call(next, "next")),
+      stmt => panic!("expected let, found {:?}", stmt),
+    }
+  }
+
+  #[test]
+  fn test_methods() {
+    let program = lower(
+      "struct Counter { name: str, count: int32 }
+       interface Describe {
+         func describe(): int64;
+       }
+       impl Describe for Counter {
+         func describe(): int64 { return self.count; }
+       }
+       func dynamic(value: dyn Describe): int64 { return value.describe(); }
+       func main(): int64 {
+         let counter = Counter { count: 1 as int32, name: \"counter\" };
+         dynamic(counter);
+         return counter.describe();
+       }",
+    );
+    assert_eq!(program.bodies.len(), 3);
+    let describe = &program.bodies[0];
+    assert!(matches!(&describe.owner, MonoItem::Method(path) if path.name == "describe"));
+    assert_eq!(describe.local(LocalId(0)).name, "self");
+    match &returned(describe).kind {
+      ExprKind::Cast(field, target) => {
+        assert_eq!(target, &Type::int64());
+        assert!(matches!(field.kind, ExprKind::Field(_, 1)));
+      }
+      kind => panic!("expected cast, found {:?}", kind),
+    }
+    assert!(matches!(
+      returned(function(&program, "dynamic")).kind,
+      ExprKind::DynCall { index: 0, .. }
+    ));
+
+    let main = function(&program, "main");
+    match &main.block.stmts[0].kind {
+      StmtKind::Let(
+        _,
+        Some(Expr {
+          kind: ExprKind::Struct(fields),
+          ..
+        }),
+      ) => {
+        let indices = fields.iter().map(|(index, _)| *index).collect::<Vec<_>>();
+        assert_eq!(indices, vec![1, 0]);
+      }
+      stmt => panic!("expected struct literal, found {:?}", stmt),
+    }
+    match &main.block.stmts[1].kind {
+      StmtKind::Expr(Expr {
+        kind: ExprKind::Call(callee, args),
+        ..
+      }) => {
+        assert!(
+          matches!(&callee.kind, ExprKind::Function(def, args) if def.name == "dynamic" && args.is_empty())
+        );
+        assert!(matches!(&args[0].kind, ExprKind::ToDyn(_)));
+        assert_eq!(args[0].ty, Type::Dyn(Path::from("Describe")));
+      }
+      stmt => panic!("expected call, found {:?}", stmt),
+    }
+    match &returned(main).kind {
+      ExprKind::Call(callee, args) => {
+        assert!(matches!(&callee.kind, ExprKind::Method(path) if path.name == "describe"));
+        assert!(matches!(
+          args.as_slice(),
+          [Expr {
+            kind: ExprKind::Local(LocalId(0)),
+            ..
+          }]
+        ));
+      }
+      kind => panic!("expected call, found {:?}", kind),
+    }
+  }
+
+  #[test]
+  fn test_generics() {
+    let program = lower(
+      "interface Next {
+         func next(): Self;
+       }
+       impl Next for int64 {
+         func next(): int64 { return self + 1; }
+       }
+       func advance<T: Next>(value: T): T { return value.next(); }
+       func main(): int64 { return advance(1); }",
+    );
+    match &returned(function(&program, "advance")).kind {
+      ExprKind::Call(callee, _) => match &callee.kind {
+        ExprKind::BoundMethod { name, self_ty, .. } => {
+          assert_eq!(name, "next");
+          assert_eq!(self_ty, &Type::named(Path::from("T")));
+        }
+        kind => panic!("expected bound method, found {:?}", kind),
+      },
+      kind => panic!("expected call, found {:?}", kind),
+    }
+    match &returned(function(&program, "main")).kind {
+      ExprKind::Call(callee, _) => {
+        assert!(matches!(&callee.kind, ExprKind::Function(_, args) if args == &[Type::int64()]));
+      }
+      kind => panic!("expected call, found {:?}", kind),
+    }
+  }
+
+  #[test]
+  fn test_index() {
+    let program = lower(
+      "func main(i: int32): int64 {
+         let values = [1, 2, 3];
+         return values[i];
+       }",
+    );
+    let main = function(&program, "main");
+    assert_eq!(main.local(LocalId(1)).ty, Type::array(Type::int64(), 3));
+    match &returned(main).kind {
+      ExprKind::Index(base, index) => {
+        assert!(matches!(base.kind, ExprKind::Local(LocalId(1))));
+        assert!(matches!(&index.kind, ExprKind::Cast(_, target) if target == &Type::int64()));
+      }
+      kind => panic!("expected index, found {:?}", kind),
+    }
+  }
+
+  #[test]
+  fn test_consts() {
+    let program = lower(
+      "const func double(x: int64): int64 { return x * 2; }
+       const SIZE: int64 = double(2);
+       static NAME: str = \"vsp\";
+       func main(values: int64[SIZE]): int64 { return SIZE; }",
+    );
+    let owners = program.bodies.iter().map(|body| &body.owner).collect::<Vec<_>>();
+    assert!(matches!(
+      owners.as_slice(),
+      [
+        MonoItem::Function(_),
+        MonoItem::Const(size),
+        MonoItem::Static(name),
+        MonoItem::Function(_),
+      ] if size.name == "SIZE" && name.name == "NAME"
+    ));
+    assert!(function(&program, "double").constancy.is_constant());
+    assert!(!function(&program, "main").constancy.is_constant());
+    let size = &program.bodies[1];
+    assert_eq!(size.params, 0);
+    assert_eq!(size.ret, Type::int64());
+    assert!(matches!(returned(size).kind, ExprKind::Call(..)));
+    let main = function(&program, "main");
+    assert_eq!(
+      main.local(LocalId(0)).ty,
+      Type::ConstArray(Box::new(Type::int64()), Path::from("SIZE"))
+    );
+    assert!(matches!(&returned(main).kind, ExprKind::Const(def) if def.name == "SIZE"));
+  }
+
+  #[test]
+  fn test_async() {
+    let program = lower(
+      "@Lang(Poll) enum Poll<F, T> { Ready(T), Pending(F) }
+       @Lang(Future) interface Future {
+         type Output;
+         func poll(): Poll<Self, Self::Output>;
+       }
+       @Lang(BlockOn) func block_on<F: Future>(future: F): F::Output;
+       async func fetch(id: int64): int64 { return id; }
+       async func twice(id: int64): int64 { return fetch(id).await * 2; }
+       func main(): int64 { return block_on(twice(1)); }",
+    );
+    let block_on = function(&program, "block_on");
+    assert_eq!(block_on.params, 1);
+    assert_eq!(block_on.ret, Type::named(Path::from("F::Output")));
+    assert!(block_on.coroutine.is_none());
+    match &returned(block_on).kind {
+      ExprKind::Await { operand, poll } => {
+        assert!(matches!(operand.kind, ExprKind::Local(LocalId(0))));
+        assert!(matches!(
+          &poll.kind,
+          ExprKind::BoundMethod { name, self_ty, .. }
+            if name == "poll" && self_ty == &Type::named(Path::from("F"))
+        ));
+      }
+      kind => panic!("expected await, found {:?}", kind),
+    }
+
+    let twice = function(&program, "twice");
+    assert_eq!(twice.ret, Type::int64());
+    let coroutine = twice.coroutine.as_ref().unwrap();
+    assert_eq!(coroutine.ty.to_string(), "{async twice}");
+    assert_eq!(coroutine.poll.to_string(), "Poll<{async twice}, int64>");
+    match &returned(twice).kind {
+      ExprKind::Binary(BinaryOp::Multiply, left, _) => match &left.kind {
+        ExprKind::Await { operand, poll } => {
+          assert_eq!(operand.ty.to_string(), "{async fetch}");
+          assert_eq!(
+            poll.ty.to_string(),
+            "func({async fetch}) -> Poll<{async fetch}, int64>"
+          );
+        }
+        kind => panic!("expected await, found {:?}", kind),
+      },
+      kind => panic!("expected multiplication, found {:?}", kind),
+    }
+  }
+}
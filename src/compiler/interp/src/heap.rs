@@ -0,0 +1,181 @@
+//! Heap of the interpreter, which holds the local variables, the static items and whatever the raw
+//! pointers point to.
+use crate::value::Address;
+use crate::value::Value;
+
+/// Allocated values in blocks, whose slots are reused once freed. Accesses through the addresses
+/// of the freed blocks, and beyond the bounds of the blocks, are errors instead of undefined
+/// behaviors.
+#[derive(Debug, Default)]
+pub struct Heap {
+  blocks: Vec<Block>,
+  /// Slots of the freed blocks to be reused.
+  free:   Vec<usize>,
+}
+
+#[derive(Debug, Default)]
+struct Block {
+  values:     Vec<Value>,
+  generation: usize,
+  live:       bool,
+}
+
+impl Heap {
+  /// Allocate the block holding the values, and return the address of the first one.
+  pub fn alloc(&mut self, values: Vec<Value>) -> Address {
+    let block = match self.free.pop() {
+      Some(block) => block,
+      None => {
+        self.blocks.push(Block::default());
+        self.blocks.len() - 1
+      }
+    };
+    let slot = &mut self.blocks[block];
+    slot.values = values;
+    slot.live = true;
+    Address {
+      block,
+      generation: slot.generation,
+      index: 0,
+      path: vec![],
+      element: None,
+    }
+  }
+
+  /// Free the block of the address, which is no longer accessible.
+  pub fn free(&mut self, address: &Address) {
+    let slot = &mut self.blocks[address.block];
+    if slot.live && slot.generation == address.generation {
+      slot.values = vec![];
+      slot.live = false;
+      slot.generation += 1;
+      self.free.push(address.block);
+    }
+  }
+
+  /// Number of the live blocks.
+  pub fn live_blocks(&self) -> usize {
+    self.blocks.iter().filter(|block| block.live).count()
+  }
+
+  pub fn read(&self, address: &Address) -> Result<Value, String> {
+    let slot = self.block(address)?;
+    let mut value = usize::try_from(address.index)
+      .ok()
+      .and_then(|index| slot.values.get(index))
+      .ok_or_else(|| out_of_bounds(address, slot.values.len()))?;
+    for index in &address.path {
+      value = value.field(*index).ok_or("read from an invalid field")?;
+    }
+    if let Some(element) = address.element {
+      value = match value {
+        Value::Array(elements) => usize::try_from(element)
+          .ok()
+          .and_then(|index| elements.get(index))
+          .ok_or_else(|| element_out_of_bounds(element, elements.len()))?,
+        _ => return Err("read from an invalid element".to_owned()),
+      };
+    }
+    Ok(value.clone())
+  }
+
+  pub fn write(&mut self, address: &Address, value: Value) -> Result<(), String> {
+    self.block(address)?;
+    let slot = &mut self.blocks[address.block];
+    let length = slot.values.len();
+    let mut target = usize::try_from(address.index)
+      .ok()
+      .and_then(|index| slot.values.get_mut(index))
+      .ok_or_else(|| out_of_bounds(address, length))?;
+    for index in &address.path {
+      target = target.field_mut(*index).ok_or("write to an invalid field")?;
+    }
+    if let Some(element) = address.element {
+      target = match target {
+        Value::Array(elements) => {
+          let length = elements.len();
+          usize::try_from(element)
+            .ok()
+            .and_then(|index| elements.get_mut(index))
+            .ok_or_else(|| element_out_of_bounds(element, length))?
+        }
+        _ => return Err("write to an invalid element".to_owned()),
+      };
+    }
+    *target = value;
+    Ok(())
+  }
+
+  fn block(&self, address: &Address) -> Result<&Block, String> {
+    match self.blocks.get(address.block) {
+      Some(slot) if slot.live && slot.generation == address.generation => Ok(slot),
+      _ => Err("use of a dangling pointer".to_owned()),
+    }
+  }
+}
+
+fn out_of_bounds(address: &Address, length: usize) -> String {
+  format!(
+    "pointer offset {} is out of bounds of the allocation of {} value(s)",
+    address.index, length
+  )
+}
+
+fn element_out_of_bounds(element: i64, length: usize) -> String {
+  format!(
+    "pointer offset {} is out of bounds of the array of {} element(s)",
+    element, length
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_heap() {
+    let mut heap = Heap::default();
+    let point = heap.alloc(vec![Value::Struct(vec![Value::Int(1), Value::Int(2)])]);
+    let y = point.field(1);
+    heap.write(&y, Value::Int(5)).unwrap();
+    assert_eq!(heap.read(&y).unwrap(), Value::Int(5));
+    assert_eq!(
+      heap.read(&point).unwrap(),
+      Value::Struct(vec![Value::Int(1), Value::Int(5)])
+    );
+    assert_eq!(y.offset(1), None);
+
+    let array = heap.alloc(vec![Value::Int(7), Value::Int(8)]);
+    let second = array.offset(1).unwrap();
+    assert_eq!(heap.read(&second).unwrap(), Value::Int(8));
+    assert_eq!(
+      heap.read(&array.offset(2).unwrap()).unwrap_err(),
+      "pointer offset 2 is out of bounds of the allocation of 2 value(s)"
+    );
+    assert_eq!(heap.live_blocks(), 2);
+
+    heap.free(&point);
+    assert_eq!(heap.read(&y).unwrap_err(), "use of a dangling pointer");
+    // The slot is reused by a new generation, which the old addresses do not reach.
+    let reused = heap.alloc(vec![Value::Bool(true)]);
+    assert_eq!(reused.block, point.block);
+    assert_eq!(heap.read(&point).unwrap_err(), "use of a dangling pointer");
+    assert_eq!(heap.read(&reused).unwrap(), Value::Bool(true));
+    assert_eq!(heap.live_blocks(), 2);
+
+    // The pointers to the elements of an array are offset between its elements.
+    let values = heap.alloc(vec![Value::Array(vec![Value::Int(1), Value::Int(2)])]);
+    let last = values.elements().offset(1).unwrap();
+    heap.write(&last, Value::Int(3)).unwrap();
+    assert_eq!(heap.read(&last).unwrap(), Value::Int(3));
+    assert_eq!(last.distance(&values.elements()), Some(1));
+    assert_eq!(
+      heap.read(&last.field(0)).unwrap_err(),
+      "read from an invalid field"
+    );
+    assert_eq!(
+      heap.read(&last.offset(1).unwrap()).unwrap_err(),
+      "pointer offset 2 is out of bounds of the array of 2 element(s)"
+    );
+  }
+}
@@ -0,0 +1,1323 @@
+//! Interpreter of the HIR.
+//!
+//! Bodies are interpreted by walking their statements and expressions. Each call pushes a frame
+//! whose local variables are allocated in the heap, so that the raw pointers to them work as they
+//! do when compiled, until the frame is popped and they are freed. Generic functions are
+//! interpreted with the substitution of their type arguments, by which the methods of the trait
+//! bounds are resolved, and the methods of trait objects are dispatched by the type of the value
+//! behind them.
+//!
+//! Calling `async func` returns its state machine holding the arguments, and polling it runs the
+//! body to the completion, since the awaited futures are polled until they are ready within the
+//! body.
+//!
+//! Errors at runtime, such as the overflows and the uses of the dangling pointers, stop the
+//! interpretation with the location in the source. Without the overflow checks, the arithmetic
+//! wraps around as the optimized builds do.
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::Write;
+
+use vsp_ast::ast::expr::BinaryOp;
+use vsp_ast::ast::expr::UnaryOp;
+use vsp_ast::ast::module::Path;
+use vsp_ast::ast::types::PrimitiveType;
+use vsp_ast::ast::types::Type;
+use vsp_error::VspError;
+use vsp_error::VspResult;
+use vsp_hir::hir::Block;
+use vsp_hir::hir::Body;
+use vsp_hir::hir::Expr;
+use vsp_hir::hir::ExprKind;
+use vsp_hir::hir::ForeignFunction;
+use vsp_hir::hir::Literal;
+use vsp_hir::hir::LocalId;
+use vsp_hir::hir::LoopId;
+use vsp_hir::hir::Pattern;
+use vsp_hir::hir::PatternKind;
+use vsp_hir::hir::Program;
+use vsp_hir::hir::Stmt;
+use vsp_hir::hir::StmtKind;
+use vsp_sema::items::ItemTable;
+use vsp_sema::items::MethodPath;
+use vsp_sema::lang::LangItem;
+use vsp_sema::mono::substitute;
+use vsp_sema::mono::Instance;
+use vsp_sema::mono::MonoItem;
+use vsp_sema::resolve::DefPath;
+use vsp_span::Span;
+
+use crate::heap::Heap;
+use crate::native;
+use crate::native::Native;
+use crate::native::Trap;
+use crate::ops;
+use crate::value::Address;
+use crate::value::Callee;
+use crate::value::Value;
+
+/// Size of the stack for the thread running the interpreter, which the limit of the nested calls
+/// fits in.
+pub const STACK_SIZE: usize = 256 << 20;
+
+/// Name of the entry of the programs.
+pub const MAIN: &str = "main";
+
+/// Limit of the nested calls, since each call is interpreted recursively on the stack of the host.
+pub const CALL_DEPTH_LIMIT: usize = 2048;
+
+/// Interpreter of the program, which keeps the heap and the values of the constants and the static
+/// items between the calls.
+pub struct Interpreter<'a> {
+  program:         &'a Program,
+  items:           &'a ItemTable,
+  bodies:          HashMap<&'a MonoItem, &'a Body>,
+  heap:            Heap,
+  consts:          HashMap<DefPath, Value>,
+  statics:         HashMap<DefPath, Address>,
+  natives:         HashMap<String, Native>,
+  output:          Box<dyn Write + 'a>,
+  /// Whether the overflow of the arithmetic is an error rather than wrapping around.
+  overflow_checks: bool,
+  depth:           usize,
+  /// Local variables of the outermost call whose values are kept when it returns.
+  keep:            Vec<LocalId>,
+  kept:            Vec<Value>,
+}
+
+/// Call being interpreted.
+struct Frame<'a> {
+  body:         &'a Body,
+  /// Address of each local variable.
+  locals:       Vec<Address>,
+  /// Values whose addresses are taken without being in places, which live until the return.
+  temporaries:  Vec<Address>,
+  /// Type arguments of the generic parameters of the body.
+  substitution: HashMap<String, Type>,
+  /// Number of the loops entered, whose innermost one is the target of `break` and `continue`.
+  loops:        usize,
+}
+
+/// Control flow after a statement.
+enum Flow {
+  Next,
+  Break(LoopId),
+  Continue(LoopId),
+}
+
+/// Control flow leaving the call.
+enum Unwind {
+  /// Return from the call with the value, by `return` or by `?`.
+  Return(Value),
+  /// Exit of the program with the code.
+  Exit(i32),
+  /// `break` or `continue` out of the block of the match arm, which goes on as the flow of the
+  /// statement around the match, so that it never leaves the call.
+  Jump(Flow),
+  Error(VspError),
+}
+
+type Result<T> = std::result::Result<T, Unwind>;
+
+impl<'a> Interpreter<'a> {
+  /// Interpreter of the program, whose output is written to the standard output.
+  pub fn new(program: &'a Program, items: &'a ItemTable) -> Self {
+    Self {
+      program,
+      items,
+      bodies: program.bodies.iter().map(|body| (&body.owner, body)).collect(),
+      heap: Heap::default(),
+      consts: HashMap::new(),
+      statics: HashMap::new(),
+      natives: native::defaults(),
+      output: Box::new(std::io::stdout()),
+      overflow_checks: true,
+      depth: 0,
+      keep: vec![],
+      kept: vec![],
+    }
+  }
+
+  pub fn set_output(&mut self, output: Box<dyn Write + 'a>) {
+    self.output = output;
+  }
+
+  /// Check the overflow of the arithmetic, which is on by default, as `-O0` does.
+  pub fn set_overflow_checks(&mut self, overflow_checks: bool) {
+    self.overflow_checks = overflow_checks;
+  }
+
+  /// Bind the foreign functions of the symbol to the native, replacing the default one if any.
+  pub fn define_native(&mut self, symbol: impl Into<String>, native: Native) {
+    self.natives.insert(symbol.into(), native);
+  }
+
+  pub fn heap(&self) -> &Heap {
+    &self.heap
+  }
+
+  /// Replace the heap, so that the pointers into the heap of another interpreter stay valid.
+  pub fn set_heap(&mut self, heap: Heap) {
+    self.heap = heap;
+  }
+
+  pub fn take_heap(&mut self) -> Heap {
+    std::mem::take(&mut self.heap)
+  }
+
+  /// Run `main` of the program with the arguments of the command line which start with the name
+  /// of the program, and return its exit code. `main` either takes no parameters, or `argc: int32`
+  /// and `argv: *str`, and returns nothing or an integer, as the JIT requires.
+  pub fn run_main(&mut self, args: &[String]) -> VspResult<i32> {
+    let owner = MonoItem::Function(DefPath::new(Path::root(), MAIN));
+    let body = match self.bodies.get(&owner) {
+      Some(body) => *body,
+      None => return VspError::new("`main` function not found").to_err(),
+    };
+    let returns_code = match &body.ret {
+      Type::Primitive(PrimitiveType::Unit) => false,
+      Type::Primitive(primitive) if primitive.integer_range().is_some() => true,
+      Type::Primitive(PrimitiveType::Bool) => true,
+      _ => return VspError::new("`main` must return nothing or an integer").to_err(),
+    };
+    let params = body.params().map(|param| &body.local(param).ty).collect::<Vec<_>>();
+    let argv = Type::Pointer(Box::new(Type::str()));
+    let (args, argv) = match params.as_slice() {
+      [] => (vec![], None),
+      [argc, pointer] if **argc == Type::int32() && **pointer == argv => {
+        let mut values = args.iter().map(|arg| Value::Str(arg.to_owned())).collect::<Vec<_>>();
+        // `argv[argc]` is the null pointer as in C.
+        values.push(Value::null());
+        let address = self.heap.alloc(values);
+        let args = vec![
+          Value::Int(args.len() as i128),
+          Value::Pointer(Some(address.clone())),
+        ];
+        (args, Some(address))
+      }
+      _ => {
+        return VspError::new("`main` must take no parameters, or `argc: int32` and `argv: *str`")
+          .to_err()
+      }
+    };
+
+    let result = self.execute(&owner, args);
+    if let Some(argv) = argv {
+      self.heap.free(&argv);
+    }
+    self.output.flush().map_err(VspError::from)?;
+    match result {
+      Ok(Value::Int(code)) if returns_code => Ok(code as i32),
+      Ok(Value::Bool(code)) if returns_code => Ok(code as i32),
+      Ok(_) => Ok(0),
+      Err(Unwind::Exit(code)) => Ok(code),
+      Err(Unwind::Error(error)) => Err(error),
+      Err(Unwind::Return(_) | Unwind::Jump(_)) => unreachable!("return is caught by the call"),
+    }
+  }
+
+  /// Call the function, the method or the initializer of the item with the arguments, and return
+  /// its value. Calling `async func` returns its state machine.
+  pub fn call(&mut self, item: &MonoItem, args: Vec<Value>) -> VspResult<Value> {
+    let result = self.execute(item, args);
+    self.output.flush().map_err(VspError::from)?;
+    match result {
+      Ok(value) => Ok(value),
+      Err(Unwind::Exit(code)) => VspError::new(format!("exited with code {}", code)).to_err(),
+      Err(Unwind::Error(error)) => Err(error),
+      Err(Unwind::Return(_) | Unwind::Jump(_)) => unreachable!("return is caught by the call"),
+    }
+  }
+
+  /// Call the function as [`Self::call`] does, and also return the values of the local variables
+  /// of the function when it returns, such as the bindings which the REPL keeps between its lines.
+  pub fn call_keeping(
+    &mut self,
+    item: &MonoItem,
+    args: Vec<Value>,
+    locals: &[LocalId],
+  ) -> VspResult<(Value, Vec<Value>)> {
+    self.keep = locals.to_vec();
+    let value = self.call(item, args);
+    self.keep.clear();
+    let kept = std::mem::take(&mut self.kept);
+    Ok((value?, kept))
+  }
+
+  fn execute(&mut self, item: &MonoItem, args: Vec<Value>) -> Result<Value> {
+    let callee = match item {
+      MonoItem::Function(def) => Callee::Function(def.clone(), vec![]),
+      MonoItem::Method(path) => Callee::Method(path.clone()),
+      MonoItem::Coroutine(def) => Callee::Poll(def.clone(), vec![]),
+      MonoItem::Const(def) => return self.constant(def),
+      MonoItem::Static(def) => {
+        let address = self.static_address(def)?;
+        return self
+          .heap
+          .read(&address)
+          .map_err(|message| Unwind::Error(VspError::new(message)));
+      }
+    };
+    self.call_value(callee, args, None)
+  }
+
+  /// Text of the value of the type, with the names of the fields and the variants, as the REPL
+  /// prints it.
+  pub fn describe(&self, value: &Value, ty: &Type) -> String {
+    match (value, ty) {
+      (Value::Unit, _) => "()".to_string(),
+      (Value::Str(value), _) => format!("{:?}", value),
+      (Value::Struct(fields), Type::Named(path, args)) => {
+        let def = def_path(path);
+        let (info, definition) = match (
+          self.items.structs.get(&def),
+          self.program.structs.iter().find(|definition| definition.def == def),
+        ) {
+          (Some(info), Some(definition)) => (info, definition),
+          _ => return format!("{:?}", value),
+        };
+        let substitution = generic_substitution(&definition.generics, args);
+        let fields = fields
+          .iter()
+          .zip(&info.fields)
+          .zip(&definition.fields)
+          .map(|((value, field), ty)| {
+            let ty = substitute(ty, &substitution);
+            format!("{}: {}", field.name, self.describe(value, &ty))
+          })
+          .collect::<Vec<_>>();
+        format!("{} {{ {} }}", def.name, fields.join(", "))
+      }
+      (Value::Variant(index, fields), Type::Named(path, args)) => {
+        let def = def_path(path);
+        let (info, definition) = match (
+          self.items.enums.get(&def),
+          self.program.enums.iter().find(|definition| definition.def == def),
+        ) {
+          (Some(info), Some(definition)) => (info, definition),
+          _ => return format!("{:?}", value),
+        };
+        let name = &info.variants[*index].name;
+        if fields.is_empty() {
+          return name.to_owned();
+        }
+        let substitution = generic_substitution(&definition.generics, args);
+        let fields = fields
+          .iter()
+          .zip(&definition.variants[*index])
+          .map(|(value, ty)| self.describe(value, &substitute(ty, &substitution)))
+          .collect::<Vec<_>>();
+        format!("{}({})", name, fields.join(", "))
+      }
+      (Value::Function(_), _) => format!("<{}>", ty),
+      (Value::Coroutine { def, .. }, _) => format!("<async {}>", def),
+      (Value::Dyn(value, ty), _) => self.describe(value, ty),
+      (value, _) => native::display(value),
+    }
+  }
+
+  /// Value of the constant, which is evaluated once when first used.
+  fn constant(&mut self, def: &DefPath) -> Result<Value> {
+    if let Some(value) = self.consts.get(def) {
+      return Ok(value.clone());
+    }
+    let body = self.item_body(&MonoItem::Const(def.clone()))?;
+    let value = self.run(body, HashMap::new(), vec![])?;
+    self.consts.insert(def.clone(), value.clone());
+    Ok(value)
+  }
+
+  /// Value of the local variable of the type before it is initialized. Arrays hold their elements,
+  /// so that they are written through the pointers to the elements.
+  fn uninit(&mut self, ty: &Type) -> Result<Value> {
+    let element = match ty {
+      Type::Array(element, _) | Type::ConstArray(element, _) => element,
+      _ => return Ok(Value::Unit),
+    };
+    let size = self.length(ty)?;
+    let element = self.uninit(element)?;
+    Ok(Value::Array(vec![element; size]))
+  }
+
+  /// Length of the array type, whose size might be given by the constant.
+  fn length(&mut self, ty: &Type) -> Result<usize> {
+    match ty {
+      Type::Array(_, size) => Ok(*size),
+      Type::ConstArray(_, size) => match self.constant(&def_path(size))? {
+        Value::Int(size) => Ok(size as usize),
+        value => unreachable!("size of the array is not an integer: {:?}", value),
+      },
+      ty => unreachable!("length of non-array type `{}`", ty),
+    }
+  }
+
+  /// Evaluate the index into the array of the length, which must be within the length.
+  fn index(
+    &mut self,
+    frame: &mut Frame<'a>,
+    index: &'a Expr,
+    length: usize,
+    span: &Span,
+  ) -> Result<usize> {
+    match self.eval(frame, index)? {
+      Value::Int(index) if 0 <= index && index < length as i128 => Ok(index as usize),
+      Value::Int(index) => {
+        let message = format!(
+          "index out of bounds: the length is {} but the index is {}",
+          length, index
+        );
+        Err(error(frame, span, message))
+      }
+      value => unreachable!("index of non-integer value {:?}", value),
+    }
+  }
+
+  /// Address of the static item, which is initialized when first used.
+  fn static_address(&mut self, def: &DefPath) -> Result<Address> {
+    if let Some(address) = self.statics.get(def) {
+      return Ok(address.clone());
+    }
+    let body = self.item_body(&MonoItem::Static(def.clone()))?;
+    let value = self.run(body, HashMap::new(), vec![])?;
+    let address = self.heap.alloc(vec![value]);
+    self.statics.insert(def.clone(), address.clone());
+    Ok(address)
+  }
+
+  fn item_body(&self, item: &MonoItem) -> Result<&'a Body> {
+    match self.bodies.get(item) {
+      Some(body) => Ok(*body),
+      None => Err(Unwind::Error(VspError::new(format!(
+        "cannot call {} without a body",
+        describe(item)
+      )))),
+    }
+  }
+
+  /// Call the function value with the arguments. Errors are located at the span of the call in the
+  /// frame, if any.
+  fn call_value(
+    &mut self,
+    callee: Callee,
+    args: Vec<Value>,
+    location: Option<(&Frame, &Span)>,
+  ) -> Result<Value> {
+    if self.depth >= CALL_DEPTH_LIMIT {
+      let message = format!("reached the limit of {} nested calls", CALL_DEPTH_LIMIT);
+      return Err(error_at(location, message));
+    }
+    match callee {
+      Callee::Function(def, targs) => {
+        if let Some(function) = self.program.foreign_function(&def) {
+          return self.call_native(function, args, location);
+        }
+        let body = self.item_body(&MonoItem::Function(def.clone()))?;
+        if body.coroutine.is_some() {
+          return Ok(Value::Coroutine { def, targs, args });
+        }
+        let instance = Instance {
+          item: MonoItem::Function(def),
+          args: targs,
+        };
+        self.run(body, instance.substitution(self.items), args)
+      }
+      Callee::Method(path) => {
+        let body = self.item_body(&MonoItem::Method(path))?;
+        self.run(body, HashMap::new(), args)
+      }
+      Callee::Poll(_, _) => {
+        let (def, targs, args) = match args.into_iter().next() {
+          Some(Value::Coroutine { def, targs, args }) => (def, targs, args),
+          value => unreachable!("polling non-coroutine value {:?}", value),
+        };
+        let instance = Instance {
+          item: MonoItem::Function(def),
+          args: targs,
+        };
+        let body = self.item_body(&instance.item)?;
+        let value = self.run(body, instance.substitution(self.items), args)?;
+        Ok(Value::Variant(LangItem::READY, vec![value]))
+      }
+    }
+  }
+
+  fn call_native(
+    &mut self,
+    function: &ForeignFunction,
+    args: Vec<Value>,
+    location: Option<(&Frame, &Span)>,
+  ) -> Result<Value> {
+    let native = match self.natives.get(function.symbol()) {
+      Some(native) => *native,
+      None => {
+        let message = format!(
+          "foreign function `{}` is not available in the interpreter",
+          function.symbol()
+        );
+        return Err(error_at(location, message));
+      }
+    };
+    match native(&mut *self.output, &args) {
+      Ok(_) if function.ret == Type::unit() => Ok(Value::Unit),
+      Ok(Value::Int(value)) => Ok(Value::Int(ops::primitive(&function.ret).wrap(value))),
+      Ok(value) => Ok(value),
+      Err(Trap::Exit(code)) => Err(Unwind::Exit(code)),
+      Err(Trap::Error(message)) => {
+        let message = format!("in `{}`: {}", function.symbol(), message);
+        Err(error_at(location, message))
+      }
+    }
+  }
+
+  /// Interpret the body with the arguments, returning the returned value.
+  fn run(
+    &mut self,
+    body: &'a Body,
+    substitution: HashMap<String, Type>,
+    args: Vec<Value>,
+  ) -> Result<Value> {
+    let mut locals = Vec::with_capacity(body.locals.len());
+    for local in &body.locals {
+      let value = match substitution.is_empty() {
+        true => self.uninit(&local.ty)?,
+        false => self.uninit(&substitute(&local.ty, &substitution))?,
+      };
+      locals.push(self.heap.alloc(vec![value]));
+    }
+    let mut frame = Frame {
+      body,
+      locals,
+      temporaries: vec![],
+      substitution,
+      loops: 0,
+    };
+    for (param, value) in body.params().zip(args) {
+      self.write(&frame, &frame.locals[param.0], value, &body.span)?;
+    }
+
+    self.depth += 1;
+    let result = self.exec_block(&mut frame, &body.block);
+    self.depth -= 1;
+    if self.depth == 0 && !self.keep.is_empty() {
+      let heap = &self.heap;
+      let value = |local: &LocalId| heap.read(&frame.locals[local.0]).unwrap_or(Value::Unit);
+      self.kept = self.keep.iter().map(value).collect();
+    }
+    for address in frame.locals.iter().chain(&frame.temporaries) {
+      self.heap.free(address);
+    }
+    match result {
+      Ok(_) => Ok(Value::Unit),
+      Err(Unwind::Return(value)) => Ok(value),
+      Err(unwind) => Err(unwind),
+    }
+  }
+
+  fn exec_block(&mut self, frame: &mut Frame<'a>, block: &'a Block) -> Result<Flow> {
+    for stmt in &block.stmts {
+      let flow = match self.exec_stmt(frame, stmt) {
+        Err(Unwind::Jump(flow)) => flow,
+        result => result?,
+      };
+      if !matches!(flow, Flow::Next) {
+        return Ok(flow);
+      }
+    }
+    Ok(Flow::Next)
+  }
+
+  fn exec_stmt(&mut self, frame: &mut Frame<'a>, stmt: &'a Stmt) -> Result<Flow> {
+    Ok(match &stmt.kind {
+      StmtKind::Let(local, init) => {
+        if let Some(init) = init {
+          let value = self.eval(frame, init)?;
+          self.write(frame, &frame.locals[local.0], value, &stmt.span)?;
+        }
+        Flow::Next
+      }
+      StmtKind::Expr(expr) => {
+        self.eval(frame, expr)?;
+        Flow::Next
+      }
+      StmtKind::If(condition, then, otherwise) => {
+        if self.eval(frame, condition)? == Value::Bool(true) {
+          self.exec_block(frame, then)?
+        } else if let Some(otherwise) = otherwise {
+          self.exec_block(frame, otherwise)?
+        } else {
+          Flow::Next
+        }
+      }
+      StmtKind::Loop(body) => {
+        let id = LoopId(frame.loops);
+        frame.loops += 1;
+        let flow = loop {
+          match self.exec_block(frame, body)? {
+            Flow::Break(target) if target == id => break Flow::Next,
+            Flow::Continue(target) if target == id => continue,
+            Flow::Next => continue,
+            flow => break flow,
+          }
+        };
+        frame.loops -= 1;
+        flow
+      }
+      StmtKind::Break(target) => Flow::Break(*target),
+      StmtKind::Continue(target) => Flow::Continue(*target),
+      StmtKind::Return(value) => {
+        let value = match value {
+          Some(value) => self.eval(frame, value)?,
+          None => Value::Unit,
+        };
+        return Err(Unwind::Return(value));
+      }
+      StmtKind::Block(block) => self.exec_block(frame, block)?,
+    })
+  }
+
+  fn eval(&mut self, frame: &mut Frame<'a>, expr: &'a Expr) -> Result<Value> {
+    let span = &expr.span;
+    match &expr.kind {
+      ExprKind::Literal(literal) => Ok(match literal {
+        Literal::Unit => Value::Unit,
+        Literal::Integer(value) => Value::Int(ops::primitive(&expr.ty).wrap(*value as i128)),
+        Literal::Float(value) => Value::Float(*value),
+        Literal::Bool(value) => Value::Bool(*value),
+        Literal::Str(value) => Value::Str(value.to_owned()),
+      }),
+      ExprKind::Local(local) => self.read(frame, &frame.locals[local.0], span),
+      ExprKind::Function(def, targs) => {
+        let targs = targs.iter().map(|arg| substitute(arg, &frame.substitution)).collect();
+        Ok(Value::Function(Callee::Function(def.clone(), targs)))
+      }
+      ExprKind::Method(path) => Ok(Value::Function(Callee::Method(path.clone()))),
+      ExprKind::Const(def) => self.constant(def),
+      ExprKind::Static(def) => {
+        let address = self.static_address(def)?;
+        self.read(frame, &address, span)
+      }
+      ExprKind::BoundMethod {
+        trait_,
+        name,
+        self_ty,
+      } => {
+        let self_ty = substitute(self_ty, &frame.substitution);
+        match self.resolve(trait_, name, &self_ty) {
+          Some(callee) => Ok(Value::Function(callee)),
+          None => {
+            let message = format!("`{}` does not implement `{}`", self_ty, trait_);
+            Err(error(frame, span, message))
+          }
+        }
+      }
+      ExprKind::Unary(UnaryOp::Dereference, _) | ExprKind::Field(..) | ExprKind::Index(..) => {
+        match self.place(frame, expr)? {
+          Some(address) => self.read(frame, &address, span),
+          None => match &expr.kind {
+            ExprKind::Field(base, index) => match self.eval(frame, base)? {
+              Value::Struct(mut fields) => Ok(fields.swap_remove(*index)),
+              value => unreachable!("field of non-struct value {:?}", value),
+            },
+            ExprKind::Index(base, index) => match self.eval(frame, base)? {
+              Value::Array(mut elements) => {
+                let index = self.index(frame, index, elements.len(), span)?;
+                Ok(elements.swap_remove(index))
+              }
+              value => unreachable!("index into non-array value {:?}", value),
+            },
+            _ => unreachable!("dereference is always a place"),
+          },
+        }
+      }
+      ExprKind::Array(elements) => Ok(Value::Array(self.eval_all(frame, elements)?)),
+      ExprKind::Unary(UnaryOp::AddressOf, operand) => match self.place(frame, operand)? {
+        Some(address) => Ok(Value::Pointer(Some(address))),
+        None => {
+          let value = self.eval(frame, operand)?;
+          let address = self.heap.alloc(vec![value]);
+          frame.temporaries.push(address.clone());
+          Ok(Value::Pointer(Some(address)))
+        }
+      },
+      ExprKind::Unary(op, operand) => match (op, self.eval(frame, operand)?) {
+        (UnaryOp::Not, Value::Bool(value)) => Ok(Value::Bool(!value)),
+        (UnaryOp::Negative, Value::Float(value)) => Ok(Value::Float(-value)),
+        (UnaryOp::Negative, Value::Int(value)) => {
+          let ty = frame.ty(&operand.ty);
+          if !self.overflow_checks {
+            return Ok(Value::Int(ops::primitive(&ty).wrap(-value)));
+          }
+          let result = ops::primitive(&ty).check_range(-value, "negate");
+          result.map(Value::Int).map_err(|message| error(frame, span, message))
+        }
+        (op, value) => unreachable!("invalid operand of `{}`: {:?}", op.as_str(), value),
+      },
+      ExprKind::Binary(op @ (BinaryOp::And | BinaryOp::Or), left, right) => {
+        let left = self.eval(frame, left)?;
+        match (op, left) {
+          (BinaryOp::And, Value::Bool(false)) => Ok(Value::Bool(false)),
+          (BinaryOp::Or, Value::Bool(true)) => Ok(Value::Bool(true)),
+          _ => self.eval(frame, right),
+        }
+      }
+      ExprKind::Binary(op, left, right) => {
+        let ty = frame.ty(&left.ty);
+        let left = self.eval(frame, left)?;
+        let right = self.eval(frame, right)?;
+        let result = ops::binary(op, left, right, &ty, self.overflow_checks);
+        result.map_err(|message| error(frame, span, message))
+      }
+      ExprKind::Assign(target, value) => {
+        let address = match self.place(frame, target)? {
+          Some(address) => address,
+          None => unreachable!("assignment to non-place {:?}", target),
+        };
+        let value = self.eval(frame, value)?;
+        self.write(frame, &address, value, span)?;
+        Ok(Value::Unit)
+      }
+      ExprKind::If(condition, then, otherwise) => {
+        if self.eval(frame, condition)? == Value::Bool(true) {
+          self.eval(frame, then)
+        } else {
+          self.eval(frame, otherwise)
+        }
+      }
+      ExprKind::Match(scrutinee, arms) => {
+        let value = self.eval(frame, scrutinee)?;
+        for arm in arms {
+          if self.bind(frame, &arm.pattern, &value)? {
+            return self.eval(frame, &arm.body);
+          }
+        }
+        unreachable!("patterns are checked to be exhaustive")
+      }
+      ExprKind::Block(block) => match self.exec_block(frame, block)? {
+        Flow::Next => Ok(Value::Unit),
+        flow => Err(Unwind::Jump(flow)),
+      },
+      ExprKind::Cast(operand, target) => {
+        let value = self.eval(frame, operand)?;
+        let source = frame.ty(&operand.ty);
+        let target = frame.ty(target);
+        ops::cast(value, &source, &target).map_err(|message| error(frame, span, message))
+      }
+      ExprKind::ToDyn(operand) => {
+        let value = self.eval(frame, operand)?;
+        Ok(Value::Dyn(
+          Box::new(value),
+          frame.ty(&operand.ty).into_owned(),
+        ))
+      }
+      ExprKind::Struct(fields) => {
+        let mut values = vec![Value::Unit; fields.len()];
+        for (index, field) in fields {
+          values[*index] = self.eval(frame, field)?;
+        }
+        Ok(Value::Struct(values))
+      }
+      ExprKind::Variant(index, fields) => {
+        let fields = self.eval_all(frame, fields)?;
+        Ok(Value::Variant(*index, fields))
+      }
+      ExprKind::Discriminant(operand, discriminants) => match self.eval(frame, operand)? {
+        Value::Variant(index, _) => match discriminants.get(index) {
+          Some(discriminant) => self.constant(discriminant),
+          None => Ok(Value::Int(index as i128)),
+        },
+        value => unreachable!("discriminant of non-enum value: {:?}", value),
+      },
+      ExprKind::Await { operand, poll } => {
+        let mut future = self.eval(frame, operand)?;
+        let poll = self.eval_callee(frame, poll)?;
+        loop {
+          match self.call_value(poll.clone(), vec![future], Some((&*frame, span)))? {
+            Value::Variant(LangItem::READY, mut fields) => return Ok(fields.swap_remove(0)),
+            Value::Variant(_, mut fields) => future = fields.swap_remove(0),
+            value => unreachable!("`poll` returned non-enum value {:?}", value),
+          }
+        }
+      }
+      ExprKind::Call(callee, args) => {
+        let callee = self.eval_callee(frame, callee)?;
+        let args = self.eval_all(frame, args)?;
+        self.call_value(callee, args, Some((&*frame, span)))
+      }
+      ExprKind::DynCall {
+        object,
+        trait_,
+        index,
+        args,
+      } => {
+        let (object, self_ty) = match self.eval(frame, object)? {
+          Value::Dyn(object, self_ty) => (*object, self_ty),
+          value => unreachable!("calling methods of non-object value {:?}", value),
+        };
+        let items = self.items;
+        let name = &items.traits[trait_].methods[*index].name;
+        let callee = match self.resolve(trait_, name, &self_ty) {
+          Some(callee) => callee,
+          None => {
+            let message = format!("`{}` does not implement `{}`", self_ty, trait_);
+            return Err(error(frame, span, message));
+          }
+        };
+        let mut values = vec![object];
+        values.extend(self.eval_all(frame, args)?);
+        self.call_value(callee, values, Some((&*frame, span)))
+      }
+    }
+  }
+
+  /// Match the value against the pattern, writing the parts bound by the pattern to the locals.
+  fn bind(&mut self, frame: &Frame, pattern: &Pattern, value: &Value) -> Result<bool> {
+    match (&pattern.kind, value) {
+      (PatternKind::Wild, _) => Ok(true),
+      (PatternKind::Binding(local), value) => {
+        self.write(frame, &frame.locals[local.0], value.clone(), &pattern.span)?;
+        Ok(true)
+      }
+      (PatternKind::Literal(Literal::Integer(literal)), Value::Int(value)) => {
+        Ok(ops::primitive(&frame.ty(&pattern.ty)).wrap(*literal as i128) == *value)
+      }
+      (PatternKind::Literal(Literal::Bool(literal)), Value::Bool(value)) => Ok(literal == value),
+      (PatternKind::Variant(index, patterns), Value::Variant(found, fields)) => {
+        if index != found {
+          return Ok(false);
+        }
+        for (pattern, field) in patterns.iter().zip(fields) {
+          if !self.bind(frame, pattern, field)? {
+            return Ok(false);
+          }
+        }
+        Ok(true)
+      }
+      (kind, value) => unreachable!("pattern {:?} against value {:?}", kind, value),
+    }
+  }
+
+  fn eval_all(&mut self, frame: &mut Frame<'a>, exprs: &'a [Expr]) -> Result<Vec<Value>> {
+    exprs.iter().map(|expr| self.eval(frame, expr)).collect()
+  }
+
+  fn eval_callee(&mut self, frame: &mut Frame<'a>, expr: &'a Expr) -> Result<Callee> {
+    match self.eval(frame, expr)? {
+      Value::Function(callee) => Ok(callee),
+      value => unreachable!("calling non-function value {:?}", value),
+    }
+  }
+
+  /// Address of the place which the expression refers to, or `None` if it is not a place. Only
+  /// the pointers dereferenced by the place are evaluated.
+  fn place(&mut self, frame: &mut Frame<'a>, expr: &'a Expr) -> Result<Option<Address>> {
+    match &expr.kind {
+      ExprKind::Local(local) => Ok(Some(frame.locals[local.0].clone())),
+      ExprKind::Static(def) => self.static_address(def).map(Some),
+      ExprKind::Field(base, index) => {
+        Ok(self.place(frame, base)?.map(|address| address.field(*index)))
+      }
+      ExprKind::Index(base, index) => match self.place(frame, base)? {
+        Some(address) => {
+          let length = self.length(&frame.ty(&base.ty))?;
+          let index = self.index(frame, index, length, &expr.span)?;
+          Ok(Some(address.field(index)))
+        }
+        None => Ok(None),
+      },
+      ExprKind::Unary(UnaryOp::Dereference, pointer) => match self.eval(frame, pointer)? {
+        Value::Pointer(Some(address)) => Ok(Some(address)),
+        Value::Pointer(None) => Err(error(frame, &expr.span, "dereference of a null pointer")),
+        value => unreachable!("dereference of non-pointer value {:?}", value),
+      },
+      _ => Ok(None),
+    }
+  }
+
+  /// Method of the trait for the type, where the state machines of `async func` implement `Future`
+  /// by their `poll`.
+  fn resolve(&self, trait_: &DefPath, name: &str, self_ty: &Type) -> Option<Callee> {
+    if let Type::Coroutine(path, args) = self_ty {
+      return Some(Callee::Poll(def_path(path), args.clone()));
+    }
+    let implementation = self.items.impls.iter().find(|i| {
+      i.trait_.as_ref() == Some(trait_) && i.self_ty.to_type().as_ref() == Some(self_ty)
+    })?;
+    Some(Callee::Method(MethodPath {
+      impl_id: implementation.id,
+      name:    name.to_owned(),
+    }))
+  }
+
+  fn read(&self, frame: &Frame, address: &Address, span: &Span) -> Result<Value> {
+    self.heap.read(address).map_err(|message| error(frame, span, message))
+  }
+
+  fn write(&mut self, frame: &Frame, address: &Address, value: Value, span: &Span) -> Result<()> {
+    self.heap.write(address, value).map_err(|message| error(frame, span, message))
+  }
+}
+
+impl<'a> Frame<'a> {
+  /// Type in the body with the generic parameters substituted.
+  fn ty<'t>(&self, ty: &'t Type) -> Cow<'t, Type> {
+    if self.substitution.is_empty() {
+      Cow::Borrowed(ty)
+    } else {
+      Cow::Owned(substitute(ty, &self.substitution))
+    }
+  }
+}
+
+/// Error at the span in the body of the frame, located by the file, the line and the column.
+fn error(frame: &Frame, span: &Span, message: impl Into<String>) -> Unwind {
+  let location = match &frame.body.file {
+    Some(file) => format!("{}:{}:{}", file, span.start.line, span.start.column),
+    None => format!("{}:{}", span.start.line, span.start.column),
+  };
+  Unwind::Error(VspError::new(format!("{}: {}", location, message.into())))
+}
+
+fn error_at(location: Option<(&Frame, &Span)>, message: String) -> Unwind {
+  match location {
+    Some((frame, span)) => error(frame, span, message),
+    None => Unwind::Error(VspError::new(message)),
+  }
+}
+
+fn def_path(path: &Path) -> DefPath {
+  DefPath::new(
+    path.parent().unwrap_or_default(),
+    path.name().unwrap_or_default(),
+  )
+}
+
+fn generic_substitution(generics: &[String], args: &[Type]) -> HashMap<String, Type> {
+  generics.iter().cloned().zip(args.iter().cloned()).collect()
+}
+
+fn describe(item: &MonoItem) -> String {
+  match item {
+    MonoItem::Const(def) => format!("constant `{}`", def),
+    MonoItem::Static(def) => format!("static `{}`", def),
+    MonoItem::Function(def) => format!("function `{}`", def),
+    MonoItem::Coroutine(def) => format!("`poll` of `async func` `{}`", def),
+    MonoItem::Method(path) => format!("method `{}`", path.name),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::cell::RefCell;
+  use std::rc::Rc;
+
+  use vsp_sema::testing::check_source;
+
+  use super::*;
+
+  /// Output shared with the test after the interpreter takes it.
+  #[derive(Clone, Default)]
+  struct Output(Rc<RefCell<Vec<u8>>>);
+
+  impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+      self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+      Ok(())
+    }
+  }
+
+  /// Run `main` of the program with the arguments on a thread with the stack for the limit of the
+  /// calls, returning its exit code and its output.
+  fn run(source: &str, args: &[&str]) -> (VspResult<i32>, String) {
+    let source = source.to_owned();
+    let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+    std::thread::Builder::new()
+      .stack_size(STACK_SIZE)
+      .spawn(move || run_on_thread(&source, &args))
+      .unwrap()
+      .join()
+      .unwrap()
+  }
+
+  fn run_on_thread(source: &str, args: &[String]) -> (VspResult<i32>, String) {
+    let (unit, results) = check_source(source);
+    let program = vsp_hir::lower_compilation_unit(&unit, &results);
+
+    let output = Output::default();
+    let mut interpreter = Interpreter::new(&program, results.items());
+    interpreter.set_output(Box::new(output.clone()));
+    let code = interpreter.run_main(args);
+    // Only the static items are left in the heap.
+    if code.is_ok() {
+      assert_eq!(interpreter.heap().live_blocks(), interpreter.statics.len());
+    }
+    let output = String::from_utf8(output.0.borrow().clone()).unwrap();
+    (code, output)
+  }
+
+  /// Call the function of the program with the arguments, and describe the returned value.
+  fn call(source: &str, name: &str, args: Vec<Value>) -> String {
+    let (unit, results) = check_source(source);
+    let program = vsp_hir::lower_compilation_unit(&unit, &results);
+
+    let mut interpreter = Interpreter::new(&program, results.items());
+    let item = MonoItem::Function(DefPath::new(Path::root(), name));
+    let value = interpreter.call(&item, args).unwrap();
+    interpreter.describe(&value, &program.body(&item).unwrap().ret)
+  }
+
+  fn code(source: &str) -> i32 {
+    run(source, &["main"]).0.unwrap()
+  }
+
+  fn message(source: &str) -> String {
+    run(source, &["main"]).0.unwrap_err().message()
+  }
+
+  #[test]
+  fn test_control_flow() {
+    assert_eq!(code("func main() {}"), 0);
+    assert_eq!(code("func main(): bool { return true; }"), 1);
+    assert_eq!(
+      code(
+        "func fibonacci(n: int64): int64 {
+           if n < 2 { return n; }
+           return fibonacci(n - 1) + fibonacci(n - 2);
+         }
+         func main(): int64 {
+           var total = 0;
+           var i = 0;
+           while true {
+             i = i + 1;
+             if i > 10 { break; }
+             if i % 2 == 0 { continue; }
+             total = total + fibonacci(i);
+           }
+           return total;
+         }"
+      ),
+      1 + 2 + 5 + 13 + 34
+    );
+    assert_eq!(
+      code(
+        "const func square(x: int64): int64 { return x * x; }
+         const SIZE: int64 = square(3);
+         static COUNT: int64 = SIZE + 1;
+         func bump(p: *int64): int64 { unsafe { *p = *p + 1; return *p; } }
+         func main(): int64 {
+           var count = COUNT;
+           bump(&count);
+           return (bump(&count) + COUNT) * (300 as uint8) as int64;
+         }"
+      ),
+      22 * 44
+    );
+    // `break` and `continue` in the blocks of the arms go on with the loop around the match.
+    assert_eq!(
+      code(
+        "enum Shape { Circle(int64), Rect(int64, int64), Empty }
+         func area(shape: Shape): int64 {
+           return match shape { Shape::Rect(1, h) => h, Shape::Rect(w, h) => w * h, _ => 0 };
+         }
+         func main(): int64 {
+           var i = 0;
+           var total = area(Shape::Rect(1, 7)) + area(Shape::Rect(2, 3)) + area(Shape::Empty);
+           loop {
+             i = i + 1;
+             match i % 4 {
+               0 => { break; }
+               1 => { continue; }
+               n => { total = total + n * 10; }
+             }
+           }
+           return match total > 0 { true => total, false => -1 };
+         }"
+      ),
+      7 + 6 + 20 + 30
+    );
+  }
+
+  #[test]
+  fn test_values() {
+    assert_eq!(
+      code(
+        "struct Point { x: int64, y: int64 }
+         struct Line { from: Point, to: Point }
+         impl Point {
+           func sum(): int64 { return self.x + self.y; }
+         }
+         func length(line: Line): int64 { return line.to.x - line.from.x; }
+         func main(): int64 {
+           var p = Point { y: 2, x: 1 };
+           p.y = 10;
+           var line = Line { from: p, to: Point { x: 7, y: 0 } };
+           line.to.y = p.sum();
+           return length(line) * 100 + line.to.y;
+         }"
+      ),
+      611
+    );
+    // Enums without fields are cast to integers as their discriminants.
+    assert_eq!(
+      code(
+        "const BASE: int64 = 4;
+         enum Color { Red = BASE, Green, Blue = BASE * 4 }
+         enum Plain { First, Second }
+         func main(): int64 {
+           let color = Color::Green;
+           return (color as int64) * 100 + (Color::Blue as int64) + (Plain::Second as int64);
+         }"
+      ),
+      517
+    );
+  }
+
+  #[test]
+  fn test_pointers() {
+    let source = "func main(argc: int32, argv: *str): int64 {
+        var x: int64 = 1;
+        let p = &x;
+        unsafe { *p = *p + argc as int64; }
+        return x;
+      }";
+    assert_eq!(run(source, &["main", "a", "b"]).0.unwrap(), 4);
+    let source = "extern \"C\" func strlen(s: str): int64;
+      func main(argc: int32, argv: *str): int64 {
+        unsafe { return strlen(*(argv + 1)) + strlen(*(argv + 2)); }
+      }";
+    assert_eq!(run(source, &["main", "four", "xy"]).0.unwrap(), 6);
+    let source = "func dangling(): *int64 { var x: int64 = 1; return &x; }
+      func main(): int64 { let p = dangling(); unsafe { return *p; } }";
+    assert_eq!(
+      run(source, &["main"]).0.unwrap_err().message(),
+      "2:64: use of a dangling pointer"
+    );
+    let source = "func main(argc: int32, argv: *str): int64 {
+        var values: int64[2];
+        let p = &values as *int64;
+        unsafe {
+          *p = argc as int64;
+          *(p + 1) = 2;
+          if p < p + 1 { return *p * *(p + 1) + ((p + 1) - p) * 10; }
+        }
+        return 0;
+      }";
+    assert_eq!(run(source, &["main", "a", "b"]).0.unwrap(), 16);
+    let source = "func main(): int64 {
+        var values: int64[2];
+        let p = &values as *int64;
+        unsafe { return *(p + 2); }
+      }";
+    assert_eq!(
+      message(source),
+      "4:25: pointer offset 2 is out of bounds of the array of 2 element(s)"
+    );
+  }
+
+  #[test]
+  fn test_arrays() {
+    let source = "func squares(): int64[3] { return [0, 1, 4]; }
+      func fill(grid: *int64[2][2], value: int64) {
+        unsafe { (*grid)[1][0] = value; }
+      }
+      func main(argc: int32, argv: *str): int64 {
+        var grid = [[1, 2], [3, 4]];
+        grid[0][argc] = 20;
+        fill(&grid, 30);
+        return grid[0][1] + grid[1][0] + squares()[2] + [5, 6][argc - 1];
+      }";
+    assert_eq!(run(source, &["main"]).0.unwrap(), 59);
+    let source = "func main(argc: int32, argv: *str): int64 {
+        var values = [1, 2, 3];
+        values[argc + 2] = 4;
+        return values[0];
+      }";
+    assert_eq!(
+      message(source),
+      "3:9: index out of bounds: the length is 3 but the index is 3"
+    );
+    assert_eq!(
+      message("func main(argc: int32, argv: *str): int64 { return [1][argc - 2]; }"),
+      "1:52: index out of bounds: the length is 1 but the index is -1"
+    );
+  }
+
+  #[test]
+  fn test_natives() {
+    let source = "extern \"C\" func println(s: str);
+      extern \"C\" func printf(format: str, ...): int32;
+      extern \"C\" func exit(code: int32);
+      func main(): int32 {
+        unsafe {
+          println(\"Hello World!!\");
+          printf(\"%s has %d items\\n\", \"list\", 3);
+          exit(7);
+        }
+        return 0;
+      }";
+    let (code, output) = run(source, &["main"]);
+    assert_eq!(code.unwrap(), 7);
+    assert_eq!(output, "Hello World!!\nlist has 3 items\n");
+    assert_eq!(
+      message("extern \"C\" func rand(): int32; func main(): int32 { unsafe { return rand(); } }"),
+      "1:69: foreign function `rand` is not available in the interpreter"
+    );
+  }
+
+  #[test]
+  fn test_traits() {
+    assert_eq!(
+      code(
+        "struct Counter { name: str, count: int32 }
+         interface Describe {
+           func describe(): int64;
+         }
+         impl Describe for Counter {
+           func describe(): int64 { return self.count; }
+         }
+         impl Describe for int64 {
+           func describe(): int64 { return self * 10; }
+         }
+         func dynamic(value: dyn Describe): int64 { return value.describe(); }
+         func bound<T: Describe>(value: T): int64 { return value.describe(); }
+         func main(): int64 {
+           let counter = Counter { count: 3 as int32, name: \"counter\" };
+           let four: int64 = 4;
+           return dynamic(counter) + dynamic(four) + bound(counter) + bound(5 as int64);
+         }"
+      ),
+      3 + 40 + 3 + 50
+    );
+    // Iterators keep their states behind the pointers, which `for` advances by `next`.
+    assert_eq!(
+      code(
+        "@Lang(Iterator) interface Iterator {
+           type Entry;
+           func has_next(): bool;
+           func next(): Self::Entry;
+         }
+         struct Counter { next: int64, end: int64 }
+         impl Iterator for *Counter {
+           type Entry = int64;
+           func has_next(): bool { unsafe { return (*self).next < (*self).end; } }
+           func next(): int64 {
+             unsafe { let value = (*self).next; (*self).next = value + 1; return value; }
+           }
+         }
+         func count<I: Iterator>(iter: I): int64 {
+           var n = 0;
+           for x in iter { n = n + 1; }
+           return n;
+         }
+         func main(): int64 {
+           var counter = Counter { next: 2, end: 6 };
+           var product = 1;
+           for x in &counter {
+             if x == 3 { continue; }
+             if x > 4 { break; }
+             product = product * x;
+           }
+           var other = Counter { next: 1, end: 5 };
+           return product + count(&other) * 10 + counter.next * 100;
+         }"
+      ),
+      8 + 40 + 600
+    );
+  }
+
+  #[test]
+  fn test_try_and_async() {
+    let source = "@Lang(Optional) enum Optional<T> { Some(T), None }
+      @Lang(Expected) enum Expected<T, E> { Ok(T), Err(E) }
+      struct Pair<T> { first: T, second: T }
+      func half(n: int64): Optional<int64> {
+        if n % 2 == 0 { return Optional::Some(n / 2); }
+        return Optional::None;
+      }
+      func quarter(n: int64): Optional<int64> { return Optional::Some(half(half(n)?)?); }
+      func check(n: int64): Expected<int64, str> {
+        if n < 0 { return Expected::Err(\"negative\"); }
+        return Expected::Ok(n);
+      }
+      func sum(a: int64, b: int64): Expected<Pair<int64>, str> {
+        return Expected::Ok(Pair { first: check(a)?, second: check(b)? });
+      }";
+    let calls = [
+      ("quarter", vec![Value::Int(48)], "Some(12)"),
+      ("quarter", vec![Value::Int(6)], "None"),
+      (
+        "sum",
+        vec![Value::Int(1), Value::Int(2)],
+        "Ok(Pair { first: 1, second: 2 })",
+      ),
+      (
+        "sum",
+        vec![Value::Int(1), Value::Int(-2)],
+        "Err(\"negative\")",
+      ),
+    ];
+    for (name, args, expected) in calls {
+      assert_eq!(call(source, name, args), expected);
+    }
+
+    assert_eq!(
+      code(
+        "@Lang(Poll) enum Poll<F, T> { Ready(T), Pending(F) }
+         @Lang(Future) interface Future {
+           type Output;
+           func poll(): Poll<Self, Self::Output>;
+         }
+         @Lang(BlockOn) func block_on<F: Future>(future: F): F::Output;
+         struct Countdown { left: int64 }
+         impl Future for Countdown {
+           type Output = int64;
+           func poll(): Poll<Countdown, int64> {
+             if self.left == 0 { return Poll::Ready(40); }
+             return Poll::Pending(Countdown { left: self.left - 1 });
+           }
+         }
+         async func double(x: int64): int64 { return x * 2; }
+         async func wait<T: Future>(future: T): T::Output { return future.await; }
+         async func add(a: int64): int64 {
+           var total = a + Countdown { left: 3 }.await;
+           total = total + wait(Countdown { left: 2 }).await;
+           return total + double(wait(double(1)).await).await;
+         }
+         func main(): int64 { return block_on(add(1)); }"
+      ),
+      85
+    );
+  }
+
+  #[test]
+  fn test_errors() {
+    assert_eq!(
+      message("func main(): int8 {\n  let x: int8 = 100;\n  return x + 28;\n}"),
+      "3:10: attempt to add with overflow"
+    );
+    assert_eq!(
+      message("func main(): int64 { return 1 / (1 - 1); }"),
+      "1:29: attempt to divide by zero"
+    );
+    assert_eq!(
+      message(
+        "func forever(n: int64): int64 { return forever(n + 1); } func main() { forever(0); }"
+      ),
+      format!(
+        "1:40: reached the limit of {} nested calls",
+        CALL_DEPTH_LIMIT
+      )
+    );
+    assert_eq!(
+      message("func main(): *int64 { return 0 as *int64; }"),
+      "`main` must return nothing or an integer"
+    );
+    assert_eq!(
+      message("func main(n: int64) {}"),
+      "`main` must take no parameters, or `argc: int32` and `argv: *str`"
+    );
+    assert_eq!(message("func run() {}"), "`main` function not found");
+  }
+}
@@ -0,0 +1,26 @@
+//! # Interpreter
+//!
+//! Tree-walking interpreter of the HIR, which runs the programs without the code generation, and
+//! thus without LLVM. It serves `vsp run` and the REPL in the builds without LLVM.
+//!
+//! ```rust
+//! use vsp_ast_parser::lex::DefaultLexer;
+//! use vsp_ast_parser::parser::ASTParser;
+//! use vsp_ast_parser::parser::TraditionalParser;
+//! use vsp_diag::DiagnosticEngine;
+//! use vsp_interp::interp::Interpreter;
+//!
+//! let tokens = DefaultLexer {}.tokenize("func main(): int32 { return 6 * 7; }").unwrap();
+//! let unit = TraditionalParser {}.parse(tokens).unwrap();
+//! let mut diagnostics = DiagnosticEngine::default();
+//! let results = vsp_sema::check_compilation_unit(&unit, &mut diagnostics);
+//! let program = vsp_hir::lower_compilation_unit(&unit, &results);
+//! assert!(!diagnostics.has_errors());
+//! let mut interpreter = Interpreter::new(&program, results.items());
+//! assert_eq!(interpreter.run_main(&["main".to_string()]).unwrap(), 42);
+//! ```
+pub mod heap;
+pub mod interp;
+pub mod native;
+pub mod ops;
+pub mod value;
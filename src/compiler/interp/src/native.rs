@@ -0,0 +1,247 @@
+//! Native bindings of the foreign functions.
+//!
+//! Foreign functions declared by `extern "C"` are bound by their symbols to the natives written in
+//! Rust, since no C library is loaded into the interpreter. The natives cover the output, such as
+//! `println` and `printf`, and a few functions of the C library which the programs commonly use.
+//! Embedders define more natives by [`crate::interp::Interpreter::define_native`].
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::value::Value;
+
+/// Native function, which writes its output to the output of the interpreter.
+pub type Native = fn(&mut dyn Write, &[Value]) -> Result<Value, Trap>;
+
+/// Abnormal end of the native function.
+#[derive(Debug, PartialEq)]
+pub enum Trap {
+  /// Error at the call of the native function.
+  Error(String),
+  /// Exit of the program with the code, by `exit`.
+  Exit(i32),
+}
+
+impl From<std::io::Error> for Trap {
+  fn from(error: std::io::Error) -> Self {
+    Trap::Error(error.to_string())
+  }
+}
+
+/// Natives bound by default.
+pub fn defaults() -> HashMap<String, Native> {
+  let natives: [(&str, Native); 14] = [
+    ("print", print),
+    ("println", println),
+    ("puts", puts),
+    ("putchar", putchar),
+    ("printf", printf),
+    ("strlen", strlen),
+    ("abs", abs),
+    ("labs", abs),
+    ("llabs", abs),
+    ("atoi", atoi),
+    ("atol", atoi),
+    ("sqrt", sqrt),
+    ("pow", pow),
+    ("exit", exit),
+  ];
+  natives.iter().map(|(name, native)| (name.to_string(), *native)).collect()
+}
+
+/// Text of the primitive value as written by the output functions, where strings are not quoted.
+pub fn display(value: &Value) -> String {
+  match value {
+    Value::Unit => "()".to_string(),
+    Value::Int(value) => value.to_string(),
+    Value::Float(value) => format!("{:?}", value),
+    Value::Bool(value) => value.to_string(),
+    Value::Str(value) => value.to_owned(),
+    Value::Pointer(None) => "null".to_string(),
+    Value::Pointer(Some(address)) => format!("<pointer {}+{}>", address.block, address.index),
+    value => format!("{:?}", value),
+  }
+}
+
+fn int(value: &Value) -> Result<i128, Trap> {
+  match value {
+    Value::Int(value) => Ok(*value),
+    Value::Bool(value) => Ok(*value as i128),
+    value => Err(Trap::Error(format!(
+      "expected an integer, found {}",
+      display(value)
+    ))),
+  }
+}
+
+fn float(value: &Value) -> Result<f64, Trap> {
+  match value {
+    Value::Float(value) => Ok(*value),
+    Value::Int(value) => Ok(*value as f64),
+    value => Err(Trap::Error(format!(
+      "expected a float, found {}",
+      display(value)
+    ))),
+  }
+}
+
+fn string(value: &Value) -> Result<&str, Trap> {
+  match value {
+    Value::Str(value) => Ok(value),
+    Value::Pointer(None) => Err(Trap::Error("null pointer passed as a string".to_string())),
+    value => Err(Trap::Error(format!(
+      "expected a string, found {}",
+      display(value)
+    ))),
+  }
+}
+
+/// Write the arguments one after another.
+fn print(output: &mut dyn Write, args: &[Value]) -> Result<Value, Trap> {
+  for arg in args {
+    write!(output, "{}", display(arg))?;
+  }
+  Ok(Value::Unit)
+}
+
+/// Write the arguments one after another, and a new line.
+fn println(output: &mut dyn Write, args: &[Value]) -> Result<Value, Trap> {
+  print(output, args)?;
+  writeln!(output)?;
+  Ok(Value::Unit)
+}
+
+fn puts(output: &mut dyn Write, args: &[Value]) -> Result<Value, Trap> {
+  writeln!(output, "{}", string(&args[0])?)?;
+  Ok(Value::Int(0))
+}
+
+fn putchar(output: &mut dyn Write, args: &[Value]) -> Result<Value, Trap> {
+  let code = int(&args[0])?;
+  let byte = code as u8;
+  output.write_all(&[byte])?;
+  Ok(Value::Int(byte as i128))
+}
+
+/// `printf` with the conversions `%d`, `%i`, `%u`, `%x`, `%c`, `%s`, `%f` and `%%`, whose length
+/// modifiers are ignored, and the flags and the widths are not supported.
+fn printf(output: &mut dyn Write, args: &[Value]) -> Result<Value, Trap> {
+  let format = string(&args[0])?;
+  let mut args = args[1..].iter();
+  let mut text = String::new();
+  let mut chars = format.chars();
+  while let Some(c) = chars.next() {
+    if c != '%' {
+      text.push(c);
+      continue;
+    }
+    let mut conversion = chars.next();
+    while let Some('l' | 'h' | 'z') = conversion {
+      conversion = chars.next();
+    }
+    let mut arg = || args.next().ok_or_else(|| Trap::Error("too few arguments".to_string()));
+    match conversion {
+      Some('%') => text.push('%'),
+      Some('d' | 'i' | 'u') => text.push_str(&int(arg()?)?.to_string()),
+      Some('x') => text.push_str(&format!("{:x}", int(arg()?)?)),
+      Some('c') => text.push(int(arg()?)? as u8 as char),
+      Some('s') => text.push_str(string(arg()?)?),
+      Some('f') => text.push_str(&format!("{:.6}", float(arg()?)?)),
+      Some(c) => return Err(Trap::Error(format!("unsupported conversion `%{}`", c))),
+      None => return Err(Trap::Error("incomplete conversion at the end".to_string())),
+    }
+  }
+  output.write_all(text.as_bytes())?;
+  Ok(Value::Int(text.len() as i128))
+}
+
+fn strlen(_: &mut dyn Write, args: &[Value]) -> Result<Value, Trap> {
+  Ok(Value::Int(string(&args[0])?.len() as i128))
+}
+
+fn abs(_: &mut dyn Write, args: &[Value]) -> Result<Value, Trap> {
+  Ok(Value::Int(int(&args[0])?.abs()))
+}
+
+/// Leading integer of the string after the white spaces, or zero.
+fn atoi(_: &mut dyn Write, args: &[Value]) -> Result<Value, Trap> {
+  let text = string(&args[0])?.trim_start();
+  let (sign, digits) = match text.strip_prefix('-') {
+    Some(digits) => (-1, digits),
+    None => (1, text.strip_prefix('+').unwrap_or(text)),
+  };
+  let digits = digits.chars().take_while(char::is_ascii_digit).collect::<String>();
+  Ok(Value::Int(sign * digits.parse::<i128>().unwrap_or(0)))
+}
+
+fn sqrt(_: &mut dyn Write, args: &[Value]) -> Result<Value, Trap> {
+  Ok(Value::Float(float(&args[0])?.sqrt()))
+}
+
+fn pow(_: &mut dyn Write, args: &[Value]) -> Result<Value, Trap> {
+  Ok(Value::Float(float(&args[0])?.powf(float(&args[1])?)))
+}
+
+fn exit(_: &mut dyn Write, args: &[Value]) -> Result<Value, Trap> {
+  Err(Trap::Exit(int(&args[0])? as i32))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn call(name: &str, args: &[Value]) -> (Result<Value, Trap>, String) {
+    let mut output = Vec::new();
+    let result = defaults()[name](&mut output, args);
+    (result, String::from_utf8(output).unwrap())
+  }
+
+  #[test]
+  fn test_output() {
+    let args = [Value::Str("x = ".to_string()), Value::Int(-3)];
+    assert_eq!(
+      call("println", &args),
+      (Ok(Value::Unit), "x = -3\n".to_string())
+    );
+    assert_eq!(call("print", &[Value::Float(1.0)]).1, "1.0");
+    assert_eq!(call("putchar", &[Value::Int(65)]).1, "A");
+    let args = [
+      Value::Str("%s: %d%% %ld %c %x\n".to_string()),
+      Value::Str("rate".to_string()),
+      Value::Int(50),
+      Value::Int(-7),
+      Value::Int(98),
+      Value::Int(255),
+    ];
+    assert_eq!(
+      call("printf", &args),
+      (Ok(Value::Int(18)), "rate: 50% -7 b ff\n".to_string())
+    );
+    assert_eq!(
+      call("printf", &[Value::Str("%.2f".to_string())]).0,
+      Err(Trap::Error("unsupported conversion `%.`".to_string()))
+    );
+  }
+
+  #[test]
+  fn test_library() {
+    assert_eq!(
+      call("strlen", &[Value::Str("four".to_string())]).0,
+      Ok(Value::Int(4))
+    );
+    assert_eq!(call("abs", &[Value::Int(-4)]).0, Ok(Value::Int(4)));
+    assert_eq!(
+      call("atoi", &[Value::Str("  -42abc".to_string())]).0,
+      Ok(Value::Int(-42))
+    );
+    assert_eq!(
+      call("atoi", &[Value::Str("x".to_string())]).0,
+      Ok(Value::Int(0))
+    );
+    assert_eq!(call("sqrt", &[Value::Float(9.0)]).0, Ok(Value::Float(3.0)));
+    assert_eq!(call("exit", &[Value::Int(3)]).0, Err(Trap::Exit(3)));
+    assert_eq!(
+      call("strlen", &[Value::null()]).0,
+      Err(Trap::Error("null pointer passed as a string".to_string()))
+    );
+  }
+}
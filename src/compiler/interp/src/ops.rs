@@ -0,0 +1,302 @@
+//! Arithmetic, comparisons and conversions of the primitive values, which are checked as the
+//! evaluation at compile time does, or wrap around without the overflow checks.
+use vsp_ast::ast::expr::BinaryOp;
+use vsp_ast::ast::types::PrimitiveType;
+use vsp_ast::ast::types::Type;
+
+use crate::value::Value;
+
+/// Primitive type of the type, into whose range the integers of the type are checked and wrapped.
+/// Other types have no range, as `unit` does.
+pub fn primitive(ty: &Type) -> &PrimitiveType {
+  match ty {
+    Type::Primitive(primitive) => primitive,
+    _ => &PrimitiveType::Unit,
+  }
+}
+
+/// Binary operation other than `&&` and `||` on the operands of the type, returning the message of
+/// the error if any. Division by zero always fails, while the overflow fails only with the overflow
+/// checks.
+pub fn binary(
+  op: &BinaryOp,
+  left: Value,
+  right: Value,
+  ty: &Type,
+  checks: bool,
+) -> Result<Value, String> {
+  let value = match (left, right) {
+    (Value::Int(left), Value::Int(right)) => {
+      let ty = primitive(ty);
+      let (verb, result) = match op {
+        BinaryOp::Add => ("add", left.checked_add(right)),
+        BinaryOp::Subtract => ("subtract", left.checked_sub(right)),
+        BinaryOp::Multiply => ("multiply", left.checked_mul(right)),
+        BinaryOp::Division if right == 0 => return Err("attempt to divide by zero".to_string()),
+        BinaryOp::Division => ("divide", left.checked_div(right)),
+        BinaryOp::Remainder if right == 0 => {
+          return Err("attempt to calculate the remainder with a divisor of zero".to_string())
+        }
+        // `MIN % -1` overflows as `MIN / -1` does, which traps when compiled, and wraps around
+        // into `0`.
+        BinaryOp::Remainder => {
+          let quotient = left.checked_div(right).unwrap_or(i128::MAX);
+          if checks && ty.check_range(quotient, "").is_err() {
+            return Err("attempt to calculate the remainder with overflow".to_string());
+          }
+          ("calculate the remainder", left.checked_rem(right))
+        }
+        op => return Ok(Value::Bool(op.compare(left.cmp(&right)))),
+      };
+      // Operands in `i128` never overflow it, except the products of `uint64`.
+      let result = match result {
+        Some(result) => result,
+        None if checks => return Err(format!("attempt to {} with overflow", verb)),
+        None => left.wrapping_mul(right),
+      };
+      if checks {
+        return ty.check_range(result, verb).map(Value::Int);
+      }
+      return Ok(Value::Int(ty.wrap(result)));
+    }
+    (Value::Float(left), Value::Float(right)) => match op {
+      BinaryOp::Add => Value::Float(left + right),
+      BinaryOp::Subtract => Value::Float(left - right),
+      BinaryOp::Multiply => Value::Float(left * right),
+      BinaryOp::Division => Value::Float(left / right),
+      BinaryOp::Remainder => Value::Float(left % right),
+      BinaryOp::Equal => Value::Bool(left == right),
+      BinaryOp::NotEqual => Value::Bool(left != right),
+      BinaryOp::Less => Value::Bool(left < right),
+      BinaryOp::LessEqual => Value::Bool(left <= right),
+      BinaryOp::Greater => Value::Bool(left > right),
+      BinaryOp::GreaterEqual => Value::Bool(left >= right),
+      op => unreachable!("invalid float operator `{}`", op.as_str()),
+    },
+    (Value::Bool(left), Value::Bool(right)) => Value::Bool(op.compare(left.cmp(&right))),
+    (Value::Str(left), Value::Str(right)) => Value::Bool(op.compare(left.cmp(&right))),
+    (Value::Pointer(left), Value::Pointer(right)) => match op {
+      BinaryOp::Equal => Value::Bool(left == right),
+      BinaryOp::NotEqual => Value::Bool(left != right),
+      // Pointers are only ordered and subtracted within the same allocation or array, since they
+      // do not have numeric addresses.
+      op => match left.zip(right).and_then(|(left, right)| left.distance(&right)) {
+        Some(distance) if *op == BinaryOp::Subtract => Value::Int(distance as i128),
+        Some(distance) => Value::Bool(op.compare(distance.cmp(&0))),
+        None => {
+          return Err(format!(
+            "cannot {} raw pointers into different allocations",
+            match op {
+              BinaryOp::Subtract => "subtract",
+              _ => "compare",
+            }
+          ))
+        }
+      },
+    },
+    (Value::Pointer(Some(address)), Value::Int(count)) => {
+      let count = match op {
+        BinaryOp::Add => count,
+        BinaryOp::Subtract => -count,
+        op => unreachable!("invalid pointer operator `{}`", op.as_str()),
+      };
+      match i64::try_from(count).ok().and_then(|count| address.offset(count)) {
+        Some(address) => Value::Pointer(Some(address)),
+        None => return Err("cannot offset the pointer into a field".to_string()),
+      }
+    }
+    (Value::Pointer(None), Value::Int(_)) => {
+      return Err("attempt to offset a null pointer".to_string())
+    }
+    (left, right) => unreachable!(
+      "invalid operands of `{}`: {:?} and {:?}",
+      op.as_str(),
+      left,
+      right
+    ),
+  };
+  Ok(value)
+}
+
+fn is_array(ty: &Type) -> bool {
+  matches!(ty, Type::Array(..) | Type::ConstArray(..))
+}
+
+/// Conversion between primitive types and raw pointers. Floats are converted into integers with
+/// saturation. Raw pointers do not have numeric addresses, so only the null pointer converts from
+/// and into integers. Pointers to arrays convert into the pointers to their first elements.
+pub fn cast(value: Value, source: &Type, target: &Type) -> Result<Value, String> {
+  if let Type::Pointer(pointee) = target {
+    return match (value, source) {
+      (Value::Int(0), _) => Ok(Value::null()),
+      (Value::Int(_), _) => Err("cannot convert non-zero integers into raw pointers".to_string()),
+      (Value::Pointer(Some(address)), Type::Pointer(array))
+        if is_array(array) && !is_array(pointee) =>
+      {
+        Ok(Value::Pointer(Some(address.elements())))
+      }
+      (value, _) => Ok(value),
+    };
+  }
+  let target = match target {
+    Type::Primitive(primitive) => primitive,
+    _ => return Ok(value),
+  };
+  if target.is_float() {
+    return Ok(match value {
+      Value::Int(value) => Value::Float(value as f64),
+      value => value,
+    });
+  }
+  Ok(match value {
+    Value::Int(value) => Value::Int(target.wrap(value)),
+    Value::Bool(value) => Value::Int(value as i128),
+    Value::Float(value) => Value::Int(target.saturate(value)),
+    Value::Pointer(None) => Value::Int(0),
+    Value::Pointer(Some(_)) => {
+      return Err("cannot convert raw pointers into integers in the interpreter".to_string())
+    }
+    value => value,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::value::Address;
+
+  #[test]
+  fn test_binary() {
+    let int8 = Type::int8();
+    assert_eq!(
+      binary(&BinaryOp::Add, Value::Int(100), Value::Int(27), &int8, true),
+      Ok(Value::Int(127))
+    );
+    assert_eq!(
+      binary(&BinaryOp::Add, Value::Int(100), Value::Int(28), &int8, true),
+      Err("attempt to add with overflow".to_string())
+    );
+    assert_eq!(
+      binary(
+        &BinaryOp::Remainder,
+        Value::Int(-128),
+        Value::Int(-1),
+        &int8,
+        true
+      ),
+      Err("attempt to calculate the remainder with overflow".to_string())
+    );
+    // The arithmetic wraps around without the overflow checks.
+    assert_eq!(
+      binary(
+        &BinaryOp::Add,
+        Value::Int(100),
+        Value::Int(28),
+        &int8,
+        false
+      ),
+      Ok(Value::Int(-128))
+    );
+    assert_eq!(
+      binary(
+        &BinaryOp::Remainder,
+        Value::Int(-128),
+        Value::Int(-1),
+        &int8,
+        false
+      ),
+      Ok(Value::Int(0))
+    );
+    assert_eq!(
+      binary(&BinaryOp::Less, Value::Int(-1), Value::Int(1), &int8, true),
+      Ok(Value::Bool(true))
+    );
+
+    let address = Address {
+      block:      0,
+      generation: 0,
+      index:      2,
+      path:       vec![],
+      element:    None,
+    };
+    let pointer = Type::Pointer(Box::new(Type::int64()));
+    match binary(
+      &BinaryOp::Subtract,
+      Value::Pointer(Some(address.clone())),
+      Value::Int(2),
+      &pointer,
+      true,
+    ) {
+      Ok(Value::Pointer(Some(offset))) => assert_eq!(offset.index, 0),
+      value => panic!("expected pointer, found {:?}", value),
+    }
+    assert_eq!(
+      binary(
+        &BinaryOp::Add,
+        Value::Pointer(Some(address.field(1))),
+        Value::Int(1),
+        &pointer,
+        true
+      ),
+      Err("cannot offset the pointer into a field".to_string())
+    );
+    let other = Value::Pointer(Some(Address {
+      block: 1,
+      ..address.clone()
+    }));
+    let address = Value::Pointer(Some(address));
+    assert_eq!(
+      binary(
+        &BinaryOp::Less,
+        address.clone(),
+        other.clone(),
+        &pointer,
+        true
+      ),
+      Err("cannot compare raw pointers into different allocations".to_string())
+    );
+    assert_eq!(
+      binary(&BinaryOp::Subtract, address, other, &pointer, true),
+      Err("cannot subtract raw pointers into different allocations".to_string())
+    );
+  }
+
+  #[test]
+  fn test_cast() {
+    let int64 = Type::int64();
+    assert_eq!(
+      cast(Value::Int(300), &int64, &Type::uint8()),
+      Ok(Value::Int(44))
+    );
+    assert_eq!(
+      cast(Value::Int(-1), &int64, &Type::uint16()),
+      Ok(Value::Int(65535))
+    );
+    assert_eq!(
+      cast(Value::Float(1e10), &Type::float64(), &Type::int32()),
+      Ok(Value::Int(2147483647))
+    );
+    assert_eq!(
+      cast(Value::Bool(true), &Type::bool(), &int64),
+      Ok(Value::Int(1))
+    );
+    let pointer = Type::Pointer(Box::new(Type::int64()));
+    assert_eq!(cast(Value::Int(0), &int64, &pointer), Ok(Value::null()));
+    assert!(cast(Value::Int(8), &int64, &pointer).is_err());
+
+    let address = Address {
+      block:      0,
+      generation: 0,
+      index:      0,
+      path:       vec![],
+      element:    None,
+    };
+    let array = Type::Pointer(Box::new(Type::Array(Box::new(Type::int64()), 2)));
+    let value = Value::Pointer(Some(address.clone()));
+    assert_eq!(
+      cast(value.clone(), &array, &pointer),
+      Ok(Value::Pointer(Some(address.elements())))
+    );
+    assert_eq!(cast(value.clone(), &array, &array), Ok(value));
+  }
+}
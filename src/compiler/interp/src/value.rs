@@ -0,0 +1,136 @@
+//! Values of the interpreter.
+use vsp_ast::ast::types::Type;
+use vsp_sema::items::MethodPath;
+use vsp_sema::resolve::DefPath;
+
+/// Value during the interpretation. Integers of all types, including `char`, are held in `i128`
+/// within the range of their types.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+  Unit,
+  Int(i128),
+  Float(f64),
+  Bool(bool),
+  Str(String),
+  /// Struct value, with the fields in the order of declaration.
+  Struct(Vec<Value>),
+  /// Array value, with its elements.
+  Array(Vec<Value>),
+  /// Enum value, with the index of the variant and its fields.
+  Variant(usize, Vec<Value>),
+  /// Raw pointer into the heap, which is `None` for the null pointer.
+  Pointer(Option<Address>),
+  Function(Callee),
+  /// State machine returned by calling the `async func`, which holds the arguments of the call
+  /// until it is polled.
+  Coroutine {
+    def:   DefPath,
+    targs: Vec<Type>,
+    args:  Vec<Value>,
+  },
+  /// Trait object, with the type of the value behind it, by which its methods are dispatched.
+  Dyn(Box<Value>, Type),
+}
+
+impl Value {
+  pub fn null() -> Self {
+    Value::Pointer(None)
+  }
+
+  /// Project the value into its field or its element, or `None` if it is not a struct with the
+  /// field or an array with the element.
+  pub fn field(&self, index: usize) -> Option<&Value> {
+    match self {
+      Value::Struct(fields) | Value::Array(fields) => fields.get(index),
+      _ => None,
+    }
+  }
+
+  pub fn field_mut(&mut self, index: usize) -> Option<&mut Value> {
+    match self {
+      Value::Struct(fields) | Value::Array(fields) => fields.get_mut(index),
+      _ => None,
+    }
+  }
+}
+
+/// Function value, whose generic parameters are substituted already.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Callee {
+  /// Function by its path, with its type arguments, which might be a foreign function bound to a
+  /// native one.
+  Function(DefPath, Vec<Type>),
+  Method(MethodPath),
+  /// `poll` of the state machine of the `async func`, which runs its body to the completion.
+  Poll(DefPath, Vec<Type>),
+}
+
+/// Location in the heap pointed by the raw pointers. Each allocation holds a number of values, as
+/// an array does, and the pointers are offset between them. The fields of structs and the elements
+/// of arrays are reached by the path of their indices, and the pointers to the elements of an array
+/// are offset between its elements.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Address {
+  pub block:      usize,
+  /// Generation of the block when the address is taken, which no longer matches once the block is
+  /// freed, so that the dangling pointers are detected.
+  pub generation: usize,
+  /// Index of the value in the block, which might be out of bounds after the offsets.
+  pub index:      i64,
+  /// Indices of the fields from the value.
+  pub path:       Vec<usize>,
+  /// Index of the element in the array at the path, if the address is of an element of the array,
+  /// which might be out of bounds after the offsets.
+  pub element:    Option<i64>,
+}
+
+impl Address {
+  /// Address of the field of the value at the address.
+  pub fn field(&self, index: usize) -> Address {
+    let mut address = self.clone();
+    address.settle();
+    address.path.push(index);
+    address
+  }
+
+  /// Address of the first element of the array at the address.
+  pub fn elements(&self) -> Address {
+    let mut address = self.clone();
+    address.settle();
+    address.element = Some(0);
+    address
+  }
+
+  /// Address offset by the number of the values, which is only valid for the addresses of whole
+  /// values in the block, and of the elements of arrays.
+  pub fn offset(&self, count: i64) -> Option<Address> {
+    let mut address = self.clone();
+    match self.element {
+      Some(element) => address.element = Some(element.checked_add(count)?),
+      None if self.path.is_empty() => address.index = self.index.checked_add(count)?,
+      None => return None,
+    }
+    Some(address)
+  }
+
+  /// Number of the values or the elements between the addresses, or `None` if they are not in the
+  /// same block or the same array.
+  pub fn distance(&self, other: &Address) -> Option<i64> {
+    if (self.block, self.generation, &self.path) != (other.block, other.generation, &other.path) {
+      return None;
+    }
+    match (self.element, other.element) {
+      (Some(left), Some(right)) if self.index == other.index => left.checked_sub(right),
+      (None, None) if self.path.is_empty() => self.index.checked_sub(other.index),
+      _ => None,
+    }
+  }
+
+  /// Move the index of the element into the path, so that the element is reached as a field.
+  /// Elements out of bounds are moved as the invalid fields.
+  fn settle(&mut self) {
+    if let Some(element) = self.element.take() {
+      self.path.push(usize::try_from(element).unwrap_or(usize::MAX));
+    }
+  }
+}
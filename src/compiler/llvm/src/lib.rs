@@ -1,5 +1,43 @@
-/// # LLVM vs. Inkwell
-/// This module is using `inkwell`, a type-safe wrapper of expose LLVM APIs.
+//! # LLVM vs. Inkwell
+//! This module is using `inkwell`, a type-safe wrapper of expose LLVM APIs.
+//!
+//! The program checked by the semantic analysis is lowered into MIR, and then generated into a
+//! verified LLVM module, which could be compiled ahead of time or run by the JIT:
+//!
+//! ```rust
+//! use inkwell::context::Context;
+//! use inkwell::execution_engine::JitFunction;
+//! use inkwell::OptimizationLevel;
+//! use vsp_ast_parser::lex::DefaultLexer;
+//! use vsp_ast_parser::parser::ASTParser;
+//! use vsp_ast_parser::parser::TraditionalParser;
+//! use vsp_diag::DiagnosticEngine;
+//! use vsp_llvm::llvm::ir::OverflowMode;
+//! use vsp_llvm::llvm::CodegenContext;
+//!
+//! let tokens = DefaultLexer {}
+//!   .tokenize(
+//!     "func square(x: int64): int64 { return x * x; }
+//!      func main(n: int64): int64 { return square(n) + 1; }",
+//!   )
+//!   .unwrap();
+//! let unit = TraditionalParser {}.parse(tokens).unwrap();
+//! let mut diagnostics = DiagnosticEngine::default();
+//! let results = vsp_sema::check_compilation_unit(&unit, &mut diagnostics);
+//! let program = vsp_hir::lower_compilation_unit(&unit, &results);
+//! let program = vsp_mir::build::build_program(&program, &mut diagnostics);
+//! assert!(!diagnostics.has_errors());
+//!
+//! let context = Context::create();
+//! let codegen = CodegenContext::new("main".to_owned(), &context);
+//! codegen.add_program(&program, &results, OverflowMode::Checked).unwrap();
+//! let module = codegen.into_module();
+//! let engine = module.create_jit_execution_engine(OptimizationLevel::None).unwrap();
+//! unsafe {
+//!   let main: JitFunction<unsafe extern "C" fn(i64) -> i64> = engine.get_function("main").unwrap();
+//!   assert_eq!(main.call(7), 50);
+//! }
+//! ```
 use std::error::Error;
 use std::ffi::c_void;
 
@@ -15,6 +53,23 @@ pub mod llvm;
 pub(crate) mod types;
 
 /// ```rust
+/// # extern crate llvm_sys as llvm;
+/// # use std::collections::HashSet;
+/// # use std::ffi::CString;
+/// # use std::ptr;
+/// #
+/// # use llvm::prelude::*;
+/// #
+/// # enum Expr {
+/// #   Literal(String),
+/// #   Ref(String),
+/// #   Add(Box<Expr>, Box<Expr>),
+/// #   Sub(Box<Expr>, Box<Expr>),
+/// #   Mul(Box<Expr>, Box<Expr>),
+/// #   Div(Box<Expr>, Box<Expr>),
+/// #   Assign(String, Box<Expr>),
+/// #   If(Box<Expr>, Vec<Expr>, Vec<Expr>),
+/// # }
 /// use std::collections::HashMap;
 ///
 /// unsafe fn codegen(input: Vec<Expr>) {
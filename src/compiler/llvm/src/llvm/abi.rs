@@ -0,0 +1,642 @@
+//! C calling convention
+//!
+//! Foreign functions, and the functions exported by `extern "C"` with bodies, are called by the
+//! System V x86-64 ABI, where each argument and the return value is classified by the C layout of
+//! its type:
+//!
+//! - Scalars are passed directly, and `bool` and the integers narrower than 32 bits are extended by
+//!   `zeroext` or `signext`.
+//! - Aggregates of at most 16 bytes are split into eightbytes, each of which is passed in a general
+//!   purpose register if it contains any integer or pointer, or in an SSE register otherwise. The
+//!   value is coerced through memory into the eightbytes, such as `i64 %0, double %1`.
+//! - Larger aggregates are passed in memory, by the pointer marked `byval` for the arguments, and
+//!   by the hidden pointer marked `sret` for the return value. So is an aggregate argument when
+//!   there are not enough registers left for all of its eightbytes.
+//!
+//! Variable arguments are promoted as C does, so `bool` and the narrow integers become `int32`.
+//!
+//! The scalars are passed the same way by the C ABIs of the other targets, where LLVM assigns the
+//! registers, but the aggregates are not supported there yet.
+use inkwell::attributes::Attribute;
+use inkwell::attributes::AttributeLoc;
+use inkwell::builder::Builder;
+use inkwell::context::Context;
+use inkwell::module::Linkage;
+use inkwell::module::Module;
+use inkwell::types::AnyType;
+use inkwell::types::BasicMetadataTypeEnum;
+use inkwell::types::BasicType;
+use inkwell::types::BasicTypeEnum;
+use inkwell::types::FunctionType;
+use inkwell::values::BasicMetadataValueEnum;
+use inkwell::values::BasicValueEnum;
+use inkwell::values::FunctionValue;
+use inkwell::values::PointerValue;
+use inkwell::AddressSpace;
+use vsp_ast::ast::types::PrimitiveType;
+use vsp_ast::ast::types::Type;
+use vsp_mir::mir::Program;
+
+use crate::llvm::layout::layout_of;
+use crate::llvm::layout::primitive_size;
+use crate::types::basic_type;
+use crate::types::struct_fields;
+
+/// Number of the general purpose registers for the arguments, `rdi`, `rsi`, `rdx`, `rcx`, `r8`
+/// and `r9`.
+const INTEGER_REGISTERS: usize = 6;
+/// Number of the SSE registers for the arguments, `xmm0` to `xmm7`.
+const SSE_REGISTERS: usize = 8;
+
+/// Class of an eightbyte, which decides the kind of register to pass it in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RegClass {
+  Integer,
+  Sse,
+}
+
+/// Extension of the integers narrower than 32 bits, which the caller is responsible for.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Extension {
+  None,
+  Zero,
+  Sign,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PassMode {
+  /// Not passed at all, which is the case of `unit`.
+  Ignore,
+  /// Passed as the LLVM type of the value.
+  Direct(Extension),
+  /// Passed in registers, one for each eightbyte of the value.
+  Cast(Vec<RegClass>),
+  /// Passed in memory.
+  Indirect,
+}
+
+/// Argument or return value of a function with the C calling convention.
+#[derive(Clone, Debug)]
+pub struct ArgAbi {
+  pub ty:   Type,
+  pub mode: PassMode,
+}
+
+/// How the arguments and the return value of a function with the C calling convention are passed.
+#[derive(Clone, Debug)]
+pub struct FnAbi {
+  pub args:     Vec<ArgAbi>,
+  pub ret:      ArgAbi,
+  pub variadic: bool,
+}
+
+impl FnAbi {
+  pub fn new(program: &Program, params: &[Type], ret: &Type, variadic: bool) -> Self {
+    let ret = ArgAbi {
+      ty:   ret.clone(),
+      mode: classify(program, ret),
+    };
+    let mut integer = INTEGER_REGISTERS;
+    let mut sse = SSE_REGISTERS;
+    if ret.mode == PassMode::Indirect {
+      integer -= 1;
+    }
+    let args = params
+      .iter()
+      .map(|param| {
+        let mut mode = classify(program, param);
+        let classes = match &mode {
+          PassMode::Cast(classes) => classes.clone(),
+          PassMode::Direct(_) if matches!(param, Type::Primitive(p) if p.is_float()) => {
+            vec![RegClass::Sse]
+          }
+          PassMode::Direct(_) => vec![RegClass::Integer],
+          PassMode::Ignore | PassMode::Indirect => vec![],
+        };
+        let needed_integer = classes.iter().filter(|c| **c == RegClass::Integer).count();
+        let needed_sse = classes.len() - needed_integer;
+        if needed_integer <= integer && needed_sse <= sse {
+          integer -= needed_integer;
+          sse -= needed_sse;
+        } else if let PassMode::Cast(_) = mode {
+          mode = PassMode::Indirect;
+        }
+        ArgAbi {
+          ty: param.clone(),
+          mode,
+        }
+      })
+      .collect();
+    Self {
+      args,
+      ret,
+      variadic,
+    }
+  }
+
+  /// LLVM type of the function, where the aggregates are replaced with their eightbytes or
+  /// pointers.
+  pub fn function_type<'ctx>(
+    &self,
+    context: &'ctx Context,
+    program: &Program,
+  ) -> FunctionType<'ctx> {
+    let mut params: Vec<BasicMetadataTypeEnum> = vec![];
+    if self.ret.mode == PassMode::Indirect {
+      params.push(pointer_type(context, program, &self.ret.ty).into());
+    }
+    for arg in &self.args {
+      match &arg.mode {
+        PassMode::Ignore => {}
+        PassMode::Direct(_) => params.push(basic_type(context, program, &arg.ty).into()),
+        PassMode::Cast(classes) => {
+          let size = layout_of(program, &arg.ty).size;
+          let types = cast_types(context, size, classes);
+          params.extend(types.into_iter().map(BasicMetadataTypeEnum::from));
+        }
+        PassMode::Indirect => params.push(pointer_type(context, program, &arg.ty).into()),
+      }
+    }
+    match &self.ret.mode {
+      PassMode::Ignore | PassMode::Indirect => context.void_type().fn_type(&params, self.variadic),
+      PassMode::Direct(_) => {
+        basic_type(context, program, &self.ret.ty).fn_type(&params, self.variadic)
+      }
+      PassMode::Cast(classes) => {
+        cast_return_type(context, program, &self.ret.ty, classes).fn_type(&params, self.variadic)
+      }
+    }
+  }
+
+  /// Attributes of the parameters and the return value, which are added to both of the
+  /// declaration and the call sites.
+  fn attributes(&self, context: &Context, program: &Program) -> Vec<(AttributeLoc, Attribute)> {
+    let mut attributes = vec![];
+    if let PassMode::Direct(extension) = &self.ret.mode {
+      if let Some(attribute) = extension_attribute(context, *extension) {
+        attributes.push((AttributeLoc::Return, attribute));
+      }
+    }
+    let mut index = 0;
+    if self.ret.mode == PassMode::Indirect {
+      let ty = basic_type(context, program, &self.ret.ty).as_any_type_enum();
+      attributes.push((AttributeLoc::Param(0), type_attribute(context, "sret", ty)));
+      attributes.push((
+        AttributeLoc::Param(0),
+        enum_attribute(context, "noalias", 0),
+      ));
+      index += 1;
+    }
+    for arg in &self.args {
+      match &arg.mode {
+        PassMode::Ignore => {}
+        PassMode::Direct(extension) => {
+          if let Some(attribute) = extension_attribute(context, *extension) {
+            attributes.push((AttributeLoc::Param(index), attribute));
+          }
+          index += 1;
+        }
+        PassMode::Cast(classes) => {
+          let size = layout_of(program, &arg.ty).size;
+          index += cast_types(context, size, classes).len() as u32;
+        }
+        PassMode::Indirect => {
+          let ty = basic_type(context, program, &arg.ty).as_any_type_enum();
+          let align = layout_of(program, &arg.ty).align;
+          attributes.push((
+            AttributeLoc::Param(index),
+            type_attribute(context, "byval", ty),
+          ));
+          attributes.push((
+            AttributeLoc::Param(index),
+            enum_attribute(context, "align", align),
+          ));
+          index += 1;
+        }
+      }
+    }
+    attributes
+  }
+
+  /// Declare the function in the module unless it is already declared.
+  pub fn declare<'ctx>(
+    &self,
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    program: &Program,
+    name: &str,
+  ) -> FunctionValue<'ctx> {
+    if let Some(function) = module.get_function(name) {
+      return function;
+    }
+    let function_type = self.function_type(context, program);
+    let function = module.add_function(name, function_type, Some(Linkage::External));
+    for (loc, attribute) in self.attributes(context, program) {
+      function.add_attribute(loc, attribute);
+    }
+    function
+  }
+
+  /// Call the function with the arguments and their types, and return the result as the
+  /// value of the return type.
+  pub fn build_call<'ctx>(
+    &self,
+    context: &'ctx Context,
+    builder: &Builder<'ctx>,
+    program: &Program,
+    function: FunctionValue<'ctx>,
+    args: &[(BasicValueEnum<'ctx>, &Type)],
+  ) -> BasicValueEnum<'ctx> {
+    let mut values: Vec<BasicMetadataValueEnum> = vec![];
+    let ret_slot = match self.ret.mode {
+      PassMode::Indirect => {
+        let ty = basic_type(context, program, &self.ret.ty);
+        let slot = build_entry_alloca(context, builder, ty, "sret");
+        values.push(slot.into());
+        Some(slot)
+      }
+      _ => None,
+    };
+    for (index, (value, ty)) in args.iter().enumerate() {
+      match self.args.get(index).map(|arg| &arg.mode) {
+        Some(PassMode::Ignore) => {}
+        Some(PassMode::Direct(_)) => values.push((*value).into()),
+        Some(PassMode::Cast(classes)) => {
+          let size = layout_of(program, ty).size;
+          let cast = context.struct_type(&cast_types(context, size, classes), false);
+          let slot = build_entry_alloca(context, builder, cast.into(), "coerce");
+          let pointer = value.get_type().ptr_type(AddressSpace::default());
+          builder.build_store(builder.build_pointer_cast(slot, pointer, "coerce"), *value);
+          for field in 0..classes.len() as u32 {
+            let pointer = builder.build_struct_gep(slot, field, "coerce").unwrap();
+            values.push(builder.build_load(pointer, "coerce").into());
+          }
+        }
+        Some(PassMode::Indirect) => {
+          let slot = build_entry_alloca(context, builder, value.get_type(), "byval");
+          builder.build_store(slot, *value);
+          values.push(slot.into());
+        }
+        None => values.push(promote_variadic(context, builder, *value, ty).into()),
+      }
+    }
+
+    let call = builder.build_call(function, &values, "call");
+    for (loc, attribute) in self.attributes(context, program) {
+      call.add_attribute(loc, attribute);
+    }
+    match &self.ret.mode {
+      PassMode::Ignore => context.const_struct(&[], false).into(),
+      PassMode::Direct(_) => call.try_as_basic_value().left().unwrap(),
+      PassMode::Cast(_) => {
+        let result = call.try_as_basic_value().left().unwrap();
+        let slot = build_entry_alloca(context, builder, result.get_type(), "coerce");
+        builder.build_store(slot, result);
+        let ty = basic_type(context, program, &self.ret.ty).ptr_type(AddressSpace::default());
+        builder.build_load(builder.build_pointer_cast(slot, ty, "coerce"), "ret")
+      }
+      PassMode::Indirect => builder.build_load(ret_slot.unwrap(), "ret"),
+    }
+  }
+
+  /// Store the parameters of the function defined by this ABI into the stack slots of its
+  /// arguments, where the eightbytes and the values passed in memory are reassembled.
+  pub fn build_prologue<'ctx>(
+    &self,
+    context: &'ctx Context,
+    builder: &Builder<'ctx>,
+    program: &Program,
+    function: FunctionValue<'ctx>,
+    slots: &[PointerValue<'ctx>],
+  ) {
+    let mut params = function.get_param_iter();
+    if self.ret.mode == PassMode::Indirect {
+      params.next();
+    }
+    for (arg, slot) in self.args.iter().zip(slots) {
+      match &arg.mode {
+        PassMode::Ignore => {}
+        PassMode::Direct(_) => {
+          builder.build_store(*slot, params.next().unwrap());
+        }
+        PassMode::Cast(classes) => {
+          let size = layout_of(program, &arg.ty).size;
+          let cast = context.struct_type(&cast_types(context, size, classes), false);
+          let pointer = cast.ptr_type(AddressSpace::default());
+          let pointer = builder.build_pointer_cast(*slot, pointer, "coerce");
+          for field in 0..classes.len() as u32 {
+            let field = builder.build_struct_gep(pointer, field, "coerce").unwrap();
+            builder.build_store(field, params.next().unwrap());
+          }
+        }
+        PassMode::Indirect => {
+          let pointer = params.next().unwrap().into_pointer_value();
+          builder.build_store(*slot, builder.build_load(pointer, "byval"));
+        }
+      }
+    }
+  }
+
+  /// Return the value from the function defined by this ABI.
+  pub fn build_return<'ctx>(
+    &self,
+    context: &'ctx Context,
+    builder: &Builder<'ctx>,
+    program: &Program,
+    function: FunctionValue<'ctx>,
+    value: BasicValueEnum<'ctx>,
+  ) {
+    match &self.ret.mode {
+      PassMode::Ignore => builder.build_return(None),
+      PassMode::Direct(_) => builder.build_return(Some(&value)),
+      PassMode::Cast(classes) => {
+        let ty = cast_return_type(context, program, &self.ret.ty, classes);
+        let slot = build_entry_alloca(context, builder, ty, "coerce");
+        let pointer = value.get_type().ptr_type(AddressSpace::default());
+        builder.build_store(builder.build_pointer_cast(slot, pointer, "coerce"), value);
+        builder.build_return(Some(&builder.build_load(slot, "coerce")))
+      }
+      PassMode::Indirect => {
+        let pointer = function.get_first_param().unwrap().into_pointer_value();
+        builder.build_store(pointer, value);
+        builder.build_return(None)
+      }
+    };
+  }
+}
+
+/// Collect the classes of the scalars in the type laid out at the offset, with their offsets.
+fn flatten(program: &Program, ty: &Type, offset: u64, scalars: &mut Vec<(u64, RegClass)>) {
+  match ty {
+    Type::Primitive(PrimitiveType::Unit) => {}
+    Type::Primitive(primitive) if primitive.is_float() => scalars.push((offset, RegClass::Sse)),
+    Type::Primitive(_) | Type::Pointer(_) => scalars.push((offset, RegClass::Integer)),
+    Type::Array(element, len) => {
+      let layout = layout_of(program, ty);
+      for index in 0..*len {
+        flatten(program, element, offset + layout.offset(index), scalars);
+      }
+    }
+    Type::Named(path, args) if program.struct_def(path).is_some() => {
+      let layout = layout_of(program, ty);
+      for (index, field) in struct_fields(program, path, args).iter().enumerate() {
+        flatten(program, field, offset + layout.offset(index), scalars);
+      }
+    }
+    _ => unreachable!("type `{}` has no C layout", ty),
+  }
+}
+
+/// Parameter or return type which is an aggregate, so that it is passed by the rules of the System
+/// V x86-64 ABI only.
+pub fn find_aggregate<'t>(
+  program: &Program,
+  params: &'t [Type],
+  ret: &'t Type,
+) -> Option<&'t Type> {
+  params.iter().chain([ret]).find(|ty| {
+    matches!(
+      classify(program, ty),
+      PassMode::Cast(_) | PassMode::Indirect
+    )
+  })
+}
+
+/// Classify the type as an argument or a return value.
+pub fn classify(program: &Program, ty: &Type) -> PassMode {
+  match ty {
+    Type::Primitive(PrimitiveType::Unit) => PassMode::Ignore,
+    Type::Primitive(PrimitiveType::Bool) => PassMode::Direct(Extension::Zero),
+    Type::Primitive(primitive) if primitive_size(primitive) < 4 => {
+      if primitive.is_signed_integer() {
+        PassMode::Direct(Extension::Sign)
+      } else {
+        PassMode::Direct(Extension::Zero)
+      }
+    }
+    Type::Primitive(_) | Type::Pointer(_) => PassMode::Direct(Extension::None),
+    _ => {
+      let mut scalars = vec![];
+      let layout = layout_of(program, ty);
+      flatten(program, ty, 0, &mut scalars);
+      if layout.size == 0 {
+        return PassMode::Ignore;
+      }
+      if layout.size > 16 {
+        return PassMode::Indirect;
+      }
+      let classes = (0..(layout.size + 7) / 8)
+        .map(|eightbyte| {
+          let sse_only = scalars
+            .iter()
+            .filter(|(offset, _)| offset / 8 == eightbyte)
+            .all(|(_, class)| *class == RegClass::Sse);
+          let any = scalars.iter().any(|(offset, _)| offset / 8 == eightbyte);
+          if any && sse_only {
+            RegClass::Sse
+          } else {
+            RegClass::Integer
+          }
+        })
+        .collect();
+      PassMode::Cast(classes)
+    }
+  }
+}
+
+/// Types of the eightbytes, where the last integer eightbyte is narrowed to the rest of the value.
+fn cast_types<'ctx>(
+  context: &'ctx Context,
+  size: u64,
+  classes: &[RegClass],
+) -> Vec<BasicTypeEnum<'ctx>> {
+  classes
+    .iter()
+    .enumerate()
+    .map(|(index, class)| match class {
+      RegClass::Sse => context.f64_type().into(),
+      RegClass::Integer => {
+        let bytes = (size - index as u64 * 8).min(8);
+        context.custom_width_int_type(bytes as u32 * 8).into()
+      }
+    })
+    .collect()
+}
+
+/// Type of the value returned in registers, which is a single eightbyte or a pair of them.
+fn cast_return_type<'ctx>(
+  context: &'ctx Context,
+  program: &Program,
+  ty: &Type,
+  classes: &[RegClass],
+) -> BasicTypeEnum<'ctx> {
+  let types = cast_types(context, layout_of(program, ty).size, classes);
+  match types.as_slice() {
+    [single] => *single,
+    _ => context.struct_type(&types, false).into(),
+  }
+}
+
+fn pointer_type<'ctx>(context: &'ctx Context, program: &Program, ty: &Type) -> BasicTypeEnum<'ctx> {
+  basic_type(context, program, ty).ptr_type(AddressSpace::default()).into()
+}
+
+fn enum_attribute(context: &Context, name: &str, value: u64) -> Attribute {
+  context.create_enum_attribute(Attribute::get_named_enum_kind_id(name), value)
+}
+
+fn type_attribute<'ctx>(
+  context: &'ctx Context,
+  name: &str,
+  ty: inkwell::types::AnyTypeEnum<'ctx>,
+) -> Attribute {
+  context.create_type_attribute(Attribute::get_named_enum_kind_id(name), ty)
+}
+
+fn extension_attribute(context: &Context, extension: Extension) -> Option<Attribute> {
+  match extension {
+    Extension::None => None,
+    Extension::Zero => Some(enum_attribute(context, "zeroext", 0)),
+    Extension::Sign => Some(enum_attribute(context, "signext", 0)),
+  }
+}
+
+/// Promote the variable argument as C does, where the integers narrower than `int32` are extended
+/// to `int32` with the signedness of their types.
+fn promote_variadic<'ctx>(
+  context: &'ctx Context,
+  builder: &Builder<'ctx>,
+  value: BasicValueEnum<'ctx>,
+  ty: &Type,
+) -> BasicValueEnum<'ctx> {
+  match value {
+    BasicValueEnum::IntValue(value) if value.get_type().get_bit_width() < 32 => {
+      let signed = matches!(ty, Type::Primitive(p) if p.is_signed_integer());
+      builder
+        .build_int_cast_sign_flag(value, context.i32_type(), signed, "promote")
+        .into()
+    }
+    value => value,
+  }
+}
+
+/// Allocate the stack slot in the entry block of the current function, so that a call in a loop
+/// does not grow the stack.
+pub(crate) fn build_entry_alloca<'ctx>(
+  context: &'ctx Context,
+  builder: &Builder<'ctx>,
+  ty: BasicTypeEnum<'ctx>,
+  name: &str,
+) -> PointerValue<'ctx> {
+  let entry = builder
+    .get_insert_block()
+    .and_then(|block| block.get_parent())
+    .and_then(|function| function.get_first_basic_block())
+    .expect("the builder is not positioned in a function");
+  let entry_builder = context.create_builder();
+  match entry.get_first_instruction() {
+    Some(instruction) => entry_builder.position_before(&instruction),
+    None => entry_builder.position_at_end(entry),
+  }
+  entry_builder.build_alloca(ty, name)
+}
+
+#[cfg(test)]
+mod tests {
+  use vsp_ast::ast::module::Path;
+  use vsp_hir::hir::StructDef;
+  use vsp_sema::resolve::DefPath;
+
+  use super::*;
+
+  fn program(structs: &[(&str, Vec<Type>)]) -> Program {
+    let mut program = Program::default();
+    for (name, fields) in structs {
+      program.structs.push(StructDef {
+        def:      DefPath::new(Path::default(), *name),
+        generics: vec![],
+        fields:   fields.clone(),
+      });
+    }
+    program
+  }
+
+  fn named(name: &str) -> Type {
+    Type::Named(Path::from(name), vec![])
+  }
+
+  #[test]
+  fn test_classify() {
+    let int32 = Type::Primitive(PrimitiveType::Int32);
+    let int64 = Type::Primitive(PrimitiveType::Int64);
+    let float64 = Type::Primitive(PrimitiveType::Float64);
+    let program = program(&[
+      ("Pair", vec![int64.clone(), int64.clone()]),
+      ("Mixed", vec![float64.clone(), int32.clone()]),
+      ("Vector", vec![float64.clone(), float64.clone()]),
+      (
+        "Small",
+        vec![int32.clone(), Type::Primitive(PrimitiveType::Uint8)],
+      ),
+      ("Large", vec![int64.clone(), int64.clone(), int64.clone()]),
+      (
+        "Bytes",
+        vec![Type::array(Type::Primitive(PrimitiveType::Uint8), 12)],
+      ),
+    ]);
+    use RegClass::Integer;
+    use RegClass::Sse;
+    assert_eq!(
+      classify(&program, &named("Pair")),
+      PassMode::Cast(vec![Integer, Integer])
+    );
+    assert_eq!(
+      classify(&program, &named("Mixed")),
+      PassMode::Cast(vec![Sse, Integer])
+    );
+    assert_eq!(
+      classify(&program, &named("Vector")),
+      PassMode::Cast(vec![Sse, Sse])
+    );
+    assert_eq!(
+      classify(&program, &named("Small")),
+      PassMode::Cast(vec![Integer])
+    );
+    assert_eq!(classify(&program, &named("Large")), PassMode::Indirect);
+    assert_eq!(
+      classify(&program, &named("Bytes")),
+      PassMode::Cast(vec![Integer, Integer])
+    );
+    assert_eq!(
+      classify(&program, &Type::Primitive(PrimitiveType::Int8)),
+      PassMode::Direct(Extension::Sign)
+    );
+    assert_eq!(
+      classify(&program, &Type::Primitive(PrimitiveType::Bool)),
+      PassMode::Direct(Extension::Zero)
+    );
+    assert_eq!(classify(&program, &Type::unit()), PassMode::Ignore);
+  }
+
+  #[test]
+  fn test_registers() {
+    let int64 = Type::Primitive(PrimitiveType::Int64);
+    let program = program(&[("Pair", vec![int64.clone(), int64.clone()])]);
+    // Five integers leave one general purpose register, which is not enough for `Pair`.
+    let mut params = vec![int64; 5];
+    params.push(named("Pair"));
+    let abi = FnAbi::new(&program, &params, &named("Pair"), false);
+    assert_eq!(abi.args[5].mode, PassMode::Indirect);
+    assert_eq!(
+      abi.ret.mode,
+      PassMode::Cast(vec![RegClass::Integer, RegClass::Integer])
+    );
+
+    let context = Context::create();
+    let ty = abi.function_type(&context, &program);
+    assert_eq!(
+      ty.print_to_string().to_string(),
+      "{ i64, i64 } (i64, i64, i64, i64, i64, { i64, i64 }*)"
+    );
+  }
+}
@@ -0,0 +1,548 @@
+//! Debug information in DWARF, generated under `-g` so that debuggers could map the machine code
+//! back to the source.
+//!
+//! The compile unit is the root file of the package, and each function generated from a body is
+//! described by a subprogram in the file of the body. The blocks of statements in the body are the
+//! lexical blocks nested in the subprogram, where the user variables declared in them live, and
+//! every instruction is located at the statement or the terminator it is generated from, in the
+//! innermost block containing it.
+//!
+//! Types are described by their C layouts, which debuggers understand best:
+//!
+//! - primitives are the base types of their sizes and encodings, and `str` is `char *`;
+//! - structs are structures with their named fields at the offsets of [`layout_of`];
+//! - enums are structures of the tag and the union of their variants, each of which is a structure
+//!   of the fields `__0`, `__1`, and so on;
+//! - trait objects are structures of the pointers to the data and to the vtable.
+//!
+//! Pointers to the structs being described, such as `*Node` in the fields of `Node`, refer to the
+//! forward declarations of the structs, which the debuggers resolve by their names.
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
+
+use inkwell::basic_block::BasicBlock;
+use inkwell::context::Context;
+use inkwell::debug_info::AsDIScope;
+use inkwell::debug_info::DICompileUnit;
+use inkwell::debug_info::DIFile;
+use inkwell::debug_info::DIFlags;
+use inkwell::debug_info::DIFlagsConstants;
+use inkwell::debug_info::DILocation;
+use inkwell::debug_info::DIScope;
+use inkwell::debug_info::DISubprogram;
+use inkwell::debug_info::DIType;
+use inkwell::debug_info::DWARFEmissionKind;
+use inkwell::debug_info::DWARFSourceLanguage;
+use inkwell::debug_info::DebugInfoBuilder;
+use inkwell::module::FlagBehavior;
+use inkwell::module::Module;
+use inkwell::values::FunctionValue;
+use inkwell::values::PointerValue;
+use inkwell::AddressSpace;
+use vsp_ast::ast::types::PrimitiveType;
+use vsp_ast::ast::types::Type;
+use vsp_mir::mir::Body;
+use vsp_mir::mir::Program;
+use vsp_mir::mir::SourceScope;
+use vsp_sema::items::ItemTable;
+use vsp_span::Span;
+
+use crate::llvm::layout::enum_variants;
+use crate::llvm::layout::layout_of;
+use crate::llvm::layout::Shape;
+use crate::types::struct_fields;
+
+/// Version of DWARF to emit.
+const DWARF_VERSION: u64 = 4;
+
+/// Base type encodings of DWARF, `DW_ATE_*`.
+const DW_ATE_BOOLEAN: u32 = 0x02;
+const DW_ATE_FLOAT: u32 = 0x04;
+const DW_ATE_SIGNED: u32 = 0x05;
+const DW_ATE_SIGNED_CHAR: u32 = 0x06;
+const DW_ATE_UNSIGNED: u32 = 0x08;
+const DW_ATE_UTF: u32 = 0x10;
+
+/// Generator of the debug information of a module.
+pub struct DebugInfo<'ctx> {
+  context:     &'ctx Context,
+  builder:     DebugInfoBuilder<'ctx>,
+  unit:        DICompileUnit<'ctx>,
+  optimized:   bool,
+  /// Files of the bodies by their paths.
+  files:       RefCell<HashMap<String, DIFile<'ctx>>>,
+  /// Types described so far, by their names.
+  types:       RefCell<HashMap<String, DIType<'ctx>>>,
+  /// Structs and enums whose fields are being described.
+  in_progress: RefCell<HashSet<String>>,
+}
+
+impl<'ctx> DebugInfo<'ctx> {
+  /// Start the debug information of the module compiled from the package rooted at the file.
+  pub fn new(context: &'ctx Context, module: &Module<'ctx>, root: &Path, optimized: bool) -> Self {
+    let (filename, directory) = split_path(root);
+    let (builder, unit) = module.create_debug_info_builder(
+      true,
+      DWARFSourceLanguage::C,
+      &filename,
+      &directory,
+      concat!("vsp ", env!("CARGO_PKG_VERSION")),
+      optimized,
+      "",
+      0,
+      "",
+      DWARFEmissionKind::Full,
+      0,
+      false,
+      false,
+      "",
+      "",
+    );
+    let i32_type = context.i32_type();
+    module.add_basic_value_flag(
+      "Debug Info Version",
+      FlagBehavior::Warning,
+      i32_type.const_int(inkwell::debug_info::debug_metadata_version() as u64, false),
+    );
+    module.add_basic_value_flag(
+      "Dwarf Version",
+      FlagBehavior::Warning,
+      i32_type.const_int(DWARF_VERSION, false),
+    );
+    Self {
+      context,
+      builder,
+      unit,
+      optimized,
+      files: RefCell::new(HashMap::new()),
+      types: RefCell::new(HashMap::new()),
+      in_progress: RefCell::new(HashSet::new()),
+    }
+  }
+
+  /// Resolve the types and the scopes described so far, which must be done before the module is
+  /// verified or emitted.
+  pub fn finalize(&self) {
+    self.builder.finalize();
+  }
+
+  /// File of the body, or of the compile unit if the body has none.
+  fn file(&self, path: Option<&str>) -> DIFile<'ctx> {
+    let path = match path {
+      Some(path) => path,
+      None => return self.unit.get_file(),
+    };
+    *self.files.borrow_mut().entry(path.to_owned()).or_insert_with(|| {
+      let (filename, directory) = split_path(Path::new(path));
+      self.builder.create_file(&filename, &directory)
+    })
+  }
+
+  /// Describe the function generated from the body, whose parameters and locals have the types
+  /// with the generic parameters substituted.
+  pub fn describe_function(
+    &self,
+    cx: TypeCx<'_>,
+    function: FunctionValue<'ctx>,
+    name: &str,
+    body: &Body,
+    types: &[Type],
+  ) -> FunctionDebugInfo<'ctx> {
+    let file = self.file(body.file.as_deref());
+    let line = body.span.start.line as u32;
+    let ret = match &types[0] {
+      ty if *ty == Type::unit() => None,
+      ty => Some(self.ty(cx, ty)),
+    };
+    let params = body.args().map(|arg| self.ty(cx, &types[arg.0])).collect::<Vec<_>>();
+    let subroutine = self.builder.create_subroutine_type(file, ret, &params, DIFlags::PUBLIC);
+    let linkage_name = function.get_name().to_string_lossy().into_owned();
+    let subprogram = self.builder.create_function(
+      file.as_debug_info_scope(),
+      name,
+      Some(&linkage_name),
+      file,
+      line,
+      subroutine,
+      false,
+      true,
+      line,
+      DIFlags::PROTOTYPED,
+      self.optimized,
+    );
+    function.set_subprogram(subprogram);
+    let mut scopes = vec![subprogram.as_debug_info_scope()];
+    for scope in body.scopes.iter().skip(1) {
+      let parent = scopes[scope.parent.unwrap_or(SourceScope::OUTERMOST).0];
+      let block = self.builder.create_lexical_block(
+        parent,
+        file,
+        scope.span.start.line as u32,
+        scope.span.start.column as u32,
+      );
+      scopes.push(block.as_debug_info_scope());
+    }
+    FunctionDebugInfo {
+      subprogram,
+      file,
+      scopes,
+    }
+  }
+
+  /// Declare the arguments and the user variables of the body, which are located in the memory
+  /// of their stack slots or pointed to by their parameters, at the end of the entry block.
+  pub fn declare_locals(
+    &self,
+    cx: TypeCx<'_>,
+    function: &FunctionDebugInfo<'ctx>,
+    body: &Body,
+    types: &[Type],
+    storage: &[PointerValue<'ctx>],
+    entry: BasicBlock<'ctx>,
+  ) {
+    for (index, decl) in body.locals.iter().enumerate() {
+      let name = match &decl.name {
+        Some(name) => name,
+        None => continue,
+      };
+      let scope = function.scopes[decl.scope.0];
+      let line = decl.span.start.line as u32;
+      let ty = self.ty(cx, &types[index]);
+      let variable = if (1..=body.arg_count).contains(&index) {
+        self.builder.create_parameter_variable(
+          scope,
+          name,
+          index as u32,
+          function.file,
+          line,
+          ty,
+          true,
+          DIFlags::ZERO,
+        )
+      } else {
+        self.builder.create_auto_variable(
+          scope,
+          name,
+          function.file,
+          line,
+          ty,
+          true,
+          DIFlags::ZERO,
+          0,
+        )
+      };
+      let location = self.location(function, body, &decl.span);
+      self
+        .builder
+        .insert_declare_at_end(storage[index], Some(variable), None, location, entry);
+    }
+  }
+
+  /// Location of the span in the innermost scope of the body containing it.
+  pub fn location(
+    &self,
+    function: &FunctionDebugInfo<'ctx>,
+    body: &Body,
+    span: &Span,
+  ) -> DILocation<'ctx> {
+    let scope = function.scopes[body.innermost_scope(span).0];
+    self.builder.create_debug_location(
+      self.context,
+      span.start.line as u32,
+      span.start.column as u32,
+      scope,
+      None,
+    )
+  }
+
+  /// Description of the type, which must have the generic parameters substituted.
+  pub fn ty(&self, cx: TypeCx<'_>, ty: &Type) -> DIType<'ctx> {
+    let name = ty.to_string();
+    if let Some(described) = self.types.borrow().get(&name) {
+      return *described;
+    }
+    let described = self.describe(cx, ty, &name);
+    self.types.borrow_mut().insert(name, described);
+    described
+  }
+
+  fn describe(&self, cx: TypeCx<'_>, ty: &Type, name: &str) -> DIType<'ctx> {
+    match ty {
+      Type::Primitive(PrimitiveType::Str) => {
+        let char_type = self.base_type("char", 8, DW_ATE_SIGNED_CHAR);
+        self.pointer_type(name, char_type)
+      }
+      Type::Primitive(primitive) => {
+        let size = layout_of(cx.program, ty).size * 8;
+        self.base_type(name, size, encoding(primitive))
+      }
+      Type::Pointer(pointee) => {
+        let pointee = match pointee.as_ref() {
+          Type::Named(..) if self.in_progress.borrow().contains(&pointee.to_string()) => {
+            self.forward_declaration(&pointee.to_string())
+          }
+          pointee => self.ty(cx, pointee),
+        };
+        self.pointer_type(name, pointee)
+      }
+      Type::Function(_) => {
+        let unit = self.ty(cx, &Type::unit());
+        self.pointer_type(name, unit)
+      }
+      Type::Array(element, count) => {
+        let layout = layout_of(cx.program, ty);
+        let element = self.ty(cx, element);
+        let subscript = 0..*count as i64;
+        self
+          .builder
+          .create_array_type(
+            element,
+            layout.size * 8,
+            layout.align as u32 * 8,
+            &[subscript],
+          )
+          .as_type()
+      }
+      Type::Dyn(_) => {
+        let erased = self.ty(
+          cx,
+          &Type::Pointer(Box::new(Type::Primitive(PrimitiveType::Uint8))),
+        );
+        self.struct_type(cx, ty, name, &[("data", erased), ("vtable", erased)])
+      }
+      Type::Named(path, args) => {
+        self.in_progress.borrow_mut().insert(name.to_owned());
+        let described = if let Some(def) = cx.program.struct_def(path) {
+          let names = cx.items.structs[&def.def].fields.iter().map(|field| field.name.clone());
+          let fields = struct_fields(cx.program, path, args);
+          let fields = names.zip(fields.iter().map(|field| self.ty(cx, field))).collect::<Vec<_>>();
+          let fields = fields.iter().map(|(name, ty)| (name.as_str(), *ty)).collect::<Vec<_>>();
+          self.struct_type(cx, ty, name, &fields)
+        } else {
+          self.enum_type(cx, ty, name)
+        };
+        self.in_progress.borrow_mut().remove(name);
+        described
+      }
+      Type::Coroutine(..) => self.enum_type(cx, ty, name),
+      _ => self.base_type(name, 0, DW_ATE_UNSIGNED),
+    }
+  }
+
+  fn base_type(&self, name: &str, size: u64, encoding: u32) -> DIType<'ctx> {
+    self
+      .builder
+      .create_basic_type(name, size, encoding, DIFlags::PUBLIC)
+      .expect("base types have names")
+      .as_type()
+  }
+
+  fn pointer_type(&self, name: &str, pointee: DIType<'ctx>) -> DIType<'ctx> {
+    self
+      .builder
+      .create_pointer_type(name, pointee, 64, 64, AddressSpace::default())
+      .as_type()
+  }
+
+  fn forward_declaration(&self, name: &str) -> DIType<'ctx> {
+    let file = self.unit.get_file();
+    self
+      .builder
+      .create_struct_type(
+        file.as_debug_info_scope(),
+        name,
+        file,
+        0,
+        0,
+        0,
+        DIFlags::FWD_DECL,
+        None,
+        &[],
+        0,
+        None,
+        name,
+      )
+      .as_type()
+  }
+
+  /// Structure of the named fields at the offsets of the layout of the type.
+  fn struct_type(
+    &self,
+    cx: TypeCx<'_>,
+    ty: &Type,
+    name: &str,
+    fields: &[(&str, DIType<'ctx>)],
+  ) -> DIType<'ctx> {
+    let layout = layout_of(cx.program, ty);
+    let offsets = match &layout.shape {
+      Shape::Struct { offsets } => offsets.clone(),
+      shape => unreachable!("{:?} is not a struct", shape),
+    };
+    let fields = fields.iter().zip(offsets).map(|((name, ty), offset)| (*name, *ty, offset));
+    self.composite(name, layout.size, layout.align, &fields.collect::<Vec<_>>())
+  }
+
+  /// Structure of the tag and the union of the variants of the enum.
+  fn enum_type(&self, cx: TypeCx<'_>, ty: &Type, name: &str) -> DIType<'ctx> {
+    let layout = layout_of(cx.program, ty);
+    let (payload, offsets) = match &layout.shape {
+      Shape::Enum {
+        payload, variants, ..
+      } => (*payload, variants.clone()),
+      shape => unreachable!("{:?} is not an enum", shape),
+    };
+    let variants = enum_variants(cx.program, ty);
+    // States of the state machines are unnamed, so they are named by their indices.
+    let names = match ty {
+      Type::Named(path, _) => {
+        let def = cx.program.enum_def(path).expect("undefined enum");
+        let info = &cx.items.enums[&def.def];
+        info.variants.iter().map(|variant| variant.name.clone()).collect()
+      }
+      _ => (0..variants.len()).map(|index| format!("state{}", index)).collect::<Vec<_>>(),
+    };
+    let file = self.unit.get_file();
+    let variants = variants
+      .iter()
+      .zip(offsets)
+      .zip(&names)
+      .map(|((fields, offsets), variant)| {
+        let names = (0..fields.len()).map(|index| format!("__{}", index)).collect::<Vec<_>>();
+        let fields = fields
+          .iter()
+          .zip(offsets)
+          .zip(&names)
+          .map(|((field, offset), name)| (name.as_str(), self.ty(cx, field), offset - payload))
+          .collect::<Vec<_>>();
+        let size = fields.iter().map(|(_, ty, offset)| offset + ty.get_size_in_bits() / 8).max();
+        let described = self.composite(
+          &format!("{}::{}", name, variant),
+          size.unwrap_or(0),
+          1,
+          &fields,
+        );
+        let member = self.builder.create_member_type(
+          file.as_debug_info_scope(),
+          variant,
+          file,
+          0,
+          described.get_size_in_bits(),
+          0,
+          0,
+          DIFlags::PUBLIC,
+          described,
+        );
+        member.as_type()
+      })
+      .collect::<Vec<_>>();
+    let payload_size = layout.size - payload;
+    let union = self.builder.create_union_type(
+      file.as_debug_info_scope(),
+      &format!("{}::payload", name),
+      file,
+      0,
+      payload_size * 8,
+      0,
+      DIFlags::PUBLIC,
+      &variants,
+      0,
+      "",
+    );
+    let tag = self.ty(cx, &Type::Primitive(PrimitiveType::Uint32));
+    self.composite(
+      name,
+      layout.size,
+      layout.align,
+      &[("tag", tag, 0), ("payload", union.as_type(), payload)],
+    )
+  }
+
+  /// Structure of the fields at their offsets in bytes.
+  fn composite(
+    &self,
+    name: &str,
+    size: u64,
+    align: u64,
+    fields: &[(&str, DIType<'ctx>, u64)],
+  ) -> DIType<'ctx> {
+    let file = self.unit.get_file();
+    let scope = file.as_debug_info_scope();
+    let members = fields
+      .iter()
+      .map(|(field, ty, offset)| {
+        self
+          .builder
+          .create_member_type(
+            scope,
+            field,
+            file,
+            0,
+            ty.get_size_in_bits(),
+            0,
+            offset * 8,
+            DIFlags::PUBLIC,
+            *ty,
+          )
+          .as_type()
+      })
+      .collect::<Vec<_>>();
+    self
+      .builder
+      .create_struct_type(
+        scope,
+        name,
+        file,
+        0,
+        size * 8,
+        align as u32 * 8,
+        DIFlags::PUBLIC,
+        None,
+        &members,
+        0,
+        None,
+        name,
+      )
+      .as_type()
+  }
+}
+
+/// Program and items which the types are described by.
+#[derive(Clone, Copy)]
+pub struct TypeCx<'a> {
+  pub program: &'a Program,
+  pub items:   &'a ItemTable,
+}
+
+/// Debug information of a function being generated.
+pub struct FunctionDebugInfo<'ctx> {
+  pub subprogram: DISubprogram<'ctx>,
+  file:           DIFile<'ctx>,
+  /// Subprogram followed by the lexical blocks, indexed by [`SourceScope`].
+  scopes:         Vec<DIScope<'ctx>>,
+}
+
+/// File name and directory of the path.
+fn split_path(path: &Path) -> (String, String) {
+  let filename = path.file_name().map(|name| name.to_string_lossy().into_owned());
+  let directory = match path.parent() {
+    Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+    _ => Path::new(".").to_path_buf(),
+  };
+  let directory = directory.canonicalize().unwrap_or(directory);
+  (
+    filename.unwrap_or_default(),
+    directory.to_string_lossy().into_owned(),
+  )
+}
+
+fn encoding(primitive: &PrimitiveType) -> u32 {
+  match primitive {
+    PrimitiveType::Bool => DW_ATE_BOOLEAN,
+    PrimitiveType::Float64 | PrimitiveType::Double64 => DW_ATE_FLOAT,
+    PrimitiveType::Char => DW_ATE_UTF,
+    primitive if primitive.is_signed_integer() => DW_ATE_SIGNED,
+    _ => DW_ATE_UNSIGNED,
+  }
+}
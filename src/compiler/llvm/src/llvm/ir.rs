@@ -1,49 +1,1163 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+
+use inkwell::basic_block::BasicBlock;
 use inkwell::builder::Builder;
 use inkwell::context::Context;
+use inkwell::intrinsics::Intrinsic;
+use inkwell::module::Linkage;
+use inkwell::module::Module;
+use inkwell::types::BasicTypeEnum;
+use inkwell::values::BasicMetadataValueEnum;
+use inkwell::values::BasicValue;
+use inkwell::values::BasicValueEnum;
+use inkwell::values::CallableValue;
+use inkwell::values::FloatValue;
 use inkwell::values::FunctionValue;
+use inkwell::values::GlobalValue;
 use inkwell::values::IntValue;
+use inkwell::values::PointerValue;
+use inkwell::AddressSpace;
+use inkwell::FloatPredicate;
+use inkwell::IntPredicate;
 use vsp_ast::ast::expr::BinaryOp;
-use vsp_ast::ast::expr::Expression;
+use vsp_ast::ast::expr::UnaryOp;
+use vsp_ast::ast::types::PrimitiveType;
+use vsp_ast::ast::types::Type;
+use vsp_hir::hir::ForeignFunction;
+use vsp_mir::mir::BasicBlock as BasicBlockId;
+use vsp_mir::mir::Body;
+use vsp_mir::mir::Constant;
+use vsp_mir::mir::ConstantKind;
+use vsp_mir::mir::Local;
+use vsp_mir::mir::Operand;
+use vsp_mir::mir::Place;
+use vsp_mir::mir::Program;
+use vsp_mir::mir::ProjectionElem;
+use vsp_mir::mir::Rvalue;
+use vsp_mir::mir::StatementKind;
+use vsp_mir::mir::TerminatorKind;
+use vsp_sema::items::ItemTable;
+use vsp_sema::items::MethodPath;
+use vsp_sema::mono::substitute;
+use vsp_sema::mono::Instance;
+use vsp_sema::mono::MonoItem;
+use vsp_sema::resolve::DefPath;
+use vsp_span::Position;
+use vsp_span::Span;
+
+use crate::llvm::abi::build_entry_alloca;
+use crate::llvm::abi::FnAbi;
+use crate::llvm::debuginfo::DebugInfo;
+use crate::llvm::debuginfo::FunctionDebugInfo;
+use crate::llvm::debuginfo::TypeCx;
+use crate::llvm::layout::enum_variants;
+use crate::llvm::layout::pass_by;
+use crate::llvm::layout::PassBy;
+use crate::llvm::runtime::declare_bounds_panic;
+use crate::llvm::runtime::declare_panic;
+use crate::llvm::vtable::VtableBuilder;
+use crate::types::basic_type;
+use crate::types::fn_type;
+use crate::types::variant_type;
+
+/// Behavior of the integer arithmetic on overflow.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OverflowMode {
+  /// Trap on overflow, which is used by debug builds.
+  Checked,
+  /// Wrap around in two's complement, which is used by release builds.
+  Wrapping,
+}
+
+impl OverflowMode {
+  pub fn from_overflow_checks(overflow_checks: bool) -> Self {
+    if overflow_checks {
+      Self::Checked
+    } else {
+      Self::Wrapping
+    }
+  }
+}
+
+/// Code generator of the instances of a program into the module.
+///
+/// Functions and methods are called by the calling convention of vsp, where the arguments and the
+/// return value are passed as the values of their LLVM types, and `unit` is the empty struct,
+/// except the large aggregates passed by pointer as [`pass_by`] decides.
+/// Functions exported by `extern "C"` are named by their names instead of the paths, and follow the
+/// C calling convention of [`FnAbi`] so that C could call them, as do the calls to them.
+pub struct ProgramCodegen<'a, 'ctx> {
+  context:  &'ctx Context,
+  module:   &'a Module<'ctx>,
+  builder:  &'a Builder<'ctx>,
+  overflow: OverflowMode,
+  program:  &'a Program,
+  items:    &'a ItemTable,
+  /// Debug information of the generated functions, only under `-g`.
+  debug:    Option<&'a DebugInfo<'ctx>>,
+}
+
+impl<'a, 'ctx> ProgramCodegen<'a, 'ctx> {
+  pub fn new(
+    context: &'ctx Context,
+    module: &'a Module<'ctx>,
+    builder: &'a Builder<'ctx>,
+    overflow: OverflowMode,
+    program: &'a Program,
+    items: &'a ItemTable,
+  ) -> Self {
+    Self {
+      context,
+      module,
+      builder,
+      overflow,
+      program,
+      items,
+      debug: None,
+    }
+  }
+
+  /// Describe the generated functions and their locals by the debug information.
+  pub fn set_debug_info(&mut self, debug: &'a DebugInfo<'ctx>) {
+    self.debug = Some(debug);
+  }
+
+  /// Symbol name of the instance, which is its name for the functions exported by `extern "C"`.
+  pub fn symbol_name(&self, instance: &Instance) -> String {
+    match &instance.item {
+      MonoItem::Function(def) if self.items.functions[def].abi.is_c() => def.name.to_owned(),
+      _ => instance.symbol_name(self.items),
+    }
+  }
+
+  /// Declare the function of the instance unless it is already declared.
+  pub fn declare_instance(&self, instance: &Instance) -> FunctionValue<'ctx> {
+    let name = self.symbol_name(instance);
+    if let Some(function) = self.module.get_function(&name) {
+      return function;
+    }
+    if let Some(abi) = self.c_abi(instance) {
+      return abi.declare(self.context, self.module, self.program, &name);
+    }
+    let body = self.body(instance);
+    let substitution = instance.substitution(self.items);
+    let params = body
+      .args()
+      .map(|arg| substitute(&body.local(arg).ty, &substitution))
+      .collect::<Vec<_>>();
+    let ret = substitute(body.return_type(), &substitution);
+    let function_type = fn_type(self.context, self.program, &params, &ret);
+    self.module.add_function(&name, function_type, None)
+  }
+
+  /// Generate the body of the instance into its function, and declare the functions it calls.
+  pub fn define_instance(&self, instance: &Instance) -> FunctionValue<'ctx> {
+    let function = self.declare_instance(instance);
+    BodyCodegen {
+      cx: self,
+      context: self.context,
+      module: self.module,
+      builder: self.builder,
+      overflow: self.overflow,
+      program: self.program,
+      function,
+      body: self.body(instance),
+      substitution: instance.substitution(self.items),
+      abi: self.c_abi(instance),
+      locals: vec![],
+      blocks: vec![],
+      position: Cell::new(Position::start()),
+      debug: None,
+    }
+    .codegen_body(&self.debug_name(instance));
+    function
+  }
+
+  fn body(&self, instance: &Instance) -> &'a Body {
+    self.program.body(&instance.item).expect("the instance has no body")
+  }
+
+  /// C calling convention of the instance, or `None` for the calling convention of vsp.
+  fn c_abi(&self, instance: &Instance) -> Option<FnAbi> {
+    match &instance.item {
+      MonoItem::Function(def) if self.items.functions[def].abi.is_c() => {
+        let body = self.body(instance);
+        let params = body.args().map(|arg| body.local(arg).ty.clone()).collect::<Vec<_>>();
+        Some(FnAbi::new(self.program, &params, body.return_type(), false))
+      }
+      _ => None,
+    }
+  }
+
+  /// Name of the function of the instance in the debug information, such as `main` or
+  /// `Point::norm`.
+  fn debug_name(&self, instance: &Instance) -> String {
+    match &instance.item {
+      MonoItem::Function(def) | MonoItem::Const(def) | MonoItem::Static(def) => def.name.clone(),
+      MonoItem::Coroutine(def) => format!("{}::poll", def.name),
+      MonoItem::Method(path) => match self.items.impl_info(path.impl_id).self_ty.to_type() {
+        Some(ty) => format!("{}::{}", ty, path.name),
+        None => path.name.clone(),
+      },
+    }
+  }
+
+  /// Instance of the callee, where the type arguments and the methods of the trait bounds are
+  /// resolved by the substitution of the caller.
+  fn resolve(&self, callee: &ConstantKind, substitution: &HashMap<String, Type>) -> Instance {
+    match callee {
+      ConstantKind::Function(def, args) => Instance {
+        item: MonoItem::Function(def.clone()),
+        args: args.iter().map(|arg| substitute(arg, substitution)).collect(),
+      },
+      ConstantKind::Method(path) => Instance {
+        item: MonoItem::Method(path.clone()),
+        args: vec![],
+      },
+      ConstantKind::BoundMethod {
+        trait_,
+        name,
+        self_ty,
+      } => match substitute(self_ty, substitution) {
+        Type::Coroutine(path, args) => Instance {
+          item: MonoItem::Coroutine(DefPath::new(
+            path.parent().unwrap_or_default(),
+            path.name().unwrap_or_default(),
+          )),
+          args,
+        },
+        self_ty => self.method_instance(trait_, &self_ty, name),
+      },
+      kind => unreachable!("unexpected callee: {:?}", kind),
+    }
+  }
+
+  /// Instance of the method of the implementation of the trait for the type.
+  fn method_instance(&self, trait_: &DefPath, self_ty: &Type, name: &str) -> Instance {
+    let implementation =
+      self.items.impls.iter().find(|i| {
+        i.trait_.as_ref() == Some(trait_) && i.self_ty.to_type().as_ref() == Some(self_ty)
+      });
+    let implementation = implementation.expect("bounds are checked by the type checker");
+    Instance {
+      item: MonoItem::Method(MethodPath {
+        impl_id: implementation.id,
+        name:    name.to_owned(),
+      }),
+      args: vec![],
+    }
+  }
+
+  /// Vtable of the implementation of the trait for the type, which is emitted once in the module.
+  fn vtable(&self, trait_: &DefPath, self_ty: &Type) -> GlobalValue<'ctx> {
+    let name = format!("vtable.{}.{}", trait_, self_ty);
+    if let Some(vtable) = self.module.get_global(&name) {
+      return vtable;
+    }
+    let methods = self.items.traits[trait_]
+      .methods
+      .iter()
+      .map(|method| self.declare_instance(&self.method_instance(trait_, self_ty, &method.name)))
+      .collect::<Vec<_>>();
+    let self_type = basic_type(self.context, self.program, self_ty);
+    VtableBuilder::new(self.context, self.module).build_vtable(&name, self_type, &methods)
+  }
+}
 
-#[allow(dead_code, non_snake_case)]
-struct IRBuilder<'ctx> {
-  context: &'ctx Context,
-  builder: &'ctx Builder<'ctx>,
+/// Code generator of a MIR body into the LLVM function.
+///
+/// Every local is stored in a stack slot allocated in the entry block, which `mem2reg` promotes
+/// into registers, and each basic block of the body is generated into its own LLVM block. Types of
+/// the operands are resolved by the type checking, so the integer constants get their actual width,
+/// and the signedness of the operands decides the instructions to use, such as `sdiv` or `udiv`.
+/// Generic parameters in the types are substituted by the type arguments of the instance.
+struct BodyCodegen<'a, 'ctx> {
+  cx:           &'a ProgramCodegen<'a, 'ctx>,
+  context:      &'ctx Context,
+  module:       &'a Module<'ctx>,
+  builder:      &'a Builder<'ctx>,
+  overflow:     OverflowMode,
+  program:      &'a Program,
+  function:     FunctionValue<'ctx>,
+  body:         &'a Body,
+  substitution: HashMap<String, Type>,
+  /// C calling convention of the function exported by `extern "C"`.
+  abi:          Option<FnAbi>,
+  locals:       Vec<PointerValue<'ctx>>,
+  blocks:       Vec<BasicBlock<'ctx>>,
+  /// Start of the statement or the terminator being generated, which locates the failed checks.
+  position:     Cell<Position>,
+  /// Debug information of the function, only under `-g`.
+  debug:        Option<(&'a DebugInfo<'ctx>, FunctionDebugInfo<'ctx>)>,
 }
 
-unsafe fn codegen_expr<'ctx>(
-  context: &'ctx Context,
-  builder: &'ctx Builder,
-  func: FunctionValue,
-  expr: &Expression,
-) -> IntValue<'ctx> {
-  match expr {
-    Expression::Unit => {}
-    Expression::LiteralInteger(int) => {
-      let int_type = context.i32_type();
-      return int_type.const_int(*int as u64, *int > 0);
-    }
-    Expression::LiteralFloat(_) => {}
-    Expression::LiteralBoolean(_) => {}
-    Expression::LiteralString(_) => {}
-    Expression::Identifier(_) => {}
-    Expression::Unary(_, _) => {}
-    Expression::Binary(op, lhs, rhs) => {
-      let lhs = codegen_expr(context, builder, func, lhs.as_ref());
-      let rhs = codegen_expr(context, builder, func, rhs.as_ref());
+impl<'a, 'ctx> BodyCodegen<'a, 'ctx> {
+  /// Generate the body into the function, which must have no blocks yet. The function is described
+  /// by the name in the debug information, if any.
+  fn codegen_body(&mut self, name: &str) {
+    let start = self.context.append_basic_block(self.function, "start");
+    self.blocks = (0..self.body.blocks.len())
+      .map(|index| {
+        let name = BasicBlockId(index).to_string();
+        self.context.append_basic_block(self.function, &name)
+      })
+      .collect();
+
+    self.builder.position_at_end(start);
+    let cx = TypeCx {
+      program: self.program,
+      items:   self.cx.items,
+    };
+    let mut types = vec![];
+    if let Some(debug) = self.cx.debug {
+      types = self.body.locals.iter().map(|decl| self.ty(&decl.ty)).collect();
+      let function = debug.describe_function(cx, self.function, name, self.body, &types);
+      self.debug = Some((debug, function));
+      self.set_location(&self.body.span);
+    }
+    // Arguments and the return value passed by pointer are located in the memory they point to.
+    let mut incoming = HashMap::new();
+    if self.abi.is_none() {
+      let mut params = self.function.get_param_iter();
+      if self.returns_by_pointer() {
+        incoming.insert(Body::RETURN_PLACE, params.next().unwrap());
+      }
+      for (param, arg) in params.zip(self.body.args()) {
+        if self.pass_by(&self.body.local(arg).ty) == PassBy::Pointer {
+          incoming.insert(arg, param);
+        }
+      }
+    }
+    self.locals = self
+      .body
+      .locals
+      .iter()
+      .enumerate()
+      .map(|(index, decl)| match incoming.get(&Local(index)) {
+        Some(param) => param.into_pointer_value(),
+        None => {
+          let name = Local(index).to_string();
+          self.builder.build_alloca(self.basic_type(&decl.ty), &name)
+        }
+      })
+      .collect();
+    if let Some((debug, function)) = &self.debug {
+      debug.declare_locals(cx, function, self.body, &types, &self.locals, start);
+    }
+    match &self.abi {
+      Some(abi) => {
+        let slots = self.body.args().map(|arg| self.locals[arg.0]).collect::<Vec<_>>();
+        abi.build_prologue(
+          self.context,
+          self.builder,
+          self.program,
+          self.function,
+          &slots,
+        );
+      }
+      None => {
+        let skip = incoming.contains_key(&Body::RETURN_PLACE) as usize;
+        for (param, arg) in self.function.get_param_iter().skip(skip).zip(self.body.args()) {
+          if !incoming.contains_key(&arg) {
+            self.builder.build_store(self.locals[arg.0], param);
+          }
+        }
+      }
+    }
+    self.builder.build_unconditional_branch(self.blocks[0]);
+
+    for (index, block) in self.body.blocks.iter().enumerate() {
+      self.builder.position_at_end(self.blocks[index]);
+      for statement in &block.statements {
+        self.set_location(&statement.span);
+        match &statement.kind {
+          StatementKind::Assign(place, rvalue) => {
+            let value = self.codegen_rvalue(rvalue);
+            let pointer = self.codegen_place(place);
+            self.builder.build_store(pointer, value);
+          }
+          StatementKind::StorageLive(_) => {}
+        }
+      }
+      self.set_location(&block.terminator().span);
+      self.codegen_terminator(&block.terminator().kind);
+    }
+    self.builder.unset_current_debug_location();
+  }
+
+  /// Locate the instructions generated next at the span, for the messages of the failed checks
+  /// and in the debug information, if any.
+  fn set_location(&self, span: &Span) {
+    self.position.set(span.start);
+    if let Some((debug, function)) = &self.debug {
+      let location = debug.location(function, self.body, span);
+      self.builder.set_current_debug_location(location);
+    }
+  }
+
+  /// Type in the body with the generic parameters substituted.
+  fn ty(&self, ty: &Type) -> Type {
+    substitute(ty, &self.substitution)
+  }
+
+  fn basic_type(&self, ty: &Type) -> BasicTypeEnum<'ctx> {
+    basic_type(self.context, self.program, &self.ty(ty))
+  }
+
+  /// How the value of the type in the body is passed by the calling convention of vsp.
+  fn pass_by(&self, ty: &Type) -> PassBy {
+    pass_by(self.program, &self.ty(ty))
+  }
+
+  fn returns_by_pointer(&self) -> bool {
+    self.abi.is_none() && self.pass_by(self.body.return_type()) == PassBy::Pointer
+  }
+
+  fn codegen_terminator(&self, kind: &TerminatorKind) {
+    match kind {
+      TerminatorKind::Goto(target) => {
+        self.builder.build_unconditional_branch(self.blocks[target.0]);
+      }
+      TerminatorKind::If {
+        condition,
+        then,
+        otherwise,
+      } => {
+        let condition = self.codegen_operand(condition).into_int_value();
+        self.builder.build_conditional_branch(
+          condition,
+          self.blocks[then.0],
+          self.blocks[otherwise.0],
+        );
+      }
+      // The return value passed by pointer is already in the memory of the caller.
+      TerminatorKind::Return if self.returns_by_pointer() => {
+        self.builder.build_return(None);
+      }
+      TerminatorKind::Return => {
+        let value = self.builder.build_load(self.locals[Body::RETURN_PLACE.0], "ret");
+        match &self.abi {
+          Some(abi) => abi.build_return(
+            self.context,
+            self.builder,
+            self.program,
+            self.function,
+            value,
+          ),
+          None => {
+            self.builder.build_return(Some(&value));
+          }
+        }
+      }
+      TerminatorKind::Unreachable => {
+        self.builder.build_unreachable();
+      }
+      TerminatorKind::Yield { .. } => unreachable!("`yield` is removed from `async func`"),
+      TerminatorKind::Call {
+        func,
+        args,
+        destination,
+        target,
+      } => {
+        let value = match func {
+          Operand::Constant(Constant {
+            kind: ConstantKind::Function(def, _),
+            ..
+          }) if self.program.foreign_function(def).is_some() => {
+            let function = self.program.foreign_function(def).unwrap();
+            self.codegen_foreign_call(function, args)
+          }
+          Operand::Constant(constant) => {
+            let instance = self.cx.resolve(&constant.kind, &self.substitution);
+            self.codegen_instance_call(&instance, args, destination.ty(self.body))
+          }
+          func => self.codegen_indirect_call(func, args, destination.ty(self.body)),
+        };
+        self.builder.build_store(self.codegen_place(destination), value);
+        self.builder.build_unconditional_branch(self.blocks[target.0]);
+      }
+      TerminatorKind::DynCall {
+        object,
+        index,
+        args,
+        destination,
+        target,
+        ..
+      } => {
+        let value = self.codegen_dyn_call(object, *index, args, destination.ty(self.body));
+        self.builder.build_store(self.codegen_place(destination), value);
+        self.builder.build_unconditional_branch(self.blocks[target.0]);
+      }
+    }
+  }
+
+  /// Call the foreign function by the C calling convention, with the symbol of its name.
+  fn codegen_foreign_call(
+    &self,
+    function: &ForeignFunction,
+    args: &[Operand],
+  ) -> BasicValueEnum<'ctx> {
+    let (abi, callee) = self.declare_foreign(function);
+    self.codegen_c_call(&abi, callee, args)
+  }
+
+  fn declare_foreign(&self, function: &ForeignFunction) -> (FnAbi, FunctionValue<'ctx>) {
+    let abi = FnAbi::new(
+      self.program,
+      &function.params,
+      &function.ret,
+      function.variadic,
+    );
+    let callee = abi.declare(self.context, self.module, self.program, function.symbol());
+    (abi, callee)
+  }
+
+  fn codegen_c_call(
+    &self,
+    abi: &FnAbi,
+    callee: FunctionValue<'ctx>,
+    args: &[Operand],
+  ) -> BasicValueEnum<'ctx> {
+    let args = args
+      .iter()
+      .map(|arg| (self.codegen_operand(arg), self.ty(arg.ty(self.body))))
+      .collect::<Vec<_>>();
+    let args = args.iter().map(|(value, ty)| (*value, ty)).collect::<Vec<_>>();
+    abi.build_call(self.context, self.builder, self.program, callee, &args)
+  }
+
+  /// Call the function or the method of the instance, declaring it if it is not generated yet.
+  fn codegen_instance_call(
+    &self,
+    instance: &Instance,
+    args: &[Operand],
+    ret: &Type,
+  ) -> BasicValueEnum<'ctx> {
+    let callee = self.cx.declare_instance(instance);
+    if let Some(abi) = self.cx.c_abi(instance) {
+      return self.codegen_c_call(&abi, callee, args);
+    }
+    self.codegen_vsp_call(callee.into(), args, ret)
+  }
+
+  /// Call the function pointer, which follows the calling convention of vsp.
+  fn codegen_indirect_call(
+    &self,
+    func: &Operand,
+    args: &[Operand],
+    ret: &Type,
+  ) -> BasicValueEnum<'ctx> {
+    let pointer = self.codegen_operand(func).into_pointer_value();
+    let callee = CallableValue::try_from(pointer).unwrap();
+    self.codegen_vsp_call(callee, args, ret)
+  }
+
+  /// Call by the calling convention of vsp, where the arguments passed by pointer are copied into
+  /// the stack slots of the caller, and the return value passed by pointer is written into one.
+  fn codegen_vsp_call(
+    &self,
+    callee: CallableValue<'ctx>,
+    args: &[Operand],
+    ret: &Type,
+  ) -> BasicValueEnum<'ctx> {
+    let (values, ret_slot) = self.codegen_vsp_args(args, ret);
+    let call = self.builder.build_call(callee, &values, "call");
+    self.vsp_return(call.try_as_basic_value().left(), ret_slot)
+  }
+
+  /// Call the method through the vtable of the trait object by the calling convention of vsp,
+  /// where the receiver is the erased pointer to the value.
+  fn codegen_dyn_call(
+    &self,
+    object: &Operand,
+    index: usize,
+    args: &[Operand],
+    ret: &Type,
+  ) -> BasicValueEnum<'ctx> {
+    let object = self.codegen_operand(object).into_struct_value();
+    let (values, ret_slot) = self.codegen_vsp_args(args, ret);
+    // The receiver is replaced by the erased pointer, so any pointer type would do.
+    let mut params = vec![Type::Pointer(Box::new(Type::Primitive(
+      PrimitiveType::Uint8,
+    )))];
+    params.extend(args.iter().map(|arg| self.ty(arg.ty(self.body))));
+    let method = fn_type(self.context, self.program, &params, &self.ty(ret));
+    let vtables = VtableBuilder::new(self.context, self.module);
+    let value = vtables.build_dynamic_call(self.builder, object, index as u32, method, &values);
+    self.vsp_return(value, ret_slot)
+  }
+
+  /// Arguments of the call by the calling convention of vsp, with the stack slot of the return
+  /// value passed by pointer, if any.
+  fn codegen_vsp_args(
+    &self,
+    args: &[Operand],
+    ret: &Type,
+  ) -> (
+    Vec<BasicMetadataValueEnum<'ctx>>,
+    Option<PointerValue<'ctx>>,
+  ) {
+    let mut values = Vec::<BasicMetadataValueEnum>::new();
+    let ret_slot = match self.pass_by(ret) {
+      PassBy::Pointer => {
+        let slot = build_entry_alloca(self.context, self.builder, self.basic_type(ret), "sret");
+        values.push(slot.into());
+        Some(slot)
+      }
+      PassBy::Value => None,
+    };
+    for arg in args {
+      let value = self.codegen_operand(arg);
+      values.push(match self.pass_by(arg.ty(self.body)) {
+        PassBy::Value => value.into(),
+        PassBy::Pointer => {
+          let slot = build_entry_alloca(self.context, self.builder, value.get_type(), "arg");
+          self.builder.build_store(slot, value);
+          slot.into()
+        }
+      });
+    }
+    (values, ret_slot)
+  }
+
+  /// Value returned by the call, which is in the stack slot if it is passed by pointer.
+  fn vsp_return(
+    &self,
+    value: Option<BasicValueEnum<'ctx>>,
+    ret_slot: Option<PointerValue<'ctx>>,
+  ) -> BasicValueEnum<'ctx> {
+    match ret_slot {
+      Some(slot) => self.builder.build_load(slot, "ret"),
+      None => value.unwrap(),
+    }
+  }
+
+  /// Pointer to the place, which is the stack slot of the local unless a raw pointer is
+  /// dereferenced.
+  fn codegen_place(&self, place: &Place) -> PointerValue<'ctx> {
+    place.projection.iter().fold(
+      self.locals[place.local.0],
+      |pointer, (elem, _)| match elem {
+        ProjectionElem::Field(index) => {
+          self.builder.build_struct_gep(pointer, *index as u32, "field").unwrap()
+        }
+        ProjectionElem::Deref => self.builder.build_load(pointer, "deref").into_pointer_value(),
+        ProjectionElem::Index(index) => {
+          let index = self.builder.build_load(self.locals[index.0], "index").into_int_value();
+          let length = pointer.get_type().get_element_type().into_array_type().len();
+          self.build_bounds_check(index, length);
+          let zero = self.context.i64_type().const_zero();
+          unsafe { self.builder.build_in_bounds_gep(pointer, &[zero, index], "element") }
+        }
+      },
+    )
+  }
+
+  fn codegen_operand(&self, operand: &Operand) -> BasicValueEnum<'ctx> {
+    match operand {
+      Operand::Copy(place) => self.builder.build_load(self.codegen_place(place), "copy"),
+      Operand::Constant(constant) => self.codegen_constant(constant),
+    }
+  }
+
+  fn codegen_constant(&self, constant: &Constant) -> BasicValueEnum<'ctx> {
+    match &constant.kind {
+      ConstantKind::Unit => self.context.const_struct(&[], false).into(),
+      ConstantKind::Integer(value) => {
+        let signed = matches!(&constant.ty, Type::Primitive(p) if p.is_signed_integer());
+        let int_type = self.basic_type(&constant.ty).into_int_type();
+        int_type.const_int(*value as u64, signed).into()
+      }
+      ConstantKind::Float(value) => self.context.f64_type().const_float(*value).into(),
+      ConstantKind::Bool(value) => self.context.bool_type().const_int(*value as u64, false).into(),
+      ConstantKind::Str(value) => {
+        self.builder.build_global_string_ptr(value, "str").as_pointer_value().into()
+      }
+      ConstantKind::Static(def) => {
+        let global = self.module.get_global(&def.to_string()).expect("undefined static item");
+        self.builder.build_load(global.as_pointer_value(), "static")
+      }
+      ConstantKind::Const(_) => unreachable!("constants are replaced by their values"),
+      kind => {
+        let function = match kind {
+          ConstantKind::Function(def, _) if self.program.foreign_function(def).is_some() => {
+            self.declare_foreign(self.program.foreign_function(def).unwrap()).1
+          }
+          kind => self.cx.declare_instance(&self.cx.resolve(kind, &self.substitution)),
+        };
+        // Functions of the C calling convention are called as the functions of vsp by the
+        // pointers, which agree on the scalar arguments.
+        let pointer = self.basic_type(&constant.ty).into_pointer_type();
+        let function = function.as_global_value().as_pointer_value();
+        self.builder.build_pointer_cast(function, pointer, "function").into()
+      }
+    }
+  }
+
+  fn codegen_rvalue(&self, rvalue: &Rvalue) -> BasicValueEnum<'ctx> {
+    match rvalue {
+      Rvalue::Use(operand) => self.codegen_operand(operand),
+      Rvalue::Unary(op, operand) => self.codegen_unary(op, operand),
+      Rvalue::Binary(op, left, right) => self.codegen_binary(op, left, right),
+      Rvalue::AddressOf(place) => self.codegen_place(place).into(),
+      Rvalue::Cast(operand, target) => {
+        let value = self.codegen_operand(operand);
+        self.codegen_conversion(value, &self.ty(operand.ty(self.body)), &self.ty(target))
+      }
+      Rvalue::Struct(ty, fields) => {
+        let struct_type = self.basic_type(ty).into_struct_type();
+        let value = fields.iter().fold(struct_type.get_undef(), |value, (index, field)| {
+          let field = self.codegen_operand(field);
+          let value = self.builder.build_insert_value(value, field, *index as u32, "field");
+          value.unwrap().into_struct_value()
+        });
+        value.into()
+      }
+      Rvalue::Variant(ty, index, fields) => self.codegen_variant(ty, *index, fields),
+      Rvalue::Array(ty, elements) => {
+        let array_type = self.basic_type(ty).into_array_type();
+        let value =
+          elements
+            .iter()
+            .enumerate()
+            .fold(array_type.get_undef(), |value, (index, element)| {
+              let element = self.codegen_operand(element);
+              let value = self.builder.build_insert_value(value, element, index as u32, "element");
+              value.unwrap().into_array_value()
+            });
+        value.into()
+      }
+      Rvalue::Discriminant(place) => {
+        let tag = self.builder.build_struct_gep(self.codegen_place(place), 0, "tag").unwrap();
+        self.builder.build_load(tag, "discriminant")
+      }
+      Rvalue::VariantField(place, variant, index) => {
+        let ty = self.ty(place.ty(self.body));
+        let fields = self.variant_pointer(self.codegen_place(place), &ty, *variant);
+        let field = self.builder.build_struct_gep(fields, *index as u32, "field").unwrap();
+        self.builder.build_load(field, "field")
+      }
+      Rvalue::ToDyn(operand, ty) => self.codegen_to_dyn(operand, &self.ty(ty)),
+    }
+  }
+
+  /// Trait object of the value, which is moved into the memory allocated by `malloc`, as trait
+  /// objects are copied freely and might outlive the function. The memory is never freed.
+  fn codegen_to_dyn(&self, operand: &Operand, ty: &Type) -> BasicValueEnum<'ctx> {
+    let trait_ = match ty {
+      Type::Dyn(path) => DefPath::new(
+        path.parent().unwrap_or_default(),
+        path.name().unwrap_or_default(),
+      ),
+      ty => unreachable!("type `{}` is not a trait object", ty),
+    };
+    let value = self.codegen_operand(operand);
+    let data = self.builder.build_malloc(value.get_type(), "data").unwrap();
+    self.builder.build_store(data, value);
+    let vtable = self.cx.vtable(&trait_, &self.ty(operand.ty(self.body)));
+    let vtables = VtableBuilder::new(self.context, self.module);
+    vtables.build_trait_object(self.builder, data, vtable).into()
+  }
+
+  /// Enum value of the variant, which is built in a stack slot, since the fields are stored
+  /// through the payload. Bytes of the payload after the fields are left undefined.
+  fn codegen_variant(&self, ty: &Type, index: usize, fields: &[Operand]) -> BasicValueEnum<'ctx> {
+    let ty = self.ty(ty);
+    let slot = build_entry_alloca(self.context, self.builder, self.basic_type(&ty), "enum");
+    let tag = self.builder.build_struct_gep(slot, 0, "tag").unwrap();
+    let discriminant = self.context.i32_type().const_int(index as u64, false);
+    self.builder.build_store(tag, discriminant);
+    let pointer = self.variant_pointer(slot, &ty, index);
+    for (index, field) in fields.iter().enumerate() {
+      let value = self.codegen_operand(field);
+      let field = self.builder.build_struct_gep(pointer, index as u32, "field").unwrap();
+      self.builder.build_store(field, value);
+    }
+    self.builder.build_load(slot, "enum")
+  }
 
+  /// Pointer to the fields of the variant, which is the payload of the enum value cast to the
+  /// pointer to the struct of the fields.
+  fn variant_pointer(
+    &self,
+    pointer: PointerValue<'ctx>,
+    ty: &Type,
+    index: usize,
+  ) -> PointerValue<'ctx> {
+    let fields = enum_variants(self.program, ty).swap_remove(index);
+    let variant = variant_type(self.context, self.program, &fields);
+    let payload = self.builder.build_struct_gep(pointer, 1, "payload").unwrap();
+    let pointer_type = variant.ptr_type(AddressSpace::default());
+    self.builder.build_pointer_cast(payload, pointer_type, "variant")
+  }
+
+  fn codegen_unary(&self, op: &UnaryOp, operand: &Operand) -> BasicValueEnum<'ctx> {
+    let value = self.codegen_operand(operand);
+    match op {
+      UnaryOp::Not => self.builder.build_not(value.into_int_value(), "not").into(),
+      UnaryOp::Negative if value.is_float_value() => {
+        self.builder.build_float_neg(value.into_float_value(), "neg").into()
+      }
+      UnaryOp::Negative => {
+        let value = value.into_int_value();
+        let zero = value.get_type().const_zero();
+        self.build_int_arith(&BinaryOp::Subtract, zero, value, true, "negate").into()
+      }
+      UnaryOp::Dereference | UnaryOp::AddressOf => {
+        unreachable!("`{}` is lowered into the place", op.as_str())
+      }
+    }
+  }
+
+  /// Binary operation, where the short-circuit logical operations are already lowered into the
+  /// control flow.
+  fn codegen_binary(&self, op: &BinaryOp, left: &Operand, right: &Operand) -> BasicValueEnum<'ctx> {
+    // The operands have the same type after the implicit widening.
+    let signed = matches!(self.ty(left.ty(self.body)), Type::Primitive(p) if p.is_signed_integer());
+    let lhs = self.codegen_operand(left);
+    let rhs = self.codegen_operand(right);
+    if lhs.is_pointer_value() {
+      return self.build_pointer_binary(op, lhs.into_pointer_value(), rhs);
+    }
+    if lhs.is_float_value() {
+      return self.build_float_binary(op, lhs.into_float_value(), rhs.into_float_value());
+    }
+    let (lhs, rhs) = (lhs.into_int_value(), rhs.into_int_value());
+    match int_predicate(op, signed) {
+      Some(predicate) => self.builder.build_int_compare(predicate, lhs, rhs, "cmp").into(),
+      None => {
+        let verb = match op {
+          BinaryOp::Add => "add",
+          BinaryOp::Subtract => "subtract",
+          _ => "multiply",
+        };
+        self.build_int_arith(op, lhs, rhs, signed, verb).into()
+      }
+    }
+  }
+
+  /// Pointer offset by `int64`, difference of two pointers in elements, or comparison of the
+  /// addresses. Offsets are not checked to stay within the object.
+  fn build_pointer_binary(
+    &self,
+    op: &BinaryOp,
+    lhs: PointerValue<'ctx>,
+    rhs: BasicValueEnum<'ctx>,
+  ) -> BasicValueEnum<'ctx> {
+    if let BasicValueEnum::PointerValue(rhs) = rhs {
+      if let BinaryOp::Subtract = op {
+        return self.builder.build_ptr_diff(lhs, rhs, "diff").into();
+      }
+      let address_type = self.context.i64_type();
+      let lhs = self.builder.build_ptr_to_int(lhs, address_type, "address");
+      let rhs = self.builder.build_ptr_to_int(rhs, address_type, "address");
+      let predicate = int_predicate(op, false).expect("invalid pointer operator");
+      return self.builder.build_int_compare(predicate, lhs, rhs, "cmp").into();
+    }
+    let offset = match op {
+      BinaryOp::Subtract => self.builder.build_int_neg(rhs.into_int_value(), "neg"),
+      _ => rhs.into_int_value(),
+    };
+    unsafe { self.builder.build_in_bounds_gep(lhs, &[offset], "offset") }.into()
+  }
+
+  fn build_float_binary(
+    &self,
+    op: &BinaryOp,
+    lhs: FloatValue<'ctx>,
+    rhs: FloatValue<'ctx>,
+  ) -> BasicValueEnum<'ctx> {
+    let predicate = match op {
+      BinaryOp::Add => return self.builder.build_float_add(lhs, rhs, "add").into(),
+      BinaryOp::Subtract => return self.builder.build_float_sub(lhs, rhs, "sub").into(),
+      BinaryOp::Multiply => return self.builder.build_float_mul(lhs, rhs, "mul").into(),
+      BinaryOp::Division => return self.builder.build_float_div(lhs, rhs, "div").into(),
+      BinaryOp::Remainder => return self.builder.build_float_rem(lhs, rhs, "rem").into(),
+      BinaryOp::Equal => FloatPredicate::OEQ,
+      BinaryOp::NotEqual => FloatPredicate::UNE,
+      BinaryOp::Less => FloatPredicate::OLT,
+      BinaryOp::LessEqual => FloatPredicate::OLE,
+      BinaryOp::Greater => FloatPredicate::OGT,
+      BinaryOp::GreaterEqual => FloatPredicate::OGE,
+      _ => unreachable!("invalid float operator `{}`", op.as_str()),
+    };
+    self.builder.build_float_compare(predicate, lhs, rhs, "cmp").into()
+  }
+
+  /// Integer arithmetic. Division by zero always traps, while the overflow traps or wraps around
+  /// according to the overflow mode. The overflow is reported as the attempt to do the verb.
+  fn build_int_arith(
+    &self,
+    op: &BinaryOp,
+    lhs: IntValue<'ctx>,
+    rhs: IntValue<'ctx>,
+    signed: bool,
+    verb: &str,
+  ) -> IntValue<'ctx> {
+    let intrinsic = match (op, signed) {
+      (BinaryOp::Add, true) => "llvm.sadd.with.overflow",
+      (BinaryOp::Add, false) => "llvm.uadd.with.overflow",
+      (BinaryOp::Subtract, true) => "llvm.ssub.with.overflow",
+      (BinaryOp::Subtract, false) => "llvm.usub.with.overflow",
+      (BinaryOp::Multiply, true) => "llvm.smul.with.overflow",
+      (BinaryOp::Multiply, false) => "llvm.umul.with.overflow",
+      (BinaryOp::Division | BinaryOp::Remainder, _) => {
+        return self.build_int_division(op, lhs, rhs, signed)
+      }
+      _ => unreachable!("invalid integer operator `{}`", op.as_str()),
+    };
+    if self.overflow == OverflowMode::Wrapping {
+      return match op {
+        BinaryOp::Add => self.builder.build_int_add(lhs, rhs, "add"),
+        BinaryOp::Subtract => self.builder.build_int_sub(lhs, rhs, "sub"),
+        _ => self.builder.build_int_mul(lhs, rhs, "mul"),
+      };
+    }
+    let function = Intrinsic::find(intrinsic)
+      .and_then(|i| i.get_declaration(self.module, &[lhs.get_type().into()]))
+      .unwrap();
+    let result = self
+      .builder
+      .build_call(function, &[lhs.into(), rhs.into()], "checked")
+      .try_as_basic_value()
+      .left()
+      .unwrap()
+      .into_struct_value();
+    let overflow = self.builder.build_extract_value(result, 1, "overflow").unwrap();
+    let message = format!("attempt to {} with overflow", verb);
+    self.build_trap_if(overflow.into_int_value(), "overflow", &message);
+    self.builder.build_extract_value(result, 0, "value").unwrap().into_int_value()
+  }
+
+  fn build_int_division(
+    &self,
+    op: &BinaryOp,
+    lhs: IntValue<'ctx>,
+    rhs: IntValue<'ctx>,
+    signed: bool,
+  ) -> IntValue<'ctx> {
+    let ty = lhs.get_type();
+    let is_zero = self
+      .builder
+      .build_int_compare(IntPredicate::EQ, rhs, ty.const_zero(), "is_zero");
+    let message = match op {
+      BinaryOp::Division => "attempt to divide by zero",
+      _ => "attempt to calculate the remainder with a divisor of zero",
+    };
+    self.build_trap_if(is_zero, "div_zero", message);
+
+    if !signed {
+      return match op {
+        BinaryOp::Division => self.builder.build_int_unsigned_div(lhs, rhs, "div"),
+        _ => self.builder.build_int_unsigned_rem(lhs, rhs, "rem"),
+      };
+    }
+
+    // `MIN / -1` overflows, which is undefined behavior of `sdiv` and `srem` in LLVM.
+    let minus_one = ty.const_all_ones();
+    let is_minus_one =
+      self.builder.build_int_compare(IntPredicate::EQ, rhs, minus_one, "is_minus_one");
+    if self.overflow == OverflowMode::Checked {
+      let min = ty
+        .const_int(1, false)
+        .const_shl(ty.const_int(ty.get_bit_width() as u64 - 1, false));
+      let is_min = self.builder.build_int_compare(IntPredicate::EQ, lhs, min, "is_min");
+      let overflow = self.builder.build_and(is_min, is_minus_one, "overflow");
+      let message = match op {
+        BinaryOp::Division => "attempt to divide with overflow",
+        _ => "attempt to calculate the remainder with overflow",
+      };
+      self.build_trap_if(overflow, "overflow", message);
       return match op {
-        BinaryOp::Add => builder.build_int_add(lhs, rhs, "addtmp"),
-        BinaryOp::Subtract => builder.build_int_sub(lhs, rhs, "addtmp"),
-        BinaryOp::Multiply => builder.build_int_mul(lhs, rhs, "addtmp"),
-        BinaryOp::Division => builder.build_int_unsigned_div(lhs, rhs, "addtmp"),
-        _ => unreachable!(),
+        BinaryOp::Division => self.builder.build_int_signed_div(lhs, rhs, "div"),
+        _ => self.builder.build_int_signed_rem(lhs, rhs, "rem"),
       };
     }
-    Expression::MethodCall(_, _) => {}
-    Expression::LambdaExpression(_, _) => {}
+    // Wrapping: `x / -1` is `-x` and `x % -1` is `0`, and the divisor is replaced with `1` to
+    // avoid the undefined behavior.
+    let one = ty.const_int(1, false);
+    let divisor = self.builder.build_select(is_minus_one, one, rhs, "divisor").into_int_value();
+    let (value, special) = match op {
+      BinaryOp::Division => (
+        self.builder.build_int_signed_div(lhs, divisor, "div"),
+        self.builder.build_int_neg(lhs, "neg"),
+      ),
+      _ => (
+        self.builder.build_int_signed_rem(lhs, divisor, "rem"),
+        ty.const_zero(),
+      ),
+    };
+    self
+      .builder
+      .build_select(is_minus_one, special, value, "wrapping")
+      .into_int_value()
+  }
+
+  /// Branch to a block which panics with the message if the condition holds, and continue at a new
+  /// block otherwise. The message is located at the statement being generated.
+  fn build_trap_if(&self, condition: IntValue<'ctx>, name: &str, message: &str) {
+    self.build_panic_if(condition, name, || {
+      let message = format!("{}: {}", self.location(), message);
+      let message = self.builder.build_global_string_ptr(&message, "panic.message");
+      let panic = declare_panic(self.module);
+      self.builder.build_call(panic, &[message.as_pointer_value().into()], "");
+    });
+  }
+
+  /// Panic unless the `int64` index is within the length of the array, where the negative indices
+  /// are out of bounds as unsigned integers.
+  fn build_bounds_check(&self, index: IntValue<'ctx>, length: u32) {
+    let length = self.context.i64_type().const_int(length as u64, false);
+    let out_of_bounds =
+      self
+        .builder
+        .build_int_compare(IntPredicate::UGE, index, length, "out_of_bounds");
+    self.build_panic_if(out_of_bounds, "bounds", || {
+      let location = self.builder.build_global_string_ptr(&self.location(), "panic.location");
+      let panic = declare_bounds_panic(self.module);
+      let args = [
+        location.as_pointer_value().into(),
+        length.into(),
+        index.into(),
+      ];
+      self.builder.build_call(panic, &args, "");
+    });
+  }
+
+  /// Branch to a block which calls the panic built by the closure if the condition holds, and
+  /// continue at a new block otherwise.
+  fn build_panic_if(&self, condition: IntValue<'ctx>, name: &str, panic: impl FnOnce()) {
+    let trap_block = self.context.append_basic_block(self.function, &format!("{}.trap", name));
+    let cont_block = self.context.append_basic_block(self.function, &format!("{}.cont", name));
+    self.builder.build_conditional_branch(condition, trap_block, cont_block);
+
+    self.builder.position_at_end(trap_block);
+    panic();
+    self.builder.build_unreachable();
+
+    self.builder.position_at_end(cont_block);
+  }
+
+  /// Location of the statement being generated, by the file, the line and the column.
+  fn location(&self) -> String {
+    let position = self.position.get();
+    match &self.body.file {
+      Some(file) => format!("{}:{}:{}", file, position.line, position.column),
+      None => format!("{}:{}", position.line, position.column),
+    }
+  }
+
+  /// Conversion between primitive types, used by both `as` casts and implicit widening, and
+  /// between raw pointers and addresses.
+  fn codegen_conversion(
+    &self,
+    value: BasicValueEnum<'ctx>,
+    from: &Type,
+    to: &Type,
+  ) -> BasicValueEnum<'ctx> {
+    match (value, to) {
+      (BasicValueEnum::PointerValue(pointer), Type::Pointer(_)) => {
+        let target = self.basic_type(to).into_pointer_type();
+        return self.builder.build_pointer_cast(pointer, target, "cast").into();
+      }
+      (BasicValueEnum::PointerValue(pointer), _) if matches!(from, Type::Pointer(_)) => {
+        let target = self.basic_type(to).into_int_type();
+        return self.builder.build_ptr_to_int(pointer, target, "address").into();
+      }
+      (BasicValueEnum::IntValue(address), Type::Pointer(_)) => {
+        let target = self.basic_type(to).into_pointer_type();
+        return self.builder.build_int_to_ptr(address, target, "pointer").into();
+      }
+      _ => {}
+    }
+    let (from, to) = match (from, to) {
+      (Type::Primitive(from), Type::Primitive(to)) if from != to => (from, to),
+      _ => return value,
+    };
+    let target = self.basic_type(&Type::Primitive(to.clone()));
+    match (value, to.is_float()) {
+      (BasicValueEnum::FloatValue(value), true) => value.as_basic_value_enum(),
+      (BasicValueEnum::FloatValue(value), false) if to.is_signed_integer() => self
+        .builder
+        .build_float_to_signed_int(value, target.into_int_type(), "cast")
+        .into(),
+      (BasicValueEnum::FloatValue(value), false) => self
+        .builder
+        .build_float_to_unsigned_int(value, target.into_int_type(), "cast")
+        .into(),
+      (BasicValueEnum::IntValue(value), true) if from.is_signed_integer() => self
+        .builder
+        .build_signed_int_to_float(value, target.into_float_type(), "cast")
+        .into(),
+      (BasicValueEnum::IntValue(value), true) => self
+        .builder
+        .build_unsigned_int_to_float(value, target.into_float_type(), "cast")
+        .into(),
+      // Truncate, or extend with the sign of the source, where `bool` and `char` are unsigned.
+      (BasicValueEnum::IntValue(value), false) => self
+        .builder
+        .build_int_cast_sign_flag(
+          value,
+          target.into_int_type(),
+          from.is_signed_integer(),
+          "cast",
+        )
+        .into(),
+      (value, _) => value,
+    }
   }
-  unreachable!();
+}
+
+/// Define the static items of the program as the global variables initialized by their values,
+/// which are evaluated at compile time.
+pub fn define_statics<'ctx>(context: &'ctx Context, module: &Module<'ctx>, program: &Program) {
+  for value in &program.consts {
+    let def = match &value.owner {
+      MonoItem::Static(def) => def,
+      _ => continue,
+    };
+    let constant = &value.value;
+    let initializer: BasicValueEnum = match &constant.kind {
+      ConstantKind::Unit => context.const_struct(&[], false).into(),
+      ConstantKind::Integer(value) => {
+        let signed = matches!(&constant.ty, Type::Primitive(p) if p.is_signed_integer());
+        let int_type = basic_type(context, program, &constant.ty).into_int_type();
+        int_type.const_int(*value as u64, signed).into()
+      }
+      ConstantKind::Float(value) => context.f64_type().const_float(*value).into(),
+      ConstantKind::Bool(value) => context.bool_type().const_int(*value as u64, false).into(),
+      ConstantKind::Str(value) => {
+        let string = context.const_string(value.as_bytes(), true);
+        let bytes = module.add_global(string.get_type(), None, &format!("{}.str", def));
+        bytes.set_initializer(&string);
+        bytes.set_constant(true);
+        bytes.set_linkage(Linkage::Private);
+        let pointer = basic_type(context, program, &constant.ty).into_pointer_type();
+        bytes.as_pointer_value().const_cast(pointer).into()
+      }
+      kind => unreachable!("static items could not have the value {:?}", kind),
+    };
+    let global = module.add_global(initializer.get_type(), None, &def.to_string());
+    global.set_initializer(&initializer);
+  }
+}
+
+fn int_predicate(op: &BinaryOp, signed: bool) -> Option<IntPredicate> {
+  let predicate = match (op, signed) {
+    (BinaryOp::Equal, _) => IntPredicate::EQ,
+    (BinaryOp::NotEqual, _) => IntPredicate::NE,
+    (BinaryOp::Less, true) => IntPredicate::SLT,
+    (BinaryOp::Less, false) => IntPredicate::ULT,
+    (BinaryOp::LessEqual, true) => IntPredicate::SLE,
+    (BinaryOp::LessEqual, false) => IntPredicate::ULE,
+    (BinaryOp::Greater, true) => IntPredicate::SGT,
+    (BinaryOp::Greater, false) => IntPredicate::UGT,
+    (BinaryOp::GreaterEqual, true) => IntPredicate::SGE,
+    (BinaryOp::GreaterEqual, false) => IntPredicate::UGE,
+    _ => return None,
+  };
+  Some(predicate)
 }
 
 #[cfg(test)]
@@ -51,33 +1165,801 @@ mod tests {
   use inkwell::context::Context;
   use inkwell::execution_engine::JitFunction;
   use inkwell::OptimizationLevel;
+  use vsp_diag::DiagnosticEngine;
+  use vsp_sema::testing::check_source;
 
   use super::*;
+  use crate::llvm::opt::Pipeline;
+  use crate::llvm::target::native_target_machine;
+  use crate::llvm::CodegenContext;
+
+  /// Compile the functions of the source into the module.
+  fn compile_source<'ctx>(
+    context: &'ctx Context,
+    source: &str,
+    overflow: OverflowMode,
+  ) -> Module<'ctx> {
+    let (unit, results) = check_source(source);
+    let mut diagnostics = DiagnosticEngine::default();
+    let program = vsp_hir::lower_compilation_unit(&unit, &results);
+    let program = vsp_mir::build::build_program(&program, &mut diagnostics);
+    assert!(!diagnostics.has_errors(), "{:?}", diagnostics.diagnostics());
+
+    let codegen = CodegenContext::new("main".to_owned(), context);
+    codegen.add_program(&program, &results, overflow).unwrap();
+    codegen.into_module()
+  }
+
+  /// Compile `func main() -> <ty> { return <expr>; }` into the module.
+  fn compile<'ctx>(
+    context: &'ctx Context,
+    ty: &str,
+    expr: &str,
+    overflow: OverflowMode,
+  ) -> Module<'ctx> {
+    let source = format!("func main() -> {} {{ return {}; }}", ty, expr);
+    compile_source(context, &source, overflow)
+  }
+
+  fn run<T>(ty: &str, expr: &str, overflow: OverflowMode) -> T {
+    let context = Context::create();
+    let module = compile(&context, ty, expr, overflow);
+    let engine = module.create_jit_execution_engine(OptimizationLevel::None).unwrap();
+    unsafe {
+      let main: JitFunction<unsafe extern "C" fn() -> T> = engine.get_function("main").unwrap();
+      main.call()
+    }
+  }
 
   #[test]
   pub fn test() {
+    assert_eq!(run::<i32>("int32", "1 + 2 * 5", OverflowMode::Checked), 11);
+    assert_eq!(run::<i64>("int64", "-7 / 2", OverflowMode::Checked), -3);
+    assert_eq!(run::<i64>("int64", "-7 % 2", OverflowMode::Checked), -1);
+    assert_eq!(run::<u8>("uint8", "200 / 3", OverflowMode::Checked), 66);
+    assert_eq!(
+      run::<u32>("uint32", "4000000000 / 3", OverflowMode::Checked),
+      1333333333
+    );
+  }
+
+  #[test]
+  pub fn test_wrapping() {
+    assert_eq!(run::<i8>("int8", "127 + 1", OverflowMode::Wrapping), -128);
+    assert_eq!(run::<u8>("uint8", "0 - 1", OverflowMode::Wrapping), 255);
+    assert_eq!(
+      run::<i8>("int8", "(-128 as int8) / -1", OverflowMode::Wrapping),
+      -128
+    );
+    assert_eq!(
+      run::<i8>("int8", "(-128 as int8) % -1", OverflowMode::Wrapping),
+      0
+    );
+  }
+
+  #[test]
+  pub fn test_checked() {
+    let context = Context::create();
+    let module = compile(&context, "int8", "100 + 100", OverflowMode::Checked);
+    let ir = module.print_to_string().to_string();
+    assert!(ir.contains("@llvm.sadd.with.overflow.i8"));
+    assert!(ir.contains("@vsp.panic"));
+    assert!(ir.contains("attempt to add with overflow"));
+
+    let module = compile(&context, "uint8", "100 + 100", OverflowMode::Wrapping);
+    let ir = module.print_to_string().to_string();
+    assert!(!ir.contains("with.overflow"));
+  }
+
+  #[test]
+  pub fn test_cast() {
+    assert_eq!(
+      run::<i64>("int64", "(-1 as int8) as int64", OverflowMode::Checked),
+      -1
+    );
+    assert_eq!(
+      run::<i64>("int64", "(255 as uint8) as int64", OverflowMode::Checked),
+      255
+    );
+    assert_eq!(
+      run::<u8>("uint8", "300 as uint8", OverflowMode::Checked),
+      44
+    );
+    assert_eq!(
+      run::<i32>("int32", "2.9 as int32", OverflowMode::Checked),
+      2
+    );
+    assert_eq!(
+      run::<f64>("float64", "3 as float64 / 2.0", OverflowMode::Checked),
+      1.5
+    );
+    assert_eq!(
+      run::<u32>("uint32", "true as uint32 + 1", OverflowMode::Checked),
+      2
+    );
+    assert!(run::<bool>(
+      "bool",
+      "(200 as uint8) > (100 as uint8)",
+      OverflowMode::Checked
+    ));
+    assert!(run::<bool>(
+      "bool",
+      "(-1 as int8) < (1 as int8)",
+      OverflowMode::Checked
+    ));
+  }
+
+  #[test]
+  pub fn test_widening() {
+    assert_eq!(
+      run::<i64>("int64", "(-1 as int8) + 1", OverflowMode::Checked),
+      0
+    );
+    assert_eq!(
+      run::<i64>(
+        "int64",
+        "(200 as uint8) + (-1 as int16)",
+        OverflowMode::Checked
+      ),
+      199
+    );
+  }
+
+  #[test]
+  pub fn test_consts() {
+    let context = Context::create();
+    let module = compile_source(
+      &context,
+      "func main(n: int64): int64 {
+         return n * SCALE + OFFSET;
+       }
+       const func square(x: int64): int64 { return x * x; }
+       const SCALE: int64 = square(3);
+       static OFFSET: int64 = SCALE + 1;
+       static GREETING: str = \"hello\";",
+      OverflowMode::Checked,
+    );
+    let engine = module.create_jit_execution_engine(OptimizationLevel::None).unwrap();
+    unsafe {
+      let main: JitFunction<unsafe extern "C" fn(i64) -> i64> =
+        engine.get_function("main").unwrap();
+      assert_eq!(main.call(2), 28);
+    }
+  }
+
+  #[test]
+  pub fn test_control_flow() {
+    let context = Context::create();
+    let module = compile_source(
+      &context,
+      "func main(n: int64): int64 {
+         var sum = 0;
+         var i = 0;
+         while i < n && sum < 100 {
+           if i % 2 == 0 { sum = sum + i; } else { sum = sum - 1; }
+           i = i + 1;
+         }
+         return sum;
+       }",
+      OverflowMode::Checked,
+    );
+    let engine = module.create_jit_execution_engine(OptimizationLevel::None).unwrap();
+    unsafe {
+      let main: JitFunction<unsafe extern "C" fn(i64) -> i64> =
+        engine.get_function("main").unwrap();
+      assert_eq!(main.call(0), 0);
+      assert_eq!(main.call(5), 4);
+      assert_eq!(main.call(100), 100);
+    }
+  }
+
+  #[test]
+  pub fn test_loops() {
+    let context = Context::create();
+    let module = compile_source(
+      &context,
+      "func main(n: int64): int64 {
+         var sum = 0;
+         'outer: for i in 0..n {
+           for j in 0..i {
+             if i * j > 40 { break 'outer; }
+             if j == 3 { continue 'outer; }
+             sum = sum + j;
+           }
+           sum = sum + 100;
+         }
+         var k = 0;
+         loop {
+           k = k + 1;
+           if k % 2 == 0 { continue; }
+           if k > 7 { break; }
+           sum = sum + k;
+         }
+         return sum;
+       }",
+      OverflowMode::Checked,
+    );
+    let engine = module.create_jit_execution_engine(OptimizationLevel::None).unwrap();
+    unsafe {
+      let main: JitFunction<unsafe extern "C" fn(i64) -> i64> =
+        engine.get_function("main").unwrap();
+      assert_eq!(main.call(0), 16);
+      assert_eq!(main.call(-3), 16);
+      assert_eq!(main.call(3), 317);
+      assert_eq!(main.call(5), 423);
+      assert_eq!(main.call(20), 453);
+    }
+  }
+
+  #[test]
+  pub fn test_if_expressions() {
+    let context = Context::create();
+    let module = compile_source(
+      &context,
+      "func sign(x: int64): int64 {
+         return if x < 0 { -1 } else if x == 0 { 0 } else { 1 };
+       }
+       func main(n: int64): int64 {
+         let safe = n != 0 && 100 / n > 5;
+         let other = n == 0 || 100 / n < 5;
+         let a = if safe { 1000 } else { 0 };
+         let b = if other { 100 } else { 0 };
+         var c = 0;
+         'outer: while true {
+           loop { c = c + 1; if c == 3 { break 'outer; } }
+         }
+         return a + b + (if n > 3 { n } else { sign(n) }) * 10 + c;
+       }",
+      OverflowMode::Checked,
+    );
+    let engine = module.create_jit_execution_engine(OptimizationLevel::None).unwrap();
+    unsafe {
+      let main: JitFunction<unsafe extern "C" fn(i64) -> i64> =
+        engine.get_function("main").unwrap();
+      // The division by zero is never evaluated, since `&&` and `||` short-circuit.
+      assert_eq!(main.call(0), 103);
+      assert_eq!(main.call(5), 1053);
+      assert_eq!(main.call(-50), 93);
+      assert_eq!(main.call(50), 603);
+    }
+  }
+
+  #[test]
+  pub fn test_pointers() {
+    let context = Context::create();
+    let module = compile_source(
+      &context,
+      "func main(n: int64): int64 {
+         var values: int64[2];
+         let p = &values as *int64;
+         unsafe {
+           *p = n;
+           *(p + 1) = 2;
+           let last = (p + 1) - p;
+           if p < p + 1 && (p as uint64) + 8 == (p + 1) as uint64 {
+             return *p * *(p + 1) + last;
+           }
+         }
+         return 0;
+       }",
+      OverflowMode::Checked,
+    );
+    let engine = module.create_jit_execution_engine(OptimizationLevel::None).unwrap();
+    unsafe {
+      let main: JitFunction<unsafe extern "C" fn(i64) -> i64> =
+        engine.get_function("main").unwrap();
+      assert_eq!(main.call(5), 11);
+    }
+  }
+
+  #[test]
+  pub fn test_arrays() {
+    let context = Context::create();
+    let module = compile_source(
+      &context,
+      "func squares(): int64[3] { return [0, 1, 4]; }
+       func fill(grid: *int64[2][2], value: int64) {
+         unsafe { (*grid)[1][0] = value; }
+       }
+       func main(n: int64): int64 {
+         var grid = [[1, 2], [3, 4]];
+         grid[0][n] = 20;
+         fill(&grid, 30);
+         return grid[0][1] + grid[1][0] + squares()[2] + [5, 6][n - 1];
+       }",
+      OverflowMode::Checked,
+    );
+    let ir = module.print_to_string().to_string();
+    assert!(ir.contains("@vsp.panic_bounds"));
+    assert!(ir.contains("index out of bounds: the length is %lld but the index is %lld"));
+    let engine = module.create_jit_execution_engine(OptimizationLevel::None).unwrap();
+    unsafe {
+      let main: JitFunction<unsafe extern "C" fn(i64) -> i64> =
+        engine.get_function("main").unwrap();
+      assert_eq!(main.call(1), 59);
+    }
+  }
+
+  #[test]
+  pub fn test_foreign() {
+    let context = Context::create();
+    let module = compile_source(
+      &context,
+      "func main(n: int64): int64 {
+         var buffer: uint8[16];
+         let p = &buffer as *uint8;
+         unsafe {
+           let written = snprintf(p, 16, \"%d-%s%d\", n as int32, \"ok\", -1 as int8);
+           return labs(-(written as int64)) + strlen(p) as int64 * 100;
+         }
+       }
+       extern \"C\" func labs(value: int64): int64;
+       extern \"C\" func strlen(s: *uint8): uint64;
+       extern \"C\" func snprintf(buffer: *uint8, size: uint64, format: str, ...): int32;",
+      OverflowMode::Checked,
+    );
+    let ir = module.print_to_string().to_string();
+    assert!(
+      ir.contains("declare i32 @snprintf(i8*, i64, i8*, ...)"),
+      "{}",
+      ir
+    );
+    assert!(ir.contains("sext i8"), "{}", ir);
+    let engine = module.create_jit_execution_engine(OptimizationLevel::None).unwrap();
+    unsafe {
+      let main: JitFunction<unsafe extern "C" fn(i64) -> i64> =
+        engine.get_function("main").unwrap();
+      // "42-ok-1"
+      assert_eq!(main.call(42), 707);
+    }
+  }
+
+  #[repr(C)]
+  struct Mixed {
+    x: f64,
+    n: i32,
+  }
+
+  #[repr(C)]
+  struct Small {
+    n: i32,
+    b: u8,
+  }
+
+  #[repr(C)]
+  struct Large {
+    a: i64,
+    b: i64,
+    c: i64,
+  }
+
+  #[repr(C)]
+  struct Pair {
+    first:  i64,
+    second: i64,
+  }
+
+  extern "C" fn mix(m: Mixed, s: Small) -> Large {
+    Large {
+      a: m.x as i64,
+      b: m.n as i64,
+      c: s.n as i64 * s.b as i64,
+    }
+  }
+
+  extern "C" fn spill(a: i64, b: i64, c: i64, d: i64, e: i64, p: Pair, l: Large) -> Pair {
+    Pair {
+      first:  a + b + c + d + e + p.first,
+      second: p.second + l.a + l.b + l.c,
+    }
+  }
+
+  #[test]
+  pub fn test_foreign_structs() {
+    let context = Context::create();
+    let module = compile_source(
+      &context,
+      "func main(n: int64): int64 {
+         unsafe {
+           let large = mix(Mixed { x: 2.5, n: 3 }, Small { n: n as int32, b: 4 });
+           let pair = spill(1, 2, 3, 4, large.a, Pair { first: large.b, second: large.c }, large);
+           return pair.first * 1000 + pair.second;
+         }
+       }
+       @Repr(C) struct Mixed { x: float64, n: int32 }
+       @Repr(C) struct Small { n: int32, b: uint8 }
+       @Repr(C) struct Large { a: int64, b: int64, c: int64 }
+       @Repr(C) struct Pair { first: int64, second: int64 }
+       extern \"C\" func mix(m: Mixed, s: Small): Large;
+       extern \"C\" func spill(a: int64, b: int64, c: int64, d: int64, e: int64, p: Pair,
+                                l: Large): Pair;",
+      OverflowMode::Checked,
+    );
+    let ir = module.print_to_string().to_string();
+    assert!(ir.contains("sret({ i64, i64, i64 })"), "{}", ir);
+    assert!(ir.contains("byval({ i64, i64 })"), "{}", ir);
+    let engine = module.create_jit_execution_engine(OptimizationLevel::None).unwrap();
+    let mappings = [("mix", mix as *const ()), ("spill", spill as *const ())];
+    for (name, address) in mappings {
+      engine.add_global_mapping(&module.get_function(name).unwrap(), address as usize);
+    }
+    unsafe {
+      let main: JitFunction<unsafe extern "C" fn(i64) -> i64> =
+        engine.get_function("main").unwrap();
+      assert_eq!(main.call(5), 15045);
+    }
+  }
+
+  #[test]
+  pub fn test_functions() {
+    let context = Context::create();
+    let module = compile_source(
+      &context,
+      "struct Point { x: float64, y: float64 }
+       impl Point {
+         func scale(factor: float64): Point {
+           return Point { x: self.x * factor, y: self.y * factor };
+         }
+         func norm2(): float64 { return self.x * self.x + self.y * self.y; }
+       }
+       func fib(n: int64): int64 {
+         if n < 2 { return n; }
+         return fib(n - 1) + fib(n - 2);
+       }
+       func is_even(n: int64): bool {
+         if n == 0 { return true; }
+         return is_odd(n - 1);
+       }
+       func is_odd(n: int64): bool {
+         if n == 0 { return false; }
+         return is_even(n - 1);
+       }
+       func nothing() {}
+       func greeting(): str { return \"hello\"; }
+       extern \"C\" func strlen(s: str): uint64;
+       func main(n: int64): int64 {
+         nothing();
+         let p = Point { x: 1.5, y: 2.0 }.scale(2.0);
+         var result = fib(n) * 1000 + p.norm2() as int64;
+         if is_even(n) { result = result + 100; }
+         unsafe { result = result + strlen(greeting()) as int64 * 10; }
+         let f = fib;
+         return result + f(3);
+       }",
+      OverflowMode::Checked,
+    );
+    let ir = module.print_to_string().to_string();
+    assert!(ir.contains("define i1 @is_even(i64"), "{}", ir);
+    assert!(ir.contains("define {} @nothing()"), "{}", ir);
+    let engine = module.create_jit_execution_engine(OptimizationLevel::None).unwrap();
+    unsafe {
+      let main: JitFunction<unsafe extern "C" fn(i64) -> i64> =
+        engine.get_function("main").unwrap();
+      assert_eq!(main.call(10), 55177);
+      assert_eq!(main.call(1), 1077);
+    }
+  }
+
+  #[test]
+  pub fn test_generics() {
+    let context = Context::create();
+    let module = compile_source(
+      &context,
+      "interface Shape { func area(): int64; }
+       struct Square { side: int64 }
+       struct Rect { w: int64, h: int64 }
+       impl Shape for Square { func area(): int64 { return self.side * self.side; } }
+       impl Shape for Rect { func area(): int64 { return self.w * self.h; } }
+       struct Pair<A, B> { first: A, second: B }
+       func total<S: Shape>(shapes: Pair<S, S>): int64 {
+         return shapes.first.area() + shapes.second.area();
+       }
+       func pick<T>(condition: bool, a: T, b: T): T {
+         if condition { return a; }
+         return b;
+       }
+       func main(n: int64): int64 {
+         let squares = Pair { first: Square { side: n }, second: Square { side: 2 } };
+         let rects = Pair { first: Rect { w: n, h: 3 }, second: Rect { w: 1, h: 1 } };
+         let half = pick(n > 2, 1.5, 2.5) as int64;
+         return total(squares) * 100 + total(rects) + pick(n > 2, 1000, 2000) + half;
+       }",
+      OverflowMode::Checked,
+    );
+    let ir = module.print_to_string().to_string();
+    assert!(
+      ir.contains("define double @_VN4pickEI7float64E(i1"),
+      "{}",
+      ir
+    );
+    let engine = module.create_jit_execution_engine(OptimizationLevel::None).unwrap();
+    unsafe {
+      let main: JitFunction<unsafe extern "C" fn(i64) -> i64> =
+        engine.get_function("main").unwrap();
+      assert_eq!(main.call(5), 3917);
+      assert_eq!(main.call(1), 2506);
+    }
+  }
+
+  #[test]
+  pub fn test_trait_objects() {
+    let context = Context::create();
+    let module = compile_source(
+      &context,
+      "interface Shape {
+         func area(): int64;
+         func scaled(factor: int64): Rect;
+       }
+       struct Square { side: int64 }
+       struct Rect { w: int64, h: int64, name: str, scale: int64 }
+       impl Shape for Square {
+         func area(): int64 { return self.side * self.side; }
+         func scaled(factor: int64): Rect {
+           return Rect { w: self.side * factor, h: self.side, name: \"square\", scale: factor };
+         }
+       }
+       impl Shape for Rect {
+         func area(): int64 { return self.w * self.h; }
+         func scaled(factor: int64): Rect {
+           return Rect { w: self.w * factor, h: self.h, name: self.name, scale: factor };
+         }
+       }
+       impl Shape for int64 {
+         func area(): int64 { return self; }
+         func scaled(factor: int64): Rect {
+           return Rect { w: self, h: factor, name: \"int\", scale: 1 };
+         }
+       }
+       func pick(n: int64): dyn Shape {
+         if n > 2 { return Rect { w: n, h: 3, name: \"rect\", scale: 1 }; }
+         if n > 1 { return Square { side: n }; }
+         return n;
+       }
+       func main(n: int64): int64 {
+         let shape = pick(n);
+         return shape.area() * 100 + shape.scaled(2).area();
+       }",
+      OverflowMode::Checked,
+    );
+    let ir = module.print_to_string().to_string();
+    // The vtables are emitted once for each implementation converted into the trait objects.
+    assert_eq!(ir.matches("@vtable.Shape.Rect = ").count(), 1, "{}", ir);
+    assert!(ir.contains("@vtable.Shape.int64 = "), "{}", ir);
+    let engine = module.create_jit_execution_engine(OptimizationLevel::None).unwrap();
     unsafe {
-      let expr = Expression::Binary(
-        BinaryOp::Add,
-        Box::new(Expression::LiteralInteger(1)),
-        Box::new(Expression::Binary(
-          BinaryOp::Multiply,
-          Box::new(Expression::LiteralInteger(2)),
-          Box::new(Expression::LiteralInteger(5)),
-        )),
+      let main: JitFunction<unsafe extern "C" fn(i64) -> i64> =
+        engine.get_function("main").unwrap();
+      assert_eq!(main.call(4), 1200 + 24);
+      assert_eq!(main.call(2), 400 + 8);
+      assert_eq!(main.call(1), 100 + 2);
+    }
+  }
+
+  #[test]
+  pub fn test_async() {
+    let context = Context::create();
+    let module = compile_source(
+      &context,
+      "@Lang(Poll) enum Poll<F, T> { Ready(T), Pending(F) }
+       @Lang(Future) interface Future {
+         type Output;
+         func poll(): Poll<Self, Self::Output>;
+       }
+       @Lang(BlockOn) func block_on<F: Future>(future: F): F::Output;
+       async func fetch(id: int64): int64 { return id * 10; }
+       async func first<T>(value: T): T { return value; }
+       async func total(n: int64): int64 {
+         let a = fetch(n).await;
+         let b = first(a + 1).await;
+         let big = first(n > 2).await;
+         if big { return a * 100 + b; }
+         return b;
+       }
+       func main(n: int64): int32 {
+         return block_on(total(n)) as int32;
+       }",
+      OverflowMode::Checked,
+    );
+    let engine = module.create_jit_execution_engine(OptimizationLevel::None).unwrap();
+    unsafe {
+      let main: JitFunction<unsafe extern "C" fn(i64) -> i32> =
+        engine.get_function("main").unwrap();
+      assert_eq!(main.call(3), 3000 + 31);
+      assert_eq!(main.call(1), 11);
+    }
+  }
+
+  #[repr(C)]
+  #[derive(Debug, PartialEq)]
+  struct Vec2 {
+    x: f64,
+    y: f64,
+  }
+
+  #[test]
+  pub fn test_exports() {
+    let context = Context::create();
+    let module = compile_source(
+      &context,
+      "@Repr(C) struct Vec2 { x: float64, y: float64 }
+       @Repr(C) struct Large { a: int64, b: int64, c: int64 }
+       extern \"C\" func add(u: Vec2, v: Vec2): Vec2 {
+         return Vec2 { x: u.x + v.x, y: u.y + v.y };
+       }
+       extern \"C\" func scale(large: Large, factor: int8): Large {
+         let factor = factor as int64;
+         return Large { a: large.a * factor, b: large.b * factor, c: large.c * factor };
+       }
+       func main(n: int64): int64 {
+         let v = add(Vec2 { x: 1.0, y: 2.0 }, Vec2 { x: n as float64, y: 0.5 });
+         let large = scale(Large { a: 1, b: 2, c: 3 }, -2);
+         return (v.x + v.y) as int64 * 100 + large.a + large.b + large.c;
+       }",
+      OverflowMode::Checked,
+    );
+    let ir = module.print_to_string().to_string();
+    assert!(
+      ir.contains("define { double, double } @add(double %0, double %1, double %2, double %3)"),
+      "{}",
+      ir
+    );
+    assert!(ir.contains("sret({ i64, i64, i64 })"), "{}", ir);
+    assert!(ir.contains("signext %"), "{}", ir);
+    let engine = module.create_jit_execution_engine(OptimizationLevel::None).unwrap();
+    unsafe {
+      let main: JitFunction<unsafe extern "C" fn(i64) -> i64> =
+        engine.get_function("main").unwrap();
+      assert_eq!(main.call(4), 688);
+
+      // Called from Rust by the C calling convention.
+      let add: JitFunction<unsafe extern "C" fn(Vec2, Vec2) -> Vec2> =
+        engine.get_function("add").unwrap();
+      assert_eq!(
+        add.call(Vec2 { x: 1.0, y: 2.0 }, Vec2 { x: 3.0, y: 4.5 }),
+        Vec2 { x: 4.0, y: 6.5 }
       );
-      let context = Context::create();
-      let module = context.create_module("add");
-      let builder = context.create_builder();
+      let scale: JitFunction<unsafe extern "C" fn(Large, i8) -> Large> =
+        engine.get_function("scale").unwrap();
+      let large = scale.call(Large { a: 1, b: -2, c: 3 }, 3);
+      assert_eq!((large.a, large.b, large.c), (3, -6, 9));
+    }
+  }
+
+  #[repr(C)]
+  struct Optional {
+    tag:   u32,
+    value: i64,
+  }
 
-      let int_type = context.i32_type();
-      let fn_type = int_type.fn_type(&[], false);
-      let function = module.add_function("add", fn_type, None);
-      codegen_expr(&context, &builder, function, &expr);
+  #[repr(C)]
+  #[derive(Clone, Copy)]
+  struct Matrix {
+    a: i64,
+    b: i64,
+    c: i64,
+    d: i64,
+  }
 
+  #[repr(C)]
+  union Checked {
+    value: i8,
+    error: Matrix,
+  }
+
+  #[repr(C)]
+  struct Expected {
+    tag:     u32,
+    payload: Checked,
+  }
+
+  #[test]
+  pub fn test_layout() {
+    let context = Context::create();
+    let module = compile_source(
+      &context,
+      "@Lang(Optional) enum Optional<T> { Value(T), None }
+       @Lang(Expected) enum Expected<T, E> { Value(T), Error(E) }
+       struct Matrix { a: int64, b: int64, c: int64, d: int64 }
+       func half(n: int64): Optional<int64> {
+         if n % 2 == 1 { return Optional::None; }
+         return Optional::Value(n / 2);
+       }
+       func quarter(n: int64): Optional<int64> { return Optional::Value(half(n)? / 2); }
+       func check(n: int64): Expected<int8, Matrix> {
+         if n < 0 { return Expected::Error(Matrix { a: n, b: 0, c: 0, d: 0 }); }
+         return Expected::Value(n as int8);
+       }
+       func transpose(m: Matrix): Matrix {
+         return Matrix { a: m.a, b: m.c, c: m.b, d: m.d };
+       }
+       func main(n: int64, quarters: *Optional<int64>, checked: *Expected<int8, Matrix>): int64 {
+         unsafe {
+           *quarters = quarter(n);
+           *checked = check(n);
+         }
+         let m = transpose(Matrix { a: 1, b: n, c: 3, d: 4 });
+         return m.b * 100 + m.c;
+       }",
+      OverflowMode::Checked,
+    );
+    let ir = module.print_to_string().to_string();
+    for signature in [
+      "define void @transpose({ i64, i64, i64, i64 }* %0, { i64, i64, i64, i64 }* %1)",
+      "define void @check({ i32, [4 x i64] }* %0, i64 %1)",
+      "define { i32, [1 x i64] } @quarter(i64 %0)",
+    ] {
+      assert!(ir.contains(signature), "{}", ir);
+    }
+    let engine = module.create_jit_execution_engine(OptimizationLevel::None).unwrap();
+    unsafe {
+      let main: JitFunction<unsafe extern "C" fn(i64, *mut Optional, *mut Expected) -> i64> =
+        engine.get_function("main").unwrap();
+      let mut quarters = Optional { tag: 7, value: 0 };
+      let mut checked = Expected {
+        tag:     7,
+        payload: Checked { value: 0 },
+      };
+      assert_eq!(main.call(8, &mut quarters, &mut checked), 308);
+      assert_eq!((quarters.tag, quarters.value), (0, 2));
+      assert_eq!((checked.tag, checked.payload.value), (0, 8));
+      assert_eq!(main.call(5, &mut quarters, &mut checked), 305);
+      assert_eq!(quarters.tag, 1);
+      assert_eq!((checked.tag, checked.payload.value), (0, 5));
+      assert_eq!(main.call(-4, &mut quarters, &mut checked), 296);
+      assert_eq!((quarters.tag, quarters.value), (0, -1));
+      assert_eq!(checked.tag, 1);
+      let error = checked.payload.error;
+      assert_eq!((error.a, error.b, error.c, error.d), (-4, 0, 0, 0));
+    }
+  }
+
+  #[test]
+  pub fn test_optimization() {
+    let source = "func sum(values: *int64, count: int64): int64 {
+         var total = 0;
+         for i in 0..count { unsafe { total = total + *(values + i); } }
+         return total;
+       }
+       func main(n: int64): int64 {
+         var values: int64[8];
+         let p = &values as *int64;
+         for i in 0..8 { unsafe { *(p + i) = i * n; } }
+         return sum(p, 8);
+       }";
+    for (speed, size) in [(0, 0), (1, 0), (2, 0), (3, 0), (2, 1), (2, 2)] {
+      let context = Context::create();
+      let module = compile_source(&context, source, OverflowMode::Wrapping);
+      let pipeline = Pipeline::default_for(speed, size);
+      let machine = native_target_machine(pipeline.codegen_level()).unwrap();
+      let timings = pipeline.run(&module, &machine).unwrap();
+      assert_eq!(timings.len(), pipeline.passes().len());
+      module.verify().unwrap();
+      let ir = module.print_to_string().to_string();
+      // Only the array, whose address is taken, stays in memory once the passes run.
+      assert_eq!(
+        ir.matches(" alloca ").count() <= 1,
+        speed > 0 || size > 0,
+        "{}",
+        ir
+      );
+      assert_eq!(ir.contains("minsize"), size == 2, "{}", ir);
       let engine = module.create_jit_execution_engine(OptimizationLevel::None).unwrap();
-      let func: JitFunction<unsafe extern "C" fn() -> i32> = engine.get_function("add").unwrap();
-      println!("{}", func.call());
+      unsafe {
+        let main: JitFunction<unsafe extern "C" fn(i64) -> i64> =
+          engine.get_function("main").unwrap();
+        assert_eq!(main.call(3), 84);
+      }
     }
+
+    let context = Context::create();
+    let module = compile_source(&context, source, OverflowMode::Wrapping);
+    let pipeline = Pipeline::custom("sroa,function(instcombine,no-such-pass)", 2, 0).unwrap();
+    let machine = native_target_machine(pipeline.codegen_level()).unwrap();
+    let message = pipeline.run(&module, &machine).unwrap_err().message();
+    assert!(
+      message.starts_with("invalid pass `function(instcombine,no-such-pass)`"),
+      "{}",
+      message
+    );
   }
 }
@@ -0,0 +1,215 @@
+//! Execution of the generated modules in memory by the JIT of LLVM, without the linker.
+//!
+//! The entry of a program is its `main` function, which either takes no parameters, or the
+//! arguments of the command line as C does, `argc: int32` and `argv: *str`. It returns nothing, or
+//! an integer which is the exit code of the program. The JIT calls it through an entry of the C
+//! type of `main`, which passes the arguments if `main` takes them, and converts its result.
+//!
+//! Foreign functions are resolved against the symbols of the running process, which brings the C
+//! library, and of the libraries loaded into it before. The output functions of the prelude, which
+//! the C library does not define, are bound to the natives of the JIT.
+use std::ffi::CStr;
+use std::io::Write;
+use std::os::raw::c_char;
+use std::os::raw::c_void;
+
+use inkwell::module::Module;
+use inkwell::support::load_library_permanently;
+use inkwell::targets::InitializationConfig;
+use inkwell::targets::Target;
+use inkwell::types::BasicTypeEnum;
+use inkwell::values::BasicMetadataValueEnum;
+use inkwell::values::FunctionValue;
+use inkwell::AddressSpace;
+use inkwell::OptimizationLevel;
+use vsp_error::VspError;
+use vsp_error::VspResult;
+
+/// Name of the entry of the programs.
+pub const MAIN: &str = "main";
+
+/// Name of the entry of the C type of `main`, which the JIT calls.
+const ENTRY: &str = "vsp.main";
+
+/// Natives of the output functions of the prelude by their symbols.
+const NATIVES: [(&str, extern "C" fn(*const c_char)); 2] = [("print", print), ("println", println)];
+
+/// Run `main` of the module compiled by the JIT at the level, with the arguments of the command
+/// line which start with the name of the program, and return its exit code.
+pub fn run_main(module: &Module, level: OptimizationLevel, args: &[String]) -> VspResult<i32> {
+  Target::initialize_native(&InitializationConfig::default()).map_err(VspError::new)?;
+  let main = module
+    .get_function(MAIN)
+    .filter(|function| function.count_basic_blocks() > 0)
+    .ok_or_else(|| VspError::new("`main` function not found"))?;
+  let entry = define_entry(module, main)?;
+  let engine = module.create_jit_execution_engine(level).map_err(|message| {
+    VspError::new(format!("could not create the JIT: {}", message.to_string()))
+  })?;
+  for (symbol, native) in NATIVES {
+    let declared = module
+      .get_function(symbol)
+      .filter(|function| function.count_basic_blocks() == 0);
+    if let Some(function) = declared {
+      engine.add_global_mapping(&function, native as usize);
+    }
+  }
+  let args: Vec<&str> = args.iter().map(String::as_str).collect();
+  engine.run_static_constructors();
+  let code = unsafe { engine.run_function_as_main(entry, &args) };
+  engine.run_static_destructors();
+  Ok(code)
+}
+
+/// Define the entry of the C type `int32 (int32, **int8)` calling `main`, whose result is
+/// truncated or extended to `int32`, or zero if it returns nothing.
+fn define_entry<'ctx>(
+  module: &Module<'ctx>,
+  main: FunctionValue<'ctx>,
+) -> VspResult<FunctionValue<'ctx>> {
+  let context = module.get_context();
+  let ty = main.get_type();
+  let result = match ty.get_return_type() {
+    None => None,
+    Some(BasicTypeEnum::StructType(unit)) if unit.count_fields() == 0 => None,
+    Some(BasicTypeEnum::IntType(int)) => Some(int),
+    Some(_) => {
+      return VspError::new("`main` must return nothing or an integer").to_err();
+    }
+  };
+  let int32 = context.i32_type();
+  let argv = context
+    .i8_type()
+    .ptr_type(AddressSpace::default())
+    .ptr_type(AddressSpace::default());
+  let takes_args = match ty.get_param_types().as_slice() {
+    [] => false,
+    [BasicTypeEnum::IntType(argc), BasicTypeEnum::PointerType(pointer)]
+      if *argc == int32 && *pointer == argv =>
+    {
+      true
+    }
+    _ => {
+      return VspError::new("`main` must take no parameters, or `argc: int32` and `argv: *str`")
+        .to_err()
+    }
+  };
+
+  let entry = module.add_function(
+    ENTRY,
+    int32.fn_type(&[int32.into(), argv.into()], false),
+    None,
+  );
+  let builder = context.create_builder();
+  builder.position_at_end(context.append_basic_block(entry, "entry"));
+  let args: Vec<BasicMetadataValueEnum> = if takes_args {
+    entry.get_params().into_iter().map(Into::into).collect()
+  } else {
+    Vec::new()
+  };
+  let value = builder.build_call(main, &args, "code").try_as_basic_value().left();
+  let code = match (value, result) {
+    (Some(value), Some(int)) if int.get_bit_width() == 1 => {
+      builder.build_int_z_extend(value.into_int_value(), int32, "code")
+    }
+    (Some(value), Some(_)) => builder.build_int_cast(value.into_int_value(), int32, "code"),
+    _ => int32.const_zero(),
+  };
+  builder.build_return(Some(&code));
+  Ok(entry)
+}
+
+extern "C" {
+  fn fflush(stream: *mut c_void) -> i32;
+}
+
+/// Write the string to the standard output.
+extern "C" fn print(s: *const c_char) {
+  if s.is_null() {
+    return;
+  }
+  // Both the buffers of the C library and of the output are flushed, so that the outputs of both
+  // are in order.
+  let mut stdout = std::io::stdout();
+  unsafe { fflush(std::ptr::null_mut()) };
+  let _ = stdout.write_all(unsafe { CStr::from_ptr(s) }.to_bytes());
+  let _ = stdout.flush();
+}
+
+/// Write the string and a new line to the standard output.
+extern "C" fn println(s: *const c_char) {
+  print(s);
+  print(b"\n\0".as_ptr() as *const c_char);
+}
+
+/// Load the shared library into the process, so that the foreign functions are resolved against
+/// it. The name is looked up by the dynamic loader if it is not a path.
+pub fn load_library(path: &str) -> VspResult<()> {
+  // LLVM returns true on failure.
+  if load_library_permanently(path) {
+    return VspError::new(format!("could not load the library `{}`", path)).to_err();
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use inkwell::context::Context;
+  use vsp_diag::DiagnosticEngine;
+  use vsp_sema::testing::check_source;
+
+  use super::*;
+  use crate::llvm::ir::OverflowMode;
+  use crate::llvm::CodegenContext;
+
+  fn run(source: &str, args: &[&str]) -> VspResult<i32> {
+    let (unit, results) = check_source(source);
+    let mut diagnostics = DiagnosticEngine::default();
+    let program = vsp_hir::lower_compilation_unit(&unit, &results);
+    let program = vsp_mir::build::build_program(&program, &mut diagnostics);
+    assert!(!diagnostics.has_errors());
+
+    let context = Context::create();
+    let codegen = CodegenContext::new("main".to_owned(), &context);
+    codegen.add_program(&program, &results, OverflowMode::Checked).unwrap();
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    run_main(codegen.module(), OptimizationLevel::None, &args)
+  }
+
+  #[test]
+  fn test_run_main() {
+    assert_eq!(run("func main() {}", &["main"]).unwrap(), 0);
+    assert_eq!(
+      run("func main(): int32 { return 3; }", &["main"]).unwrap(),
+      3
+    );
+    assert_eq!(
+      run("func main(): int64 { return 7; }", &["main"]).unwrap(),
+      7
+    );
+    assert_eq!(
+      run("func main(): int8 { return -1; }", &["main"]).unwrap(),
+      -1
+    );
+    let source = "extern \"C\" func strlen(s: str): int64;
+      func main(argc: int32, argv: *str): int64 {
+        unsafe { return argc as int64 * 10 + strlen(*(argv + 1)); }
+      }";
+    assert_eq!(run(source, &["main", "four", "x"]).unwrap(), 34);
+  }
+
+  #[test]
+  fn test_main_type() {
+    let message = |source| run(source, &["main"]).unwrap_err().message();
+    assert_eq!(
+      message("func main(n: int64): int64 { return n; }"),
+      "`main` must take no parameters, or `argc: int32` and `argv: *str`"
+    );
+    assert_eq!(
+      message("func main(): float64 { return 1.0; }"),
+      "`main` must return nothing or an integer"
+    );
+    assert_eq!(message("func run() {}"), "`main` function not found");
+    assert!(load_library("libvsp-missing.so").is_err());
+  }
+}
@@ -0,0 +1,455 @@
+//! Memory layout
+//!
+//! Size, alignment and offsets of the values in memory, which agree with the LLVM types lowered
+//! by [`basic_type`](crate::types::basic_type) under the data layout of x86-64:
+//!
+//! - Primitives are aligned to their sizes, and `unit` is zero-sized. Raw pointers, `str` and
+//!   function pointers are 8 bytes.
+//! - Fields of a struct are laid out in the order of declaration, each at the next offset aligned
+//!   to the field, and the size is rounded up to the alignment of the struct. This is also the C
+//!   layout of `@Repr(C)` structs.
+//! - Elements of an array are laid out one after another, since sizes are multiples of alignments.
+//! - Enums are tagged unions. The `uint32` tag at offset 0 is the index of the variant, and the
+//!   fields of each variant are laid out as a struct in the payload after the tag, which is aligned
+//!   to the most aligned variant and as large as the largest one. The state machines of `async
+//!   func` are laid out as enums with a variant for each state.
+//! - Trait objects are pairs of the pointers to the data and to the vtable.
+//!
+//! By the calling convention of vsp, values of at most [`MAX_BY_VALUE_SIZE`] bytes are passed by
+//! value, and larger aggregates by pointer.
+use std::collections::HashMap;
+
+use vsp_ast::ast::module::Path;
+use vsp_ast::ast::types::PrimitiveType;
+use vsp_ast::ast::types::Type;
+use vsp_mir::mir::CoroutineLayout;
+use vsp_mir::mir::Program;
+use vsp_sema::mono::substitute;
+use vsp_sema::mono::MonoItem;
+use vsp_sema::resolve::DefPath;
+
+use crate::types::struct_fields;
+
+/// Size of the tag of enums, which is `uint32`.
+pub const TAG_SIZE: u64 = 4;
+
+/// Largest size of the aggregates passed by value by the calling convention of vsp.
+pub const MAX_BY_VALUE_SIZE: u64 = 16;
+
+/// Size, alignment and shape of the type in bytes.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Layout {
+  pub size:  u64,
+  pub align: u64,
+  pub shape: Shape,
+}
+
+/// Where the parts of a value are located, relative to the start of the value.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Shape {
+  /// Primitives, pointers and function pointers, which have no parts.
+  Scalar,
+  /// Elements of an array at the multiples of the stride.
+  Array { stride: u64, count: u64 },
+  /// Fields of a struct or a trait object at the offsets, in the order of declaration.
+  Struct { offsets: Vec<u64> },
+  /// Tag of an enum at offset 0, and the fields of each variant at the offsets, which start at
+  /// the offset of the payload aligned to the most aligned variant.
+  Enum {
+    payload:       u64,
+    payload_align: u64,
+    variants:      Vec<Vec<u64>>,
+  },
+}
+
+impl Layout {
+  fn scalar(size: u64) -> Self {
+    Self {
+      size,
+      align: size.max(1),
+      shape: Shape::Scalar,
+    }
+  }
+
+  /// Whether the value consists of parts, which are accessed by the offsets.
+  pub fn is_aggregate(&self) -> bool {
+    !matches!(self.shape, Shape::Scalar)
+  }
+
+  /// Offset of the field of the struct, or the element of the array.
+  pub fn offset(&self, index: usize) -> u64 {
+    match &self.shape {
+      Shape::Array { stride, .. } => stride * index as u64,
+      Shape::Struct { offsets } => offsets[index],
+      shape => unreachable!("{:?} has no fields", shape),
+    }
+  }
+}
+
+/// How a value is passed as an argument or returned by the calling convention of vsp.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PassBy {
+  /// As the value of its LLVM type.
+  Value,
+  /// By the pointer to a copy owned by the callee for arguments, or by the hidden pointer to the
+  /// memory of the caller for return values.
+  Pointer,
+}
+
+/// Layout of the type, which must have the generic parameters substituted.
+pub fn layout_of(program: &Program, ty: &Type) -> Layout {
+  match ty {
+    Type::Primitive(primitive) => Layout::scalar(primitive_size(primitive)),
+    Type::Pointer(_) | Type::Function(_) => Layout::scalar(8),
+    Type::Dyn(_) => struct_layout(&[Layout::scalar(8), Layout::scalar(8)]),
+    Type::Array(element, count) => {
+      let element = layout_of(program, element);
+      Layout {
+        size:  element.size * *count as u64,
+        align: element.align,
+        shape: Shape::Array {
+          stride: element.size,
+          count:  *count as u64,
+        },
+      }
+    }
+    Type::Named(path, args) if program.struct_def(path).is_some() => {
+      let fields = struct_fields(program, path, args)
+        .iter()
+        .map(|field| layout_of(program, field))
+        .collect::<Vec<_>>();
+      struct_layout(&fields)
+    }
+    Type::Named(path, _) if program.enum_def(path).is_some() => enum_layout(program, ty),
+    Type::Coroutine(..) => enum_layout(program, ty),
+    _ => unreachable!("type `{}` has no layout", ty),
+  }
+}
+
+/// Whether the value of the type is passed by value or by pointer. Only the aggregates larger than
+/// [`MAX_BY_VALUE_SIZE`] are passed by pointer, so that small structs and enums stay in registers.
+pub fn pass_by(program: &Program, ty: &Type) -> PassBy {
+  let layout = layout_of(program, ty);
+  if layout.is_aggregate() && layout.size > MAX_BY_VALUE_SIZE {
+    PassBy::Pointer
+  } else {
+    PassBy::Value
+  }
+}
+
+/// Types of the fields of each variant of the enum, with the generic arguments substituted. The
+/// state machines of `async func` are enums whose variants are the states, saving the locals.
+pub fn enum_variants(program: &Program, ty: &Type) -> Vec<Vec<Type>> {
+  let (generics, variants, args) = match ty {
+    Type::Named(path, args) => {
+      let def = program.enum_def(path).expect("undefined enum");
+      (def.generics.clone(), &def.variants, args)
+    }
+    Type::Coroutine(path, args) => {
+      let layout = coroutine_layout(program, path);
+      let generics = match &layout.ty {
+        Type::Coroutine(_, params) => params.iter().map(ToString::to_string).collect(),
+        _ => unreachable!(),
+      };
+      (generics, &layout.states, args)
+    }
+    _ => unreachable!("type `{}` is not an enum", ty),
+  };
+  let substitution = generics.into_iter().zip(args.iter().cloned()).collect::<HashMap<_, _>>();
+  variants
+    .iter()
+    .map(|fields| fields.iter().map(|field| substitute(field, &substitution)).collect())
+    .collect()
+}
+
+/// Layout of the state machine of the `async func` at the path, kept by its `poll`.
+fn coroutine_layout<'a>(program: &'a Program, path: &Path) -> &'a CoroutineLayout {
+  let def = DefPath::new(
+    path.parent().unwrap_or_default(),
+    path.name().unwrap_or_default(),
+  );
+  program
+    .body(&MonoItem::Coroutine(def))
+    .and_then(|body| body.coroutine.as_ref())
+    .expect("undefined coroutine")
+}
+
+/// Lay out the tag and the variants of the enum or the state machine in the payload after it.
+fn enum_layout(program: &Program, ty: &Type) -> Layout {
+  let variants = enum_variants(program, ty)
+    .iter()
+    .map(|fields| {
+      let fields = fields.iter().map(|field| layout_of(program, field)).collect::<Vec<_>>();
+      struct_layout(&fields)
+    })
+    .collect::<Vec<_>>();
+  let payload_align = variants.iter().map(|variant| variant.align).max().unwrap_or(1);
+  let payload_size = variants.iter().map(|variant| variant.size).max().unwrap_or(0);
+  let payload = align_to(TAG_SIZE, payload_align);
+  let align = payload_align.max(TAG_SIZE);
+  let variants = variants
+    .iter()
+    .map(|variant| match &variant.shape {
+      Shape::Struct { offsets } => offsets.iter().map(|offset| payload + offset).collect(),
+      _ => unreachable!(),
+    })
+    .collect();
+  Layout {
+    size: align_to(payload + payload_size, align),
+    align,
+    shape: Shape::Enum {
+      payload,
+      payload_align,
+      variants,
+    },
+  }
+}
+
+/// Lay out the fields one after another, each aligned to itself.
+fn struct_layout(fields: &[Layout]) -> Layout {
+  let mut size = 0;
+  let mut align = 1;
+  let mut offsets = vec![];
+  for field in fields {
+    size = align_to(size, field.align);
+    offsets.push(size);
+    size += field.size;
+    align = align.max(field.align);
+  }
+  Layout {
+    size: align_to(size, align),
+    align,
+    shape: Shape::Struct { offsets },
+  }
+}
+
+pub(crate) fn primitive_size(primitive: &PrimitiveType) -> u64 {
+  match primitive {
+    PrimitiveType::Unit => 0,
+    PrimitiveType::Bool | PrimitiveType::Int8 | PrimitiveType::Uint8 => 1,
+    PrimitiveType::Int16 | PrimitiveType::Uint16 => 2,
+    PrimitiveType::Int32 | PrimitiveType::Uint32 | PrimitiveType::Char => 4,
+    PrimitiveType::Int64
+    | PrimitiveType::Uint64
+    | PrimitiveType::Float64
+    | PrimitiveType::Double64
+    | PrimitiveType::Str => 8,
+  }
+}
+
+fn align_to(offset: u64, align: u64) -> u64 {
+  (offset + align - 1) / align * align
+}
+
+#[cfg(test)]
+mod tests {
+  use inkwell::context::Context;
+  use inkwell::targets::TargetData;
+  use vsp_hir::hir::EnumDef;
+  use vsp_hir::hir::StructDef;
+  use vsp_sema::resolve::DefPath;
+
+  use super::*;
+  use crate::types::basic_type;
+  use crate::types::enum_type;
+
+  /// Data layout of x86-64 Linux.
+  const DATA_LAYOUT: &str =
+    "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128";
+
+  fn program() -> Program {
+    let int8 = Type::Primitive(PrimitiveType::Int8);
+    let int32 = Type::Primitive(PrimitiveType::Int32);
+    let int64 = Type::Primitive(PrimitiveType::Int64);
+    let float64 = Type::Primitive(PrimitiveType::Float64);
+    let param = Type::Named(Path::from("T"), vec![]);
+    let mut program = Program::default();
+    for (name, generics, fields) in [
+      ("Mixed", vec![], vec![float64, int32.clone()]),
+      (
+        "Small",
+        vec![],
+        vec![int32.clone(), Type::Primitive(PrimitiveType::Uint8)],
+      ),
+      (
+        "Bytes",
+        vec![],
+        vec![Type::array(Type::Primitive(PrimitiveType::Uint8), 12)],
+      ),
+      (
+        "Padded",
+        vec![],
+        vec![int8.clone(), int64.clone(), int8.clone()],
+      ),
+      (
+        "Pair",
+        vec!["T".to_owned()],
+        vec![int8.clone(), param.clone()],
+      ),
+    ] {
+      program.structs.push(StructDef {
+        def: DefPath::new(Path::default(), name),
+        generics,
+        fields,
+      });
+    }
+    for (name, generics, variants) in [
+      ("Optional", vec!["T".to_owned()], vec![vec![param], vec![]]),
+      ("Ordering", vec![], vec![vec![], vec![], vec![]]),
+      (
+        "Shape",
+        vec![],
+        vec![vec![int8.clone(), int8], vec![named("Padded")]],
+      ),
+      (
+        "Small",
+        vec![],
+        vec![vec![Type::Primitive(PrimitiveType::Bool)]],
+      ),
+    ] {
+      program.enums.push(EnumDef {
+        def: DefPath::new(Path::default(), name),
+        generics,
+        variants,
+        discriminants: vec![],
+      });
+    }
+    program
+  }
+
+  fn named(name: &str) -> Type {
+    Type::Named(Path::from(name), vec![])
+  }
+
+  fn generic(name: &str, arg: Type) -> Type {
+    Type::Named(Path::from(name), vec![arg])
+  }
+
+  #[test]
+  fn test_structs() {
+    let program = program();
+    let layout = layout_of(&program, &named("Mixed"));
+    assert_eq!((layout.size, layout.align), (16, 8));
+    assert_eq!(
+      layout.shape,
+      Shape::Struct {
+        offsets: vec![0, 8],
+      }
+    );
+    let layout = layout_of(&program, &named("Small"));
+    assert_eq!((layout.size, layout.align), (8, 4));
+    let layout = layout_of(&program, &named("Bytes"));
+    assert_eq!((layout.size, layout.align), (12, 1));
+    assert_eq!(layout.offset(0), 0);
+    let layout = layout_of(&program, &named("Padded"));
+    assert_eq!((layout.size, layout.align), (24, 8));
+    assert_eq!(
+      layout.shape,
+      Shape::Struct {
+        offsets: vec![0, 8, 16],
+      }
+    );
+    let layout = layout_of(&program, &generic("Pair", Type::int16()));
+    assert_eq!((layout.size, layout.align), (4, 2));
+    assert_eq!(layout.offset(1), 2);
+
+    let layout = layout_of(&program, &Type::array(named("Small"), 3));
+    assert_eq!((layout.size, layout.align), (24, 4));
+    assert_eq!(layout.offset(2), 16);
+    let layout = layout_of(&program, &Type::Dyn(Path::from("Describe")));
+    assert_eq!((layout.size, layout.align), (16, 8));
+    assert_eq!(layout_of(&program, &Type::unit()).size, 0);
+  }
+
+  #[test]
+  fn test_enums() {
+    let program = program();
+    let layout = layout_of(&program, &generic("Optional", Type::int64()));
+    assert_eq!((layout.size, layout.align), (16, 8));
+    assert_eq!(
+      layout.shape,
+      Shape::Enum {
+        payload:       8,
+        payload_align: 8,
+        variants:      vec![vec![8], vec![]],
+      }
+    );
+    let layout = layout_of(&program, &generic("Optional", Type::int8()));
+    assert_eq!((layout.size, layout.align), (8, 4));
+    let layout = layout_of(&program, &named("Ordering"));
+    assert_eq!((layout.size, layout.align), (4, 4));
+    let layout = layout_of(&program, &named("Shape"));
+    assert_eq!((layout.size, layout.align), (32, 8));
+    assert_eq!(
+      layout.shape,
+      Shape::Enum {
+        payload:       8,
+        payload_align: 8,
+        variants:      vec![vec![8, 9], vec![8]],
+      }
+    );
+  }
+
+  #[test]
+  fn test_pass_by() {
+    let program = program();
+    assert_eq!(pass_by(&program, &named("Mixed")), PassBy::Value);
+    assert_eq!(pass_by(&program, &named("Padded")), PassBy::Pointer);
+    assert_eq!(pass_by(&program, &named("Shape")), PassBy::Pointer);
+    assert_eq!(
+      pass_by(&program, &generic("Optional", Type::int64())),
+      PassBy::Value
+    );
+    assert_eq!(
+      pass_by(&program, &Type::array(Type::int64(), 3)),
+      PassBy::Pointer
+    );
+    assert_eq!(pass_by(&program, &Type::int64()), PassBy::Value);
+  }
+
+  /// The layouts agree with LLVM on the lowered types.
+  #[test]
+  fn test_llvm_agreement() {
+    let program = program();
+    let context = Context::create();
+    let target_data = TargetData::create(DATA_LAYOUT);
+    for ty in [
+      named("Mixed"),
+      named("Small"),
+      named("Bytes"),
+      named("Padded"),
+      generic("Pair", Type::int16()),
+      Type::array(named("Small"), 3),
+      generic("Optional", Type::int64()),
+      generic("Optional", Type::int8()),
+      generic("Optional", named("Bytes")),
+      named("Ordering"),
+      named("Shape"),
+      named("Small"),
+    ] {
+      let layout = layout_of(&program, &ty);
+      let llvm_type = basic_type(&context, &program, &ty);
+      assert_eq!(target_data.get_abi_size(&llvm_type), layout.size, "{}", ty);
+      assert_eq!(
+        target_data.get_abi_alignment(&llvm_type) as u64,
+        layout.align,
+        "{}",
+        ty
+      );
+      match &layout.shape {
+        Shape::Struct { offsets } => {
+          for (index, offset) in offsets.iter().enumerate() {
+            let struct_type = llvm_type.into_struct_type();
+            let llvm_offset = target_data.offset_of_element(&struct_type, index as u32);
+            assert_eq!(llvm_offset, Some(*offset), "{}", ty);
+          }
+        }
+        Shape::Enum { payload, .. } => {
+          let enum_type = enum_type(&context, &program, &ty);
+          assert_eq!(target_data.offset_of_element(&enum_type, 1), Some(*payload));
+        }
+        _ => {}
+      }
+    }
+  }
+}
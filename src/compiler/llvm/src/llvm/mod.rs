@@ -1,19 +1,49 @@
+use std::path::Path;
+
 use inkwell::builder::Builder;
 use inkwell::context::Context;
+use inkwell::module::FlagBehavior;
 use inkwell::module::Module;
-use inkwell::passes::PassManager;
-use inkwell::passes::PassManagerBuilder;
-use inkwell::targets::InitializationConfig;
-use inkwell::targets::Target;
-use inkwell::OptimizationLevel;
-use vsp_ast::ast::function::Function;
+use inkwell::targets::FileType;
+use inkwell::targets::TargetMachine;
+use inkwell::values::FunctionValue;
+use vsp_error::VspError;
+use vsp_error::VspResult;
+use vsp_mir::mir::Program;
+use vsp_sema::check::TypeckResults;
+use vsp_sema::items::ItemTable;
+use vsp_sema::mono::Instance;
+use vsp_sema::mono::MonoItem;
+use vsp_sema::mono::MonoItems;
+
+use crate::llvm::abi::find_aggregate;
+use crate::llvm::debuginfo::DebugInfo;
+use crate::llvm::ir::define_statics;
+use crate::llvm::ir::OverflowMode;
+use crate::llvm::ir::ProgramCodegen;
+use crate::llvm::opt::PassTiming;
+use crate::llvm::opt::Pipeline;
+use crate::llvm::spec::target_spec;
+use crate::llvm::spec::CAbi;
 
+pub mod abi;
+pub mod debuginfo;
 pub mod ir;
+pub mod jit;
+pub mod layout;
+pub mod opt;
+pub mod runtime;
+pub mod spec;
+pub mod target;
+pub mod vtable;
 
 pub struct CodegenContext<'ctx> {
-  context: &'ctx Context,
-  module:  Module<'ctx>,
-  builder: Builder<'ctx>,
+  /// Debug information of the program, only under `-g`. It is dropped before the module it is
+  /// generated into.
+  debug_info: Option<DebugInfo<'ctx>>,
+  context:    &'ctx Context,
+  module:     Module<'ctx>,
+  builder:    Builder<'ctx>,
 }
 
 impl<'ctx> CodegenContext<'ctx> {
@@ -23,9 +53,16 @@ impl<'ctx> CodegenContext<'ctx> {
       context,
       module,
       builder: context.create_builder(),
+      debug_info: None,
     }
   }
 
+  /// Generate the debug information of the program added next, compiled from the package rooted at
+  /// the file, which is optimized or not.
+  pub fn enable_debug_info(&mut self, root: &Path, optimized: bool) {
+    self.debug_info = Some(DebugInfo::new(self.context, &self.module, root, optimized));
+  }
+
   pub fn context(&self) -> &'ctx Context {
     self.context
   }
@@ -38,79 +75,187 @@ impl<'ctx> CodegenContext<'ctx> {
     &self.builder
   }
 
-  pub fn optimize(&mut self) {
-    let config = InitializationConfig::default();
-    Target::initialize_native(&config).unwrap();
-    let pass_manager_builder = PassManagerBuilder::create();
-    pass_manager_builder.set_optimization_level(OptimizationLevel::Aggressive);
-    let mut pass_manager = PassManager::create(());
+  /// Optimize the module by the pipeline for the target machine, and return the time spent by each
+  /// pass. The module is verified again after the passes.
+  pub fn optimize(
+    &self,
+    pipeline: &Pipeline,
+    machine: &TargetMachine,
+  ) -> VspResult<Vec<PassTiming>> {
+    let timings = pipeline.run(&self.module, machine)?;
+    self
+      .module
+      .verify()
+      .map_err(|message| VspError::new(format!("invalid optimized module: {}", message)))?;
+    Ok(timings)
+  }
 
-    // Add passes.
-    add_default_pass(&mut pass_manager);
-    add_customized_pass(&mut pass_manager);
+  /// Generate the module for the target machine, by its triple and data layout, which should be set
+  /// before the program is added. The C ABI of the target is recorded as well if the triple does
+  /// not imply it.
+  pub fn set_target(&self, machine: &TargetMachine) {
+    let triple = machine.get_triple();
+    self.module.set_triple(&triple);
+    self.module.set_data_layout(&machine.get_target_data().get_data_layout());
+    let abi = target_spec(&triple.as_str().to_string_lossy()).and_then(|spec| spec.abi.llvm_name());
+    if let Some(abi) = abi {
+      self.module.add_metadata_flag(
+        "target-abi",
+        FlagBehavior::Error,
+        self.context.metadata_string(abi),
+      );
+    }
+  }
 
-    pass_manager.run_on(&self.module);
-    pass_manager_builder.populate_module_pass_manager(&pass_manager);
+  /// Write the object file of the module compiled by the target machine.
+  pub fn emit_object(&self, machine: &TargetMachine, path: &Path) -> VspResult<()> {
+    machine
+      .write_to_file(&self.module, FileType::Object, path)
+      .map_err(|message| write_error(path, message.to_string()))?;
+    let triple = machine.get_triple();
+    let spec = target_spec(&triple.as_str().to_string_lossy());
+    if spec.map_or(false, |spec| spec.abi == CAbi::Lp64d) {
+      set_double_float_abi(path)?;
+    }
+    Ok(())
   }
 
-  pub fn add_function(&self, function: Function) {
-    let i64_type = self.context.i64_type();
-    let fn_type = i64_type.fn_type(&[], false);
-    let function = self.module.add_function(function.name.as_str(), fn_type, None);
-    let basic_block = self.context.append_basic_block(function, "entry");
+  /// Write the assembly of the module compiled by the target machine.
+  pub fn emit_assembly(&self, machine: &TargetMachine, path: &Path) -> VspResult<()> {
+    machine
+      .write_to_file(&self.module, FileType::Assembly, path)
+      .map_err(|message| write_error(path, message.to_string()))
+  }
+
+  /// Write the bitcode of the module.
+  pub fn emit_bitcode(&self, path: &Path) -> VspResult<()> {
+    if self.module.write_bitcode_to_path(path) {
+      Ok(())
+    } else {
+      Err(write_error(path, "failed to write the bitcode".to_owned()))
+    }
   }
-}
 
-pub fn convert_to_llvm_optimization_level(num: u8) {
-  debug_assert!(num <= 3);
+  /// Write the textual IR of the module.
+  pub fn emit_ir(&self, path: &Path) -> VspResult<()> {
+    self
+      .module
+      .print_to_file(path)
+      .map_err(|message| write_error(path, message.to_string()))
+  }
+
+  pub fn into_module(self) -> Module<'ctx> {
+    self.module
+  }
+
+  /// Code generator of the program into the module of this context.
+  pub fn program_codegen<'a>(
+    &'a self,
+    program: &'a Program,
+    items: &'a ItemTable,
+    overflow: OverflowMode,
+  ) -> ProgramCodegen<'a, 'ctx> {
+    ProgramCodegen::new(
+      self.context,
+      &self.module,
+      &self.builder,
+      overflow,
+      program,
+      items,
+    )
+  }
+
+  /// Generate the program checked without errors: the static items, and all instances of the
+  /// functions and methods reachable from the non-generic ones. The module is verified at last.
+  pub fn add_program(
+    &self,
+    program: &Program,
+    results: &TypeckResults,
+    overflow: OverflowMode,
+  ) -> VspResult<()> {
+    self.check_c_abi(program, results.items())?;
+    define_statics(self.context, &self.module, program);
+    let mut codegen = self.program_codegen(program, results.items(), overflow);
+    if let Some(debug_info) = &self.debug_info {
+      codegen.set_debug_info(debug_info);
+    }
+    for instance in MonoItems::collect(results).instances() {
+      // Foreign functions are declared by their calls.
+      if program.body(&instance.item).is_some() {
+        self.add_function(&codegen, instance);
+      }
+    }
+    if let Some(debug_info) = &self.debug_info {
+      debug_info.finalize();
+    }
+    self
+      .module
+      .verify()
+      .map_err(|message| VspError::new(format!("invalid module: {}", message)))
+  }
+
+  /// Check that the functions with the C calling convention pass no aggregates by value, unless the
+  /// C ABI of the target is System V x86-64, the only one they are implemented for.
+  fn check_c_abi(&self, program: &Program, items: &ItemTable) -> VspResult<()> {
+    let triple = self.module.get_triple();
+    let spec = match target_spec(&triple.as_str().to_string_lossy()) {
+      Some(spec) if spec.abi != CAbi::SysV64 => spec,
+      _ => return Ok(()),
+    };
+    let foreign = program
+      .foreign
+      .iter()
+      .map(|function| (&function.def, function.params.clone(), &function.ret));
+    let exported = program.bodies.iter().filter_map(|body| match &body.owner {
+      MonoItem::Function(def) if items.functions[def].abi.is_c() => {
+        let params = body.args().map(|arg| body.local(arg).ty.clone()).collect();
+        Some((def, params, body.return_type()))
+      }
+      _ => None,
+    });
+    for (def, params, ret) in foreign.chain(exported) {
+      if let Some(ty) = find_aggregate(program, &params, ret) {
+        return VspError::new(format!(
+          "function `{}` passes `{}` by value, which the C ABI of `{}` does not support yet",
+          def.name, ty, spec.triple
+        ))
+        .to_err();
+      }
+    }
+    Ok(())
+  }
+
+  /// Generate the function of the instance with its parameters and body, where the functions it
+  /// calls are declared.
+  pub fn add_function(
+    &self,
+    codegen: &ProgramCodegen<'_, 'ctx>,
+    instance: &Instance,
+  ) -> FunctionValue<'ctx> {
+    codegen.define_instance(instance)
+  }
 }
 
-/// Add default LLVM passes to the pass manager.
-#[inline]
-fn add_default_pass(manager: &mut PassManager<Module>) {
-  manager.add_promote_memory_to_register_pass();
-  manager.add_demote_memory_to_register_pass();
-  manager.add_argument_promotion_pass();
-  manager.add_always_inliner_pass();
-  manager.add_gvn_pass();
-  manager.add_new_gvn_pass();
-  manager.add_function_attrs_pass();
-  manager.add_prune_eh_pass();
-  manager.add_constant_merge_pass();
-  manager.add_scalarizer_pass();
-  manager.add_merged_load_store_motion_pass();
-  manager.add_instruction_combining_pass();
-  manager.add_memcpy_optimize_pass();
-  manager.add_partially_inline_lib_calls_pass();
-  manager.add_lower_switch_pass();
-  manager.add_reassociate_pass();
-  manager.add_simplify_lib_calls_pass();
-  manager.add_aggressive_inst_combiner_pass();
-  manager.add_instruction_simplify_pass();
-  manager.add_function_inlining_pass();
-  manager.add_global_optimizer_pass();
-  manager.add_dead_arg_elimination_pass();
-  manager.add_strip_symbol_pass();
-  manager.add_strip_dead_prototypes_pass();
-  manager.add_internalize_pass(true);
-  manager.add_sccp_pass();
-  manager.add_aggressive_dce_pass();
-  manager.add_global_dce_pass();
-  manager.add_tail_call_elimination_pass();
-  manager.add_basic_alias_analysis_pass();
-  manager.add_licm_pass();
-  manager.add_ind_var_simplify_pass();
-  manager.add_loop_vectorize_pass();
-  manager.add_loop_unswitch_pass();
-  manager.add_loop_idiom_pass();
-  manager.add_loop_rotate_pass();
-  manager.add_loop_unroll_and_jam_pass();
-  manager.add_loop_unroll_pass();
-  manager.add_loop_deletion_pass();
-  manager.add_cfg_simplification_pass();
-  manager.add_verifier_pass();
+/// Mark the RISC-V object file to follow the double-float ABI in the flags of its ELF header. LLVM
+/// passes the floating point values in the registers by the `target-abi` flag of the module, but
+/// marks the object by the options of the target machine only, which are soft-float by default.
+fn set_double_float_abi(path: &Path) -> VspResult<()> {
+  /// Offset of `e_flags` in the header of the 64-bit ELF files.
+  const FLAGS_OFFSET: usize = 48;
+  /// Bits of the floating point ABI in `e_flags`, `EF_RISCV_FLOAT_ABI`.
+  const FLOAT_ABI: u32 = 0x6;
+  /// Double-float ABI, `EF_RISCV_FLOAT_ABI_DOUBLE`.
+  const FLOAT_ABI_DOUBLE: u32 = 0x4;
+
+  let mut object = std::fs::read(path).map_err(VspError::from)?;
+  let bytes = object
+    .get_mut(FLAGS_OFFSET..FLAGS_OFFSET + 4)
+    .ok_or_else(|| write_error(path, "the ELF header is truncated".to_owned()))?;
+  let flags = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+  bytes.copy_from_slice(&((flags & !FLOAT_ABI) | FLOAT_ABI_DOUBLE).to_le_bytes());
+  std::fs::write(path, object).map_err(VspError::from)
 }
 
-/// Add customized LLVM passes to the pass manager.
-#[inline]
-fn add_customized_pass(_manager: &mut PassManager<Module>) {}
+fn write_error(path: &Path, message: String) -> VspError {
+  VspError::new(format!("could not write `{}`: {}", path.display(), message))
+}
@@ -0,0 +1,291 @@
+//! Optimization pipelines run by the new pass manager of LLVM.
+//!
+//! The pipeline of an optimization level is a list of passes in the textual format of `opt
+//! -passes`, such as `sroa` or `loop-mssa(licm)`, which are run one by one so that each of them
+//! could be timed. The levels trade off as follows:
+//!
+//! - `-O0` only inlines the functions that must be inlined;
+//! - `-O1` cleans up the generated code by promoting the allocas into registers, and simplifying
+//!   the instructions and the control flow;
+//! - `-O2` also inlines the calls, eliminates the redundancies and the dead stores, hoists the
+//!   invariants out of the loops, unrolls and vectorizes them;
+//! - `-O3` also promotes the arguments passed by pointer, and unrolls more aggressively;
+//! - `-Os` and `-Oz` run the passes of `-O2` except the unrolling and the vectorization, which grow
+//!   the code, and mark the functions `optsize`, and also `minsize` under `-Oz`.
+use std::fmt::Write;
+use std::time::Duration;
+use std::time::Instant;
+
+use inkwell::attributes::Attribute;
+use inkwell::attributes::AttributeLoc;
+use inkwell::module::Module;
+use inkwell::passes::PassBuilderOptions;
+use inkwell::targets::TargetMachine;
+use inkwell::OptimizationLevel;
+use vsp_error::VspError;
+use vsp_error::VspResult;
+
+/// Passes run at every level above `-O0`, to clean up the generated code.
+const CLEANUP_PASSES: &[&str] = &["sroa", "early-cse", "simplifycfg", "instcombine"];
+
+/// Passes of the middle of the pipeline under `-O2` and above, and the size levels.
+const SCALAR_PASSES: &[&str] = &[
+  "inline",
+  "sroa",
+  "gvn",
+  "sccp",
+  "instcombine",
+  "memcpyopt",
+  "dse",
+  "loop-mssa(licm)",
+  "loop-rotate",
+  "indvars",
+  "loop-deletion",
+];
+
+/// Passes at the end of the pipeline at every level above `-O0`.
+const FINAL_PASSES: &[&str] = &[
+  "adce",
+  "simplifycfg",
+  "instcombine",
+  "globaldce",
+  "constmerge",
+];
+
+/// Pipeline of the optimization passes to run on a module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pipeline {
+  /// Passes in the textual format of `opt -passes`.
+  passes: Vec<String>,
+  /// Speed level from 0 to 3.
+  speed:  u8,
+  /// Size level, 1 for `-Os` and 2 for `-Oz`.
+  size:   u8,
+}
+
+impl Pipeline {
+  /// Pipeline of the optimization level, by the speed level from 0 to 3 and the size level, which
+  /// is 1 for `-Os` and 2 for `-Oz`.
+  pub fn default_for(speed: u8, size: u8) -> Self {
+    let mut passes = Vec::new();
+    if speed == 0 && size == 0 {
+      passes.push("always-inline");
+    } else {
+      passes.extend_from_slice(CLEANUP_PASSES);
+      if speed >= 3 {
+        passes.extend_from_slice(&["argpromotion", "aggressive-instcombine"]);
+      }
+      if speed >= 2 || size > 0 {
+        passes.extend_from_slice(SCALAR_PASSES);
+      }
+      if speed >= 2 && size == 0 {
+        passes.push(if speed >= 3 {
+          "loop-unroll<O3>"
+        } else {
+          "loop-unroll<O2>"
+        });
+        passes.extend_from_slice(&["loop-vectorize", "slp-vectorizer"]);
+      }
+      passes.extend_from_slice(FINAL_PASSES);
+    }
+    Self {
+      passes: passes.into_iter().map(str::to_owned).collect(),
+      speed,
+      size,
+    }
+  }
+
+  /// Pipeline of the passes listed by `--passes=`, separated by the commas outside of the
+  /// parentheses, such as `sroa,function(instcombine,gvn)`, instead of those of the level.
+  pub fn custom(passes: &str, speed: u8, size: u8) -> VspResult<Self> {
+    let mut list = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (index, c) in passes.char_indices() {
+      match c {
+        '(' | '<' => depth += 1,
+        ')' | '>' if depth == 0 => {
+          return VspError::new(format!("unbalanced `{}` in the passes `{}`", c, passes)).to_err();
+        }
+        ')' | '>' => depth -= 1,
+        ',' if depth == 0 => {
+          list.push(passes[start..index].trim().to_owned());
+          start = index + 1;
+        }
+        _ => {}
+      }
+    }
+    if depth > 0 {
+      return VspError::new(format!("unclosed parenthesis in the passes `{}`", passes)).to_err();
+    }
+    list.push(passes[start..].trim().to_owned());
+    if list.iter().any(String::is_empty) {
+      return VspError::new(format!("empty pass in the passes `{}`", passes)).to_err();
+    }
+    Ok(Self {
+      passes: list,
+      speed,
+      size,
+    })
+  }
+
+  pub fn passes(&self) -> &[String] {
+    &self.passes
+  }
+
+  /// Optimization level of the code generation by the target machine.
+  pub fn codegen_level(&self) -> OptimizationLevel {
+    match (self.speed, self.size) {
+      (_, 1..) => OptimizationLevel::Default,
+      (0, _) => OptimizationLevel::None,
+      (1, _) => OptimizationLevel::Less,
+      (2, _) => OptimizationLevel::Default,
+      _ => OptimizationLevel::Aggressive,
+    }
+  }
+
+  /// Run the passes one by one on the module for the target machine, and time each of them.
+  pub fn run(&self, module: &Module, machine: &TargetMachine) -> VspResult<Vec<PassTiming>> {
+    self.add_size_attributes(module);
+    let mut timings = Vec::new();
+    for pass in &self.passes {
+      let start = Instant::now();
+      module.run_passes(pass, machine, self.builder_options()).map_err(|message| {
+        VspError::new(format!("invalid pass `{}`: {}", pass, message.to_string()))
+      })?;
+      timings.push(PassTiming {
+        pass:     pass.clone(),
+        duration: start.elapsed(),
+      });
+    }
+    Ok(timings)
+  }
+
+  fn builder_options(&self) -> PassBuilderOptions {
+    let options = PassBuilderOptions::create();
+    let fast = self.speed >= 2 && self.size == 0;
+    options.set_loop_vectorization(fast);
+    options.set_loop_slp_vectorization(fast);
+    options.set_loop_unrolling(fast);
+    options.set_merge_functions(self.size > 0);
+    options
+  }
+
+  /// Mark the defined functions to be optimized for size under `-Os` and `-Oz`, which the passes
+  /// respect when they choose between speed and size, such as inlining.
+  fn add_size_attributes(&self, module: &Module) {
+    let context = module.get_context();
+    let kinds: &[&str] = match self.size {
+      0 => &[],
+      1 => &["optsize"],
+      _ => &["optsize", "minsize"],
+    };
+    for function in module.get_functions() {
+      if function.count_basic_blocks() == 0 {
+        continue;
+      }
+      for kind in kinds {
+        let kind = Attribute::get_named_enum_kind_id(kind);
+        function.add_attribute(
+          AttributeLoc::Function,
+          context.create_enum_attribute(kind, 0),
+        );
+      }
+    }
+  }
+}
+
+/// Time spent by a pass of the pipeline.
+#[derive(Clone, Debug)]
+pub struct PassTiming {
+  pub pass:     String,
+  pub duration: Duration,
+}
+
+/// Report of the time spent by the passes in the order they run, with their percents of the total.
+pub fn timing_report(timings: &[PassTiming]) -> String {
+  let total: Duration = timings.iter().map(|timing| timing.duration).sum();
+  let percent = |duration: Duration| {
+    if total.is_zero() {
+      0.0
+    } else {
+      duration.as_secs_f64() / total.as_secs_f64() * 100.0
+    }
+  };
+  let mut report = String::from("===-- Pass execution timing report --===\n");
+  report.push_str("  Time (ms)  Percent  Pass\n");
+  for timing in timings.iter().chain([&PassTiming {
+    pass:     "Total".to_owned(),
+    duration: total,
+  }]) {
+    let _ = writeln!(
+      report,
+      "{:>11.3}  {:>6.1}%  {}",
+      timing.duration.as_secs_f64() * 1000.0,
+      percent(timing.duration),
+      timing.pass
+    );
+  }
+  report
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_default_pipelines() {
+    assert_eq!(Pipeline::default_for(0, 0).passes(), ["always-inline"]);
+    let o1 = Pipeline::default_for(1, 0);
+    assert!(o1.passes().starts_with(&["sroa".to_owned()]));
+    assert!(!o1.passes().contains(&"inline".to_owned()));
+    let o2 = Pipeline::default_for(2, 0);
+    assert!(o2.passes().contains(&"inline".to_owned()));
+    assert!(o2.passes().contains(&"loop-unroll<O2>".to_owned()));
+    assert!(!o2.passes().contains(&"argpromotion".to_owned()));
+    let o3 = Pipeline::default_for(3, 0);
+    assert!(o3.passes().contains(&"loop-unroll<O3>".to_owned()));
+    assert!(o3.passes().contains(&"argpromotion".to_owned()));
+    for size in [1, 2] {
+      let pipeline = Pipeline::default_for(2, size);
+      assert!(pipeline.passes().contains(&"inline".to_owned()));
+      assert!(!pipeline.passes().iter().any(|pass| pass.starts_with("loop-unroll")));
+      assert!(!pipeline.passes().contains(&"loop-vectorize".to_owned()));
+      assert_eq!(pipeline.codegen_level(), OptimizationLevel::Default);
+    }
+    assert_eq!(o1.codegen_level(), OptimizationLevel::Less);
+    assert_eq!(o3.codegen_level(), OptimizationLevel::Aggressive);
+  }
+
+  #[test]
+  fn test_custom_pipelines() {
+    let pipeline =
+      Pipeline::custom("sroa, function(instcombine,gvn),loop-unroll<O3>", 2, 0).unwrap();
+    assert_eq!(
+      pipeline.passes(),
+      ["sroa", "function(instcombine,gvn)", "loop-unroll<O3>"]
+    );
+    let message = |passes| Pipeline::custom(passes, 0, 0).unwrap_err().message();
+    assert!(message("sroa,,gvn").contains("empty pass"));
+    assert!(message("function(gvn").contains("unclosed"));
+    assert!(message("gvn)").contains("unbalanced `)`"));
+  }
+
+  #[test]
+  fn test_timing_report() {
+    let report = timing_report(&[
+      PassTiming {
+        pass:     "sroa".to_owned(),
+        duration: Duration::from_millis(3),
+      },
+      PassTiming {
+        pass:     "gvn".to_owned(),
+        duration: Duration::from_millis(1),
+      },
+    ]);
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines[2], "      3.000    75.0%  sroa");
+    assert_eq!(lines[3], "      1.000    25.0%  gvn");
+    assert_eq!(lines[4], "      4.000   100.0%  Total");
+  }
+}
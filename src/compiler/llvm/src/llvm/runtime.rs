@@ -0,0 +1,123 @@
+//! Runtime functions which the generated code calls, defined by the C library.
+//!
+//! The C library does not define `print` and `println` of the prelude, which the JIT binds to its
+//! natives. The modules linked into the artifacts define them instead, by `printf` and `puts` of
+//! the C library. The panics of the failed runtime checks, such as an overflow or an index out of
+//! bounds, are defined in every module, so that both the JIT and the artifacts report the error as
+//! the interpreter does. The definitions are internal to each module, so that the objects of
+//! several modules could be linked together.
+use inkwell::attributes::Attribute;
+use inkwell::attributes::AttributeLoc;
+use inkwell::module::Linkage;
+use inkwell::module::Module;
+use inkwell::types::BasicMetadataTypeEnum;
+use inkwell::values::BasicMetadataValueEnum;
+use inkwell::values::FunctionValue;
+use inkwell::AddressSpace;
+
+/// Define the output functions of the prelude which the module declares without bodies: `print`
+/// writes the string by `printf`, and `println` writes it with a new line by `puts`.
+pub fn define_output_functions(module: &Module) {
+  let context = module.get_context();
+  let builder = context.create_builder();
+  let string = context.i8_type().ptr_type(AddressSpace::default());
+  let c_function = |name: &str, variadic: bool| {
+    module.get_function(name).unwrap_or_else(|| {
+      let ty = context.i32_type().fn_type(&[string.into()], variadic);
+      module.add_function(name, ty, None)
+    })
+  };
+  if let Some(print) = declared(module, "print") {
+    let printf = c_function("printf", true);
+    builder.position_at_end(context.append_basic_block(print, "entry"));
+    let format = builder.build_global_string_ptr("%s", "print.format");
+    let s = print.get_first_param().unwrap();
+    builder.build_call(printf, &[format.as_pointer_value().into(), s.into()], "");
+    builder.build_return(None);
+    print.set_linkage(Linkage::Internal);
+  }
+  if let Some(println) = declared(module, "println") {
+    let puts = c_function("puts", false);
+    builder.position_at_end(context.append_basic_block(println, "entry"));
+    let s = println.get_first_param().unwrap();
+    builder.build_call(puts, &[s.into()], "");
+    builder.build_return(None);
+    println.set_linkage(Linkage::Internal);
+  }
+}
+
+/// Function of the name declared by the module without a body.
+fn declared<'ctx>(module: &Module<'ctx>, name: &str) -> Option<FunctionValue<'ctx>> {
+  module.get_function(name).filter(|function| function.count_basic_blocks() == 0)
+}
+
+/// Name of the function which reports the failed runtime check and exits the program.
+pub const PANIC: &str = "vsp.panic";
+/// Name of the function which reports the index out of bounds of an array and exits the program.
+pub const BOUNDS_PANIC: &str = "vsp.panic_bounds";
+
+/// Declare the panic in the module, defining it on the first use: the message is written to the
+/// standard error with a new line by `dprintf`, and the program exits with the code `1`, as the
+/// interpreter does on errors.
+pub fn declare_panic<'ctx>(module: &Module<'ctx>) -> FunctionValue<'ctx> {
+  let string = module.get_context().i8_type().ptr_type(AddressSpace::default());
+  define_panic(module, PANIC, &[string.into()], "%s\n")
+}
+
+/// Declare the panic of the index out of bounds, which takes the location, the length of the
+/// array and the index as `int64`, in the same way as [`declare_panic`].
+pub fn declare_bounds_panic<'ctx>(module: &Module<'ctx>) -> FunctionValue<'ctx> {
+  let context = module.get_context();
+  let string = context.i8_type().ptr_type(AddressSpace::default());
+  let int64 = context.i64_type();
+  define_panic(
+    module,
+    BOUNDS_PANIC,
+    &[string.into(), int64.into(), int64.into()],
+    "%s: index out of bounds: the length is %lld but the index is %lld\n",
+  )
+}
+
+/// Function of the name which writes its arguments by the format to the standard error, and exits
+/// the program with the code `1`.
+fn define_panic<'ctx>(
+  module: &Module<'ctx>,
+  name: &str,
+  params: &[BasicMetadataTypeEnum<'ctx>],
+  format: &str,
+) -> FunctionValue<'ctx> {
+  if let Some(panic) = module.get_function(name) {
+    return panic;
+  }
+  let context = module.get_context();
+  let string = context.i8_type().ptr_type(AddressSpace::default());
+  let ty = context.void_type().fn_type(params, false);
+  let panic = module.add_function(name, ty, Some(Linkage::Internal));
+  for name in ["noreturn", "cold", "noinline"] {
+    let kind = Attribute::get_named_enum_kind_id(name);
+    panic.add_attribute(
+      AttributeLoc::Function,
+      context.create_enum_attribute(kind, 0),
+    );
+  }
+
+  let i32_type = context.i32_type();
+  let dprintf = module.get_function("dprintf").unwrap_or_else(|| {
+    let ty = i32_type.fn_type(&[i32_type.into(), string.into()], true);
+    module.add_function("dprintf", ty, None)
+  });
+  let exit = module.get_function("exit").unwrap_or_else(|| {
+    let ty = context.void_type().fn_type(&[i32_type.into()], false);
+    module.add_function("exit", ty, None)
+  });
+  let builder = context.create_builder();
+  builder.position_at_end(context.append_basic_block(panic, "entry"));
+  let format = builder.build_global_string_ptr(format, "panic.format");
+  let stderr = i32_type.const_int(2, false);
+  let mut args: Vec<BasicMetadataValueEnum> = vec![stderr.into(), format.as_pointer_value().into()];
+  args.extend(panic.get_param_iter().map(BasicMetadataValueEnum::from));
+  builder.build_call(dprintf, &args, "");
+  builder.build_call(exit, &[i32_type.const_int(1, false).into()], "");
+  builder.build_unreachable();
+  panic
+}
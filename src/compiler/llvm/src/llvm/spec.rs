@@ -0,0 +1,167 @@
+//! Built-in specifications of the targets which vsp compiles for.
+//!
+//! A target is named by the triple given to `--target`, which is the LLVM triple of the target
+//! unless it names the CPU features as well, such as `riscv64gc`. The spec tells what LLVM does not
+//! know about the target by the triple alone: the CPU and features it runs by default, the C ABI
+//! and the linker of its artifacts. The data layout is the one LLVM chooses for the triple, which
+//! the spec records to check that vsp and LLVM agree on it.
+//!
+//! Other triples are left to LLVM as they are, with the generic CPU and the host linker.
+
+/// C calling convention of a target, which the foreign functions and the functions exported by
+/// `extern "C"` follow.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CAbi {
+  /// System V x86-64.
+  SysV64,
+  /// Procedure call standard of AArch64.
+  Aapcs64,
+  /// RISC-V LP64 with the floating point values in the double precision registers.
+  Lp64d,
+  /// Basic C ABI of WebAssembly.
+  Wasm,
+}
+
+impl CAbi {
+  /// Name of the ABI in the `target-abi` flag of the module, for the targets whose ABI is not
+  /// implied by the triple.
+  pub fn llvm_name(&self) -> Option<&'static str> {
+    match self {
+      Self::Lp64d => Some("lp64d"),
+      _ => None,
+    }
+  }
+}
+
+/// Specification of a target.
+#[derive(Clone, Debug)]
+pub struct TargetSpec {
+  /// Triple given to `--target`.
+  pub triple:        &'static str,
+  /// Triple of the target for LLVM.
+  pub llvm_triple:   &'static str,
+  /// Width of the pointers in bits.
+  pub pointer_width: u32,
+  /// Data layout of the modules for the target.
+  pub data_layout:   &'static str,
+  pub abi:           CAbi,
+  /// CPU run by default, instead of the generic CPU.
+  pub cpu:           &'static str,
+  /// Features enabled by default, before those given by `-C target-feature=`.
+  pub features:      &'static str,
+  /// Linker program of the artifacts, unless the target is the host.
+  pub linker:        &'static str,
+  /// Flavor of the linker, as in `-C linker-flavor=`.
+  pub linker_flavor: &'static str,
+}
+
+/// Targets with built-in specs.
+pub const TARGETS: &[TargetSpec] = &[
+  TargetSpec {
+    triple:        "x86_64-unknown-linux-gnu",
+    llvm_triple:   "x86_64-unknown-linux-gnu",
+    pointer_width: 64,
+    data_layout:   "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128",
+    abi:           CAbi::SysV64,
+    cpu:           "x86-64",
+    features:      "",
+    linker:        "cc",
+    linker_flavor: "cc",
+  },
+  TargetSpec {
+    triple:        "x86_64-unknown-linux-musl",
+    llvm_triple:   "x86_64-unknown-linux-musl",
+    pointer_width: 64,
+    data_layout:   "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128",
+    abi:           CAbi::SysV64,
+    cpu:           "x86-64",
+    features:      "",
+    linker:        "musl-gcc",
+    linker_flavor: "cc",
+  },
+  TargetSpec {
+    triple:        "aarch64-unknown-linux-gnu",
+    llvm_triple:   "aarch64-unknown-linux-gnu",
+    pointer_width: 64,
+    data_layout:   "e-m:e-i8:8:32-i16:16:32-i64:64-i128:128-n32:64-S128",
+    abi:           CAbi::Aapcs64,
+    cpu:           "generic",
+    features:      "+v8a,+neon",
+    linker:        "aarch64-linux-gnu-gcc",
+    linker_flavor: "cc",
+  },
+  TargetSpec {
+    triple:        "riscv64gc-unknown-linux-gnu",
+    llvm_triple:   "riscv64-unknown-linux-gnu",
+    pointer_width: 64,
+    data_layout:   "e-m:e-p:64:64-i64:64-i128:128-n64-S128",
+    abi:           CAbi::Lp64d,
+    cpu:           "generic-rv64",
+    features:      "+m,+a,+f,+d,+c",
+    linker:        "riscv64-linux-gnu-gcc",
+    linker_flavor: "cc",
+  },
+  TargetSpec {
+    triple:        "wasm32-unknown-unknown",
+    llvm_triple:   "wasm32-unknown-unknown",
+    pointer_width: 32,
+    data_layout:   "e-m:e-p:32:32-p10:8:8-p20:8:8-i64:64-n32:64-S128-ni:1:10:20",
+    abi:           CAbi::Wasm,
+    cpu:           "generic",
+    features:      "",
+    linker:        "wasm-ld",
+    linker_flavor: "wasm-ld",
+  },
+];
+
+/// Spec of the target named by the triple given to `--target`, or by its LLVM triple.
+pub fn target_spec(triple: &str) -> Option<&'static TargetSpec> {
+  TARGETS.iter().find(|spec| spec.triple == triple || spec.llvm_triple == triple)
+}
+
+#[cfg(test)]
+mod tests {
+  use inkwell::OptimizationLevel;
+
+  use super::*;
+  use crate::llvm::target::create_target_machine;
+  use crate::llvm::target::MachineOptions;
+
+  #[test]
+  fn test_data_layouts() {
+    for spec in TARGETS {
+      let machine = create_target_machine(&MachineOptions {
+        triple: spec.triple.to_owned(),
+        cpu: String::new(),
+        features: String::new(),
+        ..MachineOptions::native(OptimizationLevel::Default)
+      })
+      .unwrap();
+      let data = machine.get_target_data();
+      assert_eq!(
+        data.get_data_layout().as_str().to_str().unwrap(),
+        spec.data_layout,
+        "{}",
+        spec.triple
+      );
+      assert_eq!(data.get_pointer_byte_size(None) * 8, spec.pointer_width);
+    }
+  }
+
+  #[test]
+  fn test_target_spec() {
+    assert_eq!(
+      target_spec("riscv64gc-unknown-linux-gnu").unwrap().abi,
+      CAbi::Lp64d
+    );
+    assert_eq!(
+      target_spec("riscv64-unknown-linux-gnu").unwrap().triple,
+      "riscv64gc-unknown-linux-gnu"
+    );
+    assert_eq!(
+      target_spec("wasm32-unknown-unknown").unwrap().pointer_width,
+      32
+    );
+    assert!(target_spec("mips-unknown-linux-gnu").is_none());
+  }
+}
@@ -0,0 +1,162 @@
+//! Target machines which the modules are optimized and emitted for.
+use inkwell::targets::CodeModel;
+use inkwell::targets::InitializationConfig;
+use inkwell::targets::RelocMode;
+use inkwell::targets::Target;
+use inkwell::targets::TargetMachine;
+use inkwell::targets::TargetTriple;
+use inkwell::OptimizationLevel;
+use vsp_error::VspError;
+use vsp_error::VspResult;
+
+use crate::llvm::spec::target_spec;
+
+/// CPU name standing for the host CPU along with its features, as `-C target-cpu=native`.
+pub const NATIVE_CPU: &str = "native";
+
+/// Options of the target machine.
+#[derive(Clone, Debug)]
+pub struct MachineOptions {
+  /// Target triple, such as `x86_64-unknown-linux-gnu`.
+  pub triple:     String,
+  /// CPU name, such as `skylake`, or [`NATIVE_CPU`], or empty for the default CPU of the target.
+  pub cpu:        String,
+  /// Comma separated features enabled by `+` or disabled by `-`, such as `+avx2,-sse4a`.
+  pub features:   String,
+  pub reloc:      RelocMode,
+  pub code_model: CodeModel,
+  pub level:      OptimizationLevel,
+}
+
+impl MachineOptions {
+  /// Options of the host machine at the level.
+  pub fn native(level: OptimizationLevel) -> Self {
+    Self {
+      triple: TargetMachine::get_default_triple().as_str().to_string_lossy().into_owned(),
+      cpu: NATIVE_CPU.to_owned(),
+      features: String::new(),
+      reloc: RelocMode::Default,
+      code_model: CodeModel::Default,
+      level,
+    }
+  }
+}
+
+/// Create the target machine by the options, where the native CPU comes with the features of the
+/// host, and the other CPUs of the targets with built-in specs with the features of their specs,
+/// before the features given. The CPU of the spec is the default one.
+pub fn create_target_machine(options: &MachineOptions) -> VspResult<TargetMachine> {
+  Target::initialize_all(&InitializationConfig::default());
+  let spec = target_spec(&options.triple);
+  let triple = TargetTriple::create(spec.map_or(options.triple.as_str(), |spec| spec.llvm_triple));
+  let target = Target::from_triple(&triple).map_err(|message| {
+    VspError::new(format!(
+      "unsupported target `{}`: {}",
+      options.triple,
+      message.to_string()
+    ))
+  })?;
+  let (cpu, mut features) = if options.cpu == NATIVE_CPU {
+    (
+      TargetMachine::get_host_cpu_name().to_string(),
+      TargetMachine::get_host_cpu_features().to_string(),
+    )
+  } else {
+    let cpu = match spec {
+      Some(spec) if options.cpu.is_empty() => spec.cpu.to_owned(),
+      _ => options.cpu.clone(),
+    };
+    (cpu, spec.map_or("", |spec| spec.features).to_owned())
+  };
+  if !options.features.is_empty() {
+    if !features.is_empty() {
+      features.push(',');
+    }
+    features.push_str(&options.features);
+  }
+  target
+    .create_target_machine(
+      &triple,
+      &cpu,
+      &features,
+      options.level,
+      options.reloc,
+      options.code_model,
+    )
+    .ok_or_else(|| {
+      VspError::new(format!(
+        "could not create the target machine for `{}`",
+        options.triple
+      ))
+    })
+}
+
+/// Target machine of the host, which the code is generated for at the level.
+pub fn native_target_machine(level: OptimizationLevel) -> VspResult<TargetMachine> {
+  create_target_machine(&MachineOptions::native(level))
+}
+
+/// Relocation model by its name in `-C relocation-model=`.
+pub fn reloc_mode(name: &str) -> VspResult<RelocMode> {
+  match name {
+    "default" => Ok(RelocMode::Default),
+    "static" => Ok(RelocMode::Static),
+    "pic" => Ok(RelocMode::PIC),
+    "dynamic-no-pic" => Ok(RelocMode::DynamicNoPic),
+    _ => VspError::new(format!(
+      "unknown relocation model `{}`, expected default, static, pic or dynamic-no-pic",
+      name
+    ))
+    .to_err(),
+  }
+}
+
+/// Code model by its name in `-C code-model=`.
+pub fn code_model(name: &str) -> VspResult<CodeModel> {
+  match name {
+    "default" => Ok(CodeModel::Default),
+    "small" => Ok(CodeModel::Small),
+    "kernel" => Ok(CodeModel::Kernel),
+    "medium" => Ok(CodeModel::Medium),
+    "large" => Ok(CodeModel::Large),
+    _ => VspError::new(format!(
+      "unknown code model `{}`, expected default, small, kernel, medium or large",
+      name
+    ))
+    .to_err(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_target_machine() {
+    let mut options = MachineOptions::native(OptimizationLevel::Default);
+    let machine = create_target_machine(&options).unwrap();
+    assert_eq!(machine.get_triple(), TargetMachine::get_default_triple());
+    assert_eq!(
+      machine.get_cpu().to_string(),
+      TargetMachine::get_host_cpu_name().to_string()
+    );
+
+    options.cpu = "x86-64".to_owned();
+    options.features = "+avx2".to_owned();
+    options.reloc = reloc_mode("pic").unwrap();
+    options.code_model = code_model("large").unwrap();
+    let machine = create_target_machine(&options).unwrap();
+    assert_eq!(machine.get_cpu().to_string(), "x86-64");
+    assert_eq!(machine.get_feature_string().to_str().unwrap(), "+avx2");
+
+    options.triple = "unknown-none-none".to_owned();
+    let message = create_target_machine(&options).unwrap_err().message();
+    assert!(
+      message.starts_with("unsupported target `unknown-none-none`"),
+      "{}",
+      message
+    );
+    assert!(reloc_mode("pie").is_err());
+    assert!(code_model("tiny").is_err());
+  }
+}
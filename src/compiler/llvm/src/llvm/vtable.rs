@@ -0,0 +1,336 @@
+//! Trait objects
+//!
+//! A trait object `dyn Trait` is a fat pointer made of the pointer to the value and the pointer to
+//! the vtable of the implementation of the trait for the type of the value, both erased to `i8*`.
+//!
+//! ```plaintext
+//! dyn Trait = { i8* data, i8* vtable }
+//! vtable    = { i64 size, i64 align, [N x i8*] methods }
+//! ```
+//!
+//! Methods are stored in the order of their declarations in the trait. The methods of the
+//! implementation take the receiver by value or by typed pointer, so each entry of the vtable is a
+//! shim taking the erased pointer, which casts it back, loads the receiver passed by value, and
+//! forwards the call. The receiver follows the pointer to the return value of the methods which
+//! return it by pointer, i.e. return `void`.
+use inkwell::builder::Builder;
+use inkwell::context::Context;
+use inkwell::module::Linkage;
+use inkwell::module::Module;
+use inkwell::types::BasicMetadataTypeEnum;
+use inkwell::types::BasicType;
+use inkwell::types::BasicTypeEnum;
+use inkwell::types::FunctionType;
+use inkwell::types::PointerType;
+use inkwell::types::StructType;
+use inkwell::values::BasicMetadataValueEnum;
+use inkwell::values::BasicValueEnum;
+use inkwell::values::CallableValue;
+use inkwell::values::FunctionValue;
+use inkwell::values::GlobalValue;
+use inkwell::values::IntValue;
+use inkwell::values::PointerValue;
+use inkwell::values::StructValue;
+use inkwell::AddressSpace;
+
+/// Index of the method table in the vtable, after the size and the alignment of the value.
+const METHODS_INDEX: u32 = 2;
+
+fn erased_pointer_type(context: &Context) -> PointerType<'_> {
+  context.i8_type().ptr_type(AddressSpace::default())
+}
+
+/// Type of the trait objects, which is the same for all traits.
+pub(crate) fn trait_object_type(context: &Context) -> StructType<'_> {
+  let pointer = erased_pointer_type(context).as_basic_type_enum();
+  context.struct_type(&[pointer, pointer], false)
+}
+
+fn vtable_type(context: &Context, methods: u32) -> StructType<'_> {
+  let i64_type = context.i64_type().as_basic_type_enum();
+  let methods = erased_pointer_type(context).array_type(methods).as_basic_type_enum();
+  context.struct_type(&[i64_type, i64_type, methods], false)
+}
+
+/// Index of the receiver in the parameters of the method, after the pointer to the return value
+/// of the methods returning `void`.
+fn receiver_index(method: FunctionType<'_>) -> usize {
+  method.get_return_type().is_none() as usize
+}
+
+/// Type of the vtable entry for the method, which takes the erased receiver instead.
+pub fn dyn_method_type<'ctx>(
+  context: &'ctx Context,
+  method: FunctionType<'ctx>,
+) -> FunctionType<'ctx> {
+  let mut params = method.get_param_types();
+  if let Some(receiver) = params.get_mut(receiver_index(method)) {
+    *receiver = erased_pointer_type(context).as_basic_type_enum();
+  }
+  let params = params.into_iter().map(BasicMetadataTypeEnum::from).collect::<Vec<_>>();
+  match method.get_return_type() {
+    Some(ret) => ret.fn_type(&params, false),
+    None => context.void_type().fn_type(&params, false),
+  }
+}
+
+fn alignment_of(ty: BasicTypeEnum<'_>) -> IntValue<'_> {
+  match ty {
+    BasicTypeEnum::ArrayType(ty) => ty.get_alignment(),
+    BasicTypeEnum::FloatType(ty) => ty.get_alignment(),
+    BasicTypeEnum::IntType(ty) => ty.get_alignment(),
+    BasicTypeEnum::PointerType(ty) => ty.get_alignment(),
+    BasicTypeEnum::StructType(ty) => ty.get_alignment(),
+    BasicTypeEnum::VectorType(ty) => ty.get_alignment(),
+  }
+}
+
+/// Builder of the vtables and the dynamic calls through them.
+pub struct VtableBuilder<'a, 'ctx> {
+  context: &'ctx Context,
+  module:  &'a Module<'ctx>,
+}
+
+impl<'a, 'ctx> VtableBuilder<'a, 'ctx> {
+  pub fn new(context: &'ctx Context, module: &'a Module<'ctx>) -> Self {
+    Self { context, module }
+  }
+
+  /// Emit the vtable of the implementation as a constant global named `name`. The methods are in
+  /// the order of the trait, and take `self_type` or the pointer to it as the receiver.
+  pub fn build_vtable(
+    &self,
+    name: &str,
+    self_type: BasicTypeEnum<'ctx>,
+    methods: &[FunctionValue<'ctx>],
+  ) -> GlobalValue<'ctx> {
+    let erased = erased_pointer_type(self.context);
+    let entries = methods
+      .iter()
+      .map(|method| {
+        let shim = self.build_shim(*method, self_type);
+        shim.as_global_value().as_pointer_value().const_cast(erased)
+      })
+      .collect::<Vec<_>>();
+    let i64_type = self.context.i64_type();
+    let size = self_type.size_of().unwrap_or_else(|| i64_type.const_zero());
+    let align = alignment_of(self_type);
+    let ty = vtable_type(self.context, methods.len() as u32);
+    let vtable = self.module.add_global(ty, None, name);
+    vtable.set_linkage(Linkage::Private);
+    vtable.set_constant(true);
+    vtable.set_initializer(&ty.const_named_struct(&[
+      size.const_cast(i64_type, false).into(),
+      align.const_cast(i64_type, false).into(),
+      erased.const_array(&entries).into(),
+    ]));
+    vtable
+  }
+
+  /// The shim taking the erased receiver, which casts it back and calls the method.
+  fn build_shim(
+    &self,
+    method: FunctionValue<'ctx>,
+    self_type: BasicTypeEnum<'ctx>,
+  ) -> FunctionValue<'ctx> {
+    let name = format!("{}$dyn", method.get_name().to_string_lossy());
+    if let Some(shim) = self.module.get_function(&name) {
+      return shim;
+    }
+    let ty = dyn_method_type(self.context, method.get_type());
+    let shim = self.module.add_function(&name, ty, Some(Linkage::Private));
+    let builder = self.context.create_builder();
+    builder.position_at_end(self.context.append_basic_block(shim, "entry"));
+    let mut args = shim.get_param_iter().map(BasicMetadataValueEnum::from).collect::<Vec<_>>();
+    let index = receiver_index(method.get_type());
+    if let (Some(receiver), Some(ty)) = (
+      args.get_mut(index),
+      method.get_type().get_param_types().get(index),
+    ) {
+      let erased = receiver.into_pointer_value();
+      let pointer = self_type.ptr_type(AddressSpace::default());
+      let pointer = builder.build_pointer_cast(erased, pointer, "self");
+      *receiver = match *ty == self_type {
+        true => builder.build_load(pointer, "self").into(),
+        false => pointer.into(),
+      };
+    }
+    let value = builder.build_call(method, &args, "call").try_as_basic_value().left();
+    match value {
+      Some(value) => builder.build_return(Some(&value)),
+      None => builder.build_return(None),
+    };
+    shim
+  }
+
+  /// Make the trait object from the pointer to the value and the vtable of its type.
+  pub fn build_trait_object(
+    &self,
+    builder: &Builder<'ctx>,
+    data: PointerValue<'ctx>,
+    vtable: GlobalValue<'ctx>,
+  ) -> StructValue<'ctx> {
+    let erased = erased_pointer_type(self.context);
+    let data = builder.build_pointer_cast(data, erased, "data");
+    let vtable = builder.build_pointer_cast(vtable.as_pointer_value(), erased, "vtable");
+    let object = trait_object_type(self.context).get_undef();
+    let object = builder.build_insert_value(object, data, 0, "object").unwrap();
+    builder
+      .build_insert_value(object, vtable, 1, "object")
+      .unwrap()
+      .into_struct_value()
+  }
+
+  /// Call the method at `index` of the trait through the vtable of the object. `method` is the type
+  /// of the methods of the implementations, whose receivers do not matter, and the arguments
+  /// exclude the receiver.
+  pub fn build_dynamic_call(
+    &self,
+    builder: &Builder<'ctx>,
+    object: StructValue<'ctx>,
+    index: u32,
+    method: FunctionType<'ctx>,
+    args: &[BasicMetadataValueEnum<'ctx>],
+  ) -> Option<BasicValueEnum<'ctx>> {
+    let data = builder.build_extract_value(object, 0, "data").unwrap();
+    let vtable = builder.build_extract_value(object, 1, "vtable").unwrap().into_pointer_value();
+    // The length of the method table does not matter for the indexing.
+    let vtable_ptr = vtable_type(self.context, 0).ptr_type(AddressSpace::default());
+    let vtable = builder.build_pointer_cast(vtable, vtable_ptr, "vtable");
+    let methods = builder.build_struct_gep(vtable, METHODS_INDEX, "methods").unwrap();
+    let i32_type = self.context.i32_type();
+    let entry = unsafe {
+      builder.build_in_bounds_gep(
+        methods,
+        &[
+          i32_type.const_zero(),
+          i32_type.const_int(index as u64, false),
+        ],
+        "entry",
+      )
+    };
+    let entry = builder.build_load(entry, "method").into_pointer_value();
+    let ty = dyn_method_type(self.context, method).ptr_type(AddressSpace::default());
+    let callee = builder.build_pointer_cast(entry, ty, "method");
+    let callee = CallableValue::try_from(callee).unwrap();
+    let mut call_args = args.to_vec();
+    call_args.insert(receiver_index(method), data.into());
+    builder.build_call(callee, &call_args, "call").try_as_basic_value().left()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use inkwell::execution_engine::JitFunction;
+  use inkwell::OptimizationLevel;
+
+  use super::*;
+
+  /// Add `<name>(self: { i64 }*, i64) -> i64` computing `self.0 <op> arg`.
+  fn add_method<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    name: &str,
+    self_type: StructType<'ctx>,
+    multiply: bool,
+  ) -> FunctionValue<'ctx> {
+    let i64_type = context.i64_type();
+    let ty = i64_type.fn_type(
+      &[
+        self_type.ptr_type(AddressSpace::default()).into(),
+        i64_type.into(),
+      ],
+      false,
+    );
+    let function = module.add_function(name, ty, None);
+    let builder = context.create_builder();
+    builder.position_at_end(context.append_basic_block(function, "entry"));
+    let this = function.get_nth_param(0).unwrap().into_pointer_value();
+    let field = builder.build_struct_gep(this, 0, "field").unwrap();
+    let field = builder.build_load(field, "value").into_int_value();
+    let arg = function.get_nth_param(1).unwrap().into_int_value();
+    let value = if multiply {
+      builder.build_int_mul(field, arg, "mul")
+    } else {
+      builder.build_int_add(field, arg, "add")
+    };
+    builder.build_return(Some(&value));
+    function
+  }
+
+  #[test]
+  fn test_dynamic_call() {
+    let context = Context::create();
+    let module = context.create_module("main");
+    let vtables = VtableBuilder::new(&context, &module);
+    let i64_type = context.i64_type();
+    let self_type = context.struct_type(&[i64_type.into()], false);
+
+    // Two implementations of a trait with the methods `apply` and `scale`.
+    let add = add_method(&context, &module, "Add::apply", self_type, false);
+    let add_scale = add_method(&context, &module, "Add::scale", self_type, true);
+    let mul = add_method(&context, &module, "Mul::apply", self_type, true);
+    let mul_scale = add_method(&context, &module, "Mul::scale", self_type, true);
+    let add_vtable = vtables.build_vtable("vtable.Add", self_type.into(), &[add, add_scale]);
+    let mul_vtable = vtables.build_vtable("vtable.Mul", self_type.into(), &[mul, mul_scale]);
+
+    // func main(kind: bool, index: int32) -> int64, calling through the object of either type.
+    let main_type = i64_type.fn_type(&[context.bool_type().into()], false);
+    let main = module.add_function("main", main_type, None);
+    let builder = context.create_builder();
+    builder.position_at_end(context.append_basic_block(main, "entry"));
+    let value = builder.build_alloca(self_type, "value");
+    let field = builder.build_struct_gep(value, 0, "field").unwrap();
+    builder.build_store(field, i64_type.const_int(6, false));
+    let add_object = vtables.build_trait_object(&builder, value, add_vtable);
+    let mul_object = vtables.build_trait_object(&builder, value, mul_vtable);
+    let kind = main.get_nth_param(0).unwrap().into_int_value();
+    let object = builder.build_select(kind, add_object, mul_object, "object").into_struct_value();
+    let arg = i64_type.const_int(7, false).into();
+    let apply = vtables
+      .build_dynamic_call(&builder, object, 0, add.get_type(), &[arg])
+      .unwrap()
+      .into_int_value();
+    let scale = vtables
+      .build_dynamic_call(&builder, object, 1, add.get_type(), &[arg])
+      .unwrap()
+      .into_int_value();
+    let result = builder.build_int_sub(apply, scale, "result");
+    builder.build_return(Some(&result));
+    module.verify().unwrap();
+
+    let engine = module.create_jit_execution_engine(OptimizationLevel::None).unwrap();
+    unsafe {
+      let main: JitFunction<unsafe extern "C" fn(bool) -> i64> =
+        engine.get_function("main").unwrap();
+      assert_eq!(main.call(true), 13 - 42);
+      assert_eq!(main.call(false), 0);
+    }
+  }
+
+  #[test]
+  fn test_vtable_layout() {
+    let context = Context::create();
+    let module = context.create_module("main");
+    let vtables = VtableBuilder::new(&context, &module);
+    let self_type = context.struct_type(&[context.i64_type().into()], false);
+    let method = add_method(&context, &module, "Add::apply", self_type, false);
+    let vtable = vtables.build_vtable("vtable.Add", self_type.into(), &[method]);
+    assert!(vtable.is_constant());
+    assert_eq!(
+      vtable.get_initializer().unwrap().get_type(),
+      vtable_type(&context, 1).as_basic_type_enum()
+    );
+    // Shims are shared by the vtables of the same implementation.
+    vtables.build_vtable("vtable.Add.2", self_type.into(), &[method]);
+    assert!(module.get_function("Add::apply$dyn").is_some());
+    assert_eq!(
+      module
+        .get_functions()
+        .filter(|f| f.get_name().to_bytes().ends_with(b"$dyn"))
+        .count(),
+      1
+    );
+    module.verify().unwrap();
+  }
+}
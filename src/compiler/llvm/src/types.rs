@@ -1 +1,163 @@
+use std::collections::HashMap;
 
+use inkwell::context::Context;
+use inkwell::types::BasicMetadataTypeEnum;
+use inkwell::types::BasicType;
+use inkwell::types::BasicTypeEnum;
+use inkwell::types::FunctionType;
+use inkwell::types::StructType;
+use inkwell::AddressSpace;
+use vsp_ast::ast::module::Path;
+use vsp_ast::ast::types::PrimitiveType;
+use vsp_ast::ast::types::Type;
+use vsp_mir::mir::Program;
+use vsp_sema::mono::substitute;
+
+use crate::llvm::layout::layout_of;
+use crate::llvm::layout::pass_by;
+use crate::llvm::layout::PassBy;
+use crate::llvm::layout::Shape;
+use crate::llvm::layout::TAG_SIZE;
+use crate::llvm::vtable::trait_object_type;
+
+/// Lower the type into LLVM type.
+///
+/// Signedness is not a property of LLVM integer types, so `int32` and `uint32` are both lowered to
+/// `i32`, and the instructions decide how the bits are interpreted. Structs are lowered to the
+/// LLVM structs of their fields in the declaration order, with the generic arguments substituted,
+/// and enums and the state machines of `async func` to their tagged unions. Functions are lowered
+/// to the pointers to the LLVM functions of the calling convention of vsp.
+pub(crate) fn basic_type<'ctx>(
+  context: &'ctx Context,
+  program: &Program,
+  ty: &Type,
+) -> BasicTypeEnum<'ctx> {
+  match ty {
+    Type::Primitive(primitive) => primitive_type(context, primitive),
+    Type::Array(element, size) => basic_type(context, program, element)
+      .array_type(*size as u32)
+      .as_basic_type_enum(),
+    Type::Dyn(_) => trait_object_type(context).as_basic_type_enum(),
+    Type::Function(function) => fn_type(context, program, &function.params, &function.ret)
+      .ptr_type(AddressSpace::default())
+      .as_basic_type_enum(),
+    Type::Pointer(pointee) => basic_type(context, program, pointee)
+      .ptr_type(AddressSpace::default())
+      .as_basic_type_enum(),
+    Type::Named(path, args) if program.struct_def(path).is_some() => {
+      let fields = struct_fields(program, path, args)
+        .iter()
+        .map(|field| basic_type(context, program, field))
+        .collect::<Vec<_>>();
+      context.struct_type(&fields, false).as_basic_type_enum()
+    }
+    Type::Named(path, _) if program.enum_def(path).is_some() => {
+      enum_type(context, program, ty).as_basic_type_enum()
+    }
+    Type::Coroutine(..) => enum_type(context, program, ty).as_basic_type_enum(),
+    // Generic parameters, `Self` and the associated types are substituted by the monomorphization,
+    // and the sizes of the arrays are evaluated before the codegen.
+    _ => unreachable!("type `{}` could not be lowered", ty),
+  }
+}
+
+pub(crate) fn primitive_type<'ctx>(
+  context: &'ctx Context,
+  primitive: &PrimitiveType,
+) -> BasicTypeEnum<'ctx> {
+  match primitive {
+    PrimitiveType::Unit => context.struct_type(&[], false).into(),
+    PrimitiveType::Bool => context.bool_type().into(),
+    PrimitiveType::Int8 | PrimitiveType::Uint8 => context.i8_type().into(),
+    PrimitiveType::Int16 | PrimitiveType::Uint16 => context.i16_type().into(),
+    PrimitiveType::Int32 | PrimitiveType::Uint32 => context.i32_type().into(),
+    PrimitiveType::Int64 | PrimitiveType::Uint64 => context.i64_type().into(),
+    PrimitiveType::Float64 | PrimitiveType::Double64 => context.f64_type().into(),
+    // Unicode scalar value.
+    PrimitiveType::Char => context.i32_type().into(),
+    PrimitiveType::Str => context.i8_type().ptr_type(AddressSpace::default()).into(),
+  }
+}
+
+/// Types of the fields of the struct, with the generic arguments substituted.
+pub(crate) fn struct_fields(program: &Program, path: &Path, args: &[Type]) -> Vec<Type> {
+  let def = program.struct_def(path).expect("undefined struct");
+  let substitution = def
+    .generics
+    .iter()
+    .cloned()
+    .zip(args.iter().cloned())
+    .collect::<HashMap<_, _>>();
+  def.fields.iter().map(|field| substitute(field, &substitution)).collect()
+}
+
+/// Tagged union of the enum, as `{ i32, [N x iA] }` where the payload is the array of the integers
+/// as aligned as the most aligned variant, so that LLVM lays it out as [`layout_of`] does. Fields
+/// of the variants are accessed through the payload cast to the pointer to [`variant_type`].
+pub(crate) fn enum_type<'ctx>(
+  context: &'ctx Context,
+  program: &Program,
+  ty: &Type,
+) -> StructType<'ctx> {
+  let layout = layout_of(program, ty);
+  let (payload, payload_align) = match layout.shape {
+    Shape::Enum {
+      payload,
+      payload_align,
+      ..
+    } => (payload, payload_align),
+    _ => unreachable!("type `{}` is not an enum", ty),
+  };
+  let unit = context.custom_width_int_type(payload_align as u32 * 8);
+  let count = (layout.size - payload) / payload_align;
+  let tag = context.custom_width_int_type(TAG_SIZE as u32 * 8);
+  context.struct_type(&[tag.into(), unit.array_type(count as u32).into()], false)
+}
+
+/// Struct of the fields of the variant, which is located at the payload of the enum.
+pub(crate) fn variant_type<'ctx>(
+  context: &'ctx Context,
+  program: &Program,
+  fields: &[Type],
+) -> StructType<'ctx> {
+  let fields = fields
+    .iter()
+    .map(|field| basic_type(context, program, field))
+    .collect::<Vec<_>>();
+  context.struct_type(&fields, false)
+}
+
+/// LLVM function type of the calling convention of vsp, which passes the values of the lowered
+/// types as they are, except those passed by pointer as [`pass_by`] decides. Such a return value
+/// is written into the memory pointed to by the hidden first parameter, and the function returns
+/// `void`.
+pub(crate) fn fn_type<'ctx>(
+  context: &'ctx Context,
+  program: &Program,
+  params: &[Type],
+  ret: &Type,
+) -> FunctionType<'ctx> {
+  let mut types = vec![];
+  let ret_by_pointer = pass_by(program, ret) == PassBy::Pointer;
+  if ret_by_pointer {
+    types.push(pointer_type(context, program, ret));
+  }
+  for param in params {
+    types.push(match pass_by(program, param) {
+      PassBy::Value => basic_type(context, program, param).into(),
+      PassBy::Pointer => pointer_type(context, program, param),
+    });
+  }
+  match ret_by_pointer {
+    true => context.void_type().fn_type(&types, false),
+    false => basic_type(context, program, ret).fn_type(&types, false),
+  }
+}
+
+fn pointer_type<'ctx>(
+  context: &'ctx Context,
+  program: &Program,
+  ty: &Type,
+) -> BasicMetadataTypeEnum<'ctx> {
+  basic_type(context, program, ty).ptr_type(AddressSpace::default()).into()
+}
@@ -0,0 +1,1456 @@
+//! Construction of the MIR from the HIR.
+//!
+//! Expressions are evaluated into places in the order of evaluation. Intermediate values are
+//! stored into fresh temporaries, and the short-circuit logical operations, calls and statements
+//! with control flow split the current block.
+use std::collections::HashSet;
+
+use vsp_ast::ast::expr::BinaryOp;
+use vsp_ast::ast::expr::UnaryOp;
+use vsp_ast::ast::types::PrimitiveType;
+use vsp_ast::ast::types::Type;
+use vsp_diag::Diagnostic;
+use vsp_diag::DiagnosticEngine;
+use vsp_hir::hir;
+use vsp_hir::hir::ExprKind;
+use vsp_hir::hir::Literal;
+use vsp_hir::hir::PatternKind;
+use vsp_hir::hir::StmtKind;
+use vsp_sema::lang::LangItem;
+use vsp_sema::resolve::DefPath;
+use vsp_span::Span;
+
+use crate::check::check_body;
+use crate::coroutine::split_coroutine;
+use crate::eval::evaluate_consts;
+use crate::mir::BasicBlock;
+use crate::mir::BasicBlockData;
+use crate::mir::Body;
+use crate::mir::Constant;
+use crate::mir::ConstantKind;
+use crate::mir::Local;
+use crate::mir::LocalDecl;
+use crate::mir::Operand;
+use crate::mir::Place;
+use crate::mir::Program;
+use crate::mir::Rvalue;
+use crate::mir::SourceScope;
+use crate::mir::SourceScopeData;
+use crate::mir::Statement;
+use crate::mir::StatementKind;
+use crate::mir::Terminator;
+use crate::mir::TerminatorKind;
+
+/// Build the bodies of the program, and check them by the analyses on the control flow graph. The
+/// unreachable blocks are removed after the checks, and the bodies of `async func` are split into
+/// the state machines. Then the constants and the static items are evaluated. Errors are reported
+/// to the diagnostics.
+pub fn build_program(program: &hir::Program, diagnostics: &mut DiagnosticEngine) -> Program {
+  let mut bodies = vec![];
+  for hir_body in &program.bodies {
+    diagnostics.set_current_file(hir_body.file.clone());
+    let mut body = build_body(hir_body, diagnostics);
+    check_body(&body, diagnostics);
+    remove_unreachable_blocks(&mut body);
+    match &hir_body.coroutine {
+      Some(coroutine) => {
+        let (constructor, poll) = split_coroutine(body, coroutine);
+        bodies.push(constructor);
+        bodies.push(poll);
+      }
+      None => bodies.push(body),
+    }
+  }
+  diagnostics.set_current_file(None);
+  let mut program = Program {
+    bodies,
+    consts: vec![],
+    foreign: program.foreign.clone(),
+    structs: program.structs.clone(),
+    enums: program.enums.clone(),
+  };
+  evaluate_consts(&mut program, diagnostics);
+  program
+}
+
+/// Build the control flow graph of the body. Statements after `return` and `break` are kept in
+/// blocks without predecessors, and reported as unreachable.
+pub fn build_body(body: &hir::Body, diagnostics: &mut DiagnosticEngine) -> Body {
+  let mut builder = Builder::new(body);
+  builder.block(&body.block);
+  // Falling off the end returns, where the return place is only initialized for unit functions.
+  if body.ret == Type::unit() {
+    builder.assign(Body::RETURN_PLACE.into(), unit(), body.span.clone());
+  }
+  builder.terminate(TerminatorKind::Return, body.span.clone());
+  let markers = std::mem::take(&mut builder.markers);
+  let body = Body {
+    owner:     body.owner.clone(),
+    file:      body.file.clone(),
+    locals:    builder.locals,
+    arg_count: body.params,
+    scopes:    builder.scopes,
+    blocks:    builder.blocks,
+    span:      body.span.clone(),
+    constancy: body.constancy,
+    coroutine: None,
+  };
+  report_unreachable_stmts(&body, &markers, diagnostics);
+  body
+}
+
+/// Report the first unreachable statement of each block, unless the statement containing the
+/// block is already unreachable.
+fn report_unreachable_stmts(body: &Body, markers: &[Marker], diagnostics: &mut DiagnosticEngine) {
+  let reachable = body.reachable_blocks();
+  let is_reachable = |marker: &Marker| reachable[marker.block.0];
+  let mut reported = HashSet::new();
+  for marker in markers {
+    let parent_reachable = marker.parent.map_or(true, |parent| is_reachable(&markers[parent]));
+    if !is_reachable(marker) && parent_reachable && reported.insert(marker.group) {
+      diagnostics.emit(Diagnostic::warning(
+        "unreachable statement",
+        marker.span.clone(),
+      ));
+    }
+  }
+}
+
+/// Remove the blocks which are not reachable from the entry, and renumber the rest in order.
+pub fn remove_unreachable_blocks(body: &mut Body) {
+  let reachable = body.reachable_blocks();
+  let mut renumbered = vec![None; body.blocks.len()];
+  let blocks = std::mem::take(&mut body.blocks);
+  for (index, block) in blocks.into_iter().enumerate() {
+    if reachable[index] {
+      renumbered[index] = Some(BasicBlock(body.blocks.len()));
+      body.blocks.push(block);
+    }
+  }
+  let renumber = |block: &mut BasicBlock| *block = renumbered[block.0].unwrap();
+  for block in &mut body.blocks {
+    match &mut block.terminator.as_mut().unwrap().kind {
+      TerminatorKind::Goto(target)
+      | TerminatorKind::Yield { resume: target }
+      | TerminatorKind::Call { target, .. }
+      | TerminatorKind::DynCall { target, .. } => renumber(target),
+      TerminatorKind::If {
+        then, otherwise, ..
+      } => {
+        renumber(then);
+        renumber(otherwise);
+      }
+      TerminatorKind::Return | TerminatorKind::Unreachable => {}
+    }
+  }
+}
+
+/// Block which a statement of the HIR starts at.
+struct Marker {
+  block:  BasicBlock,
+  span:   Span,
+  /// Statement containing the block of statements which the statement belongs to.
+  parent: Option<usize>,
+  /// Index of the block of statements.
+  group:  usize,
+}
+
+struct Builder {
+  locals:   Vec<LocalDecl>,
+  blocks:   Vec<BasicBlockData>,
+  current:  BasicBlock,
+  /// Header and exit blocks of the enclosing loops, indexed by [`hir::LoopId`].
+  loops:    Vec<(BasicBlock, BasicBlock)>,
+  markers:  Vec<Marker>,
+  /// Statement being built, which contains the blocks of statements built next.
+  parent:   Option<usize>,
+  groups:   usize,
+  scopes:   Vec<SourceScopeData>,
+  /// Scope of the block of statements being built.
+  scope:    SourceScope,
+  /// Whether the body is of `async func`, which suspends on pending futures by `yield`.
+  is_async: bool,
+}
+
+impl Builder {
+  fn new(body: &hir::Body) -> Self {
+    let mut locals = vec![LocalDecl {
+      name:    None,
+      ty:      body.ret.clone(),
+      mutable: true,
+      span:    body.span.clone(),
+      scope:   SourceScope::OUTERMOST,
+    }];
+    locals.extend(body.locals.iter().map(|local| LocalDecl {
+      name:    Some(local.name.to_owned()),
+      ty:      local.ty.clone(),
+      mutable: local.mutable,
+      span:    local.span.clone(),
+      scope:   SourceScope::OUTERMOST,
+    }));
+    Self {
+      locals,
+      blocks: vec![BasicBlockData::default()],
+      current: BasicBlock(0),
+      loops: vec![],
+      markers: vec![],
+      parent: None,
+      groups: 0,
+      scopes: vec![SourceScopeData {
+        parent: None,
+        span:   body.span.clone(),
+      }],
+      scope: SourceScope::OUTERMOST,
+      is_async: body.coroutine.is_some(),
+    }
+  }
+
+  fn new_block(&mut self) -> BasicBlock {
+    self.blocks.push(BasicBlockData::default());
+    BasicBlock(self.blocks.len() - 1)
+  }
+
+  fn temp(&mut self, ty: Type, span: Span) -> Local {
+    self.locals.push(LocalDecl {
+      name: None,
+      ty,
+      mutable: false,
+      span,
+      scope: SourceScope::OUTERMOST,
+    });
+    Local(self.locals.len() - 1)
+  }
+
+  fn assign(&mut self, place: Place, rvalue: Rvalue, span: Span) {
+    self.blocks[self.current.0].statements.push(Statement {
+      kind: StatementKind::Assign(place, Box::new(rvalue)),
+      span,
+    });
+  }
+
+  fn terminate(&mut self, kind: TerminatorKind, span: Span) {
+    self.blocks[self.current.0].terminator = Some(Terminator { kind, span });
+  }
+
+  /// Terminate the current block, and continue at a new block which is unreachable unless other
+  /// blocks jump to it.
+  fn diverge(&mut self, kind: TerminatorKind, span: Span) {
+    self.terminate(kind, span);
+    self.current = self.new_block();
+  }
+
+  /// Build the block of statements, which opens a new scope unless it is the outermost block of the
+  /// body.
+  fn block(&mut self, block: &hir::Block) {
+    let group = self.groups;
+    self.groups += 1;
+    let parent = self.parent;
+    let scope = self.scope;
+    if let (Some(_), Some(first), Some(last)) = (parent, block.stmts.first(), block.stmts.last()) {
+      self.scopes.push(SourceScopeData {
+        parent: Some(scope),
+        span:   Span::range(first.span.start, last.span.end),
+      });
+      self.scope = SourceScope(self.scopes.len() - 1);
+    }
+    for stmt in &block.stmts {
+      self.parent = Some(self.markers.len());
+      self.markers.push(Marker {
+        block: self.current,
+        span: stmt.span.clone(),
+        parent,
+        group,
+      });
+      self.stmt(stmt);
+    }
+    self.parent = parent;
+    self.scope = scope;
+  }
+
+  fn stmt(&mut self, stmt: &hir::Stmt) {
+    match &stmt.kind {
+      StmtKind::Let(id, init) => {
+        self.locals[local(*id).0].scope = self.scope;
+        let statement = Statement {
+          kind: StatementKind::StorageLive(local(*id)),
+          span: stmt.span.clone(),
+        };
+        self.blocks[self.current.0].statements.push(statement);
+        if let Some(init) = init {
+          self.expr_into(local(*id).into(), init);
+        }
+      }
+      StmtKind::Expr(expr) => match &expr.kind {
+        // The unit value of the assignment is discarded instead of stored into a temporary.
+        ExprKind::Assign(place, value) => self.assign_expr(place, value, expr.span.clone()),
+        _ => {
+          self.as_operand(expr);
+        }
+      },
+      StmtKind::If(condition, then, otherwise) => {
+        let span = condition.span.clone();
+        let condition = self.as_operand(condition);
+        let then_block = self.new_block();
+        let else_block = self.new_block();
+        let join_block = match otherwise {
+          Some(_) => self.new_block(),
+          None => else_block,
+        };
+        self.terminate(
+          TerminatorKind::If {
+            condition,
+            then: then_block,
+            otherwise: else_block,
+          },
+          span.clone(),
+        );
+        self.current = then_block;
+        self.block(then);
+        self.terminate(TerminatorKind::Goto(join_block), span.clone());
+        if let Some(otherwise) = otherwise {
+          self.current = else_block;
+          self.block(otherwise);
+          self.terminate(TerminatorKind::Goto(join_block), span);
+        }
+        self.current = join_block;
+      }
+      StmtKind::Loop(body) => {
+        let header = self.new_block();
+        let exit = self.new_block();
+        self.terminate(TerminatorKind::Goto(header), stmt.span.clone());
+        self.current = header;
+        self.loops.push((header, exit));
+        self.block(body);
+        self.loops.pop();
+        self.terminate(TerminatorKind::Goto(header), stmt.span.clone());
+        self.current = exit;
+      }
+      StmtKind::Break(id) => {
+        let (_, exit) = self.loops[id.0];
+        self.diverge(TerminatorKind::Goto(exit), stmt.span.clone());
+      }
+      StmtKind::Continue(id) => {
+        let (header, _) = self.loops[id.0];
+        self.diverge(TerminatorKind::Goto(header), stmt.span.clone());
+      }
+      StmtKind::Return(value) => {
+        match value {
+          Some(value) => self.expr_into(Body::RETURN_PLACE.into(), value),
+          None => self.assign(Body::RETURN_PLACE.into(), unit(), stmt.span.clone()),
+        }
+        self.diverge(TerminatorKind::Return, stmt.span.clone());
+      }
+      StmtKind::Block(block) => self.block(block),
+    }
+  }
+
+  /// Evaluate the expression and store its value into the place.
+  fn expr_into(&mut self, destination: Place, expr: &hir::Expr) {
+    let span = expr.span.clone();
+    match &expr.kind {
+      ExprKind::Binary(op @ (BinaryOp::And | BinaryOp::Or), left, right) => {
+        // `a && b` is `if a { b } else { false }`, and `a || b` is `if a { true } else { b }`.
+        let condition = self.as_operand(left);
+        let right_block = self.new_block();
+        let short_block = self.new_block();
+        let join_block = self.new_block();
+        let (then, otherwise) = match op {
+          BinaryOp::And => (right_block, short_block),
+          _ => (short_block, right_block),
+        };
+        self.terminate(
+          TerminatorKind::If {
+            condition,
+            then,
+            otherwise,
+          },
+          span.clone(),
+        );
+        self.current = right_block;
+        self.expr_into(destination.clone(), right);
+        self.terminate(TerminatorKind::Goto(join_block), span.clone());
+        self.current = short_block;
+        let value = Rvalue::Use(Operand::Constant(Constant {
+          kind: ConstantKind::Bool(op == &BinaryOp::Or),
+          ty:   Type::Primitive(PrimitiveType::Bool),
+        }));
+        self.assign(destination, value, span.clone());
+        self.terminate(TerminatorKind::Goto(join_block), span);
+        self.current = join_block;
+      }
+      ExprKind::If(condition, then, otherwise) => {
+        let condition = self.as_operand(condition);
+        let then_block = self.new_block();
+        let else_block = self.new_block();
+        let join_block = self.new_block();
+        self.terminate(
+          TerminatorKind::If {
+            condition,
+            then: then_block,
+            otherwise: else_block,
+          },
+          span.clone(),
+        );
+        self.current = then_block;
+        self.expr_into(destination.clone(), then);
+        self.terminate(TerminatorKind::Goto(join_block), span.clone());
+        self.current = else_block;
+        self.expr_into(destination, otherwise);
+        self.terminate(TerminatorKind::Goto(join_block), span);
+        self.current = join_block;
+      }
+      ExprKind::Match(scrutinee, arms) => {
+        self.match_into(destination, scrutinee, arms, &expr.ty, span)
+      }
+      ExprKind::Block(block) => {
+        self.block(block);
+        self.assign(destination, unit(), span);
+      }
+      ExprKind::Assign(place, value) => {
+        self.assign_expr(place, value, span.clone());
+        self.assign(destination, unit(), span);
+      }
+      ExprKind::Call(callee, args) => {
+        let func = self.as_operand(callee);
+        let args = args.iter().map(|arg| self.as_operand(arg)).collect();
+        let target = self.new_block();
+        self.terminate(
+          TerminatorKind::Call {
+            func,
+            args,
+            destination,
+            target,
+          },
+          span,
+        );
+        self.current = target;
+      }
+      ExprKind::DynCall {
+        object,
+        trait_,
+        index,
+        args,
+      } => {
+        let object = self.as_operand(object);
+        let args = args.iter().map(|arg| self.as_operand(arg)).collect();
+        let target = self.new_block();
+        self.terminate(
+          TerminatorKind::DynCall {
+            object,
+            trait_: trait_.clone(),
+            index: *index,
+            args,
+            destination,
+            target,
+          },
+          span,
+        );
+        self.current = target;
+      }
+      ExprKind::Await { operand, poll } => self.await_into(destination, operand, poll, span),
+      ExprKind::Discriminant(operand, discriminants) => {
+        self.discriminant_into(destination, operand, discriminants, span)
+      }
+      _ => {
+        let rvalue = self.as_rvalue(expr);
+        self.assign(destination, rvalue, span);
+      }
+    }
+  }
+
+  /// The arms are tried in order, each testing its pattern against the scrutinee and going on with
+  /// the next arm unless it matches. The last arm is not tested, as the patterns are exhaustive.
+  fn match_into(
+    &mut self,
+    destination: Place,
+    scrutinee: &hir::Expr,
+    arms: &[hir::Arm],
+    ty: &Type,
+    span: Span,
+  ) {
+    let place = self.as_place(scrutinee);
+    if arms.is_empty() {
+      self.diverge(TerminatorKind::Unreachable, span);
+      return;
+    }
+    let join_block = self.new_block();
+    for (index, arm) in arms.iter().enumerate() {
+      let next_block = (index + 1 < arms.len()).then(|| self.new_block());
+      self.test_pattern(place.clone(), &arm.pattern, next_block);
+      match &arm.body.kind {
+        // The block which never completes leaves the destination of other types unassigned.
+        ExprKind::Block(block) if ty != &Type::unit() => self.block(block),
+        _ => self.expr_into(destination.clone(), &arm.body),
+      }
+      self.terminate(TerminatorKind::Goto(join_block), span.clone());
+      if let Some(next_block) = next_block {
+        self.current = next_block;
+      }
+    }
+    self.current = join_block;
+  }
+
+  /// Test the value of the place against the pattern, and jump to `otherwise` unless it matches,
+  /// or only bind it without `otherwise`. The parts of the value are bound to the locals of the
+  /// pattern as they are tested.
+  fn test_pattern(&mut self, place: Place, pattern: &hir::Pattern, otherwise: Option<BasicBlock>) {
+    let span = pattern.span.clone();
+    match &pattern.kind {
+      PatternKind::Wild => {}
+      PatternKind::Binding(id) => self.bind(*id, Rvalue::Use(Operand::Copy(place)), span),
+      PatternKind::Literal(Literal::Bool(value)) => {
+        let otherwise = match otherwise {
+          Some(otherwise) => otherwise,
+          None => return,
+        };
+        let matched = self.new_block();
+        let (then, otherwise) = match value {
+          true => (matched, otherwise),
+          false => (otherwise, matched),
+        };
+        let condition = Operand::Copy(place);
+        self.terminate(
+          TerminatorKind::If {
+            condition,
+            then,
+            otherwise,
+          },
+          span,
+        );
+        self.current = matched;
+      }
+      PatternKind::Literal(literal) => {
+        let otherwise = match otherwise {
+          Some(otherwise) => otherwise,
+          None => return,
+        };
+        let value = Operand::Constant(Constant {
+          kind: match literal {
+            Literal::Integer(value) => ConstantKind::Integer(*value),
+            literal => unreachable!("pattern of literal {:?}", literal),
+          },
+          ty:   pattern.ty.clone(),
+        });
+        let rvalue = Rvalue::Binary(BinaryOp::Equal, Operand::Copy(place), value);
+        self.branch_unless(rvalue, otherwise, span);
+      }
+      PatternKind::Variant(index, fields) => {
+        if let Some(otherwise) = otherwise {
+          let discriminant = self.temp(Type::Primitive(PrimitiveType::Uint32), span.clone());
+          let rvalue = Rvalue::Discriminant(place.clone());
+          self.assign(discriminant.into(), rvalue, span.clone());
+          let variant = Operand::Constant(Constant {
+            kind: ConstantKind::Integer(*index as i64),
+            ty:   Type::Primitive(PrimitiveType::Uint32),
+          });
+          let rvalue = Rvalue::Binary(BinaryOp::Equal, Operand::Copy(discriminant.into()), variant);
+          self.branch_unless(rvalue, otherwise, span.clone());
+        }
+        for (field, pattern) in fields.iter().enumerate() {
+          let rvalue = Rvalue::VariantField(place.clone(), *index, field);
+          match &pattern.kind {
+            PatternKind::Wild => {}
+            // Fields are bound to the locals directly, rather than through temporaries.
+            PatternKind::Binding(id) => self.bind(*id, rvalue, span.clone()),
+            _ => {
+              let value = self.temp(pattern.ty.clone(), span.clone());
+              self.assign(value.into(), rvalue, span.clone());
+              self.test_pattern(value.into(), pattern, otherwise);
+            }
+          }
+        }
+      }
+    }
+  }
+
+  fn bind(&mut self, id: hir::LocalId, rvalue: Rvalue, span: Span) {
+    self.locals[local(id).0].scope = self.scope;
+    let statement = Statement {
+      kind: StatementKind::StorageLive(local(id)),
+      span: span.clone(),
+    };
+    self.blocks[self.current.0].statements.push(statement);
+    self.assign(local(id).into(), rvalue, span);
+  }
+
+  /// Go on at a new block if the condition holds, or jump to `otherwise`.
+  fn branch_unless(&mut self, condition: Rvalue, otherwise: BasicBlock, span: Span) {
+    let holds = self.temp(Type::Primitive(PrimitiveType::Bool), span.clone());
+    self.assign(holds.into(), condition, span.clone());
+    let then = self.new_block();
+    self.terminate(
+      TerminatorKind::If {
+        condition: Operand::Copy(holds.into()),
+        then,
+        otherwise,
+      },
+      span,
+    );
+    self.current = then;
+  }
+
+  /// The discriminant of the enum value is the index of its variant, unless the discriminants are
+  /// given by the constants of the variants, which are chosen by comparing the index.
+  fn discriminant_into(
+    &mut self,
+    destination: Place,
+    operand: &hir::Expr,
+    discriminants: &[DefPath],
+    span: Span,
+  ) {
+    let place = self.as_place(operand);
+    let index_ty = Type::Primitive(PrimitiveType::Uint32);
+    let index = self.temp(index_ty.clone(), span.clone());
+    self.assign(index.into(), Rvalue::Discriminant(place), span.clone());
+    if discriminants.is_empty() {
+      let rvalue = Rvalue::Cast(Operand::Copy(index.into()), Type::int64());
+      self.assign(destination, rvalue, span);
+      return;
+    }
+
+    let join_block = self.new_block();
+    for (variant, discriminant) in discriminants.iter().enumerate() {
+      // The last variant is left when the index matches none of the others.
+      if variant + 1 < discriminants.len() {
+        let is_variant = self.temp(Type::Primitive(PrimitiveType::Bool), span.clone());
+        let variant = Operand::Constant(Constant {
+          kind: ConstantKind::Integer(variant as i64),
+          ty:   index_ty.clone(),
+        });
+        let rvalue = Rvalue::Binary(BinaryOp::Equal, Operand::Copy(index.into()), variant);
+        self.assign(is_variant.into(), rvalue, span.clone());
+        let then_block = self.new_block();
+        let else_block = self.new_block();
+        self.terminate(
+          TerminatorKind::If {
+            condition: Operand::Copy(is_variant.into()),
+            then:      then_block,
+            otherwise: else_block,
+          },
+          span.clone(),
+        );
+        self.current = then_block;
+        self.assign_discriminant(destination.clone(), discriminant, span.clone());
+        self.terminate(TerminatorKind::Goto(join_block), span.clone());
+        self.current = else_block;
+      } else {
+        self.assign_discriminant(destination.clone(), discriminant, span.clone());
+        self.terminate(TerminatorKind::Goto(join_block), span.clone());
+      }
+    }
+    self.current = join_block;
+  }
+
+  fn assign_discriminant(&mut self, destination: Place, discriminant: &DefPath, span: Span) {
+    let rvalue = Rvalue::Use(Operand::Constant(Constant {
+      kind: ConstantKind::Const(discriminant.clone()),
+      ty:   Type::int64(),
+    }));
+    self.assign(destination, rvalue, span);
+  }
+
+  /// `operand.await` calls `poll` on the future until it is ready, and stores the output into the
+  /// destination. Pending futures are replaced by those returned, on which `async func` suspends
+  /// by `yield`, while the executor polls them again right away.
+  fn await_into(&mut self, destination: Place, operand: &hir::Expr, poll: &hir::Expr, span: Span) {
+    let future = self.temp(operand.ty.clone(), span.clone());
+    self.locals[future.0].mutable = true;
+    self.expr_into(future.into(), operand);
+    let func = self.as_operand(poll);
+    let state_ty = match &poll.ty {
+      Type::Function(function) => function.ret.as_ref().clone(),
+      ty => unreachable!("expected function, found `{}`", ty),
+    };
+    let state = self.temp(state_ty, span.clone());
+    let poll_block = self.new_block();
+    let check_block = self.new_block();
+    self.terminate(TerminatorKind::Goto(poll_block), span.clone());
+    self.current = poll_block;
+    self.terminate(
+      TerminatorKind::Call {
+        func,
+        args: vec![Operand::Copy(future.into())],
+        destination: state.into(),
+        target: check_block,
+      },
+      span.clone(),
+    );
+
+    self.current = check_block;
+    let discriminant = self.temp(Type::Primitive(PrimitiveType::Uint32), span.clone());
+    let rvalue = Rvalue::Discriminant(state.into());
+    self.assign(discriminant.into(), rvalue, span.clone());
+    let is_ready = self.temp(Type::Primitive(PrimitiveType::Bool), span.clone());
+    let ready = Operand::Constant(Constant {
+      kind: ConstantKind::Integer(LangItem::READY as i64),
+      ty:   Type::Primitive(PrimitiveType::Uint32),
+    });
+    let rvalue = Rvalue::Binary(BinaryOp::Equal, Operand::Copy(discriminant.into()), ready);
+    self.assign(is_ready.into(), rvalue, span.clone());
+    let ready_block = self.new_block();
+    let pending_block = self.new_block();
+    self.terminate(
+      TerminatorKind::If {
+        condition: Operand::Copy(is_ready.into()),
+        then:      ready_block,
+        otherwise: pending_block,
+      },
+      span.clone(),
+    );
+
+    self.current = pending_block;
+    let rvalue = Rvalue::VariantField(state.into(), LangItem::PENDING, 0);
+    self.assign(future.into(), rvalue, span.clone());
+    let kind = match self.is_async {
+      true => TerminatorKind::Yield { resume: poll_block },
+      false => TerminatorKind::Goto(poll_block),
+    };
+    self.terminate(kind, span.clone());
+
+    self.current = ready_block;
+    let rvalue = Rvalue::VariantField(state.into(), LangItem::READY, 0);
+    self.assign(destination, rvalue, span);
+  }
+
+  /// Assignment by the expression `place = value`, whose span is given to the statement.
+  fn assign_expr(&mut self, place: &hir::Expr, value: &hir::Expr, span: Span) {
+    let place = self.as_place(place);
+    let value = self.as_rvalue(value);
+    self.assign(place, value, span);
+  }
+
+  fn as_rvalue(&mut self, expr: &hir::Expr) -> Rvalue {
+    match &expr.kind {
+      ExprKind::Unary(UnaryOp::AddressOf, operand) => Rvalue::AddressOf(self.as_place(operand)),
+      ExprKind::Unary(UnaryOp::Dereference, _) => Rvalue::Use(Operand::Copy(self.as_place(expr))),
+      ExprKind::Unary(op, operand) => Rvalue::Unary(op.clone(), self.as_operand(operand)),
+      ExprKind::Binary(op, left, right) if !op.is_logical() => {
+        let left = self.as_operand(left);
+        let right = self.as_operand(right);
+        Rvalue::Binary(op.clone(), left, right)
+      }
+      ExprKind::Cast(operand, ty) => Rvalue::Cast(self.as_operand(operand), ty.clone()),
+      ExprKind::ToDyn(operand) => Rvalue::ToDyn(self.as_operand(operand), expr.ty.clone()),
+      ExprKind::Struct(fields) => {
+        let fields = fields.iter().map(|(index, value)| (*index, self.as_operand(value))).collect();
+        Rvalue::Struct(expr.ty.clone(), fields)
+      }
+      ExprKind::Variant(index, fields) => {
+        let fields = fields.iter().map(|value| self.as_operand(value)).collect();
+        Rvalue::Variant(expr.ty.clone(), *index, fields)
+      }
+      ExprKind::Array(elements) => {
+        let elements = elements.iter().map(|element| self.as_operand(element)).collect();
+        Rvalue::Array(expr.ty.clone(), elements)
+      }
+      _ => Rvalue::Use(self.as_operand(expr)),
+    }
+  }
+
+  fn as_operand(&mut self, expr: &hir::Expr) -> Operand {
+    let kind = match &expr.kind {
+      ExprKind::Literal(literal) => match literal {
+        Literal::Unit => ConstantKind::Unit,
+        Literal::Integer(value) => ConstantKind::Integer(*value),
+        Literal::Float(value) => ConstantKind::Float(*value),
+        Literal::Bool(value) => ConstantKind::Bool(*value),
+        Literal::Str(value) => ConstantKind::Str(value.to_owned()),
+      },
+      ExprKind::Function(def, args) => ConstantKind::Function(def.clone(), args.clone()),
+      ExprKind::Method(path) => ConstantKind::Method(path.clone()),
+      ExprKind::Const(def) => ConstantKind::Const(def.clone()),
+      ExprKind::Static(def) => ConstantKind::Static(def.clone()),
+      ExprKind::BoundMethod {
+        trait_,
+        name,
+        self_ty,
+      } => ConstantKind::BoundMethod {
+        trait_:  trait_.clone(),
+        name:    name.to_owned(),
+        self_ty: self_ty.clone(),
+      },
+      ExprKind::Local(_)
+      | ExprKind::Field(..)
+      | ExprKind::Index(..)
+      | ExprKind::Unary(UnaryOp::Dereference, _) => return Operand::Copy(self.as_place(expr)),
+      _ => {
+        let temp = self.temp(expr.ty.clone(), expr.span.clone());
+        self.expr_into(temp.into(), expr);
+        return Operand::Copy(temp.into());
+      }
+    };
+    Operand::Constant(Constant {
+      kind,
+      ty: expr.ty.clone(),
+    })
+  }
+
+  fn as_place(&mut self, expr: &hir::Expr) -> Place {
+    match &expr.kind {
+      ExprKind::Local(id) => local(*id).into(),
+      ExprKind::Field(base, index) => self.as_place(base).field(*index, expr.ty.clone()),
+      // The index is copied into a temporary, since the projection refers to it by the local.
+      ExprKind::Index(base, index) => {
+        let base = self.as_place(base);
+        let temp = self.temp(Type::int64(), index.span.clone());
+        self.expr_into(temp.into(), index);
+        base.index(temp, expr.ty.clone())
+      }
+      ExprKind::Unary(UnaryOp::Dereference, pointer) => {
+        self.as_place(pointer).deref(expr.ty.clone())
+      }
+      _ => {
+        let temp = self.temp(expr.ty.clone(), expr.span.clone());
+        self.expr_into(temp.into(), expr);
+        temp.into()
+      }
+    }
+  }
+}
+
+/// Local of the HIR local variable, placed after the return place.
+fn local(id: hir::LocalId) -> Local {
+  Local(id.0 + 1)
+}
+
+fn unit() -> Rvalue {
+  Rvalue::Use(Operand::Constant(Constant {
+    kind: ConstantKind::Unit,
+    ty:   Type::unit(),
+  }))
+}
+
+#[cfg(test)]
+mod tests {
+  use vsp_ast::ast::module::Path;
+  use vsp_diag::DiagnosticEngine;
+  use vsp_sema::mono::MonoItem;
+  use vsp_sema::resolve::DefPath;
+  use vsp_sema::testing::check_source;
+
+  use super::*;
+
+  fn build(source: &str) -> Program {
+    let (unit, results) = check_source(source);
+    let mut diagnostics = DiagnosticEngine::default();
+    let program = build_program(
+      &vsp_hir::lower_compilation_unit(&unit, &results),
+      &mut diagnostics,
+    );
+    assert!(!diagnostics.has_errors(), "{:?}", diagnostics.diagnostics());
+    program
+  }
+
+  #[test]
+  fn test_loop() {
+    let program = build(
+      "func count(n: int64, skip: bool): int64 {
+         var i = 0;
+         while i < n && !skip {
+           if i == 3 { return 0; }
+           i = i + 1;
+         }
+         return i;
+       }",
+    );
+    assert_eq!(
+      program.to_string(),
+      "\
+func count(_1: int64, _2: bool) -> int64 {
+  let mut _0: int64;
+  let _1: int64; // n
+  let _2: bool; // skip
+  let mut _3: int64; // i
+  let _4: bool;
+  let _5: bool;
+  let _6: bool;
+  let _7: bool;
+
+  bb0: {
+    StorageLive(_3);
+    _3 = const 0_int64;
+    goto -> bb1;
+  }
+
+  bb1: {
+    _6 = _3 < _1;
+    if _6 -> [true: bb3, false: bb4];
+  }
+
+  bb2: {
+    _0 = _3;
+    return;
+  }
+
+  bb3: {
+    _5 = !_2;
+    goto -> bb5;
+  }
+
+  bb4: {
+    _5 = const false;
+    goto -> bb5;
+  }
+
+  bb5: {
+    _4 = !_5;
+    if _4 -> [true: bb6, false: bb7];
+  }
+
+  bb6: {
+    goto -> bb2;
+  }
+
+  bb7: {
+    _7 = _3 == const 3_int64;
+    if _7 -> [true: bb8, false: bb9];
+  }
+
+  bb8: {
+    _0 = const 0_int64;
+    return;
+  }
+
+  bb9: {
+    _3 = _3 + const 1_int64;
+    goto -> bb1;
+  }
+}
+"
+    );
+  }
+
+  #[test]
+  fn test_scopes() {
+    let program = build(
+      "func scoped(n: int64): int64 {
+         let a = n;
+         if a > 0 {
+           let b = a * 2;
+           {
+             let c = b;
+             return c;
+           }
+         }
+         return a;
+       }",
+    );
+    let body = &program.bodies[0];
+    let parents = body.scopes.iter().map(|scope| scope.parent).collect::<Vec<_>>();
+    assert_eq!(parents, [None, Some(SourceScope(0)), Some(SourceScope(1))]);
+    let scopes = body
+      .locals
+      .iter()
+      .filter_map(|local| Some((local.name.as_deref()?, local.scope.0)))
+      .collect::<Vec<_>>();
+    assert_eq!(scopes, [("n", 0), ("a", 0), ("b", 1), ("c", 2)]);
+    let statement = &body.block(BasicBlock(0)).statements[2];
+    assert_eq!(body.innermost_scope(&statement.span), SourceScope(0));
+    let returns = body
+      .blocks
+      .iter()
+      .filter(|block| matches!(block.terminator().kind, TerminatorKind::Return));
+    let scopes = returns.map(|block| body.innermost_scope(&block.terminator().span).0);
+    assert_eq!(scopes.collect::<Vec<_>>(), [2, 0]);
+  }
+
+  #[test]
+  fn test_for() {
+    let program = build(
+      "func pick(n: int64): int64 {
+         for i in 0..n {
+           if i == 1 { continue; }
+           return if i > 2 { i } else { -i };
+         }
+         return n;
+       }",
+    );
+    assert_eq!(
+      program.to_string(),
+      "\
+func pick(_1: int64) -> int64 {
+  let mut _0: int64;
+  let _1: int64; // n
+  let mut _2: int64; // next
+  let _3: int64; // end
+  let _4: int64; // i
+  let _5: bool;
+  let _6: bool;
+  let _7: bool;
+  let _8: bool;
+
+  bb0: {
+    StorageLive(_2);
+    _2 = const 0_int64;
+    StorageLive(_3);
+    _3 = _1;
+    goto -> bb1;
+  }
+
+  bb1: {
+    _6 = _2 < _3;
+    _5 = !_6;
+    if _5 -> [true: bb3, false: bb4];
+  }
+
+  bb2: {
+    _0 = _1;
+    return;
+  }
+
+  bb3: {
+    goto -> bb2;
+  }
+
+  bb4: {
+    StorageLive(_4);
+    _4 = _2;
+    _2 = _2 + const 1_int64;
+    _7 = _4 == const 1_int64;
+    if _7 -> [true: bb5, false: bb6];
+  }
+
+  bb5: {
+    goto -> bb1;
+  }
+
+  bb6: {
+    _8 = _4 > const 2_int64;
+    if _8 -> [true: bb7, false: bb8];
+  }
+
+  bb7: {
+    _0 = _4;
+    goto -> bb9;
+  }
+
+  bb8: {
+    _0 = -_4;
+    goto -> bb9;
+  }
+
+  bb9: {
+    return;
+  }
+}
+"
+    );
+  }
+
+  #[test]
+  fn test_calls() {
+    let program = build(
+      "struct Point { x: int64, y: int64 }
+       func add(a: int64, b: int64): int64 { return a + b; }
+       func main(): int64 {
+         let p = Point { x: 1, y: 2 };
+         return add(p.x, add(p.y, 3));
+         p.x;
+       }",
+    );
+    assert_eq!(
+      program.to_string(),
+      "\
+func add(_1: int64, _2: int64) -> int64 {
+  let mut _0: int64;
+  let _1: int64; // a
+  let _2: int64; // b
+
+  bb0: {
+    _0 = _1 + _2;
+    return;
+  }
+}
+
+func main() -> int64 {
+  let mut _0: int64;
+  let _1: Point; // p
+  let _2: int64;
+
+  bb0: {
+    StorageLive(_1);
+    _1 = Point { 0: const 1_int64, 1: const 2_int64 };
+    _2 = add(_1.1, const 3_int64) -> bb1;
+  }
+
+  bb1: {
+    _0 = add(_1.0, _2) -> bb2;
+  }
+
+  bb2: {
+    return;
+  }
+}
+"
+    );
+  }
+
+  #[test]
+  fn test_extern() {
+    let program = build(
+      "extern \"C\" func printf(format: str, ...): int32;
+       func main() {
+         unsafe { printf(\"%d\", 1); }
+       }",
+    );
+    assert_eq!(
+      program.to_string(),
+      "\
+extern \"C\" func printf(str, ...) -> int32;
+
+func main() -> unit {
+  let mut _0: unit;
+  let _1: int32;
+
+  bb0: {
+    _1 = printf(const \"%d\", const 1_int32) -> bb1;
+  }
+
+  bb1: {
+    _0 = const ();
+    return;
+  }
+}
+"
+    );
+  }
+
+  #[test]
+  fn test_pointers() {
+    let program = build(
+      "func swap(a: *int64, b: *int64) {
+         unsafe {
+           let t = *a;
+           *a = *(b + 0);
+           *b = t;
+         }
+       }
+       func main(): int64 {
+         var x = 1;
+         var y = 2;
+         swap(&x, &y);
+         return x;
+       }",
+    );
+    assert_eq!(
+      program.to_string(),
+      "\
+func swap(_1: *int64, _2: *int64) -> unit {
+  let mut _0: unit;
+  let _1: *int64; // a
+  let _2: *int64; // b
+  let _3: int64; // t
+  let _4: *int64;
+
+  bb0: {
+    StorageLive(_3);
+    _3 = (*_1);
+    _4 = _2 + const 0_int64;
+    (*_1) = (*_4);
+    (*_2) = _3;
+    _0 = const ();
+    return;
+  }
+}
+
+func main() -> int64 {
+  let mut _0: int64;
+  let mut _1: int64; // x
+  let mut _2: int64; // y
+  let _3: unit;
+  let _4: *int64;
+  let _5: *int64;
+
+  bb0: {
+    StorageLive(_1);
+    _1 = const 1_int64;
+    StorageLive(_2);
+    _2 = const 2_int64;
+    _4 = &_1;
+    _5 = &_2;
+    _3 = swap(_4, _5) -> bb1;
+  }
+
+  bb1: {
+    _0 = _1;
+    return;
+  }
+}
+"
+    );
+  }
+
+  #[test]
+  fn test_try() {
+    let program = build(
+      "@Lang(Optional) enum Optional<T> { Value(T), None }
+       func half(n: int64): Optional<int64> {
+         if n % 2 == 1 { return Optional::None; }
+         return Optional::Value(n / 2);
+       }
+       func quarter(n: int64): Optional<int64> {
+         return Optional::Value(half(n)? / 2);
+       }",
+    );
+    assert_eq!(
+      program.to_string(),
+      "\
+func half(_1: int64) -> Optional<int64> {
+  let mut _0: Optional<int64>;
+  let _1: int64; // n
+  let _2: bool;
+  let _3: int64;
+  let _4: int64;
+
+  bb0: {
+    _3 = _1 % const 2_int64;
+    _2 = _3 == const 1_int64;
+    if _2 -> [true: bb1, false: bb2];
+  }
+
+  bb1: {
+    _0 = Optional<int64>#1();
+    return;
+  }
+
+  bb2: {
+    _4 = _1 / const 2_int64;
+    _0 = Optional<int64>#0(_4);
+    return;
+  }
+}
+
+func quarter(_1: int64) -> Optional<int64> {
+  let mut _0: Optional<int64>;
+  let _1: int64; // n
+  let _2: int64; // value
+  let _3: int64;
+  let _4: int64;
+  let _5: Optional<int64>;
+  let _6: uint32;
+  let _7: bool;
+
+  bb0: {
+    _5 = half(_1) -> bb1;
+  }
+
+  bb1: {
+    _6 = discriminant(_5);
+    _7 = _6 == const 0_uint32;
+    if _7 -> [true: bb4, false: bb3];
+  }
+
+  bb2: {
+    _3 = _4 / const 2_int64;
+    _0 = Optional<int64>#0(_3);
+    return;
+  }
+
+  bb3: {
+    _0 = Optional<int64>#1();
+    return;
+  }
+
+  bb4: {
+    StorageLive(_2);
+    _2 = (_5 as #0).0;
+    _4 = _2;
+    goto -> bb2;
+  }
+}
+"
+    );
+  }
+
+  #[test]
+  fn test_match() {
+    let program = build(
+      "enum Shape { Circle(int64), Rect(int64, int64), Empty }
+       func area(shape: Shape, flag: bool): int64 {
+         match flag { true => { return 0; } false => {} }
+         return match shape { Shape::Rect(1, h) => h, Shape::Circle(r) => r, _ => 0 };
+       }",
+    );
+    assert_eq!(
+      program.to_string(),
+      "\
+func area(_1: Shape, _2: bool) -> int64 {
+  let mut _0: int64;
+  let _1: Shape; // shape
+  let _2: bool; // flag
+  let _3: int64; // h
+  let _4: int64; // r
+  let _5: unit;
+  let _6: uint32;
+  let _7: bool;
+  let _8: int64;
+  let _9: bool;
+  let _10: uint32;
+  let _11: bool;
+
+  bb0: {
+    if _2 -> [true: bb3, false: bb2];
+  }
+
+  bb1: {
+    _6 = discriminant(_1);
+    _7 = _6 == const 1_uint32;
+    if _7 -> [true: bb6, false: bb5];
+  }
+
+  bb2: {
+    _5 = const ();
+    goto -> bb1;
+  }
+
+  bb3: {
+    _0 = const 0_int64;
+    return;
+  }
+
+  bb4: {
+    return;
+  }
+
+  bb5: {
+    _10 = discriminant(_1);
+    _11 = _10 == const 0_uint32;
+    if _11 -> [true: bb9, false: bb8];
+  }
+
+  bb6: {
+    _8 = (_1 as #1).0;
+    _9 = _8 == const 1_int64;
+    if _9 -> [true: bb7, false: bb5];
+  }
+
+  bb7: {
+    StorageLive(_3);
+    _3 = (_1 as #1).1;
+    _0 = _3;
+    goto -> bb4;
+  }
+
+  bb8: {
+    _0 = const 0_int64;
+    goto -> bb4;
+  }
+
+  bb9: {
+    StorageLive(_4);
+    _4 = (_1 as #0).0;
+    _0 = _4;
+    goto -> bb4;
+  }
+}
+"
+    );
+  }
+
+  #[test]
+  fn test_async() {
+    let program = build(
+      "@Lang(Poll) enum Poll<F, T> { Ready(T), Pending(F) }
+       @Lang(Future) interface Future {
+         type Output;
+         func poll(): Poll<Self, Self::Output>;
+       }
+       async func fetch(id: int64): int64 { return id; }
+       async func twice(id: int64): int64 { return fetch(id).await * 2; }",
+    );
+    let def = DefPath::new(Path::root(), "twice");
+    let constructor = program.body(&MonoItem::Function(def.clone())).unwrap();
+    assert_eq!(
+      constructor.to_string(),
+      "\
+func twice(_1: int64) -> {async twice} {
+  let mut _0: {async twice};
+  let _1: int64; // id
+
+  bb0: {
+    _0 = {async twice}#0(_1);
+    return;
+  }
+}
+"
+    );
+    let poll = program.body(&MonoItem::Coroutine(def)).unwrap();
+    assert_eq!(poll.coroutine.as_ref().unwrap().states.len(), 2);
+    assert_eq!(
+      poll.to_string(),
+      "\
+func {async twice}::poll(_1: {async twice}) -> Poll<{async twice}, int64> {
+  let mut _0: Poll<{async twice}, int64>;
+  let _1: {async twice}; // self
+  let mut _2: int64;
+  let _3: int64; // id
+  let _4: int64;
+  let mut _5: {async fetch};
+  let _6: Poll<{async fetch}, int64>;
+  let _7: uint32;
+  let _8: bool;
+  let mut _9: {async twice};
+  let mut _10: uint32;
+  let mut _11: bool;
+
+  bb0: {
+    _10 = discriminant(_1);
+    _11 = _10 == const 0_uint32;
+    if _11 -> [true: bb7, false: bb8];
+  }
+
+  bb1: {
+    _5 = fetch(_3) -> bb2;
+  }
+
+  bb2: {
+    goto -> bb3;
+  }
+
+  bb3: {
+    _6 = <{async fetch} as Future>::poll(_5) -> bb4;
+  }
+
+  bb4: {
+    _7 = discriminant(_6);
+    _8 = _7 == const 0_uint32;
+    if _8 -> [true: bb5, false: bb6];
+  }
+
+  bb5: {
+    _4 = (_6 as #0).0;
+    _2 = _4 * const 2_int64;
+    _0 = Poll<{async twice}, int64>#0(_2);
+    return;
+  }
+
+  bb6: {
+    _5 = (_6 as #1).0;
+    _9 = {async twice}#1(_5);
+    _0 = Poll<{async twice}, int64>#1(_9);
+    return;
+  }
+
+  bb7: {
+    _3 = (_1 as #0).0;
+    goto -> bb1;
+  }
+
+  bb8: {
+    _11 = _10 == const 1_uint32;
+    if _11 -> [true: bb9, false: bb10];
+  }
+
+  bb9: {
+    _5 = (_1 as #1).0;
+    goto -> bb3;
+  }
+
+  bb10: {
+    unreachable;
+  }
+}
+"
+    );
+  }
+}
@@ -0,0 +1,429 @@
+//! Checks of the bodies by the dataflow analysis on the control flow graph.
+//!
+//! The analysis computes whether each local is initialized at the start of each block, where a
+//! local is uninitialized at the entry unless it is an argument, and again at its `StorageLive`.
+//! With the states, the following errors are reported:
+//!
+//! - use of a user variable which is possibly uninitialized,
+//! - assignment to an immutable variable declared by `let` which is possibly initialized,
+//! - return from a function whose return value is possibly uninitialized, which means that the
+//!   function falls off the end without returning a value.
+//!
+//! Taking the address of a local by `&` counts as initializing it, since it might be initialized
+//! through the raw pointer, and writes through raw pointers are not checked against `let`.
+//!
+//! Only the reachable blocks are checked, since unreachable statements are reported separately.
+use std::collections::HashSet;
+
+use vsp_diag::Diagnostic;
+use vsp_diag::DiagnosticEngine;
+use vsp_sema::mono::MonoItem;
+use vsp_span::Span;
+
+use crate::mir::BasicBlock;
+use crate::mir::Body;
+use crate::mir::Local;
+use crate::mir::Operand;
+use crate::mir::Place;
+use crate::mir::Rvalue;
+use crate::mir::StatementKind;
+use crate::mir::TerminatorKind;
+
+/// Check the body. Errors are reported to the diagnostics in the order of their locations.
+pub fn check_body(body: &Body, diagnostics: &mut DiagnosticEngine) {
+  let entries = states_at_entries(body);
+  let mut checker = Checker {
+    body,
+    diagnostics: vec![],
+    state: vec![],
+    reported: HashSet::new(),
+  };
+  for (index, entry) in entries.into_iter().enumerate() {
+    // Blocks without the entry state are unreachable.
+    if let Some(entry) = entry {
+      checker.state = entry;
+      checker.check_block(BasicBlock(index));
+    }
+  }
+  let mut errors = checker.diagnostics;
+  errors.sort_by_key(|diagnostic| diagnostic.span.as_ref().map(|span| span.start));
+  errors.into_iter().for_each(|diagnostic| diagnostics.emit(diagnostic));
+}
+
+/// Initialization state of a local at a point of the body.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Initialized {
+  No,
+  Yes,
+  /// Initialized on some of the paths reaching the point.
+  Maybe,
+}
+
+/// States of the locals at the start of each reachable block.
+fn states_at_entries(body: &Body) -> Vec<Option<Vec<Initialized>>> {
+  let mut entries: Vec<Option<Vec<Initialized>>> = vec![None; body.blocks.len()];
+  let mut start = vec![Initialized::No; body.locals.len()];
+  body.args().for_each(|arg| start[arg.0] = Initialized::Yes);
+  entries[0] = Some(start);
+  let mut worklist = vec![BasicBlock(0)];
+  while let Some(block) = worklist.pop() {
+    let mut state = entries[block.0].clone().unwrap();
+    let data = body.block(block);
+    for statement in &data.statements {
+      transfer(&statement.kind, &mut state);
+    }
+    if let Some(destination) = destination(&data.terminator().kind) {
+      initialize(destination, &mut state);
+    }
+    for successor in data.terminator().successors() {
+      let changed = match &mut entries[successor.0] {
+        Some(entry) => join(entry, &state),
+        entry => {
+          *entry = Some(state.clone());
+          true
+        }
+      };
+      if changed {
+        worklist.push(successor);
+      }
+    }
+  }
+  entries
+}
+
+fn transfer(kind: &StatementKind, state: &mut [Initialized]) {
+  match kind {
+    StatementKind::Assign(place, rvalue) => {
+      if let Rvalue::AddressOf(target) = rvalue.as_ref() {
+        if !target.is_indirect() {
+          state[target.local.0] = Initialized::Yes;
+        }
+      }
+      initialize(place, state)
+    }
+    StatementKind::StorageLive(local) => state[local.0] = Initialized::No,
+  }
+}
+
+/// Assignment to the whole local initializes it, while assignment to its field or through it as a
+/// raw pointer does not.
+fn initialize(place: &Place, state: &mut [Initialized]) {
+  if place.projection.is_empty() {
+    state[place.local.0] = Initialized::Yes;
+  }
+}
+
+fn destination(kind: &TerminatorKind) -> Option<&Place> {
+  match kind {
+    TerminatorKind::Call { destination, .. } | TerminatorKind::DynCall { destination, .. } => {
+      Some(destination)
+    }
+    _ => None,
+  }
+}
+
+/// Merge the state into the entry, and return whether the entry is changed.
+fn join(entry: &mut [Initialized], state: &[Initialized]) -> bool {
+  let mut changed = false;
+  for (entry, state) in entry.iter_mut().zip(state) {
+    if entry != state && *entry != Initialized::Maybe {
+      *entry = Initialized::Maybe;
+      changed = true;
+    }
+  }
+  changed
+}
+
+struct Checker<'a> {
+  body:        &'a Body,
+  diagnostics: Vec<Diagnostic>,
+  /// States of the locals at the current statement.
+  state:       Vec<Initialized>,
+  /// Variables whose uninitialized uses are already reported.
+  reported:    HashSet<Local>,
+}
+
+impl<'a> Checker<'a> {
+  fn check_block(&mut self, block: BasicBlock) {
+    let data = self.body.block(block);
+    for statement in &data.statements {
+      match &statement.kind {
+        StatementKind::Assign(place, rvalue) => {
+          self.check_rvalue(rvalue, &statement.span);
+          self.check_assign(place, &statement.span);
+        }
+        StatementKind::StorageLive(_) => {}
+      }
+      transfer(&statement.kind, &mut self.state);
+    }
+
+    let terminator = data.terminator();
+    match &terminator.kind {
+      TerminatorKind::If { condition, .. } => self.check_operand(condition, &terminator.span),
+      TerminatorKind::Return if self.state[Body::RETURN_PLACE.0] != Initialized::Yes => {
+        self.report_missing_return()
+      }
+      TerminatorKind::Call {
+        func,
+        args,
+        destination,
+        ..
+      } => {
+        self.check_operand(func, &terminator.span);
+        args.iter().for_each(|arg| self.check_operand(arg, &terminator.span));
+        self.check_assign(destination, &terminator.span);
+      }
+      TerminatorKind::DynCall {
+        object,
+        args,
+        destination,
+        ..
+      } => {
+        self.check_operand(object, &terminator.span);
+        args.iter().for_each(|arg| self.check_operand(arg, &terminator.span));
+        self.check_assign(destination, &terminator.span);
+      }
+      TerminatorKind::Goto(_)
+      | TerminatorKind::Yield { .. }
+      | TerminatorKind::Return
+      | TerminatorKind::Unreachable => {}
+    }
+  }
+
+  fn check_rvalue(&mut self, rvalue: &Rvalue, span: &Span) {
+    match rvalue {
+      Rvalue::Use(operand)
+      | Rvalue::Unary(_, operand)
+      | Rvalue::Cast(operand, _)
+      | Rvalue::ToDyn(operand, _) => self.check_operand(operand, span),
+      Rvalue::Binary(_, left, right) => {
+        self.check_operand(left, span);
+        self.check_operand(right, span);
+      }
+      Rvalue::Struct(_, fields) => {
+        fields.iter().for_each(|(_, operand)| self.check_operand(operand, span))
+      }
+      Rvalue::Variant(_, _, fields) | Rvalue::Array(_, fields) => {
+        fields.iter().for_each(|operand| self.check_operand(operand, span))
+      }
+      Rvalue::Discriminant(place) | Rvalue::VariantField(place, ..) => {
+        self.check_initialized(place.local, span)
+      }
+      Rvalue::AddressOf(place) if place.is_indirect() => self.check_initialized(place.local, span),
+      Rvalue::AddressOf(_) => {}
+    }
+  }
+
+  fn check_operand(&mut self, operand: &Operand, span: &Span) {
+    if let Operand::Copy(place) = operand {
+      self.check_initialized(place.local, span);
+    }
+  }
+
+  /// Report the use of the user variable which is possibly uninitialized. Temporaries are always
+  /// initialized before their uses by the construction.
+  fn check_initialized(&mut self, local: Local, span: &Span) {
+    let decl = self.body.local(local);
+    if let (Some(name), false) = (&decl.name, self.state[local.0] == Initialized::Yes) {
+      if self.reported.insert(local) {
+        self.diagnostics.push(Diagnostic::error(
+          format!("use of possibly uninitialized variable `{}`", name),
+          span.clone(),
+        ));
+      }
+    }
+  }
+
+  fn check_assign(&mut self, place: &Place, span: &Span) {
+    if place.is_indirect() {
+      self.check_initialized(place.local, span);
+      return;
+    }
+    let decl = self.body.local(place.local);
+    let name = match &decl.name {
+      Some(name) if !decl.mutable => name,
+      _ => {
+        if !place.projection.is_empty() {
+          self.check_initialized(place.local, span);
+        }
+        return;
+      }
+    };
+    if !place.projection.is_empty() {
+      self.diagnostics.push(
+        Diagnostic::error(
+          format!("cannot assign to a field of immutable variable `{}`", name),
+          span.clone(),
+        )
+        .with_note("variables declared by `let` are immutable, use `var` instead"),
+      );
+    } else if self.state[place.local.0] != Initialized::No {
+      self.diagnostics.push(
+        Diagnostic::error(
+          format!("cannot assign twice to immutable variable `{}`", name),
+          span.clone(),
+        )
+        .with_note("variables declared by `let` are immutable, use `var` instead"),
+      );
+    }
+  }
+
+  fn report_missing_return(&mut self) {
+    let name = match &self.body.owner {
+      MonoItem::Function(def)
+      | MonoItem::Coroutine(def)
+      | MonoItem::Const(def)
+      | MonoItem::Static(def) => def.name.to_owned(),
+      MonoItem::Method(path) => path.name.to_owned(),
+    };
+    self.diagnostics.push(Diagnostic::error(
+      format!(
+        "function `{}` returns `{}`, but could reach the end without returning a value",
+        name,
+        self.body.return_type()
+      ),
+      self.body.span.clone(),
+    ));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use vsp_sema::testing::check_source;
+
+  use super::*;
+  use crate::build::build_program;
+
+  fn check(source: &str) -> Vec<String> {
+    let (unit, results) = check_source(source);
+    let mut diagnostics = DiagnosticEngine::default();
+    build_program(
+      &vsp_hir::lower_compilation_unit(&unit, &results),
+      &mut diagnostics,
+    );
+    diagnostics.diagnostics().iter().map(|d| d.to_string()).collect()
+  }
+
+  #[test]
+  fn test_initialized() {
+    let messages = check(
+      "func main(c: bool): int64 {
+         let x: int64;
+         if c { x = 1; } else { x = 2; }
+         var y: int64;
+         while c { y = x; }
+         y = 0;
+         return x + y;
+       }",
+    );
+    assert_eq!(messages, Vec::<String>::new());
+  }
+
+  #[test]
+  fn test_uninitialized() {
+    let messages = check(
+      "func main(c: bool): int64 {
+         let x: int64;
+         if c { x = 1; }
+         var y: int64;
+         while c { y = 1; }
+         return x + y + x;
+       }",
+    );
+    assert_eq!(
+      messages,
+      vec![
+        "6:17: error: use of possibly uninitialized variable `x`",
+        "6:17: error: use of possibly uninitialized variable `y`",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_immutable() {
+    let messages = check(
+      "struct Point { x: int64, y: int64 }
+       func main(c: bool): int64 {
+         let x = 1;
+         x = 2;
+         let y: int64;
+         while c { y = 1; }
+         let p = Point { x: 1, y: 2 };
+         p.x = 3;
+         var q = Point { x: 1, y: 2 };
+         q.y = x;
+         while c { let z = 1; }
+         return p.x;
+       }",
+    );
+    assert_eq!(
+      messages,
+      vec![
+        "4:10: error: cannot assign twice to immutable variable `x`",
+        "6:20: error: cannot assign twice to immutable variable `y`",
+        "8:10: error: cannot assign to a field of immutable variable `p`",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_pointers() {
+    let messages = check(
+      "func main(): int64 {
+         var x: int64;
+         let p = &x;
+         let q: *int64;
+         unsafe {
+           *p = 1;
+           *q = 2;
+         }
+         return x;
+       }",
+    );
+    assert_eq!(
+      messages,
+      vec!["7:12: error: use of possibly uninitialized variable `q`"]
+    );
+  }
+
+  #[test]
+  fn test_missing_return() {
+    let messages = check(
+      "func sign(x: int64): int64 {
+         if x > 0 { return 1; } else if x < 0 { return -1; }
+       }
+       func abs(x: int64): int64 {
+         if x < 0 { return -x; } else { return x; }
+       }
+       func nothing() {}",
+    );
+    assert_eq!(messages, vec![
+      "1:1: error: function `sign` returns `int64`, but could reach the end without returning a value",
+    ]);
+  }
+
+  #[test]
+  fn test_unreachable() {
+    let messages = check(
+      "func main(c: bool): int64 {
+         while c {
+           return 1;
+           let x = 2;
+           let y = 3;
+         }
+         if c { return 2; } else { return 3; }
+         {
+           let z = 4;
+         }
+         return 5;
+       }",
+    );
+    assert_eq!(
+      messages,
+      vec![
+        "4:12: warning: unreachable statement",
+        "9:12: warning: unreachable statement",
+      ]
+    );
+  }
+}
@@ -0,0 +1,449 @@
+//! Transformation of the bodies of `async func` into the state machines.
+//!
+//! Calling `async func` returns the state machine holding the arguments, instead of running the
+//! body. The body is run by `poll` of the state machine, which is the lang item `Future` of the
+//! type `{async f}`. Once the body is checked, it is split into two bodies:
+//!
+//! - the constructor, owned by the function, which stores the arguments into the state 0,
+//! - `poll`, owned by [`MonoItem::Coroutine`], which takes the state machine as `_1` and restores
+//!   the locals saved in its state, then continues from the start of the body or from the
+//!   suspension point where it was suspended. `return` returns the output as `Poll::Ready`, and
+//!   `yield` saves the locals live across the suspension point into the next state, which is
+//!   returned as `Poll::Pending`.
+//!
+//! The locals of the original body are placed after `_1` in `poll`, so `_2` is the output. Locals
+//! are copied in and out of the state machine, so raw pointers to them do not stay valid across
+//! suspension points.
+//!
+//! ```plaintext
+//! func fetch(_1: int64) -> {async fetch} {
+//!   let mut _0: {async fetch};
+//!   let _1: int64; // id
+//!
+//!   bb0: {
+//!     _0 = {async fetch}#0(_1);
+//!     return;
+//!   }
+//! }
+//! ```
+use vsp_ast::ast::expr::BinaryOp;
+use vsp_ast::ast::types::PrimitiveType;
+use vsp_ast::ast::types::Type;
+use vsp_hir::hir;
+use vsp_sema::lang::LangItem;
+use vsp_sema::mono::MonoItem;
+use vsp_span::Span;
+
+use crate::mir::BasicBlock;
+use crate::mir::BasicBlockData;
+use crate::mir::Body;
+use crate::mir::Constant;
+use crate::mir::ConstantKind;
+use crate::mir::CoroutineLayout;
+use crate::mir::Local;
+use crate::mir::LocalDecl;
+use crate::mir::Operand;
+use crate::mir::Place;
+use crate::mir::ProjectionElem;
+use crate::mir::Rvalue;
+use crate::mir::SourceScope;
+use crate::mir::Statement;
+use crate::mir::StatementKind;
+use crate::mir::Terminator;
+use crate::mir::TerminatorKind;
+
+/// Locals placed before those of the original body in `poll`, i.e. `_0` and `_1`.
+const POLL_LOCALS: usize = 2;
+
+/// The receiver `self` of `poll`, which is the state machine.
+const STATE_MACHINE: Local = Local(1);
+
+/// Split the body of `async func` into the constructor of the state machine and its `poll`.
+pub fn split_coroutine(body: Body, coroutine: &hir::Coroutine) -> (Body, Body) {
+  let live = live_locals(&body);
+  let mut states = vec![body.args().collect::<Vec<_>>()];
+  for block in &body.blocks {
+    if let TerminatorKind::Yield { resume } = block.terminator().kind {
+      let saved = (0..body.locals.len()).filter(|local| live[resume.0][*local]);
+      states.push(saved.map(Local).collect());
+    }
+  }
+  let constructor = constructor(&body, coroutine);
+  let poll = poll(body, coroutine, &states);
+  (constructor, poll)
+}
+
+/// Body of the function, which returns the state machine in the state 0 holding the arguments.
+fn constructor(body: &Body, coroutine: &hir::Coroutine) -> Body {
+  let mut locals = vec![LocalDecl {
+    name:    None,
+    ty:      coroutine.ty.clone(),
+    mutable: true,
+    span:    body.span.clone(),
+    scope:   SourceScope::OUTERMOST,
+  }];
+  locals.extend(body.args().map(|arg| body.local(arg).clone()));
+  let args = body.args().map(|arg| Operand::Copy(arg.into())).collect();
+  let statement = Statement {
+    kind: StatementKind::Assign(
+      Body::RETURN_PLACE.into(),
+      Box::new(Rvalue::Variant(coroutine.ty.clone(), 0, args)),
+    ),
+    span: body.span.clone(),
+  };
+  Body {
+    owner: body.owner.clone(),
+    file: body.file.clone(),
+    locals,
+    arg_count: body.arg_count,
+    scopes: body.scopes.clone(),
+    blocks: vec![BasicBlockData {
+      statements: vec![statement],
+      terminator: Some(Terminator {
+        kind: TerminatorKind::Return,
+        span: body.span.clone(),
+      }),
+    }],
+    span: body.span.clone(),
+    constancy: body.constancy,
+    coroutine: None,
+  }
+}
+
+/// Body of `poll`, which dispatches on the state at the entry `bb0`, followed by the blocks of the
+/// original body and the blocks restoring the locals of each state.
+fn poll(body: Body, coroutine: &hir::Coroutine, states: &[Vec<Local>]) -> Body {
+  let span = body.span.clone();
+  let def = match &body.owner {
+    MonoItem::Function(def) => def.clone(),
+    owner => unreachable!("unexpected owner of `async func`: {:?}", owner),
+  };
+  let mut poll = Body {
+    owner:     MonoItem::Coroutine(def),
+    file:      body.file.clone(),
+    locals:    vec![
+      LocalDecl {
+        name:    None,
+        ty:      coroutine.poll.clone(),
+        mutable: true,
+        span:    span.clone(),
+        scope:   SourceScope::OUTERMOST,
+      },
+      LocalDecl {
+        name:    Some("self".to_owned()),
+        ty:      coroutine.ty.clone(),
+        mutable: false,
+        span:    span.clone(),
+        scope:   SourceScope::OUTERMOST,
+      },
+    ],
+    arg_count: 1,
+    scopes:    body.scopes.clone(),
+    blocks:    vec![BasicBlockData::default()],
+    span:      span.clone(),
+    constancy: body.constancy,
+    coroutine: Some(CoroutineLayout {
+      ty:     coroutine.ty.clone(),
+      states: states
+        .iter()
+        .map(|saved| saved.iter().map(|local| body.local(*local).ty.clone()).collect())
+        .collect(),
+    }),
+  };
+  poll.locals.extend(body.locals);
+
+  // Temporary holding the next state, only if the body could be suspended.
+  let mut next = None;
+  let mut suspensions = 0;
+  let mut resumes = vec![BasicBlock(1)];
+  for mut block in body.blocks {
+    visit_locals(&mut block, &mut |local| local.0 += POLL_LOCALS);
+    let terminator = block.terminator.as_mut().unwrap();
+    let rvalue = match &mut terminator.kind {
+      TerminatorKind::Return => {
+        let output = Operand::Copy(shifted(Body::RETURN_PLACE).into());
+        Rvalue::Variant(coroutine.poll.clone(), LangItem::READY, vec![output])
+      }
+      TerminatorKind::Yield { resume } => {
+        suspensions += 1;
+        resumes.push(BasicBlock(resume.0 + 1));
+        let saved = states[suspensions].iter().map(|local| Operand::Copy(shifted(*local).into()));
+        let rvalue = Rvalue::Variant(coroutine.ty.clone(), suspensions, saved.collect());
+        let next = *next.get_or_insert_with(|| temp(&mut poll, coroutine.ty.clone(), &span));
+        let statement = assign(next.into(), rvalue, &terminator.span);
+        block.statements.push(statement);
+        let next = Operand::Copy(next.into());
+        Rvalue::Variant(coroutine.poll.clone(), LangItem::PENDING, vec![next])
+      }
+      kind => {
+        shift_blocks(kind, 1);
+        poll.blocks.push(block);
+        continue;
+      }
+    };
+    block
+      .statements
+      .push(assign(Body::RETURN_PLACE.into(), rvalue, &terminator.span));
+    terminator.kind = TerminatorKind::Return;
+    poll.blocks.push(block);
+  }
+
+  // `bb0: _d = discriminant(_1); _c = _d == const 0_uint32; if _c -> [restore 0, next state]`
+  let discriminant = temp(&mut poll, Type::Primitive(PrimitiveType::Uint32), &span);
+  let is_state = temp(&mut poll, Type::Primitive(PrimitiveType::Bool), &span);
+  let rvalue = Rvalue::Discriminant(STATE_MACHINE.into());
+  poll.blocks[0].statements.push(assign(discriminant.into(), rvalue, &span));
+  let mut dispatch = BasicBlock(0);
+  for (state, saved) in states.iter().enumerate() {
+    let statements = saved
+      .iter()
+      .enumerate()
+      .map(|(field, local)| {
+        let rvalue = Rvalue::VariantField(STATE_MACHINE.into(), state, field);
+        assign(shifted(*local).into(), rvalue, &span)
+      })
+      .collect();
+    let restore = push_block(
+      &mut poll,
+      statements,
+      TerminatorKind::Goto(resumes[state]),
+      &span,
+    );
+    let otherwise = push_block(&mut poll, vec![], TerminatorKind::Unreachable, &span);
+    let state = Operand::Constant(Constant {
+      kind: ConstantKind::Integer(state as i64),
+      ty:   Type::Primitive(PrimitiveType::Uint32),
+    });
+    let rvalue = Rvalue::Binary(BinaryOp::Equal, Operand::Copy(discriminant.into()), state);
+    let data = &mut poll.blocks[dispatch.0];
+    data.statements.push(assign(is_state.into(), rvalue, &span));
+    data.terminator = Some(Terminator {
+      kind: TerminatorKind::If {
+        condition: Operand::Copy(is_state.into()),
+        then: restore,
+        otherwise,
+      },
+      span: span.clone(),
+    });
+    dispatch = otherwise;
+  }
+  poll
+}
+
+/// Locals live at the start of each block, i.e. those which might be read before being assigned as
+/// a whole. Assignments to the fields read the rest of the local.
+fn live_locals(body: &Body) -> Vec<Vec<bool>> {
+  let mut live = vec![vec![false; body.locals.len()]; body.blocks.len()];
+  let mut changed = true;
+  while changed {
+    changed = false;
+    for (index, block) in body.blocks.iter().enumerate().rev() {
+      let terminator = block.terminator();
+      let mut state = vec![false; body.locals.len()];
+      for successor in terminator.successors() {
+        state
+          .iter_mut()
+          .zip(&live[successor.0])
+          .for_each(|(state, live)| *state |= live);
+      }
+      match &terminator.kind {
+        TerminatorKind::Return => state[Body::RETURN_PLACE.0] = true,
+        TerminatorKind::If { condition, .. } => read_operand(condition, &mut state),
+        TerminatorKind::Call {
+          func,
+          args,
+          destination,
+          ..
+        } => {
+          write(destination, &mut state);
+          std::iter::once(func).chain(args).for_each(|arg| read_operand(arg, &mut state));
+        }
+        TerminatorKind::DynCall {
+          object,
+          args,
+          destination,
+          ..
+        } => {
+          write(destination, &mut state);
+          std::iter::once(object)
+            .chain(args)
+            .for_each(|arg| read_operand(arg, &mut state));
+        }
+        TerminatorKind::Goto(_) | TerminatorKind::Yield { .. } | TerminatorKind::Unreachable => {}
+      }
+      for statement in block.statements.iter().rev() {
+        match &statement.kind {
+          StatementKind::Assign(place, rvalue) => {
+            write(place, &mut state);
+            read_rvalue(rvalue, &mut state);
+          }
+          StatementKind::StorageLive(local) => state[local.0] = false,
+        }
+      }
+      if state != live[index] {
+        live[index] = state;
+        changed = true;
+      }
+    }
+  }
+  live
+}
+
+fn write(place: &Place, state: &mut [bool]) {
+  state[place.local.0] = !place.projection.is_empty();
+  place.indices().for_each(|index| state[index.0] = true);
+}
+
+fn read_place(place: &Place, state: &mut [bool]) {
+  state[place.local.0] = true;
+  place.indices().for_each(|index| state[index.0] = true);
+}
+
+fn read_operand(operand: &Operand, state: &mut [bool]) {
+  if let Operand::Copy(place) = operand {
+    read_place(place, state);
+  }
+}
+
+fn read_rvalue(rvalue: &Rvalue, state: &mut [bool]) {
+  match rvalue {
+    Rvalue::Use(operand)
+    | Rvalue::Unary(_, operand)
+    | Rvalue::Cast(operand, _)
+    | Rvalue::ToDyn(operand, _) => read_operand(operand, state),
+    Rvalue::Binary(_, left, right) => {
+      read_operand(left, state);
+      read_operand(right, state);
+    }
+    Rvalue::Struct(_, fields) => fields.iter().for_each(|(_, field)| read_operand(field, state)),
+    Rvalue::Variant(_, _, fields) | Rvalue::Array(_, fields) => {
+      fields.iter().for_each(|field| read_operand(field, state))
+    }
+    Rvalue::Discriminant(place) | Rvalue::VariantField(place, ..) | Rvalue::AddressOf(place) => {
+      read_place(place, state)
+    }
+  }
+}
+
+/// Apply the function to each local referred by the block.
+fn visit_locals(block: &mut BasicBlockData, f: &mut impl FnMut(&mut Local)) {
+  let place = |place: &mut Place, f: &mut dyn FnMut(&mut Local)| {
+    f(&mut place.local);
+    for (elem, _) in &mut place.projection {
+      if let ProjectionElem::Index(index) = elem {
+        f(index);
+      }
+    }
+  };
+  let operand = |operand: &mut Operand, f: &mut dyn FnMut(&mut Local)| {
+    if let Operand::Copy(copied) = operand {
+      place(copied, f);
+    }
+  };
+  for statement in &mut block.statements {
+    match &mut statement.kind {
+      StatementKind::Assign(assigned, rvalue) => {
+        place(assigned, f);
+        match rvalue.as_mut() {
+          Rvalue::Use(value)
+          | Rvalue::Unary(_, value)
+          | Rvalue::Cast(value, _)
+          | Rvalue::ToDyn(value, _) => operand(value, f),
+          Rvalue::Binary(_, left, right) => {
+            operand(left, f);
+            operand(right, f);
+          }
+          Rvalue::Struct(_, fields) => fields.iter_mut().for_each(|(_, field)| operand(field, f)),
+          Rvalue::Variant(_, _, fields) | Rvalue::Array(_, fields) => {
+            fields.iter_mut().for_each(|field| operand(field, f))
+          }
+          Rvalue::Discriminant(target)
+          | Rvalue::VariantField(target, ..)
+          | Rvalue::AddressOf(target) => place(target, f),
+        }
+      }
+      StatementKind::StorageLive(local) => f(local),
+    }
+  }
+  match &mut block.terminator.as_mut().unwrap().kind {
+    TerminatorKind::If { condition, .. } => operand(condition, f),
+    TerminatorKind::Call {
+      func,
+      args,
+      destination,
+      ..
+    } => {
+      std::iter::once(func).chain(args).for_each(|arg| operand(arg, f));
+      place(destination, f);
+    }
+    TerminatorKind::DynCall {
+      object,
+      args,
+      destination,
+      ..
+    } => {
+      std::iter::once(object).chain(args).for_each(|arg| operand(arg, f));
+      place(destination, f);
+    }
+    TerminatorKind::Goto(_)
+    | TerminatorKind::Yield { .. }
+    | TerminatorKind::Return
+    | TerminatorKind::Unreachable => {}
+  }
+}
+
+/// Shift the successors of the terminator by the blocks inserted before.
+fn shift_blocks(kind: &mut TerminatorKind, offset: usize) {
+  match kind {
+    TerminatorKind::Goto(target)
+    | TerminatorKind::Yield { resume: target }
+    | TerminatorKind::Call { target, .. }
+    | TerminatorKind::DynCall { target, .. } => target.0 += offset,
+    TerminatorKind::If {
+      then, otherwise, ..
+    } => {
+      then.0 += offset;
+      otherwise.0 += offset;
+    }
+    TerminatorKind::Return | TerminatorKind::Unreachable => {}
+  }
+}
+
+/// Local in `poll` of the local in the original body.
+fn shifted(local: Local) -> Local {
+  Local(local.0 + POLL_LOCALS)
+}
+
+fn temp(body: &mut Body, ty: Type, span: &Span) -> Local {
+  body.locals.push(LocalDecl {
+    name: None,
+    ty,
+    mutable: true,
+    span: span.clone(),
+    scope: SourceScope::OUTERMOST,
+  });
+  Local(body.locals.len() - 1)
+}
+
+fn assign(place: Place, rvalue: Rvalue, span: &Span) -> Statement {
+  Statement {
+    kind: StatementKind::Assign(place, Box::new(rvalue)),
+    span: span.clone(),
+  }
+}
+
+fn push_block(
+  body: &mut Body,
+  statements: Vec<Statement>,
+  kind: TerminatorKind,
+  span: &Span,
+) -> BasicBlock {
+  body.blocks.push(BasicBlockData {
+    statements,
+    terminator: Some(Terminator {
+      kind,
+      span: span.clone(),
+    }),
+  });
+  BasicBlock(body.blocks.len() - 1)
+}
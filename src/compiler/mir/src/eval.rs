@@ -0,0 +1,1216 @@
+//! Evaluation of constants and static items at compile time.
+//!
+//! Initializers are evaluated by interpreting their bodies, which might call `const func`s with
+//! loops and recursion. Bodies evaluated at compile time are checked ahead not to call functions
+//! other than `const func`s, nor to read static items unless they initialize static items. Calls
+//! through function values are only known during the evaluation, so they are checked as they
+//! happen. Integer arithmetic is checked against the range of its type, and the overflows and the
+//! divisions by zero are reported instead of wrapping around or trapping at runtime.
+//!
+//! Once evaluated, uses of the constants are replaced by their values, and so are the sizes of
+//! arrays given by the constants, i.e. [`Type::ConstArray`] is replaced by [`Type::Array`].
+//!
+//! The same interpreter executes whole programs by [`execute`], as if at runtime, which runs the
+//! programs in tests until the code generation supports them.
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use vsp_ast::ast::expr::BinaryOp;
+use vsp_ast::ast::expr::UnaryOp;
+use vsp_ast::ast::module::Path;
+use vsp_ast::ast::types::FunctionType;
+use vsp_ast::ast::types::PrimitiveType;
+use vsp_ast::ast::types::Type;
+use vsp_diag::Diagnostic;
+use vsp_diag::DiagnosticEngine;
+use vsp_sema::items::ItemTable;
+use vsp_sema::items::MethodPath;
+use vsp_sema::mono::substitute;
+use vsp_sema::mono::Instance;
+use vsp_sema::mono::MonoItem;
+use vsp_sema::resolve::DefPath;
+use vsp_span::Span;
+
+use crate::mir::BasicBlock;
+use crate::mir::Body;
+use crate::mir::ConstValue;
+use crate::mir::Constant;
+use crate::mir::ConstantKind;
+use crate::mir::Local;
+use crate::mir::Operand;
+use crate::mir::Place;
+use crate::mir::Program;
+use crate::mir::ProjectionElem;
+use crate::mir::Rvalue;
+use crate::mir::StatementKind;
+use crate::mir::TerminatorKind;
+
+/// Limit of the statements and the terminators executed to evaluate each item, so that infinite
+/// loops are reported rather than hanging the compilation.
+const STEP_LIMIT: usize = 1_000_000;
+
+/// Limit of the nested calls, since each call is evaluated recursively.
+const CALL_DEPTH_LIMIT: usize = 128;
+
+/// Evaluate all constants and static items of the program, record their values in
+/// [`Program::consts`], and replace the uses of the constants by their values. Errors are reported
+/// to the diagnostics.
+pub fn evaluate_consts(program: &mut Program, diagnostics: &mut DiagnosticEngine) {
+  let bodies = program.bodies.iter().map(|body| (&body.owner, body)).collect::<HashMap<_, _>>();
+  for body in &program.bodies {
+    if body.constancy.is_constant() {
+      check_const_body(body, &bodies, diagnostics);
+    }
+  }
+
+  let items = program
+    .bodies
+    .iter()
+    .filter(|body| matches!(body.owner, MonoItem::Const(_) | MonoItem::Static(_)))
+    .map(|body| body.owner.clone())
+    .collect::<Vec<_>>();
+  let mut evaluator = Evaluator {
+    bodies,
+    diagnostics: &mut *diagnostics,
+    values: HashMap::new(),
+    stack: vec![],
+    steps: 0,
+    runtime: None,
+  };
+  for item in &items {
+    evaluator.evaluate(item);
+  }
+  let mut values = evaluator.values;
+  program.consts = items
+    .into_iter()
+    .filter_map(|owner| {
+      let value = values.remove(&owner).flatten()?;
+      let body = program.body(&owner)?;
+      let value = value.into_constant(body.return_type().clone());
+      Some(ConstValue { owner, value })
+    })
+    .collect();
+
+  let mut replacer = ConstReplacer {
+    diagnostics: &mut *diagnostics,
+    values:      HashMap::new(),
+    reported:    HashSet::new(),
+  };
+  for value in &program.consts {
+    if let MonoItem::Const(def) = &value.owner {
+      let body = program.body(&value.owner).unwrap();
+      let location = (body.span.clone(), body.file.clone());
+      replacer.values.insert(def.clone(), (value.value.clone(), location));
+    }
+  }
+  program.bodies.iter_mut().for_each(|body| replacer.replace_body(body));
+  diagnostics.set_current_file(None);
+}
+
+/// Execute the function without parameters as if at runtime, and return its value. Unlike the
+/// evaluation of the constants, any function with a body could be called, and the methods of the
+/// trait bounds are resolved by the type arguments, while trait objects are not supported yet.
+/// Errors are reported to the diagnostics.
+pub fn execute(
+  program: &Program,
+  items: &ItemTable,
+  entry: &MonoItem,
+  diagnostics: &mut DiagnosticEngine,
+) -> Option<Constant> {
+  let bodies = program.bodies.iter().map(|body| (&body.owner, body)).collect::<HashMap<_, _>>();
+  let body = *bodies.get(entry)?;
+  if body.arg_count > 0 || !matches!(body.return_type(), Type::Primitive(_)) {
+    diagnostics.set_current_file(body.file.clone());
+    diagnostics.emit(Diagnostic::error(
+      format!(
+        "only functions without parameters returning primitive types could be executed, found {}",
+        describe(entry)
+      ),
+      body.span.clone(),
+    ));
+    return None;
+  }
+  let mut evaluator = Evaluator {
+    bodies,
+    diagnostics: &mut *diagnostics,
+    values: HashMap::new(),
+    stack: vec![],
+    steps: 0,
+    runtime: Some(items),
+  };
+  let result = evaluator.call(body, HashMap::new(), vec![], 0);
+  let value = evaluator.report(entry, result);
+  diagnostics.set_current_file(None);
+  Some(value?.into_constant(body.return_type().clone()))
+}
+
+/// Context of the evaluation, which is named in the diagnostics.
+fn const_context(owner: &MonoItem) -> &'static str {
+  match owner {
+    MonoItem::Const(_) => "constants",
+    MonoItem::Static(_) => "statics",
+    MonoItem::Function(_) | MonoItem::Coroutine(_) | MonoItem::Method(_) => "constant functions",
+  }
+}
+
+/// Check the body evaluated at compile time for the operations which could not be evaluated.
+fn check_const_body(
+  body: &Body,
+  bodies: &HashMap<&MonoItem, &Body>,
+  diagnostics: &mut DiagnosticEngine,
+) {
+  let context = const_context(&body.owner);
+  let mut errors = vec![];
+  let check_operand = |operand: &Operand, span: &Span, errors: &mut Vec<(String, Span)>| {
+    if let Operand::Constant(Constant {
+      kind: ConstantKind::Static(_),
+      ..
+    }) = operand
+    {
+      if !matches!(body.owner, MonoItem::Static(_)) {
+        errors.push((format!("{} cannot refer to statics", context), span.clone()));
+      }
+    }
+  };
+  for block in &body.blocks {
+    for statement in &block.statements {
+      let (place, rvalue) = match &statement.kind {
+        StatementKind::Assign(place, rvalue) => (place, rvalue),
+        StatementKind::StorageLive(_) => continue,
+      };
+      // Raw pointers are only created by `&` and casts, and never exist during the evaluation.
+      let creates_pointer = match rvalue.as_ref() {
+        Rvalue::AddressOf(_) => true,
+        Rvalue::Cast(operand, target) => {
+          matches!(target, Type::Pointer(_)) || matches!(operand.ty(body), Type::Pointer(_))
+        }
+        _ => false,
+      };
+      if creates_pointer || place.is_indirect() {
+        errors.push((
+          format!("raw pointers cannot be used in {}", context),
+          statement.span.clone(),
+        ));
+      }
+      match rvalue.as_ref() {
+        Rvalue::Use(operand) | Rvalue::Unary(_, operand) | Rvalue::Cast(operand, _) => {
+          check_operand(operand, &statement.span, &mut errors)
+        }
+        Rvalue::Binary(_, left, right) => {
+          check_operand(left, &statement.span, &mut errors);
+          check_operand(right, &statement.span, &mut errors);
+        }
+        Rvalue::Struct(_, fields) => fields
+          .iter()
+          .for_each(|(_, operand)| check_operand(operand, &statement.span, &mut errors)),
+        Rvalue::Variant(_, _, fields) | Rvalue::Array(_, fields) => fields
+          .iter()
+          .for_each(|operand| check_operand(operand, &statement.span, &mut errors)),
+        Rvalue::Discriminant(_) | Rvalue::VariantField(..) | Rvalue::AddressOf(_) => {}
+        Rvalue::ToDyn(..) => errors.push((
+          format!("trait objects cannot be created in {}", context),
+          statement.span.clone(),
+        )),
+      }
+    }
+    let terminator = block.terminator();
+    match &terminator.kind {
+      TerminatorKind::Call { func, args, .. } => {
+        args.iter().for_each(|arg| check_operand(arg, &terminator.span, &mut errors));
+        let func = match func {
+          Operand::Constant(constant) => &constant.kind,
+          // Calls of the function values are checked during the evaluation.
+          Operand::Copy(_) => continue,
+        };
+        if let Err(message) = callee(func, bodies, context) {
+          errors.push((message, terminator.span.clone()));
+        }
+      }
+      TerminatorKind::DynCall { .. } => errors.push((
+        format!("cannot call methods of trait objects in {}", context),
+        terminator.span.clone(),
+      )),
+      TerminatorKind::If { condition, .. } => {
+        check_operand(condition, &terminator.span, &mut errors)
+      }
+      TerminatorKind::Goto(_)
+      | TerminatorKind::Yield { .. }
+      | TerminatorKind::Return
+      | TerminatorKind::Unreachable => {}
+    }
+  }
+  diagnostics.set_current_file(body.file.clone());
+  for (message, span) in errors {
+    diagnostics.emit(Diagnostic::error(message, span));
+  }
+}
+
+/// Body of the function called at compile time, which must be a `const func`.
+fn callee<'a>(
+  func: &ConstantKind,
+  bodies: &HashMap<&MonoItem, &'a Body>,
+  context: &str,
+) -> Result<&'a Body, String> {
+  let (owner, name) = match func {
+    ConstantKind::Function(def, _) => (MonoItem::Function(def.clone()), def.to_string()),
+    ConstantKind::Method(path) => (MonoItem::Method(path.clone()), path.name.to_owned()),
+    ConstantKind::BoundMethod { name, .. } => {
+      return Err(format!(
+        "cannot call method `{}` of the trait bound in {}",
+        name, context
+      ))
+    }
+    kind => unreachable!("unexpected callee: {:?}", kind),
+  };
+  match bodies.get(&owner) {
+    Some(body) if body.constancy.is_constant() => Ok(body),
+    _ => Err(format!(
+      "cannot call non-const function `{}` in {}",
+      name, context
+    )),
+  }
+}
+
+/// Value during the evaluation. Integers of all types, including `char`, are held in `i128`
+/// within the range of their types.
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+  Unit,
+  Int(i128),
+  Float(f64),
+  Bool(bool),
+  Str(String),
+  /// Struct value, with the fields in the order of declaration.
+  Struct(Vec<Option<Value>>),
+  /// Enum value, with the index of the variant and its fields.
+  Variant(usize, Vec<Value>),
+  /// Array value, with its elements in order.
+  Array(Vec<Option<Value>>),
+  Function(ConstantKind),
+}
+
+impl Value {
+  /// Convert the value of the primitive type into the constant.
+  fn into_constant(self, ty: Type) -> Constant {
+    let kind = match self {
+      Value::Unit => ConstantKind::Unit,
+      // Unsigned integers beyond the range of `int64` keep their bits.
+      Value::Int(value) => ConstantKind::Integer(value as i64),
+      Value::Float(value) => ConstantKind::Float(value),
+      Value::Bool(value) => ConstantKind::Bool(value),
+      Value::Str(value) => ConstantKind::Str(value),
+      value => unreachable!("constants of primitive types could not be {:?}", value),
+    };
+    Constant { kind, ty }
+  }
+}
+
+/// Failure of the evaluation.
+enum EvalError {
+  /// Error to be reported at the span of the body.
+  Error {
+    message: String,
+    span:    Span,
+    file:    Option<String>,
+  },
+  /// Error already reported, by the checks ahead or by the evaluation of another item.
+  Reported,
+}
+
+type EvalResult<T> = Result<T, EvalError>;
+
+struct Evaluator<'a, 'd> {
+  bodies:      HashMap<&'a MonoItem, &'a Body>,
+  diagnostics: &'d mut DiagnosticEngine,
+  /// Values of the evaluated items, which are `None` if the evaluation failed.
+  values:      HashMap<MonoItem, Option<Value>>,
+  /// Items being evaluated, to detect the cycles.
+  stack:       Vec<MonoItem>,
+  /// Statements and terminators executed for the item being evaluated.
+  steps:       usize,
+  /// Items of the program executed by [`execute`], where the callees are resolved at runtime.
+  /// `None` while evaluating the constants.
+  runtime:     Option<&'a ItemTable>,
+}
+
+/// Local variables of a call being evaluated, which are `None` while uninitialized.
+struct Frame<'a> {
+  body:         &'a Body,
+  locals:       Vec<Option<Value>>,
+  /// Type arguments of the generic parameters of the body executed at runtime.
+  substitution: HashMap<String, Type>,
+}
+
+impl<'a, 'd> Evaluator<'a, 'd> {
+  /// Evaluate the constant or the static item, reporting the error if it fails.
+  fn evaluate(&mut self, item: &MonoItem) -> Option<Value> {
+    if let Some(value) = self.values.get(item) {
+      return value.clone();
+    }
+    let body = *self.bodies.get(item)?;
+    if self.stack.contains(item) {
+      self.diagnostics.set_current_file(body.file.clone());
+      self.diagnostics.emit(Diagnostic::error(
+        format!("cycle detected when evaluating {}", describe(item)),
+        body.span.clone(),
+      ));
+      return None;
+    }
+
+    self.stack.push(item.clone());
+    let steps = std::mem::take(&mut self.steps);
+    let result = self.call(body, HashMap::new(), vec![], 0);
+    self.steps = steps;
+    self.stack.pop();
+    let value = self.report(item, result);
+    self.values.insert(item.clone(), value.clone());
+    value
+  }
+
+  /// Report the error of the evaluation of the item if any.
+  fn report(&mut self, item: &MonoItem, result: EvalResult<Value>) -> Option<Value> {
+    match result {
+      Ok(value) => Some(value),
+      Err(EvalError::Error {
+        message,
+        span,
+        file,
+      }) => {
+        self.diagnostics.set_current_file(file);
+        self.diagnostics.emit(
+          Diagnostic::error(message, span)
+            .with_note(format!("while evaluating {}", describe(item))),
+        );
+        None
+      }
+      Err(EvalError::Reported) => None,
+    }
+  }
+
+  /// Evaluate the call of the body with the arguments, returning the returned value.
+  fn call(
+    &mut self,
+    body: &'a Body,
+    substitution: HashMap<String, Type>,
+    args: Vec<Value>,
+    depth: usize,
+  ) -> EvalResult<Value> {
+    let mut frame = Frame {
+      body,
+      locals: vec![None; body.locals.len()],
+      substitution,
+    };
+    for (arg, value) in body.args().zip(args) {
+      frame.locals[arg.0] = Some(value);
+    }
+    let mut block = BasicBlock(0);
+    loop {
+      let data = body.block(block);
+      for statement in &data.statements {
+        self.step(body, &statement.span)?;
+        match &statement.kind {
+          StatementKind::Assign(place, rvalue) => {
+            let value = self.eval_rvalue(&frame, rvalue, &statement.span)?;
+            frame.assign(place, value, &statement.span)?;
+          }
+          StatementKind::StorageLive(local) => frame.locals[local.0] = None,
+        }
+      }
+
+      let terminator = data.terminator();
+      let span = &terminator.span;
+      self.step(body, span)?;
+      block = match &terminator.kind {
+        TerminatorKind::Goto(target) => *target,
+        TerminatorKind::If {
+          condition,
+          then,
+          otherwise,
+        } => match self.eval_operand(&frame, condition, span)? {
+          Value::Bool(true) => *then,
+          _ => *otherwise,
+        },
+        // The return value is always initialized, otherwise it is reported by the checks.
+        TerminatorKind::Return => return frame.locals[0].take().ok_or(EvalError::Reported),
+        TerminatorKind::Call {
+          func,
+          args,
+          destination,
+          target,
+        } => {
+          let kind = match self.eval_operand(&frame, func, span)? {
+            Value::Function(kind) => kind,
+            value => unreachable!("calling non-function value {:?}", value),
+          };
+          let (callee, substitution) = match self.runtime {
+            Some(items) => match self.resolve(items, &kind, &frame.substitution) {
+              Ok(callee) => callee,
+              Err(message) => return Err(self.error(body, message, span)),
+            },
+            None => match callee(&kind, &self.bodies, const_context(&self.stack[0])) {
+              Ok(callee) => (callee, HashMap::new()),
+              // Direct calls are checked ahead.
+              Err(_) if matches!(func, Operand::Constant(_)) => return Err(EvalError::Reported),
+              Err(message) => return Err(self.error(body, message, span)),
+            },
+          };
+          if depth >= CALL_DEPTH_LIMIT {
+            let message = format!("reached the limit of {} nested calls", CALL_DEPTH_LIMIT);
+            return Err(self.error(body, message, span));
+          }
+          let args = args
+            .iter()
+            .map(|arg| self.eval_operand(&frame, arg, span))
+            .collect::<EvalResult<Vec<_>>>()?;
+          let value = self.call(callee, substitution, args, depth + 1)?;
+          frame.assign(destination, value, span)?;
+          *target
+        }
+        TerminatorKind::DynCall { .. } if self.runtime.is_some() => {
+          return Err(self.error(body, "trait objects are not supported yet", span))
+        }
+        TerminatorKind::DynCall { .. } => return Err(EvalError::Reported),
+        TerminatorKind::Yield { .. } => unreachable!("`yield` is removed from `async func`"),
+        TerminatorKind::Unreachable => {
+          return Err(self.error(body, "entered unreachable code", span))
+        }
+      };
+    }
+  }
+
+  /// Body of the function called at runtime, with the substitution of its generic parameters. The
+  /// methods of the trait bounds are resolved by the type arguments of the caller.
+  fn resolve(
+    &self,
+    items: &ItemTable,
+    func: &ConstantKind,
+    substitution: &HashMap<String, Type>,
+  ) -> Result<(&'a Body, HashMap<String, Type>), String> {
+    let instance = match func {
+      ConstantKind::Function(def, args) => Instance {
+        item: MonoItem::Function(def.clone()),
+        args: args.iter().map(|arg| substitute(arg, substitution)).collect(),
+      },
+      ConstantKind::Method(path) => Instance {
+        item: MonoItem::Method(path.clone()),
+        args: vec![],
+      },
+      ConstantKind::BoundMethod {
+        trait_,
+        name,
+        self_ty,
+      } => match substitute(self_ty, substitution) {
+        Type::Coroutine(path, args) => Instance {
+          item: MonoItem::Coroutine(DefPath::new(
+            path.parent().unwrap_or_default(),
+            path.name().unwrap_or_default(),
+          )),
+          args,
+        },
+        self_ty => {
+          let implementation = items.impls.iter().find(|i| {
+            i.trait_.as_ref() == Some(trait_) && i.self_ty.to_type() == Some(self_ty.clone())
+          });
+          match implementation {
+            Some(implementation) => Instance {
+              item: MonoItem::Method(MethodPath {
+                impl_id: implementation.id,
+                name:    name.to_owned(),
+              }),
+              args: vec![],
+            },
+            None => return Err(format!("`{}` does not implement `{}`", self_ty, trait_)),
+          }
+        }
+      },
+      kind => unreachable!("unexpected callee: {:?}", kind),
+    };
+    match self.bodies.get(&instance.item) {
+      Some(body) => Ok((body, instance.substitution(items))),
+      None => Err(format!(
+        "cannot call {} without a body",
+        describe(&instance.item)
+      )),
+    }
+  }
+
+  fn step(&mut self, body: &Body, span: &Span) -> EvalResult<()> {
+    self.steps += 1;
+    if self.steps > STEP_LIMIT {
+      let message = format!("exceeded the limit of {} steps", STEP_LIMIT);
+      return Err(self.error(body, message, span));
+    }
+    Ok(())
+  }
+
+  fn error(&self, body: &Body, message: impl Into<String>, span: &Span) -> EvalError {
+    EvalError::Error {
+      message: message.into(),
+      span:    span.clone(),
+      file:    body.file.clone(),
+    }
+  }
+
+  fn eval_operand(&mut self, frame: &Frame, operand: &Operand, span: &Span) -> EvalResult<Value> {
+    let constant = match operand {
+      Operand::Copy(place) => return frame.read(place, span),
+      Operand::Constant(constant) => constant,
+    };
+    Ok(match &constant.kind {
+      ConstantKind::Unit => Value::Unit,
+      ConstantKind::Integer(value) => Value::Int(primitive(&constant.ty).wrap(*value as i128)),
+      ConstantKind::Float(value) => Value::Float(*value),
+      ConstantKind::Bool(value) => Value::Bool(*value),
+      ConstantKind::Str(value) => Value::Str(value.to_owned()),
+      ConstantKind::Const(def) => {
+        self.evaluate(&MonoItem::Const(def.clone())).ok_or(EvalError::Reported)?
+      }
+      ConstantKind::Static(def) => {
+        self.evaluate(&MonoItem::Static(def.clone())).ok_or(EvalError::Reported)?
+      }
+      kind => Value::Function(kind.clone()),
+    })
+  }
+
+  fn eval_rvalue(&mut self, frame: &Frame, rvalue: &Rvalue, span: &Span) -> EvalResult<Value> {
+    let body = frame.body;
+    match rvalue {
+      Rvalue::Use(operand) => self.eval_operand(frame, operand, span),
+      Rvalue::Unary(op, operand) => {
+        let value = self.eval_operand(frame, operand, span)?;
+        match (op, value) {
+          (UnaryOp::Not, Value::Bool(value)) => Ok(Value::Bool(!value)),
+          (UnaryOp::Negative, Value::Float(value)) => Ok(Value::Float(-value)),
+          (UnaryOp::Negative, Value::Int(value)) => primitive(operand.ty(body))
+            .check_range(-value, "negate")
+            .map(Value::Int)
+            .map_err(|message| self.error(body, message, span)),
+          (op, value) => unreachable!("invalid operand of `{}`: {:?}", op.as_str(), value),
+        }
+      }
+      Rvalue::Binary(op, left, right) => {
+        let ty = left.ty(body);
+        let left = self.eval_operand(frame, left, span)?;
+        let right = self.eval_operand(frame, right, span)?;
+        eval_binary(op, left, right, ty).map_err(|message| self.error(body, message, span))
+      }
+      Rvalue::AddressOf(_) | Rvalue::Cast(_, Type::Pointer(_)) if self.runtime.is_some() => {
+        Err(self.error(body, "raw pointers are not supported yet", span))
+      }
+      Rvalue::AddressOf(_) | Rvalue::Cast(_, Type::Pointer(_)) => Err(EvalError::Reported),
+      Rvalue::Cast(operand, target) => {
+        let value = self.eval_operand(frame, operand, span)?;
+        Ok(cast(value, target))
+      }
+      Rvalue::Struct(_, fields) => {
+        let mut values = vec![None; fields.len()];
+        for (index, operand) in fields {
+          values[*index] = Some(self.eval_operand(frame, operand, span)?);
+        }
+        Ok(Value::Struct(values))
+      }
+      Rvalue::Variant(_, index, fields) => {
+        let values = fields
+          .iter()
+          .map(|operand| self.eval_operand(frame, operand, span))
+          .collect::<EvalResult<_>>()?;
+        Ok(Value::Variant(*index, values))
+      }
+      Rvalue::Array(_, elements) => {
+        let values = elements
+          .iter()
+          .map(|operand| self.eval_operand(frame, operand, span).map(Some))
+          .collect::<EvalResult<_>>()?;
+        Ok(Value::Array(values))
+      }
+      Rvalue::Discriminant(place) => match frame.read(place, span)? {
+        Value::Variant(index, _) => Ok(Value::Int(index as i128)),
+        value => unreachable!("discriminant of non-enum value: {:?}", value),
+      },
+      Rvalue::VariantField(place, index, field) => match frame.read(place, span)? {
+        Value::Variant(variant, mut fields) if variant == *index => Ok(fields.swap_remove(*field)),
+        value => unreachable!("field of another variant read from {:?}", value),
+      },
+      Rvalue::ToDyn(..) if self.runtime.is_some() => {
+        Err(self.error(body, "trait objects are not supported yet", span))
+      }
+      Rvalue::ToDyn(..) => Err(EvalError::Reported),
+    }
+  }
+}
+
+impl<'a> Frame<'a> {
+  /// Read the value of the place. Uses of uninitialized variables are reported by the checks.
+  fn read(&self, place: &Place, span: &Span) -> EvalResult<Value> {
+    let mut value = self.locals[place.local.0].as_ref();
+    for (elem, _) in &place.projection {
+      value = match (elem, value) {
+        (ProjectionElem::Field(index), Some(Value::Struct(fields))) => fields[*index].as_ref(),
+        (ProjectionElem::Index(index), Some(Value::Array(elements))) => {
+          elements[self.index(*index, elements.len(), span)?].as_ref()
+        }
+        _ => None,
+      };
+    }
+    value.cloned().ok_or(EvalError::Reported)
+  }
+
+  fn assign(&mut self, place: &Place, value: Value, span: &Span) -> EvalResult<()> {
+    // Indices are read ahead, since the locals are borrowed while projecting the place.
+    let indices = place
+      .projection
+      .iter()
+      .map(|(elem, _)| match elem {
+        ProjectionElem::Index(index) => self.locals[index.0].clone(),
+        _ => None,
+      })
+      .collect::<Vec<_>>();
+    let body = self.body;
+    let mut target = &mut self.locals[place.local.0];
+    for ((elem, _), index) in place.projection.iter().zip(indices) {
+      target = match (elem, target, index) {
+        (ProjectionElem::Field(index), Some(Value::Struct(fields)), _) => &mut fields[*index],
+        (ProjectionElem::Index(_), Some(Value::Array(elements)), Some(Value::Int(index))) => {
+          let index = check_index(body, index, elements.len(), span)?;
+          &mut elements[index]
+        }
+        _ => return Err(EvalError::Reported),
+      };
+    }
+    *target = Some(value);
+    Ok(())
+  }
+
+  /// Index in the local, checked against the length of the array.
+  fn index(&self, index: Local, length: usize, span: &Span) -> EvalResult<usize> {
+    match &self.locals[index.0] {
+      Some(Value::Int(index)) => check_index(self.body, *index, length, span),
+      _ => Err(EvalError::Reported),
+    }
+  }
+}
+
+/// Index into the array of the length, or the error if it is out of bounds.
+fn check_index(body: &Body, index: i128, length: usize, span: &Span) -> EvalResult<usize> {
+  if index < 0 || index >= length as i128 {
+    return Err(EvalError::Error {
+      message: format!(
+        "index out of bounds: the length is {} but the index is {}",
+        length, index
+      ),
+      span:    span.clone(),
+      file:    body.file.clone(),
+    });
+  }
+  Ok(index as usize)
+}
+
+fn describe(item: &MonoItem) -> String {
+  match item {
+    MonoItem::Const(def) => format!("constant `{}`", def),
+    MonoItem::Static(def) => format!("static `{}`", def),
+    MonoItem::Function(def) => format!("function `{}`", def),
+    MonoItem::Coroutine(def) => format!("`poll` of `async func` `{}`", def),
+    MonoItem::Method(path) => format!("method `{}`", path.name),
+  }
+}
+
+/// Primitive type of the type, into whose range the integers of the type are checked and wrapped.
+/// Other types have no range, as `unit` does.
+fn primitive(ty: &Type) -> &PrimitiveType {
+  match ty {
+    Type::Primitive(primitive) => primitive,
+    _ => &PrimitiveType::Unit,
+  }
+}
+
+/// Binary operation on the operands of the type, returning the message of the error if any.
+fn eval_binary(op: &BinaryOp, left: Value, right: Value, ty: &Type) -> Result<Value, String> {
+  let value = match (left, right) {
+    (Value::Int(left), Value::Int(right)) => {
+      let ty = primitive(ty);
+      let (verb, result) = match op {
+        BinaryOp::Add => ("add", left.checked_add(right)),
+        BinaryOp::Subtract => ("subtract", left.checked_sub(right)),
+        BinaryOp::Multiply => ("multiply", left.checked_mul(right)),
+        BinaryOp::Division if right == 0 => return Err("attempt to divide by zero".to_string()),
+        BinaryOp::Division => ("divide", left.checked_div(right)),
+        BinaryOp::Remainder if right == 0 => {
+          return Err("attempt to calculate the remainder with a divisor of zero".to_string())
+        }
+        // `MIN % -1` overflows as `MIN / -1` does, which traps at runtime.
+        BinaryOp::Remainder => {
+          let overflow = ty.check_range(left.checked_div(right).unwrap_or(i128::MAX), "");
+          let result = left.checked_rem(right).filter(|_| overflow.is_ok());
+          ("calculate the remainder", result)
+        }
+        op => return Ok(Value::Bool(op.compare(left.cmp(&right)))),
+      };
+      return ty.check_range(result.unwrap_or(i128::MAX), verb).map(Value::Int);
+    }
+    (Value::Float(left), Value::Float(right)) => match op {
+      BinaryOp::Add => Value::Float(left + right),
+      BinaryOp::Subtract => Value::Float(left - right),
+      BinaryOp::Multiply => Value::Float(left * right),
+      BinaryOp::Division => Value::Float(left / right),
+      BinaryOp::Remainder => Value::Float(left % right),
+      BinaryOp::Equal => Value::Bool(left == right),
+      BinaryOp::NotEqual => Value::Bool(left != right),
+      BinaryOp::Less => Value::Bool(left < right),
+      BinaryOp::LessEqual => Value::Bool(left <= right),
+      BinaryOp::Greater => Value::Bool(left > right),
+      BinaryOp::GreaterEqual => Value::Bool(left >= right),
+      op => unreachable!("invalid float operator `{}`", op.as_str()),
+    },
+    (Value::Bool(left), Value::Bool(right)) => Value::Bool(op.compare(left.cmp(&right))),
+    (Value::Str(left), Value::Str(right)) => Value::Bool(op.compare(left.cmp(&right))),
+    (left, right) => unreachable!(
+      "invalid operands of `{}`: {:?} and {:?}",
+      op.as_str(),
+      left,
+      right
+    ),
+  };
+  Ok(value)
+}
+
+/// Conversion between primitive types. Floats are converted into integers with saturation.
+fn cast(value: Value, target: &Type) -> Value {
+  let target = match target {
+    Type::Primitive(primitive) => primitive,
+    _ => return value,
+  };
+  if target.is_float() {
+    return match value {
+      Value::Int(value) => Value::Float(value as f64),
+      value => value,
+    };
+  }
+  match value {
+    Value::Int(value) => Value::Int(target.wrap(value)),
+    Value::Bool(value) => Value::Int(value as i128),
+    Value::Float(value) => Value::Int(target.saturate(value)),
+    value => value,
+  }
+}
+
+/// Replacement of the uses of the evaluated constants by their values.
+struct ConstReplacer<'d> {
+  diagnostics: &'d mut DiagnosticEngine,
+  /// Value of each constant, with the location of its definition.
+  values:      HashMap<DefPath, (Constant, (Span, Option<String>))>,
+  /// Constants already reported as negative sizes of arrays.
+  reported:    HashSet<DefPath>,
+}
+
+impl<'d> ConstReplacer<'d> {
+  fn replace_body(&mut self, body: &mut Body) {
+    for decl in &mut body.locals {
+      decl.ty = self.replace_type(&decl.ty);
+    }
+    for state in body.coroutine.iter_mut().flat_map(|layout| &mut layout.states) {
+      for ty in state {
+        *ty = self.replace_type(ty);
+      }
+    }
+    for block in &mut body.blocks {
+      for statement in &mut block.statements {
+        if let StatementKind::Assign(place, rvalue) = &mut statement.kind {
+          self.replace_place(place);
+          self.replace_rvalue(rvalue);
+        }
+      }
+      let terminator = match &mut block.terminator {
+        Some(terminator) => terminator,
+        None => continue,
+      };
+      match &mut terminator.kind {
+        TerminatorKind::If { condition, .. } => self.replace_operand(condition),
+        TerminatorKind::Call {
+          func,
+          args,
+          destination,
+          ..
+        } => {
+          self.replace_operand(func);
+          args.iter_mut().for_each(|arg| self.replace_operand(arg));
+          self.replace_place(destination);
+        }
+        TerminatorKind::DynCall {
+          object,
+          args,
+          destination,
+          ..
+        } => {
+          self.replace_operand(object);
+          args.iter_mut().for_each(|arg| self.replace_operand(arg));
+          self.replace_place(destination);
+        }
+        TerminatorKind::Goto(_)
+        | TerminatorKind::Yield { .. }
+        | TerminatorKind::Return
+        | TerminatorKind::Unreachable => {}
+      }
+    }
+  }
+
+  fn replace_rvalue(&mut self, rvalue: &mut Rvalue) {
+    match rvalue {
+      Rvalue::Use(operand) | Rvalue::Unary(_, operand) => self.replace_operand(operand),
+      Rvalue::Binary(_, left, right) => {
+        self.replace_operand(left);
+        self.replace_operand(right);
+      }
+      Rvalue::Cast(operand, ty) | Rvalue::ToDyn(operand, ty) => {
+        self.replace_operand(operand);
+        *ty = self.replace_type(ty);
+      }
+      Rvalue::Struct(ty, fields) => {
+        *ty = self.replace_type(ty);
+        fields.iter_mut().for_each(|(_, operand)| self.replace_operand(operand));
+      }
+      Rvalue::Variant(ty, _, fields) | Rvalue::Array(ty, fields) => {
+        *ty = self.replace_type(ty);
+        fields.iter_mut().for_each(|operand| self.replace_operand(operand));
+      }
+      Rvalue::Discriminant(place) | Rvalue::VariantField(place, ..) | Rvalue::AddressOf(place) => {
+        self.replace_place(place)
+      }
+    }
+  }
+
+  fn replace_operand(&mut self, operand: &mut Operand) {
+    match operand {
+      Operand::Copy(place) => self.replace_place(place),
+      Operand::Constant(constant) => {
+        if let ConstantKind::Const(def) = &constant.kind {
+          if let Some((value, _)) = self.values.get(def) {
+            *constant = value.clone();
+          }
+        }
+        constant.ty = self.replace_type(&constant.ty);
+      }
+    }
+  }
+
+  fn replace_place(&mut self, place: &mut Place) {
+    for (_, ty) in &mut place.projection {
+      *ty = self.replace_type(ty);
+    }
+  }
+
+  fn replace_type(&mut self, ty: &Type) -> Type {
+    match ty {
+      Type::ConstArray(element, size) => {
+        let element = self.replace_type(element);
+        match self.array_size(size) {
+          Some(size) => Type::array(element, size),
+          None => Type::ConstArray(Box::new(element), size.clone()),
+        }
+      }
+      Type::Array(element, size) => Type::array(self.replace_type(element), *size),
+      Type::Pointer(pointee) => Type::pointer(self.replace_type(pointee)),
+      Type::Named(path, args) => Type::Named(
+        path.clone(),
+        args.iter().map(|arg| self.replace_type(arg)).collect(),
+      ),
+      Type::Function(function) => Type::Function(FunctionType::new(
+        function.params.iter().map(|param| self.replace_type(param)).collect(),
+        Box::new(self.replace_type(&function.ret)),
+      )),
+      ty => ty.clone(),
+    }
+  }
+
+  /// Size of the array given by the constant, which must not be negative.
+  fn array_size(&mut self, path: &Path) -> Option<usize> {
+    let def = DefPath::new(path.parent().unwrap_or_default(), path.name()?);
+    let (value, (span, file)) = self.values.get(&def)?;
+    let size = match value.kind {
+      ConstantKind::Integer(size) => size,
+      _ => return None,
+    };
+    let unsigned = primitive(&value.ty).is_unsigned_integer();
+    if unsigned || size >= 0 {
+      return Some(size as u64 as usize);
+    }
+    if self.reported.insert(def.clone()) {
+      self.diagnostics.set_current_file(file.clone());
+      self.diagnostics.emit(
+        Diagnostic::error(
+          format!("constant `{}` used as the size of arrays is negative", def),
+          span.clone(),
+        )
+        .with_note(format!("the value of `{}` is `{}`", def, size)),
+      );
+    }
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use vsp_sema::testing::check_source;
+
+  use super::*;
+  use crate::build::build_program;
+  use crate::mir::Local;
+
+  fn evaluate(source: &str) -> (Program, Vec<String>) {
+    let (unit, results) = check_source(source);
+    let mut diagnostics = DiagnosticEngine::default();
+    let program = build_program(
+      &vsp_hir::lower_compilation_unit(&unit, &results),
+      &mut diagnostics,
+    );
+    let messages = diagnostics.diagnostics().iter().map(|d| d.to_string()).collect();
+    (program, messages)
+  }
+
+  /// Execute `main` of the program, returning its value and the messages of the diagnostics.
+  fn run(source: &str) -> (Option<String>, Vec<String>) {
+    let (unit, results) = check_source(source);
+    let mut diagnostics = DiagnosticEngine::default();
+    let program = build_program(
+      &vsp_hir::lower_compilation_unit(&unit, &results),
+      &mut diagnostics,
+    );
+    assert!(!diagnostics.has_errors(), "{:?}", diagnostics.diagnostics());
+    let main = MonoItem::Function(DefPath::new(Path::root(), "main"));
+    let value = execute(&program, results.items(), &main, &mut diagnostics);
+    let messages = diagnostics.diagnostics().iter().map(|d| d.to_string()).collect();
+    (value.map(|value| value.to_string()), messages)
+  }
+
+  fn values(program: &Program) -> Vec<String> {
+    program
+      .consts
+      .iter()
+      .map(|value| match &value.owner {
+        MonoItem::Const(def) | MonoItem::Static(def) => format!("{} = {}", def, value.value),
+        owner => panic!("unexpected owner {:?}", owner),
+      })
+      .collect()
+  }
+
+  #[test]
+  fn test_evaluate() {
+    let (program, messages) = evaluate(
+      "const func factorial(n: uint64): uint64 {
+         var result: uint64 = 1;
+         var i: uint64 = 2;
+         while i <= n { result = result * i; i = i + 1; }
+         return result;
+       }
+       const func fibonacci(n: int32): int32 {
+         if n < 2 { return n; }
+         return fibonacci(n - 1) + fibonacci(n - 2);
+       }
+       const LIMIT: uint64 = factorial(20);
+       const FIB: int32 = fibonacci(FIB_INDEX);
+       const FIB_INDEX: int32 = 10;
+       const RATIO: double = 1.5 * 2.0;
+       const BYTE: uint8 = (300 as uint8) + (-254 as uint8);
+       const ENABLED: bool = LIMIT > 1000 && !(RATIO < 1.0);
+       static NAME: str = \"vsp\";
+       static SAME: bool = NAME == \"vsp\";",
+    );
+    assert_eq!(messages, Vec::<String>::new());
+    assert_eq!(
+      values(&program),
+      vec![
+        "LIMIT = 2432902008176640000_uint64",
+        "FIB = 55_int32",
+        "FIB_INDEX = 10_int32",
+        "RATIO = 3.0_double",
+        "BYTE = 46_uint8",
+        "ENABLED = true",
+        "NAME = \"vsp\"",
+        "SAME = true",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_replace() {
+    let (program, messages) = evaluate(
+      "const func square(x: int64): int64 { return x * x; }
+       const SIZE: int64 = square(3) - 1;
+       func first(values: int64[SIZE][2]): int64 { return SIZE; }",
+    );
+    assert_eq!(messages, Vec::<String>::new());
+    let first = program.body(&MonoItem::Function(DefPath::new(Path::root(), "first"))).unwrap();
+    assert_eq!(
+      first.local(Local(1)).ty,
+      Type::array(Type::array(Type::int64(), 8), 2)
+    );
+    match &first.blocks[0].statements[0].kind {
+      StatementKind::Assign(place, rvalue) => {
+        assert_eq!(format!("{} = {}", place, rvalue), "_0 = const 8_int64")
+      }
+      kind => panic!("unexpected statement {:?}", kind),
+    }
+  }
+
+  #[test]
+  fn test_overflow() {
+    let (_, messages) = evaluate(
+      "const func add(a: int8, b: int8): int8 { return a + b; }
+       const SUM: int8 = add(100, 28);
+       const PRODUCT: uint64 = 4294967296 * 4294967296;
+       const NEGATED: int16 = -(-32768 as int16);
+       const QUOTIENT: int32 = 1 / (QUOTIENT_DIVISOR - 1);
+       const QUOTIENT_DIVISOR: int32 = 1;
+       const REMAINDER: int64 = (-9223372036854775807 - 1) % -1;
+       const SIZE: int64 = -1;
+       func main(values: int32[SIZE]) {}",
+    );
+    assert_eq!(
+      messages,
+      vec![
+        "1:49: error: attempt to add with overflow",
+        "3:32: error: attempt to multiply with overflow",
+        "4:31: error: attempt to negate with overflow",
+        "5:32: error: attempt to divide by zero",
+        "7:33: error: attempt to calculate the remainder with overflow",
+        "8:8: error: constant `SIZE` used as the size of arrays is negative",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_index() {
+    let (program, messages) = evaluate(
+      "const func sum(n: int64): int64 {
+         var values = [1, 2, 3];
+         values[n] = 10;
+         return values[0] + values[1] + values[2];
+       }
+       const SUM: int64 = sum(1);
+       const OUT: int64 = sum(3);",
+    );
+    assert_eq!(
+      messages,
+      vec!["3:10: error: index out of bounds: the length is 3 but the index is 3"]
+    );
+    assert_eq!(values(&program), vec!["SUM = 14_int64"]);
+  }
+
+  #[test]
+  fn test_discriminants() {
+    let (program, messages) = evaluate(
+      "const BASE: int64 = 10;
+       const func twice(x: int64): int64 { return x * 2; }
+       enum Color { Red = BASE, Green, Blue = twice(BASE) }
+       enum Ordering { Less = -1, Equal, Greater }
+       enum Plain { First, Second }",
+    );
+    assert_eq!(messages, Vec::<String>::new());
+    let mut values = values(&program);
+    values.sort();
+    assert_eq!(
+      values,
+      vec![
+        "BASE = 10_int64",
+        "Color::Blue = 20_int64",
+        "Color::Green = 11_int64",
+        "Color::Red = 10_int64",
+        "Ordering::Equal = 0_int64",
+        "Ordering::Greater = 1_int64",
+        "Ordering::Less = -1_int64",
+      ]
+    );
+
+    let (_, messages) = evaluate(
+      "func runtime(): int64 { return 1; }
+       enum Big { Last = 9223372036854775807, Next }
+       enum Dynamic { Value = runtime() }",
+    );
+    assert_eq!(
+      messages,
+      vec![
+        "3:31: error: cannot call non-const function `runtime` in constants",
+        "2:47: error: attempt to add with overflow",
+      ]
+    );
+
+    let (value, messages) = run(
+      "enum Color { Red = 3, Green, Blue = 8 }
+       enum Plain { First, Second }
+       func pick(first: bool): Color {
+         if first { return Color::Red; }
+         return Color::Blue;
+       }
+       func main(): int32 {
+         let total = (pick(true) as int64) + (pick(false) as int64) + (Color::Green as int64);
+         return total as int32 * 10 + (Plain::Second as int32);
+       }",
+    );
+    assert_eq!(messages, Vec::<String>::new());
+    assert_eq!(value.as_deref(), Some("151_int32"));
+  }
+
+  #[test]
+  fn test_non_constant() {
+    let (_, messages) = evaluate(
+      "func runtime(): int64 { return 1; }
+       const func indirect(): int64 { let f = runtime; return f(); }
+       const func cycle(): int64 { return A; }
+       static COUNT: int64 = 1;
+       const CALL: int64 = runtime();
+       const INDIRECT: int64 = indirect();
+       const READ: int64 = COUNT;
+       static READ_STATIC: int64 = COUNT + 1;
+       const A: int64 = B + 1;
+       const B: int64 = cycle();
+       const func forever(): int64 { while true {} return 0; }
+       const LOOP: int64 = forever();
+       const func address(): int64 { var x = 1; let p = &x; return p as int64; }",
+    );
+    assert_eq!(
+      messages,
+      vec![
+        "5:28: error: cannot call non-const function `runtime` in constants",
+        "7:28: error: constants cannot refer to statics",
+        "13:57: error: raw pointers cannot be used in constant functions",
+        "13:68: error: raw pointers cannot be used in constant functions",
+        "2:63: error: cannot call non-const function `runtime` in constants",
+        "9:8: error: cycle detected when evaluating constant `A`",
+        "11:38: error: exceeded the limit of 1000000 steps",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_execute() {
+    let (value, messages) = run(
+      "@Lang(Poll) enum Poll<F, T> { Ready(T), Pending(F) }
+       @Lang(Future) interface Future {
+         type Output;
+         func poll(): Poll<Self, Self::Output>;
+       }
+       @Lang(BlockOn) func block_on<F: Future>(future: F): F::Output;
+       struct Countdown { left: int64 }
+       impl Future for Countdown {
+         type Output = int64;
+         func poll(): Poll<Countdown, int64> {
+           if self.left == 0 { return Poll::Ready(40); }
+           return Poll::Pending(Countdown { left: self.left - 1 });
+         }
+       }
+       async func double(x: int64): int64 { return x * 2; }
+       async func wait<T: Future>(future: T): T::Output { return future.await; }
+       async func add(a: int64): int64 {
+         var total = a + Countdown { left: 3 }.await;
+         total = total + wait(Countdown { left: 2 }).await;
+         return total + double(wait(double(1)).await).await;
+       }
+       func main(): int64 {
+         let sum = block_on(add(1));
+         return sum;
+       }",
+    );
+    assert_eq!(messages, Vec::<String>::new());
+    assert_eq!(value.as_deref(), Some("85_int64"));
+  }
+}
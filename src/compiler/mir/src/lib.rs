@@ -0,0 +1,28 @@
+//! # Mid-level IR
+//!
+//! MIR is a control flow graph of basic blocks built from the HIR of each function, which is used
+//! by the flow-sensitive analyses and consumed by the code generation. The analyses report the
+//! uninitialized variables, the unreachable statements and the missing return values, and the
+//! constants and the static items are evaluated on it at compile time.
+//!
+//! ```rust
+//! use vsp_ast_parser::lex::DefaultLexer;
+//! use vsp_ast_parser::parser::ASTParser;
+//! use vsp_ast_parser::parser::TraditionalParser;
+//! use vsp_diag::DiagnosticEngine;
+//!
+//! let tokens = DefaultLexer {}.tokenize("func main(): int64 { return 0; }").unwrap();
+//! let unit = TraditionalParser {}.parse(tokens).unwrap();
+//! let mut diagnostics = DiagnosticEngine::default();
+//! let results = vsp_sema::check_compilation_unit(&unit, &mut diagnostics);
+//! let program = vsp_hir::lower_compilation_unit(&unit, &results);
+//! let program = vsp_mir::build::build_program(&program, &mut diagnostics);
+//! assert!(!diagnostics.has_errors());
+//! assert_eq!(program.bodies[0].blocks.len(), 1);
+//! ```
+pub mod build;
+pub mod check;
+pub mod coroutine;
+pub mod eval;
+pub mod mir;
+pub mod pretty;
@@ -0,0 +1,404 @@
+//! Definition of the MIR.
+//!
+//! Each body is a control flow graph of basic blocks. A basic block is a sequence of statements
+//! which assign the values of rvalues to places, ended by a terminator which transfers the control
+//! to other blocks, calls a function, or returns. Operands of the rvalues are either constants or
+//! places, so that intermediate values are stored in explicit temporaries.
+use vsp_ast::ast::expr::BinaryOp;
+use vsp_ast::ast::expr::UnaryOp;
+use vsp_ast::ast::modifier::Constancy;
+use vsp_ast::ast::module::Path;
+use vsp_ast::ast::types::Type;
+use vsp_hir::hir::EnumDef;
+use vsp_hir::hir::ForeignFunction;
+use vsp_hir::hir::StructDef;
+use vsp_sema::items::MethodPath;
+use vsp_sema::mono::MonoItem;
+use vsp_sema::resolve::DefPath;
+use vsp_span::Span;
+
+/// Bodies of all functions, methods, constants and static items in the package, with the values of
+/// the constants and the static items once evaluated. Declarations of the foreign functions and
+/// the definitions of the structs and the enums are kept from the HIR.
+#[derive(Debug, Default)]
+pub struct Program {
+  pub bodies:  Vec<Body>,
+  pub consts:  Vec<ConstValue>,
+  pub foreign: Vec<ForeignFunction>,
+  pub structs: Vec<StructDef>,
+  pub enums:   Vec<EnumDef>,
+}
+
+impl Program {
+  pub fn body(&self, owner: &MonoItem) -> Option<&Body> {
+    self.bodies.iter().find(|body| &body.owner == owner)
+  }
+
+  pub fn foreign_function(&self, def: &DefPath) -> Option<&ForeignFunction> {
+    self.foreign.iter().find(|function| &function.def == def)
+  }
+
+  /// Definition of the struct at the path, if the type is a struct.
+  pub fn struct_def(&self, path: &Path) -> Option<&StructDef> {
+    self.structs.iter().find(|definition| &definition.def.path() == path)
+  }
+
+  /// Definition of the enum at the path, if the type is an enum.
+  pub fn enum_def(&self, path: &Path) -> Option<&EnumDef> {
+    self.enums.iter().find(|definition| &definition.def.path() == path)
+  }
+}
+
+#[derive(Debug)]
+pub struct Body {
+  pub owner:     MonoItem,
+  /// Source file which the function is located in.
+  pub file:      Option<String>,
+  /// Local variables. The first is the return place `_0`, followed by the arguments, the user
+  /// variables and the temporaries.
+  pub locals:    Vec<LocalDecl>,
+  pub arg_count: usize,
+  /// Lexical scopes, where the first one is the outermost block of the body.
+  pub scopes:    Vec<SourceScopeData>,
+  /// Basic blocks, where the first one `bb0` is the entry.
+  pub blocks:    Vec<BasicBlockData>,
+  pub span:      Span,
+  /// Whether the body could be evaluated at compile time, i.e. `const func` or the initializer.
+  pub constancy: Constancy,
+  /// Layout of the state machine which `poll` of `async func` takes as `_1`.
+  pub coroutine: Option<CoroutineLayout>,
+}
+
+/// State machine of `async func`, an enum with a variant for each state. The state 0 holds the
+/// arguments, and the state `k + 1` holds the locals live across the `k`-th suspension point.
+#[derive(Debug)]
+pub struct CoroutineLayout {
+  pub ty:     Type,
+  /// Types of the locals saved in each state.
+  pub states: Vec<Vec<Type>>,
+}
+
+/// Value of the constant or the static item, evaluated at compile time.
+#[derive(Debug)]
+pub struct ConstValue {
+  pub owner: MonoItem,
+  pub value: Constant,
+}
+
+impl Body {
+  pub const RETURN_PLACE: Local = Local(0);
+
+  pub fn local(&self, local: Local) -> &LocalDecl {
+    &self.locals[local.0]
+  }
+
+  pub fn block(&self, block: BasicBlock) -> &BasicBlockData {
+    &self.blocks[block.0]
+  }
+
+  pub fn args(&self) -> impl Iterator<Item = Local> {
+    (1..=self.arg_count).map(Local)
+  }
+
+  pub fn return_type(&self) -> &Type {
+    &self.locals[0].ty
+  }
+
+  /// Innermost scope whose block contains the start of the span.
+  pub fn innermost_scope(&self, span: &Span) -> SourceScope {
+    let position = span.start;
+    let index = self
+      .scopes
+      .iter()
+      .rposition(|scope| scope.span.start <= position && position <= scope.span.end);
+    index.map_or(SourceScope::OUTERMOST, SourceScope)
+  }
+
+  /// Whether each block is reachable from the entry.
+  pub fn reachable_blocks(&self) -> Vec<bool> {
+    let mut reachable = vec![false; self.blocks.len()];
+    let mut stack = vec![BasicBlock(0)];
+    while let Some(block) = stack.pop() {
+      if !std::mem::replace(&mut reachable[block.0], true) {
+        stack.extend(self.block(block).terminator().successors());
+      }
+    }
+    reachable
+  }
+
+  /// Blocks which could transfer the control to each block.
+  pub fn predecessors(&self) -> Vec<Vec<BasicBlock>> {
+    let mut predecessors = vec![vec![]; self.blocks.len()];
+    for (index, block) in self.blocks.iter().enumerate() {
+      for successor in block.terminator().successors() {
+        predecessors[successor.0].push(BasicBlock(index));
+      }
+    }
+    predecessors
+  }
+}
+
+/// Index of the lexical scope in the body.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SourceScope(pub usize);
+
+impl SourceScope {
+  pub const OUTERMOST: SourceScope = SourceScope(0);
+}
+
+/// Block of statements in the source, which the user variables declared in it belong to. Scopes
+/// are in the order of their blocks in the source, so the nested scopes come after their parents.
+#[derive(Clone, Debug)]
+pub struct SourceScopeData {
+  /// Enclosing scope, which is `None` only for the outermost one.
+  pub parent: Option<SourceScope>,
+  pub span:   Span,
+}
+
+/// Index of the local variable in the body, printed as `_N`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Local(pub usize);
+
+#[derive(Clone, Debug)]
+pub struct LocalDecl {
+  /// Name of the user variable or the argument, which is `None` for the return place and the
+  /// temporaries.
+  pub name:    Option<String>,
+  pub ty:      Type,
+  pub mutable: bool,
+  pub span:    Span,
+  /// Scope of the user variable, which is the outermost one for the others.
+  pub scope:   SourceScope,
+}
+
+/// Index of the basic block in the body, printed as `bbN`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct BasicBlock(pub usize);
+
+#[derive(Debug, Default)]
+pub struct BasicBlockData {
+  pub statements: Vec<Statement>,
+  /// Terminator of the block, which is only missing while the body is being built.
+  pub terminator: Option<Terminator>,
+}
+
+impl BasicBlockData {
+  pub fn terminator(&self) -> &Terminator {
+    self.terminator.as_ref().expect("terminator of the block is missing")
+  }
+}
+
+#[derive(Debug)]
+pub struct Statement {
+  pub kind: StatementKind,
+  pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum StatementKind {
+  Assign(Place, Box<Rvalue>),
+  /// Start of the scope of the user variable, which is uninitialized until assigned. It is placed
+  /// at the declaration, so that the variables declared in loops are fresh in each iteration.
+  StorageLive(Local),
+}
+
+/// Local variable with the projections of the fields, the elements of arrays and the dereferences
+/// of raw pointers, such as `_1.0.2`, `_1[_2]` or `(*_1).0`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Place {
+  pub local:      Local,
+  /// Each projection with the type of the projected place.
+  pub projection: Vec<(ProjectionElem, Type)>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ProjectionElem {
+  /// Field of the struct by its index in the declaration.
+  Field(usize),
+  /// Value which the raw pointer points to.
+  Deref,
+  /// Element of the array by the `int64` index in the local, which is checked against the length
+  /// of the array when the place is used.
+  Index(Local),
+}
+
+impl Place {
+  pub fn field(mut self, index: usize, ty: Type) -> Self {
+    self.projection.push((ProjectionElem::Field(index), ty));
+    self
+  }
+
+  pub fn deref(mut self, ty: Type) -> Self {
+    self.projection.push((ProjectionElem::Deref, ty));
+    self
+  }
+
+  pub fn index(mut self, index: Local, ty: Type) -> Self {
+    self.projection.push((ProjectionElem::Index(index), ty));
+    self
+  }
+
+  /// Locals of the indices in the projections, which are read whenever the place is used.
+  pub fn indices(&self) -> impl Iterator<Item = Local> + '_ {
+    self.projection.iter().filter_map(|(elem, _)| match elem {
+      ProjectionElem::Index(index) => Some(*index),
+      _ => None,
+    })
+  }
+
+  /// True if the place is in the memory which a raw pointer points to, rather than in the local.
+  pub fn is_indirect(&self) -> bool {
+    self.projection.iter().any(|(elem, _)| *elem == ProjectionElem::Deref)
+  }
+
+  pub fn ty<'a>(&'a self, body: &'a Body) -> &'a Type {
+    match self.projection.last() {
+      Some((_, ty)) => ty,
+      None => &body.local(self.local).ty,
+    }
+  }
+}
+
+impl From<Local> for Place {
+  fn from(local: Local) -> Self {
+    Self {
+      local,
+      projection: vec![],
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operand {
+  /// Value read from the place.
+  Copy(Place),
+  Constant(Constant),
+}
+
+impl Operand {
+  pub fn ty<'a>(&'a self, body: &'a Body) -> &'a Type {
+    match self {
+      Operand::Copy(place) => place.ty(body),
+      Operand::Constant(constant) => &constant.ty,
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Constant {
+  pub kind: ConstantKind,
+  pub ty:   Type,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConstantKind {
+  Unit,
+  Integer(i64),
+  Float(f64),
+  Bool(bool),
+  Str(String),
+  /// Function with its type arguments.
+  Function(DefPath, Vec<Type>),
+  /// Method or associated function of the implementation.
+  Method(MethodPath),
+  /// Value of the constant, which is replaced by the value once evaluated.
+  Const(DefPath),
+  /// Value of the static item, read from its location in memory.
+  Static(DefPath),
+  /// Method of the trait bound of the generic parameter `self_ty`.
+  BoundMethod {
+    trait_:  DefPath,
+    name:    String,
+    self_ty: Type,
+  },
+}
+
+/// Computation of a value, to be assigned to a place.
+#[derive(Debug)]
+pub enum Rvalue {
+  Use(Operand),
+  Unary(UnaryOp, Operand),
+  /// Binary operation other than the assignment and the short-circuit logical operations, which
+  /// are lowered into the control flow.
+  Binary(BinaryOp, Operand, Operand),
+  /// Raw pointer to the place.
+  AddressOf(Place),
+  /// Conversion between primitive types, or between raw pointers and addresses.
+  Cast(Operand, Type),
+  /// Conversion into the trait object of the type.
+  ToDyn(Operand, Type),
+  /// Struct value of the type, with the index of each field.
+  Struct(Type, Vec<(usize, Operand)>),
+  /// Enum value of the type, by the index of the variant with its fields.
+  Variant(Type, usize, Vec<Operand>),
+  /// Array value of the type, with its elements in order.
+  Array(Type, Vec<Operand>),
+  /// Index of the variant of the enum value in the place, as `uint32`.
+  Discriminant(Place),
+  /// Field of the enum value in the place, by the index of the variant and the index of the field.
+  /// The value must be of the variant.
+  VariantField(Place, usize, usize),
+}
+
+#[derive(Debug)]
+pub struct Terminator {
+  pub kind: TerminatorKind,
+  pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum TerminatorKind {
+  Goto(BasicBlock),
+  /// Branch on the boolean operand.
+  If {
+    condition: Operand,
+    then:      BasicBlock,
+    otherwise: BasicBlock,
+  },
+  /// Return the value of the return place `_0`.
+  Return,
+  /// Call the function, store the result into the destination, and continue at the target.
+  Call {
+    func:        Operand,
+    args:        Vec<Operand>,
+    destination: Place,
+    target:      BasicBlock,
+  },
+  /// Call the method of the trait object through its vtable.
+  DynCall {
+    object:      Operand,
+    trait_:      DefPath,
+    index:       usize,
+    args:        Vec<Operand>,
+    destination: Place,
+    target:      BasicBlock,
+  },
+  /// Suspend `async func` at `.await` on the pending future, which resumes at `resume` once polled
+  /// again. It only appears until the body is split into the state machine.
+  Yield {
+    resume: BasicBlock,
+  },
+  /// Control never reaches here, such as the end of a function which always returns.
+  Unreachable,
+}
+
+impl TerminatorKind {
+  pub fn successors(&self) -> Vec<BasicBlock> {
+    match self {
+      TerminatorKind::Goto(target) | TerminatorKind::Yield { resume: target } => vec![*target],
+      TerminatorKind::If {
+        then, otherwise, ..
+      } => vec![*then, *otherwise],
+      TerminatorKind::Call { target, .. } | TerminatorKind::DynCall { target, .. } => {
+        vec![*target]
+      }
+      TerminatorKind::Return | TerminatorKind::Unreachable => vec![],
+    }
+  }
+}
+
+impl Terminator {
+  pub fn successors(&self) -> Vec<BasicBlock> {
+    self.kind.successors()
+  }
+}
@@ -0,0 +1,292 @@
+//! Textual form of the MIR, printed by `vsp dump --mir`.
+//!
+//! ```plaintext
+//! func max(_1: int64, _2: int64) -> int64 {
+//!   let mut _0: int64;
+//!   let _1: int64; // a
+//!   let _2: int64; // b
+//!   let _3: bool;
+//!
+//!   bb0: {
+//!     _3 = _1 > _2;
+//!     if _3 -> [true: bb1, false: bb2];
+//!   }
+//!   ...
+//! }
+//! ```
+use core::fmt::Display;
+use core::fmt::Formatter;
+use core::fmt::Result;
+
+use vsp_sema::mono::MonoItem;
+use vsp_sema::resolve::is_prelude;
+
+use crate::mir::BasicBlock;
+use crate::mir::Body;
+use crate::mir::Constant;
+use crate::mir::ConstantKind;
+use crate::mir::Local;
+use crate::mir::Operand;
+use crate::mir::Place;
+use crate::mir::Program;
+use crate::mir::ProjectionElem;
+use crate::mir::Rvalue;
+use crate::mir::StatementKind;
+use crate::mir::TerminatorKind;
+
+impl Display for Program {
+  fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+    // The prelude is declared in every program, so its foreign functions are left out.
+    let foreign = self
+      .foreign
+      .iter()
+      .filter(|function| !is_prelude(&function.def.module))
+      .collect::<Vec<_>>();
+    for function in &foreign {
+      let params = function.params.iter().map(ToString::to_string);
+      let params = match function.variadic {
+        true => params.chain(Some("...".to_owned())).collect::<Vec<_>>(),
+        false => params.collect(),
+      };
+      writeln!(
+        f,
+        "extern \"C\" func {}({}) -> {};",
+        function.def,
+        params.join(", "),
+        function.ret
+      )?;
+    }
+    if !foreign.is_empty() && !self.bodies.is_empty() {
+      writeln!(f)?;
+    }
+    for (index, body) in self.bodies.iter().enumerate() {
+      if index > 0 {
+        writeln!(f)?;
+      }
+      write!(f, "{}", body)?;
+    }
+    // Values of the constants and the static items, once evaluated.
+    if !self.consts.is_empty() {
+      writeln!(f)?;
+    }
+    for value in &self.consts {
+      let (keyword, def) = match &value.owner {
+        MonoItem::Const(def) => ("const", def),
+        MonoItem::Static(def) => ("static", def),
+        owner => unreachable!("unexpected owner of the constant: {:?}", owner),
+      };
+      writeln!(f, "{} {} = const {};", keyword, def, value.value)?;
+    }
+    Ok(())
+  }
+}
+
+impl Display for Body {
+  fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+    match &self.owner {
+      MonoItem::Const(def) => writeln!(f, "const {}: {} {{", def, self.return_type())?,
+      MonoItem::Static(def) => writeln!(f, "static {}: {} {{", def, self.return_type())?,
+      owner => {
+        let name = match owner {
+          MonoItem::Method(path) => format!("impl#{}::{}", path.impl_id.0, path.name),
+          MonoItem::Coroutine(def) => format!("{{async {}}}::poll", def),
+          MonoItem::Function(def) | MonoItem::Const(def) | MonoItem::Static(def) => def.to_string(),
+        };
+        let args = self
+          .args()
+          .map(|arg| format!("{}: {}", arg, self.local(arg).ty))
+          .collect::<Vec<_>>();
+        let keyword = if self.constancy.is_constant() {
+          "const func"
+        } else {
+          "func"
+        };
+        writeln!(
+          f,
+          "{} {}({}) -> {} {{",
+          keyword,
+          name,
+          args.join(", "),
+          self.return_type()
+        )?;
+      }
+    }
+    for (index, decl) in self.locals.iter().enumerate() {
+      let mutable = if decl.mutable { "mut " } else { "" };
+      write!(f, "  let {}{}: {};", mutable, Local(index), decl.ty)?;
+      match &decl.name {
+        Some(name) => writeln!(f, " // {}", name)?,
+        None => writeln!(f)?,
+      }
+    }
+    for (index, block) in self.blocks.iter().enumerate() {
+      writeln!(f)?;
+      writeln!(f, "  {}: {{", BasicBlock(index))?;
+      for statement in &block.statements {
+        match &statement.kind {
+          StatementKind::Assign(place, rvalue) => writeln!(f, "    {} = {};", place, rvalue)?,
+          StatementKind::StorageLive(local) => writeln!(f, "    StorageLive({});", local)?,
+        }
+      }
+      match &block.terminator {
+        Some(terminator) => writeln!(f, "    {};", terminator.kind)?,
+        None => writeln!(f, "    // missing terminator")?,
+      }
+      writeln!(f, "  }}")?;
+    }
+    writeln!(f, "}}")
+  }
+}
+
+impl Display for Local {
+  fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+    write!(f, "_{}", self.0)
+  }
+}
+
+impl Display for BasicBlock {
+  fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+    write!(f, "bb{}", self.0)
+  }
+}
+
+impl Display for Place {
+  fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+    // Dereferences are prefixed, so the place is parenthesized before the projections after them.
+    for _ in self.projection.iter().filter(|(elem, _)| *elem == ProjectionElem::Deref) {
+      write!(f, "(*")?;
+    }
+    write!(f, "{}", self.local)?;
+    for (elem, _) in &self.projection {
+      match elem {
+        ProjectionElem::Field(index) => write!(f, ".{}", index)?,
+        ProjectionElem::Deref => write!(f, ")")?,
+        ProjectionElem::Index(index) => write!(f, "[{}]", index)?,
+      }
+    }
+    Ok(())
+  }
+}
+
+impl Display for Operand {
+  fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+    match self {
+      Operand::Copy(place) => write!(f, "{}", place),
+      Operand::Constant(constant) => write!(f, "const {}", constant),
+    }
+  }
+}
+
+impl Display for Constant {
+  fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+    match &self.kind {
+      ConstantKind::Unit => write!(f, "()"),
+      ConstantKind::Integer(value) => write!(f, "{}_{}", value, self.ty),
+      ConstantKind::Float(value) => write!(f, "{:?}_{}", value, self.ty),
+      ConstantKind::Bool(value) => write!(f, "{}", value),
+      ConstantKind::Str(value) => write!(f, "{:?}", value),
+      ConstantKind::Function(def, args) => {
+        write!(f, "{}", def)?;
+        if !args.is_empty() {
+          let args = args.iter().map(ToString::to_string).collect::<Vec<_>>();
+          write!(f, "::<{}>", args.join(", "))?;
+        }
+        Ok(())
+      }
+      ConstantKind::Method(path) => write!(f, "impl#{}::{}", path.impl_id.0, path.name),
+      ConstantKind::Const(def) | ConstantKind::Static(def) => write!(f, "{}", def),
+      ConstantKind::BoundMethod {
+        trait_,
+        name,
+        self_ty,
+      } => write!(f, "<{} as {}>::{}", self_ty, trait_, name),
+    }
+  }
+}
+
+impl Display for Rvalue {
+  fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+    match self {
+      Rvalue::Use(operand) => write!(f, "{}", operand),
+      Rvalue::Unary(op, operand) => write!(f, "{}{}", op.as_str(), operand),
+      Rvalue::AddressOf(place) => write!(f, "&{}", place),
+      Rvalue::Binary(op, left, right) => write!(f, "{} {} {}", left, op.as_str(), right),
+      Rvalue::Cast(operand, ty) | Rvalue::ToDyn(operand, ty) => {
+        write!(f, "{} as {}", operand, ty)
+      }
+      Rvalue::Struct(ty, fields) => {
+        let fields = fields
+          .iter()
+          .map(|(index, value)| format!("{}: {}", index, value))
+          .collect::<Vec<_>>();
+        write!(f, "{} {{ {} }}", ty, fields.join(", "))
+      }
+      Rvalue::Variant(ty, index, fields) => write!(f, "{}#{}({})", ty, index, join(fields)),
+      Rvalue::Array(_, elements) => write!(f, "[{}]", join(elements)),
+      Rvalue::Discriminant(place) => write!(f, "discriminant({})", place),
+      Rvalue::VariantField(place, index, field) => write!(f, "({} as #{}).{}", place, index, field),
+    }
+  }
+}
+
+impl Display for TerminatorKind {
+  fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+    match self {
+      TerminatorKind::Goto(target) => write!(f, "goto -> {}", target),
+      TerminatorKind::If {
+        condition,
+        then,
+        otherwise,
+      } => write!(
+        f,
+        "if {} -> [true: {}, false: {}]",
+        condition, then, otherwise
+      ),
+      TerminatorKind::Return => write!(f, "return"),
+      TerminatorKind::Call {
+        func,
+        args,
+        destination,
+        target,
+      } => {
+        let func = match func {
+          Operand::Constant(constant) => constant.to_string(),
+          func => format!("({})", func),
+        };
+        write!(
+          f,
+          "{} = {}({}) -> {}",
+          destination,
+          func,
+          join(args),
+          target
+        )
+      }
+      TerminatorKind::DynCall {
+        object,
+        trait_,
+        index,
+        args,
+        destination,
+        target,
+      } => {
+        let args = std::iter::once(object).chain(args.iter()).cloned().collect::<Vec<_>>();
+        write!(
+          f,
+          "{} = <dyn {}>::#{}({}) -> {}",
+          destination,
+          trait_,
+          index,
+          join(&args),
+          target
+        )
+      }
+      TerminatorKind::Yield { resume } => write!(f, "yield -> {}", resume),
+      TerminatorKind::Unreachable => write!(f, "unreachable"),
+    }
+  }
+}
+
+fn join(operands: &[Operand]) -> String {
+  operands.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+}
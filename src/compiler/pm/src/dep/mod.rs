@@ -4,3 +4,20 @@ pub struct Dependency {
   name:    String,
   version: semver::Version,
 }
+
+impl Dependency {
+  pub fn new(name: impl Into<String>, version: semver::Version) -> Self {
+    Self {
+      name: name.into(),
+      version,
+    }
+  }
+
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  pub fn version(&self) -> &semver::Version {
+    &self.version
+  }
+}
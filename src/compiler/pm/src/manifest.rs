@@ -1,11 +1,17 @@
+use std::path::Path;
+
 use semver::Version;
+use vsp_error::VspError;
+use vsp_error::VspResult;
 
 use crate::dep::Dependencies;
+use crate::dep::Dependency;
 
 #[allow(dead_code)]
 pub struct Manifest {
   project:      Project,
   dependencies: Dependencies,
+  link:         Link,
 }
 
 impl Manifest {
@@ -16,8 +22,58 @@ impl Manifest {
         version: Version::parse("0.1.0").unwrap(),
       },
       dependencies: vec![],
+      link:         Link::default(),
     }
   }
+
+  /// Read the manifest from `manifest.toml`.
+  pub fn load(path: &Path) -> VspResult<Self> {
+    let source = std::fs::read_to_string(path).map_err(VspError::from)?;
+    Self::parse(&source)
+  }
+
+  /// Parse the manifest, which is the subset of TOML made of the tables whose values are strings
+  /// or arrays of strings.
+  pub fn parse(source: &str) -> VspResult<Self> {
+    let mut manifest = Self::new("");
+    let mut name = None;
+    let mut version = None;
+    for (table, key, value, line) in parse_tables(source)? {
+      match (table.as_str(), key.as_str()) {
+        ("project", "name") => name = Some(value.into_string(line)?),
+        ("project", "version") => version = Some(parse_version(&value.into_string(line)?, line)?),
+        ("dependencies", _) => {
+          let version = parse_version(&value.into_string(line)?, line)?;
+          manifest.dependencies.push(Dependency::new(key, version));
+        }
+        ("link", "libraries") => manifest.link.libraries = value.into_array(line)?,
+        ("link", "search-paths") => manifest.link.search_paths = value.into_array(line)?,
+        ("link", "args") => manifest.link.args = value.into_array(line)?,
+        _ => {
+          return Err(VspError::new(format!(
+            "{}: unknown key `{}` in the table `[{}]`",
+            line, key, table
+          )))
+        }
+      }
+    }
+    manifest.project.name = name.ok_or_else(|| VspError::new("missing `name` in `[project]`"))?;
+    manifest.project.version =
+      version.ok_or_else(|| VspError::new("missing `version` in `[project]`"))?;
+    Ok(manifest)
+  }
+
+  pub fn name(&self) -> &str {
+    &self.project.name
+  }
+
+  pub fn version(&self) -> &Version {
+    &self.project.version
+  }
+
+  pub fn link(&self) -> &Link {
+    &self.link
+  }
 }
 
 impl Default for Manifest {
@@ -34,11 +90,198 @@ pub(crate) struct Project {
   version: Version,
 }
 
+/// Native libraries to link against, declared in the table `[link]`:
+///
+/// ```toml
+/// [link]
+/// libraries = ["c", "m"]
+/// search-paths = ["/usr/local/lib"]
+/// args = ["-Wl,--as-needed"]
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Link {
+  pub libraries:    Vec<String>,
+  pub search_paths: Vec<String>,
+  /// Extra arguments passed to the linker as is.
+  pub args:         Vec<String>,
+}
+
+impl Link {
+  /// Flags of the linker driver, where the search paths come before the libraries.
+  pub fn flags(&self) -> Vec<String> {
+    let search_paths = self.search_paths.iter().map(|path| format!("-L{}", path));
+    let libraries = self.libraries.iter().map(|library| format!("-l{}", library));
+    search_paths.chain(libraries).chain(self.args.iter().cloned()).collect()
+  }
+}
+
+enum Value {
+  String(String),
+  Array(Vec<String>),
+}
+
+impl Value {
+  fn into_string(self, line: usize) -> VspResult<String> {
+    match self {
+      Value::String(value) => Ok(value),
+      Value::Array(_) => Err(VspError::new(format!("{}: expected a string", line))),
+    }
+  }
+
+  fn into_array(self, line: usize) -> VspResult<Vec<String>> {
+    match self {
+      Value::Array(values) => Ok(values),
+      Value::String(_) => Err(VspError::new(format!(
+        "{}: expected an array of strings",
+        line
+      ))),
+    }
+  }
+}
+
+fn parse_version(text: &str, line: usize) -> VspResult<Version> {
+  Version::parse(text).map_err(|e| VspError::new(format!("{}: invalid version: {}", line, e)))
+}
+
+/// Split the source into the entries `(table, key, value, line)`, where arrays may span several
+/// lines.
+fn parse_tables(source: &str) -> VspResult<Vec<(String, String, Value, usize)>> {
+  let mut entries = vec![];
+  let mut table = String::new();
+  let mut lines = source.lines().enumerate().map(|(index, text)| (index + 1, text));
+  while let Some((line, text)) = lines.next() {
+    let text = text.trim();
+    if text.is_empty() || text.starts_with('#') {
+      continue;
+    }
+    if let Some(name) = text.strip_prefix('[') {
+      let name = strip_comment(name)
+        .strip_suffix(']')
+        .ok_or_else(|| VspError::new(format!("{}: expected `]`", line)))?;
+      table = name.trim().to_owned();
+      continue;
+    }
+    let (key, value) = text
+      .split_once('=')
+      .ok_or_else(|| VspError::new(format!("{}: expected `key = value`", line)))?;
+    let mut value = strip_comment(value.trim()).to_owned();
+    // Join the following lines until the array is closed.
+    while value.starts_with('[') && !value.ends_with(']') {
+      let (_, next) =
+        lines.next().ok_or_else(|| VspError::new(format!("{}: unclosed array", line)))?;
+      value.push(' ');
+      value.push_str(strip_comment(next.trim()));
+    }
+    let value = parse_value(&value, line)?;
+    entries.push((table.clone(), key.trim().to_owned(), value, line));
+  }
+  Ok(entries)
+}
+
+/// Remove the trailing comment outside of the strings.
+fn strip_comment(text: &str) -> &str {
+  let mut quoted = false;
+  for (index, c) in text.char_indices() {
+    match c {
+      '"' => quoted = !quoted,
+      '#' if !quoted => return text[..index].trim_end(),
+      _ => {}
+    }
+  }
+  text.trim_end()
+}
+
+fn parse_value(text: &str, line: usize) -> VspResult<Value> {
+  match text.strip_prefix('[').and_then(|text| text.strip_suffix(']')) {
+    Some(items) => split_items(items)
+      .into_iter()
+      .map(|item| parse_string(item, line))
+      .collect::<VspResult<Vec<_>>>()
+      .map(Value::Array),
+    None => parse_string(text, line).map(Value::String),
+  }
+}
+
+/// Split the items of an array by the commas outside of the strings, allowing a trailing comma.
+fn split_items(items: &str) -> Vec<&str> {
+  let mut result = vec![];
+  let mut quoted = false;
+  let mut start = 0;
+  for (index, c) in items.char_indices() {
+    match c {
+      '"' => quoted = !quoted,
+      ',' if !quoted => {
+        result.push(items[start..index].trim());
+        start = index + 1;
+      }
+      _ => {}
+    }
+  }
+  result.push(items[start..].trim());
+  result.retain(|item| !item.is_empty());
+  result
+}
+
+fn parse_string(text: &str, line: usize) -> VspResult<String> {
+  text
+    .strip_prefix('"')
+    .and_then(|text| text.strip_suffix('"'))
+    .filter(|text| !text.contains('"'))
+    .map(ToOwned::to_owned)
+    .ok_or_else(|| VspError::new(format!("{}: expected a string, found `{}`", line, text)))
+}
+
 #[cfg(test)]
 mod tests {
+  use super::*;
+
   /// Tests write the project config file to the temporary directory.
   #[test]
   fn test_manifest() {
-    let temp_dir = std::env::temp_dir();
+    let path = std::env::temp_dir().join("vsp_test_manifest.toml");
+    std::fs::write(
+      &path,
+      "[project]
+       name = \"std\" # the standard library
+       version = \"0.0.1\"
+
+       [dependencies]
+       core = \"0.1.0\"
+
+       [link]
+       libraries = [\"c\", \"m\"]
+       search-paths = [
+         \"/usr/local/lib\", # local
+         \"/opt/lib\",
+       ]
+       args = [\"-Wl,--as-needed\"]",
+    )
+    .unwrap();
+    let manifest = Manifest::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(manifest.name(), "std");
+    assert_eq!(manifest.version(), &Version::new(0, 0, 1));
+    assert_eq!(manifest.dependencies.len(), 1);
+    assert_eq!(
+      manifest.link().flags(),
+      [
+        "-L/usr/local/lib",
+        "-L/opt/lib",
+        "-lc",
+        "-lm",
+        "-Wl,--as-needed"
+      ]
+    );
+
+    let manifest = Manifest::parse("[project]\nname = \"hello\"\nversion = \"0.1.0\"").unwrap();
+    assert!(manifest.link().flags().is_empty());
+
+    let error = Manifest::parse("[link]\nlibraries = \"c\"").err().unwrap();
+    assert_eq!(error.message(), "2: expected an array of strings");
+    let error = Manifest::parse("[link]\nframeworks = []").err().unwrap();
+    assert_eq!(
+      error.message(),
+      "2: unknown key `frameworks` in the table `[link]`"
+    );
   }
 }
@@ -0,0 +1,2968 @@
+//! Type checking over the AST of functions and the initializers of constants and static items.
+use core::fmt::Display;
+use std::collections::HashMap;
+
+use vsp_ast::ast::constant::ConstDefinition;
+use vsp_ast::ast::constant::ConstKind;
+use vsp_ast::ast::expr::BinaryOp;
+use vsp_ast::ast::expr::Expression;
+use vsp_ast::ast::expr::ExpressionKind;
+use vsp_ast::ast::expr::UnaryOp;
+use vsp_ast::ast::function::Function;
+use vsp_ast::ast::modifier::Asyncness;
+use vsp_ast::ast::modifier::Unsafety;
+use vsp_ast::ast::module::Path;
+use vsp_ast::ast::pattern::MatchArm;
+use vsp_ast::ast::pattern::Pattern;
+use vsp_ast::ast::pattern::PatternKind;
+use vsp_ast::ast::stmt::ForIterable;
+use vsp_ast::ast::stmt::ForStatement;
+use vsp_ast::ast::stmt::JumpStatement;
+use vsp_ast::ast::stmt::Statement;
+use vsp_ast::ast::stmt::StatementBlock;
+use vsp_ast::ast::stmt::VariableDeclaration;
+use vsp_ast::ast::types::PrimitiveType;
+use vsp_ast::ast::types::Type;
+use vsp_ast::ast::CompilationUnit;
+use vsp_ast::ast::NodeId;
+use vsp_diag::Diagnostic;
+use vsp_diag::DiagnosticEngine;
+use vsp_span::Span;
+
+use crate::infer::InferenceTable;
+use crate::items::discriminant_def;
+use crate::items::GenericsInfo;
+use crate::items::ItemTable;
+use crate::items::LookupError;
+use crate::items::MethodCallee;
+use crate::items::MethodPath;
+use crate::items::TypeScope;
+use crate::lang::LangItem;
+use crate::mono::ItemUse;
+use crate::mono::MonoItem;
+use crate::pattern::Pat;
+use crate::pattern::Usefulness;
+use crate::resolve::collect_modules;
+use crate::resolve::DefKind;
+use crate::resolve::DefPath;
+use crate::resolve::Resolver;
+use crate::ty::Ty;
+use crate::ty::TyVarKind;
+
+/// Results of the type checking, as side tables keyed by the node ID of the AST.
+#[derive(Default)]
+pub struct TypeckResults {
+  /// Type of each expression.
+  expr_types:     HashMap<NodeId, Type>,
+  /// Type of each local variable declaration, and of each pattern of the match arms.
+  local_types:    HashMap<NodeId, Type>,
+  /// Target type of the expressions which are widened implicitly, or converted into trait
+  /// objects.
+  coercions:      HashMap<NodeId, Type>,
+  /// Methods called by method calls and paths to associated functions, `poll` of the futures
+  /// awaited other than the state machines of `async func`, and `next` of the iterators of `for`
+  /// statements.
+  method_callees: HashMap<NodeId, MethodCallee>,
+  /// Functions referred to by identifiers and paths.
+  functions:      HashMap<NodeId, DefPath>,
+  /// Constants and static items referred to by identifiers and paths.
+  consts:         HashMap<NodeId, DefPath>,
+  /// Index of the field in the declaration of the struct, for field access expressions.
+  fields:         HashMap<NodeId, usize>,
+  /// Index of the enum variant constructed by paths to unit variants and calls of tuple variants,
+  /// or matched by the patterns of variants.
+  variants:       HashMap<NodeId, usize>,
+  /// Methods converting the errors of `Expected` for the `?` operator.
+  conversions:    HashMap<NodeId, MethodPath>,
+  /// Type arguments of the references to generic functions and of the generic struct literals.
+  instantiations: HashMap<NodeId, Vec<Type>>,
+  /// Items used by each body, to be followed by the monomorphization.
+  uses:           HashMap<MonoItem, Vec<ItemUse>>,
+  items:          ItemTable,
+}
+
+impl TypeckResults {
+  pub fn expr_type(&self, id: NodeId) -> Option<&Type> {
+    self.expr_types.get(&id)
+  }
+
+  pub fn local_type(&self, id: NodeId) -> Option<&Type> {
+    self.local_types.get(&id)
+  }
+
+  /// Type which the expression is implicitly converted to, if any.
+  pub fn coercion(&self, id: NodeId) -> Option<&Type> {
+    self.coercions.get(&id)
+  }
+
+  /// Method called by the method call expression, or referred to by the path expression.
+  pub fn method_callee(&self, id: NodeId) -> Option<&MethodCallee> {
+    self.method_callees.get(&id)
+  }
+
+  /// Function which the identifier or the path expression refers to.
+  pub fn function(&self, id: NodeId) -> Option<&DefPath> {
+    self.functions.get(&id)
+  }
+
+  /// Constant or static item which the identifier or the path expression refers to.
+  pub fn constant(&self, id: NodeId) -> Option<&DefPath> {
+    self.consts.get(&id)
+  }
+
+  /// Index of the field accessed by the field access expression.
+  pub fn field_index(&self, id: NodeId) -> Option<usize> {
+    self.fields.get(&id).copied()
+  }
+
+  /// Index of the enum variant constructed by the expression.
+  pub fn variant(&self, id: NodeId) -> Option<usize> {
+    self.variants.get(&id).copied()
+  }
+
+  /// Method of the `From` lang item converting the error for the `?` expression, if the error is
+  /// converted.
+  pub fn conversion(&self, id: NodeId) -> Option<&MethodPath> {
+    self.conversions.get(&id)
+  }
+
+  /// Type arguments inferred for the generic function or struct at the expression, which might
+  /// mention the generic parameters of the enclosing function.
+  pub fn instantiation(&self, id: NodeId) -> Option<&[Type]> {
+    self.instantiations.get(&id).map(Vec::as_slice)
+  }
+
+  pub fn uses(&self, item: &MonoItem) -> &[ItemUse] {
+    self.uses.get(item).map(Vec::as_slice).unwrap_or_default()
+  }
+
+  /// Items of the package, with the lowered signatures.
+  pub fn items(&self) -> &ItemTable {
+    &self.items
+  }
+}
+
+/// Reference to the generic item whose type arguments are inferred with the function body.
+/// Associated type of a bound in an instantiation, such as `T::Entry`, as the index of the
+/// parameter, the trait, the name and the type variable standing for it.
+type AssociatedVar = (usize, DefPath, String, Ty);
+
+struct PendingInstantiation {
+  id:         NodeId,
+  def:        DefPath,
+  generics:   GenericsInfo,
+  args:       Vec<Ty>,
+  associated: Vec<AssociatedVar>,
+  span:       Span,
+  function:   bool,
+}
+
+/// Type checker for a compilation unit. Functions are checked one by one, and the type inference
+/// is local to each function body.
+pub struct TypeChecker<'a> {
+  diagnostics:  &'a mut DiagnosticEngine,
+  items:        ItemTable,
+  /// Module of the function being checked.
+  module:       Path,
+  /// Type of `Self` and its associated types, in methods of impls.
+  self_ty:      Option<Ty>,
+  associated:   HashMap<String, Ty>,
+  /// Generic parameters of the function being checked.
+  generics:     GenericsInfo,
+  functions:    HashMap<String, DefPath>,
+  results:      TypeckResults,
+  table:        InferenceTable,
+  scopes:       Vec<HashMap<String, Ty>>,
+  return_type:  Ty,
+  /// Whether the function being checked is `async func`, where `.await` is allowed.
+  asyncness:    Asyncness,
+  /// Whether the function being checked is `unsafe func`, whose whole body is an `unsafe` context.
+  unsafety:     Unsafety,
+  /// Depth of the `unsafe` blocks around the statement being checked.
+  unsafe_depth: usize,
+  /// Labels of the loops around the statement being checked, with the innermost one last.
+  loops:        Vec<Option<String>>,
+  /// Callee of the call being checked, to tell the calls of `unsafe func`s from other uses.
+  callee:       Option<NodeId>,
+  /// Types recorded for the function being checked, which might contain type variables.
+  pending:      Vec<(NodeId, Ty, Span, bool)>,
+  /// Integer literals with their values, to be checked against the range of the solved types.
+  literals:     Vec<(i128, Ty, Span)>,
+  /// Operands of the negations, which must not be unsigned once solved.
+  negations:    Vec<(Ty, Span)>,
+  /// Casts from the types unknown until the associated types are normalized, with the targets.
+  casts:        Vec<(Ty, Ty, Span)>,
+  instances:    Vec<PendingInstantiation>,
+  uses:         Vec<ItemUse>,
+}
+
+impl<'a> TypeChecker<'a> {
+  pub fn new(diagnostics: &'a mut DiagnosticEngine) -> Self {
+    Self {
+      diagnostics,
+      items: ItemTable::default(),
+      module: Path::root(),
+      self_ty: None,
+      associated: HashMap::new(),
+      generics: GenericsInfo::default(),
+      functions: HashMap::new(),
+      results: TypeckResults::default(),
+      table: InferenceTable::new(),
+      scopes: vec![],
+      return_type: Ty::unit(),
+      asyncness: Asyncness::None,
+      unsafety: Unsafety::None,
+      unsafe_depth: 0,
+      loops: vec![],
+      callee: None,
+      pending: vec![],
+      literals: vec![],
+      negations: vec![],
+      casts: vec![],
+      instances: vec![],
+      uses: vec![],
+    }
+  }
+
+  /// Check all functions, methods and initializers of constants in the compilation unit and its
+  /// modules. Names are resolved first, so that each function sees the names in scope of its
+  /// module.
+  pub fn check_compilation_unit(mut self, unit: &CompilationUnit) -> TypeckResults {
+    let modules = collect_modules(unit);
+    let scopes = Resolver::new(self.diagnostics).resolve(&modules);
+    self.items = ItemTable::collect(&modules, scopes, self.diagnostics);
+
+    for module in &modules {
+      self.module = module.path.clone();
+      self.functions = self
+        .items
+        .scopes()
+        .scope(&module.path)
+        .into_iter()
+        .flatten()
+        .filter(|(_, def)| self.items.functions.contains_key(def))
+        .map(|(name, def)| (name.to_owned(), def.clone()))
+        .collect();
+      for unit in &module.units {
+        let file = Some(unit.filename().to_owned()).filter(|f| !f.is_empty());
+        self.diagnostics.set_current_file(file);
+        let mut consts = unit.consts.values().collect::<Vec<_>>();
+        consts.sort_by_key(|definition| definition.span.start);
+        for definition in consts {
+          let def = DefPath::new(module.path.clone(), definition.name.as_str());
+          self.check_const(def, definition);
+        }
+        let mut enums = unit.enums.values().collect::<Vec<_>>();
+        enums.sort_by_key(|definition| definition.span.start);
+        for definition in enums {
+          let def = DefPath::new(module.path.clone(), definition.name.as_str());
+          if self.items.enums[&def].discriminants.is_empty() {
+            continue;
+          }
+          for variant in &definition.variants {
+            if let Some(discriminant) = &variant.discriminant {
+              self.check_discriminant(discriminant_def(&def, &variant.name), discriminant);
+            }
+          }
+        }
+        // Check in the order of appearance so that the diagnostics are stable.
+        let mut functions =
+          unit.functions.values().map(|function| (None, function)).collect::<Vec<_>>();
+        for definition in &unit.impls {
+          // Implementations of unresolved traits are dropped by the collection.
+          let info = self.items.impl_at(&module.path, unit.filename(), &definition.span);
+          if let Some(info) = info {
+            let id = info.id;
+            functions.extend(definition.methods.iter().map(|method| (Some(id), method)));
+          }
+        }
+        functions.sort_by_key(|(_, function)| function.span.start);
+        for (impl_id, function) in functions {
+          let (owner, ty) = match impl_id {
+            Some(id) => {
+              let info = self.items.impl_info(id);
+              self.self_ty = Some(info.self_ty.clone());
+              self.associated = info.associated_types.clone();
+              self.generics = GenericsInfo::default();
+              let owner = MonoItem::Method(MethodPath {
+                impl_id: id,
+                name:    function.name.to_owned(),
+              });
+              (owner, info.methods[&function.name].ty.clone())
+            }
+            None => {
+              let def = DefPath::new(module.path.clone(), function.name.as_str());
+              let info = &self.items.functions[&def];
+              self.self_ty = None;
+              self.associated.clear();
+              self.generics = info.generics.clone();
+              // The body of `async func` returns the output of the state machine.
+              let ty = match &info.ty {
+                Ty::Function(params, _) => {
+                  Ty::Function(params.clone(), Box::new(info.output.clone()))
+                }
+                ty => ty.clone(),
+              };
+              (MonoItem::Function(def), ty)
+            }
+          };
+          self.check_function(owner, function, &ty);
+        }
+      }
+    }
+    self.diagnostics.set_current_file(None);
+    self.results.items = std::mem::take(&mut self.items);
+    self.results
+  }
+
+  /// Check the initializer of the constant or the static item against its type.
+  fn check_const(&mut self, def: DefPath, definition: &ConstDefinition) {
+    let info = &self.items.consts[&def];
+    let ty = info.ty.clone();
+    let owner = match info.kind {
+      ConstKind::Const => MonoItem::Const(def),
+      ConstKind::Static => MonoItem::Static(def),
+    };
+    self.self_ty = None;
+    self.associated.clear();
+    self.generics = GenericsInfo::default();
+    self.begin_body(ty.clone());
+    self.scopes.push(HashMap::new());
+    self.check_expr(&definition.init, &ty);
+    self.scopes.pop();
+    self.finish_body(owner);
+  }
+
+  /// Check the discriminant given to the variant explicitly, which is an `int64` constant.
+  fn check_discriminant(&mut self, def: DefPath, discriminant: &Expression) {
+    let ty = self.items.consts[&def].ty.clone();
+    self.self_ty = None;
+    self.associated.clear();
+    self.generics = GenericsInfo::default();
+    self.begin_body(ty.clone());
+    self.scopes.push(HashMap::new());
+    self.check_expr(discriminant, &ty);
+    self.scopes.pop();
+    self.finish_body(MonoItem::Const(def));
+  }
+
+  /// Check the body of the function against its signature lowered ahead.
+  fn check_function(&mut self, owner: MonoItem, function: &Function, signature: &Ty) {
+    let (params, ret) = match signature {
+      Ty::Function(params, ret) => (params.clone(), ret.as_ref().clone()),
+      _ => (
+        vec![Ty::Error; function.signature.parameters.len()],
+        Ty::Error,
+      ),
+    };
+    self.begin_body(ret);
+    self.asyncness = function.signature.asyncness;
+    self.unsafety = function.signature.unsafety;
+    let lang = &self.items.lang;
+    let futures = lang.get(LangItem::Poll).and(lang.get(LangItem::Future));
+    if self.asyncness.is_async() && futures.is_none() {
+      self.error(
+        "`async func` requires the lang items `Poll` and `Future`",
+        function.span.clone(),
+      );
+    }
+    // The body of `block_on` generated by the compiler polls the future.
+    let future = self.items.lang.get(LangItem::Future).cloned();
+    if let (MonoItem::Function(def), Some(future)) = (&owner, future) {
+      if self.items.lang.item_of(def) == Some(LangItem::BlockOn) {
+        let param = self.generics.names().remove(0);
+        self.uses.push(ItemUse::BoundMethod {
+          trait_:  future,
+          name:    "poll".to_owned(),
+          self_ty: Type::named(Path::from(param.as_str())),
+        });
+      }
+    }
+
+    let mut scope = HashMap::new();
+    if function.signature.receiver {
+      scope.insert("self".to_owned(), self.self_ty.clone().unwrap_or(Ty::Error));
+    }
+    for (param, ty) in function.signature.parameters.iter().zip(params) {
+      scope.insert(param.name.to_owned(), ty);
+    }
+    self.scopes.push(scope);
+    if let Some(body) = &function.body {
+      self.check_block(body);
+    }
+    self.scopes.pop();
+    self.finish_body(owner);
+  }
+
+  fn begin_body(&mut self, return_type: Ty) {
+    self.table = InferenceTable::new();
+    self.pending.clear();
+    self.literals.clear();
+    self.negations.clear();
+    self.return_type = return_type;
+    self.asyncness = Asyncness::None;
+    self.unsafety = Unsafety::None;
+  }
+
+  /// True if raw pointers could be dereferenced and `unsafe func`s used here.
+  fn in_unsafe(&self) -> bool {
+    self.unsafety.is_unsafe() || self.unsafe_depth > 0
+  }
+
+  /// Report the operation which is only allowed in `unsafe` blocks and functions, if it is used
+  /// outside them.
+  fn require_unsafe(&mut self, operation: impl Display, span: Span) {
+    if !self.in_unsafe() {
+      self.error(
+        format!("{} requires an `unsafe` block or function", operation),
+        span,
+      );
+    }
+  }
+
+  /// Solve the types recorded for the body, and check what could only be checked once solved.
+  fn finish_body(&mut self, owner: MonoItem) {
+    self.table.apply_defaults();
+    // Associated types of the instances are only known once their type arguments are solved.
+    for instance in std::mem::take(&mut self.instances) {
+      self.check_instantiation(instance);
+    }
+    for (source, target, span) in std::mem::take(&mut self.casts) {
+      let source = self.table.resolve(&source);
+      if let Ty::Var(..) = source {
+        self.error("type annotations needed", span);
+      } else if !self.is_valid_cast(&source, &target) {
+        self.error(
+          format!("casting `{}` as `{}` is invalid", source, target),
+          span,
+        );
+      }
+    }
+    for (id, ty, span, is_local) in std::mem::take(&mut self.pending) {
+      let ty = self.table.resolve(&ty);
+      match ty.to_type() {
+        Some(ty) => {
+          if is_local {
+            self.results.local_types.insert(id, ty);
+          } else {
+            self.results.expr_types.insert(id, ty);
+          }
+        }
+        None if is_local && !ty.is_error() => {
+          self.error("type annotations needed", span);
+        }
+        None => {}
+      }
+    }
+    for (value, ty, span) in std::mem::take(&mut self.literals) {
+      let ty = self.table.resolve(&ty);
+      if let Ty::Primitive(primitive) = &ty {
+        if let Some((min, max)) = primitive.integer_range() {
+          if value < min || value > max {
+            self.diagnostics.emit(
+              Diagnostic::error(
+                format!("literal out of range for `{}`", primitive.keyword()),
+                span,
+              )
+              .with_note(format!(
+                "the literal `{}` does not fit into the type `{}` whose range is `{}..={}`",
+                value,
+                primitive.keyword(),
+                min,
+                max
+              )),
+            );
+          }
+        }
+      }
+    }
+    for (ty, span) in std::mem::take(&mut self.negations) {
+      let ty = self.table.resolve(&ty);
+      if matches!(&ty, Ty::Primitive(primitive) if primitive.is_unsigned_integer()) {
+        self.error(
+          format!("cannot apply unary operator `-` to type `{}`", ty),
+          span,
+        );
+      }
+    }
+    let uses = std::mem::take(&mut self.uses);
+    self.results.uses.insert(owner, uses);
+  }
+
+  /// Check the inferred type arguments against the bounds of the generic parameters.
+  fn check_instantiation(&mut self, instance: PendingInstantiation) {
+    let args = instance.args.iter().map(|arg| self.table.resolve(arg)).collect::<Vec<_>>();
+    let mut types = vec![];
+    for (index, (param, arg)) in instance.generics.params.iter().zip(args.iter()).enumerate() {
+      if arg.is_error() {
+        return;
+      }
+      match arg.to_type() {
+        Some(ty) => types.push(ty),
+        None => {
+          self.error(
+            format!(
+              "type annotations needed: cannot infer type for type parameter `{}` of `{}`",
+              param.name, instance.def
+            ),
+            instance.span,
+          );
+          return;
+        }
+      }
+      for bound in &param.bounds {
+        if !self.items.implements(arg, bound, &self.generics) {
+          self.diagnostics.emit(
+            Diagnostic::error(
+              format!("the trait bound `{}: {}` is not satisfied", arg, bound),
+              instance.span.clone(),
+            )
+            .with_note(format!(
+              "required by the bound of `{}` in `{}`",
+              param.name, instance.def
+            )),
+          );
+          continue;
+        }
+        self.normalize_associated(&instance, index, bound, arg);
+      }
+    }
+    if instance.function {
+      self.uses.push(ItemUse::Function(instance.def, types.clone()));
+    }
+    self.results.instantiations.insert(instance.id, types);
+  }
+
+  /// Unify the type variables standing for the associated types of the bound with the associated
+  /// types of the implementation for the type argument.
+  fn normalize_associated(
+    &mut self,
+    instance: &PendingInstantiation,
+    index: usize,
+    bound: &DefPath,
+    arg: &Ty,
+  ) {
+    let associated = instance
+      .associated
+      .iter()
+      .filter(|(param, trait_, ..)| *param == index && trait_ == bound);
+    for (_, _, name, var) in associated {
+      let ty = match self.items.associated_type(bound, name, arg) {
+        Some(ty) => ty,
+        None => continue,
+      };
+      if self.table.unify(var, &ty).is_err() {
+        let var = self.table.resolve(var);
+        self.error(
+          format!("expected {}, found {}", var, ty),
+          instance.span.clone(),
+        );
+      }
+    }
+  }
+
+  /// Instantiate the generic item with fresh type variables for its generic parameters and the
+  /// associated types of their bounds, returning the substituted type, the arguments and the
+  /// associated types.
+  fn instantiate(&mut self, generics: &GenericsInfo, ty: &Ty) -> (Ty, Vec<Ty>, Vec<AssociatedVar>) {
+    let args = generics
+      .params
+      .iter()
+      .map(|_| self.table.new_var(TyVarKind::General))
+      .collect::<Vec<_>>();
+    let mut substitution = generics
+      .params
+      .iter()
+      .map(|p| p.name.to_owned())
+      .zip(args.iter().cloned())
+      .collect::<HashMap<_, _>>();
+    let mut associated = vec![];
+    for (index, param) in generics.params.iter().enumerate() {
+      for bound in &param.bounds {
+        for name in &self.items.traits[bound].associated_types {
+          let var = self.table.new_var(TyVarKind::General);
+          substitution.insert(format!("{}::{}", param.name, name), var.clone());
+          associated.push((index, bound.clone(), name.to_owned(), var));
+        }
+      }
+    }
+    (ty.substitute(&substitution), args, associated)
+  }
+
+  /// Type of the reference to the function, instantiated if the function is generic.
+  fn function_ref(&mut self, expr: &Expression, def: DefPath) -> Ty {
+    self.results.functions.insert(expr.id, def.clone());
+    if self.items.functions[&def].unsafety.is_unsafe() {
+      let verb = if self.callee == Some(expr.id) {
+        "call to"
+      } else {
+        "use of"
+      };
+      self.require_unsafe(
+        format!("{} unsafe function `{}`", verb, def),
+        expr.span.clone(),
+      );
+    }
+    if self.items.functions[&def].variadic && self.callee != Some(expr.id) {
+      self.error(
+        format!("variadic function `{}` could only be called directly", def),
+        expr.span.clone(),
+      );
+    }
+    let info = &self.items.functions[&def];
+    if info.generics.is_empty() {
+      let ty = info.ty.clone();
+      self.uses.push(ItemUse::Function(def, vec![]));
+      return ty;
+    }
+    let generics = info.generics.clone();
+    let (ty, args, associated) = self.instantiate(&generics, &info.ty.clone());
+    self.instances.push(PendingInstantiation {
+      id: expr.id,
+      def,
+      generics,
+      args,
+      associated,
+      span: expr.span.clone(),
+      function: true,
+    });
+    ty
+  }
+
+  /// Resolve the path to the constant or the static item, if it is.
+  fn const_def(&self, path: &Path) -> Option<DefPath> {
+    match self.items.resolve_path(&self.module, path)? {
+      (def, DefKind::Const | DefKind::Static) if self.items.consts.contains_key(&def) => Some(def),
+      _ => None,
+    }
+  }
+
+  /// Type of the reference to the constant or the static item.
+  fn const_ref(&mut self, expr: &Expression, def: DefPath) -> Ty {
+    let ty = self.items.consts[&def].ty.clone();
+    self.results.consts.insert(expr.id, def);
+    ty
+  }
+
+  fn check_block(&mut self, block: &StatementBlock) {
+    self.scopes.push(HashMap::new());
+    for stmt in block.stmts() {
+      self.check_stmt(stmt);
+    }
+    self.scopes.pop();
+  }
+
+  fn check_stmt(&mut self, stmt: &Statement) {
+    match stmt {
+      Statement::NoOp => {}
+      Statement::Expression(expr) => {
+        self.infer_expr(expr);
+      }
+      Statement::If(stmt) => {
+        self.check_expr(&stmt.condition, &Ty::bool());
+        self.check_block(&stmt.then);
+        if let Some(otherwise) = &stmt.otherwise {
+          self.check_stmt(otherwise);
+        }
+      }
+      Statement::While(stmt) => {
+        self.check_expr(&stmt.condition, &Ty::bool());
+        self.check_loop_body(&stmt.label, &stmt.body);
+      }
+      Statement::Loop(stmt) => self.check_loop_body(&stmt.label, &stmt.body),
+      Statement::For(stmt) => self.check_for(stmt),
+      Statement::Break(stmt) => self.check_jump("break", stmt),
+      Statement::Continue(stmt) => self.check_jump("continue", stmt),
+      Statement::VariableDeclaration(decl) => self.check_variable_declaration(decl),
+      Statement::Return(stmt) => {
+        let expected = self.return_type.clone();
+        match &stmt.value {
+          Some(value) => self.check_expr(value, &expected),
+          None => self.unify_at(&expected, &Ty::unit(), stmt.span.clone()),
+        }
+      }
+      Statement::Block(block) => self.check_block(block),
+      Statement::Unsafe(block) => {
+        self.unsafe_depth += 1;
+        self.check_block(block);
+        self.unsafe_depth -= 1;
+      }
+    }
+  }
+
+  fn check_loop_body(&mut self, label: &Option<String>, body: &StatementBlock) {
+    self.loops.push(label.clone());
+    self.check_block(body);
+    self.loops.pop();
+  }
+
+  /// The bounds of the range are of the same integer type, which is the type of the binding.
+  /// Otherwise the binding is the `Entry` of the iterator.
+  fn check_for(&mut self, stmt: &ForStatement) {
+    let ty = match &stmt.iterable {
+      ForIterable::Range(start, end) => {
+        let ty = self.table.new_var(TyVarKind::Integral);
+        self.check_expr(start, &ty);
+        self.check_expr(end, &ty);
+        ty
+      }
+      ForIterable::Iterator(iterator) => self.infer_iterator(stmt, iterator),
+    };
+    self.pending.push((stmt.id, ty.clone(), stmt.span.clone(), true));
+    self.scopes.push(HashMap::new());
+    self.declare(stmt.name.to_owned(), ty);
+    self.check_loop_body(&stmt.label, &stmt.body);
+    self.scopes.pop();
+  }
+
+  /// Type of the entries of the iterator, whose type implements the lang item `Iterator`, or is
+  /// the generic parameter bounded by it. `next` is recorded as the callee of the statement, and
+  /// `has_next` is found by the same implementation.
+  fn infer_iterator(&mut self, stmt: &ForStatement, iterator: &Expression) -> Ty {
+    let ty = self.infer_expr(iterator);
+    let ty = self.table.resolve(&ty);
+    let trait_ = self.items.lang.get(LangItem::Iterator).cloned();
+    match ty {
+      Ty::Error => Ty::Error,
+      Ty::Var(_, TyVarKind::General) => {
+        self.error("type annotations needed", iterator.span.clone());
+        Ty::Error
+      }
+      Ty::Param(param)
+        if trait_.as_ref().map_or(false, |t| self.generics.bounds(&param).contains(t)) =>
+      {
+        let trait_ = trait_.unwrap();
+        for name in ["has_next", "next"] {
+          self.uses.push(ItemUse::BoundMethod {
+            trait_:  trait_.clone(),
+            name:    name.to_owned(),
+            self_ty: Type::named(Path::from(param.as_str())),
+          });
+        }
+        let callee = MethodCallee::Bound {
+          trait_,
+          name: "next".to_owned(),
+        };
+        self.results.method_callees.insert(stmt.id, callee);
+        Ty::Param(format!("{}::Entry", param))
+      }
+      ty => {
+        let found = trait_.as_ref().and_then(|trait_| self.items.find_impl(trait_, &ty));
+        match found {
+          Some(info) => {
+            let entry = info.associated_types["Entry"].clone();
+            let path = MethodPath {
+              impl_id: info.id,
+              name:    "next".to_owned(),
+            };
+            self.results.method_callees.insert(stmt.id, MethodCallee::Static(path));
+            entry
+          }
+          None => {
+            self.diagnostics.emit(
+              Diagnostic::error(
+                format!("`{}` is not an iterator", ty),
+                iterator.span.clone(),
+              )
+              .with_note("`for` requires the type to implement the lang item `Iterator`"),
+            );
+            Ty::Error
+          }
+        }
+      }
+    }
+  }
+
+  /// `break` and `continue` must be in a loop, which has the label if any.
+  fn check_jump(&mut self, keyword: &str, stmt: &JumpStatement) {
+    match &stmt.label {
+      None if self.loops.is_empty() => {
+        self.error(
+          format!("`{}` outside of a loop", keyword),
+          stmt.span.clone(),
+        );
+      }
+      Some(label) if !self.loops.iter().any(|l| l.as_ref() == Some(label)) => {
+        self.error(
+          format!("use of undeclared label `'{}`", label),
+          stmt.span.clone(),
+        );
+      }
+      _ => {}
+    }
+  }
+
+  fn check_variable_declaration(&mut self, decl: &VariableDeclaration) {
+    let ty = match &decl.ty {
+      Some(ty) => self.lower_type(ty, &decl.span),
+      None => self.table.new_var(TyVarKind::General),
+    };
+    if let Some(init) = &decl.init {
+      self.check_expr(init, &ty);
+    }
+    self.pending.push((decl.id, ty.clone(), decl.span.clone(), true));
+    self.declare(decl.name.to_owned(), ty);
+  }
+
+  /// Check the expression against the expected type.
+  fn check_expr(&mut self, expr: &Expression, expected: &Ty) {
+    let found = self.infer_expr(expr);
+    if !self.try_widen(expr, &found, expected) && !self.try_coerce_dyn(expr, &found, expected) {
+      self.unify_at(expected, &found, expr.span.clone());
+    }
+  }
+
+  /// Convert the value into the trait object implicitly if the target is a trait object, and the
+  /// type of the value is known to implement the trait.
+  fn try_coerce_dyn(&mut self, expr: &Expression, found: &Ty, target: &Ty) -> bool {
+    let (found, trait_) = match (
+      self.table.shallow_resolve(found),
+      self.table.shallow_resolve(target),
+    ) {
+      (Ty::Var(..) | Ty::Error | Ty::Dyn(_), _) => return false,
+      (found, Ty::Dyn(trait_)) => (found, trait_),
+      _ => return false,
+    };
+    if self.items.find_impl(&trait_, &found).is_none() {
+      self.error(
+        format!("the trait `{}` is not implemented for `{}`", trait_, found),
+        expr.span.clone(),
+      );
+      return true;
+    }
+    self.results.coercions.insert(expr.id, Type::Dyn(trait_.path()));
+    true
+  }
+
+  /// Widen the integer expression implicitly if its type is known to be narrower than the target.
+  fn try_widen(&mut self, expr: &Expression, found: &Ty, target: &Ty) -> bool {
+    match (
+      self.table.shallow_resolve(found),
+      self.table.shallow_resolve(target),
+    ) {
+      (Ty::Primitive(from), Ty::Primitive(to)) if from.can_widen_to(&to) => {
+        self.results.coercions.insert(expr.id, Type::Primitive(to));
+        true
+      }
+      _ => false,
+    }
+  }
+
+  /// Infer the type of the expression, and record it.
+  fn infer_expr(&mut self, expr: &Expression) -> Ty {
+    let ty = self.infer_expr_kind(expr);
+    self.pending.push((expr.id, ty.clone(), expr.span.clone(), false));
+    ty
+  }
+
+  fn infer_expr_kind(&mut self, expr: &Expression) -> Ty {
+    match &expr.kind {
+      ExpressionKind::Unit => Ty::unit(),
+      ExpressionKind::LiteralInteger(value) => {
+        let ty = self.table.new_var(TyVarKind::Integral);
+        self.literals.push((*value as i128, ty.clone(), expr.span.clone()));
+        ty
+      }
+      ExpressionKind::LiteralFloat(_) => self.table.new_var(TyVarKind::Float),
+      ExpressionKind::LiteralBoolean(_) => Ty::bool(),
+      ExpressionKind::LiteralString(_) => Ty::Primitive(PrimitiveType::Str),
+      ExpressionKind::Identifier(name) => match self.lookup(name) {
+        Some(ty) => ty,
+        None => match self.functions.get(name).cloned() {
+          Some(def) => self.function_ref(expr, def),
+          None => match self.const_def(&Path::from(name.as_str())) {
+            Some(def) => self.const_ref(expr, def),
+            None => {
+              self.error(
+                format!("cannot find value `{}` in this scope", name),
+                expr.span.clone(),
+              );
+              Ty::Error
+            }
+          },
+        },
+      },
+      ExpressionKind::Unary(op, operand) => self.infer_unary(op, operand, expr.span.clone()),
+      ExpressionKind::Binary(op, left, right) => {
+        self.infer_binary(op, left, right, expr.span.clone())
+      }
+      ExpressionKind::Cast(operand, ty) => self.infer_cast(operand, ty, expr.span.clone()),
+      ExpressionKind::Call(callee, args) => match self.variant_of(callee) {
+        Some((def, index)) => self.infer_variant(expr, def, index, Some(args)),
+        None => self.infer_call(callee, args, expr.span.clone()),
+      },
+      ExpressionKind::Path(path) => self.infer_path(expr, path),
+      ExpressionKind::StructLiteral(path, fields) => self.infer_struct_literal(expr, path, fields),
+      ExpressionKind::Field(base, name) => self.infer_field(expr, base, name),
+      ExpressionKind::ArrayLiteral(elements) => self.infer_array_literal(elements),
+      ExpressionKind::Index(base, index) => self.infer_index(expr, base, index),
+      ExpressionKind::MethodCall(receiver, name, args) => {
+        self.infer_method_call(expr, receiver, name, args)
+      }
+      ExpressionKind::Try(operand) => self.infer_try(expr, operand),
+      ExpressionKind::Await(operand) => self.infer_await(expr, operand),
+      ExpressionKind::If(condition, then, otherwise) => {
+        self.check_expr(condition, &Ty::bool());
+        let ty = self.infer_expr(then);
+        self.check_expr(otherwise, &ty);
+        ty
+      }
+      ExpressionKind::Match(scrutinee, arms) => self.infer_match(expr, scrutinee, arms),
+      ExpressionKind::Block(block) => {
+        self.check_block(block);
+        Ty::unit()
+      }
+      ExpressionKind::LambdaExpression(..) => {
+        self.error("expression is not supported yet", expr.span.clone());
+        Ty::Error
+      }
+    }
+  }
+
+  fn infer_unary(&mut self, op: &UnaryOp, operand: &Expression, span: Span) -> Ty {
+    let ty = self.infer_expr(operand);
+    let resolved = self.table.shallow_resolve(&ty);
+    match op {
+      UnaryOp::Not => {
+        self.unify_at(&Ty::bool(), &ty, operand.span.clone());
+        Ty::bool()
+      }
+      UnaryOp::Negative => {
+        if !resolved.is_numeric() && !resolved.is_error() {
+          self.error(
+            format!("cannot apply unary operator `-` to type `{}`", resolved),
+            span,
+          );
+          return Ty::Error;
+        }
+        if matches!(operand.kind, ExpressionKind::LiteralInteger(_)) {
+          // Negative literals are checked against the range as a whole, such as `-128` for `int8`.
+          if let Some((value, _, literal_span)) = self.literals.last_mut() {
+            *value = -*value;
+            *literal_span = span;
+          }
+        } else {
+          self.negations.push((ty.clone(), span));
+        }
+        ty
+      }
+      UnaryOp::Dereference => match resolved {
+        Ty::Pointer(pointee) => {
+          self.require_unsafe("dereference of raw pointer", span);
+          *pointee
+        }
+        Ty::Error => Ty::Error,
+        resolved => {
+          self.error(format!("type `{}` cannot be dereferenced", resolved), span);
+          Ty::Error
+        }
+      },
+      UnaryOp::AddressOf => {
+        let item = match self.results.consts.get(&operand.id) {
+          Some(def) => Some((
+            self.items.scopes().kind(def).map_or("constant", |k| k.descr()),
+            def,
+          )),
+          None => self.results.functions.get(&operand.id).map(|def| ("function", def)),
+        };
+        if let Some((kind, def)) = item {
+          let message = format!("cannot take the address of {} `{}`", kind, def);
+          self.error(message, span);
+          return Ty::Error;
+        }
+        if !operand.is_place() {
+          self.error("cannot take the address of a temporary value", span);
+          return Ty::Error;
+        }
+        Ty::Pointer(Box::new(ty))
+      }
+    }
+  }
+
+  fn infer_binary(
+    &mut self,
+    op: &BinaryOp,
+    left: &Expression,
+    right: &Expression,
+    span: Span,
+  ) -> Ty {
+    if op == &BinaryOp::Assignment {
+      if !left.is_place() {
+        self.error("invalid left-hand side of assignment", left.span.clone());
+      }
+      let ty = self.infer_expr(left);
+      if let Some(def) = self.results.consts.get(&left.id) {
+        let kind = self.items.scopes().kind(def).map_or("constant", |kind| kind.descr());
+        self.error(
+          format!("cannot assign to {} `{}`", kind, def),
+          left.span.clone(),
+        );
+      }
+      self.check_expr(right, &ty);
+      return Ty::unit();
+    }
+    if op.is_logical() {
+      self.check_expr(left, &Ty::bool());
+      self.check_expr(right, &Ty::bool());
+      return Ty::bool();
+    }
+
+    let left_ty = self.infer_expr(left);
+    if let Ty::Pointer(_) = self.table.shallow_resolve(&left_ty) {
+      return self.infer_pointer_binary(op, &left_ty, right, span);
+    }
+    let ty = self.infer_binary_operands(left, left_ty, right);
+    let resolved = self.table.shallow_resolve(&ty);
+    if resolved.is_error() {
+      return if op.is_comparison() {
+        Ty::bool()
+      } else {
+        Ty::Error
+      };
+    }
+    let valid = match op {
+      BinaryOp::Equal | BinaryOp::NotEqual => !matches!(
+        resolved,
+        Ty::Function(..) | Ty::Adt(..) | Ty::Dyn(_) | Ty::Param(_)
+      ),
+      _ if op.is_comparison() => {
+        resolved.is_numeric() || resolved == Ty::Primitive(PrimitiveType::Char)
+      }
+      _ => resolved.is_numeric(),
+    };
+    if !valid {
+      self.error(
+        format!(
+          "cannot apply binary operator `{}` to type `{}`",
+          op.as_str(),
+          resolved
+        ),
+        span,
+      );
+    }
+    if op.is_comparison() {
+      Ty::bool()
+    } else if valid {
+      ty
+    } else {
+      Ty::Error
+    }
+  }
+
+  /// Infer the common type of both operands. When the integer types of both operands are known
+  /// and one is narrower than the other, the narrower one is widened implicitly.
+  fn infer_binary_operands(&mut self, left: &Expression, left_ty: Ty, right: &Expression) -> Ty {
+    let right_ty = self.infer_expr(right);
+    if self.try_widen(left, &left_ty, &right_ty) {
+      return right_ty;
+    }
+    if !self.try_widen(right, &right_ty, &left_ty) {
+      self.unify_at(&left_ty, &right_ty, right.span.clone());
+    }
+    left_ty
+  }
+
+  /// Arithmetic and comparisons on the raw pointer on the left. Adding or subtracting an `int64`
+  /// offsets the pointer by that many values of the pointee, and subtracting two pointers results
+  /// in the distance between them in values of the pointee.
+  fn infer_pointer_binary(
+    &mut self,
+    op: &BinaryOp,
+    left_ty: &Ty,
+    right: &Expression,
+    span: Span,
+  ) -> Ty {
+    let offset = Ty::Primitive(PrimitiveType::Int64);
+    match op {
+      BinaryOp::Add => {
+        self.check_expr(right, &offset);
+        left_ty.clone()
+      }
+      BinaryOp::Subtract => {
+        let right_ty = self.infer_expr(right);
+        if let Ty::Pointer(_) = self.table.shallow_resolve(&right_ty) {
+          self.unify_at(left_ty, &right_ty, right.span.clone());
+          return offset;
+        }
+        if !self.try_widen(right, &right_ty, &offset) {
+          self.unify_at(&offset, &right_ty, right.span.clone());
+        }
+        left_ty.clone()
+      }
+      op if op.is_comparison() => {
+        self.check_expr(right, left_ty);
+        Ty::bool()
+      }
+      op => {
+        self.infer_expr(right);
+        let resolved = self.table.resolve(left_ty);
+        self.error(
+          format!(
+            "cannot apply binary operator `{}` to type `{}`",
+            op.as_str(),
+            resolved
+          ),
+          span,
+        );
+        Ty::Error
+      }
+    }
+  }
+
+  /// Explicit conversion between primitive types. Numbers are converted to each other, while
+  /// `bool` and `char` could be converted to integers, and `uint8` could be converted to `char`.
+  /// Raw pointers are converted to each other, and to and from `int64` and `uint64` as addresses.
+  /// Enums without fields are converted to integers as their discriminants. Casts from the types
+  /// unknown yet, such as the outputs of the futures, are checked once the body is solved.
+  fn infer_cast(&mut self, operand: &Expression, ty: &Type, span: Span) -> Ty {
+    let target = self.lower_type(ty, &span);
+    let source = self.infer_expr(operand);
+    let resolved = self.table.shallow_resolve(&source);
+    let valid = match (&resolved, &target) {
+      (Ty::Var(_, TyVarKind::General), _) => {
+        self.casts.push((source, target.clone(), span));
+        return target;
+      }
+      (from, to) if self.is_valid_cast(from, to) => true,
+      (Ty::Var(_, TyVarKind::Integral), Ty::Pointer(_)) => {
+        // Integer literals are cast to pointers as `uint64` addresses.
+        self.unify_at(
+          &Ty::Primitive(PrimitiveType::Uint64),
+          &source,
+          operand.span.clone(),
+        );
+        true
+      }
+      (Ty::Var(_, TyVarKind::Integral), Ty::Primitive(PrimitiveType::Char)) => {
+        // Integer literals could only be cast to `char` as `uint8`.
+        self.unify_at(
+          &Ty::Primitive(PrimitiveType::Uint8),
+          &source,
+          operand.span.clone(),
+        );
+        true
+      }
+      _ => false,
+    };
+    if !valid {
+      self.error(
+        format!("casting `{}` as `{}` is invalid", resolved, target),
+        span,
+      );
+      return Ty::Error;
+    }
+    target
+  }
+
+  fn is_valid_cast(&self, from: &Ty, to: &Ty) -> bool {
+    match (from, to) {
+      (Ty::Error, _) | (_, Ty::Error) => true,
+      (from, to) if from == to => true,
+      (from, Ty::Primitive(to)) if to.is_numeric() && from.is_numeric() => true,
+      (Ty::Primitive(PrimitiveType::Bool | PrimitiveType::Char), Ty::Primitive(to)) => {
+        to.is_integer()
+      }
+      (Ty::Primitive(PrimitiveType::Uint8), Ty::Primitive(PrimitiveType::Char)) => true,
+      (Ty::Adt(def, _), Ty::Primitive(to)) if to.is_integer() => {
+        self.items.enums.get(def).map_or(false, |info| info.is_fieldless())
+      }
+      (Ty::Pointer(_), Ty::Pointer(_)) => true,
+      (Ty::Pointer(_), Ty::Primitive(PrimitiveType::Int64 | PrimitiveType::Uint64)) => true,
+      (Ty::Primitive(PrimitiveType::Int64 | PrimitiveType::Uint64), Ty::Pointer(_)) => true,
+      _ => false,
+    }
+  }
+
+  /// Path to the function of other modules, such as `std::length`, or to the associated function
+  /// of the type, such as `Point::new`. Methods referred to by paths take the receiver as the first
+  /// argument.
+  fn infer_path(&mut self, expr: &Expression, path: &Path) -> Ty {
+    if let Some(def) = self.const_def(path) {
+      return self.const_ref(expr, def);
+    }
+    if let Some((def, DefKind::Function)) = self.items.resolve_path(&self.module, path) {
+      if self.items.functions.contains_key(&def) {
+        return self.function_ref(expr, def);
+      }
+    }
+    if let Some((def, index)) = self.resolve_variant(path) {
+      return self.infer_variant(expr, def, index, None);
+    }
+    let (parent, name) = match (path.parent(), path.name()) {
+      (Some(parent), Some(name)) => (parent, name),
+      _ => return Ty::Error,
+    };
+    let ty = match self.resolve_type_path(&parent, expr.span.clone()) {
+      Some(ty) => ty,
+      None => return Ty::Error,
+    };
+    match self.lookup_method(&ty, name, expr.span.clone()) {
+      Some((callee, receiver, Ty::Function(mut params, ret))) => {
+        if let Some(callee) = callee {
+          self.results.method_callees.insert(expr.id, callee);
+        }
+        if receiver {
+          params.insert(0, ty);
+        }
+        Ty::Function(params, ret)
+      }
+      Some(_) => Ty::Error,
+      None => {
+        self.error(
+          format!(
+            "no function or associated item named `{}` found for type `{}`",
+            name, ty
+          ),
+          expr.span.clone(),
+        );
+        Ty::Error
+      }
+    }
+  }
+
+  /// Resolve the path to the type in the expression, such as `Self` or `Point` in `Point::new`.
+  /// Generic arguments of structs are left to the inference.
+  fn resolve_type_path(&mut self, path: &Path, span: Span) -> Option<Ty> {
+    if path.len() == 1 && path.name() == Some("Self") {
+      if self.self_ty.is_none() {
+        self.error("`Self` is only available in traits and impls", span);
+      }
+      return self.self_ty.clone();
+    }
+    if let Some(param) = self.generics.params.iter().find(|p| Some(p.name.as_str()) == path.name())
+    {
+      if path.len() == 1 {
+        return Some(Ty::Param(param.name.to_owned()));
+      }
+    }
+    match self.items.resolve_path(&self.module, path) {
+      Some((def, DefKind::Struct | DefKind::Enum)) => {
+        let params = self.items.adt_generics(&def).params.len();
+        let args = (0..params).map(|_| self.table.new_var(TyVarKind::General)).collect();
+        Some(Ty::Adt(def, args))
+      }
+      Some((_, kind)) => {
+        self.error(
+          format!("expected type, found {} `{}`", kind.descr(), path),
+          span,
+        );
+        None
+      }
+      None => {
+        self.error(
+          format!("failed to resolve: use of undeclared type `{}`", path),
+          span,
+        );
+        None
+      }
+    }
+  }
+
+  /// Look up the method of the type, returning the method, whether it takes the receiver and its
+  /// signature. Methods of generic parameters are those of their trait bounds. Errors other than
+  /// not found are reported, leaving the method unknown.
+  fn lookup_method(
+    &mut self,
+    ty: &Ty,
+    name: &str,
+    span: Span,
+  ) -> Option<(Option<MethodCallee>, bool, Ty)> {
+    let ty = &self.table.resolve(ty);
+    let found =
+      match ty {
+        Ty::Param(param) => self.items.bound_method(param, &self.generics, name).map(
+          |(trait_, receiver, signature)| {
+            self.uses.push(ItemUse::BoundMethod {
+              trait_:  trait_.clone(),
+              name:    name.to_owned(),
+              self_ty: Type::named(Path::from(param.as_str())),
+            });
+            let callee = MethodCallee::Bound {
+              trait_,
+              name: name.to_owned(),
+            };
+            (callee, receiver, signature)
+          },
+        ),
+        ty => self.items.lookup_method(ty, name).map(|(path, method)| {
+          let module = &self.items.impl_info(path.impl_id).module;
+          let private = !method.public && !self.module.starts_with(module);
+          let (receiver, signature) = (method.receiver, method.ty.clone());
+          if private {
+            self.diagnostics.emit(Diagnostic::error(
+              format!("method `{}` is private", name),
+              span.clone(),
+            ));
+          }
+          (MethodCallee::Static(path), receiver, signature)
+        }),
+      };
+    match found {
+      Ok((callee, receiver, signature)) => Some((Some(callee), receiver, signature)),
+      Err(LookupError::NotFound) => None,
+      Err(LookupError::Ambiguous(traits)) => {
+        let candidates = traits.iter().map(|t| format!("`{}`", t)).collect::<Vec<_>>();
+        self.diagnostics.emit(
+          Diagnostic::error("multiple applicable items in scope", span).with_note(format!(
+            "`{}` is defined in traits {} for type `{}`",
+            name,
+            candidates.join(", "),
+            ty
+          )),
+        );
+        Some((None, true, Ty::Error))
+      }
+    }
+  }
+
+  fn infer_struct_literal(
+    &mut self,
+    expr: &Expression,
+    path: &Path,
+    fields: &[(String, Expression)],
+  ) -> Ty {
+    let span = expr.span.clone();
+    let ty = self.resolve_type_path(path, span.clone());
+    let (def, args) = match &ty {
+      Some(Ty::Adt(def, args)) if self.items.structs.contains_key(def) => {
+        (def.clone(), args.clone())
+      }
+      other => {
+        if let Some(other) = other.as_ref().filter(|ty| !ty.is_error()) {
+          self.error(format!("expected struct, found `{}`", other), span);
+        }
+        fields.iter().for_each(|(_, value)| {
+          self.infer_expr(value);
+        });
+        return Ty::Error;
+      }
+    };
+    let info = &self.items.structs[&def];
+    let generics = info.generics.clone();
+    let substitution = generics.names().into_iter().zip(args.iter().cloned()).collect();
+    let declared = info
+      .fields
+      .iter()
+      .map(|f| (f.name.to_owned(), f.ty.substitute(&substitution), f.public))
+      .collect::<Vec<_>>();
+    if !generics.is_empty() {
+      self.instances.push(PendingInstantiation {
+        id: expr.id,
+        def: def.clone(),
+        generics,
+        args: args.clone(),
+        associated: vec![],
+        span: span.clone(),
+        function: false,
+      });
+    }
+    let mut initialized: Vec<&str> = vec![];
+    for (name, value) in fields {
+      match declared.iter().find(|(field, ..)| field == name) {
+        Some((_, field_ty, public)) => {
+          if initialized.contains(&name.as_str()) {
+            self.error(
+              format!("field `{}` specified more than once", name),
+              value.span.clone(),
+            );
+          }
+          self.check_field_visibility(&def, name, *public, value.span.clone());
+          initialized.push(name);
+          self.check_expr(value, field_ty);
+        }
+        None => {
+          self.error(
+            format!("struct `{}` has no field named `{}`", def, name),
+            value.span.clone(),
+          );
+          self.infer_expr(value);
+        }
+      }
+    }
+    let missing = declared
+      .iter()
+      .filter(|(field, ..)| !initialized.contains(&field.as_str()))
+      .map(|(field, ..)| format!("`{}`", field))
+      .collect::<Vec<_>>();
+    if !missing.is_empty() {
+      self.error(
+        format!(
+          "missing field{} {} in initializer of `{}`",
+          if missing.len() == 1 { "" } else { "s" },
+          missing.join(", "),
+          def
+        ),
+        span,
+      );
+    }
+    Ty::Adt(def, args)
+  }
+
+  fn infer_field(&mut self, expr: &Expression, base: &Expression, name: &str) -> Ty {
+    let span = expr.span.clone();
+    let base_ty = self.infer_expr(base);
+    let (def, args) = match self.table.shallow_resolve(&base_ty) {
+      Ty::Adt(def, args) => (def, args),
+      Ty::Error => return Ty::Error,
+      Ty::Var(_, TyVarKind::General) => {
+        self.error("type annotations needed", base.span.clone());
+        return Ty::Error;
+      }
+      ty => {
+        self.error(format!("no field `{}` on type `{}`", name, ty), span);
+        return Ty::Error;
+      }
+    };
+    let info = match self.items.structs.get(&def) {
+      Some(info) => info,
+      None => {
+        self.error(format!("no field `{}` on type `{}`", name, def), span);
+        return Ty::Error;
+      }
+    };
+    let substitution = info.generics.names().into_iter().zip(args).collect();
+    let field = info
+      .fields
+      .iter()
+      .enumerate()
+      .find(|(_, f)| f.name == name)
+      .map(|(index, f)| (index, f.ty.substitute(&substitution), f.public));
+    match field {
+      Some((index, ty, public)) => {
+        self.results.fields.insert(expr.id, index);
+        self.check_field_visibility(&def, name, public, span);
+        ty
+      }
+      None => {
+        self.error(format!("no field `{}` on type `{}`", name, def), span);
+        Ty::Error
+      }
+    }
+  }
+
+  /// Elements of the array expression have the type of the first element.
+  fn infer_array_literal(&mut self, elements: &[Expression]) -> Ty {
+    let element = match elements.first() {
+      Some(first) => self.infer_expr(first),
+      None => self.table.new_var(TyVarKind::General),
+    };
+    for other in elements.iter().skip(1) {
+      self.check_expr(other, &element);
+    }
+    Ty::Array(Box::new(element), elements.len())
+  }
+
+  /// Index into the array by an `int64`, which is checked against the length at runtime, or here
+  /// if the index is a literal and the length is known.
+  fn infer_index(&mut self, expr: &Expression, base: &Expression, index: &Expression) -> Ty {
+    let base_ty = self.infer_expr(base);
+    self.check_expr(index, &Ty::Primitive(PrimitiveType::Int64));
+    let (element, length) = match self.table.shallow_resolve(&base_ty) {
+      Ty::Array(element, length) => (element, Some(length)),
+      Ty::ConstArray(element, _) => (element, None),
+      Ty::Error => return Ty::Error,
+      Ty::Var(_, TyVarKind::General) => {
+        self.error("type annotations needed", base.span.clone());
+        return Ty::Error;
+      }
+      ty => {
+        self.error(
+          format!("cannot index into a value of type `{}`", ty),
+          expr.span.clone(),
+        );
+        return Ty::Error;
+      }
+    };
+    if let (ExpressionKind::LiteralInteger(value), Some(length)) = (&index.kind, length) {
+      if *value as u64 >= length as u64 {
+        self.error(
+          format!(
+            "index out of bounds: the length is {} but the index is {}",
+            length, value
+          ),
+          expr.span.clone(),
+        );
+      }
+    }
+    *element
+  }
+
+  /// Private fields are only accessible in the module defining the struct and its descendants.
+  fn check_field_visibility(&mut self, def: &DefPath, name: &str, public: bool, span: Span) {
+    if !public && !self.module.starts_with(&def.module) {
+      self.error(
+        format!("field `{}` of struct `{}` is private", name, def),
+        span,
+      );
+    }
+  }
+
+  /// Method calls on trait objects are dispatched dynamically through the vtable, while others are
+  /// resolved statically to the method of the implementation.
+  fn infer_method_call(
+    &mut self,
+    expr: &Expression,
+    receiver: &Expression,
+    name: &str,
+    args: &[Expression],
+  ) -> Ty {
+    let receiver_ty = self.infer_expr(receiver);
+    let span = expr.span.clone();
+    let (callee, signature) = match self.table.shallow_resolve(&receiver_ty) {
+      Ty::Error => (None, Ty::Error),
+      Ty::Var(_, TyVarKind::General) => {
+        self.error("type annotations needed", receiver.span.clone());
+        (None, Ty::Error)
+      }
+      Ty::Dyn(trait_) => match self.items.trait_method(&trait_, name) {
+        Some((index, method)) => (
+          Some(MethodCallee::Dynamic {
+            trait_: trait_.clone(),
+            index,
+          }),
+          method.dyn_ty.clone().unwrap_or(Ty::Error),
+        ),
+        None => {
+          self.error(
+            format!(
+              "no method named `{}` found for trait object `dyn {}`",
+              name, trait_
+            ),
+            span.clone(),
+          );
+          (None, Ty::Error)
+        }
+      },
+      ty => match self.lookup_method(&ty, name, span.clone()) {
+        Some((callee, true, signature)) => (callee, signature),
+        Some((_, false, _)) => {
+          self.diagnostics.emit(
+            Diagnostic::error(
+              format!("no method named `{}` found for type `{}`", name, ty),
+              span.clone(),
+            )
+            .with_note(format!(
+              "`{}` is an associated function, call it as `{}::{}(...)`",
+              name, ty, name
+            )),
+          );
+          (None, Ty::Error)
+        }
+        None => {
+          self.error(
+            format!("no method named `{}` found for type `{}`", name, ty),
+            span.clone(),
+          );
+          (None, Ty::Error)
+        }
+      },
+    };
+    if let Some(callee) = callee {
+      self.results.method_callees.insert(expr.id, callee);
+    }
+    self.check_arguments(&signature, args, span)
+  }
+
+  /// Resolve the path to the variant of the enum, such as `Optional::None`.
+  fn resolve_variant(&self, path: &Path) -> Option<(DefPath, usize)> {
+    let def = match self.items.resolve_path(&self.module, &path.parent()?)? {
+      (def, DefKind::Enum) => def,
+      _ => return None,
+    };
+    let (index, _) = self.items.variant(&def, path.name()?)?;
+    Some((def, index))
+  }
+
+  fn variant_of(&self, callee: &Expression) -> Option<(DefPath, usize)> {
+    match &callee.kind {
+      ExpressionKind::Path(path) => self.resolve_variant(path),
+      _ => None,
+    }
+  }
+
+  /// Construction of the enum variant, either by the path to the unit variant, such as
+  /// `Optional::None`, or by the call of the tuple variant with its fields, such as
+  /// `Optional::Value(1)`. Generic arguments of the enum are left to the inference.
+  fn infer_variant(
+    &mut self,
+    expr: &Expression,
+    def: DefPath,
+    index: usize,
+    args: Option<&[Expression]>,
+  ) -> Ty {
+    let span = expr.span.clone();
+    let info = &self.items.enums[&def];
+    let generics = info.generics.clone();
+    let name = info.variants[index].name.to_owned();
+    let adt = Ty::Adt(
+      def.clone(),
+      generics.names().into_iter().map(Ty::Param).collect(),
+    );
+    let constructor = Ty::Function(info.variants[index].fields.clone(), Box::new(adt));
+    let (constructor, type_args, associated) = self.instantiate(&generics, &constructor);
+    if !generics.is_empty() {
+      self.instances.push(PendingInstantiation {
+        id: expr.id,
+        def: def.clone(),
+        generics,
+        args: type_args,
+        associated,
+        span: span.clone(),
+        function: false,
+      });
+    }
+    self.results.variants.insert(expr.id, index);
+    let (fields, ty) = match constructor {
+      Ty::Function(fields, ty) => (fields, *ty),
+      _ => unreachable!("constructors of variants are functions"),
+    };
+    let args = match args {
+      Some(args) => args,
+      None if fields.is_empty() => return ty,
+      None => {
+        self.diagnostics.emit(
+          Diagnostic::error(
+            format!("expected value, found tuple variant `{}::{}`", def, name),
+            span,
+          )
+          .with_note(format!(
+            "the variant takes {} field{}, call it as `{}::{}(...)`",
+            fields.len(),
+            if fields.len() == 1 { "" } else { "s" },
+            def,
+            name
+          )),
+        );
+        return Ty::Error;
+      }
+    };
+    if args.len() != fields.len() {
+      self.error(
+        format!(
+          "this variant takes {} field{} but {} {} supplied",
+          fields.len(),
+          if fields.len() == 1 { "" } else { "s" },
+          args.len(),
+          if args.len() == 1 { "was" } else { "were" },
+        ),
+        span,
+      );
+      args.iter().for_each(|arg| {
+        self.infer_expr(arg);
+      });
+      return ty;
+    }
+    for (arg, field) in args.iter().zip(fields.iter()) {
+      self.check_expr(arg, field);
+    }
+    ty
+  }
+
+  /// Pattern matching, whose arms are of the same type except the blocks which never complete. The
+  /// patterns must cover all values of the scrutinee, and each of them must match some values
+  /// which the patterns before it do not.
+  fn infer_match(&mut self, expr: &Expression, scrutinee: &Expression, arms: &[MatchArm]) -> Ty {
+    let errors = self.diagnostics.error_count();
+    let scrutinee_ty = self.infer_expr(scrutinee);
+    let ty = self.table.new_var(TyVarKind::General);
+    let mut typed = false;
+    for arm in arms {
+      self.scopes.push(HashMap::new());
+      self.check_pattern(&arm.pattern, &scrutinee_ty);
+      match &arm.body.kind {
+        ExpressionKind::Block(block) if block.diverges() => {
+          self.infer_expr(&arm.body);
+        }
+        _ => {
+          self.check_expr(&arm.body, &ty);
+          typed = true;
+        }
+      }
+      self.scopes.pop();
+    }
+    if !typed {
+      self.unify_at(&ty, &Ty::unit(), expr.span.clone());
+    }
+    // Patterns which are ill-typed could not be compared with each other.
+    if self.diagnostics.error_count() == errors {
+      self.check_exhaustive(expr, arms);
+    }
+    ty
+  }
+
+  /// Check the pattern against the type of the value matched, declaring its bindings.
+  fn check_pattern(&mut self, pattern: &Pattern, expected: &Ty) {
+    let span = pattern.span.clone();
+    self.pending.push((pattern.id, expected.clone(), span.clone(), true));
+    match &pattern.kind {
+      PatternKind::Wildcard => {}
+      PatternKind::Binding(name) => self.declare(name.to_owned(), expected.clone()),
+      PatternKind::Integer(value) => {
+        let ty = self.table.new_var(TyVarKind::Integral);
+        self.literals.push((*value as i128, ty.clone(), span.clone()));
+        self.unify_at(expected, &ty, span);
+      }
+      PatternKind::Boolean(_) => self.unify_at(expected, &Ty::bool(), span),
+      PatternKind::Variant(path, fields) => {
+        self.check_variant_pattern(pattern, path, fields, expected)
+      }
+    }
+  }
+
+  /// The pattern of the variant, such as `Optional::Value(x)`, whose generic arguments are left to
+  /// the inference, and whose fields are matched by the patterns in the parentheses.
+  fn check_variant_pattern(
+    &mut self,
+    pattern: &Pattern,
+    path: &Path,
+    fields: &[Pattern],
+    expected: &Ty,
+  ) {
+    let span = pattern.span.clone();
+    let (def, index) = match self.resolve_variant(path) {
+      Some(variant) => variant,
+      None => {
+        self.error(format!("cannot find variant `{}`", path), span);
+        fields.iter().for_each(|field| self.check_pattern(field, &Ty::Error));
+        return;
+      }
+    };
+    let info = &self.items.enums[&def];
+    let generics = info.generics.clone();
+    let adt = Ty::Adt(
+      def.clone(),
+      generics.names().into_iter().map(Ty::Param).collect(),
+    );
+    let constructor = Ty::Function(info.variants[index].fields.clone(), Box::new(adt));
+    let (types, ty) = match self.instantiate(&generics, &constructor).0 {
+      Ty::Function(types, ty) => (types, *ty),
+      _ => unreachable!("constructors of variants are functions"),
+    };
+    self.unify_at(expected, &ty, span.clone());
+    self.results.variants.insert(pattern.id, index);
+    if fields.len() != types.len() {
+      self.error(
+        format!(
+          "this variant has {} field{} but the pattern has {}",
+          types.len(),
+          if types.len() == 1 { "" } else { "s" },
+          fields.len(),
+        ),
+        span,
+      );
+      fields.iter().for_each(|field| self.check_pattern(field, &Ty::Error));
+      return;
+    }
+    for (field, ty) in fields.iter().zip(types.iter()) {
+      self.check_pattern(field, ty);
+    }
+  }
+
+  /// Report the values which none of the arms matches, and the arms which are never reached.
+  fn check_exhaustive(&mut self, expr: &Expression, arms: &[MatchArm]) {
+    let rows = arms.iter().map(|arm| vec![self.pat(&arm.pattern)]).collect::<Vec<_>>();
+    let usefulness = Usefulness::new(&self.items.enums);
+    for (index, row) in rows.iter().enumerate() {
+      if !usefulness.is_useful(&rows[..index], row) {
+        let span = arms[index].pattern.span.clone();
+        self.diagnostics.emit(Diagnostic::warning("unreachable pattern", span));
+      }
+    }
+    if let Some(missing) = usefulness.missing(&rows, 1) {
+      let missing = usefulness.display(&missing[0]);
+      self.diagnostics.emit(Diagnostic::error(
+        format!("non-exhaustive patterns: `{}` not covered", missing),
+        expr.span.clone(),
+      ));
+    }
+  }
+
+  fn pat(&self, pattern: &Pattern) -> Pat {
+    match &pattern.kind {
+      PatternKind::Wildcard | PatternKind::Binding(_) => Pat::Wild,
+      PatternKind::Integer(value) => Pat::Int(*value),
+      PatternKind::Boolean(value) => Pat::Bool(*value),
+      PatternKind::Variant(path, fields) => {
+        let (def, index) =
+          self.resolve_variant(path).expect("variants are resolved by the checking");
+        Pat::Variant(
+          def,
+          index,
+          fields.iter().map(|field| self.pat(field)).collect(),
+        )
+      }
+    }
+  }
+
+  /// The `?` operator, which unwraps the value of `Optional` or `Expected`, or returns the residual
+  /// from the function early. The function must return the same lang item as the operand.
+  fn infer_try(&mut self, expr: &Expression, operand: &Expression) -> Ty {
+    let span = expr.span.clone();
+    let operand_ty = self.infer_expr(operand);
+    let operand_ty = self.table.resolve(&operand_ty);
+    let (item, args) = match self.items.lang.of_type(&operand_ty) {
+      Some((item, args)) => (item, args.to_vec()),
+      None => {
+        match operand_ty {
+          Ty::Error => {}
+          Ty::Var(_, TyVarKind::General) => {
+            self.error("type annotations needed", operand.span.clone())
+          }
+          ty => self.error(
+            format!(
+              "the `?` operator can only be applied to values of type `Optional` or `Expected`, \
+               found `{}`",
+              ty
+            ),
+            span,
+          ),
+        }
+        return Ty::Error;
+      }
+    };
+    let return_type = self.table.resolve(&self.return_type);
+    match self.items.lang.of_type(&return_type) {
+      Some((LangItem::Expected, returned)) if item == LangItem::Expected => {
+        let returned = returned[1].clone();
+        self.convert_error(expr, &args[1], &returned, span);
+      }
+      Some((returned, _)) if returned == item => {}
+      _ if return_type.is_error() => {}
+      _ => {
+        self.diagnostics.emit(
+          Diagnostic::error(
+            format!(
+              "the `?` operator cannot be applied to `{}` in a function that returns `{}`",
+              operand_ty, return_type
+            ),
+            span,
+          )
+          .with_note(format!(
+            "the `?` operator on `{}` requires the function to return `{}`",
+            item.name(),
+            item.name()
+          )),
+        );
+      }
+    }
+    args[0].clone()
+  }
+
+  /// Type of `.await`, i.e. the output of the future. Futures are the state machines of
+  /// `async func`, and the types implementing the lang item `Future`, including the generic
+  /// parameters bounded by it.
+  fn infer_await(&mut self, expr: &Expression, operand: &Expression) -> Ty {
+    let operand_ty = self.infer_expr(operand);
+    let operand_ty = self.table.resolve(&operand_ty);
+    if !self.asyncness.is_async() {
+      self.error(
+        "`await` is only allowed inside `async` functions",
+        expr.span.clone(),
+      );
+      return Ty::Error;
+    }
+    let future = self.items.lang.get(LangItem::Future).cloned();
+    let poll = || "poll".to_owned();
+    match operand_ty {
+      Ty::Error => Ty::Error,
+      // The state machine is polled by its own `poll`, which the monomorphization resolves.
+      Ty::Coroutine(def, args) => match future {
+        Some(trait_) => {
+          let callee = MethodCallee::Bound {
+            trait_,
+            name: poll(),
+          };
+          self.results.method_callees.insert(expr.id, callee);
+          self.items.coroutine_output(&def, &args)
+        }
+        None => Ty::Error,
+      },
+      Ty::Var(_, TyVarKind::General) => {
+        self.error("type annotations needed", operand.span.clone());
+        Ty::Error
+      }
+      Ty::Param(param)
+        if future.as_ref().map_or(false, |f| self.generics.bounds(&param).contains(f)) =>
+      {
+        let trait_ = future.unwrap();
+        self.uses.push(ItemUse::BoundMethod {
+          trait_:  trait_.clone(),
+          name:    poll(),
+          self_ty: Type::named(Path::from(param.as_str())),
+        });
+        let callee = MethodCallee::Bound {
+          trait_,
+          name: poll(),
+        };
+        self.results.method_callees.insert(expr.id, callee);
+        Ty::Param(format!("{}::Output", param))
+      }
+      ty => {
+        let found = future.as_ref().and_then(|future| self.items.find_impl(future, &ty));
+        match found {
+          Some(info) => {
+            let output = info.associated_types["Output"].clone();
+            let path = MethodPath {
+              impl_id: info.id,
+              name:    poll(),
+            };
+            self.results.method_callees.insert(expr.id, MethodCallee::Static(path));
+            output
+          }
+          None => {
+            self.diagnostics.emit(
+              Diagnostic::error(format!("`{}` is not a future", ty), operand.span.clone())
+                .with_note("`.await` requires the type to implement the lang item `Future`"),
+            );
+            Ty::Error
+          }
+        }
+      }
+    }
+  }
+
+  /// Convert the error of `Expected` for the `?` operator into the error which the function
+  /// returns. Unless both errors are known to be different, they are unified. Otherwise the error
+  /// is converted by the implementation of the `From` lang item for the returned error, whose
+  /// `Source` is the error of the operand.
+  fn convert_error(&mut self, expr: &Expression, found: &Ty, expected: &Ty, span: Span) {
+    let found = self.table.resolve(found);
+    let expected = self.table.resolve(expected);
+    if found == expected || !found.is_known() || !expected.is_known() {
+      self.unify_at(&expected, &found, span);
+      return;
+    }
+    if found.is_error() || expected.is_error() {
+      return;
+    }
+    let from = self.items.lang.get(LangItem::From).cloned();
+    let conversion = from.as_ref().and_then(|from| {
+      self
+        .items
+        .find_impl(from, &expected)
+        .filter(|i| i.associated_types.get("Source") == Some(&found))
+    });
+    if let Some(info) = conversion {
+      let path = MethodPath {
+        impl_id: info.id,
+        name:    "from".to_owned(),
+      };
+      self.results.conversions.insert(expr.id, path);
+      return;
+    }
+    let note = match from {
+      Some(from) => format!(
+        "implement `{}` for `{}` with `type Source = {};` to convert the error",
+        from, expected, found
+      ),
+      None => "the lang item `From` to convert errors is not defined".to_owned(),
+    };
+    self.diagnostics.emit(
+      Diagnostic::error(
+        format!(
+          "`?` couldn't convert the error from `{}` to `{}`",
+          found, expected
+        ),
+        span,
+      )
+      .with_note(note),
+    );
+  }
+
+  fn infer_call(&mut self, callee: &Expression, args: &[Expression], span: Span) -> Ty {
+    self.callee = Some(callee.id);
+    let callee_ty = self.infer_expr(callee);
+    let variadic = match self.results.functions.get(&callee.id) {
+      Some(def) => self.items.functions[def].variadic,
+      None => false,
+    };
+    match self.table.shallow_resolve(&callee_ty) {
+      Ty::Function(params, ret) if variadic => {
+        self.check_variadic_arguments(&params, args, span);
+        *ret
+      }
+      ty @ Ty::Function(..) => self.check_arguments(&ty, args, span),
+      Ty::Error => {
+        args.iter().for_each(|arg| {
+          self.infer_expr(arg);
+        });
+        Ty::Error
+      }
+      ty => {
+        self.error(
+          format!("expected function, found `{}`", ty),
+          callee.span.clone(),
+        );
+        Ty::Error
+      }
+    }
+  }
+
+  /// Check the arguments against the parameters of the function type, returning the return type.
+  fn check_arguments(&mut self, signature: &Ty, args: &[Expression], span: Span) -> Ty {
+    match signature.clone() {
+      Ty::Function(params, ret) => {
+        if params.len() != args.len() {
+          self.error(
+            format!(
+              "this function takes {} argument{} but {} {} supplied",
+              params.len(),
+              if params.len() == 1 { "" } else { "s" },
+              args.len(),
+              if args.len() == 1 { "was" } else { "were" },
+            ),
+            span,
+          );
+          args.iter().for_each(|arg| {
+            self.infer_expr(arg);
+          });
+          return *ret;
+        }
+        for (arg, param) in args.iter().zip(params.iter()) {
+          self.check_expr(arg, param);
+        }
+        *ret
+      }
+      _ => {
+        args.iter().for_each(|arg| {
+          self.infer_expr(arg);
+        });
+        Ty::Error
+      }
+    }
+  }
+
+  /// Check the arguments of the variadic foreign function, which are passed as C does. Integer and
+  /// float literals in the variable arguments are `int32` and `float64`, i.e. `int` and `double` of
+  /// C, and only primitive types and raw pointers could be passed.
+  fn check_variadic_arguments(&mut self, params: &[Ty], args: &[Expression], span: Span) {
+    if args.len() < params.len() {
+      self.error(
+        format!(
+          "this function takes at least {} argument{} but {} {} supplied",
+          params.len(),
+          if params.len() == 1 { "" } else { "s" },
+          args.len(),
+          if args.len() == 1 { "was" } else { "were" },
+        ),
+        span,
+      );
+      args.iter().for_each(|arg| {
+        self.infer_expr(arg);
+      });
+      return;
+    }
+    for (arg, param) in args.iter().zip(params.iter()) {
+      self.check_expr(arg, param);
+    }
+    for arg in &args[params.len()..] {
+      let ty = self.infer_expr(arg);
+      match self.table.shallow_resolve(&ty) {
+        var @ Ty::Var(_, TyVarKind::Integral) => {
+          self.unify_at(&Ty::Primitive(PrimitiveType::Int32), &var, arg.span.clone())
+        }
+        var @ Ty::Var(_, TyVarKind::Float) => self.unify_at(
+          &Ty::Primitive(PrimitiveType::Float64),
+          &var,
+          arg.span.clone(),
+        ),
+        Ty::Var(..) | Ty::Error => {}
+        ty @ (Ty::Primitive(_) | Ty::Pointer(_)) if ty != Ty::unit() => {}
+        ty => self.error(
+          format!("cannot pass `{}` as a variable argument", ty),
+          arg.span.clone(),
+        ),
+      }
+    }
+  }
+
+  /// Unify the types and report the mismatch at the span.
+  fn unify_at(&mut self, expected: &Ty, found: &Ty, span: Span) {
+    if let Err(e) = self.table.unify(expected, found) {
+      self.error(format!("expected {}, found {}", e.expected, e.found), span);
+    }
+  }
+
+  fn lower_type(&mut self, ty: &Type, span: &Span) -> Ty {
+    let scope = TypeScope {
+      module:           &self.module,
+      self_ty:          self.self_ty.as_ref(),
+      associated_types: self.self_ty.as_ref().map(|_| &self.associated),
+      generics:         Some(&self.generics),
+    };
+    self.items.lower_type(ty, scope, span, self.diagnostics)
+  }
+
+  fn declare(&mut self, name: String, ty: Ty) {
+    if let Some(scope) = self.scopes.last_mut() {
+      scope.insert(name, ty);
+    }
+  }
+
+  fn lookup(&self, name: &str) -> Option<Ty> {
+    self.scopes.iter().rev().find_map(|scope| scope.get(name)).cloned()
+  }
+
+  fn error(&mut self, message: impl Into<String>, span: Span) {
+    self.diagnostics.emit(Diagnostic::error(message, span));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use vsp_ast::ast::pattern::PatternKind;
+  use vsp_ast::ast::stmt::Statement;
+  use vsp_ast_parser::lex::DefaultLexer;
+  use vsp_ast_parser::parser::ASTParser;
+  use vsp_ast_parser::parser::TraditionalParser;
+
+  use super::*;
+
+  fn check(source: &str) -> (CompilationUnit, TypeckResults, Vec<String>) {
+    let tokens = DefaultLexer {}.tokenize(source).unwrap();
+    let unit = TraditionalParser {}.parse(tokens).unwrap();
+    let mut diagnostics = DiagnosticEngine::default();
+    let results = TypeChecker::new(&mut diagnostics).check_compilation_unit(&unit);
+    let messages = diagnostics.diagnostics().iter().map(|d| d.to_string()).collect();
+    (unit, results, messages)
+  }
+
+  #[test]
+  fn test_well_typed() {
+    let (_, _, messages) = check(
+      "func add(a: int64, b: int64) -> int64 { return a + b; }
+       func main() -> int64 {
+         let x = 1;
+         var y: bool = x > 0 && true;
+         y = !y;
+         if y { return add(x, 2); }
+         return 0;
+       }",
+    );
+    assert!(messages.is_empty(), "{:?}", messages);
+  }
+
+  #[test]
+  fn test_let_inference() {
+    let (unit, results, messages) = check(
+      "func main() {
+         let x = 1;
+         let y: int16 = x;
+         let z = 1.5;
+       }",
+    );
+    assert!(messages.is_empty(), "{:?}", messages);
+    let stmts = unit.functions["main"].body.as_ref().unwrap().stmts().to_vec();
+    let types = stmts
+      .iter()
+      .map(|stmt| match stmt {
+        Statement::VariableDeclaration(decl) => results.local_type(decl.id).unwrap().to_string(),
+        _ => unreachable!(),
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(types, vec!["int16", "int16", "float64"]);
+  }
+
+  #[test]
+  fn test_mismatch() {
+    let (_, _, messages) = check(
+      "func main() -> int16 {
+         let x: int16 = true;
+         return false;
+       }",
+    );
+    assert_eq!(
+      messages,
+      vec![
+        "2:25: error: expected int16, found bool",
+        "3:17: error: expected int16, found bool",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_call() {
+    let (_, _, messages) = check(
+      "func square(x: int32) -> int32 { return x * x; }
+       func main() {
+         square(1, 2);
+         square(true);
+         let s = \"text\";
+         s(1);
+         unknown();
+       }",
+    );
+    assert_eq!(
+      messages,
+      vec![
+        "3:10: error: this function takes 1 argument but 2 were supplied",
+        "4:17: error: expected int32, found bool",
+        "6:10: error: expected function, found `str`",
+        "7:10: error: cannot find value `unknown` in this scope",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_operands() {
+    let (_, _, messages) = check(
+      "func main() {
+         let a = true + false;
+         let b = 1 && true;
+         let c = -\"text\";
+       }",
+    );
+    assert_eq!(
+      messages,
+      vec![
+        "2:18: error: cannot apply binary operator `+` to type `bool`",
+        "3:18: error: expected bool, found {integer}",
+        "4:18: error: cannot apply unary operator `-` to type `str`",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_integer_widening() {
+    let (unit, results, messages) = check(
+      "func widen(a: int8, b: uint8) -> int64 {
+         let c: int16 = b;
+         let d: uint = b;
+         return a + c;
+       }
+       func narrow(a: int32, b: uint32) {
+         let c: int8 = a;
+         let d: int32 = b;
+         let e: uint64 = a;
+       }",
+    );
+    assert_eq!(
+      messages,
+      vec![
+        "7:24: error: expected int8, found int32",
+        "8:25: error: expected int32, found uint32",
+        "9:26: error: expected uint64, found int32",
+      ]
+    );
+    let stmts = unit.functions["widen"].body.as_ref().unwrap().stmts().to_vec();
+    let Statement::Return(ret) = &stmts[2] else {
+      unreachable!()
+    };
+    let sum = ret.value.as_ref().unwrap();
+    assert_eq!(results.expr_type(sum.id), Some(&Type::int16()));
+    assert_eq!(results.coercion(sum.id), Some(&Type::int64()));
+  }
+
+  #[test]
+  fn test_literal_range() {
+    let (_, _, messages) = check(
+      "func main() {
+         let a: int8 = 127;
+         let b: int8 = -128;
+         let c: int8 = 128;
+         let d: uint8 = -1;
+         let e: uint64 = 0;
+         let f = 3000000000;
+       }",
+    );
+    assert_eq!(
+      messages,
+      vec![
+        "4:24: error: literal out of range for `int8`",
+        "5:25: error: literal out of range for `uint8`",
+        "7:18: error: literal out of range for `int32`",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_cast() {
+    let (_, _, messages) = check(
+      "func main(x: uint16) {
+         let a = x as int8;
+         let b = 2.5 as uint64;
+         let c = true as int32;
+         let d = 65 as char;
+         let e = -x;
+         let f = \"text\" as int32;
+         let g = 1 as bool;
+       }",
+    );
+    assert_eq!(
+      messages,
+      vec![
+        "7:18: error: casting `str` as `int32` is invalid",
+        "8:18: error: casting `{integer}` as `bool` is invalid",
+        "6:18: error: cannot apply unary operator `-` to type `uint16`",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_index() {
+    let (_, _, messages) = check(
+      "func main(i: int32, n: int64) {
+         var values: int64[3];
+         values[i] = 1;
+         let a: int64 = values[2] + [1, 2, 3][n];
+         let b = values[3];
+         let c = n[0];
+         let d = [1, true];
+         let e = values[1.5];
+       }",
+    );
+    assert_eq!(
+      messages,
+      vec![
+        "5:18: error: index out of bounds: the length is 3 but the index is 3",
+        "6:18: error: cannot index into a value of type `int64`",
+        "7:22: error: expected {integer}, found bool",
+        "8:25: error: expected int64, found {float}",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_consts() {
+    let (_, _, messages) = check(
+      "const SIZE: int64 = 4;
+       static NAME: str = \"vsp\";
+       const BYTE: uint8 = 256;
+       const FLAG: bool = SIZE;
+       const ORIGIN: Buffer = 0;
+       const RATIO: double = 1.5;
+       struct Buffer { data: int64[SIZE], ratios: double[RATIO] }
+       func length(buffer: Buffer): int64 { return SIZE * 2; }
+       func main(a: int64[NAME], b: int64[Buffer], c: int64[MISSING]) {
+         SIZE = 1;
+         let name: str = NAME;
+       }",
+    );
+    assert_eq!(
+      messages,
+      vec![
+        "5:8: error: the type of constant `ORIGIN` must be a primitive type, found `Buffer`",
+        "7:43: error: the size of the array must be an integer, found `double`",
+        "9:18: error: expected constant, found static `NAME`",
+        "9:34: error: expected constant, found struct `Buffer`",
+        "9:52: error: cannot find constant `MISSING` in this scope",
+        "3:28: error: literal out of range for `uint8`",
+        "4:27: error: expected bool, found int64",
+        "10:10: error: cannot assign to constant `SIZE`",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_annotations_needed() {
+    let (_, _, messages) = check("func main() { var x; }");
+    assert_eq!(messages, vec!["1:15: error: type annotations needed"]);
+  }
+
+  #[test]
+  fn test_generic_inference() {
+    let (unit, results, messages) = check(
+      "struct Pair<A, B> { first: A, second: B }
+       func first<A, B>(pair: Pair<A, B>): A { return pair.first; }
+       func identity<T>(value: T): T { return value; }
+       func main(): int64 {
+         let pair = Pair { first: 1, second: true };
+         let x: int64 = first(pair);
+         let flag = identity(pair.second);
+         return identity(x);
+       }",
+    );
+    assert!(messages.is_empty(), "{:?}", messages);
+    let stmts = unit.functions["main"].body.as_ref().unwrap().stmts();
+    let callee_args = |index: usize| {
+      let init = match &stmts[index] {
+        Statement::VariableDeclaration(decl) => decl.init.as_ref().unwrap(),
+        _ => unreachable!(),
+      };
+      match &init.kind {
+        ExpressionKind::Call(callee, _) => results.instantiation(callee.id).unwrap().to_vec(),
+        _ => results.instantiation(init.id).unwrap().to_vec(),
+      }
+    };
+    assert_eq!(callee_args(0), vec![Type::int64(), Type::bool()]);
+    assert_eq!(callee_args(1), vec![Type::int64(), Type::bool()]);
+    assert_eq!(callee_args(2), vec![Type::bool()]);
+  }
+
+  #[test]
+  fn test_generic_bounds() {
+    let (_, _, messages) = check(
+      "interface Compare { func compare(other: Self): int32; }
+       interface Show { func show(): int64; }
+       struct Number { value: int64 }
+       impl Compare for Number {
+         func compare(other: Number): int32 { return 0; }
+       }
+       func max<T: Compare>(a: T, b: T): T {
+         if a.compare(b) > 0 { return a; }
+         return b;
+       }
+       func describe<T>(value: T): int64 where T: Compare + Show {
+         max(value, value);
+         value.missing();
+         return value.show();
+       }
+       func main() {
+         max(Number { value: 1 }, Number { value: 2 });
+         max(1, 2);
+         describe(Number { value: 1 });
+       }",
+    );
+    assert_eq!(
+      messages,
+      vec![
+        "13:10: error: no method named `missing` found for type `T`",
+        "18:10: error: the trait bound `int32: Compare` is not satisfied",
+        "19:10: error: the trait bound `Number: Show` is not satisfied",
+      ]
+    );
+  }
+
+  /// Lang items as defined in the standard library.
+  const LANG_ITEMS: &str = "
+    @Lang(Optional) enum Optional<T> { Value(T), None }
+    @Lang(Expected) enum Expected<T, E> { Value(T), Error(E) }
+    @Lang(From) interface From {
+      type Source;
+      static func from(value: Self::Source): Self;
+    }
+  ";
+
+  const FUTURE_LANG_ITEMS: &str = "
+    @Lang(Poll) enum Poll<F, T> { Ready(T), Pending(F) }
+    @Lang(Future) interface Future {
+      type Output;
+      func poll(): Poll<Self, Self::Output>;
+    }
+    @Lang(BlockOn) func block_on<F: Future>(future: F): F::Output;
+  ";
+
+  #[test]
+  fn test_enums() {
+    let (_, results, messages) = check(
+      "enum Shape { Circle(float64), Rect(float64, float64), Empty }
+       enum Either<L, R> { Left(L), Right(R) }
+       func main(): Either<int8, Shape> {
+         let shape = Shape::Rect(1.0, 2.0);
+         let empty: Shape = Shape::Empty;
+         return Either::Left(1);
+       }
+       func wrong() {
+         let a = Shape::Circle;
+         let b = Shape::Rect(1.0);
+         let c: Shape = Shape::Circle(true);
+         let d = Either::Left(1);
+         let e = Shape::Empty.radius;
+         let f = Shape { radius: 1.0 };
+       }",
+    );
+    assert_eq!(
+      messages,
+      vec![
+      "9:18: error: expected value, found tuple variant `Shape::Circle`",
+      "10:18: error: this variant takes 2 fields but 1 was supplied",
+      "11:39: error: expected float64, found bool",
+      "13:18: error: no field `radius` on type `Shape`",
+      "14:18: error: expected struct, found `Shape`",
+      "12:18: error: type annotations needed: cannot infer type for type parameter `R` of `Either`",
+      "12:10: error: type annotations needed",
+    ]
+    );
+    let mut variants = results.variants.values().copied().collect::<Vec<_>>();
+    variants.sort_unstable();
+    assert_eq!(variants, vec![0, 0, 0, 0, 1, 1, 2, 2]);
+  }
+
+  #[test]
+  fn test_discriminants() {
+    let (_, results, messages) = check(
+      "const BASE: int64 = 2;
+       enum Color { Red = BASE * 2, Green, Blue = 9 }
+       enum Shape { Circle(float64) = 1, Empty }
+       enum Flag { On = true, Off = 1.5 }
+       enum Plain { First, Second }
+       func main(): int32 {
+         let color = Color::Green as int32;
+         let plain = Plain::Second as uint8;
+         let shape = Shape::Empty as int64;
+         let float = Color::Red as float64;
+         return color;
+       }",
+    );
+    assert_eq!(messages, vec![
+      "3:21: error: discriminants are only allowed in enums without fields, but `Shape::Circle` has fields",
+      "4:25: error: expected int64, found bool",
+      "4:37: error: expected int64, found {float}",
+      "9:22: error: casting `Shape` as `int64` is invalid",
+      "10:22: error: casting `Color` as `float64` is invalid",
+    ]);
+    let color = DefPath::new(Path::root(), "Color");
+    let discriminants = &results.items().enums[&color].discriminants;
+    assert_eq!(
+      discriminants.iter().map(ToString::to_string).collect::<Vec<_>>(),
+      vec!["Color::Red", "Color::Green", "Color::Blue"]
+    );
+    let plain = DefPath::new(Path::root(), "Plain");
+    assert!(results.items().enums[&plain].discriminants.is_empty());
+  }
+
+  #[test]
+  fn test_try() {
+    let source = format!(
+      "{}
+       struct ParseError {{ position: int64 }}
+       struct AppError {{ code: int64 }}
+       impl From for AppError {{
+         type Source = ParseError;
+         static func from(value: ParseError): AppError {{ return AppError {{ code: 1 }}; }}
+       }}
+       func parse(): Expected<int64, ParseError> {{ return Expected::Value(1); }}
+       func find(): Optional<int64> {{ return Optional::None; }}
+       func twice(): Expected<int64, ParseError> {{ return Expected::Value(parse()? * 2); }}
+       func run(): Expected<int64, AppError> {{ return Expected::Value(parse()? + 1); }}
+       func first(): Optional<int64> {{ return Optional::Value(find()?); }}",
+      LANG_ITEMS
+    );
+    let (unit, results, messages) = check(&source);
+    assert!(messages.is_empty(), "{:?}", messages);
+    let conversions = results.conversions.values().map(|path| &path.name).collect::<Vec<_>>();
+    assert_eq!(conversions, vec!["from"]);
+    assert!(unit.functions["run"].body.is_some());
+
+    let source = format!(
+      "{}
+       struct ParseError {{ position: int64 }}
+       func parse(): Expected<int64, ParseError> {{ return Expected::Value(1); }}
+       func find(): Optional<int64> {{ return Optional::None; }}
+       func plain(): int64 {{ return parse()?; }}
+       func mixed(): Optional<int64> {{ return Optional::Value(parse()?); }}
+       func unconverted(): Expected<int64, str> {{ return Expected::Value(parse()?); }}
+       func value(): Optional<int64> {{ let x = 1; return Optional::Value(x?); }}",
+      LANG_ITEMS
+    );
+    let (_, _, messages) = check(&source);
+    assert_eq!(
+      messages,
+      vec![
+        "12:37: error: the `?` operator cannot be applied to `Expected<int64, ParseError>` in a \
+       function that returns `int64`",
+        "13:63: error: the `?` operator cannot be applied to `Expected<int64, ParseError>` in a \
+       function that returns `Optional<int64>`",
+        "14:74: error: `?` couldn't convert the error from `ParseError` to `str`",
+        "15:74: error: the `?` operator can only be applied to values of type `Optional` or \
+       `Expected`, found `{integer}`",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_match() {
+    let (unit, results, messages) = check(
+      "enum Either<L, R> { Left(L), Right(R) }
+       enum Shape { Circle(int64), Rect(int64, int64), Empty }
+       func area(shape: Shape): int64 {
+         return match shape {
+           Shape::Circle(r) => 3 * r * r,
+           Shape::Rect(w, h) => w * h,
+           Shape::Empty => 0,
+         };
+       }
+       func sign(x: int32, flag: bool): int32 {
+         match flag { true => { return 0; } false => {} }
+         return match x { 0 => 0, -1 => -1, _ => 1 };
+       }
+       func pick(e: Either<bool, int8>): int8 {
+         return match e { Either::Left(true) => 1, Either::Left(false) => 0, Either::Right(v) => v };
+       }",
+    );
+    assert!(messages.is_empty(), "{:?}", messages);
+    let arms = match &unit.functions["area"].body.as_ref().unwrap().stmts()[0] {
+      Statement::Return(stmt) => match &stmt.value.as_ref().unwrap().kind {
+        ExpressionKind::Match(_, arms) => arms.clone(),
+        kind => panic!("unexpected expression: {:?}", kind),
+      },
+      stmt => panic!("unexpected statement: {:?}", stmt),
+    };
+    let bindings = match &arms[1].pattern.kind {
+      PatternKind::Variant(_, fields) => fields.clone(),
+      kind => panic!("unexpected pattern: {:?}", kind),
+    };
+    assert_eq!(results.variant(arms[1].pattern.id), Some(1));
+    assert_eq!(
+      results.local_type(bindings[0].id),
+      Some(&Type::Primitive(PrimitiveType::Int64))
+    );
+
+    let (_, _, messages) = check(
+      "enum Shape { Circle(int64), Rect(int64, int64), Empty }
+       enum Either<L, R> { Left(L), Right(R) }
+       func wrong(shape: Shape, e: Either<bool, int8>, x: uint8) {
+         match shape { Shape::Circle(_) => {} Shape::Empty => {} }
+         match e { Either::Left(true) => {} Either::Right(_) => {} }
+         match x { 0 => {} 1 => {} }
+         match shape { _ => {} Shape::Empty => {} }
+         let y = match x { 0 => 1, _ => true };
+         match shape { Shape::Rect(w) => {} _ => {} }
+         match x { -1 => {} _ => {} }
+         match e { Shape::Empty => {} _ => {} }
+       }",
+    );
+    assert_eq!(
+      messages,
+      vec![
+        "4:10: error: non-exhaustive patterns: `Shape::Rect(_, _)` not covered",
+        "5:10: error: non-exhaustive patterns: `Either::Left(false)` not covered",
+        "6:10: error: non-exhaustive patterns: `_` not covered",
+        "7:32: warning: unreachable pattern",
+        "8:41: error: expected {integer}, found bool",
+        "9:24: error: this variant has 2 fields but the pattern has 1",
+        "11:20: error: expected Either<bool, int8>, found Shape",
+        "10:20: error: literal out of range for `uint8`",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_generic_errors() {
+    let (_, _, messages) = check(
+      "struct Box<T> { value: T }
+       func make<T>(): int64 { return 0; }
+       func add<T>(a: T, b: T): T { return a + b; }
+       func wrong(value: Box): int64 where int64: Copy { return make(); }",
+    );
+    assert_eq!(
+      messages,
+      vec![
+        "4:44: error: cannot find trait `Copy` in this scope",
+        "4:44: error: `where` clauses may only constrain generic parameters, found `int64`",
+        "4:19: error: struct `Box` takes 1 generic argument but 0 were supplied",
+        "3:44: error: cannot apply binary operator `+` to type `T`",
+        "4:65: error: type annotations needed: cannot infer type for type parameter `T` of `make`",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_async() {
+    let source = format!(
+      "{}
+       struct Ready {{ value: int64 }}
+       impl Future for Ready {{
+         type Output = int64;
+         func poll(): Poll<Ready, int64> {{ return Poll::Ready(self.value); }}
+       }}
+       async func fetch(id: int64): int64 {{ return Ready {{ value: id }}.await; }}
+       async func wait<T: Future>(future: T): T::Output {{ return future.await; }}
+       async func total(): int64 {{ return fetch(1).await + wait(fetch(2)).await; }}
+       func main(): int32 {{ let value = block_on(total()); return value as int32; }}",
+      FUTURE_LANG_ITEMS
+    );
+    let (_, results, messages) = check(&source);
+    assert!(messages.is_empty(), "{:?}", messages);
+    let bound = results
+      .method_callees
+      .values()
+      .filter(|callee| matches!(callee, MethodCallee::Bound { name, .. } if name == "poll"));
+    assert_eq!(bound.count(), 3);
+
+    let source = format!(
+      "{}
+       struct Ready {{ value: int64 }}
+       impl Ready {{
+         async func get(): int64 {{ return self.value; }}
+       }}
+       async func fetch(): int64 {{ return 1; }}
+       async func plain<T>(value: T): int64 {{ value.await; return 2.await; }}
+       func sync(): int64 {{ return fetch().await; }}
+       func unawaited(): int64 {{ return fetch(); }}",
+      FUTURE_LANG_ITEMS
+    );
+    let (_, _, messages) = check(&source);
+    assert_eq!(
+      messages,
+      vec![
+        "11:10: error: `async` methods are not supported yet",
+        "14:47: error: `T` is not a future",
+        "14:67: error: `{integer}` is not a future",
+        "15:36: error: `await` is only allowed inside `async` functions",
+        "16:41: error: expected int64, found {async fetch}",
+      ]
+    );
+
+    // The outputs of the futures are known once the body is solved, and so are the casts.
+    let source = format!(
+      "{}
+       async func fetch(): int64 {{ return 1; }}
+       func main(): int32 {{
+         let valid = block_on(fetch()) as int32;
+         let invalid = block_on(fetch()) as bool;
+         return valid;
+       }}",
+      FUTURE_LANG_ITEMS
+    );
+    let (_, _, messages) = check(&source);
+    assert_eq!(
+      messages,
+      vec!["12:24: error: casting `int64` as `bool` is invalid"]
+    );
+
+    let (_, _, messages) = check("async func fetch(): int64 { return 1; }");
+    assert_eq!(
+      messages,
+      vec!["1:1: error: `async func` requires the lang items `Poll` and `Future`",]
+    );
+  }
+
+  #[test]
+  fn test_unsafe() {
+    let (unit, results, messages) = check(
+      "unsafe func read(p: *int64): int64 { return *p; }
+       func main(): int64 {
+         var values: int64[2];
+         let first = &values as *int64;
+         let second = first + 1;
+         let distance = second - first;
+         let address = (0 as *uint8) as uint64;
+         unsafe {
+           *second = read(first) + distance;
+         }
+         return address as int64;
+       }",
+    );
+    assert!(messages.is_empty(), "{:?}", messages);
+    let stmts = unit.functions["main"].body.as_ref().unwrap().stmts().to_vec();
+    let types = stmts[1..5]
+      .iter()
+      .map(|stmt| match stmt {
+        Statement::VariableDeclaration(decl) => results.local_type(decl.id).unwrap().to_string(),
+        stmt => panic!("unexpected statement: {:?}", stmt),
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(types, vec!["*int64", "*int64", "int64", "uint64"]);
+
+    let (_, _, messages) = check(
+      "unsafe func read(p: *int64): int64 { return *p; }
+       const LIMIT: int64 = 1;
+       struct Cell { value: int64 }
+       impl Cell { unsafe func get(): int64 { return self.value; } }
+       func main(p: *int64): int64 {
+         let f = read;
+         *p = read(p);
+         let q = &LIMIT;
+         let r = &(1 + 2);
+         let s = p * 2;
+         let t = p as int32;
+         return *(p as *bool);
+       }",
+    );
+    assert_eq!(
+      messages,
+      vec![
+        "4:20: error: `unsafe` methods are not supported yet",
+        "6:18: error: use of unsafe function `read` requires an `unsafe` block or function",
+        "7:10: error: dereference of raw pointer requires an `unsafe` block or function",
+        "7:15: error: call to unsafe function `read` requires an `unsafe` block or function",
+        "8:18: error: cannot take the address of constant `LIMIT`",
+        "9:18: error: cannot take the address of a temporary value",
+        "10:18: error: cannot apply binary operator `*` to type `*int64`",
+        "11:18: error: casting `*int64` as `int32` is invalid",
+        "12:17: error: dereference of raw pointer requires an `unsafe` block or function",
+        "12:17: error: expected int64, found bool",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_extern() {
+    let (unit, results, messages) = check(
+      "@Repr(C) struct Pair { first: int64, second: float64, digits: uint8[4] }
+       extern \"C\" func printf(format: str, ...): int32;
+       extern func div(pair: Pair, p: *Pair): Pair;
+       public extern func add(a: int32, b: int32): int32 { return a + b; }
+       func main(): int32 {
+         var pair: Pair;
+         unsafe {
+           printf(\"%d %f %s\", 1, 2.5, \"three\");
+           div(pair, &pair);
+         }
+         return add(1, 2);
+       }",
+    );
+    assert!(messages.is_empty(), "{:?}", messages);
+    let info = &results.items().functions[&DefPath::new(Path::root(), "printf")];
+    assert!(info.unsafety.is_unsafe() && info.variadic);
+    let add = &results.items().functions[&DefPath::new(Path::root(), "add")];
+    assert!(!add.unsafety.is_unsafe());
+    let stmts = unit.functions["main"].body.as_ref().unwrap().stmts().to_vec();
+    let args = match &stmts[1] {
+      Statement::Unsafe(block) => match &block.stmts()[0] {
+        Statement::Expression(Expression {
+          kind: ExpressionKind::Call(_, args),
+          ..
+        }) => args.clone(),
+        stmt => panic!("unexpected statement: {:?}", stmt),
+      },
+      stmt => panic!("unexpected statement: {:?}", stmt),
+    };
+    let types = args
+      .iter()
+      .map(|arg| results.expr_type(arg.id).unwrap().to_string())
+      .collect::<Vec<_>>();
+    assert_eq!(types, vec!["str", "int32", "float64", "str"]);
+
+    let (_, _, messages) = check(
+      "struct Point { x: int64, y: int64 }
+       enum Color { Red, Green }
+       extern \"C\" func printf(format: str, ...): int32;
+       extern func paint(color: Color);
+       func nothing() {}
+       extern func generic<T>(value: T): T;
+       extern const func length(s: str): uint64;
+       impl Point { extern func norm(): int64 { return 0; } }
+       func main() {
+         printf(\"\");
+         let f = printf;
+         unsafe {
+           printf();
+           printf(\"%d\", Point { x: 1, y: 2 });
+           printf(\"%d\", nothing());
+         }
+       }",
+    );
+    assert_eq!(
+      messages,
+      vec![
+        "6:8: error: `extern` functions cannot be generic",
+        "7:8: error: foreign functions cannot be `const`",
+        "8:21: error: `extern` methods are not supported",
+        "4:8: error: `extern` function `paint` uses type `Color`, which is not FFI-safe",
+        "10:10: error: call to unsafe function `printf` requires an `unsafe` block or function",
+        "11:18: error: use of unsafe function `printf` requires an `unsafe` block or function",
+        "11:18: error: variadic function `printf` could only be called directly",
+        "13:12: error: this function takes at least 1 argument but 0 were supplied",
+        "14:25: error: cannot pass `Point` as a variable argument",
+        "15:25: error: cannot pass `unit` as a variable argument",
+      ]
+    );
+
+    let (_, _, messages) = check(
+      "struct Point { x: int64, y: int64 }
+       @Repr(Packed) struct Packed { x: int8 }
+       extern func area(p: *Point): int64;
+       extern func norm(p: Point): int64;
+       @Repr(C) struct Wrapper<T> { value: T }
+       @Repr(C) struct Shape { color: Color, next: *Shape }
+       enum Color { Red, Green }
+       extern func missing(p: Missing): Unknown;",
+    );
+    assert_eq!(
+      messages,
+      vec![
+        "2:8: error: unsupported representation, expected `@Repr(C)`",
+        "5:8: error: `@Repr(C)` structs cannot be generic",
+        "8:28: error: cannot find type `Missing` in this scope",
+        "8:8: error: cannot find type `Unknown` in this scope",
+        "6:8: error: field `color` of `@Repr(C)` struct `Shape` has type `Color`, which is not FFI-safe",
+        "4:8: error: `extern` function `norm` uses type `Point`, which is not FFI-safe",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_loops() {
+    let (unit, results, messages) = check(
+      "func main(n: int32): int32 {
+         var sum = 0;
+         'outer: for i in 0..n {
+           loop {
+             if i > 2 { break 'outer; }
+             continue 'outer;
+           }
+         }
+         while sum < 10 { sum = sum + 1; break; }
+         return if sum > 5 && n > 0 { sum } else { 0 };
+       }",
+    );
+    assert!(messages.is_empty(), "{:?}", messages);
+    let stmts = unit.functions["main"].body.as_ref().unwrap().stmts().to_vec();
+    match &stmts[1] {
+      Statement::For(stmt) => assert_eq!(results.local_type(stmt.id).unwrap().to_string(), "int32"),
+      stmt => panic!("unexpected statement: {:?}", stmt),
+    }
+
+    let (_, _, messages) = check(
+      "func main(n: int64): int64 {
+         break;
+         while true { continue 'outer; }
+         for i in 0..true {}
+         for x in 0..1.5 {}
+         return if n { 1 } else { false };
+       }",
+    );
+    assert_eq!(
+      messages,
+      vec![
+        "2:10: error: `break` outside of a loop",
+        "3:23: error: use of undeclared label `'outer`",
+        "4:22: error: expected {integer}, found bool",
+        "5:22: error: expected {integer}, found {float}",
+        "6:20: error: expected bool, found int64",
+        "6:35: error: expected {integer}, found bool",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_iterators() {
+    let (unit, results, messages) = check(
+      "@Lang(Iterator) interface Iterator {
+         type Entry;
+         func has_next(): bool;
+         func next(): Self::Entry;
+       }
+       struct Counter { next: int64, end: int64 }
+       impl Iterator for *Counter {
+         type Entry = int64;
+         func has_next(): bool { unsafe { return (*self).next < (*self).end; } }
+         func next(): int64 { unsafe { (*self).next = (*self).next + 1; return (*self).next; } }
+       }
+       func sum<I: Iterator>(iter: I): int64 {
+         for x in iter {}
+         return 0;
+       }
+       func main(): int64 {
+         var counter = Counter { next: 0, end: 3 };
+         for x in &counter {}
+         return 0;
+       }",
+    );
+    assert!(messages.is_empty(), "{:?}", messages);
+    let entry = |name: &str| {
+      let stmts = unit.functions[name].body.as_ref().unwrap().stmts().to_vec();
+      match stmts.iter().find(|stmt| matches!(stmt, Statement::For(_))) {
+        Some(Statement::For(stmt)) => results.local_type(stmt.id).unwrap().to_string(),
+        stmt => panic!("unexpected statement: {:?}", stmt),
+      }
+    };
+    assert_eq!(entry("sum"), "I::Entry");
+    assert_eq!(entry("main"), "int64");
+
+    let (_, _, messages) = check(
+      "@Lang(Iterator) interface Iterator {
+         type Entry;
+         func has_next(): bool;
+         func next(): Self::Entry;
+       }
+       func main(n: int64): int64 {
+         for x in n {}
+         return 0;
+       }",
+    );
+    assert_eq!(messages, vec!["7:19: error: `int64` is not an iterator"]);
+  }
+}
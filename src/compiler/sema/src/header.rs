@@ -0,0 +1,266 @@
+//! C header of the package
+//!
+//! Public `extern "C"` functions with bodies are exported by their names, so that C could call them
+//! with the header declaring them and all `@Repr(C)` structs. Types are mapped to the C types of
+//! the same size and alignment, as the code generation lowers them:
+//!
+//! ```plaintext
+//! bool             bool
+//! int8 .. uint64   int8_t .. uint64_t
+//! float64          double
+//! char             uint32_t
+//! str              const char *
+//! *T               T *, or void * if T has no C type
+//! unit             void, as the return type
+//! ```
+//!
+//! Structs are declared ahead by `typedef`, and defined after the structs of their fields.
+use std::collections::HashSet;
+
+use vsp_ast::ast::modifier::Accessibility;
+use vsp_ast::ast::types::PrimitiveType;
+use vsp_ast::ast::CompilationUnit;
+
+use crate::check::TypeckResults;
+use crate::items::sorted;
+use crate::items::ItemTable;
+use crate::resolve::collect_modules;
+use crate::resolve::DefPath;
+use crate::ty::Ty;
+
+/// Generate the header of the package checked without errors, where the name is used for the
+/// include guard.
+pub fn generate_c_header(unit: &CompilationUnit, results: &TypeckResults, name: &str) -> String {
+  let items = results.items();
+  let mut structs = vec![];
+  let mut functions = vec![];
+  for module in collect_modules(unit) {
+    for unit in &module.units {
+      for definition in sorted(unit.structs.values(), |s| &s.span) {
+        let def = DefPath::new(module.path.clone(), definition.name.as_str());
+        if items.structs[&def].repr_c {
+          structs.push(def);
+        }
+      }
+      for function in sorted(unit.functions.values(), |f| &f.span) {
+        let signature = &function.signature;
+        if signature.accessibility == Accessibility::Public
+          && signature.abi.is_c()
+          && function.body.is_some()
+        {
+          let def = DefPath::new(module.path.clone(), function.name.as_str());
+          let names = signature.parameters.iter().map(|p| p.name.as_str()).collect::<Vec<_>>();
+          functions.push((def, names));
+        }
+      }
+    }
+  }
+
+  let guard = guard_name(name);
+  let mut header = String::new();
+  header.push_str("/* Generated by vsp. Do not edit. */\n");
+  header.push_str(&format!("#ifndef {}\n#define {}\n\n", guard, guard));
+  header.push_str("#include <stdbool.h>\n#include <stdint.h>\n\n");
+  header.push_str("#ifdef __cplusplus\nextern \"C\" {\n#endif\n");
+
+  if !structs.is_empty() {
+    header.push('\n');
+  }
+  for def in &structs {
+    header.push_str(&format!("typedef struct {} {};\n", def.name, def.name));
+  }
+  let mut defined = HashSet::new();
+  for def in &structs {
+    define_struct(items, def, &mut defined, &mut header);
+  }
+
+  if !functions.is_empty() {
+    header.push('\n');
+  }
+  for (def, names) in &functions {
+    let (params, ret) = items.functions[def].signature();
+    let params = params
+      .iter()
+      .zip(names)
+      .map(|(ty, name)| declarator(items, ty, name))
+      .collect::<Vec<_>>();
+    let params = match params.is_empty() {
+      true => "void".to_owned(),
+      false => params.join(", "),
+    };
+    let ret = match ret {
+      Ty::Primitive(PrimitiveType::Unit) => "void".to_owned(),
+      ret => c_type(items, ret).expect("the return type is FFI-safe"),
+    };
+    header.push_str(&format!("{} {}({});\n", ret, def.name, params));
+  }
+
+  header.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n");
+  header.push_str(&format!("#endif /* {} */\n", guard));
+  header
+}
+
+/// Define the struct after the structs of its fields, which must be complete types in C.
+fn define_struct(
+  items: &ItemTable,
+  def: &DefPath,
+  defined: &mut HashSet<DefPath>,
+  header: &mut String,
+) {
+  if !defined.insert(def.clone()) {
+    return;
+  }
+  let info = &items.structs[def];
+  for field in &info.fields {
+    let ty = match &field.ty {
+      Ty::Array(element, _) => element,
+      ty => ty,
+    };
+    if let Ty::Adt(field_def, _) = ty {
+      define_struct(items, field_def, defined, header);
+    }
+  }
+  header.push_str(&format!("\nstruct {} {{\n", def.name));
+  for field in &info.fields {
+    header.push_str(&format!(
+      "  {};\n",
+      declarator(items, &field.ty, &field.name)
+    ));
+  }
+  header.push_str("};\n");
+}
+
+/// Declaration of the name with the type, where arrays are only fields of structs.
+fn declarator(items: &ItemTable, ty: &Ty, name: &str) -> String {
+  match ty {
+    Ty::Array(element, size) => format!("{}[{}]", declarator(items, element, name), size),
+    ty => {
+      let ty = c_type(items, ty).expect("the type is FFI-safe");
+      match ty.ends_with('*') {
+        true => format!("{}{}", ty, name),
+        false => format!("{} {}", ty, name),
+      }
+    }
+  }
+}
+
+/// C type of the FFI-safe type, or `None` if the type could not be named in C.
+fn c_type(items: &ItemTable, ty: &Ty) -> Option<String> {
+  let name = match ty {
+    Ty::Primitive(primitive) => match primitive {
+      PrimitiveType::Unit => return None,
+      PrimitiveType::Bool => "bool",
+      PrimitiveType::Int8 => "int8_t",
+      PrimitiveType::Int16 => "int16_t",
+      PrimitiveType::Int32 => "int32_t",
+      PrimitiveType::Int64 => "int64_t",
+      PrimitiveType::Uint8 => "uint8_t",
+      PrimitiveType::Uint16 => "uint16_t",
+      PrimitiveType::Uint32 | PrimitiveType::Char => "uint32_t",
+      PrimitiveType::Uint64 => "uint64_t",
+      PrimitiveType::Float64 | PrimitiveType::Double64 => "double",
+      PrimitiveType::Str => "const char *",
+    },
+    Ty::Pointer(pointee) => {
+      let pointee = c_type(items, pointee).unwrap_or_else(|| "void".to_owned());
+      return Some(match pointee.ends_with('*') {
+        true => format!("{}*", pointee),
+        false => format!("{} *", pointee),
+      });
+    }
+    Ty::Adt(def, _) if items.structs.get(def).map_or(false, |info| info.repr_c) => &def.name,
+    _ => return None,
+  };
+  Some(name.to_owned())
+}
+
+/// Name of the include guard, such as `VSP_MATH_H` for `math`.
+fn guard_name(name: &str) -> String {
+  let name = name
+    .chars()
+    .map(|c| match c.is_ascii_alphanumeric() {
+      true => c.to_ascii_uppercase(),
+      false => '_',
+    })
+    .collect::<String>();
+  format!("VSP_{}_H", name)
+}
+
+#[cfg(test)]
+mod tests {
+  use vsp_ast_parser::lex::DefaultLexer;
+  use vsp_ast_parser::parser::ASTParser;
+  use vsp_ast_parser::parser::TraditionalParser;
+  use vsp_diag::DiagnosticEngine;
+
+  use super::*;
+
+  #[test]
+  fn test_header() {
+    let tokens = DefaultLexer {}
+      .tokenize(
+        "@Repr(C) struct Segment { from: Point, to: Point, next: *Segment }
+         @Repr(C) public struct Point { x: float64, y: float64 }
+         @Repr(C) struct Name { bytes: uint8[16], length: uint8 }
+         struct Opaque { value: int64 }
+         public extern \"C\" func length(segment: *Segment): float64 { return 0.0; }
+         public extern \"C\" func origin(): Point { return Point { x: 0.0, y: 0.0 }; }
+         public extern \"C\" func rename(name: Name, label: str, data: *Opaque, ok: bool) {}
+         public extern \"C\" func count(values: **int32, n: uint64, c: char): int8 { return 0; }
+         extern \"C\" func private_add(a: int32, b: int32): int32 { return a + b; }
+         public func vsp_add(a: int32, b: int32): int32 { return a + b; }
+         extern \"C\" func puts(s: str): int32;",
+      )
+      .unwrap();
+    let unit = TraditionalParser {}.parse(tokens).unwrap();
+    let mut diagnostics = DiagnosticEngine::default();
+    let results = crate::check_compilation_unit(&unit, &mut diagnostics);
+    assert!(!diagnostics.has_errors(), "{:?}", diagnostics.diagnostics());
+    assert_eq!(
+      generate_c_header(&unit, &results, "geometry-2d"),
+      "\
+/* Generated by vsp. Do not edit. */
+#ifndef VSP_GEOMETRY_2D_H
+#define VSP_GEOMETRY_2D_H
+
+#include <stdbool.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern \"C\" {
+#endif
+
+typedef struct Segment Segment;
+typedef struct Point Point;
+typedef struct Name Name;
+
+struct Point {
+  double x;
+  double y;
+};
+
+struct Segment {
+  Point from;
+  Point to;
+  Segment *next;
+};
+
+struct Name {
+  uint8_t bytes[16];
+  uint8_t length;
+};
+
+double length(Segment *segment);
+Point origin(void);
+void rename(Name name, const char *label, void *data, bool ok);
+int8_t count(int32_t **values, uint64_t n, uint32_t c);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* VSP_GEOMETRY_2D_H */
+"
+    );
+  }
+}
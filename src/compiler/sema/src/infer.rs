@@ -0,0 +1,213 @@
+//! Unification-based local type inference.
+//!
+//! Each unknown type, such as the type of `let` binding without annotation or of an integer
+//! literal, is represented as a type variable. Constraints between types are solved eagerly by
+//! unification, and the type variables are substituted by the solved types at the end.
+use vsp_ast::ast::types::PrimitiveType;
+
+use crate::ty::Ty;
+use crate::ty::TyVarKind;
+use crate::ty::TyVid;
+
+/// Error of the unification, carrying the two types which failed to be unified.
+#[derive(Clone, Debug)]
+pub struct UnifyError {
+  pub expected: Ty,
+  pub found:    Ty,
+}
+
+/// Table of type variables, which is a union-find structure where each type variable is either
+/// unbound or bound to a type (maybe another type variable).
+#[derive(Default)]
+pub struct InferenceTable {
+  values: Vec<(TyVarKind, Option<Ty>)>,
+}
+
+impl InferenceTable {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Create a new unbound type variable.
+  pub fn new_var(&mut self, kind: TyVarKind) -> Ty {
+    let vid = TyVid(self.values.len() as u32);
+    self.values.push((kind, None));
+    Ty::Var(vid, kind)
+  }
+
+  /// Resolve the type variable on the top level only.
+  pub fn shallow_resolve(&self, ty: &Ty) -> Ty {
+    let mut ty = ty.clone();
+    while let Ty::Var(vid, _) = ty {
+      match &self.values[vid.0 as usize] {
+        (_, Some(bound)) => ty = bound.clone(),
+        (kind, None) => return Ty::Var(vid, *kind),
+      }
+    }
+    ty
+  }
+
+  /// Substitute all solved type variables within the type.
+  pub fn resolve(&self, ty: &Ty) -> Ty {
+    match self.shallow_resolve(ty) {
+      Ty::Array(element, size) => Ty::Array(Box::new(self.resolve(&element)), size),
+      Ty::ConstArray(element, size) => Ty::ConstArray(Box::new(self.resolve(&element)), size),
+      Ty::Pointer(pointee) => Ty::Pointer(Box::new(self.resolve(&pointee))),
+      Ty::Function(params, ret) => Ty::Function(
+        params.iter().map(|p| self.resolve(p)).collect(),
+        Box::new(self.resolve(&ret)),
+      ),
+      Ty::Adt(def, args) => Ty::Adt(def, args.iter().map(|a| self.resolve(a)).collect()),
+      Ty::Coroutine(def, args) => {
+        Ty::Coroutine(def, args.iter().map(|a| self.resolve(a)).collect())
+      }
+      ty => ty,
+    }
+  }
+
+  /// Unify two types, where `expected` is the type required by the context and `found` is the
+  /// type actually given.
+  pub fn unify(&mut self, expected: &Ty, found: &Ty) -> Result<(), Box<UnifyError>> {
+    let a = self.shallow_resolve(expected);
+    let b = self.shallow_resolve(found);
+    match (&a, &b) {
+      (Ty::Var(x, _), Ty::Error) | (Ty::Error, Ty::Var(x, _)) => {
+        // Poison the variable so that no further error is reported about it.
+        self.bind(*x, Ty::Error);
+        Ok(())
+      }
+      (Ty::Error, _) | (_, Ty::Error) => Ok(()),
+      (Ty::Var(x, _), Ty::Var(y, _)) if x == y => Ok(()),
+      (Ty::Var(x, kx), Ty::Var(y, ky)) => {
+        // Bind the more general one to the more specific one.
+        match (kx, ky) {
+          (TyVarKind::General, _) => self.bind(*x, b.clone()),
+          (_, TyVarKind::General) => self.bind(*y, a.clone()),
+          _ if kx == ky => self.bind(*x, b.clone()),
+          _ => return Err(self.error(expected, found)),
+        }
+        Ok(())
+      }
+      (Ty::Var(x, kind), ty) | (ty, Ty::Var(x, kind)) => {
+        if !Self::kind_accepts(*kind, ty) || self.occurs(*x, ty) {
+          return Err(self.error(expected, found));
+        }
+        self.bind(*x, ty.clone());
+        Ok(())
+      }
+      (Ty::Primitive(x), Ty::Primitive(y)) if x == y => Ok(()),
+      (Ty::Array(x, m), Ty::Array(y, n)) if m == n => {
+        self.unify(x, y).map_err(|_| self.error(expected, found))
+      }
+      (Ty::ConstArray(x, m), Ty::ConstArray(y, n)) if m == n => {
+        self.unify(x, y).map_err(|_| self.error(expected, found))
+      }
+      (Ty::Pointer(x), Ty::Pointer(y)) => self.unify(x, y).map_err(|_| self.error(expected, found)),
+      (Ty::Function(xs, x), Ty::Function(ys, y)) if xs.len() == ys.len() => {
+        for (x, y) in xs.iter().zip(ys.iter()) {
+          self.unify(x, y).map_err(|_| self.error(expected, found))?;
+        }
+        self.unify(x, y).map_err(|_| self.error(expected, found))
+      }
+      (Ty::Adt(x, xs), Ty::Adt(y, ys)) | (Ty::Coroutine(x, xs), Ty::Coroutine(y, ys))
+        if x == y && xs.len() == ys.len() =>
+      {
+        for (x, y) in xs.iter().zip(ys.iter()) {
+          self.unify(x, y).map_err(|_| self.error(expected, found))?;
+        }
+        Ok(())
+      }
+      (Ty::Dyn(x), Ty::Dyn(y)) if x == y => Ok(()),
+      (Ty::Param(x), Ty::Param(y)) if x == y => Ok(()),
+      (Ty::Opaque(x), Ty::Opaque(y)) if x == y => Ok(()),
+      _ => Err(self.error(expected, found)),
+    }
+  }
+
+  /// Assign default types to the literal type variables which are still unbound: `int32` for
+  /// integers and `float64` for floats.
+  pub fn apply_defaults(&mut self) {
+    for i in 0..self.values.len() {
+      let (kind, bound) = &self.values[i];
+      if bound.is_some() {
+        continue;
+      }
+      let default = match kind {
+        TyVarKind::General => continue,
+        TyVarKind::Integral => PrimitiveType::Int32,
+        TyVarKind::Float => PrimitiveType::Float64,
+      };
+      self.values[i].1 = Some(Ty::Primitive(default));
+    }
+  }
+
+  fn error(&self, expected: &Ty, found: &Ty) -> Box<UnifyError> {
+    Box::new(UnifyError {
+      expected: self.resolve(expected),
+      found:    self.resolve(found),
+    })
+  }
+
+  fn bind(&mut self, vid: TyVid, ty: Ty) {
+    self.values[vid.0 as usize].1 = Some(ty);
+  }
+
+  fn kind_accepts(kind: TyVarKind, ty: &Ty) -> bool {
+    match (kind, ty) {
+      (TyVarKind::General, _) => true,
+      (TyVarKind::Integral, Ty::Primitive(primitive)) => primitive.is_integer(),
+      (TyVarKind::Float, Ty::Primitive(primitive)) => primitive.is_float(),
+      _ => false,
+    }
+  }
+
+  /// Occurs check, to avoid binding a type variable to a type containing itself.
+  fn occurs(&self, vid: TyVid, ty: &Ty) -> bool {
+    match self.shallow_resolve(ty) {
+      Ty::Var(other, _) => other == vid,
+      Ty::Array(element, _) | Ty::ConstArray(element, _) | Ty::Pointer(element) => {
+        self.occurs(vid, &element)
+      }
+      Ty::Function(params, ret) => {
+        params.iter().any(|p| self.occurs(vid, p)) || self.occurs(vid, &ret)
+      }
+      Ty::Adt(_, args) | Ty::Coroutine(_, args) => args.iter().any(|a| self.occurs(vid, a)),
+      _ => false,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_unify_vars() {
+    let mut table = InferenceTable::new();
+    let a = table.new_var(TyVarKind::General);
+    let b = table.new_var(TyVarKind::Integral);
+    table.unify(&a, &b).unwrap();
+    table.unify(&a, &Ty::Primitive(PrimitiveType::Int16)).unwrap();
+    assert_eq!(table.resolve(&b), Ty::Primitive(PrimitiveType::Int16));
+    assert!(table.unify(&a, &Ty::bool()).is_err());
+  }
+
+  #[test]
+  fn test_integral_rejects_bool() {
+    let mut table = InferenceTable::new();
+    let a = table.new_var(TyVarKind::Integral);
+    let error = table.unify(&Ty::bool(), &a).unwrap_err();
+    assert_eq!(format!("{}", error.expected), "bool");
+    assert_eq!(format!("{}", error.found), "{integer}");
+  }
+
+  #[test]
+  fn test_defaults() {
+    let mut table = InferenceTable::new();
+    let a = table.new_var(TyVarKind::Integral);
+    let b = table.new_var(TyVarKind::Float);
+    table.apply_defaults();
+    assert_eq!(table.resolve(&a), Ty::Primitive(PrimitiveType::Int32));
+    assert_eq!(table.resolve(&b), Ty::Primitive(PrimitiveType::Float64));
+  }
+}
@@ -0,0 +1,1717 @@
+//! Items of the package, i.e. functions, structs, enums, traits, implementations, constants and
+//! static items, collected after the name resolution.
+//!
+//! Types written in the source codes are lowered here, so are the signatures and the generic
+//! parameters of all functions and methods. Each implementation of a trait is checked against the
+//! trait: all associated types and methods of the trait must be implemented with compatible
+//! signatures, and a trait is implemented at most once for each type.
+use std::collections::HashMap;
+
+use vsp_ast::ast::constant::ConstKind;
+use vsp_ast::ast::function::Abi;
+use vsp_ast::ast::function::Function;
+use vsp_ast::ast::generics::Generics;
+use vsp_ast::ast::interface::ImplDefinition;
+use vsp_ast::ast::interface::TraitDefinition;
+use vsp_ast::ast::modifier::Accessibility;
+use vsp_ast::ast::modifier::Asyncness;
+use vsp_ast::ast::modifier::Constancy;
+use vsp_ast::ast::modifier::Unsafety;
+use vsp_ast::ast::module::Path;
+use vsp_ast::ast::structure::StructDefinition;
+use vsp_ast::ast::types::PrimitiveType;
+use vsp_ast::ast::types::Type;
+use vsp_diag::Diagnostic;
+use vsp_diag::DiagnosticEngine;
+use vsp_span::Span;
+
+use crate::lang::LangItem;
+use crate::lang::LangItems;
+use crate::resolve::is_prelude;
+use crate::resolve::DefKind;
+use crate::resolve::DefPath;
+use crate::resolve::ModuleScopes;
+use crate::resolve::ModuleSource;
+use crate::ty::Ty;
+
+pub struct StructInfo {
+  pub def:      DefPath,
+  pub generics: GenericsInfo,
+  pub fields:   Vec<FieldInfo>,
+  /// Whether the struct is annotated with `@Repr(C)`, which makes it FFI-safe.
+  pub repr_c:   bool,
+}
+
+pub struct EnumInfo {
+  pub def:           DefPath,
+  pub generics:      GenericsInfo,
+  /// Variants in the order of declaration, whose indices tell them apart in the values.
+  pub variants:      Vec<VariantInfo>,
+  /// Constants of the discriminants of the variants, which are only given if any discriminant is
+  /// written explicitly. Otherwise, the discriminants are the indices of the variants.
+  pub discriminants: Vec<DefPath>,
+}
+
+impl EnumInfo {
+  /// Whether none of the variants has fields, so that the values could be cast to integers as
+  /// their discriminants.
+  pub fn is_fieldless(&self) -> bool {
+    self.variants.iter().all(|variant| variant.fields.is_empty())
+  }
+}
+
+pub struct VariantInfo {
+  pub name:   String,
+  pub fields: Vec<Ty>,
+}
+
+pub struct FunctionInfo {
+  pub generics:  GenericsInfo,
+  /// Signature of the function, where generic parameters are [`Ty::Param`]. Calling `async func`
+  /// returns [`Ty::Coroutine`].
+  pub ty:        Ty,
+  /// Type returned by the body, which is the output of the state machine for `async func`.
+  pub output:    Ty,
+  /// Whether the function is `const func`, which could be called in constants.
+  pub constancy: Constancy,
+  pub asyncness: Asyncness,
+  /// Whether the function is `unsafe func`, which could only be used in `unsafe` contexts. Foreign
+  /// functions, i.e. `extern` functions without body, are always unsafe except those of the
+  /// prelude.
+  pub unsafety:  Unsafety,
+  pub abi:       Abi,
+  /// Whether the foreign function takes variable arguments after the parameters.
+  pub variadic:  bool,
+}
+
+impl FunctionInfo {
+  /// Parameters of the function, and the type returned by calling it.
+  pub fn signature(&self) -> (&[Ty], &Ty) {
+    match &self.ty {
+      Ty::Function(params, ret) => (params, ret),
+      ty => unreachable!("expected function type, found `{}`", ty),
+    }
+  }
+}
+
+/// Path to the constant of the discriminant of the variant, which is never in scope of any module.
+pub fn discriminant_def(def: &DefPath, variant: &str) -> DefPath {
+  DefPath::new(def.module.clone(), format!("{}::{}", def.name, variant))
+}
+
+/// Constant or static item, whose type is always a primitive type. Discriminants of enums given
+/// explicitly are `int64` constants too.
+pub struct ConstInfo {
+  pub kind: ConstKind,
+  pub ty:   Ty,
+}
+
+/// Generic parameters of the item, with the bounds from both the parameters and the `where` clause.
+#[derive(Clone, Debug, Default)]
+pub struct GenericsInfo {
+  pub params: Vec<GenericParamInfo>,
+}
+
+#[derive(Clone, Debug)]
+pub struct GenericParamInfo {
+  pub name:   String,
+  pub bounds: Vec<DefPath>,
+}
+
+impl GenericsInfo {
+  pub fn is_empty(&self) -> bool {
+    self.params.is_empty()
+  }
+
+  pub fn names(&self) -> Vec<String> {
+    self.params.iter().map(|p| p.name.to_owned()).collect()
+  }
+
+  pub fn bounds(&self, name: &str) -> &[DefPath] {
+    match self.params.iter().find(|p| p.name == name) {
+      Some(param) => &param.bounds,
+      None => &[],
+    }
+  }
+}
+
+pub struct FieldInfo {
+  pub name:   String,
+  pub ty:     Ty,
+  pub public: bool,
+}
+
+pub struct TraitInfo {
+  pub def:                 DefPath,
+  pub associated_types:    Vec<String>,
+  /// Methods in the order of declaration, which is also the order of slots in the vtable.
+  pub methods:             Vec<TraitMethod>,
+  /// Reason why the trait could not be made into trait objects, if any.
+  pub dyn_incompatibility: Option<String>,
+}
+
+pub struct TraitMethod {
+  pub name:     String,
+  pub receiver: bool,
+  /// Signature of the method excluding the receiver, where `Self` and its associated types, such
+  /// as `Self::Entry`, are [`Ty::Param`] to be substituted.
+  pub ty:       Ty,
+  /// Signature of the method called on trait objects, only if the trait is dyn-compatible.
+  pub dyn_ty:   Option<Ty>,
+}
+
+/// Index of the implementation in the order of collection.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ImplId(pub usize);
+
+pub struct ImplInfo {
+  pub id:               ImplId,
+  /// Module and file where the implementation is written.
+  pub module:           Path,
+  pub file:             String,
+  pub span:             Span,
+  pub trait_:           Option<DefPath>,
+  pub self_ty:          Ty,
+  pub associated_types: HashMap<String, Ty>,
+  pub methods:          HashMap<String, MethodInfo>,
+}
+
+pub struct MethodInfo {
+  pub receiver: bool,
+  /// Signature of the method, excluding the receiver.
+  pub ty:       Ty,
+  pub public:   bool,
+}
+
+/// Path to the method, i.e. the implementation and the name of the method.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct MethodPath {
+  pub impl_id: ImplId,
+  pub name:    String,
+}
+
+/// How the method call is dispatched.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MethodCallee {
+  /// Call the method of the implementation directly.
+  Static(MethodPath),
+  /// Call through the vtable of the trait object, by the index of the method in the trait.
+  Dynamic { trait_: DefPath, index: usize },
+  /// Call the method of the trait bound of a generic parameter. It is dispatched statically once
+  /// the parameter is substituted by the monomorphization.
+  Bound { trait_: DefPath, name: String },
+}
+
+/// Error of the method lookup.
+pub enum LookupError {
+  NotFound,
+  /// Methods with the same name are found in multiple traits.
+  Ambiguous(Vec<DefPath>),
+}
+
+/// Context to lower the types written in the source codes.
+#[derive(Clone, Copy)]
+pub struct TypeScope<'a> {
+  /// Module where the names are resolved.
+  pub module:           &'a Path,
+  /// Type of `Self`, only in traits and impls.
+  pub self_ty:          Option<&'a Ty>,
+  /// Associated types of `Self`, only in impls.
+  pub associated_types: Option<&'a HashMap<String, Ty>>,
+  /// Generic parameters in scope.
+  pub generics:         Option<&'a GenericsInfo>,
+}
+
+impl<'a> TypeScope<'a> {
+  pub fn module(module: &'a Path) -> Self {
+    Self {
+      module,
+      self_ty: None,
+      associated_types: None,
+      generics: None,
+    }
+  }
+}
+
+#[derive(Default)]
+pub struct ItemTable {
+  scopes:        ModuleScopes,
+  pub functions: HashMap<DefPath, FunctionInfo>,
+  pub structs:   HashMap<DefPath, StructInfo>,
+  pub enums:     HashMap<DefPath, EnumInfo>,
+  pub traits:    HashMap<DefPath, TraitInfo>,
+  pub impls:     Vec<ImplInfo>,
+  pub consts:    HashMap<DefPath, ConstInfo>,
+  pub lang:      LangItems,
+}
+
+impl ItemTable {
+  /// Collect all items in the modules, and check the implementations.
+  pub fn collect(
+    modules: &[ModuleSource],
+    scopes: ModuleScopes,
+    diagnostics: &mut DiagnosticEngine,
+  ) -> Self {
+    let mut table = Self {
+      scopes,
+      ..Self::default()
+    };
+
+    // Traits are collected ahead, since whether they could be made into objects is needed to
+    // lower the types.
+    let mut trait_definitions = HashMap::new();
+    for module in modules {
+      for unit in &module.units {
+        for definition in unit.traits.values() {
+          let def = DefPath::new(module.path.clone(), definition.name.as_str());
+          trait_definitions.insert(def.clone(), definition);
+          table.traits.insert(
+            def.clone(),
+            TraitInfo {
+              def,
+              associated_types: definition
+                .associated_types
+                .iter()
+                .map(|t| t.name.to_owned())
+                .collect(),
+              methods: vec![],
+              dyn_incompatibility: dyn_incompatibility(definition),
+            },
+          );
+        }
+      }
+    }
+    // So are the generic parameters of structs and enums, to check the number of generic
+    // arguments.
+    for module in modules {
+      for unit in &module.units {
+        set_file(diagnostics, unit.filename());
+        for definition in sorted(unit.structs.values(), |s| &s.span) {
+          let def = DefPath::new(module.path.clone(), definition.name.as_str());
+          let generics = table.lower_generics(&module.path, &definition.generics, diagnostics);
+          let repr_c = check_repr(definition, diagnostics);
+          table.structs.insert(
+            def.clone(),
+            StructInfo {
+              def,
+              generics,
+              fields: vec![],
+              repr_c,
+            },
+          );
+        }
+        for definition in sorted(unit.enums.values(), |e| &e.span) {
+          let def = DefPath::new(module.path.clone(), definition.name.as_str());
+          let generics = table.lower_generics(&module.path, &definition.generics, diagnostics);
+          table.enums.insert(
+            def.clone(),
+            EnumInfo {
+              def,
+              generics,
+              variants: vec![],
+              discriminants: vec![],
+            },
+          );
+        }
+      }
+    }
+
+    // So are the types of the constants, to check the sizes of arrays.
+    for module in modules {
+      for unit in &module.units {
+        set_file(diagnostics, unit.filename());
+        for definition in sorted(unit.consts.values(), |c| &c.span) {
+          let def = DefPath::new(module.path.clone(), definition.name.as_str());
+          let ty = match &definition.ty {
+            Type::Primitive(primitive) => Ty::Primitive(primitive.clone()),
+            ty => {
+              diagnostics.emit(Diagnostic::error(
+                format!(
+                  "the type of {} `{}` must be a primitive type, found `{}`",
+                  match definition.kind {
+                    ConstKind::Const => "constant",
+                    ConstKind::Static => "static",
+                  },
+                  definition.name,
+                  ty
+                ),
+                definition.span.clone(),
+              ));
+              Ty::Error
+            }
+          };
+          table.consts.insert(
+            def,
+            ConstInfo {
+              kind: definition.kind,
+              ty,
+            },
+          );
+        }
+        // Discriminants written explicitly are constants, and so are the others next to them.
+        for definition in sorted(unit.enums.values(), |e| &e.span) {
+          if definition.variants.iter().all(|variant| variant.discriminant.is_none()) {
+            continue;
+          }
+          if let Some(variant) = definition.variants.iter().find(|v| !v.fields.is_empty()) {
+            diagnostics.emit(Diagnostic::error(
+              format!(
+                "discriminants are only allowed in enums without fields, but `{}::{}` has fields",
+                definition.name, variant.name
+              ),
+              variant.span.clone(),
+            ));
+            continue;
+          }
+          let def = DefPath::new(module.path.clone(), definition.name.as_str());
+          let discriminants = definition
+            .variants
+            .iter()
+            .map(|variant| discriminant_def(&def, &variant.name))
+            .collect::<Vec<_>>();
+          for discriminant in &discriminants {
+            table.consts.insert(
+              discriminant.clone(),
+              ConstInfo {
+                kind: ConstKind::Const,
+                ty:   Ty::Primitive(PrimitiveType::Int64),
+              },
+            );
+          }
+          if let Some(info) = table.enums.get_mut(&def) {
+            info.discriminants = discriminants;
+          }
+        }
+      }
+    }
+
+    for module in modules {
+      for unit in &module.units {
+        set_file(diagnostics, unit.filename());
+        let scope = TypeScope::module(&module.path);
+        for definition in sorted(unit.traits.values(), |t| &t.span) {
+          table.collect_trait_methods(&module.path, definition, diagnostics);
+        }
+        for definition in sorted(unit.structs.values(), |s| &s.span) {
+          let def = DefPath::new(module.path.clone(), definition.name.as_str());
+          let generics = table.structs[&def].generics.clone();
+          let scope = TypeScope {
+            generics: Some(&generics),
+            ..scope
+          };
+          let fields = definition
+            .fields
+            .iter()
+            .map(|field| FieldInfo {
+              name:   field.name.to_owned(),
+              ty:     table.lower_type(&field.ty, scope, &field.span, diagnostics),
+              public: field.visibility == Accessibility::Public,
+            })
+            .collect();
+          if let Some(info) = table.structs.get_mut(&def) {
+            info.fields = fields;
+          }
+        }
+        for definition in sorted(unit.enums.values(), |e| &e.span) {
+          let def = DefPath::new(module.path.clone(), definition.name.as_str());
+          let generics = table.enums[&def].generics.clone();
+          let scope = TypeScope {
+            generics: Some(&generics),
+            ..scope
+          };
+          let variants = definition
+            .variants
+            .iter()
+            .map(|variant| VariantInfo {
+              name:   variant.name.to_owned(),
+              fields: variant
+                .fields
+                .iter()
+                .map(|field| table.lower_type(field, scope, &variant.span, diagnostics))
+                .collect(),
+            })
+            .collect();
+          if let Some(info) = table.enums.get_mut(&def) {
+            info.variants = variants;
+          }
+        }
+        for function in sorted(unit.functions.values(), |f| &f.span) {
+          let generics =
+            table.lower_generics(&module.path, &function.signature.generics, diagnostics);
+          let scope = TypeScope {
+            generics: Some(&generics),
+            ..scope
+          };
+          let ty = table.lower_signature(function, scope, diagnostics);
+          let def = DefPath::new(module.path.clone(), function.name.as_str());
+          let (ty, output) = match ty {
+            Ty::Function(params, ret) if function.signature.asyncness.is_async() => {
+              let args = generics.names().into_iter().map(Ty::Param).collect();
+              let coroutine = Ty::Coroutine(def.clone(), args);
+              (Ty::Function(params, Box::new(coroutine)), *ret)
+            }
+            Ty::Function(params, ret) => (Ty::Function(params, ret.clone()), *ret),
+            ty => unreachable!("expected function type, found `{}`", ty),
+          };
+          let foreign = function.signature.abi.is_c() && function.body.is_none();
+          if function.signature.abi.is_c() {
+            check_extern(function, !generics.is_empty(), diagnostics);
+          }
+          table.functions.insert(
+            def,
+            FunctionInfo {
+              generics,
+              ty,
+              output,
+              constancy: function.signature.constancy,
+              asyncness: function.signature.asyncness,
+              // The foreign functions of the prelude are the output functions of the runtime, which
+              // are safe to call.
+              unsafety: match foreign && !is_prelude(&module.path) {
+                true => Unsafety::Unsafe,
+                false => function.signature.unsafety,
+              },
+              abi: function.signature.abi,
+              variadic: function.signature.variadic,
+            },
+          );
+        }
+      }
+    }
+
+    for module in modules {
+      for unit in &module.units {
+        set_file(diagnostics, unit.filename());
+        for definition in &unit.impls {
+          table.collect_impl(
+            &module.path,
+            unit.filename(),
+            definition,
+            &trait_definitions,
+            diagnostics,
+          );
+        }
+      }
+    }
+    // Signatures of `extern` functions and fields of `@Repr(C)` structs are checked once all
+    // structs are lowered.
+    for module in modules {
+      for unit in &module.units {
+        set_file(diagnostics, unit.filename());
+        for definition in sorted(unit.structs.values(), |s| &s.span) {
+          let def = DefPath::new(module.path.clone(), definition.name.as_str());
+          table.check_repr_c_fields(&def, &definition.span, diagnostics);
+        }
+        for function in sorted(unit.functions.values(), |f| &f.span) {
+          if function.signature.abi.is_c() {
+            let def = DefPath::new(module.path.clone(), function.name.as_str());
+            table.check_ffi_signature(&def, &function.span, diagnostics);
+          }
+        }
+      }
+    }
+    table.lang = LangItems::collect(modules, &table, diagnostics);
+    diagnostics.set_current_file(None);
+    table
+  }
+
+  /// Check that the fields of the `@Repr(C)` struct are FFI-safe, so that it could be declared in
+  /// C.
+  fn check_repr_c_fields(&self, def: &DefPath, span: &Span, diagnostics: &mut DiagnosticEngine) {
+    let info = &self.structs[def];
+    if !info.repr_c {
+      return;
+    }
+    let unsafe_field = info.fields.iter().find(|field| match &field.ty {
+      Ty::Array(element, _) => !self.is_ffi_safe(element, &mut vec![def.clone()]),
+      ty => !self.is_ffi_safe(ty, &mut vec![def.clone()]),
+    });
+    if let Some(field) = unsafe_field {
+      diagnostics.emit(Diagnostic::error(
+        format!(
+          "field `{}` of `@Repr(C)` struct `{}` has type `{}`, which is not FFI-safe",
+          field.name, def.name, field.ty
+        ),
+        span.clone(),
+      ));
+    }
+  }
+
+  /// Check that the parameters and the return type of the `extern` function are FFI-safe.
+  fn check_ffi_signature(&self, def: &DefPath, span: &Span, diagnostics: &mut DiagnosticEngine) {
+    let info = &self.functions[def];
+    if !info.generics.is_empty() || info.asyncness.is_async() {
+      return;
+    }
+    let (params, ret) = info.signature();
+    let unsafe_ty = params
+      .iter()
+      .find(|param| **param == Ty::unit() || !self.is_ffi_safe(param, &mut vec![]))
+      .or_else(|| Some(ret).filter(|ret| !self.is_ffi_safe(ret, &mut vec![])));
+    if let Some(ty) = unsafe_ty {
+      let mut diagnostic = Diagnostic::error(
+        format!(
+          "`extern` function `{}` uses type `{}`, which is not FFI-safe",
+          def.name, ty
+        ),
+        span.clone(),
+      );
+      if let Ty::Adt(def, _) = ty {
+        if self.structs.get(def).map_or(false, |info| !info.repr_c) {
+          diagnostic = diagnostic.with_note(format!(
+            "consider annotating the struct `{}` with `@Repr(C)`",
+            def.name
+          ));
+        }
+      }
+      diagnostics.emit(diagnostic);
+    }
+  }
+
+  /// Whether the values of the type could be passed to and returned from C functions, i.e.
+  /// primitive types, raw pointers, and non-generic `@Repr(C)` structs of them. Arrays are only
+  /// allowed as fields, since C passes arrays by pointers. Erroneous types have been reported, so
+  /// they are accepted to avoid cascading errors.
+  pub fn is_ffi_safe(&self, ty: &Ty, visiting: &mut Vec<DefPath>) -> bool {
+    match ty {
+      Ty::Primitive(_) | Ty::Pointer(_) | Ty::Error => true,
+      Ty::Adt(def, args) if args.is_empty() && !visiting.contains(def) => {
+        let info = match self.structs.get(def) {
+          Some(info) if info.repr_c => info,
+          _ => return false,
+        };
+        visiting.push(def.clone());
+        let safe = info.fields.iter().all(|field| match &field.ty {
+          Ty::Array(element, _) => self.is_ffi_safe(element, visiting),
+          ty => self.is_ffi_safe(ty, visiting),
+        });
+        visiting.pop();
+        safe
+      }
+      _ => false,
+    }
+  }
+
+  pub fn scopes(&self) -> &ModuleScopes {
+    &self.scopes
+  }
+
+  /// Resolve the path to the item from the module. Paths with a single segment are names in scope
+  /// of the module, and others are absolute from the package root.
+  pub fn resolve_path(&self, module: &Path, path: &Path) -> Option<(DefPath, DefKind)> {
+    let def = match (path.len(), path.parent(), path.name()) {
+      (1, _, Some(name)) => self.scopes.resolve(module, name)?.clone(),
+      (_, Some(parent), Some(name)) => self.scopes.resolve(&parent, name)?.clone(),
+      _ => return None,
+    };
+    let kind = self.scopes.kind(&def)?;
+    Some((def, kind))
+  }
+
+  /// Lower the type written in the source codes. Errors are reported at the span, and the type is
+  /// lowered to the error type.
+  pub fn lower_type(
+    &self,
+    ty: &Type,
+    scope: TypeScope,
+    span: &Span,
+    diagnostics: &mut DiagnosticEngine,
+  ) -> Ty {
+    let error = |diagnostics: &mut DiagnosticEngine, message: String| {
+      diagnostics.emit(Diagnostic::error(message, span.clone()));
+      Ty::Error
+    };
+    match ty {
+      Type::Primitive(primitive) => Ty::Primitive(primitive.clone()),
+      Type::Array(element, size) => Ty::Array(
+        Box::new(self.lower_type(element, scope, span, diagnostics)),
+        *size,
+      ),
+      Type::ConstArray(element, size) => {
+        let element = self.lower_type(element, scope, span, diagnostics);
+        match self.resolve_path(scope.module, size) {
+          Some((def, DefKind::Const)) => match &self.consts[&def].ty {
+            Ty::Primitive(primitive) if primitive.is_integer() => {
+              Ty::ConstArray(Box::new(element), def)
+            }
+            Ty::Error => Ty::Error,
+            ty => error(
+              diagnostics,
+              format!("the size of the array must be an integer, found `{}`", ty),
+            ),
+          },
+          Some((_, kind)) => error(
+            diagnostics,
+            format!("expected constant, found {} `{}`", kind.descr(), size),
+          ),
+          None => error(
+            diagnostics,
+            format!("cannot find constant `{}` in this scope", size),
+          ),
+        }
+      }
+      Type::Function(function) => Ty::Function(
+        function
+          .params
+          .iter()
+          .map(|param| self.lower_type(param, scope, span, diagnostics))
+          .collect(),
+        Box::new(self.lower_type(&function.ret, scope, span, diagnostics)),
+      ),
+      Type::Named(path, args) => {
+        if let Some(ty) = self.lower_param(path, args, scope, span, diagnostics) {
+          return ty;
+        }
+        self.lower_named(path, args, scope, span, diagnostics)
+      }
+      Type::Dyn(path) => match self.resolve_path(scope.module, path) {
+        Some((def, DefKind::Trait)) => match &self.traits[&def].dyn_incompatibility {
+          Some(reason) => {
+            diagnostics.emit(
+              Diagnostic::error(
+                format!("the trait `{}` cannot be made into an object", path),
+                span.clone(),
+              )
+              .with_note(reason.to_owned()),
+            );
+            Ty::Error
+          }
+          None => Ty::Dyn(def),
+        },
+        Some((_, kind)) => error(
+          diagnostics,
+          format!("expected trait, found {} `{}`", kind.descr(), path),
+        ),
+        None => error(
+          diagnostics,
+          format!("cannot find trait `{}` in this scope", path),
+        ),
+      },
+      Type::SelfType => match scope.self_ty {
+        Some(ty) => ty.clone(),
+        None => error(
+          diagnostics,
+          "`Self` is only available in traits and impls".to_string(),
+        ),
+      },
+      Type::Associated(base, name) => match (base.as_ref(), scope.associated_types) {
+        (Type::SelfType, Some(associated_types)) => match associated_types.get(name) {
+          Some(ty) => ty.clone(),
+          None => error(
+            diagnostics,
+            format!("associated type `{}` not found for `Self`", name),
+          ),
+        },
+        (Type::SelfType, None) => error(
+          diagnostics,
+          "`Self` is only available in traits and impls".to_string(),
+        ),
+        _ => error(diagnostics, format!("ambiguous associated type `{}`", ty)),
+      },
+      Type::Pointer(pointee) => {
+        Ty::Pointer(Box::new(self.lower_type(pointee, scope, span, diagnostics)))
+      }
+      Type::Struct(_) => Ty::Opaque(ty.clone()),
+      Type::Coroutine(..) => unreachable!("state machines are never written in the source codes"),
+    }
+  }
+
+  /// Lower the generic parameter in scope, such as `T`, or the associated type of its bounds, such
+  /// as `T::Entry`. Returns `None` if the path does not start with a generic parameter.
+  fn lower_param(
+    &self,
+    path: &Path,
+    args: &[Type],
+    scope: TypeScope,
+    span: &Span,
+    diagnostics: &mut DiagnosticEngine,
+  ) -> Option<Ty> {
+    let generics = scope.generics?;
+    let first = path.segments().next()?;
+    let param = generics.params.iter().find(|p| p.name == first)?;
+    if !args.is_empty() {
+      diagnostics.emit(Diagnostic::error(
+        format!("type parameter `{}` does not take generic arguments", first),
+        span.clone(),
+      ));
+      return Some(Ty::Error);
+    }
+    match path.segments().skip(1).collect::<Vec<_>>().as_slice() {
+      [] => Some(Ty::Param(param.name.to_owned())),
+      [name] => {
+        let declared = param
+          .bounds
+          .iter()
+          .any(|bound| self.traits[bound].associated_types.iter().any(|t| t == name));
+        if declared {
+          Some(Ty::Param(format!("{}::{}", first, name)))
+        } else {
+          diagnostics.emit(Diagnostic::error(
+            format!("associated type `{}` not found for `{}`", name, first),
+            span.clone(),
+          ));
+          Some(Ty::Error)
+        }
+      }
+      _ => {
+        diagnostics.emit(Diagnostic::error(
+          format!("cannot find type `{}` in this scope", path),
+          span.clone(),
+        ));
+        Some(Ty::Error)
+      }
+    }
+  }
+
+  /// Lower the struct or enum type with its generic arguments.
+  fn lower_named(
+    &self,
+    path: &Path,
+    args: &[Type],
+    scope: TypeScope,
+    span: &Span,
+    diagnostics: &mut DiagnosticEngine,
+  ) -> Ty {
+    let error = |diagnostics: &mut DiagnosticEngine, message: String| {
+      diagnostics.emit(Diagnostic::error(message, span.clone()));
+      Ty::Error
+    };
+    match self.resolve_path(scope.module, path) {
+      Some((def, kind @ (DefKind::Struct | DefKind::Enum))) => {
+        let expected = self.adt_generics(&def).params.len();
+        if args.len() != expected {
+          return error(
+            diagnostics,
+            format!(
+              "{} `{}` takes {} generic argument{} but {} {} supplied",
+              kind.descr(),
+              path,
+              expected,
+              if expected == 1 { "" } else { "s" },
+              args.len(),
+              if args.len() == 1 { "was" } else { "were" }
+            ),
+          );
+        }
+        let args = args.iter().map(|arg| self.lower_type(arg, scope, span, diagnostics)).collect();
+        Ty::Adt(def, args)
+      }
+
+      Some((_, DefKind::Trait)) => {
+        diagnostics.emit(
+          Diagnostic::error(
+            format!("expected type, found trait `{}`", path),
+            span.clone(),
+          )
+          .with_note(format!("use `dyn {}` for trait objects", path)),
+        );
+        Ty::Error
+      }
+      Some((_, kind)) => error(
+        diagnostics,
+        format!("expected type, found {} `{}`", kind.descr(), path),
+      ),
+      None => error(
+        diagnostics,
+        format!("cannot find type `{}` in this scope", path),
+      ),
+    }
+  }
+
+  /// Generic parameters of the struct or the enum.
+  pub fn adt_generics(&self, def: &DefPath) -> &GenericsInfo {
+    match self.structs.get(def) {
+      Some(info) => &info.generics,
+      None => &self.enums[def].generics,
+    }
+  }
+
+  /// Find the variant of the enum by name, with its index.
+  pub fn variant(&self, def: &DefPath, name: &str) -> Option<(usize, &VariantInfo)> {
+    self.enums.get(def)?.variants.iter().enumerate().find(|(_, v)| v.name == name)
+  }
+
+  /// Lower the generic parameters, merging the bounds in the `where` clause into the parameters.
+  pub fn lower_generics(
+    &self,
+    module: &Path,
+    generics: &Generics,
+    diagnostics: &mut DiagnosticEngine,
+  ) -> GenericsInfo {
+    let mut info = GenericsInfo::default();
+    for param in &generics.params {
+      let bounds = self.lower_bounds(module, &param.bounds, &param.span, diagnostics);
+      info.params.push(GenericParamInfo {
+        name: param.name.to_owned(),
+        bounds,
+      });
+    }
+    for predicate in &generics.where_clause {
+      let bounds = self.lower_bounds(module, &predicate.bounds, &predicate.span, diagnostics);
+      let param = match &predicate.ty {
+        Type::Named(path, args) if path.len() == 1 && args.is_empty() => {
+          info.params.iter_mut().find(|p| Some(p.name.as_str()) == path.name())
+        }
+        _ => None,
+      };
+      match param {
+        Some(param) => param.bounds.extend(bounds),
+        None => diagnostics.emit(Diagnostic::error(
+          format!(
+            "`where` clauses may only constrain generic parameters, found `{}`",
+            predicate.ty
+          ),
+          predicate.span.clone(),
+        )),
+      }
+    }
+    for param in &mut info.params {
+      param.bounds.dedup();
+    }
+    info
+  }
+
+  fn lower_bounds(
+    &self,
+    module: &Path,
+    bounds: &[Path],
+    span: &Span,
+    diagnostics: &mut DiagnosticEngine,
+  ) -> Vec<DefPath> {
+    let mut result = vec![];
+    for bound in bounds {
+      match self.resolve_path(module, bound) {
+        Some((def, DefKind::Trait)) => result.push(def),
+        Some((_, kind)) => diagnostics.emit(Diagnostic::error(
+          format!("expected trait, found {} `{}`", kind.descr(), bound),
+          span.clone(),
+        )),
+        None => diagnostics.emit(Diagnostic::error(
+          format!("cannot find trait `{}` in this scope", bound),
+          span.clone(),
+        )),
+      }
+    }
+    result
+  }
+
+  /// True if the type implements the trait. Generic parameters implement the traits of their
+  /// bounds, and the state machines of `async func` implement `Future`.
+  pub fn implements(&self, ty: &Ty, trait_: &DefPath, generics: &GenericsInfo) -> bool {
+    match ty {
+      Ty::Error => true,
+      Ty::Param(name) => generics.bounds(name).contains(trait_),
+      Ty::Coroutine(..) => self.lang.get(LangItem::Future) == Some(trait_),
+      ty => self.find_impl(trait_, ty).is_some(),
+    }
+  }
+
+  /// Find the method of the trait bounds of the generic parameter, returning the trait and the
+  /// method whose signature is substituted for the parameter.
+  pub fn bound_method(
+    &self,
+    param: &str,
+    generics: &GenericsInfo,
+    name: &str,
+  ) -> Result<(DefPath, bool, Ty), LookupError> {
+    let found = generics
+      .bounds(param)
+      .iter()
+      .filter_map(|bound| Some((bound, self.trait_method(bound, name)?.1)))
+      .collect::<Vec<_>>();
+    let (trait_, method) = match found.as_slice() {
+      [] => return Err(LookupError::NotFound),
+      [found] => found,
+      found => {
+        return Err(LookupError::Ambiguous(
+          found.iter().map(|(t, _)| (*t).clone()).collect(),
+        ))
+      }
+    };
+    let mut args = HashMap::new();
+    args.insert("Self".to_owned(), Ty::Param(param.to_owned()));
+    for associated in &self.traits[*trait_].associated_types {
+      args.insert(
+        format!("Self::{}", associated),
+        Ty::Param(format!("{}::{}", param, associated)),
+      );
+    }
+    Ok((
+      (*trait_).clone(),
+      method.receiver,
+      method.ty.substitute(&args),
+    ))
+  }
+
+  /// Lower the signature of the function into the function type, excluding the receiver.
+  pub fn lower_signature(
+    &self,
+    function: &Function,
+    scope: TypeScope,
+    diagnostics: &mut DiagnosticEngine,
+  ) -> Ty {
+    let signature = &function.signature;
+    let params = signature
+      .parameters
+      .iter()
+      .map(|param| self.lower_type(&param.ty, scope, &param.span, diagnostics))
+      .collect();
+    let ret = self.lower_type(&signature.return_type, scope, &function.span, diagnostics);
+    Ty::Function(params, Box::new(ret))
+  }
+
+  pub fn impl_info(&self, id: ImplId) -> &ImplInfo {
+    &self.impls[id.0]
+  }
+
+  /// Find the implementation written at the span of the file in the module. Implementations of
+  /// unresolved traits are not collected.
+  pub fn impl_at(&self, module: &Path, file: &str, span: &Span) -> Option<&ImplInfo> {
+    self.impls.iter().find(|i| {
+      &i.module == module && i.file == file && i.span.start == span.start && i.span.end == span.end
+    })
+  }
+
+  /// Output of the state machine of the `async func` instantiated with the generic arguments,
+  /// where the associated types of their bounds are substituted as well.
+  pub fn coroutine_output(&self, def: &DefPath, args: &[Ty]) -> Ty {
+    let info = &self.functions[def];
+    let mut substitution = HashMap::new();
+    for (param, arg) in info.generics.params.iter().zip(args) {
+      substitution.insert(param.name.to_owned(), arg.clone());
+      for bound in &param.bounds {
+        for name in &self.traits[bound].associated_types {
+          if let Some(ty) = self.associated_type(bound, name, arg) {
+            substitution.insert(format!("{}::{}", param.name, name), ty);
+          }
+        }
+      }
+    }
+    info.output.substitute(&substitution)
+  }
+
+  /// Associated type of the trait for the type, such as `T::Output` of the generic parameter or
+  /// `Output` of the state machine of `async func`.
+  pub fn associated_type(&self, trait_: &DefPath, name: &str, ty: &Ty) -> Option<Ty> {
+    match ty {
+      Ty::Param(param) => Some(Ty::Param(format!("{}::{}", param, name))),
+      Ty::Coroutine(def, args) if self.lang.get(LangItem::Future) == Some(trait_) => {
+        Some(self.coroutine_output(def, args))
+      }
+      ty => self.find_impl(trait_, ty).map(|info| info.associated_types[name].clone()),
+    }
+  }
+
+  /// Find the implementation of the trait for the type.
+  pub fn find_impl(&self, trait_: &DefPath, ty: &Ty) -> Option<&ImplInfo> {
+    self
+      .impls
+      .iter()
+      .find(|i| i.trait_.as_ref() == Some(trait_) && &i.self_ty == ty)
+  }
+
+  /// Find the method or the associated function of the type by name. Inherent methods take
+  /// precedence over those of the traits implemented for the type.
+  pub fn lookup_method(
+    &self,
+    ty: &Ty,
+    name: &str,
+  ) -> Result<(MethodPath, &MethodInfo), LookupError> {
+    let candidates = |inherent: bool| {
+      self.impls.iter().filter(move |i| {
+        i.trait_.is_none() == inherent && &i.self_ty == ty && i.methods.contains_key(name)
+      })
+    };
+    let found = match candidates(true).next() {
+      Some(found) => found,
+      None => {
+        let traits = candidates(false).collect::<Vec<_>>();
+        match traits.as_slice() {
+          [] => return Err(LookupError::NotFound),
+          [found] => *found,
+          traits => {
+            return Err(LookupError::Ambiguous(
+              traits.iter().filter_map(|i| i.trait_.clone()).collect(),
+            ))
+          }
+        }
+      }
+    };
+    let path = MethodPath {
+      impl_id: found.id,
+      name:    name.to_owned(),
+    };
+    Ok((path, &found.methods[name]))
+  }
+
+  /// Find the method of the trait by name, with its index in the vtable.
+  pub fn trait_method(&self, trait_: &DefPath, name: &str) -> Option<(usize, &TraitMethod)> {
+    self
+      .traits
+      .get(trait_)?
+      .methods
+      .iter()
+      .enumerate()
+      .find(|(_, m)| m.name == name)
+  }
+
+  fn collect_trait_methods(
+    &mut self,
+    module: &Path,
+    definition: &TraitDefinition,
+    diagnostics: &mut DiagnosticEngine,
+  ) {
+    // Signatures are lowered with `Self` and its associated types as parameters. Signatures of
+    // dyn-compatible traits never mention `Self`, so they are also the signatures for trait
+    // objects.
+    let associated_types = definition
+      .associated_types
+      .iter()
+      .map(|t| (t.name.to_owned(), Ty::Param(format!("Self::{}", t.name))))
+      .collect::<HashMap<_, _>>();
+    let scope = TypeScope {
+      module,
+      self_ty: Some(&Ty::Param("Self".to_owned())),
+      associated_types: Some(&associated_types),
+      generics: None,
+    };
+    let def = DefPath::new(module.clone(), definition.name.as_str());
+    let dyn_compatible = self.traits[&def].dyn_incompatibility.is_none();
+    let mut methods = vec![];
+    for method in &definition.methods {
+      if method.body.is_some() {
+        diagnostics.emit(Diagnostic::error(
+          "default implementations of trait methods are not supported yet",
+          method.span.clone(),
+        ));
+      }
+      check_not_generic(method, diagnostics);
+      check_not_async(method, diagnostics);
+      check_not_unsafe(method, diagnostics);
+      check_not_extern(method, diagnostics);
+      let ty = self.lower_signature(method, scope, diagnostics);
+      methods.push(TraitMethod {
+        name: method.name.to_owned(),
+        receiver: method.signature.receiver,
+        dyn_ty: if dyn_compatible {
+          Some(ty.clone())
+        } else {
+          None
+        },
+        ty,
+      });
+    }
+    if let Some(info) = self.traits.get_mut(&def) {
+      info.methods = methods;
+    }
+  }
+
+  fn collect_impl(
+    &mut self,
+    module: &Path,
+    file: &str,
+    definition: &ImplDefinition,
+    trait_definitions: &HashMap<DefPath, &TraitDefinition>,
+    diagnostics: &mut DiagnosticEngine,
+  ) {
+    let span = &definition.span;
+    let id = ImplId(self.impls.len());
+    let self_ty = self.lower_type(
+      &definition.self_ty,
+      TypeScope::module(module),
+      span,
+      diagnostics,
+    );
+    let trait_ = match &definition.trait_ {
+      Some(path) => match self.resolve_path(module, path) {
+        Some((def, DefKind::Trait)) => Some(def),
+        Some((_, kind)) => {
+          diagnostics.emit(Diagnostic::error(
+            format!("expected trait, found {} `{}`", kind.descr(), path),
+            span.clone(),
+          ));
+          None
+        }
+        None => {
+          diagnostics.emit(Diagnostic::error(
+            format!("cannot find trait `{}` in this scope", path),
+            span.clone(),
+          ));
+          None
+        }
+      },
+      None => None,
+    };
+    if definition.trait_.is_some() && trait_.is_none() {
+      // The implementation is dropped, and its methods are not checked.
+      return;
+    }
+
+    let mut associated_types = HashMap::new();
+    for associated in &definition.associated_types {
+      if let Some(ty) = &associated.ty {
+        let scope = TypeScope {
+          self_ty: Some(&self_ty),
+          ..TypeScope::module(module)
+        };
+        let ty = self.lower_type(ty, scope, &associated.span, diagnostics);
+        associated_types.insert(associated.name.to_owned(), ty);
+      }
+    }
+    let scope = TypeScope {
+      self_ty: Some(&self_ty),
+      associated_types: Some(&associated_types),
+      ..TypeScope::module(module)
+    };
+    let mut methods = HashMap::new();
+    for method in &definition.methods {
+      if method.body.is_none() {
+        diagnostics.emit(Diagnostic::error(
+          "associated function in `impl` without body",
+          method.span.clone(),
+        ));
+      }
+      check_not_generic(method, diagnostics);
+      check_not_async(method, diagnostics);
+      check_not_unsafe(method, diagnostics);
+      check_not_extern(method, diagnostics);
+      let ty = self.lower_signature(method, scope, diagnostics);
+      methods.insert(
+        method.name.to_owned(),
+        MethodInfo {
+          receiver: method.signature.receiver,
+          ty,
+          // Methods of traits are as visible as the traits.
+          public: trait_.is_some() || method.signature.accessibility == Accessibility::Public,
+        },
+      );
+    }
+
+    let info = ImplInfo {
+      id,
+      module: module.clone(),
+      file: file.to_owned(),
+      span: span.clone(),
+      trait_,
+      self_ty,
+      associated_types,
+      methods,
+    };
+    match &info.trait_ {
+      Some(trait_) => {
+        self.check_trait_impl(trait_definitions[trait_], definition, &info, diagnostics)
+      }
+      None => self.check_inherent_impl(definition, &info.self_ty, diagnostics),
+    }
+    self.impls.push(info);
+  }
+
+  fn check_inherent_impl(
+    &self,
+    definition: &ImplDefinition,
+    self_ty: &Ty,
+    diagnostics: &mut DiagnosticEngine,
+  ) {
+    match self_ty {
+      Ty::Adt(..) => {}
+      Ty::Error => return,
+      ty => {
+        diagnostics.emit(
+          Diagnostic::error(
+            format!("cannot define inherent `impl` for type `{}`", ty),
+            definition.span.clone(),
+          )
+          .with_note("define a trait and implement it for the type instead"),
+        );
+        return;
+      }
+    }
+    for method in &definition.methods {
+      let duplicate = self.impls.iter().any(|i| {
+        i.trait_.is_none() && &i.self_ty == self_ty && i.methods.contains_key(&method.name)
+      });
+      if duplicate {
+        diagnostics.emit(Diagnostic::error(
+          format!("duplicate definitions with name `{}`", method.name),
+          method.span.clone(),
+        ));
+      }
+    }
+  }
+
+  fn check_trait_impl(
+    &self,
+    trait_definition: &TraitDefinition,
+    definition: &ImplDefinition,
+    implemented: &ImplInfo,
+    diagnostics: &mut DiagnosticEngine,
+  ) {
+    let trait_ = implemented.trait_.as_ref().unwrap();
+    let self_ty = &implemented.self_ty;
+    let associated_types = &implemented.associated_types;
+    let methods = &implemented.methods;
+    let info = &self.traits[trait_];
+    if !self_ty.is_error() && self.find_impl(trait_, self_ty).is_some() {
+      diagnostics.emit(Diagnostic::error(
+        format!(
+          "conflicting implementations of trait `{}` for type `{}`",
+          trait_, self_ty
+        ),
+        definition.span.clone(),
+      ));
+    }
+
+    for associated in &definition.associated_types {
+      if !info.associated_types.contains(&associated.name) {
+        diagnostics.emit(Diagnostic::error(
+          format!(
+            "type `{}` is not a member of trait `{}`",
+            associated.name, trait_
+          ),
+          associated.span.clone(),
+        ));
+      }
+    }
+    // Signatures declared in the trait are lowered in the module of the trait, with `Self` and its
+    // associated types substituted by those of the implementation.
+    let scope = TypeScope {
+      self_ty: Some(self_ty),
+      associated_types: Some(associated_types),
+      ..TypeScope::module(&trait_.module)
+    };
+    for method in &definition.methods {
+      match trait_definition.methods.iter().find(|m| m.name == method.name) {
+        Some(declared) => {
+          self.check_method_signature(declared, method, scope, &methods[&method.name], diagnostics)
+        }
+        None => diagnostics.emit(Diagnostic::error(
+          format!(
+            "method `{}` is not a member of trait `{}`",
+            method.name, trait_
+          ),
+          method.span.clone(),
+        )),
+      }
+    }
+
+    let missing = info
+      .associated_types
+      .iter()
+      .filter(|name| !associated_types.contains_key(*name))
+      .chain(info.methods.iter().map(|m| &m.name).filter(|name| !methods.contains_key(*name)))
+      .map(|name| format!("`{}`", name))
+      .collect::<Vec<_>>();
+    if !missing.is_empty() {
+      diagnostics.emit(Diagnostic::error(
+        format!(
+          "not all trait items implemented, missing: {}",
+          missing.join(", ")
+        ),
+        definition.span.clone(),
+      ));
+    }
+  }
+
+  fn check_method_signature(
+    &self,
+    declared: &Function,
+    method: &Function,
+    scope: TypeScope,
+    info: &MethodInfo,
+    diagnostics: &mut DiagnosticEngine,
+  ) {
+    if declared.signature.receiver != method.signature.receiver {
+      let (has, lacks) = if declared.signature.receiver {
+        ("trait", "impl")
+      } else {
+        ("impl", "trait")
+      };
+      diagnostics.emit(Diagnostic::error(
+        format!(
+          "method `{}` has a `self` declaration in the {}, but not in the {}",
+          method.name, has, lacks
+        ),
+        method.span.clone(),
+      ));
+      return;
+    }
+    // Errors in the declaration are reported once when the trait is collected.
+    let mut ignored = DiagnosticEngine::default();
+    let expected = self.lower_signature(declared, scope, &mut ignored);
+    if ignored.has_errors() || contains_error(&expected) || contains_error(&info.ty) {
+      return;
+    }
+    if expected != info.ty {
+      diagnostics.emit(
+        Diagnostic::error(
+          format!(
+            "method `{}` has an incompatible type for trait",
+            method.name
+          ),
+          method.span.clone(),
+        )
+        .with_note(format!("expected `{}`, found `{}`", expected, info.ty)),
+      );
+    }
+  }
+}
+
+/// Methods could not be generic yet, since they are not instantiated by the monomorphization.
+fn check_not_generic(method: &Function, diagnostics: &mut DiagnosticEngine) {
+  if !method.signature.generics.is_empty() {
+    diagnostics.emit(Diagnostic::error(
+      "generic methods are not supported yet",
+      method.span.clone(),
+    ));
+  }
+}
+
+/// Methods could not be `async` yet, since the state machines are only generated for functions.
+fn check_not_async(method: &Function, diagnostics: &mut DiagnosticEngine) {
+  if method.signature.asyncness.is_async() {
+    diagnostics.emit(Diagnostic::error(
+      "`async` methods are not supported yet",
+      method.span.clone(),
+    ));
+  }
+}
+
+/// Check the annotation `@Repr` of the struct, where `C` is the only representation supported and
+/// only for non-generic structs.
+fn check_repr(definition: &StructDefinition, diagnostics: &mut DiagnosticEngine) -> bool {
+  let annotations = definition.annotations.iter().flatten();
+  let mut repr_c = false;
+  for annotation in annotations.filter(|annotation| annotation.name() == "Repr") {
+    match annotation.attributes().collect::<Vec<_>>().as_slice() {
+      ["C"] if !definition.generics.is_empty() => diagnostics.emit(Diagnostic::error(
+        "`@Repr(C)` structs cannot be generic",
+        definition.span.clone(),
+      )),
+      ["C"] => repr_c = true,
+      _ => diagnostics.emit(Diagnostic::error(
+        "unsupported representation, expected `@Repr(C)`",
+        definition.span.clone(),
+      )),
+    }
+  }
+  repr_c
+}
+
+/// Functions of the C calling convention could not be generic or `async`, and foreign functions
+/// could not be `const` since their bodies are unknown.
+fn check_extern(function: &Function, generic: bool, diagnostics: &mut DiagnosticEngine) {
+  let signature = &function.signature;
+  let message = if generic {
+    "`extern` functions cannot be generic"
+  } else if signature.asyncness.is_async() {
+    "`extern` functions cannot be `async`"
+  } else if signature.constancy.is_constant() && function.body.is_none() {
+    "foreign functions cannot be `const`"
+  } else {
+    return;
+  };
+  diagnostics.emit(Diagnostic::error(message, function.span.clone()));
+}
+
+/// Methods are always of the calling convention of vsp.
+fn check_not_extern(method: &Function, diagnostics: &mut DiagnosticEngine) {
+  if method.signature.abi.is_c() {
+    diagnostics.emit(Diagnostic::error(
+      "`extern` methods are not supported",
+      method.span.clone(),
+    ));
+  }
+}
+
+/// Methods could not be `unsafe` yet, since the method calls are not checked for the `unsafe`
+/// contexts.
+fn check_not_unsafe(method: &Function, diagnostics: &mut DiagnosticEngine) {
+  if method.signature.unsafety.is_unsafe() {
+    diagnostics.emit(Diagnostic::error(
+      "`unsafe` methods are not supported yet",
+      method.span.clone(),
+    ));
+  }
+}
+
+/// Traits could be made into objects only if the methods could be called without knowing the
+/// concrete type, i.e. there is no associated type, and all methods take the receiver and never
+/// mention `Self` in their signatures.
+fn dyn_incompatibility(definition: &TraitDefinition) -> Option<String> {
+  if let Some(associated) = definition.associated_types.first() {
+    return Some(format!("it has the associated type `{}`", associated.name));
+  }
+  for method in &definition.methods {
+    let signature = &method.signature;
+    if !signature.receiver {
+      return Some(format!(
+        "associated function `{}` has no `self` receiver",
+        method.name
+      ));
+    }
+    if signature.return_type.mentions_self()
+      || signature.parameters.iter().any(|p| p.ty.mentions_self())
+    {
+      return Some(format!(
+        "method `{}` references the `Self` type in its signature",
+        method.name
+      ));
+    }
+  }
+  None
+}
+
+fn contains_error(ty: &Ty) -> bool {
+  match ty {
+    Ty::Error => true,
+    Ty::Array(element, _) | Ty::ConstArray(element, _) | Ty::Pointer(element) => {
+      contains_error(element)
+    }
+    Ty::Function(params, ret) => params.iter().any(contains_error) || contains_error(ret),
+    _ => false,
+  }
+}
+
+/// Items in the order of appearance, so that the diagnostics are stable.
+pub(crate) fn sorted<'a, T>(
+  items: impl Iterator<Item = &'a T>,
+  span: impl Fn(&T) -> &Span,
+) -> Vec<&'a T> {
+  let mut items = items.collect::<Vec<_>>();
+  items.sort_by_key(|item| span(item).start);
+  items
+}
+
+pub(crate) fn set_file(diagnostics: &mut DiagnosticEngine, file: &str) {
+  let file = Some(file.to_owned()).filter(|f| !f.is_empty());
+  diagnostics.set_current_file(file);
+}
+
+#[cfg(test)]
+mod tests {
+  use vsp_ast::ast::expr::ExpressionKind;
+  use vsp_ast::ast::stmt::Statement;
+  use vsp_ast::ast::CompilationUnit;
+  use vsp_ast_parser::lex::DefaultLexer;
+  use vsp_ast_parser::parser::ASTParser;
+  use vsp_ast_parser::parser::TraditionalParser;
+
+  use super::*;
+  use crate::check::TypeChecker;
+  use crate::check::TypeckResults;
+
+  const ITERATOR: &str = "
+    struct Counter { count: int64 }
+    interface Iterator {
+      type Entry;
+      func next(): Self::Entry;
+    }
+    interface Named {
+      func name(): int64;
+      static func create(): Self;
+    }";
+
+  fn check(source: &str) -> (CompilationUnit, TypeckResults, Vec<String>) {
+    let source = format!("{}\n{}", ITERATOR, source);
+    let tokens = DefaultLexer {}.tokenize(source.as_str()).unwrap();
+    let unit = TraditionalParser {}.parse(tokens).unwrap();
+    let mut diagnostics = DiagnosticEngine::default();
+    let results = TypeChecker::new(&mut diagnostics).check_compilation_unit(&unit);
+    let messages = diagnostics
+      .diagnostics()
+      .iter()
+      .map(|d| format!("{} {}", d, d.notes.join(" ")))
+      .collect();
+    (unit, results, messages)
+  }
+
+  fn assert_error(messages: &[String], expected: &str) {
+    assert!(
+      messages.iter().any(|m| m.contains(expected)),
+      "expected `{}` in {:?}",
+      expected,
+      messages
+    );
+  }
+
+  #[test]
+  fn test_impl_and_static_dispatch() {
+    let (unit, results, messages) = check(
+      "impl Iterator for Counter {
+         type Entry = int64;
+         func next(): Self::Entry { return self.count + 1; }
+       }
+       impl Counter {
+         public static func new(): Self { return Counter { count: 0 }; }
+         func peek(): int64 { return self.count; }
+       }
+       func main(): int64 {
+         let counter = Counter::new();
+         let next: int64 = counter.next();
+         return next + counter.peek();
+       }",
+    );
+    assert!(messages.is_empty(), "{:?}", messages);
+    let stmts = unit.functions["main"].body.as_ref().unwrap().stmts();
+    let callee = |index: usize| {
+      let expr = match &stmts[index] {
+        Statement::VariableDeclaration(decl) => decl.init.as_ref().unwrap(),
+        _ => unreachable!(),
+      };
+      // Associated functions are recorded on the path rather than the call.
+      let expr = match &expr.kind {
+        ExpressionKind::Call(callee, _) => callee,
+        _ => expr,
+      };
+      match results.method_callee(expr.id) {
+        Some(MethodCallee::Static(path)) => (results_impl(&unit, path), path.name.as_str()),
+        other => panic!("unexpected callee {:?}", other),
+      }
+    };
+    assert_eq!(callee(0), (None, "new"));
+    assert_eq!(callee(1), (Some("Iterator"), "next"));
+  }
+
+  /// Trait of the implementation, by the order of the impls in the unit.
+  fn results_impl<'a>(unit: &'a CompilationUnit, path: &MethodPath) -> Option<&'a str> {
+    unit.impls[path.impl_id.0].trait_.as_ref().and_then(|t| t.name())
+  }
+
+  #[test]
+  fn test_impl_completeness() {
+    let (_, _, messages) = check(
+      "impl Named for Counter {
+         func name(): int64 { return 0; }
+         func extra() {}
+       }
+       impl Iterator for Counter {
+         func next(): bool { return true; }
+       }",
+    );
+    assert_error(
+      &messages,
+      "not all trait items implemented, missing: `create`",
+    );
+    assert_error(&messages, "method `extra` is not a member of trait `Named`");
+    assert_error(
+      &messages,
+      "not all trait items implemented, missing: `Entry`",
+    );
+  }
+
+  #[test]
+  fn test_signature_mismatch() {
+    let (_, _, messages) = check(
+      "impl Iterator for Counter {
+         type Entry = int64;
+         func next(): bool { return true; }
+       }
+       impl Named for Counter {
+         static func name(): int64 { return 0; }
+         static func create(): Counter { return Counter { count: 0 }; }
+       }",
+    );
+    assert_error(
+      &messages,
+      "method `next` has an incompatible type for trait",
+    );
+    assert_error(
+      &messages,
+      "expected `func() -> int64`, found `func() -> bool`",
+    );
+    assert_error(
+      &messages,
+      "method `name` has a `self` declaration in the trait, but not in the impl",
+    );
+  }
+
+  #[test]
+  fn test_conflicting_impls() {
+    let (_, _, messages) = check(
+      "impl Named for Counter {
+         func name(): int64 { return 0; }
+         static func create(): Self { return Counter { count: 0 }; }
+       }
+       impl Named for Counter {
+         func name(): int64 { return 1; }
+         static func create(): Self { return Counter { count: 1 }; }
+       }",
+    );
+    assert_error(
+      &messages,
+      "conflicting implementations of trait `Named` for type `Counter`",
+    );
+  }
+
+  #[test]
+  fn test_methods() {
+    let (_, _, messages) = check(
+      "impl Counter {
+         static func new(): Self { return Counter { count: 0, step: 1 }; }
+       }
+       func main(): int64 {
+         let counter = Counter { };
+         counter.missing();
+         counter.new();
+         return counter.size;
+       }",
+    );
+    assert_error(&messages, "struct `Counter` has no field named `step`");
+    assert_error(
+      &messages,
+      "missing field `count` in initializer of `Counter`",
+    );
+    assert_error(
+      &messages,
+      "no method named `missing` found for type `Counter`",
+    );
+    assert_error(
+      &messages,
+      "`new` is an associated function, call it as `Counter::new(...)`",
+    );
+    assert_error(&messages, "no field `size` on type `Counter`");
+  }
+
+  #[test]
+  fn test_trait_objects() {
+    let (unit, results, messages) = check(
+      "struct Other {}
+       interface Describe {
+         func describe(): int64;
+       }
+       impl Describe for Counter {
+         func describe(): int64 { return self.count; }
+       }
+       func describe(value: dyn Describe): int64 { return value.describe(); }
+       func main(): int64 {
+         describe(Other {});
+         return describe(Counter { count: 1 });
+       }",
+    );
+    assert_eq!(messages.len(), 1, "{:?}", messages);
+    assert_error(
+      &messages,
+      "the trait `Describe` is not implemented for `Other`",
+    );
+    let body = unit.functions["describe"].body.as_ref().unwrap();
+    let call = match &body.stmts()[0] {
+      Statement::Return(stmt) => stmt.value.as_ref().unwrap(),
+      _ => unreachable!(),
+    };
+    assert!(matches!(
+      results.method_callee(call.id),
+      Some(MethodCallee::Dynamic { index: 0, .. })
+    ));
+  }
+
+  #[test]
+  fn test_object_safety() {
+    let (_, _, messages) =
+      check("func first(iter: dyn Iterator) {} func make(named: dyn Named) {}");
+    assert_error(
+      &messages,
+      "the trait `Iterator` cannot be made into an object",
+    );
+    assert_error(&messages, "the trait `Named` cannot be made into an object");
+  }
+}
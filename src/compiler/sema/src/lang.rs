@@ -0,0 +1,492 @@
+//! Lang items, i.e. items of the standard library which the compiler knows about, marked by the
+//! annotation `@Lang`.
+//!
+//! ```vsp
+//! @Lang(Optional)
+//! public enum Optional<T> { Value(T), None }
+//!
+//! @Lang(Expected)
+//! public enum Expected<T, E> { Value(T), Error(E) }
+//!
+//! @Lang(From)
+//! public interface From {
+//!   type Source;
+//!
+//!   static func from(value: Self::Source): Self;
+//! }
+//!
+//! @Lang(Poll)
+//! public enum Poll<F, T> { Ready(T), Pending(F) }
+//!
+//! @Lang(Future)
+//! public interface Future {
+//!   type Output;
+//!
+//!   func poll(): Poll<Self, Self::Output>;
+//! }
+//!
+//! @Lang(BlockOn)
+//! public func block_on<F: Future>(future: F): F::Output;
+//!
+//! @Lang(Iterator)
+//! public interface Iterator {
+//!   type Entry;
+//!
+//!   func has_next(): bool;
+//!
+//!   func next(): Self::Entry;
+//! }
+//! ```
+//!
+//! The shapes of the lang items are checked once collected, so that the later passes could rely
+//! on them: the first variant of `Optional` and `Expected` holds the value, and the second one is
+//! the residual returned early by the `?` operator. Futures are values polled by consuming them,
+//! and `Poll` gives either the output or the future to be polled again. The body of `block_on`
+//! is generated by the compiler, which polls the future until it completes. `for` statements
+//! iterate over the entries of the iterators by `has_next` and `next`.
+use std::collections::HashMap;
+
+use vsp_ast::ast::annotation::Annotation;
+use vsp_diag::Diagnostic;
+use vsp_diag::DiagnosticEngine;
+use vsp_span::Span;
+
+use crate::items::set_file;
+use crate::items::sorted;
+use crate::items::ItemTable;
+use crate::items::TraitMethod;
+use crate::resolve::DefPath;
+use crate::resolve::ModuleSource;
+use crate::ty::Ty;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum LangItem {
+  Optional,
+  Expected,
+  From,
+  Poll,
+  Future,
+  BlockOn,
+  Iterator,
+}
+
+impl LangItem {
+  const ALL: [LangItem; 7] = [
+    LangItem::Optional,
+    LangItem::Expected,
+    LangItem::From,
+    LangItem::Poll,
+    LangItem::Future,
+    LangItem::BlockOn,
+    LangItem::Iterator,
+  ];
+  /// Index of the variant of `Poll` holding the future to be polled again.
+  pub const PENDING: usize = 1;
+  /// Index of the variant of `Poll` holding the output of the completed future.
+  pub const READY: usize = 0;
+  /// Index of the variant returned early by the `?` operator in `Optional` and `Expected`.
+  pub const RESIDUAL: usize = 1;
+  /// Index of the variant holding the value in `Optional` and `Expected`.
+  pub const VALUE: usize = 0;
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      LangItem::Optional => "Optional",
+      LangItem::Expected => "Expected",
+      LangItem::From => "From",
+      LangItem::Poll => "Poll",
+      LangItem::Future => "Future",
+      LangItem::BlockOn => "BlockOn",
+      LangItem::Iterator => "Iterator",
+    }
+  }
+
+  /// Shape which the definition of the lang item must have.
+  fn shape(&self) -> &'static str {
+    match self {
+      LangItem::Optional => {
+        "`Optional` must be an enum with one generic parameter, whose variants are one holding the \
+         parameter and one without fields in order"
+      }
+      LangItem::Expected => {
+        "`Expected` must be an enum with two generic parameters, whose variants are one holding the \
+         first parameter and one holding the second parameter in order"
+      }
+      LangItem::From => {
+        "`From` must be a trait with one associated type `Source` and the associated function \
+         `static func from(value: Self::Source): Self`"
+      }
+      LangItem::Poll => {
+        "`Poll` must be an enum with two generic parameters, whose variants are one holding the \
+         second parameter and one holding the first parameter in order"
+      }
+      LangItem::Future => {
+        "`Future` must be a trait with one associated type `Output` and the method \
+         `func poll(): Poll<Self, Self::Output>`"
+      }
+      LangItem::BlockOn => {
+        "`BlockOn` must be a function declared without a body as \
+         `func block_on<F: Future>(future: F): F::Output`"
+      }
+      LangItem::Iterator => {
+        "`Iterator` must be a trait with one associated type `Entry` and the methods \
+         `func has_next(): bool` and `func next(): Self::Entry`"
+      }
+    }
+  }
+}
+
+/// Definitions of the lang items in the package.
+#[derive(Default)]
+pub struct LangItems {
+  items: HashMap<LangItem, DefPath>,
+}
+
+impl LangItems {
+  pub fn get(&self, item: LangItem) -> Option<&DefPath> {
+    self.items.get(&item)
+  }
+
+  /// Lang item which the enum, the trait or the function is, if any.
+  pub fn item_of(&self, def: &DefPath) -> Option<LangItem> {
+    LangItem::ALL.into_iter().find(|item| self.get(*item) == Some(def))
+  }
+
+  /// Lang item of the type and its generic arguments, if the type is `Optional` or `Expected`.
+  pub fn of_type<'t>(&self, ty: &'t Ty) -> Option<(LangItem, &'t [Ty])> {
+    match ty {
+      Ty::Adt(def, args) => match self.item_of(def)? {
+        item @ (LangItem::Optional | LangItem::Expected) => Some((item, args.as_slice())),
+        _ => None,
+      },
+      _ => None,
+    }
+  }
+
+  /// Collect the lang items from the annotations of the enums, traits and functions, and check
+  /// their shapes.
+  pub(crate) fn collect(
+    modules: &[ModuleSource],
+    table: &ItemTable,
+    diagnostics: &mut DiagnosticEngine,
+  ) -> Self {
+    // Shapes of some lang items mention other lang items, which might be defined later.
+    let mut candidates = HashMap::<LangItem, Vec<DefPath>>::new();
+    for module in modules {
+      for unit in &module.units {
+        let annotated = unit
+          .enums
+          .values()
+          .map(|e| (&e.name, &e.annotations))
+          .chain(unit.traits.values().map(|t| (&t.name, &t.annotations)));
+        for (name, annotations) in annotated {
+          let names = annotations
+            .iter()
+            .flatten()
+            .filter(|a| a.name() == "Lang")
+            .flat_map(|a| a.attributes());
+          for item in names.filter_map(|name| LangItem::ALL.into_iter().find(|i| i.name() == name))
+          {
+            let def = DefPath::new(module.path.clone(), name.as_str());
+            candidates.entry(item).or_default().push(def);
+          }
+        }
+      }
+    }
+
+    let mut lang = Self::default();
+    for module in modules {
+      for unit in &module.units {
+        set_file(diagnostics, unit.filename());
+        let mut definitions = unit
+          .enums
+          .values()
+          .map(|e| (&e.name, &e.annotations, &e.span, false))
+          .chain(unit.traits.values().map(|t| (&t.name, &t.annotations, &t.span, false)))
+          .chain(
+            unit
+              .functions
+              .values()
+              .map(|f| (&f.name, &f.annotations, &f.span, f.body.is_some())),
+          )
+          .collect::<Vec<_>>();
+        definitions.sort_by_key(|(_, _, span, _)| span.start);
+        for (name, annotations, span, has_body) in definitions {
+          let item = match lang_annotation(annotations.as_deref(), span, diagnostics) {
+            Some(item) => item,
+            None => continue,
+          };
+          let def = DefPath::new(module.path.clone(), name.as_str());
+          if has_body || !has_shape(item, &def, table, &candidates) {
+            diagnostics.emit(
+              Diagnostic::error(format!("invalid lang item `{}`", item.name()), span.clone())
+                .with_note(item.shape()),
+            );
+            continue;
+          }
+          if let Some(existing) = lang.items.get(&item) {
+            diagnostics.emit(
+              Diagnostic::error(
+                format!("duplicate lang item `{}`", item.name()),
+                span.clone(),
+              )
+              .with_note(format!("the lang item is first defined as `{}`", existing)),
+            );
+            continue;
+          }
+          lang.items.insert(item, def);
+        }
+        // Lang items are never structs.
+        for definition in sorted(unit.structs.values(), |s| &s.span) {
+          let span = &definition.span;
+          if lang_annotation(definition.annotations.as_deref(), span, diagnostics).is_some() {
+            diagnostics.emit(Diagnostic::error(
+              "`@Lang` is only allowed on enums, traits and functions",
+              span.clone(),
+            ));
+          }
+        }
+      }
+    }
+    lang
+  }
+}
+
+/// Lang item named by the `@Lang` annotation, if any.
+fn lang_annotation(
+  annotations: Option<&[Annotation]>,
+  span: &Span,
+  diagnostics: &mut DiagnosticEngine,
+) -> Option<LangItem> {
+  let annotation = annotations?.iter().find(|a| a.name() == "Lang")?;
+  let names = annotation.attributes().collect::<Vec<_>>();
+  let name = match names.as_slice() {
+    [name] => *name,
+    _ => {
+      diagnostics.emit(Diagnostic::error(
+        "`@Lang` takes exactly one lang item",
+        span.clone(),
+      ));
+      return None;
+    }
+  };
+  match LangItem::ALL.into_iter().find(|item| item.name() == name) {
+    Some(item) => Some(item),
+    None => {
+      diagnostics.emit(Diagnostic::error(
+        format!("unknown lang item `{}`", name),
+        span.clone(),
+      ));
+      None
+    }
+  }
+}
+
+fn has_shape(
+  item: LangItem,
+  def: &DefPath,
+  table: &ItemTable,
+  candidates: &HashMap<LangItem, Vec<DefPath>>,
+) -> bool {
+  let param = |name: &str| vec![Ty::Param(name.to_owned())];
+  let is_candidate =
+    |item: LangItem, def: &DefPath| candidates.get(&item).map_or(false, |defs| defs.contains(def));
+  match item {
+    LangItem::Optional | LangItem::Expected | LangItem::Poll => {
+      let info = match table.enums.get(def) {
+        Some(info) => info,
+        None => return false,
+      };
+      let params = info.generics.names();
+      let (first, second) = match (item, params.as_slice()) {
+        (LangItem::Optional, [value]) => (param(value), vec![]),
+        (LangItem::Expected, [value, error]) => (param(value), param(error)),
+        (LangItem::Poll, [future, output]) => (param(output), param(future)),
+        _ => return false,
+      };
+      matches!(
+        info.variants.as_slice(),
+        [value, rest] if value.fields == first && rest.fields == second
+      )
+    }
+    LangItem::Future => {
+      let info = match table.traits.get(def) {
+        Some(info) => info,
+        None => return false,
+      };
+      let is_poll = |method: &TraitMethod| match &method.ty {
+        Ty::Function(params, ret) if params.is_empty() && method.receiver => matches!(
+          ret.as_ref(),
+          Ty::Adt(poll, args) if is_candidate(LangItem::Poll, poll)
+            && args == &[Ty::Param("Self".to_owned()), Ty::Param("Self::Output".to_owned())]
+        ),
+        _ => false,
+      };
+      info.associated_types == ["Output"]
+        && info.methods.iter().any(|m| m.name == "poll" && is_poll(m))
+    }
+    LangItem::BlockOn => {
+      let info = match table.functions.get(def) {
+        Some(info) => info,
+        None => return false,
+      };
+      let future = match info.generics.params.as_slice() {
+        [param] if matches!(param.bounds.as_slice(), [bound] if is_candidate(LangItem::Future, bound)) => {
+          &param.name
+        }
+        _ => return false,
+      };
+      let signature = Ty::Function(
+        param(future),
+        Box::new(Ty::Param(format!("{}::Output", future))),
+      );
+      info.ty == signature
+        && !info.constancy.is_constant()
+        && !info.asyncness.is_async()
+        && !info.unsafety.is_unsafe()
+    }
+    LangItem::From => {
+      let info = match table.traits.get(def) {
+        Some(info) => info,
+        None => return false,
+      };
+      let signature = Ty::Function(
+        param("Self::Source"),
+        Box::new(Ty::Param("Self".to_owned())),
+      );
+      info.associated_types == ["Source"]
+        && info
+          .methods
+          .iter()
+          .any(|m| m.name == "from" && !m.receiver && m.ty == signature)
+    }
+    LangItem::Iterator => {
+      let info = match table.traits.get(def) {
+        Some(info) => info,
+        None => return false,
+      };
+      let has_method = |name: &str, ret: Ty| {
+        let signature = Ty::Function(vec![], Box::new(ret));
+        info.methods.iter().any(|m| m.name == name && m.receiver && m.ty == signature)
+      };
+      info.associated_types == ["Entry"]
+        && has_method("has_next", Ty::bool())
+        && has_method("next", Ty::Param("Self::Entry".to_owned()))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use vsp_ast_parser::lex::DefaultLexer;
+  use vsp_ast_parser::parser::ASTParser;
+  use vsp_ast_parser::parser::TraditionalParser;
+
+  use super::*;
+  use crate::check::TypeckResults;
+
+  fn check(source: &str) -> (TypeckResults, Vec<String>) {
+    let tokens = DefaultLexer {}.tokenize(source).unwrap();
+    let unit = TraditionalParser {}.parse(tokens).unwrap();
+    let mut diagnostics = DiagnosticEngine::default();
+    let results = crate::check_compilation_unit(&unit, &mut diagnostics);
+    let messages = diagnostics.diagnostics().iter().map(|d| d.to_string()).collect();
+    (results, messages)
+  }
+
+  #[test]
+  fn test_collect() {
+    let (results, messages) = check(
+      "@Lang(Optional) enum Maybe<T> { Some(T), Nothing }
+       @Lang(From) interface Convert {
+         type Source;
+         static func from(value: Self::Source): Self;
+       }
+       @Lang(Iterator) interface Iter {
+         type Entry;
+         func has_next(): bool;
+         func next(): Self::Entry;
+         func try_next(): Self::Entry;
+       }",
+    );
+    assert!(messages.is_empty(), "{:?}", messages);
+    let lang = &results.items().lang;
+    assert_eq!(
+      lang.get(LangItem::Optional),
+      Some(&DefPath::new(Default::default(), "Maybe"))
+    );
+    assert_eq!(
+      lang.item_of(&DefPath::new(Default::default(), "Convert")),
+      Some(LangItem::From)
+    );
+    assert_eq!(
+      lang.get(LangItem::Iterator),
+      Some(&DefPath::new(Default::default(), "Iter"))
+    );
+    assert_eq!(lang.get(LangItem::Expected), None);
+  }
+
+  #[test]
+  fn test_invalid() {
+    let (_, messages) = check(
+      "@Lang(Optional) enum Optional<T> { None, Value(T) }
+       @Lang(Expected) enum Expected<T, E> { Value(T), Error(E) }
+       @Lang(Expected) enum Result<T, E> { Ok(T), Err(E) }
+       @Lang(From) interface From {
+         type Source;
+         func from(): Self;
+       }
+       @Lang(Hash) interface Hash {}
+       @Lang(Optional, Expected) enum Both {}
+       @Lang(Iterator) interface Iter { func next(): int64; }
+       @Lang(From) struct Convert {}",
+    );
+    assert_eq!(
+      messages,
+      vec![
+        "1:1: error: invalid lang item `Optional`",
+        "3:8: error: duplicate lang item `Expected`",
+        "4:8: error: invalid lang item `From`",
+        "8:8: error: unknown lang item `Hash`",
+        "9:8: error: `@Lang` takes exactly one lang item",
+        "10:8: error: invalid lang item `Iterator`",
+        "11:8: error: `@Lang` is only allowed on enums, traits and functions",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_futures() {
+    let (results, messages) = check(
+      "@Lang(Poll) enum Poll<F, T> { Ready(T), Pending(F) }
+       @Lang(Future) interface Future {
+         type Output;
+         func poll(): Poll<Self, Self::Output>;
+       }
+       @Lang(BlockOn) func run<F: Future>(future: F): F::Output;",
+    );
+    assert!(messages.is_empty(), "{:?}", messages);
+    let lang = &results.items().lang;
+    assert_eq!(
+      lang.get(LangItem::BlockOn),
+      Some(&DefPath::new(Default::default(), "run"))
+    );
+
+    let (_, messages) = check(
+      "@Lang(Poll) enum Poll<F, T> { Pending(F), Ready(T) }
+       @Lang(Future) interface Future {
+         type Output;
+         func poll(): Self::Output;
+       }
+       @Lang(BlockOn) func run<F>(future: F): int64 { return 0; }",
+    );
+    assert_eq!(
+      messages,
+      vec![
+        "1:1: error: invalid lang item `Poll`",
+        "2:8: error: invalid lang item `Future`",
+        "6:8: error: invalid lang item `BlockOn`",
+      ]
+    );
+  }
+}
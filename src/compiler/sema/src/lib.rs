@@ -0,0 +1,40 @@
+//! # Semantic analysis
+//!
+//! Semantic analysis runs between the parser and the code generation. It takes the AST of the
+//! compilation unit and reports the errors which are syntactically valid but meaningless, such as
+//! type mismatches.
+//!
+//! ```rust
+//! use vsp_ast::ast::CompilationUnit;
+//! use vsp_diag::DiagnosticEngine;
+//!
+//! let unit = CompilationUnit::new("main.vsp");
+//! let mut diagnostics = DiagnosticEngine::default();
+//! let _results = vsp_sema::check_compilation_unit(&unit, &mut diagnostics);
+//! assert!(!diagnostics.has_errors());
+//! ```
+use vsp_ast::ast::CompilationUnit;
+use vsp_diag::DiagnosticEngine;
+
+use crate::check::TypeChecker;
+use crate::check::TypeckResults;
+
+pub mod check;
+pub mod header;
+pub mod infer;
+pub mod items;
+pub mod lang;
+pub mod mono;
+pub mod pattern;
+pub mod resolve;
+#[cfg(any(test, feature = "test-support"))]
+pub mod testing;
+pub mod ty;
+
+/// Run the type checking over the compilation unit. Errors are reported to the diagnostics.
+pub fn check_compilation_unit(
+  unit: &CompilationUnit,
+  diagnostics: &mut DiagnosticEngine,
+) -> TypeckResults {
+  TypeChecker::new(diagnostics).check_compilation_unit(unit)
+}
@@ -0,0 +1,484 @@
+//! Monomorphization.
+//!
+//! Generic functions are not compiled as they are. Instead, each combination of type arguments used
+//! in the compilation is instantiated into a concrete instance before the code generation. Starting
+//! from the non-generic functions and methods, which are always emitted, the uses of generic
+//! functions recorded by the type checker are followed transitively, with the generic parameters
+//! of the enclosing instance substituted by its type arguments. Calls to the methods of trait
+//! bounds are resolved to the methods of the implementations for the substituted types, so they
+//! are dispatched statically.
+//!
+//! Each instance has a unique symbol name mangled from the path of the item and its type arguments,
+//! and instances with the same item and type arguments are emitted only once.
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+
+use vsp_ast::ast::module::Path;
+use vsp_ast::ast::types::Type;
+
+use crate::check::TypeckResults;
+use crate::items::ItemTable;
+use crate::items::MethodPath;
+use crate::resolve::DefPath;
+use crate::ty::Ty;
+
+/// Item which has a body, i.e. a function or a method to be emitted, or the initializer of a
+/// constant or a static item to be evaluated at compile time.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum MonoItem {
+  Function(DefPath),
+  /// `poll` of the state machine of the `async func`, which runs its body. The state machines
+  /// implement `Future` by it, so `Future::poll` on them is resolved to this item.
+  Coroutine(DefPath),
+  Method(MethodPath),
+  Const(DefPath),
+  Static(DefPath),
+}
+
+/// Use of another item in a body, recorded by the type checker.
+#[derive(Clone, Debug)]
+pub enum ItemUse {
+  /// Function referred to by its name or path, with its type arguments, which might mention the
+  /// generic parameters of the body.
+  Function(DefPath, Vec<Type>),
+  /// Method of the trait bound called on the value of the generic parameter.
+  BoundMethod {
+    trait_:  DefPath,
+    name:    String,
+    self_ty: Type,
+  },
+}
+
+/// Item instantiated with the concrete type arguments, which are empty for non-generic items.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Instance {
+  pub item: MonoItem,
+  pub args: Vec<Type>,
+}
+
+impl Instance {
+  /// Map from the generic parameters of the item to the type arguments, including the associated
+  /// types of the trait bounds, such as `T::Entry`.
+  pub fn substitution(&self, items: &ItemTable) -> HashMap<String, Type> {
+    let mut substitution = HashMap::new();
+    let def = match &self.item {
+      MonoItem::Function(def) | MonoItem::Coroutine(def) => def,
+      _ => return substitution,
+    };
+    let generics = &items.functions[def].generics;
+    for (param, arg) in generics.params.iter().zip(self.args.iter()) {
+      substitution.insert(param.name.to_owned(), arg.clone());
+      for bound in &param.bounds {
+        if let Type::Coroutine(path, args) = arg {
+          let def = DefPath::new(
+            path.parent().unwrap_or_default(),
+            path.name().unwrap_or_default(),
+          );
+          let args = args.iter().map(Ty::from).collect::<Vec<_>>();
+          if let Some(ty) = items.coroutine_output(&def, &args).to_type() {
+            substitution.insert(format!("{}::Output", param.name), ty);
+          }
+          continue;
+        }
+        let implementation = items
+          .impls
+          .iter()
+          .find(|i| i.trait_.as_ref() == Some(bound) && i.self_ty.to_type().as_ref() == Some(arg));
+        for (name, ty) in implementation.into_iter().flat_map(|i| &i.associated_types) {
+          if let Some(ty) = ty.to_type() {
+            substitution.insert(format!("{}::{}", param.name, name), ty);
+          }
+        }
+      }
+    }
+    substitution
+  }
+
+  /// Symbol name of the instance. Non-generic functions and static items keep their paths, such as
+  /// `main` or `core::iter::next`, and others are mangled as follows.
+  ///
+  /// ```plaintext
+  /// function  = "_VN" path ["I" type+ "E"]
+  /// coroutine = "_VC" path ["I" type+ "E"]                 `poll` of `async func`
+  /// method    = "_VM" type (path | "0") name
+  /// path      = (length segment)+ "E"
+  /// type      = length keyword                        primitive types
+  ///           | "N" path ["I" type+ "E"]               structs
+  ///           | "A" size "_" type                      arrays
+  ///           | "AC" path type                         arrays sized by constants
+  ///           | "F" type* "R" type "E"                 functions
+  ///           | "D" path                               trait objects
+  ///           | "C" path ["I" type+ "E"]               state machines of `async func`
+  /// ```
+  pub fn symbol_name(&self, items: &ItemTable) -> String {
+    match &self.item {
+      MonoItem::Function(def) | MonoItem::Const(def) | MonoItem::Static(def)
+        if self.args.is_empty() =>
+      {
+        def.to_string()
+      }
+      MonoItem::Function(def) | MonoItem::Const(def) | MonoItem::Static(def) => {
+        let mut symbol = format!("_VN{}", mangle_path(&def.path()));
+        mangle_args(&self.args, &mut symbol);
+        symbol
+      }
+      MonoItem::Coroutine(def) => {
+        let mut symbol = format!("_VC{}", mangle_path(&def.path()));
+        mangle_args(&self.args, &mut symbol);
+        symbol
+      }
+      MonoItem::Method(path) => {
+        let info = items.impl_info(path.impl_id);
+        let mut symbol = "_VM".to_owned();
+        match info.self_ty.to_type() {
+          Some(ty) => mangle_type(&ty, &mut symbol),
+          None => symbol.push('0'),
+        }
+        match &info.trait_ {
+          Some(trait_) => symbol.push_str(&mangle_path(&trait_.path())),
+          None => symbol.push('0'),
+        }
+        symbol.push_str(&format!("{}{}", path.name.len(), path.name));
+        symbol
+      }
+    }
+  }
+}
+
+fn mangle_path(path: &Path) -> String {
+  let mut mangled = String::new();
+  for segment in path.segments() {
+    mangled.push_str(&format!("{}{}", segment.len(), segment));
+  }
+  mangled.push('E');
+  mangled
+}
+
+fn mangle_args(args: &[Type], mangled: &mut String) {
+  if args.is_empty() {
+    return;
+  }
+  mangled.push('I');
+  args.iter().for_each(|arg| mangle_type(arg, mangled));
+  mangled.push('E');
+}
+
+fn mangle_type(ty: &Type, mangled: &mut String) {
+  match ty {
+    Type::Primitive(primitive) => {
+      let keyword = primitive.keyword();
+      mangled.push_str(&format!("{}{}", keyword.len(), keyword));
+    }
+    Type::Named(path, args) => {
+      mangled.push('N');
+      mangled.push_str(&mangle_path(path));
+      mangle_args(args, mangled);
+    }
+    Type::Array(element, size) => {
+      mangled.push_str(&format!("A{}_", size));
+      mangle_type(element, mangled);
+    }
+    Type::ConstArray(element, size) => {
+      mangled.push_str(&format!("AC{}", mangle_path(size)));
+      mangle_type(element, mangled);
+    }
+    Type::Function(function) => {
+      mangled.push('F');
+      function.params.iter().for_each(|param| mangle_type(param, mangled));
+      mangled.push('R');
+      mangle_type(&function.ret, mangled);
+      mangled.push('E');
+    }
+    Type::Dyn(path) => {
+      mangled.push('D');
+      mangled.push_str(&mangle_path(path));
+    }
+    Type::Pointer(pointee) => {
+      mangled.push('P');
+      mangle_type(pointee, mangled);
+    }
+    Type::Coroutine(path, args) => {
+      mangled.push('C');
+      mangled.push_str(&mangle_path(path));
+      mangle_args(args, mangled);
+    }
+    // Other types never occur in the type arguments after the type checking.
+    ty => unreachable!("unexpected type argument `{}`", ty),
+  }
+}
+
+/// Substitute the generic parameters in the type, which are named types without arguments.
+pub fn substitute(ty: &Type, substitution: &HashMap<String, Type>) -> Type {
+  match ty {
+    Type::Named(path, args) if args.is_empty() => match substitution.get(&path.to_string()) {
+      Some(arg) => arg.clone(),
+      None => ty.clone(),
+    },
+    Type::Named(path, args) => Type::Named(
+      path.clone(),
+      args.iter().map(|arg| substitute(arg, substitution)).collect(),
+    ),
+    Type::Coroutine(path, args) => Type::Coroutine(
+      path.clone(),
+      args.iter().map(|arg| substitute(arg, substitution)).collect(),
+    ),
+    Type::Array(element, size) => Type::array(substitute(element, substitution), *size),
+    Type::ConstArray(element, size) => {
+      Type::ConstArray(Box::new(substitute(element, substitution)), size.clone())
+    }
+    Type::Pointer(pointee) => Type::pointer(substitute(pointee, substitution)),
+    Type::Function(function) => {
+      let mut function = function.clone();
+      function.params = function.params.iter().map(|p| substitute(p, substitution)).collect();
+      function.ret = Box::new(substitute(&function.ret, substitution));
+      Type::Function(function)
+    }
+    ty => ty.clone(),
+  }
+}
+
+/// Instances to emit, in the order of discovery.
+#[derive(Default)]
+pub struct MonoItems {
+  instances: Vec<Instance>,
+}
+
+impl MonoItems {
+  /// Collect all instances reachable from the non-generic functions and methods.
+  pub fn collect(results: &TypeckResults) -> Self {
+    let items = results.items();
+    let mut roots = items
+      .functions
+      .iter()
+      .filter(|(_, info)| info.generics.is_empty())
+      .map(|(def, _)| MonoItem::Function(def.clone()))
+      .collect::<Vec<_>>();
+    roots.sort_by(|a, b| match (a, b) {
+      (MonoItem::Function(a), MonoItem::Function(b)) => a.cmp(b),
+      _ => unreachable!(),
+    });
+    for info in &items.impls {
+      let mut names = info.methods.keys().collect::<Vec<_>>();
+      names.sort();
+      roots.extend(names.into_iter().map(|name| {
+        MonoItem::Method(MethodPath {
+          impl_id: info.id,
+          name:    name.to_owned(),
+        })
+      }));
+    }
+
+    let mut mono = Self::default();
+    let mut seen = HashSet::new();
+    let mut queue = roots
+      .into_iter()
+      .map(|item| Instance { item, args: vec![] })
+      .collect::<VecDeque<_>>();
+    while let Some(instance) = queue.pop_front() {
+      if !seen.insert(instance.clone()) {
+        continue;
+      }
+      let substitution = instance.substitution(items);
+      for item_use in results.uses(&instance.item) {
+        let used = match item_use {
+          ItemUse::Function(def, args) => Instance {
+            item: MonoItem::Function(def.clone()),
+            args: args.iter().map(|arg| substitute(arg, &substitution)).collect(),
+          },
+          ItemUse::BoundMethod {
+            trait_,
+            name,
+            self_ty,
+          } => {
+            let self_ty = substitute(self_ty, &substitution);
+            if let Type::Coroutine(path, args) = self_ty {
+              let def = DefPath::new(
+                path.parent().unwrap_or_default(),
+                path.name().unwrap_or_default(),
+              );
+              let used = Instance {
+                item: MonoItem::Coroutine(def),
+                args,
+              };
+              if !seen.contains(&used) {
+                queue.push_back(used);
+              }
+              continue;
+            }
+            let implementation = items.impls.iter().find(|i| {
+              i.trait_.as_ref() == Some(trait_) && i.self_ty.to_type().as_ref() == Some(&self_ty)
+            });
+            match implementation {
+              Some(implementation) => Instance {
+                item: MonoItem::Method(MethodPath {
+                  impl_id: implementation.id,
+                  name:    name.to_owned(),
+                }),
+                args: vec![],
+              },
+              // Bounds are checked, so the implementation always exists for the valid programs.
+              None => continue,
+            }
+          }
+        };
+        if !seen.contains(&used) {
+          queue.push_back(used);
+        }
+      }
+      // Calling `async func` returns the state machine, which is polled to run the body.
+      if let MonoItem::Function(def) = &instance.item {
+        if items.functions[def].asyncness.is_async() {
+          queue.push_back(Instance {
+            item: MonoItem::Coroutine(def.clone()),
+            args: instance.args.clone(),
+          });
+        }
+      }
+      mono.instances.push(instance);
+    }
+    mono
+  }
+
+  pub fn instances(&self) -> &[Instance] {
+    &self.instances
+  }
+
+  /// Instances of the generic function, i.e. those with type arguments.
+  pub fn instances_of<'a>(&'a self, def: &'a DefPath) -> impl Iterator<Item = &'a Instance> {
+    self
+      .instances
+      .iter()
+      .filter(move |i| !i.args.is_empty() && i.item == MonoItem::Function(def.clone()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use vsp_ast_parser::lex::DefaultLexer;
+  use vsp_ast_parser::parser::ASTParser;
+  use vsp_ast_parser::parser::TraditionalParser;
+  use vsp_diag::DiagnosticEngine;
+
+  use super::*;
+
+  fn collect(source: &str) -> (TypeckResults, MonoItems) {
+    let tokens = DefaultLexer {}.tokenize(source).unwrap();
+    let unit = TraditionalParser {}.parse(tokens).unwrap();
+    let mut diagnostics = DiagnosticEngine::default();
+    let results = crate::check_compilation_unit(&unit, &mut diagnostics);
+    assert!(!diagnostics.has_errors(), "{:?}", diagnostics.diagnostics());
+    let mono = MonoItems::collect(&results);
+    (results, mono)
+  }
+
+  fn symbols(results: &TypeckResults, mono: &MonoItems) -> Vec<String> {
+    let mut symbols = mono
+      .instances()
+      .iter()
+      .map(|i| i.symbol_name(results.items()))
+      .collect::<Vec<_>>();
+    symbols.sort();
+    symbols
+  }
+
+  #[test]
+  fn test_instances() {
+    let (results, mono) = collect(
+      "struct Pair<A, B> { first: A, second: B }
+       func identity<T>(value: T): T { return value; }
+       func wrap<T>(value: T): Pair<T, bool> { return Pair { first: identity(value), second: true }; }
+       func unused<T>(value: T) {}
+       func main(): int64 {
+         let a: int64 = identity(1);
+         let b: int64 = identity(2);
+         let c = identity(true);
+         let d = wrap(a);
+         let e = wrap(d);
+         return a + b;
+       }",
+    );
+    assert_eq!(
+      symbols(&results, &mono),
+      vec![
+        "_VN4wrapEI5int64E",
+        "_VN4wrapEIN4PairEI5int644boolEE",
+        "_VN8identityEI4boolE",
+        "_VN8identityEI5int64E",
+        "_VN8identityEIN4PairEI5int644boolEE",
+        "main",
+      ]
+    );
+    let identity = DefPath::new(Path::root(), "identity");
+    assert_eq!(mono.instances_of(&identity).count(), 3);
+    assert_eq!(
+      mono.instances_of(&DefPath::new(Path::root(), "unused")).count(),
+      0
+    );
+  }
+
+  #[test]
+  fn test_bound_methods() {
+    let (results, mono) = collect(
+      "interface Source {
+         type Entry;
+         func next(): Self::Entry;
+       }
+       struct Counter { count: int64 }
+       impl Source for Counter {
+         type Entry = int64;
+         func next(): int64 { return self.count; }
+       }
+       func take<S: Source>(source: S): S::Entry { return source.next(); }
+       func main(): int64 { return take(Counter { count: 1 }); }",
+    );
+    assert_eq!(
+      symbols(&results, &mono),
+      vec![
+        "_VMN7CounterE6SourceE4next",
+        "_VN4takeEIN7CounterEE",
+        "main",
+      ]
+    );
+    let take = DefPath::new(Path::root(), "take");
+    let take = mono.instances_of(&take).next().unwrap();
+    let substitution = take.substitution(results.items());
+    assert_eq!(substitution["S::Entry"], Type::int64());
+    assert_eq!(
+      substitute(&Type::named(Path::from("S::Entry")), &substitution),
+      Type::int64()
+    );
+  }
+
+  #[test]
+  fn test_coroutines() {
+    let (results, mono) = collect(
+      "@Lang(Poll) enum Poll<F, T> { Ready(T), Pending(F) }
+       @Lang(Future) interface Future {
+         type Output;
+         func poll(): Poll<Self, Self::Output>;
+       }
+       @Lang(BlockOn) func block_on<F: Future>(future: F): F::Output;
+       async func fetch<T>(value: T): T { return value; }
+       async func twice(): int64 { return fetch(1).await * 2; }
+       func main(): int64 { return block_on(twice()); }",
+    );
+    assert_eq!(
+      symbols(&results, &mono),
+      vec![
+        "_VC5fetchEI5int64E",
+        "_VC5twiceE",
+        "_VN5fetchEI5int64E",
+        "_VN8block_onEIC5twiceEE",
+        "main",
+        "twice",
+      ]
+    );
+    let block_on = DefPath::new(Path::root(), "block_on");
+    let block_on = mono.instances_of(&block_on).next().unwrap();
+    assert_eq!(
+      block_on.substitution(results.items())["F::Output"],
+      Type::int64()
+    );
+  }
+}
@@ -0,0 +1,188 @@
+//! # Exhaustiveness of patterns
+//!
+//! Arms of `match` are checked by the usefulness of their patterns: the pattern is useful if some
+//! value is matched by it but none of the patterns before it. The arm whose pattern is not useful
+//! is unreachable, and the match is exhaustive unless the wildcard after the last arm is useful.
+//!
+//! Patterns are seen as constructors applied to the patterns of their fields, where the variants of
+//! an enum and the two booleans make complete signatures, while the integers never do.
+use std::collections::HashMap;
+
+use crate::items::EnumInfo;
+use crate::resolve::DefPath;
+
+/// Pattern reduced to what matters to the matching, where bindings are wildcards.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pat {
+  Wild,
+  Bool(bool),
+  Int(i64),
+  /// Variant of the enum by its index, with the patterns of its fields.
+  Variant(DefPath, usize, Vec<Pat>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Constructor {
+  Bool(bool),
+  Int(i64),
+  Variant(DefPath, usize),
+}
+
+/// Usefulness of the rows of patterns, looking up the variants of the enums.
+pub struct Usefulness<'a> {
+  enums: &'a HashMap<DefPath, EnumInfo>,
+}
+
+impl<'a> Usefulness<'a> {
+  pub fn new(enums: &'a HashMap<DefPath, EnumInfo>) -> Self {
+    Self { enums }
+  }
+
+  /// Whether some values are matched by the row but none of the rows before it.
+  pub fn is_useful(&self, rows: &[Vec<Pat>], row: &[Pat]) -> bool {
+    let (head, tail) = match row.split_first() {
+      Some(split) => split,
+      None => return rows.is_empty(),
+    };
+    match self.constructor(head) {
+      Some(constructor) => {
+        let mut row = self.fields(head, &constructor);
+        row.extend_from_slice(tail);
+        self.is_useful(&self.specialize(rows, &constructor), &row)
+      }
+      None => match self.complete_signature(rows) {
+        Some(signature) => signature.iter().any(|constructor| {
+          let mut row = vec![Pat::Wild; self.arity(constructor)];
+          row.extend_from_slice(tail);
+          self.is_useful(&self.specialize(rows, constructor), &row)
+        }),
+        None => self.is_useful(&Self::default_rows(rows), tail),
+      },
+    }
+  }
+
+  /// Patterns of `width` columns which are matched by none of the rows, if any.
+  pub fn missing(&self, rows: &[Vec<Pat>], width: usize) -> Option<Vec<Pat>> {
+    if width == 0 {
+      return if rows.is_empty() { Some(vec![]) } else { None };
+    }
+    if let Some(signature) = self.complete_signature(rows) {
+      return signature.into_iter().find_map(|constructor| {
+        let arity = self.arity(&constructor);
+        let mut missing = self.missing(&self.specialize(rows, &constructor), arity + width - 1)?;
+        let fields = missing.drain(..arity).collect();
+        missing.insert(0, Self::apply(constructor, fields));
+        Some(missing)
+      });
+    }
+    let mut missing = self.missing(&Self::default_rows(rows), width - 1)?;
+    // Pick a constructor which none of the rows starts with, unless all of them are wildcards.
+    let head = match rows.iter().find_map(|row| self.constructor(&row[0])) {
+      Some(Constructor::Variant(def, _)) => {
+        let index = (0..self.enums[&def].variants.len())
+          .find(|index| {
+            !rows
+              .iter()
+              .any(|row| matches!(&row[0], Pat::Variant(d, i, _) if d == &def && i == index))
+          })
+          .unwrap_or_default();
+        let arity = self.enums[&def].variants[index].fields.len();
+        Pat::Variant(def, index, vec![Pat::Wild; arity])
+      }
+      Some(Constructor::Bool(_)) => Pat::Bool(rows.iter().any(|row| row[0] == Pat::Bool(false))),
+      Some(Constructor::Int(_)) | None => Pat::Wild,
+    };
+    missing.insert(0, head);
+    Some(missing)
+  }
+
+  /// Render the pattern in the syntax of the source, such as `Optional::Value(_)`.
+  pub fn display(&self, pat: &Pat) -> String {
+    match pat {
+      Pat::Variant(def, index, fields) => {
+        let name = format!("{}::{}", def.name, self.enums[def].variants[*index].name);
+        if fields.is_empty() {
+          return name;
+        }
+        let fields = fields.iter().map(|field| self.display(field)).collect::<Vec<_>>();
+        format!("{}({})", name, fields.join(", "))
+      }
+      Pat::Wild => "_".to_owned(),
+      Pat::Bool(value) => value.to_string(),
+      Pat::Int(value) => value.to_string(),
+    }
+  }
+
+  fn constructor(&self, pat: &Pat) -> Option<Constructor> {
+    match pat {
+      Pat::Wild => None,
+      Pat::Bool(value) => Some(Constructor::Bool(*value)),
+      Pat::Int(value) => Some(Constructor::Int(*value)),
+      Pat::Variant(def, index, _) => Some(Constructor::Variant(def.clone(), *index)),
+    }
+  }
+
+  fn arity(&self, constructor: &Constructor) -> usize {
+    match constructor {
+      Constructor::Variant(def, index) => self.enums[def].variants[*index].fields.len(),
+      _ => 0,
+    }
+  }
+
+  /// Patterns of the fields of the pattern, which has the constructor or is a wildcard.
+  fn fields(&self, pat: &Pat, constructor: &Constructor) -> Vec<Pat> {
+    match pat {
+      Pat::Variant(_, _, fields) => fields.clone(),
+      _ => vec![Pat::Wild; self.arity(constructor)],
+    }
+  }
+
+  fn apply(constructor: Constructor, fields: Vec<Pat>) -> Pat {
+    match constructor {
+      Constructor::Bool(value) => Pat::Bool(value),
+      Constructor::Int(value) => Pat::Int(value),
+      Constructor::Variant(def, index) => Pat::Variant(def, index, fields),
+    }
+  }
+
+  /// All constructors of the type of the first column, if all of them appear in the column.
+  fn complete_signature(&self, rows: &[Vec<Pat>]) -> Option<Vec<Constructor>> {
+    let heads = rows.iter().filter_map(|row| self.constructor(&row[0])).collect::<Vec<_>>();
+    let signature = match heads.first()? {
+      Constructor::Bool(_) => vec![Constructor::Bool(false), Constructor::Bool(true)],
+      Constructor::Int(_) => return None,
+      Constructor::Variant(def, _) => (0..self.enums[def].variants.len())
+        .map(|index| Constructor::Variant(def.clone(), index))
+        .collect(),
+    };
+    signature
+      .iter()
+      .all(|constructor| heads.contains(constructor))
+      .then(|| signature)
+  }
+
+  /// Rows matching the constructor in the first column, with the column replaced by its fields.
+  fn specialize(&self, rows: &[Vec<Pat>], constructor: &Constructor) -> Vec<Vec<Pat>> {
+    rows
+      .iter()
+      .filter(|row| match self.constructor(&row[0]) {
+        Some(head) => &head == constructor,
+        None => true,
+      })
+      .map(|row| {
+        let mut fields = self.fields(&row[0], constructor);
+        fields.extend_from_slice(&row[1..]);
+        fields
+      })
+      .collect()
+  }
+
+  /// Rows starting with wildcards, without the first column.
+  fn default_rows(rows: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+    rows
+      .iter()
+      .filter(|row| row[0] == Pat::Wild)
+      .map(|row| row[1..].to_vec())
+      .collect()
+  }
+}
@@ -0,0 +1,637 @@
+//! Name resolution across modules.
+//!
+//! Each module has a scope of names, made of the items defined in the module and those imported
+//! by `use` declarations. Since an import might refer to a name re-exported by another import,
+//! imports are resolved repeatedly until no more of them could be resolved, and the rest are
+//! reported as unresolved.
+//!
+//! Visibility is enforced across module boundaries: private items are only visible in the module
+//! defining them and its descendants, so are the private modules in their parent module.
+//!
+//! The public items of the prelude are in scope of every module, after the imports, so that they
+//! are shadowed by the items and the imports of the same names.
+use core::fmt::Display;
+use core::fmt::Formatter;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+use vsp_ast::ast::constant::ConstKind;
+use vsp_ast::ast::modifier::Accessibility;
+use vsp_ast::ast::module::Path;
+use vsp_ast::ast::module::UseDeclaration;
+use vsp_ast::ast::module::UseKind;
+use vsp_ast::ast::CompilationUnit;
+use vsp_diag::Diagnostic;
+use vsp_diag::DiagnosticEngine;
+use vsp_span::Span;
+
+/// Name of the module of the prelude, which is not a valid module name so that it never collides
+/// with the modules of the package.
+pub const PRELUDE: &str = "{prelude}";
+
+/// Path to the definition of an item, i.e. the module defining it and its name.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct DefPath {
+  pub module: Path,
+  pub name:   String,
+}
+
+impl DefPath {
+  pub fn new(module: Path, name: impl Into<String>) -> Self {
+    Self {
+      module,
+      name: name.into(),
+    }
+  }
+
+  /// Absolute path to the item from the package root.
+  pub fn path(&self) -> Path {
+    self.module.join(self.name.as_str())
+  }
+}
+
+impl Display for DefPath {
+  fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+    if self.module.is_empty() {
+      write!(f, "{}", self.name)
+    } else {
+      write!(f, "{}::{}", self.module, self.name)
+    }
+  }
+}
+
+/// Kind of the item which a name is defined as.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DefKind {
+  Function,
+  Struct,
+  Enum,
+  Trait,
+  Const,
+  Static,
+}
+
+impl DefKind {
+  pub fn descr(&self) -> &'static str {
+    match self {
+      DefKind::Function => "function",
+      DefKind::Struct => "struct",
+      DefKind::Enum => "enum",
+      DefKind::Trait => "trait",
+      DefKind::Const => "constant",
+      DefKind::Static => "static",
+    }
+  }
+}
+
+/// Module with the compilation units made of it, collected from the compilation unit of the
+/// entry file.
+pub struct ModuleSource<'a> {
+  pub path:       Path,
+  pub visibility: Accessibility,
+  pub units:      Vec<&'a CompilationUnit>,
+}
+
+/// Collect all modules in the package, with the root module first and others ordered by path.
+pub fn collect_modules(unit: &CompilationUnit) -> Vec<ModuleSource<'_>> {
+  let mut root = ModuleSource {
+    path:       Path::root(),
+    visibility: Accessibility::Public,
+    units:      vec![unit],
+  };
+  let mut modules = BTreeMap::new();
+  for module in unit.modules.values() {
+    if module.path.is_empty() {
+      root.units.extend(module.units.iter());
+      continue;
+    }
+    modules.insert(
+      module.path.clone(),
+      ModuleSource {
+        path:       module.path.clone(),
+        visibility: module.visibility,
+        units:      module.units.iter().collect(),
+      },
+    );
+  }
+  std::iter::once(root).chain(modules.into_values()).collect()
+}
+
+/// Names in scope of each module, resolved to their definitions.
+#[derive(Default)]
+pub struct ModuleScopes {
+  scopes: HashMap<Path, HashMap<String, DefPath>>,
+  kinds:  HashMap<DefPath, DefKind>,
+}
+
+impl ModuleScopes {
+  pub fn kind(&self, def: &DefPath) -> Option<DefKind> {
+    self.kinds.get(def).copied()
+  }
+
+  pub fn scope(&self, module: &Path) -> Option<&HashMap<String, DefPath>> {
+    self.scopes.get(module)
+  }
+
+  pub fn resolve(&self, module: &Path, name: &str) -> Option<&DefPath> {
+    self.scopes.get(module)?.get(name)
+  }
+}
+
+#[derive(Clone)]
+struct Binding {
+  def:    DefPath,
+  kind:   DefKind,
+  /// Whether the name is visible outside the module.
+  public: bool,
+  /// Whether the name is imported by a glob import, which could be shadowed by other names.
+  glob:   bool,
+}
+
+enum ModuleError {
+  NotFound(Path),
+  Private(Path),
+}
+
+/// Resolver of the names in all modules of the package.
+pub struct Resolver<'a, 'm> {
+  diagnostics: &'a mut DiagnosticEngine,
+  modules:     HashMap<Path, &'m ModuleSource<'m>>,
+  scopes:      HashMap<Path, HashMap<String, Binding>>,
+  kinds:       HashMap<DefPath, DefKind>,
+}
+
+impl<'a, 'm> Resolver<'a, 'm> {
+  pub fn new(diagnostics: &'a mut DiagnosticEngine) -> Self {
+    Self {
+      diagnostics,
+      modules: HashMap::new(),
+      scopes: HashMap::new(),
+      kinds: HashMap::new(),
+    }
+  }
+
+  pub fn resolve(mut self, modules: &'m [ModuleSource<'m>]) -> ModuleScopes {
+    for module in modules {
+      self.modules.insert(module.path.clone(), module);
+      self.define_items(module);
+    }
+
+    let imports = modules
+      .iter()
+      .flat_map(|module| {
+        module.units.iter().flat_map(move |unit| {
+          unit.uses.iter().map(move |decl| (&module.path, decl, unit.filename()))
+        })
+      })
+      .collect::<Vec<_>>();
+    let mut resolved = vec![false; imports.len()];
+    loop {
+      let mut changed = false;
+      for (i, (module, decl, file)) in imports.iter().enumerate() {
+        if resolved[i] && decl.kind != UseKind::Glob {
+          continue;
+        }
+        self.set_file(file);
+        let (done, progress) = self.resolve_import(module, decl);
+        resolved[i] |= done;
+        changed |= progress;
+      }
+      if !changed {
+        break;
+      }
+    }
+    for (i, (module, decl, file)) in imports.iter().enumerate() {
+      if !resolved[i] {
+        self.set_file(file);
+        self.report_unresolved(module, decl);
+      }
+    }
+    self.diagnostics.set_current_file(None);
+    self.import_prelude();
+
+    ModuleScopes {
+      scopes: self
+        .scopes
+        .into_iter()
+        .map(|(path, scope)| {
+          let scope = scope.into_iter().map(|(name, binding)| (name, binding.def)).collect();
+          (path, scope)
+        })
+        .collect(),
+      kinds:  self.kinds,
+    }
+  }
+
+  fn define_items(&mut self, module: &ModuleSource) {
+    let mut scope: HashMap<String, Binding> = HashMap::new();
+    for unit in &module.units {
+      self.set_file(unit.filename());
+      let mut items = unit
+        .functions
+        .values()
+        .map(|f| {
+          (
+            DefKind::Function,
+            &f.name,
+            f.signature.accessibility,
+            &f.span,
+          )
+        })
+        .chain(unit.structs.values().map(|s| (DefKind::Struct, &s.name, s.visibility, &s.span)))
+        .chain(unit.enums.values().map(|e| (DefKind::Enum, &e.name, e.visibility, &e.span)))
+        .chain(unit.traits.values().map(|t| (DefKind::Trait, &t.name, t.visibility, &t.span)))
+        .chain(unit.consts.values().map(|c| {
+          let kind = match c.kind {
+            ConstKind::Const => DefKind::Const,
+            ConstKind::Static => DefKind::Static,
+          };
+          (kind, &c.name, c.visibility, &c.span)
+        }))
+        .collect::<Vec<(DefKind, &String, Accessibility, &Span)>>();
+      items.sort_by_key(|(_, _, _, span)| span.start);
+      for (kind, name, visibility, span) in items {
+        if let Some(existing) = scope.get(name) {
+          let message = if existing.kind == kind {
+            format!(
+              "{} `{}` is defined multiple times in {}",
+              kind.descr(),
+              name,
+              describe_module(&module.path)
+            )
+          } else {
+            format!(
+              "the name `{}` is defined multiple times in {}",
+              name,
+              describe_module(&module.path)
+            )
+          };
+          self.diagnostics.emit(Diagnostic::error(message, span.clone()));
+          continue;
+        }
+        let def = DefPath::new(module.path.clone(), name.as_str());
+        self.kinds.insert(def.clone(), kind);
+        scope.insert(
+          name.to_owned(),
+          Binding {
+            def,
+            kind,
+            public: visibility == Accessibility::Public,
+            glob: false,
+          },
+        );
+      }
+    }
+    self.scopes.insert(module.path.clone(), scope);
+  }
+
+  /// Try to resolve the import, returning whether it is resolved, and whether any new name is
+  /// bound.
+  fn resolve_import(&mut self, module: &Path, decl: &UseDeclaration) -> (bool, bool) {
+    let target = absolute_path(module, &decl.path);
+    let public = decl.visibility == Accessibility::Public;
+    match &decl.kind {
+      UseKind::Glob => {
+        if self.check_module(&target, module).is_err() {
+          return (false, false);
+        }
+        if &target == module {
+          return (true, false);
+        }
+        let mut changed = false;
+        let bindings = self.scopes[&target].clone();
+        let scope = self.scopes.get_mut(module).unwrap();
+        for (name, binding) in bindings {
+          if !is_visible(&binding, &target, module) || scope.contains_key(&name) {
+            continue;
+          }
+          scope.insert(
+            name,
+            Binding {
+              def: binding.def,
+              kind: binding.kind,
+              public,
+              glob: true,
+            },
+          );
+          changed = true;
+        }
+        (true, changed)
+      }
+      UseKind::Single(_) => {
+        let (parent, name) = match (target.parent(), target.name()) {
+          (Some(parent), Some(name)) => (parent, name),
+          _ => return (false, false),
+        };
+        if self.check_module(&parent, module).is_err() {
+          return (false, false);
+        }
+        let binding = match self.scopes[&parent].get(name) {
+          Some(binding) if is_visible(binding, &parent, module) => binding.clone(),
+          _ => return (false, false),
+        };
+        let binding_name = decl.binding().unwrap().to_owned();
+        let scope = self.scopes.get_mut(module).unwrap();
+        match scope.get(&binding_name) {
+          Some(existing) if existing.def == binding.def => (true, false),
+          Some(existing) if !existing.glob => {
+            self.diagnostics.emit(Diagnostic::error(
+              format!("the name `{}` is defined multiple times", binding_name),
+              decl.span.clone(),
+            ));
+            (true, false)
+          }
+          _ => {
+            scope.insert(
+              binding_name,
+              Binding {
+                def: binding.def,
+                kind: binding.kind,
+                public,
+                glob: false,
+              },
+            );
+            (true, true)
+          }
+        }
+      }
+    }
+  }
+
+  /// Bind the public items of the prelude in every other module, as glob imports unless the names
+  /// are bound already.
+  fn import_prelude(&mut self) {
+    let bindings = match self.scopes.get(&Path::root().join(PRELUDE)) {
+      Some(scope) => scope.clone(),
+      None => return,
+    };
+    for (module, scope) in &mut self.scopes {
+      if is_prelude(module) {
+        continue;
+      }
+      for (name, binding) in bindings.iter().filter(|(_, binding)| binding.public) {
+        if !scope.contains_key(name) {
+          scope.insert(
+            name.to_owned(),
+            Binding {
+              def:    binding.def.clone(),
+              kind:   binding.kind,
+              public: false,
+              glob:   true,
+            },
+          );
+        }
+      }
+    }
+  }
+
+  fn report_unresolved(&mut self, module: &Path, decl: &UseDeclaration) {
+    let target = absolute_path(module, &decl.path);
+    let span = decl.span.clone();
+    let parent = match decl.kind {
+      UseKind::Glob => target.clone(),
+      UseKind::Single(_) => target.parent().unwrap_or_default(),
+    };
+    let diagnostic = match self.check_module(&parent, module) {
+      Err(ModuleError::NotFound(path)) => {
+        Diagnostic::error(format!("unresolved import `{}`", decl.path), span)
+          .with_note(format!("could not find module `{}`", path))
+      }
+      Err(ModuleError::Private(path)) => {
+        Diagnostic::error(format!("module `{}` is private", path), span)
+      }
+      Ok(()) => {
+        let name = target.name().unwrap_or_default();
+        match self.scopes[&parent].get(name) {
+          Some(binding) => Diagnostic::error(
+            format!("{} `{}` is private", binding.kind.descr(), name),
+            span,
+          ),
+          None if self.modules.contains_key(&target) => {
+            Diagnostic::error(format!("unresolved import `{}`", decl.path), span).with_note(
+              format!(
+                "`{}` is a module, import its items by `{}::*`",
+                decl.path, decl.path
+              ),
+            )
+          }
+          None => Diagnostic::error(format!("unresolved import `{}`", decl.path), span)
+            .with_note(format!("no `{}` in {}", name, describe_module(&parent))),
+        }
+      }
+    };
+    self.diagnostics.emit(diagnostic);
+  }
+
+  /// Check whether the module exists and is accessible from the other module.
+  fn check_module(&self, target: &Path, from: &Path) -> Result<(), ModuleError> {
+    let mut path = Path::root();
+    for segment in target.segments() {
+      path.push(segment);
+      let module = self.modules.get(&path).ok_or_else(|| ModuleError::NotFound(path.clone()))?;
+      let parent = path.parent().unwrap_or_default();
+      if module.visibility == Accessibility::Private && !from.starts_with(&parent) {
+        return Err(ModuleError::Private(path));
+      }
+    }
+    Ok(())
+  }
+
+  fn set_file(&mut self, file: &str) {
+    let file = if file.is_empty() {
+      None
+    } else {
+      Some(file.to_owned())
+    };
+    self.diagnostics.set_current_file(file);
+  }
+}
+
+/// Whether the path is the module of the prelude.
+pub fn is_prelude(path: &Path) -> bool {
+  path.len() == 1 && path.name() == Some(PRELUDE)
+}
+
+/// Paths of `use` declarations are relative to the package root, unless starting with `self`.
+fn absolute_path(module: &Path, path: &Path) -> Path {
+  let mut segments = path.segments().peekable();
+  let mut result = if segments.peek() == Some(&"self") {
+    segments.next();
+    module.clone()
+  } else {
+    Path::root()
+  };
+  segments.for_each(|segment| result.push(segment));
+  result
+}
+
+fn is_visible(binding: &Binding, module: &Path, from: &Path) -> bool {
+  binding.public || from.starts_with(module)
+}
+
+fn describe_module(path: &Path) -> String {
+  if path.is_empty() {
+    "the package root".to_string()
+  } else {
+    format!("module `{}`", path)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use vsp_ast::ast::module::Module;
+  use vsp_ast_parser::lex::DefaultLexer;
+  use vsp_ast_parser::parser::ASTParser;
+  use vsp_ast_parser::parser::TraditionalParser;
+
+  use super::*;
+  use crate::check::TypeChecker;
+
+  fn parse(filename: &str, source: &str) -> CompilationUnit {
+    let tokens = DefaultLexer {}.tokenize(source).unwrap();
+    let mut unit = TraditionalParser {}.parse(tokens).unwrap();
+    unit.set_filename(filename.to_owned());
+    unit
+  }
+
+  /// Build the package from the entry source and `(module path, source)` pairs, then check it.
+  fn check_package(entry: &str, modules: &[(&str, &str)]) -> Vec<String> {
+    let mut unit = parse("lib.vsp", entry);
+    for (path, source) in modules {
+      let file = parse(&format!("{}/module.vsp", path), source);
+      let module = unit
+        .modules
+        .entry(path.to_string())
+        .or_insert_with(|| Module::new(Path::from(*path)));
+      if let Some(decl) = &file.module {
+        module.visibility = decl.visibility;
+      }
+      module.units.push(file);
+    }
+    let mut diagnostics = DiagnosticEngine::default();
+    TypeChecker::new(&mut diagnostics).check_compilation_unit(&unit);
+    diagnostics
+      .diagnostics()
+      .iter()
+      .map(|d| format!("{} {}", d, d.notes.join(" ")))
+      .collect()
+  }
+
+  #[test]
+  fn test_imports() {
+    let messages = check_package(
+      "use core::iter::*;
+       use std::length as len;
+       func main() -> int64 { return next(1) + len(); }",
+      &[
+        ("core", "public func unused() {}"),
+        (
+          "core::iter",
+          "public func next(x: int64) -> int64 { return helper(x); }
+                        func helper(x: int64) -> int64 { return x; }",
+        ),
+        (
+          "std",
+          "public use core::iter::next;
+                 public func length() -> int64 { return next(0); }",
+        ),
+      ],
+    );
+    assert!(messages.is_empty(), "{:?}", messages);
+  }
+
+  #[test]
+  fn test_type_mismatch_across_modules() {
+    let messages = check_package(
+      "use std::length;
+       func main() -> bool { return length(); }",
+      &[("std", "public func length() -> int64 { return 0; }")],
+    );
+    assert_eq!(messages.len(), 1, "{:?}", messages);
+    assert!(
+      messages[0].contains("expected bool, found int64"),
+      "{:?}",
+      messages
+    );
+  }
+
+  #[test]
+  fn test_visibility() {
+    let messages = check_package(
+      "use core::hidden;
+       use core::secret::*;
+       func main() {}",
+      &[
+        ("core", "func hidden() {}\nuse core::secret::open;"),
+        ("core::secret", "module = {}\npublic func open() {}"),
+        (
+          "core::secret::inner",
+          "use core::secret::*;\nfunc inner() { open(); }",
+        ),
+      ],
+    );
+    assert_eq!(messages.len(), 2, "{:?}", messages);
+    assert!(
+      messages[0].contains("function `hidden` is private"),
+      "{:?}",
+      messages
+    );
+    assert!(
+      messages[1].contains("module `core::secret` is private"),
+      "{:?}",
+      messages
+    );
+  }
+
+  #[test]
+  fn test_unresolved() {
+    let messages = check_package(
+      "use missing::thing;
+       use std::nothing;
+       use std;
+       func main() { undefined(); }",
+      &[("std", "public func length() {}")],
+    );
+    assert_eq!(messages.len(), 4, "{:?}", messages);
+    assert!(
+      messages[0].contains("unresolved import `missing::thing`"),
+      "{:?}",
+      messages
+    );
+    assert!(
+      messages[0].contains("could not find module `missing`"),
+      "{:?}",
+      messages
+    );
+    assert!(
+      messages[1].contains("no `nothing` in module `std`"),
+      "{:?}",
+      messages
+    );
+    assert!(
+      messages[2].contains("import its items by `std::*`"),
+      "{:?}",
+      messages
+    );
+  }
+
+  #[test]
+  fn test_conflicts() {
+    let messages = check_package(
+      "use std::length;
+       use other::*;
+       func length() {}
+       func main() { length(); }",
+      &[
+        ("std", "public func length() {}"),
+        ("other", "public func length() {}"),
+      ],
+    );
+    assert_eq!(messages.len(), 1, "{:?}", messages);
+    assert!(
+      messages[0].contains("the name `length` is defined multiple times"),
+      "{:?}",
+      messages
+    );
+  }
+}
@@ -0,0 +1,26 @@
+//! Helpers for the tests of the passes after the semantic analysis, which take the source code
+//! through the lexer, the parser and the type checking.
+use vsp_ast::ast::CompilationUnit;
+use vsp_ast_parser::lex::DefaultLexer;
+use vsp_ast_parser::parser::ASTParser;
+use vsp_ast_parser::parser::TraditionalParser;
+use vsp_diag::DiagnosticEngine;
+
+use crate::check::TypeckResults;
+
+/// Parse and check the source code, asserting that it has no errors.
+pub fn check_source(source: &str) -> (CompilationUnit, TypeckResults) {
+  check_file("", source)
+}
+
+/// Parse and check the source code as the file of the name, which the locations of the runtime
+/// errors refer to, asserting that it has no errors.
+pub fn check_file(filename: &str, source: &str) -> (CompilationUnit, TypeckResults) {
+  let tokens = DefaultLexer {}.tokenize(source).unwrap();
+  let mut unit = TraditionalParser {}.parse(tokens).unwrap();
+  unit.set_filename(filename);
+  let mut diagnostics = DiagnosticEngine::default();
+  let results = crate::check_compilation_unit(&unit, &mut diagnostics);
+  assert!(!diagnostics.has_errors(), "{:?}", diagnostics.diagnostics());
+  (unit, results)
+}
@@ -0,0 +1,245 @@
+//! Types used during the type inference.
+//!
+//! Unlike [`Type`] in AST, the type here might contain type variables which are not solved yet.
+use core::fmt::Display;
+use core::fmt::Formatter;
+use std::collections::HashMap;
+
+use vsp_ast::ast::module::Path;
+use vsp_ast::ast::types::FunctionType;
+use vsp_ast::ast::types::PrimitiveType;
+use vsp_ast::ast::types::Type;
+
+use crate::resolve::DefPath;
+
+/// ID of the type variable in the inference table.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TyVid(pub(crate) u32);
+
+/// Kind of the type variable, which limits what type the variable could be unified with.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TyVarKind {
+  /// Any type.
+  General,
+  /// Any integer type, created by integer literals.
+  Integral,
+  /// Any floating-point type, created by float literals.
+  Float,
+}
+
+/// Type during inference.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Ty {
+  /// Type variable to be solved.
+  Var(TyVid, TyVarKind),
+  Primitive(PrimitiveType),
+  Array(Box<Ty>, usize),
+  /// Array whose size is given by the constant, which is only known after the type checking.
+  /// Arrays are the same type only if their sizes are given by the same constant.
+  ConstArray(Box<Ty>, DefPath),
+  Function(Vec<Ty>, Box<Ty>),
+  /// Struct or enum type with its generic arguments.
+  Adt(DefPath, Vec<Ty>),
+  /// Generic parameter in scope, which is opaque until the generic item is instantiated.
+  Param(String),
+  /// Trait object of the trait.
+  Dyn(DefPath),
+  /// Raw pointer to the value of the type.
+  Pointer(Box<Ty>),
+  /// State machine returned by calling the `async func`, with the generic arguments of the
+  /// function. It implements the lang item `Future`, whose output is the return type of the body.
+  Coroutine(DefPath, Vec<Ty>),
+  /// Types which are not inspected by the inference yet, compared by equality.
+  Opaque(Type),
+  /// Type of the erroneous expression. It unifies with anything to avoid cascading errors.
+  Error,
+}
+
+impl Ty {
+  #[inline]
+  pub fn unit() -> Self {
+    Ty::Primitive(PrimitiveType::Unit)
+  }
+
+  #[inline]
+  pub fn bool() -> Self {
+    Ty::Primitive(PrimitiveType::Bool)
+  }
+
+  pub fn is_error(&self) -> bool {
+    matches!(self, Ty::Error)
+  }
+
+  /// True if the type is known as a number, or a type variable which must be a number.
+  pub fn is_numeric(&self) -> bool {
+    match self {
+      Ty::Primitive(primitive) => primitive.is_numeric(),
+      Ty::Var(_, kind) => *kind != TyVarKind::General,
+      _ => false,
+    }
+  }
+
+  /// True if there is no type variable in the type.
+  pub fn is_known(&self) -> bool {
+    match self {
+      Ty::Var(..) => false,
+      Ty::Array(element, _) | Ty::ConstArray(element, _) | Ty::Pointer(element) => {
+        element.is_known()
+      }
+      Ty::Function(params, ret) => params.iter().all(Ty::is_known) && ret.is_known(),
+      Ty::Adt(_, args) | Ty::Coroutine(_, args) => args.iter().all(Ty::is_known),
+      _ => true,
+    }
+  }
+
+  /// Substitute the generic parameters by the arguments. Parameters not in the map are kept.
+  pub fn substitute(&self, args: &HashMap<String, Ty>) -> Ty {
+    match self {
+      Ty::Param(name) => args.get(name).cloned().unwrap_or_else(|| self.clone()),
+      Ty::Array(element, size) => Ty::Array(Box::new(element.substitute(args)), *size),
+      Ty::ConstArray(element, size) => {
+        Ty::ConstArray(Box::new(element.substitute(args)), size.clone())
+      }
+      Ty::Pointer(pointee) => Ty::Pointer(Box::new(pointee.substitute(args))),
+      Ty::Function(params, ret) => Ty::Function(
+        params.iter().map(|p| p.substitute(args)).collect(),
+        Box::new(ret.substitute(args)),
+      ),
+      Ty::Adt(def, params) => Ty::Adt(
+        def.clone(),
+        params.iter().map(|p| p.substitute(args)).collect(),
+      ),
+      Ty::Coroutine(def, params) => Ty::Coroutine(
+        def.clone(),
+        params.iter().map(|p| p.substitute(args)).collect(),
+      ),
+      ty => ty.clone(),
+    }
+  }
+
+  /// True if any generic parameter occurs in the type.
+  pub fn has_params(&self) -> bool {
+    match self {
+      Ty::Param(_) => true,
+      Ty::Array(element, _) | Ty::ConstArray(element, _) | Ty::Pointer(element) => {
+        element.has_params()
+      }
+      Ty::Function(params, ret) => params.iter().any(Ty::has_params) || ret.has_params(),
+      Ty::Adt(_, args) | Ty::Coroutine(_, args) => args.iter().any(Ty::has_params),
+      _ => false,
+    }
+  }
+
+  /// Convert the solved type back to the AST type. Type variables must have been resolved.
+  pub fn to_type(&self) -> Option<Type> {
+    match self {
+      Ty::Var(..) | Ty::Error => None,
+      Ty::Primitive(primitive) => Some(Type::Primitive(primitive.clone())),
+      Ty::Array(element, size) => Some(Type::array(element.to_type()?, *size)),
+      Ty::ConstArray(element, size) => {
+        Some(Type::ConstArray(Box::new(element.to_type()?), size.path()))
+      }
+      Ty::Function(params, ret) => Some(Type::Function(FunctionType::new(
+        params.iter().map(Ty::to_type).collect::<Option<Vec<_>>>()?,
+        Box::new(ret.to_type()?),
+      ))),
+      Ty::Adt(def, args) => Some(Type::Named(
+        def.path(),
+        args.iter().map(Ty::to_type).collect::<Option<Vec<_>>>()?,
+      )),
+      Ty::Param(name) => Some(Type::named(Path::from(name.as_str()))),
+      Ty::Dyn(def) => Some(Type::Dyn(def.path())),
+      Ty::Pointer(pointee) => Some(Type::pointer(pointee.to_type()?)),
+      Ty::Coroutine(def, args) => Some(Type::Coroutine(
+        def.path(),
+        args.iter().map(Ty::to_type).collect::<Option<Vec<_>>>()?,
+      )),
+      Ty::Opaque(ty) => Some(ty.clone()),
+    }
+  }
+}
+
+impl From<&Type> for Ty {
+  fn from(ty: &Type) -> Self {
+    match ty {
+      Type::Primitive(primitive) => Ty::Primitive(primitive.clone()),
+      Type::Array(element, size) => Ty::Array(Box::new(Ty::from(element.as_ref())), *size),
+      Type::ConstArray(element, size) => Ty::ConstArray(
+        Box::new(Ty::from(element.as_ref())),
+        DefPath::new(
+          size.parent().unwrap_or_default(),
+          size.name().unwrap_or_default(),
+        ),
+      ),
+      Type::Function(function) => Ty::Function(
+        function.params.iter().map(Ty::from).collect(),
+        Box::new(Ty::from(function.ret.as_ref())),
+      ),
+      Type::Pointer(pointee) => Ty::Pointer(Box::new(Ty::from(pointee.as_ref()))),
+      Type::Coroutine(path, args) => Ty::Coroutine(
+        DefPath::new(
+          path.parent().unwrap_or_default(),
+          path.name().unwrap_or_default(),
+        ),
+        args.iter().map(Ty::from).collect(),
+      ),
+      ty => Ty::Opaque(ty.clone()),
+    }
+  }
+}
+
+impl Display for Ty {
+  fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+    match self {
+      Ty::Var(_, TyVarKind::General) => write!(f, "_"),
+      Ty::Var(_, TyVarKind::Integral) => write!(f, "{{integer}}"),
+      Ty::Var(_, TyVarKind::Float) => write!(f, "{{float}}"),
+      Ty::Primitive(primitive) => write!(f, "{}", primitive.keyword()),
+      Ty::Array(element, size) => write!(f, "{}[{}]", element, size),
+      Ty::ConstArray(element, size) => write!(f, "{}[{}]", element, size),
+      Ty::Function(params, ret) => {
+        write!(f, "func(")?;
+        for (i, param) in params.iter().enumerate() {
+          if i > 0 {
+            write!(f, ", ")?;
+          }
+          write!(f, "{}", param)?;
+        }
+        write!(f, ") -> {}", ret)
+      }
+      Ty::Adt(def, args) => {
+        write!(f, "{}", def)?;
+        if !args.is_empty() {
+          write!(f, "<")?;
+          for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+              write!(f, ", ")?;
+            }
+            write!(f, "{}", arg)?;
+          }
+          write!(f, ">")?;
+        }
+        Ok(())
+      }
+      Ty::Param(name) => write!(f, "{}", name),
+      Ty::Dyn(def) => write!(f, "dyn {}", def),
+      Ty::Pointer(pointee) => write!(f, "*{}", pointee),
+      Ty::Coroutine(def, args) => {
+        write!(f, "{{async {}", def)?;
+        if !args.is_empty() {
+          write!(f, "<")?;
+          for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+              write!(f, ", ")?;
+            }
+            write!(f, "{}", arg)?;
+          }
+          write!(f, ">")?;
+        }
+        write!(f, "}}")
+      }
+      Ty::Opaque(ty) => write!(f, "{}", ty),
+      Ty::Error => write!(f, "{{error}}"),
+    }
+  }
+}
@@ -24,11 +24,24 @@ pub struct ProcessBuilder {
   env:  BTreeMap<String, Option<OsString>>,
 }
 
+/// The command line as it could be pasted into a shell, where the arguments with whitespaces or
+/// quotes are quoted.
 impl Display for ProcessBuilder {
-  #[allow(unused_variables)]
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-    todo!()
+    write!(f, "{}", quote(&self.cmd))?;
+    for arg in &self.args {
+      write!(f, " {}", quote(arg))?;
+    }
+    Ok(())
+  }
+}
+
+fn quote(arg: &OsStr) -> String {
+  let arg = arg.to_string_lossy();
+  if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || c == '\'' || c == '"') {
+    return arg.into_owned();
   }
+  format!("'{}'", arg.replace('\'', "'\\''"))
 }
 
 impl ProcessBuilder {
@@ -51,9 +64,28 @@ impl ProcessBuilder {
     self
   }
 
+  pub fn args<T: AsRef<OsStr>>(&mut self, args: impl IntoIterator<Item = T>) -> &mut Self {
+    self.args.extend(args.into_iter().map(|arg| arg.as_ref().to_os_string()));
+    self
+  }
+
+  pub fn get_program(&self) -> &OsStr {
+    &self.cmd
+  }
+
+  pub fn get_args(&self) -> &[OsString] {
+    &self.args
+  }
+
   pub fn status(&self) -> VspResult<ExitStatus> {
     let mut cmd = self.build_command();
-    let mut child = cmd.spawn().unwrap();
+    let mut child = cmd.spawn().map_err(|e| {
+      VspError::new(format!(
+        "could not run `{}`: {}",
+        self.cmd.to_string_lossy(),
+        e
+      ))
+    })?;
     match child.wait() {
       Ok(exit) => Ok(exit),
       Err(e) => Err(VspError::from(e)),
@@ -107,9 +139,11 @@ pub struct ProcessError {
 }
 
 impl Display for ProcessError {
-  #[allow(unused_variables)]
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-    todo!()
+    match self.code {
+      Some(code) => write!(f, "{} (exit code: {})", self.message, code),
+      None => write!(f, "{}", self.message),
+    }
   }
 }
 
@@ -124,3 +158,32 @@ impl ProcessError {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_display() {
+    let mut builder = ProcessBuilder::new("cc");
+    builder.arg("-o").arg("out dir/main").args(["-Wl,--as-needed", "it's", ""]);
+    assert_eq!(
+      builder.to_string(),
+      "cc -o 'out dir/main' -Wl,--as-needed 'it'\\''s' ''"
+    );
+    assert_eq!(builder.get_args().len(), 5);
+  }
+
+  #[test]
+  fn test_exec() {
+    assert!(ProcessBuilder::new("true").exec().is_ok());
+    let message = ProcessBuilder::new("false").exec().unwrap_err().message();
+    assert_eq!(message, "fail to run: false (exit code: 1)");
+    let message = ProcessBuilder::new("vsp-no-such-program").exec().unwrap_err().message();
+    assert!(
+      message.starts_with("could not run `vsp-no-such-program`"),
+      "{}",
+      message
+    );
+  }
+}
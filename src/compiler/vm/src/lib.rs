@@ -0,0 +1,32 @@
+//! # VM
+//!
+//! Stack machine running the bytecode modules of `vsp-bytecode`, which are either compiled from
+//! the source or read from the `.vspb` files. It shares the natives with the interpreter, so that
+//! the programs behave the same when run by either.
+//!
+//! ```rust
+//! use vsp_ast_parser::lex::DefaultLexer;
+//! use vsp_ast_parser::parser::ASTParser;
+//! use vsp_ast_parser::parser::TraditionalParser;
+//! use vsp_bytecode::build::build_module;
+//! use vsp_bytecode::format;
+//! use vsp_diag::DiagnosticEngine;
+//! use vsp_vm::vm::Vm;
+//!
+//! let tokens = DefaultLexer {}.tokenize("func main(): int32 { return 6 * 7; }").unwrap();
+//! let unit = TraditionalParser {}.parse(tokens).unwrap();
+//! let mut diagnostics = DiagnosticEngine::default();
+//! let results = vsp_sema::check_compilation_unit(&unit, &mut diagnostics);
+//! let program = vsp_hir::lower_compilation_unit(&unit, &results);
+//! let program = vsp_mir::build::build_program(&program, &mut diagnostics);
+//! assert!(!diagnostics.has_errors());
+//! let bytes = format::write(&build_module(&program, &results, true).unwrap());
+//! let module = format::read(&bytes).unwrap();
+//! assert_eq!(
+//!   Vm::new(&module).run_main(&["main".to_string()]).unwrap(),
+//!   42
+//! );
+//! ```
+pub mod ops;
+pub mod value;
+pub mod vm;
@@ -0,0 +1,245 @@
+//! Arithmetic, comparisons and conversions of the scalar values, which trap on overflow as the
+//! interpreter does, or wrap around as the release builds do.
+use core::cmp::Ordering;
+
+use vsp_bytecode::module::BinOp;
+use vsp_bytecode::module::Scalar;
+
+use crate::value::Value;
+
+/// Binary operation on the operands of the scalar type, returning the message of the error if any.
+/// Division by zero always traps, while the overflow traps only with the overflow checks.
+pub fn binary(
+  op: BinOp,
+  left: Value,
+  right: Value,
+  scalar: Scalar,
+  checks: bool,
+) -> Result<Value, String> {
+  let value = match (left, right) {
+    (Value::Int(left), Value::Int(right)) => {
+      let (verb, result) = match op {
+        BinOp::Add => ("add", left.checked_add(right)),
+        BinOp::Sub => ("subtract", left.checked_sub(right)),
+        BinOp::Mul => ("multiply", left.checked_mul(right)),
+        BinOp::Div if right == 0 => return Err("attempt to divide by zero".to_string()),
+        BinOp::Div => ("divide", left.checked_div(right)),
+        BinOp::Rem if right == 0 => {
+          return Err("attempt to calculate the remainder with a divisor of zero".to_string())
+        }
+        // `MIN % -1` overflows as `MIN / -1` does, and wraps around into `0`.
+        BinOp::Rem => {
+          let quotient = left.checked_div(right).unwrap_or(i128::MAX);
+          if checks && scalar.primitive().check_range(quotient, "").is_err() {
+            return Err("attempt to calculate the remainder with overflow".to_string());
+          }
+          ("calculate the remainder", left.checked_rem(right))
+        }
+        op => return Ok(Value::Bool(compare(op, left.cmp(&right)))),
+      };
+      // Operands in `i128` never overflow it, except the products of `uint64`.
+      let result = match result {
+        Some(result) => result,
+        None if checks => return Err(format!("attempt to {} with overflow", verb)),
+        None => left.wrapping_mul(right),
+      };
+      if checks {
+        return scalar.primitive().check_range(result, verb).map(Value::Int);
+      }
+      return Ok(Value::Int(scalar.primitive().wrap(result)));
+    }
+    (Value::Float(left), Value::Float(right)) => match op {
+      BinOp::Add => Value::Float(left + right),
+      BinOp::Sub => Value::Float(left - right),
+      BinOp::Mul => Value::Float(left * right),
+      BinOp::Div => Value::Float(left / right),
+      BinOp::Rem => Value::Float(left % right),
+      BinOp::Eq => Value::Bool(left == right),
+      BinOp::Ne => Value::Bool(left != right),
+      BinOp::Lt => Value::Bool(left < right),
+      BinOp::Le => Value::Bool(left <= right),
+      BinOp::Gt => Value::Bool(left > right),
+      BinOp::Ge => Value::Bool(left >= right),
+    },
+    (Value::Bool(left), Value::Bool(right)) => Value::Bool(compare(op, left.cmp(&right))),
+    (Value::Str(left), Value::Str(right)) => Value::Bool(compare(op, left.cmp(&right))),
+    (Value::Pointer(left), Value::Pointer(right)) => match op {
+      BinOp::Eq => Value::Bool(left == right),
+      BinOp::Ne => Value::Bool(left != right),
+      // Pointers are only ordered and subtracted within the same allocation or array, as in the
+      // interpreter.
+      op => match left.zip(right).and_then(|(left, right)| left.distance(&right)) {
+        Some(distance) if op == BinOp::Sub => Value::Int(distance as i128),
+        Some(distance) => Value::Bool(compare(op, distance.cmp(&0))),
+        None => {
+          return Err(format!(
+            "cannot {} raw pointers into different allocations",
+            match op {
+              BinOp::Sub => "subtract",
+              _ => "compare",
+            }
+          ))
+        }
+      },
+    },
+    (Value::Pointer(Some(pointer)), Value::Int(count)) => {
+      let count = match op {
+        BinOp::Add => count,
+        BinOp::Sub => -count,
+        op => return Err(format!("invalid pointer operator {:?}", op)),
+      };
+      match i64::try_from(count).ok().and_then(|count| pointer.offset(count)) {
+        Some(pointer) => Value::Pointer(Some(pointer)),
+        None => return Err("cannot offset the pointer into a field".to_string()),
+      }
+    }
+    (Value::Pointer(None), Value::Int(_)) => {
+      return Err("attempt to offset a null pointer".to_string())
+    }
+    (left, right) => {
+      return Err(format!(
+        "invalid operands of {:?}: {:?} and {:?}",
+        op, left, right
+      ))
+    }
+  };
+  Ok(value)
+}
+
+fn compare(op: BinOp, ordering: Ordering) -> bool {
+  match op {
+    BinOp::Eq => ordering.is_eq(),
+    BinOp::Ne => ordering.is_ne(),
+    BinOp::Lt => ordering.is_lt(),
+    BinOp::Le => ordering.is_le(),
+    BinOp::Gt => ordering.is_gt(),
+    BinOp::Ge => ordering.is_ge(),
+    // Arithmetic on booleans and strings is rejected by the type checker.
+    _ => false,
+  }
+}
+
+/// Negation of the number, which overflows for the minimum of the signed integers.
+pub fn negate(value: Value, scalar: Scalar, checks: bool) -> Result<Value, String> {
+  match value {
+    Value::Float(value) => Ok(Value::Float(-value)),
+    Value::Int(value) if checks => scalar.primitive().check_range(-value, "negate").map(Value::Int),
+    Value::Int(value) => Ok(Value::Int(scalar.primitive().wrap(-value))),
+    value => Err(format!("cannot negate {:?}", value)),
+  }
+}
+
+/// Conversion between the scalar types. Floats are converted into integers with saturation, and
+/// only the null pointer converts into integers.
+pub fn cast(value: Value, scalar: Scalar) -> Result<Value, String> {
+  if scalar == Scalar::Float {
+    return Ok(match value {
+      Value::Int(value) => Value::Float(value as f64),
+      value => value,
+    });
+  }
+  if scalar == Scalar::Bool {
+    return Ok(match value {
+      Value::Int(value) => Value::Bool(value & 1 != 0),
+      value => value,
+    });
+  }
+  Ok(match value {
+    Value::Int(value) => Value::Int(scalar.primitive().wrap(value)),
+    Value::Bool(value) => Value::Int(value as i128),
+    Value::Float(value) => Value::Int(scalar.primitive().saturate(value)),
+    Value::Pointer(None) => Value::Int(0),
+    Value::Pointer(Some(_)) => {
+      return Err("cannot convert raw pointers into integers in the VM".to_string())
+    }
+    value => value,
+  })
+}
+
+/// Conversion into a raw pointer, where only `0` converts from the integers.
+pub fn to_pointer(value: Value) -> Result<Value, String> {
+  match value {
+    Value::Int(0) => Ok(Value::null()),
+    Value::Int(_) => Err("cannot convert non-zero integers into raw pointers".to_string()),
+    value => Ok(value),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_binary() {
+    let add = |left, right, checks| {
+      binary(
+        BinOp::Add,
+        Value::Int(left),
+        Value::Int(right),
+        Scalar::Int8,
+        checks,
+      )
+    };
+    assert_eq!(add(100, 27, true), Ok(Value::Int(127)));
+    assert_eq!(
+      add(100, 28, true),
+      Err("attempt to add with overflow".to_string())
+    );
+    assert_eq!(add(100, 28, false), Ok(Value::Int(-128)));
+    assert_eq!(
+      binary(
+        BinOp::Rem,
+        Value::Int(-128),
+        Value::Int(-1),
+        Scalar::Int8,
+        false
+      ),
+      Ok(Value::Int(0))
+    );
+    assert_eq!(
+      binary(
+        BinOp::Div,
+        Value::Int(1),
+        Value::Int(0),
+        Scalar::Int8,
+        false
+      ),
+      Err("attempt to divide by zero".to_string())
+    );
+    let max = u64::MAX as i128;
+    assert_eq!(
+      binary(
+        BinOp::Mul,
+        Value::Int(max),
+        Value::Int(max),
+        Scalar::Uint64,
+        false
+      ),
+      Ok(Value::Int(1))
+    );
+    assert_eq!(
+      binary(
+        BinOp::Lt,
+        Value::Float(1.0),
+        Value::Float(2.0),
+        Scalar::Float,
+        true
+      ),
+      Ok(Value::Bool(true))
+    );
+  }
+
+  #[test]
+  fn test_cast() {
+    assert_eq!(cast(Value::Int(300), Scalar::Uint8), Ok(Value::Int(44)));
+    assert_eq!(cast(Value::Int(-1), Scalar::Uint16), Ok(Value::Int(65535)));
+    assert_eq!(cast(Value::Int(200), Scalar::Int8), Ok(Value::Int(-56)));
+    assert_eq!(
+      cast(Value::Float(1e10), Scalar::Int32),
+      Ok(Value::Int(2147483647))
+    );
+    assert_eq!(cast(Value::Bool(true), Scalar::Int64), Ok(Value::Int(1)));
+    assert_eq!(to_pointer(Value::Int(0)), Ok(Value::null()));
+    assert!(to_pointer(Value::Int(8)).is_err());
+  }
+}
@@ -0,0 +1,112 @@
+//! Values of the VM.
+use std::rc::Rc;
+
+use vsp_bytecode::module::FuncIndex;
+
+/// Value on the operand stack and in the locals. Integers of all types, including `char`, are held
+/// in `i128` within the range of their types, as in the interpreter.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+  Unit,
+  Int(i128),
+  Float(f64),
+  Bool(bool),
+  /// String, shared with the constant pool.
+  Str(Rc<str>),
+  /// Struct value, with the fields in the order of declaration.
+  Struct(Vec<Value>),
+  /// Array value, with its elements.
+  Array(Vec<Value>),
+  /// Enum value, with the index of the variant and its fields.
+  Variant(u16, Vec<Value>),
+  /// Raw pointer, which is `None` for the null pointer.
+  Pointer(Option<Pointer>),
+  Function(FuncIndex),
+  Native(u32),
+}
+
+impl Value {
+  pub fn null() -> Self {
+    Value::Pointer(None)
+  }
+}
+
+/// Location pointed by the raw pointers. The pointers to the locals only reach their own locals,
+/// and the pointers into the blocks, such as `argv`, are offset between the values of the blocks.
+/// The fields of structs and the elements of arrays are reached by the path of their indices, and
+/// the pointers to the elements of an array are offset between its elements.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Pointer {
+  pub base:    Base,
+  /// Index of the value from the base, which might be out of bounds after the offsets.
+  pub index:   i64,
+  /// Indices of the fields from the value.
+  pub path:    Vec<u16>,
+  /// Index of the element in the array at the path, if the pointer is to an element of the array,
+  /// which might be out of bounds after the offsets.
+  pub element: Option<i64>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Base {
+  /// Local of the frame at the depth of the call stack. The identifier of the frame no longer
+  /// matches once the frame returns, so that the dangling pointers are detected.
+  Local {
+    depth: usize,
+    frame: u64,
+    local: u16,
+  },
+  /// Block of the values allocated by the VM.
+  Block(usize),
+}
+
+impl Pointer {
+  /// Pointer to the field of the value at the pointer.
+  pub fn field(&self, index: u16) -> Pointer {
+    let mut pointer = self.clone();
+    pointer.settle();
+    pointer.path.push(index);
+    pointer
+  }
+
+  /// Pointer to the first element of the array at the pointer.
+  pub fn elements(&self) -> Pointer {
+    let mut pointer = self.clone();
+    pointer.settle();
+    pointer.element = Some(0);
+    pointer
+  }
+
+  /// Pointer offset by the number of the values, which is only valid for the pointers to whole
+  /// values, and to the elements of arrays.
+  pub fn offset(&self, count: i64) -> Option<Pointer> {
+    let mut pointer = self.clone();
+    match self.element {
+      Some(element) => pointer.element = Some(element.checked_add(count)?),
+      None if self.path.is_empty() => pointer.index = self.index.checked_add(count)?,
+      None => return None,
+    }
+    Some(pointer)
+  }
+
+  /// Number of the values or the elements between the pointers, or `None` if they are not into the
+  /// same base or the same array.
+  pub fn distance(&self, other: &Pointer) -> Option<i64> {
+    if (&self.base, &self.path) != (&other.base, &other.path) {
+      return None;
+    }
+    match (self.element, other.element) {
+      (Some(left), Some(right)) if self.index == other.index => left.checked_sub(right),
+      (None, None) if self.path.is_empty() => self.index.checked_sub(other.index),
+      _ => None,
+    }
+  }
+
+  /// Move the index of the element into the path, so that the element is reached as a field.
+  /// Elements out of bounds are moved as the invalid fields.
+  fn settle(&mut self) {
+    if let Some(element) = self.element.take() {
+      self.path.push(u16::try_from(element).unwrap_or(u16::MAX));
+    }
+  }
+}
@@ -0,0 +1,893 @@
+//! Stack machine running the bytecode modules.
+//!
+//! Modules are verified before they run, so that the instructions never reach outside of the module
+//! or below the operand stack of their frames, even if the module is read from a corrupted file.
+//! Calls push frames onto the call stack of the VM instead of recursing on the stack of the host,
+//! so that deep recursions only take the memory of their locals. Locals live in their frames, and
+//! the raw pointers to them are checked to be live and within bounds on every access. Foreign
+//! functions are bound by their names to the natives of the interpreter.
+//!
+//! Errors at runtime, such as the overflows and the uses of the dangling pointers, stop the VM with
+//! the location in the source, which is found by the line table of the function.
+use std::collections::HashMap;
+use std::io::Write;
+use std::rc::Rc;
+
+use vsp_bytecode::format;
+use vsp_bytecode::module::Constant;
+use vsp_bytecode::module::FuncIndex;
+use vsp_bytecode::module::Instr;
+use vsp_bytecode::module::Module;
+use vsp_error::VspError;
+use vsp_error::VspResult;
+use vsp_interp::native;
+use vsp_interp::native::Native;
+use vsp_interp::native::Trap;
+use vsp_interp::value::Value as NativeValue;
+
+use crate::ops;
+use crate::value::Base;
+use crate::value::Pointer;
+use crate::value::Value;
+
+/// Limit of the nested calls, which catches the unbounded recursions.
+pub const CALL_DEPTH_LIMIT: usize = 1 << 16;
+
+/// VM of the module, which keeps the blocks, such as `argv`, between the calls.
+pub struct Vm<'a> {
+  module:    &'a Module,
+  /// Values of the constant pool.
+  constants: Vec<Value>,
+  natives:   HashMap<String, Native>,
+  output:    Box<dyn Write + 'a>,
+  stack:     Vec<Value>,
+  frames:    Vec<Frame>,
+  blocks:    Vec<Vec<Value>>,
+  /// Identifier of the next frame.
+  next:      u64,
+  /// Whether the module is verified already.
+  verified:  bool,
+}
+
+/// Call being run.
+struct Frame {
+  function: FuncIndex,
+  /// Index of the next instruction.
+  pc:       usize,
+  locals:   Vec<Value>,
+  id:       u64,
+}
+
+/// Control flow leaving the VM.
+enum Unwind {
+  /// Exit of the program with the code.
+  Exit(i32),
+  Error(VspError),
+}
+
+impl<'a> Vm<'a> {
+  /// VM of the module, whose output is written to the standard output.
+  pub fn new(module: &'a Module) -> Self {
+    let constants = module
+      .constants
+      .iter()
+      .map(|constant| match constant {
+        Constant::Unit => Value::Unit,
+        Constant::Int(value) => Value::Int(*value),
+        Constant::Float(value) => Value::Float(*value),
+        Constant::Bool(value) => Value::Bool(*value),
+        Constant::Str(value) => Value::Str(Rc::from(value.as_str())),
+      })
+      .collect();
+    Self {
+      module,
+      constants,
+      natives: native::defaults(),
+      output: Box::new(std::io::stdout()),
+      stack: vec![],
+      frames: vec![],
+      blocks: vec![],
+      next: 0,
+      verified: false,
+    }
+  }
+
+  pub fn set_output(&mut self, output: Box<dyn Write + 'a>) {
+    self.output = output;
+  }
+
+  /// Bind the foreign functions of the symbol to the native, replacing the default one if any.
+  pub fn define_native(&mut self, symbol: impl Into<String>, native: Native) {
+    self.natives.insert(symbol.into(), native);
+  }
+
+  /// Function of the name, such as `main` or `Point::norm`.
+  pub fn function(&self, name: &str) -> Option<FuncIndex> {
+    let index = self
+      .module
+      .functions
+      .iter()
+      .position(|function| self.module.string(function.name) == Some(name));
+    index.map(|index| index as FuncIndex)
+  }
+
+  /// Run `main` of the module with the arguments of the command line which start with the name of
+  /// the program, and return its exit code, as the interpreter does.
+  pub fn run_main(&mut self, args: &[String]) -> VspResult<i32> {
+    let entry = match self.module.entry {
+      Some(entry) => entry,
+      None => return VspError::new("`main` function not found").to_err(),
+    };
+    let args = match self.module.function(entry).params {
+      0 => vec![],
+      _ => {
+        let mut values =
+          args.iter().map(|arg| Value::Str(Rc::from(arg.as_str()))).collect::<Vec<_>>();
+        // `argv[argc]` is the null pointer as in C.
+        values.push(Value::null());
+        self.blocks.push(values);
+        let argv = Pointer {
+          base:    Base::Block(self.blocks.len() - 1),
+          index:   0,
+          path:    vec![],
+          element: None,
+        };
+        vec![Value::Int(args.len() as i128), Value::Pointer(Some(argv))]
+      }
+    };
+    let result = self.execute(entry, args);
+    self.output.flush().map_err(VspError::from)?;
+    match result {
+      Ok(Value::Int(code)) => Ok(code as i32),
+      Ok(Value::Bool(code)) => Ok(code as i32),
+      Ok(_) => Ok(0),
+      Err(Unwind::Exit(code)) => Ok(code),
+      Err(Unwind::Error(error)) => Err(error),
+    }
+  }
+
+  /// Call the function with the arguments, and return its value.
+  pub fn call(&mut self, function: FuncIndex, args: Vec<Value>) -> VspResult<Value> {
+    let params = match self.module.functions.get(function as usize) {
+      Some(code) => code.params as usize,
+      None => return VspError::new(format!("function {} not found", function)).to_err(),
+    };
+    if args.len() != params {
+      let message = format!("expected {} argument(s), found {}", params, args.len());
+      return VspError::new(message).to_err();
+    }
+    let result = self.execute(function, args);
+    self.output.flush().map_err(VspError::from)?;
+    match result {
+      Ok(value) => Ok(value),
+      Err(Unwind::Exit(code)) => VspError::new(format!("exited with code {}", code)).to_err(),
+      Err(Unwind::Error(error)) => Err(error),
+    }
+  }
+
+  /// Run the function until it returns, and unwind the frames it pushed on errors.
+  fn execute(&mut self, function: FuncIndex, args: Vec<Value>) -> Result<Value, Unwind> {
+    if !self.verified {
+      format::verify(self.module).map_err(Unwind::Error)?;
+      self.verified = true;
+    }
+    let (depth, height) = (self.frames.len(), self.stack.len());
+    self.stack.extend(args);
+    let result = self.push_frame(function).and_then(|_| self.run(depth));
+    if let Err(Unwind::Error(error)) = result {
+      let error = self.locate(error);
+      self.frames.truncate(depth);
+      self.stack.truncate(height);
+      return Err(Unwind::Error(error));
+    }
+    self.frames.truncate(depth);
+    self.stack.truncate(height);
+    result
+  }
+
+  /// Push the frame of the function, whose arguments are popped from the stack.
+  fn push_frame(&mut self, function: FuncIndex) -> Result<(), Unwind> {
+    if self.frames.len() >= CALL_DEPTH_LIMIT {
+      let message = format!("reached the limit of {} nested calls", CALL_DEPTH_LIMIT);
+      return Err(Unwind::Error(VspError::new(message)));
+    }
+    let code = self.module.function(function);
+    let mut locals = vec![Value::Unit; code.locals as usize];
+    let args = self.stack.len() - code.params as usize;
+    for (local, arg) in locals[1..].iter_mut().zip(self.stack.drain(args..)) {
+      *local = arg;
+    }
+    self.frames.push(Frame {
+      function,
+      pc: 0,
+      locals,
+      id: self.next,
+    });
+    self.next += 1;
+    Ok(())
+  }
+
+  /// Run the instructions until the frame at the depth returns.
+  fn run(&mut self, depth: usize) -> Result<Value, Unwind> {
+    let module = self.module;
+    let checks = module.overflow_checks;
+    loop {
+      let top = self.frames.len() - 1;
+      let frame = &mut self.frames[top];
+      let instr = module.function(frame.function).code[frame.pc];
+      frame.pc += 1;
+      match instr {
+        Instr::Unit => self.stack.push(Value::Unit),
+        Instr::Const(index) => self.stack.push(self.constants[index as usize].clone()),
+        Instr::LoadLocal(local) => {
+          let value = frame.locals[local as usize].clone();
+          self.stack.push(value);
+        }
+        Instr::StoreLocal(local) => {
+          let value = self.stack.pop().unwrap();
+          frame.locals[local as usize] = value;
+        }
+        Instr::AddrLocal(local) => {
+          let pointer = Pointer {
+            base:    Base::Local {
+              depth: top,
+              frame: frame.id,
+              local,
+            },
+            index:   0,
+            path:    vec![],
+            element: None,
+          };
+          self.stack.push(Value::Pointer(Some(pointer)));
+        }
+        Instr::AddrField(index) => {
+          let pointer = self.pop_pointer()?;
+          self.stack.push(Value::Pointer(Some(pointer.field(index))));
+        }
+        Instr::Load => {
+          let pointer = self.pop_pointer()?;
+          let value = self.slot(&pointer).map_err(error)?.clone();
+          self.stack.push(value);
+        }
+        Instr::Store => {
+          let value = self.stack.pop().unwrap();
+          let pointer = self.pop_pointer()?;
+          *self.slot(&pointer).map_err(error)? = value;
+        }
+        Instr::Field(index) => match self.stack.pop().unwrap() {
+          Value::Struct(mut fields) if (index as usize) < fields.len() => {
+            self.stack.push(fields.swap_remove(index as usize));
+          }
+          value => return Err(error(format!("no field {} in {:?}", index, value))),
+        },
+        Instr::Pop => {
+          self.stack.pop();
+        }
+        Instr::Neg(scalar) => {
+          let value = self.stack.pop().unwrap();
+          self.stack.push(ops::negate(value, scalar, checks).map_err(error)?);
+        }
+        Instr::Not => match self.stack.pop().unwrap() {
+          Value::Bool(value) => self.stack.push(Value::Bool(!value)),
+          value => return Err(error(format!("cannot apply `!` to {:?}", value))),
+        },
+        Instr::Binary(op, scalar) => {
+          let right = self.stack.pop().unwrap();
+          let left = self.stack.pop().unwrap();
+          self.stack.push(ops::binary(op, left, right, scalar, checks).map_err(error)?);
+        }
+        Instr::Cast(scalar) => {
+          let value = self.stack.pop().unwrap();
+          self.stack.push(ops::cast(value, scalar).map_err(error)?);
+        }
+        Instr::Array(count) => {
+          let value = self.stack.pop().unwrap();
+          self.stack.push(Value::Array(vec![value; count as usize]));
+        }
+        Instr::AddrElements => {
+          let pointer = self.pop_pointer()?;
+          self.stack.push(Value::Pointer(Some(pointer.elements())));
+        }
+        Instr::ArrayOf(count) => {
+          let elements = self.pop_values(count as usize);
+          self.stack.push(Value::Array(elements));
+        }
+        Instr::Index(length) => {
+          let index = self.pop_index(length)?;
+          match self.stack.pop().unwrap() {
+            Value::Array(mut elements) if elements.len() == length as usize => {
+              self.stack.push(elements.swap_remove(index));
+            }
+            value => {
+              let message = format!("{:?} is not an array of {} element(s)", value, length);
+              return Err(error(message));
+            }
+          }
+        }
+        Instr::AddrIndex(length) => {
+          let index = self.pop_index(length)?;
+          let pointer = self.pop_pointer()?.elements();
+          let element = pointer.offset(index as i64).expect("the index is within the array");
+          self.stack.push(Value::Pointer(Some(element)));
+        }
+        Instr::ToPointer => {
+          let value = self.stack.pop().unwrap();
+          self.stack.push(ops::to_pointer(value).map_err(error)?);
+        }
+        Instr::Struct(count) => {
+          let fields = self.pop_values(count as usize);
+          self.stack.push(Value::Struct(fields));
+        }
+        Instr::Variant(variant, count) => {
+          let fields = self.pop_values(count as usize);
+          self.stack.push(Value::Variant(variant, fields));
+        }
+        Instr::Discriminant => match self.stack.pop().unwrap() {
+          Value::Variant(variant, _) => self.stack.push(Value::Int(variant as i128)),
+          value => return Err(error(format!("{:?} is not an enum value", value))),
+        },
+        Instr::VariantField(variant, index) => match self.stack.pop().unwrap() {
+          Value::Variant(actual, mut fields)
+            if actual == variant && (index as usize) < fields.len() =>
+          {
+            self.stack.push(fields.swap_remove(index as usize));
+          }
+          value => {
+            let message = format!(
+              "no field {} of the variant {} in {:?}",
+              index, variant, value
+            );
+            return Err(error(message));
+          }
+        },
+        Instr::Function(function) => self.stack.push(Value::Function(function)),
+        Instr::Native(native) => self.stack.push(Value::Native(native)),
+        Instr::Call(function, _) => self.push_frame(function)?,
+        Instr::CallNative(native, count) => self.call_native(native, count as usize)?,
+        Instr::CallIndirect(count) => {
+          let callee = self.stack.remove(self.stack.len() - 1 - count as usize);
+          match callee {
+            // The verifier only knows the arities of the direct calls.
+            Value::Function(function) if module.function(function).params != count as u16 => {
+              let message = format!(
+                "{} argument(s) passed to the function of {} parameter(s)",
+                count,
+                module.function(function).params
+              );
+              return Err(error(message));
+            }
+            Value::Function(function) => self.push_frame(function)?,
+            Value::Native(native) => self.call_native(native, count as usize)?,
+            value => return Err(error(format!("{:?} is not a function", value))),
+          }
+        }
+        Instr::Jump(target) => frame.pc = target as usize,
+        Instr::JumpIfNot(target) => match self.stack.pop().unwrap() {
+          Value::Bool(true) => {}
+          Value::Bool(false) => frame.pc = target as usize,
+          value => return Err(error(format!("{:?} is not a boolean", value))),
+        },
+        Instr::Return => {
+          let mut frame = self.frames.pop().unwrap();
+          let value = std::mem::replace(&mut frame.locals[0], Value::Unit);
+          if self.frames.len() == depth {
+            return Ok(value);
+          }
+          self.stack.push(value);
+        }
+        Instr::Unreachable => return Err(error("entered unreachable code")),
+      }
+    }
+  }
+
+  fn pop_values(&mut self, count: usize) -> Vec<Value> {
+    let start = self.stack.len() - count;
+    self.stack.split_off(start)
+  }
+
+  /// Pop the index into the array of the length, which must be within the length.
+  fn pop_index(&mut self, length: u32) -> Result<usize, Unwind> {
+    match self.stack.pop().unwrap() {
+      Value::Int(index) if 0 <= index && index < length as i128 => Ok(index as usize),
+      Value::Int(index) => Err(error(format!(
+        "index out of bounds: the length is {} but the index is {}",
+        length, index
+      ))),
+      value => Err(error(format!("{:?} is not an index", value))),
+    }
+  }
+
+  fn pop_pointer(&mut self) -> Result<Pointer, Unwind> {
+    match self.stack.pop().unwrap() {
+      Value::Pointer(Some(pointer)) => Ok(pointer),
+      Value::Pointer(None) => Err(error("dereference of a null pointer")),
+      value => Err(error(format!("{:?} is not a raw pointer", value))),
+    }
+  }
+
+  /// Value which the pointer points to.
+  fn slot(&mut self, pointer: &Pointer) -> Result<&mut Value, String> {
+    let values = match &pointer.base {
+      Base::Local {
+        depth,
+        frame,
+        local,
+      } => match self.frames.get_mut(*depth) {
+        Some(live) if live.id == *frame => std::slice::from_mut(&mut live.locals[*local as usize]),
+        _ => return Err("use of a dangling pointer".to_owned()),
+      },
+      Base::Block(block) => &mut self.blocks[*block][..],
+    };
+    let length = values.len();
+    let mut value = usize::try_from(pointer.index)
+      .ok()
+      .and_then(|index| values.get_mut(index))
+      .ok_or_else(|| {
+        format!(
+          "pointer offset {} is out of bounds of the allocation of {} value(s)",
+          pointer.index, length
+        )
+      })?;
+    for index in &pointer.path {
+      value = match value {
+        Value::Struct(fields) | Value::Array(fields) => fields.get_mut(*index as usize),
+        _ => None,
+      }
+      .ok_or("access to an invalid field")?;
+    }
+    if let Some(element) = pointer.element {
+      value = match value {
+        Value::Array(elements) => {
+          let length = elements.len();
+          usize::try_from(element)
+            .ok()
+            .and_then(|index| elements.get_mut(index))
+            .ok_or_else(|| {
+              format!(
+                "pointer offset {} is out of bounds of the array of {} element(s)",
+                element, length
+              )
+            })?
+        }
+        _ => return Err("access to an invalid element".to_owned()),
+      };
+    }
+    Ok(value)
+  }
+
+  /// Call the native with the arguments popped from the stack, and push its return value.
+  fn call_native(&mut self, native: u32, count: usize) -> Result<(), Unwind> {
+    let name = self.module.string(self.module.natives[native as usize]).unwrap_or_default();
+    let function = match self.natives.get(name) {
+      Some(function) => *function,
+      None => {
+        return Err(error(format!(
+          "foreign function `{}` is not available in the VM",
+          name
+        )))
+      }
+    };
+    let args = self.pop_values(count);
+    let args = args.into_iter().map(to_native).collect::<Result<Vec<_>, _>>()?;
+    match function(&mut self.output, &args) {
+      Ok(value) => {
+        self.stack.push(from_native(value)?);
+        Ok(())
+      }
+      Err(Trap::Exit(code)) => Err(Unwind::Exit(code)),
+      Err(Trap::Error(message)) => Err(error(message)),
+    }
+  }
+
+  /// Prefix the error with the location of the instruction being run.
+  fn locate(&self, error: VspError) -> VspError {
+    let frame = match self.frames.last() {
+      Some(frame) => frame,
+      None => return error,
+    };
+    let function = self.module.function(frame.function);
+    let location = match function.lines.locate(frame.pc.saturating_sub(1) as u32) {
+      Some(location) => location,
+      None => return error,
+    };
+    let file = function.file.and_then(|file| self.module.string(file));
+    let location = match file {
+      Some(file) => format!("{}:{}:{}", file, location.0, location.1),
+      None => format!("{}:{}", location.0, location.1),
+    };
+    VspError::new(format!("{}: {}", location, error.message()))
+  }
+}
+
+fn error(message: impl Into<String>) -> Unwind {
+  Unwind::Error(VspError::new(message.into()))
+}
+
+/// Value passed to the natives, which take scalars and strings only.
+fn to_native(value: Value) -> Result<NativeValue, Unwind> {
+  Ok(match value {
+    Value::Unit => NativeValue::Unit,
+    Value::Int(value) => NativeValue::Int(value),
+    Value::Float(value) => NativeValue::Float(value),
+    Value::Bool(value) => NativeValue::Bool(value),
+    Value::Str(value) => NativeValue::Str(value.to_string()),
+    Value::Pointer(None) => NativeValue::null(),
+    value => return Err(error(format!("cannot pass {:?} to natives", value))),
+  })
+}
+
+fn from_native(value: NativeValue) -> Result<Value, Unwind> {
+  Ok(match value {
+    NativeValue::Unit => Value::Unit,
+    NativeValue::Int(value) => Value::Int(value),
+    NativeValue::Float(value) => Value::Float(value),
+    NativeValue::Bool(value) => Value::Bool(value),
+    NativeValue::Str(value) => Value::Str(Rc::from(value)),
+    NativeValue::Pointer(None) => Value::null(),
+    value => return Err(error(format!("natives cannot return {:?}", value))),
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use std::cell::RefCell;
+
+  use vsp_bytecode::build::build_module;
+  use vsp_bytecode::format;
+  use vsp_diag::DiagnosticEngine;
+  use vsp_sema::testing::check_file;
+
+  use super::*;
+
+  /// Output shared with the test after the VM takes it.
+  #[derive(Clone, Default)]
+  struct Output(Rc<RefCell<Vec<u8>>>);
+
+  impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+      self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+      Ok(())
+    }
+  }
+
+  /// Compile the program into the module, which is serialized and read back.
+  fn compile(source: &str, overflow_checks: bool) -> Module {
+    let (unit, results) = check_file("main.vsp", source);
+    let mut diagnostics = DiagnosticEngine::default();
+    let program = vsp_hir::lower_compilation_unit(&unit, &results);
+    let program = vsp_mir::build::build_program(&program, &mut diagnostics);
+    assert!(!diagnostics.has_errors(), "{:?}", diagnostics.diagnostics());
+    let module = build_module(&program, &results, overflow_checks).unwrap();
+    format::read(&format::write(&module)).unwrap()
+  }
+
+  /// Run `main` of the program with the arguments, returning its exit code and its output.
+  fn run(source: &str, args: &[&str]) -> (VspResult<i32>, String) {
+    let module = compile(source, true);
+    let output = Output::default();
+    let mut vm = Vm::new(&module);
+    vm.set_output(Box::new(output.clone()));
+    let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+    let code = vm.run_main(&args);
+    let output = String::from_utf8(output.0.borrow().clone()).unwrap();
+    (code, output)
+  }
+
+  fn code(source: &str) -> i32 {
+    run(source, &["main"]).0.unwrap()
+  }
+
+  fn message(source: &str) -> String {
+    run(source, &["main"]).0.unwrap_err().message()
+  }
+
+  #[test]
+  fn test_control_flow() {
+    assert_eq!(code("func main() {}"), 0);
+    assert_eq!(code("func main(): bool { return true; }"), 1);
+    assert_eq!(
+      code(
+        "func fibonacci(n: int64): int64 {
+           if n < 2 { return n; }
+           return fibonacci(n - 1) + fibonacci(n - 2);
+         }
+         func main(): int64 {
+           var total = 0;
+           var i = 0;
+           while true {
+             i = i + 1;
+             if i > 10 { break; }
+             if i % 2 == 0 { continue; }
+             total = total + fibonacci(i);
+           }
+           return total;
+         }"
+      ),
+      1 + 2 + 5 + 13 + 34
+    );
+    assert_eq!(
+      code(
+        "const func square(x: int64): int64 { return x * x; }
+         const SIZE: int64 = square(3);
+         static COUNT: int64 = SIZE + 1;
+         func bump(p: *int64): int64 { unsafe { *p = *p + 1; return *p; } }
+         func main(): int64 {
+           var count = COUNT;
+           bump(&count);
+           return (bump(&count) + COUNT) * (300 as uint8) as int64;
+         }"
+      ),
+      22 * 44
+    );
+    // Recursions deeper than the stack of the host are fine, since the calls do not recurse.
+    assert_eq!(
+      code(
+        "func depth(n: int64): int64 { if n == 0 { return 0; } return depth(n - 1) + 1; }
+         func main(): int64 { return depth(50000) - 49958; }"
+      ),
+      42
+    );
+  }
+
+  #[test]
+  fn test_values() {
+    assert_eq!(
+      code(
+        "struct Point { x: int64, y: int64 }
+         struct Line { from: Point, to: Point }
+         impl Point {
+           func sum(): int64 { return self.x + self.y; }
+         }
+         func length(line: Line): int64 { return line.to.x - line.from.x; }
+         func triple(x: int64): int64 { return x * 3; }
+         func main(): int64 {
+           var p = Point { y: 2, x: 1 };
+           p.y = 10;
+           var line = Line { from: p, to: Point { x: 7, y: 0 } };
+           line.to.y = p.sum();
+           let q = &line;
+           let f = triple;
+           unsafe { (*q).from.x = f(2) - 6; }
+           return length(line) * 100 + line.to.y;
+         }"
+      ),
+      711
+    );
+  }
+
+  #[test]
+  fn test_arrays() {
+    let source = "func squares(): int64[3] { return [0, 1, 4]; }
+      func fill(grid: *int64[2][2], value: int64) {
+        unsafe { (*grid)[1][0] = value; }
+      }
+      func main(argc: int32, argv: *str): int64 {
+        var grid = [[1, 2], [3, 4]];
+        grid[0][argc] = 20;
+        fill(&grid, 30);
+        return grid[0][1] + grid[1][0] + squares()[2] + [5, 6][argc - 1];
+      }";
+    assert_eq!(code(source), 59);
+    let source = "func main(argc: int32, argv: *str): int64 {
+        var values = [1, 2, 3];
+        values[argc + 2] = 4;
+        return values[0];
+      }";
+    assert_eq!(
+      message(source),
+      "main.vsp:3:9: index out of bounds: the length is 3 but the index is 3"
+    );
+    assert_eq!(
+      message("func main(argc: int32, argv: *str): int64 { return [1][argc - 2]; }"),
+      "main.vsp:1:52: index out of bounds: the length is 1 but the index is -1"
+    );
+  }
+
+  #[test]
+  fn test_pointers() {
+    let source = "func main(argc: int32, argv: *str): int64 {
+        var x: int64 = 1;
+        let p = &x;
+        unsafe { *p = *p + argc as int64; }
+        return x;
+      }";
+    assert_eq!(run(source, &["main", "a", "b"]).0.unwrap(), 4);
+    let source = "extern \"C\" func strlen(s: str): int64;
+      func main(argc: int32, argv: *str): int64 {
+        unsafe { return strlen(*(argv + 1)) + strlen(*(argv + 2)); }
+      }";
+    assert_eq!(run(source, &["main", "four", "xy"]).0.unwrap(), 6);
+    let source = "func dangling(): *int64 { var x: int64 = 1; return &x; }
+      func main(): int64 { let p = dangling(); unsafe { return *p; } }";
+    assert_eq!(
+      run(source, &["main"]).0.unwrap_err().message(),
+      "main.vsp:2:64: use of a dangling pointer"
+    );
+    let source = "func main(): int64 {
+        var values: int64[3];
+        let p = &values as *int64;
+        unsafe {
+          *p = 4;
+          *(p + 2) = 5;
+          if p + 2 > p { return *p * (*(p + 2) - (p + 2 - p)); }
+          return *(p + 3);
+        }
+      }";
+    assert_eq!(run(source, &["main"]).0.unwrap(), 12);
+    let source = "func main(): int64 {
+        var values: int64[2];
+        let p = &values as *int64;
+        unsafe { return *(p + 2); }
+      }";
+    assert_eq!(
+      run(source, &["main"]).0.unwrap_err().message(),
+      "main.vsp:4:25: pointer offset 2 is out of bounds of the array of 2 element(s)"
+    );
+  }
+
+  #[test]
+  fn test_trait_objects() {
+    assert_eq!(
+      code(
+        "interface Shape {
+           func area(): int64;
+           func scaled(factor: int64): int64;
+         }
+         struct Square { side: int64 }
+         struct Rect { w: int64, h: int64 }
+         impl Shape for Square {
+           func area(): int64 { return self.side * self.side; }
+           func scaled(factor: int64): int64 { return self.area() * factor * factor; }
+         }
+         impl Shape for Rect {
+           func area(): int64 { return self.w * self.h; }
+           func scaled(factor: int64): int64 { return self.w * factor * self.h; }
+         }
+         func pick(wide: bool): dyn Shape {
+           if wide { return Rect { w: 2, h: 3 }; }
+           return Square { side: 2 };
+         }
+         func main(): int64 {
+           let shape = pick(true);
+           return shape.area() + shape.scaled(2) * 10 + pick(false).scaled(3) * 100;
+         }"
+      ),
+      6 + 12 * 10 + 36 * 100
+    );
+  }
+
+  #[test]
+  fn test_corrupted() {
+    // Modules are verified before they run, instead of panicking on the corrupted code.
+    let mut module = compile("func main(): int64 { return 1 + 2; }", true);
+    module.functions[0].code.insert(0, Instr::Pop);
+    assert_eq!(
+      Vm::new(&module).run_main(&[]).unwrap_err().message(),
+      "invalid bytecode module: Pop pops more values than the stack holds"
+    );
+    let module = compile(
+      "func add(a: int64, b: int64): int64 { return a + b; }",
+      true,
+    );
+    let mut vm = Vm::new(&module);
+    let add = vm.function("add").unwrap();
+    assert_eq!(
+      vm.call(add, vec![Value::Int(1)]).unwrap_err().message(),
+      "expected 2 argument(s), found 1"
+    );
+  }
+
+  #[test]
+  fn test_natives() {
+    let source = "extern \"C\" func println(s: str);
+      extern \"C\" func printf(format: str, ...): int32;
+      extern \"C\" func exit(code: int32);
+      func main(): int32 {
+        unsafe {
+          println(\"Hello World!!\");
+          printf(\"%s has %d items\\n\", \"list\", 3);
+          exit(7);
+        }
+        return 0;
+      }";
+    let (code, output) = run(source, &["main"]);
+    assert_eq!(code.unwrap(), 7);
+    assert_eq!(output, "Hello World!!\nlist has 3 items\n");
+    assert_eq!(
+      message("extern \"C\" func rand(): int32; func main(): int32 { unsafe { return rand(); } }"),
+      "main.vsp:1:69: foreign function `rand` is not available in the VM"
+    );
+  }
+
+  #[test]
+  fn test_generics_and_async() {
+    let module = compile(
+      "@Lang(Optional) enum Optional<T> { Some(T), None }
+       interface Describe {
+         func describe(): int64;
+       }
+       impl Describe for int64 {
+         func describe(): int64 { return self * 10; }
+       }
+       func bound<T: Describe>(value: T): int64 { return value.describe(); }
+       func half(n: int64): Optional<int64> {
+         if n % 2 == 0 { return Optional::Some(n / 2); }
+         return Optional::None;
+       }
+       func quarter(n: int64): Optional<int64> {
+         return Optional::Some(half(half(n)?)? + bound(n));
+       }",
+      true,
+    );
+    let mut vm = Vm::new(&module);
+    let quarter = vm.function("quarter").unwrap();
+    assert_eq!(
+      vm.call(quarter, vec![Value::Int(48)]).unwrap(),
+      Value::Variant(0, vec![Value::Int(12 + 480)])
+    );
+    assert_eq!(
+      vm.call(quarter, vec![Value::Int(6)]).unwrap(),
+      Value::Variant(1, vec![])
+    );
+
+    assert_eq!(
+      code(
+        "@Lang(Poll) enum Poll<F, T> { Ready(T), Pending(F) }
+         @Lang(Future) interface Future {
+           type Output;
+           func poll(): Poll<Self, Self::Output>;
+         }
+         @Lang(BlockOn) func block_on<F: Future>(future: F): F::Output;
+         struct Countdown { left: int64 }
+         impl Future for Countdown {
+           type Output = int64;
+           func poll(): Poll<Countdown, int64> {
+             if self.left == 0 { return Poll::Ready(40); }
+             return Poll::Pending(Countdown { left: self.left - 1 });
+           }
+         }
+         async func double(x: int64): int64 { return x * 2; }
+         async func wait<T: Future>(future: T): T::Output { return future.await; }
+         async func add(a: int64): int64 {
+           var total = a + Countdown { left: 3 }.await;
+           total = total + wait(Countdown { left: 2 }).await;
+           return total + double(wait(double(1)).await).await;
+         }
+         func main(): int64 { return block_on(add(1)); }"
+      ),
+      85
+    );
+  }
+
+  #[test]
+  fn test_errors() {
+    assert_eq!(
+      message("func main(): int8 {\n  let x: int8 = 100;\n  return x + 28;\n}"),
+      "main.vsp:3:10: attempt to add with overflow"
+    );
+    assert_eq!(
+      message("func main(): int64 { return 1 / (1 - 1); }"),
+      "main.vsp:1:29: attempt to divide by zero"
+    );
+    assert_eq!(
+      message(
+        "func forever(n: int64): int64 { return forever(n + 1); } func main() { forever(0); }"
+      ),
+      format!(
+        "main.vsp:1:40: reached the limit of {} nested calls",
+        CALL_DEPTH_LIMIT
+      )
+    );
+    assert_eq!(message("func run() {}"), "`main` function not found");
+
+    // The arithmetic wraps around without the overflow checks.
+    let module = compile(
+      "func main(): int8 { let x: int8 = 100; return x + 28; }",
+      false,
+    );
+    assert_eq!(Vm::new(&module).run_main(&[]).unwrap(), -128);
+  }
+}